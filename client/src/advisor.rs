@@ -0,0 +1,206 @@
+//! Parameter advisor: benchmark candidate TFHE parameter sets on this host.
+//!
+//! Each candidate gets a fresh key pair; a sample of clocks of the configured
+//! cipher (the circuit that dominates verification) is timed under it and the
+//! full verification is extrapolated from the clock and matching gate counts.
+//! The recommendation can be written to `fhe_params.json`, which is used
+//! the next time keys are generated (first registration).
+
+use serde::{Deserialize, Serialize};
+use shared::trivium_fhe::{ConsoleProgress, TriviumFhe};
+use shared::{template_popcount_gates, CancellationToken, Cipher, FingerprintError, ParameterSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, set_server_key, ClientKey, FheBool};
+
+use client::api::{self, TEMPLATE_BITS};
+use client::say;
+
+use crate::config;
+use crate::get_client_key_path;
+
+const PARAMS_FILE: &str = "fhe_params.json";
+
+/// Clocks timed per candidate
+const SAMPLE_CLOCKS: usize = 32;
+/// Clocks before the first keystream bit, the same for both ciphers
+const WARMUP_CLOCKS: usize = 1152;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParamsConfig {
+    pub parameter_set: ParameterSet,
+    #[serde(default)]
+    pub estimated_verify_secs: Option<u64>,
+    #[serde(default)]
+    pub recommended_at: Option<String>,
+}
+
+struct Candidate {
+    params: ParameterSet,
+    per_clock: Duration,
+    verify_estimate: Duration,
+}
+
+pub fn params_path() -> PathBuf {
+    get_client_key_path().with_file_name(PARAMS_FILE)
+}
+
+/// Parameter set to generate new keys with: the advisor's, else fingerprint.toml's, else the default
+pub fn configured_parameter_set() -> ParameterSet {
+    fs::read_to_string(params_path())
+        .ok()
+        .and_then(|data| serde_json::from_str::<ParamsConfig>(&data).ok())
+        .map(|c| c.parameter_set)
+        .or(config::get().fhe.parameter_set)
+        .unwrap_or_default()
+}
+
+/// `advise-params [--security <bits>] [--pfail <log2>] [--latency-budget <secs>] [--apply]`
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let security = flag_value(args, "--security")?.unwrap_or(128);
+    let pfail = flag_value(args, "--pfail")?.unwrap_or(40);
+    let budget = Duration::from_secs(flag_value(args, "--latency-budget")?.unwrap_or(1800));
+    let apply = args.iter().any(|a| a == "--apply");
+    let cipher = api::cipher_from_env()?;
+    
+    say!("🧭 FHE PARAMETER ADVISOR");
+    say!("{}", "─".repeat(70));
+    say!("🎯 Security: ≥{} bits, failure probability ≤ 2^-{}, verify budget: {:?}, {}", security, pfail, budget, cipher);
+    
+    let eligible: Vec<ParameterSet> = ParameterSet::CANDIDATES
+        .iter()
+        .copied()
+        .filter(|p| p.security_bits() >= security && p.pfail_log2() >= pfail)
+        .collect();
+    if eligible.is_empty() {
+        return Err("No candidate parameter set meets the requested security level".into());
+    }
+    
+    let mut candidates = Vec::new();
+    for params in eligible {
+        say!("\n⏱️  {} ({} bits, 2^-{}) ...", params, params.security_bits(), params.pfail_log2());
+        let candidate = benchmark(params, cipher)?;
+        say!("   {:?}/clock, verification ≈ {}", candidate.per_clock, format_secs(candidate.verify_estimate));
+        candidates.push(candidate);
+    }
+    
+    // Within budget, prefer the lowest failure probability, then speed
+    let within: Vec<&Candidate> = candidates.iter().filter(|c| c.verify_estimate <= budget).collect();
+    let best = match within.iter().max_by_key(|c| (c.params.pfail_log2(), std::cmp::Reverse(c.verify_estimate))) {
+        Some(c) => *c,
+        None => {
+            let fastest = candidates.iter().min_by_key(|c| c.verify_estimate).expect("at least one candidate");
+            say!("\n⚠️  No candidate fits the latency budget; the fastest one is recommended");
+            fastest
+        }
+    };
+    
+    say!("\n{}", "─".repeat(70));
+    say!("🏆 Recommended: {} (verification ≈ {})", best.params, format_secs(best.verify_estimate));
+    
+    let config = ParamsConfig {
+        parameter_set: best.params,
+        estimated_verify_secs: Some(best.verify_estimate.as_secs()),
+        recommended_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    let json = serde_json::to_string_pretty(&config)?;
+    if apply {
+        let path = params_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, json)?;
+        say!("💾 Written to {}", path.display());
+        if get_client_key_path().exists() {
+            say!("⚠️  An existing client key was generated with other parameters; it is used until it is replaced");
+        }
+    } else {
+        say!("📝 Put this in {} (or re-run with --apply):\n{}", params_path().display(), json);
+    }
+    Ok(())
+}
+
+fn benchmark(params: ParameterSet, cipher: Cipher) -> Result<Candidate, FingerprintError> {
+    let (client_key, server_key) = generate_keys(params.config());
+    set_server_key(server_key);
+    
+    let per_clock = time_per_clock(&client_key, cipher)?;
+    let verify_estimate = verify_estimate(per_clock, TEMPLATE_BITS, cipher);
+    
+    Ok(Candidate { params, per_clock, verify_estimate })
+}
+
+/// Gates of one clock: 3 AND + 11 XOR, and Kreyvium's 2 XOR of its key and IV registers
+pub fn gates_per_clock(cipher: Cipher) -> usize {
+    match cipher {
+        Cipher::Kreyvium => 16,
+        _ => 14,
+    }
+}
+
+/// Average time of a `cipher` clock under this thread's server key, from a
+/// sample of clocks on a state encrypted with `client_key`
+pub fn time_per_clock(client_key: &ClientKey, cipher: Cipher) -> Result<Duration, FingerprintError> {
+    let key: Vec<FheBool> = (0..cipher.key_bits()).map(|i| FheBool::encrypt(i % 3 == 0, client_key)).collect();
+    let iv: Vec<FheBool> = (0..cipher.iv_bits()).map(|i| FheBool::encrypt(i % 5 == 0, client_key)).collect();
+    let mut state = TriviumFhe::for_benchmark(&key, &iv)?;
+    
+    let start = Instant::now();
+    let _ = state.keystream(SAMPLE_CLOCKS, &ConsoleProgress, &CancellationToken::new());
+    Ok(start.elapsed() / SAMPLE_CLOCKS as u32)
+}
+
+/// Verification time on a host where a `cipher` clock takes `per_clock`: probe
+/// and enrolled template are both transciphered (warmup + one keystream bit
+/// per template bit), then matched once
+pub fn verify_estimate(per_clock: Duration, template_bits: usize, cipher: Cipher) -> Duration {
+    let per_gate = per_clock / gates_per_clock(cipher) as u32;
+    per_clock * (2 * (WARMUP_CLOCKS + template_bits)) as u32 + per_gate * matching_gates(template_bits) as u32
+}
+
+/// diff (1 XOR per bit) and the popcount the server's boolean backend runs over the
+/// template; the threshold compare is negligible
+fn matching_gates(template_bits: usize) -> usize {
+    let popcount = template_popcount_gates(template_bits);
+    template_bits + popcount.and + popcount.xor
+}
+
+fn flag_value<T: std::str::FromStr>(args: &[String], flag: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    match args.windows(2).find(|w| w[0] == flag) {
+        Some(w) => Ok(Some(w[1].parse().map_err(|_| format!("Invalid {} value '{}'", flag, w[1]))?)),
+        None => Ok(None),
+    }
+}
+
+pub fn format_secs(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{}m {:02}s", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_is_two_transcipherings_and_one_match() {
+        // 1µs per gate either way
+        let trivium = verify_estimate(Duration::from_micros(14), 100, Cipher::Trivium);
+        let matching = matching_gates(100) as u64;
+        assert_eq!(trivium, Duration::from_micros(14 * 2 * (1152 + 100) + matching));
+        let kreyvium = verify_estimate(Duration::from_micros(16), 100, Cipher::Kreyvium);
+        assert_eq!(kreyvium, Duration::from_micros(16 * 2 * (1152 + 100) + matching));
+
+        assert_eq!(verify_estimate(Duration::ZERO, 2048, Cipher::Trivium), Duration::ZERO);
+        assert!(verify_estimate(Duration::from_millis(5), 2048, Cipher::Trivium) > verify_estimate(Duration::from_millis(5), 1024, Cipher::Trivium));
+    }
+
+    #[test]
+    fn matching_counts_the_servers_popcount() {
+        // Full templates use the ripple counter, 11 AND + 11 XOR per bit at 1024 bits
+        assert_eq!(matching_gates(1024), 1024 * (1 + 2 * 11));
+        // Other lengths use the CSA tree, far cheaper than a ripple counter
+        assert!(matching_gates(2048) < 2048 * (1 + 2 * 12) / 2);
+    }
+}
@@ -0,0 +1,182 @@
+//! Local agent for third-party applications (ssh-agent style).
+//!
+//! `client agent` listens on a user-only Unix socket and speaks the same
+//! line-delimited JSON-RPC framing as `--rpc`, but with a restricted method
+//! set. Applications can ask for a verification or read the last result;
+//! they never see key material, images, distances or receipts.
+//!
+//! Methods:
+//! - `request-verification` { user_id } -> { user_id, authenticated, timestamp }
+//! - `last-status`          { user_id? } -> last verify attempt or null
+//!
+//! Probe images are taken from the capture spool directory (newest file
+//! first) and removed once consumed.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use client::say;
+
+use crate::history;
+use crate::rpc::{self, RpcError, METHOD_NOT_FOUND, NO_RESULT, OPERATION_FAILED};
+use crate::{get_client_key_path, handle_verify, ProbeOptions};
+
+/// The file exchange has a single request slot, so verifications are serialized
+static VERIFY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Deserialize)]
+struct VerifyParams {
+    user_id: String,
+}
+
+#[derive(Deserialize, Default)]
+struct StatusParams {
+    user_id: Option<String>,
+}
+
+pub fn default_socket_path() -> PathBuf {
+    get_client_key_path().with_file_name("agent.sock")
+}
+
+pub fn default_capture_dir() -> PathBuf {
+    get_client_key_path().with_file_name("capture")
+}
+
+#[cfg(unix)]
+pub fn run(socket_path: &Path, capture_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::BufReader;
+
+    if socket_path.exists() {
+        fs::remove_file(socket_path)?;
+    }
+    if let Some(dir) = socket_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::create_dir_all(capture_dir)?;
+
+    let listener = bind_private(socket_path)?;
+
+    say!("🤖 Agent listening on {}", socket_path.display());
+    say!("📂 Capture spool: {}", capture_dir.display());
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("⚠️  Agent accept failed: {}", e);
+                continue;
+            }
+        };
+        let capture_dir = capture_dir.to_path_buf();
+
+        std::thread::spawn(move || {
+            let reader = match stream.try_clone() {
+                Ok(s) => BufReader::new(s),
+                Err(e) => {
+                    eprintln!("⚠️  Agent connection failed: {}", e);
+                    return;
+                }
+            };
+            let dispatch = |method: &str, params: Value| dispatch(method, params, &capture_dir);
+            if let Err(e) = rpc::serve(reader, stream, dispatch) {
+                eprintln!("⚠️  Agent connection closed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Bind inside a fresh 0700 directory, restrict the socket to 0600 and only
+/// then move it into place, so no other user can connect in between
+#[cfg(unix)]
+fn bind_private(socket_path: &Path) -> std::io::Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    let staging = socket_path.with_file_name(format!(".agent.{}", std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("agent.sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&staged, socket_path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_dir_all(&staging);
+    bound
+}
+
+#[cfg(not(unix))]
+pub fn run(_socket_path: &Path, _capture_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err("The local agent requires Unix domain sockets".into())
+}
+
+fn dispatch(method: &str, params: Value, capture_dir: &Path) -> Result<Value, RpcError> {
+    match method {
+        "request-verification" => {
+            let p: VerifyParams = rpc::parse_params(params)?;
+            request_verification(&p.user_id, capture_dir)
+        }
+        "last-status" => {
+            let p: StatusParams = if params.is_null() {
+                StatusParams::default()
+            } else {
+                rpc::parse_params(params)?
+            };
+            last_status(p.user_id.as_deref())
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+    }
+}
+
+fn request_verification(user_id: &str, capture_dir: &Path) -> Result<Value, RpcError> {
+    let _guard = VERIFY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let image = newest_capture(capture_dir)
+        .ok_or_else(|| RpcError::new(NO_RESULT, "No fingerprint capture available"))?;
+    say!("🤖 Verification requested for '{}' ({})", user_id, image.display());
+
+    let result = handle_verify(user_id, &image.to_string_lossy(), &ProbeOptions::default());
+    let _ = fs::remove_file(&image);
+
+    let outcome = result.map_err(rpc::failed)?;
+    Ok(json!({
+        "user_id": outcome.user_id,
+        "authenticated": outcome.match_result,
+        "timestamp": outcome.timestamp,
+    }))
+}
+
+fn last_status(user_id: Option<&str>) -> Result<Value, RpcError> {
+    let entries = history::load().map_err(|e| RpcError::new(OPERATION_FAILED, e.to_string()))?;
+    let last = entries
+        .iter()
+        .rev()
+        .find(|e| e.operation == "verify" && user_id.is_none_or(|u| e.user_id == u));
+
+    Ok(match last {
+        Some(e) => json!({
+            "user_id": e.user_id,
+            "timestamp": e.timestamp,
+            "completed": e.success,
+            "authenticated": e.match_result.unwrap_or(false),
+        }),
+        None => Value::Null,
+    })
+}
+
+/// Most recently modified file in the capture spool
+fn newest_capture(capture_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(capture_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .max_by_key(|e| e.metadata().and_then(|m| m.modified()).ok())
+        .map(|e| e.path())
+}
@@ -0,0 +1,225 @@
+//! Core point detection and translation alignment.
+//!
+//! The template grid is fixed to the image, so a finger placed a few
+//! millimetres off between captures puts different ridges into each region.
+//! Singular points of the ridge orientation field are found with the
+//! Poincaré index: walking around a core the ridge direction turns by half a
+//! turn, around a delta by half a turn the other way, around a whorl by a full
+//! one. The image is then shifted so the core sits at the centre, padding with
+//! the mean grey level, before features are extracted. Captures without a
+//! detectable core are used as they are.
+//!
+//! Rotation is not corrected here; instead the enrollment can carry templates
+//! of the capture rotated by multiples of `ROTATION_STEP_DEGREES`, and the
+//! server keeps the closest of them (see shared/src/template.rs).
+
+use image::{imageops, GrayImage, Luma};
+use std::f32::consts::PI;
+
+use crate::feature_extraction::ridge_field;
+
+/// Side of the orientation field blocks (pixels at the reference density)
+pub const ALIGN_BLOCK: u32 = 16;
+
+/// Detections within this many blocks of the chosen core belong to it
+const CLUSTER_BLOCKS: f32 = 2.0;
+
+/// Angle between the rotated variants of an enrollment
+pub const ROTATION_STEP_DEGREES: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingularKind {
+    Core,     // Poincaré index +1/2 (or +1 for a whorl's centre)
+    Delta,    // Poincaré index -1/2
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SingularPoint {
+    pub x: f32,
+    pub y: f32,
+    pub kind: SingularKind,
+}
+
+/// Cores and deltas of an image, at the centres of the blocks they were found in
+pub fn singular_points(img: &GrayImage) -> Vec<SingularPoint> {
+    let blocks_x = (img.width() / ALIGN_BLOCK) as usize;
+    let blocks_y = (img.height() / ALIGN_BLOCK) as usize;
+    let field = ridge_field(img, ALIGN_BLOCK);
+    let has_ridges: Vec<bool> = field.iter().map(|b| b.frequency.is_some()).collect();
+    let orientations = smooth(&field.iter().map(|b| b.orientation).collect::<Vec<_>>(), blocks_x, blocks_y);
+
+    let mut points = Vec::new();
+    for by in 1..blocks_y.saturating_sub(1) {
+        for bx in 1..blocks_x.saturating_sub(1) {
+            if !LOOP.iter().all(|&(dx, dy)| has_ridges[neighbour(bx, by, dx, dy, blocks_x)]) {
+                continue;
+            }
+            let kind = match poincare_index(&orientations, bx, by, blocks_x).round() as i32 {
+                1 | 2 => SingularKind::Core,
+                -1 => SingularKind::Delta,
+                _ => continue,
+            };
+            let centre = |b: usize| (b as f32 + 0.5) * ALIGN_BLOCK as f32;
+            points.push(SingularPoint { x: centre(bx), y: centre(by), kind });
+        }
+    }
+    points
+}
+
+/// Shift the image so its core sits at the centre; returns the core found, if any
+pub fn align_to_core(img: &GrayImage) -> (GrayImage, Option<(f32, f32)>) {
+    let cores: Vec<(f32, f32)> = singular_points(img)
+        .into_iter()
+        .filter(|p| p.kind == SingularKind::Core)
+        .map(|p| (p.x, p.y))
+        .collect();
+    let (cx, cy) = (img.width() as f32 / 2.0, img.height() as f32 / 2.0);
+    let distance = |(x, y): (f32, f32), (u, v): (f32, f32)| ((x - u).powi(2) + (y - v).powi(2)).sqrt();
+    let Some(&nearest) = cores.iter().min_by(|a, b| distance(**a, (cx, cy)).total_cmp(&distance(**b, (cx, cy)))) else {
+        return (img.clone(), None);
+    };
+
+    // Neighbouring blocks around one singularity all detect it: use their mean
+    let cluster: Vec<(f32, f32)> = cores
+        .into_iter()
+        .filter(|&p| distance(p, nearest) <= CLUSTER_BLOCKS * ALIGN_BLOCK as f32)
+        .collect();
+    let core = (
+        cluster.iter().map(|p| p.0).sum::<f32>() / cluster.len() as f32,
+        cluster.iter().map(|p| p.1).sum::<f32>() / cluster.len() as f32,
+    );
+
+    let pixels = (img.width() as u64 * img.height() as u64).max(1);
+    let mean = (img.pixels().map(|p| p[0] as u64).sum::<u64>() / pixels) as u8;
+    let mut aligned = GrayImage::from_pixel(img.width(), img.height(), Luma([mean]));
+    imageops::replace(&mut aligned, img, (cx - core.0).round() as i64, (cy - core.1).round() as i64);
+    (aligned, Some(core))
+}
+
+/// Angles of `count` rotated variants: one step either way, then two, ...
+pub fn rotation_angles(count: usize) -> Vec<i32> {
+    (0..count)
+        .map(|i| {
+            let angle = (i / 2 + 1) as i32 * ROTATION_STEP_DEGREES;
+            if i % 2 == 0 { angle } else { -angle }
+        })
+        .collect()
+}
+
+/// Rotate the image about its centre (bilinear, clockwise for positive angles),
+/// padding with the mean grey level
+pub fn rotate(img: &GrayImage, degrees: i32) -> GrayImage {
+    if degrees == 0 {
+        return img.clone();
+    }
+    let pixels = (img.width() as u64 * img.height() as u64).max(1);
+    let mean = (img.pixels().map(|p| p[0] as u64).sum::<u64>() / pixels) as f32;
+    let (sin, cos) = (degrees as f32).to_radians().sin_cos();
+    let (cx, cy) = ((img.width() as f32 - 1.0) / 2.0, (img.height() as f32 - 1.0) / 2.0);
+    let sample = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+            mean
+        } else {
+            img.get_pixel(x as u32, y as u32)[0] as f32
+        }
+    };
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        // Source position of the output pixel: the inverse rotation
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let (sx, sy) = (cx + dx * cos + dy * sin, cy - dx * sin + dy * cos);
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = sample(x0, y0) * (1.0 - fx) + sample(x0 + 1, y0) * fx;
+        let bottom = sample(x0, y0 + 1) * (1.0 - fx) + sample(x0 + 1, y0 + 1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round().clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Neighbours of a block in the order the Poincaré index walks them
+const LOOP: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
+
+fn neighbour(bx: usize, by: usize, dx: i32, dy: i32, blocks_x: usize) -> usize {
+    (by as i32 + dy) as usize * blocks_x + (bx as i32 + dx) as usize
+}
+
+/// Total turn of the ridge direction around a block, in half turns
+fn poincare_index(orientations: &[f32], bx: usize, by: usize, blocks_x: usize) -> f32 {
+    let around: Vec<f32> = LOOP.iter().map(|&(dx, dy)| orientations[neighbour(bx, by, dx, dy, blocks_x)]).collect();
+    let turn: f32 = (0..around.len())
+        .map(|i| {
+            // Orientations are only defined up to π: take the smaller step
+            let step = around[(i + 1) % around.len()] - around[i];
+            (step + PI / 2.0).rem_euclid(PI) - PI / 2.0
+        })
+        .sum();
+    turn / PI
+}
+
+/// Orientations averaged over each block's 3×3 neighbourhood (as doubled angles,
+/// so that directions π apart reinforce)
+fn smooth(orientations: &[f32], blocks_x: usize, blocks_y: usize) -> Vec<f32> {
+    (0..blocks_y)
+        .flat_map(|by| (0..blocks_x).map(move |bx| (bx, by)))
+        .map(|(bx, by)| {
+            let (mut sin, mut cos) = (0.0f32, 0.0f32);
+            for y in by.saturating_sub(1)..(by + 2).min(blocks_y) {
+                for x in bx.saturating_sub(1)..(bx + 2).min(blocks_x) {
+                    let (s, c) = (2.0 * orientations[y * blocks_x + x]).sin_cos();
+                    sin += s;
+                    cos += c;
+                }
+            }
+            (0.5 * sin.atan2(cos)).rem_euclid(PI)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poincare_index_tells_cores_from_deltas() {
+        // Orientation fields turning half a turn either way around block (2, 2)
+        let field = |sign: f32| -> Vec<f32> {
+            (0..25)
+                .map(|i| {
+                    let (x, y) = ((i % 5) as f32 - 2.0, (i / 5) as f32 - 2.0);
+                    (sign * 0.5 * y.atan2(x)).rem_euclid(PI)
+                })
+                .collect()
+        };
+        assert!((poincare_index(&field(1.0), 2, 2, 5) - 1.0).abs() < 1e-3);
+        assert!((poincare_index(&field(-1.0), 2, 2, 5) + 1.0).abs() < 1e-3);
+        assert!(poincare_index(&field(1.0), 3, 1, 5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn whorl_centre_is_moved_to_the_middle() {
+        // Concentric ridges with a 9 px period around (100, 150)
+        let img = GrayImage::from_fn(256, 256, |x, y| {
+            let r = ((x as f32 - 100.0).powi(2) + (y as f32 - 150.0).powi(2)).sqrt();
+            Luma([(128.0 + 90.0 * (2.0 * PI * r / 9.0).cos()) as u8])
+        });
+        let (aligned, core) = align_to_core(&img);
+        let (x, y) = core.expect("core found");
+        assert!((x - 100.0).abs() <= 16.0 && (y - 150.0).abs() <= 16.0, "core at ({}, {})", x, y);
+        let source = |core: f32| (128 - (128.0 - core).round() as i64) as u32;
+        assert_eq!(aligned.get_pixel(128, 128), img.get_pixel(source(x), source(y)));
+
+        let blank = GrayImage::from_pixel(64, 64, Luma([200]));
+        assert_eq!(align_to_core(&blank), (blank.clone(), None));
+    }
+
+    #[test]
+    fn rotation_turns_ridges_and_steps_both_ways() {
+        assert_eq!(rotation_angles(4), vec![5, -5, 10, -10]);
+
+        // Vertical ridges (π/2) turned clockwise by 45°, in image coordinates
+        let img = GrayImage::from_fn(64, 64, |x, _| Luma([(128.0 + 90.0 * (2.0 * PI * x as f32 / 8.0).cos()) as u8]));
+        assert_eq!(rotate(&img, 0), img);
+        let orientation = ridge_field(&rotate(&img, 45), 16)[5].orientation;
+        assert!((orientation - 3.0 * PI / 4.0).abs() < 0.1, "orientation {}", orientation);
+    }
+}
@@ -0,0 +1,652 @@
+//! Library API of the client, without file exchange or console UI.
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+use shared::fuzzy;
+use shared::identity::{self, ResultAttestation};
+use shared::quality::{self, QualityReport};
+use shared::template::{self, TemplateParams, DEFAULT_TEMPLATE_BITS};
+use shared::{bytes_to_bits_80, random_bits_80, Cipher, EncryptedSample, EnrolledThreshold, ParameterSet, QualityMask, RegisterRequest, ResultMode, Trivium, VerifyRequest, VerifyResponse};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, ClientKey, CompactPublicKey, CompressedServerKey, ConfigBuilder, FheBool, ServerKey};
+
+use crate::capture_quality::{self, CaptureQuality, DEFAULT_MIN_CAPTURE_QUALITY};
+use crate::fallback::FactorInput;
+use crate::image_source::ImageSource;
+use crate::feature_extraction::{self, extract_fingerprint_bits, extract_with_quality, ExtractionOptions, Extractor, ProbeBits};
+use crate::matching::hamming_distance;
+use crate::progress;
+
+/// Template length used unless another one is configured (see shared/src/template.rs)
+pub const TEMPLATE_BITS: usize = DEFAULT_TEMPLATE_BITS;
+
+/// Environment variable selecting the template length of new enrollments
+pub const TEMPLATE_BITS_ENV: &str = "FINGERPRINT_TEMPLATE_BITS";
+
+/// Environment variable holding the tenant API key sent with every request
+pub const API_KEY_ENV: &str = "FINGERPRINT_API_KEY";
+
+/// Environment variable holding the server's admin key (`server admin key`)
+pub const ADMIN_KEY_ENV: &str = "FINGERPRINT_ADMIN_KEY";
+
+/// Environment variable with the most differing bits a verification may have,
+/// sent FHE-encrypted so the server matches against it without learning it
+pub const THRESHOLD_ENV: &str = "FINGERPRINT_MATCH_THRESHOLD";
+
+/// Environment variable with a threshold stored with a new enrollment: the
+/// most differing bits, FHE-encrypted, or `plain:<bits>` to store it readable
+pub const ENROLL_THRESHOLD_ENV: &str = "FINGERPRINT_ENROLL_THRESHOLD";
+
+/// Environment variable selecting the transciphering cipher (`trivium`, `kreyvium`)
+pub const CIPHER_ENV: &str = "FINGERPRINT_CIPHER";
+
+/// Environment variable enrolling bit weights for a weighted distance:
+/// `lbp` for the extractor's (edge patterns count most), `none` (default)
+pub const BIT_WEIGHTS_ENV: &str = "FINGERPRINT_BIT_WEIGHTS";
+
+/// Environment variable selecting the feature extractor (`lbp`, the default, or
+/// `gabor`); it is recorded with the enrollment and verification must use the same one
+pub const EXTRACTOR_ENV: &str = "FINGERPRINT_EXTRACTOR";
+
+/// Environment variable centring the core point before extraction (`core`), or
+/// not (`none`, the default); like the extractor it is recorded with the enrollment
+pub const ALIGN_ENV: &str = "FINGERPRINT_ALIGN";
+
+/// Environment variable with the lowest capture quality score (0-100) an
+/// enrollment accepts (see capture_quality.rs)
+pub const MIN_CAPTURE_QUALITY_ENV: &str = "FINGERPRINT_MIN_CAPTURE_QUALITY";
+
+/// Environment variable enrolling the primary finger as a fuzzy extractor:
+/// `on`, or the BCH field size in bits (see shared/src/fuzzy.rs)
+pub const FUZZY_ENV: &str = "FINGERPRINT_FUZZY";
+
+/// Environment variable with the bit errors each block of a fuzzy sketch
+/// corrects; `eval` measures the FRR and FAR each choice gives
+pub const FUZZY_ERRORS_ENV: &str = "FINGERPRINT_FUZZY_ERRORS";
+
+/// Environment variable sending the per-bit quality mask of each capture:
+/// `plain`, or `encrypted` to keep the unreliable positions from the server
+pub const QUALITY_MASK_ENV: &str = "FINGERPRINT_QUALITY_MASK";
+
+/// Environment variable asking verify for the match bit only (`match_only`) or
+/// also the encrypted distance (`match_and_distance`, the default)
+pub const RESULT_MODE_ENV: &str = "FINGERPRINT_RESULT_MODE";
+
+/// Environment variable sending a compressed server key with register (`on`), so a
+/// server built with the `gpu` feature can verify on its GPU (see shared/src/compute.rs)
+pub const GPU_KEY_ENV: &str = "FINGERPRINT_GPU_KEY";
+
+/// Tenant API key from the environment (None = server's default tenant)
+pub fn api_key_from_env() -> Option<String> {
+    std::env::var(API_KEY_ENV).ok().filter(|k| !k.is_empty())
+}
+
+/// Configured template length (default 1024), validated
+pub fn template_bits_from_env() -> Result<usize, Box<dyn std::error::Error>> {
+    let bits = match std::env::var(TEMPLATE_BITS_ENV) {
+        Ok(value) => value.trim().parse().map_err(|e| format!("Invalid {}: {}", TEMPLATE_BITS_ENV, e))?,
+        Err(_) => TEMPLATE_BITS,
+    };
+    template::validate(bits)?;
+    Ok(bits)
+}
+
+/// Configured transciphering cipher (default Trivium)
+pub fn cipher_from_env() -> Result<Cipher, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(CIPHER_ENV) else { return Ok(Cipher::default()) };
+    match value.trim().to_lowercase().as_str() {
+        "trivium" => Ok(Cipher::Trivium),
+        "kreyvium" => Ok(Cipher::Kreyvium),
+        _ => Err(format!("Invalid {}: {} (trivium or kreyvium)", CIPHER_ENV, value).into()),
+    }
+}
+
+/// Configured verify result mode (default match and distance)
+pub fn result_mode_from_env() -> Result<ResultMode, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(RESULT_MODE_ENV) else { return Ok(ResultMode::default()) };
+    match value.trim().to_lowercase().as_str() {
+        "" | "match_and_distance" => Ok(ResultMode::MatchAndDistance),
+        "match_only" => Ok(ResultMode::MatchOnly),
+        _ => Err(format!("Invalid {}: {} (match_only or match_and_distance)", RESULT_MODE_ENV, value).into()),
+    }
+}
+
+/// Configured feature extractor (default LBP)
+pub fn extractor_from_env() -> Result<Extractor, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(EXTRACTOR_ENV) else { return Ok(Extractor::default()) };
+    match value.trim().to_lowercase().as_str() {
+        "" | "lbp" => Ok(Extractor::Lbp),
+        "gabor" => Ok(Extractor::Gabor),
+        _ => Err(format!("Invalid {}: {} (lbp or gabor)", EXTRACTOR_ENV, value).into()),
+    }
+}
+
+/// Parameters of templates of `template_bits` from the configured extraction
+pub fn template_params(template_bits: usize) -> Result<TemplateParams, Box<dyn std::error::Error>> {
+    let options = extraction_options_from_env()?;
+    Ok(TemplateParams::new(template_bits, options.extractor)?.with_core_alignment(options.align_core))
+}
+
+/// Configured extraction: extractor and core alignment
+pub fn extraction_options_from_env() -> Result<ExtractionOptions, Box<dyn std::error::Error>> {
+    let align_core = match std::env::var(ALIGN_ENV) {
+        Err(_) => false,
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "" | "none" => false,
+            "core" => true,
+            _ => return Err(format!("Invalid {}: {} (core or none)", ALIGN_ENV, value).into()),
+        },
+    };
+    Ok(ExtractionOptions { extractor: extractor_from_env()?, align_core, rotation_degrees: 0 })
+}
+
+/// Configured minimum capture quality for enrollment (default 40)
+pub fn min_capture_quality_from_env() -> Result<u8, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(MIN_CAPTURE_QUALITY_ENV) else { return Ok(DEFAULT_MIN_CAPTURE_QUALITY) };
+    match value.trim().parse::<u8>() {
+        Ok(min) if min <= 100 => Ok(min),
+        _ => Err(format!("Invalid {}: {} (0 to 100)", MIN_CAPTURE_QUALITY_ENV, value).into()),
+    }
+}
+
+/// Configured fuzzy extractor field size (None = threshold matching)
+pub fn fuzzy_field_bits_from_env() -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(FUZZY_ENV) else { return Ok(None) };
+    match value.trim().to_lowercase().as_str() {
+        "" | "off" => Ok(None),
+        "on" => Ok(Some(fuzzy::DEFAULT_FIELD_BITS)),
+        other => match other.parse::<u32>() {
+            Ok(bits) if fuzzy::FIELD_BITS.contains(&bits) => Ok(Some(bits)),
+            _ => Err(format!(
+                "Invalid {}: {} (on, off or a field size of {} to {} bits)",
+                FUZZY_ENV, value, fuzzy::FIELD_BITS.start(), fuzzy::FIELD_BITS.end()
+            )
+            .into()),
+        },
+    }
+}
+
+/// Configured fuzzy extractor field size and bit errors per block (None = threshold
+/// matching). The errors have no default: they come from measuring the FRR with `eval`.
+pub fn fuzzy_from_env() -> Result<Option<(u32, usize)>, Box<dyn std::error::Error>> {
+    let Some(field_bits) = fuzzy_field_bits_from_env()? else { return Ok(None) };
+    let value = std::env::var(FUZZY_ERRORS_ENV).map_err(|_| {
+        format!("{} needs {}: run eval on a dataset of your sensor to measure the FRR of each choice", FUZZY_ENV, FUZZY_ERRORS_ENV)
+    })?;
+    match value.trim().parse::<usize>() {
+        Ok(errors) if errors > 0 => Ok(Some((field_bits, errors))),
+        _ => Err(format!("Invalid {}: {} (bit errors per block, at least 1)", FUZZY_ERRORS_ENV, value).into()),
+    }
+}
+
+/// Serialized compressed server key for GPU evaluation, if `FINGERPRINT_GPU_KEY` asks for one
+pub fn gpu_key_from_env(client_key: &ClientKey) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(GPU_KEY_ENV) else { return Ok(None) };
+    match value.trim().to_lowercase().as_str() {
+        "" | "off" => Ok(None),
+        "on" => Ok(Some(bincode::serialize(&CompressedServerKey::new(client_key))?)),
+        _ => Err(format!("Invalid {}: {} (on or off)", GPU_KEY_ENV, value).into()),
+    }
+}
+
+/// Serialized compact public key the server encrypts an ownership challenge under (see shared/src/ownership.rs)
+pub fn ownership_public_key(client_key: &ClientKey) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let public_key = CompactPublicKey::try_new(client_key).map_err(|e| format!("Compact public key unavailable: {}", e))?;
+    Ok(bincode::serialize(&public_key)?)
+}
+
+/// Quality score of a capture, mapped onto the reference sensor like for extraction
+pub fn assess_capture(image_path: &str) -> Result<CaptureQuality, Box<dyn std::error::Error>> {
+    let (img, _) = feature_extraction::load_capture(&ImageSource::from(image_path))?;
+    Ok(capture_quality::assess(&img))
+}
+
+/// Fingerprint bits encrypted under a fresh Trivium (or Kreyvium) key/IV
+pub struct TriviumTemplate {
+    pub ciphertext: Vec<bool>,
+    pub key_bits: Vec<bool>,  // 80 bits (Kreyvium: 128)
+    pub iv_bits: Vec<bool>,   // 80 bits (Kreyvium: 128)
+    pub cipher: Cipher,
+}
+
+/// Decrypted outcome of a verification
+#[derive(Serialize, Debug, Clone)]
+pub struct VerifyOutcome {
+    pub user_id: String,
+    pub match_result: bool,
+    pub distance: Option<usize>,  // None for a match-only result (see `RESULT_MODE_ENV`)
+    pub similarity: Option<f32>,
+    pub timestamp: String,
+    pub template_bits: usize,
+    pub compared_bits: usize,     // Less than template_bits for partial probes
+    pub weighted_bits: Option<usize>, // Total weight of the compared bits, for a weighted distance
+    pub attestation: Option<ResultAttestation>, // Server identity signature over the encrypted result
+    pub ownership_proof: Option<String>, // Decrypted ownership challenge, for a delete request
+}
+
+/// Extract the default-length binary template from an image
+pub fn extract_template(image_path: &str) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    extract_template_with(image_path, TEMPLATE_BITS)
+}
+
+/// Extract a `template_bits` long binary template from an image and check its length
+pub fn extract_template_with(image_path: &str, template_bits: usize) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    extract_template_from(&ImageSource::from(image_path), template_bits)
+}
+
+/// Extract a `template_bits` long binary template from a file or a raw sensor frame
+pub fn extract_template_from(source: &ImageSource, template_bits: usize) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    template::validate(template_bits)?;
+    let bits = extract_fingerprint_bits(source, template_bits, extraction_options_from_env()?)?;
+
+    if bits.len() != template_bits {
+        return Err(format!("Expected {} bits, got {}", template_bits, bits.len()).into());
+    }
+
+    Ok(bits)
+}
+
+/// Extract a template from the capture rotated by `degrees` (a rotated enrollment variant)
+pub fn extract_rotated(image_path: &str, template_bits: usize, degrees: i32) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    template::validate(template_bits)?;
+    let options = ExtractionOptions { rotation_degrees: degrees, ..extraction_options_from_env()? };
+    let bits = extract_fingerprint_bits(&ImageSource::from(image_path), template_bits, options)?;
+    if bits.len() != template_bits {
+        return Err(format!("Expected {} bits, got {}", template_bits, bits.len()).into());
+    }
+    Ok(bits)
+}
+
+/// Extract a probe: template bits, region coverage and per-bit quality mask
+pub fn extract_probe(
+    image_path: &str,
+    template_bits: usize,
+) -> Result<ProbeBits, Box<dyn std::error::Error>> {
+    template::validate(template_bits)?;
+    let options = extraction_options_from_env()?;
+    let (bits, coverage, quality_mask) = extract_with_quality(&ImageSource::from(image_path), template_bits, options)?;
+    if bits.len() != template_bits {
+        return Err(format!("Expected {} bits, got {}", template_bits, bits.len()).into());
+    }
+    Ok((bits, coverage, quality_mask))
+}
+
+/// Extract a partial probe: template bits and the coverage mask the server compares on
+pub fn extract_partial_template(
+    image_path: &str,
+    template_bits: usize,
+) -> Result<(Vec<bool>, Vec<bool>), Box<dyn std::error::Error>> {
+    let (bits, mask, _) = extract_probe(image_path, template_bits)?;
+    template::check_mask(&mask, template_bits)?;
+    Ok((bits, mask))
+}
+
+/// Enrollment template, its quality report and per-bit quality mask
+pub type TemplateWithQuality = (Vec<bool>, QualityReport, Vec<bool>);
+
+/// Extract an enrollment template together with its quality report and per-bit quality mask
+pub fn extract_template_with_quality(
+    image_path: &str,
+    template_bits: usize,
+) -> Result<TemplateWithQuality, Box<dyn std::error::Error>> {
+    let (bits, coverage, quality_mask) = extract_probe(image_path, template_bits)?;
+    let quality = QualityReport::assess(&bits, &coverage);
+    Ok((bits, quality, quality_mask))
+}
+
+/// Expand a numeric PIN into a deterministic template-sized bit string.
+///
+/// Digits are packed as BCD into an 80-bit Trivium key; the keystream under a
+/// fixed IV becomes the PIN "template", matched by the server with threshold 0.
+pub fn pin_template(pin: &str, template_bits: usize) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    if pin.len() < 4 || pin.len() > 16 || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err("PIN must be 4-16 digits".into());
+    }
+
+    let mut key_bits = vec![false; 80];
+    for (i, digit) in pin.bytes().map(|b| b - b'0').enumerate() {
+        for j in 0..4 {
+            key_bits[i * 4 + j] = (digit >> j) & 1 == 1;
+        }
+    }
+    // Length in bits 64..72 so "1234" and "01234" differ
+    for j in 0..8 {
+        key_bits[64 + j] = (pin.len() >> j) & 1 == 1;
+    }
+
+    // "PIN_TEMP" in the low 64 bits, as every PIN enrollment so far was made with
+    let mut iv = [0u8; 10];
+    iv[..8].copy_from_slice(&0x5049_4e5f_5445_4d50u64.to_le_bytes());
+    let iv_bits = bytes_to_bits_80(&iv);
+    let mut trivium = Trivium::new(&key_bits, &iv_bits);
+    Ok(trivium.process(&vec![false; template_bits]))
+}
+
+/// Template bits for a factor input (image extraction or PIN expansion)
+pub fn template_from_input(input: &FactorInput, template_bits: usize) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    match input {
+        FactorInput::Image(path) => extract_template_with(path, template_bits),
+        FactorInput::Pin(pin) => pin_template(pin, template_bits),
+    }
+}
+
+/// Encrypt template bits with Trivium under a random key/IV, with a local round-trip check
+pub fn trivium_encrypt(bits: &[bool]) -> Result<TriviumTemplate, Box<dyn std::error::Error>> {
+    encrypt_template(bits, Cipher::Trivium)
+}
+
+/// Encrypt template bits with `cipher` under a random key/IV, with a local round-trip check
+pub fn encrypt_template(bits: &[bool], cipher: Cipher) -> Result<TriviumTemplate, Box<dyn std::error::Error>> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+
+    let (key_bits, iv_bits) = match cipher {
+        Cipher::Trivium => (random_bits_80(&mut rng), random_bits_80(&mut rng)),
+        Cipher::Kreyvium => (
+            (0..cipher.key_bits()).map(|_| rng.gen()).collect(),
+            (0..cipher.iv_bits()).map(|_| rng.gen()).collect(),
+        ),
+    };
+
+    let ciphertext = cipher.process(&key_bits, &iv_bits, 0, bits);
+
+    // Sanity check
+    let decrypted_local = cipher.process(&key_bits, &iv_bits, 0, &ciphertext);
+    let errors = hamming_distance(&decrypted_local, bits);
+
+    if errors != 0 {
+        return Err(format!("{} sanity check failed: {} errors", cipher, errors).into());
+    }
+
+    Ok(TriviumTemplate { ciphertext, key_bits, iv_bits, cipher })
+}
+
+/// Generate a fresh FHE key pair with the default configuration
+pub fn generate_fhe_keys() -> (ClientKey, ServerKey) {
+    let config = ConfigBuilder::default().build();
+    generate_keys(config)
+}
+
+/// Generate a fresh FHE key pair with a specific parameter set
+pub fn generate_fhe_keys_with(params: ParameterSet) -> (ClientKey, ServerKey) {
+    generate_keys(params.config())
+}
+
+pub fn fhe_encrypt_bits(bits: &[bool], client_key: &ClientKey) -> Vec<FheBool> {
+    encrypt_bits_counted(bits, client_key, &ProgressBar::hidden())
+}
+
+fn encrypt_bits_counted(bits: &[bool], client_key: &ClientKey, bar: &ProgressBar) -> Vec<FheBool> {
+    bits.iter()
+        .map(|&b| {
+            let bit = FheBool::encrypt(b, client_key);
+            bar.inc(1);
+            bit
+        })
+        .collect()
+}
+
+/// FHE-encrypt the key and the IV under one progress bar
+fn encrypt_key_iv(template: &TriviumTemplate, client_key: &ClientKey) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    let bar = progress::encryption_bar((template.key_bits.len() + template.iv_bits.len()) as u64);
+    let key = bincode::serialize(&encrypt_bits_counted(&template.key_bits, client_key, &bar))?;
+    let iv = bincode::serialize(&encrypt_bits_counted(&template.iv_bits, client_key, &bar))?;
+    bar.finish_and_clear();
+    Ok((key, iv))
+}
+
+/// Encrypt one capture of a multi-sample enrollment under a fresh key/IV and FHE-encrypt those
+pub fn encrypt_sample(
+    bits: &[bool],
+    cipher: Cipher,
+    client_key: &ClientKey,
+) -> Result<EncryptedSample, Box<dyn std::error::Error>> {
+    let template = encrypt_template(bits, cipher)?;
+    let (encrypted_key_bytes, encrypted_iv_bytes) = encrypt_key_iv(&template, client_key)?;
+    Ok(EncryptedSample { ciphertext: template.ciphertext, encrypted_key_bytes, encrypted_iv_bytes })
+}
+
+/// FHE-encrypt the Trivium key/IV and build a RegisterRequest
+pub fn build_register_request(
+    user_id: &str,
+    template: TriviumTemplate,
+    client_key: &ClientKey,
+    server_key_bytes: Option<Vec<u8>>,
+) -> Result<RegisterRequest, Box<dyn std::error::Error>> {
+    let (encrypted_key_bytes, encrypted_iv_bytes) = encrypt_key_iv(&template, client_key)?;
+    let params = template_params(template.ciphertext.len())?;
+
+    Ok(RegisterRequest::new(
+        user_id.to_string(),
+        template.ciphertext,
+        encrypted_key_bytes,
+        encrypted_iv_bytes,
+        server_key_bytes,
+    )
+    .with_cipher(template.cipher)
+    .with_params(params))
+}
+
+/// FHE-encrypt the Trivium key/IV and the `true` of fail-open answers and build a VerifyRequest
+pub fn build_verify_request(
+    user_id: &str,
+    template: TriviumTemplate,
+    client_key: &ClientKey,
+) -> Result<VerifyRequest, Box<dyn std::error::Error>> {
+    let (encrypted_key_bytes, encrypted_iv_bytes) = encrypt_key_iv(&template, client_key)?;
+    let encrypted_true_bytes = bincode::serialize(&FheBool::encrypt(true, client_key))?;
+    let params = template_params(template.ciphertext.len())?;
+
+    Ok(VerifyRequest::new(
+        user_id.to_string(),
+        template.ciphertext,
+        encrypted_key_bytes,
+        encrypted_iv_bytes,
+        encrypted_true_bytes,
+    )
+    .with_cipher(template.cipher)
+    .with_params(params)
+    .with_request_id(identity::new_request_id()))
+}
+
+/// FHE-encrypt the threshold set in the environment (None = the server's policy).
+///
+/// The threshold is given for full templates; for a partial probe it is
+/// scaled to the bits `mask` covers, as the server does with its own.
+pub fn encrypted_threshold_from_env(
+    template_bits: usize,
+    mask: Option<&[bool]>,
+    client_key: &ClientKey,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(THRESHOLD_ENV) else { return Ok(None) };
+    let threshold = parse_threshold(THRESHOLD_ENV, &value, template_bits)?;
+    Ok(Some(encrypted_threshold(threshold, template_bits, mask, client_key)?))
+}
+
+/// FHE-encrypt a threshold for full templates, scaled to the bits `mask` covers
+pub fn encrypted_threshold(
+    threshold: usize,
+    template_bits: usize,
+    mask: Option<&[bool]>,
+    client_key: &ClientKey,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if threshold > template_bits {
+        return Err(format!("Threshold is {}, templates only have {} bits", threshold, template_bits).into());
+    }
+    let threshold = match mask {
+        Some(mask) => template::partial_threshold(threshold, template::check_mask(mask, template_bits)?, template_bits),
+        None => threshold,
+    };
+    encrypt_threshold(threshold, template_bits, client_key)
+}
+
+/// Per-user threshold to enroll, from the environment (None = the server's policy)
+pub fn enrolled_threshold_from_env(
+    template_bits: usize,
+    client_key: &ClientKey,
+) -> Result<Option<EnrolledThreshold>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(ENROLL_THRESHOLD_ENV) else { return Ok(None) };
+    let threshold = match value.trim().strip_prefix("plain:") {
+        Some(bits) => EnrolledThreshold::Plain(parse_threshold(ENROLL_THRESHOLD_ENV, bits, template_bits)?),
+        None => {
+            let threshold = parse_threshold(ENROLL_THRESHOLD_ENV, &value, template_bits)?;
+            EnrolledThreshold::Encrypted(encrypt_threshold(threshold, template_bits, client_key)?)
+        }
+    };
+    Ok(Some(threshold))
+}
+
+/// Bit weights to enroll, as set in the environment (None = unweighted distance)
+pub fn bit_weights_from_env(template_bits: usize) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(BIT_WEIGHTS_ENV) else { return Ok(None) };
+    match value.trim().to_lowercase().as_str() {
+        "" | "none" => Ok(None),
+        "lbp" => {
+            if extractor_from_env()? != Extractor::Lbp {
+                return Err(format!("{}=lbp needs the LBP extractor ({})", BIT_WEIGHTS_ENV, EXTRACTOR_ENV).into());
+            }
+            let weights = feature_extraction::bit_weights(template_bits);
+            template::check_weights(&weights, template_bits)?;
+            Ok(Some(weights))
+        }
+        _ => Err(format!("Invalid {}: {} (lbp or none)", BIT_WEIGHTS_ENV, value).into()),
+    }
+}
+
+/// Quality mask to send with a capture, as set in the environment (None = compare all bits)
+pub fn quality_mask_from_env(mask: &[bool], client_key: &ClientKey) -> Result<Option<QualityMask>, Box<dyn std::error::Error>> {
+    let Ok(value) = std::env::var(QUALITY_MASK_ENV) else { return Ok(None) };
+    let encrypted = match value.trim().to_lowercase().as_str() {
+        "plain" => false,
+        "encrypted" => true,
+        _ => return Err(format!("Invalid {}: {} (plain or encrypted)", QUALITY_MASK_ENV, value).into()),
+    };
+    quality::check_quality_mask(mask, mask.len())?;
+    Ok(Some(if encrypted {
+        QualityMask::Encrypted(bincode::serialize(&fhe_encrypt_bits(mask, client_key))?)
+    } else {
+        QualityMask::Plain(mask.to_vec())
+    }))
+}
+
+fn parse_threshold(name: &str, value: &str, template_bits: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    let threshold: usize = value.trim().parse().map_err(|e| format!("Invalid {}: {}", name, e))?;
+    if threshold > template_bits {
+        return Err(format!("{} is {}, templates only have {} bits", name, threshold, template_bits).into());
+    }
+    Ok(threshold)
+}
+
+/// Threshold as `template::distance_width(template_bits)` FHE-encrypted bits, LSB-first
+fn encrypt_threshold(threshold: usize, template_bits: usize, client_key: &ClientKey) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bits: Vec<bool> = (0..template::distance_width(template_bits)).map(|i| (threshold >> i) & 1 == 1).collect();
+    Ok(bincode::serialize(&fhe_encrypt_bits(&bits, client_key))?)
+}
+
+/// Decrypt the encrypted match bit and distance of a verify response
+pub fn decrypt_verify_result(
+    user_id: &str,
+    response: &VerifyResponse,
+    client_key: &ClientKey,
+) -> Result<VerifyOutcome, Box<dyn std::error::Error>> {
+    let encrypted_match: FheBool = bincode::deserialize(&response.encrypted_match_bytes)?;
+    let mut match_result: bool = encrypted_match.decrypt(client_key);
+
+    // Counter width follows the template length the server matched (11 bits for 1024), or
+    // the total weight of a weighted distance; a fail-open result carries no distance,
+    // a match-only one not even its encryption
+    let template_bits = response.template_bits.unwrap_or(TEMPLATE_BITS);
+    let compared_bits = response.compared_bits.unwrap_or(template_bits);
+    let distance = if response.encrypted_distance_bytes.is_empty() {
+        None
+    } else {
+        let encrypted_distance: Vec<FheBool> = bincode::deserialize(&response.encrypted_distance_bytes)?;
+        let distance_bits: Vec<bool> = encrypted_distance
+            .iter()
+            .map(|b| b.decrypt(client_key))
+            .collect();
+        let width = template::result_width(template_bits, response.weighted_bits);
+        if response.failure.is_none() && distance_bits.len() != width {
+            return Err(format!(
+                "Distance has {} bits, expected {} for {}-bit templates",
+                distance_bits.len(),
+                width,
+                template_bits
+            )
+            .into());
+        }
+        Some(bits_to_usize(&distance_bits))
+    };
+    let similarity = distance.map(|distance| template::similarity(distance, response.weighted_bits.unwrap_or(compared_bits)));
+
+    // A fuzzy enrollment also returns the key it recovered; only the enrolled key counts
+    if let (Some(bytes), Some(key_hash)) = (&response.encrypted_fuzzy_key_bytes, &response.fuzzy_key_hash) {
+        let encrypted_key: Vec<FheBool> = bincode::deserialize(bytes)?;
+        let key: Vec<bool> = encrypted_key.iter().map(|b| b.decrypt(client_key)).collect();
+        match_result &= fuzzy::check_key(&key, key_hash);
+    }
+
+    let ownership_proof = match &response.encrypted_ownership_bytes {
+        Some(bytes) => {
+            let encrypted_nonce: Vec<FheBool> = bincode::deserialize(bytes)?;
+            let nonce: Vec<bool> = encrypted_nonce.iter().map(|b| b.decrypt(client_key)).collect();
+            Some(shared::ownership::proof_hex(&nonce))
+        }
+        None => None,
+    };
+
+    Ok(VerifyOutcome {
+        user_id: user_id.to_string(),
+        match_result,
+        distance,
+        similarity,
+        template_bits,
+        compared_bits,
+        weighted_bits: response.weighted_bits,
+        timestamp: response.timestamp.clone(),
+        attestation: response.attestation.clone(),
+        ownership_proof,
+    })
+}
+
+/// Decrypt the duress flag of a verify response, if the server attached one.
+///
+/// Meant for the relying party; the CLI never displays it.
+pub fn decrypt_duress_flag(
+    response: &VerifyResponse,
+    client_key: &ClientKey,
+) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+    match &response.encrypted_duress_bytes {
+        Some(bytes) => {
+            let encrypted_duress: FheBool = bincode::deserialize(bytes)?;
+            Ok(Some(encrypted_duress.decrypt(client_key)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn bits_to_usize(bits: &[bool]) -> usize {
+    let mut result = 0;
+    for (i, &bit) in bits.iter().enumerate() {
+        if bit {
+            result += 1 << i;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_only_result_decrypts_without_a_distance() {
+        let (client_key, _) = generate_keys(ConfigBuilder::default().build());
+        let matched = FheBool::encrypt(true, &client_key);
+        let response = VerifyResponse::success(bincode::serialize(&matched).unwrap(), Vec::new());
+
+        let outcome = decrypt_verify_result("alice", &response, &client_key).unwrap();
+        assert!(outcome.match_result);
+        assert_eq!((outcome.distance, outcome.similarity), (None, None));
+    }
+}
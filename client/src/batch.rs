@@ -0,0 +1,403 @@
+//! `register-batch <manifest.csv>`: enroll a dataset in one run.
+//!
+//! The manifest has one `user_id,image_path` line per user; an optional
+//! `user_id,image_path` header, blank lines and `#` comments are skipped,
+//! and relative image paths are read from the manifest's directory. Every
+//! entry is encrypted under the same client key, loaded once (or generated
+//! once, its server key going out with the first enrollment). Worker
+//! threads extract and encrypt the next entries while the previous ones are
+//! with the server, so a batch takes about as long as its slowest stage.
+//!
+//! Entries are registered like `register <user_id> <image_path>`, with the
+//! settings of the environment; the client key is kept rather than replaced,
+//! leftovers of interrupted runs are not offered for recovery, and no
+//! enrollment state is kept for `update`. The run ends with a summary of
+//! the registrations that failed (with `--output json`, one JSON document)
+//! and exits with an error if any did.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tfhe::ClientKey;
+
+use client::api;
+use client::output::{self, CommandReport};
+use client::{capture_quality, progress, say, say_tr};
+use shared::fuzzy;
+use shared::protocol::server_key_fingerprint;
+use shared::quality::QualityReport;
+use shared::session::ClientCredential;
+use shared::telemetry::PhaseTimer;
+use shared::transform::CancelableTransform;
+use shared::{Cipher, RegisterRequest, RegisterResponse};
+
+use crate::history::{self, HistoryEntry};
+use crate::{
+    advisor, config, get_client_key_path, load_client_key, load_credential, negotiated_cipher, open_session, profile,
+    registration_template_bits, revocation, server_label, user_exchange, wait_for_response, REGISTER_REQUEST,
+    REGISTER_RESPONSE,
+};
+
+/// One registration of the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub line: usize,            // 1-based, for messages
+    pub user_id: String,
+    pub image_path: String,     // Resolved against the manifest's directory
+}
+
+/// Entries of a manifest; `base` is the directory relative image paths are in
+pub fn parse_manifest(data: &str, base: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    let mut user_ids = HashSet::new();
+    let mut first = true;
+    for (index, line) in data.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((user_id, image_path)) = line.split_once(',') else {
+            return Err(format!("line {}: expected user_id,image_path", line_number));
+        };
+        let unquote = |field: &str| field.trim().trim_matches('"').to_string();
+        let (user_id, image_path) = (unquote(user_id), unquote(image_path));
+        if std::mem::take(&mut first) && user_id.eq_ignore_ascii_case("user_id") {
+            continue;   // Header
+        }
+        if user_id.is_empty() || image_path.is_empty() {
+            return Err(format!("line {}: empty user_id or image_path", line_number));
+        }
+        if !user_ids.insert(user_id.clone()) {
+            return Err(format!("line {}: user '{}' is listed twice", line_number, user_id));
+        }
+        entries.push(ManifestEntry {
+            line: line_number,
+            user_id,
+            image_path: base.join(image_path).to_string_lossy().into_owned(),
+        });
+    }
+    Ok(entries)
+}
+
+/// What every entry is encrypted with, set up once for the batch
+struct Settings<'a> {
+    client_key: &'a ClientKey,
+    template_bits: usize,
+    cipher: Cipher,
+    min_quality: u8,
+    fuzzy_code: Option<(u32, usize)>,   // Field size and bit errors per block
+    bit_weights: Option<Vec<u8>>,
+}
+
+/// A request ready to send but for the session, bound when it goes out
+struct Prepared {
+    request: RegisterRequest,
+    quality: QualityReport,
+    timer: PhaseTimer,
+}
+
+/// Outcome of `register-batch`, printed with `--output json`
+#[derive(Serialize, Debug)]
+struct BatchReport {
+    command: &'static str,          // "register-batch"
+    manifest: String,
+    registered: usize,
+    failed: usize,
+    total_ms: u64,
+    entries: Vec<CommandReport>,    // In manifest order
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let manifest = Path::new(&args[0]);
+    let concurrency = args[1..]
+        .windows(2)
+        .find(|w| w[0] == "--concurrency")
+        .map(|w| match w[1].parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("Invalid --concurrency value '{}'", w[1])),
+        })
+        .transpose()?
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+
+    say_tr!("batch.title");
+    say!("{}", "─".repeat(70));
+    let data = fs::read_to_string(manifest).map_err(|e| format!("Can't read manifest {}: {}", manifest.display(), e))?;
+    let entries = parse_manifest(&data, manifest.parent().unwrap_or(Path::new(".")))
+        .map_err(|e| format!("Invalid manifest {}: {}", manifest.display(), e))?;
+    if entries.is_empty() {
+        return Err(format!("No entries in manifest {}", manifest.display()).into());
+    }
+    let concurrency = concurrency.min(entries.len());
+    say_tr!("batch.entries", entries.len(), manifest.display(), concurrency);
+
+    fs::create_dir_all(&config::get().paths.data)?;
+    let template_bits = registration_template_bits()?;
+    let cipher = negotiated_cipher()?;
+    say_tr!("client.template_bits", template_bits);
+    let (client_key, server_key_bytes) = batch_keys()?;
+    let mut pending_keys = (server_key_bytes, api::gpu_key_from_env(&client_key)?);
+    let credential = load_credential()?;
+    let settings = Settings {
+        client_key: &client_key,
+        template_bits,
+        cipher,
+        min_quality: api::min_capture_quality_from_env()?,
+        fuzzy_code: api::fuzzy_from_env()?,
+        bit_weights: api::bit_weights_from_env(template_bits)?,
+    };
+    // New transforms are only issued as their entries are sent, so failed ones leave none behind
+    let transforms = entries
+        .iter()
+        .map(|entry| revocation::proposed_transform(&entry.user_id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Bars of entries encrypted side by side would only garble each other
+    progress::set_visible(false);
+    let next = AtomicUsize::new(0);
+    let mut reports: Vec<Option<CommandReport>> = vec![None; entries.len()];
+    let (sender, receiver) = mpsc::sync_channel(concurrency);
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            let sender = sender.clone();
+            let (next, entries, transforms, settings) = (&next, &entries, &transforms, &settings);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(entry) = entries.get(index) else { break };
+                let started = Instant::now();
+                let prepared = prepare(entry, &transforms[index], settings).map_err(|e| {
+                    CommandReport::new("register", &entry.user_id)
+                        .with_error(e.as_ref())
+                        .with_timings(&PhaseTimer::start(), started.elapsed())
+                });
+                if sender.send((index, started, prepared)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Sent one at a time, in the order they are ready
+        for (done, (index, started, prepared)) in receiver.iter().enumerate() {
+            let entry = &entries[index];
+            let report = match prepared {
+                Ok(prepared) => {
+                    let mut timer = prepared.timer;
+                    // One at a time: every new transform rewrites the transforms file
+                    let result = revocation::keep_transform(&entry.user_id, &transforms[index])
+                        .and_then(|()| submit(&entry.user_id, prepared.request, prepared.quality, &credential, &mut pending_keys));
+                    timer.lap("server");
+                    record(&entry.user_id, &timer, started.elapsed(), result)
+                }
+                Err(report) => {
+                    let mut history_entry = HistoryEntry::new("register", &entry.user_id, &server_label());
+                    history_entry.error = report.error.clone();
+                    history::record(&history_entry);
+                    report
+                }
+            };
+            match &report.error {
+                None => say_tr!("batch.entry_registered", done + 1, entries.len(), entry.user_id, report.total_ms),
+                Some(error) => say_tr!("batch.entry_failed", done + 1, entries.len(), entry.user_id, error),
+            }
+            reports[index] = Some(report);
+        }
+    });
+
+    let entries_reports: Vec<CommandReport> = reports.into_iter().flatten().collect();
+    let registered = entries_reports.iter().filter(|report| report.success).count();
+    let failed = entries.len() - registered;
+    say_tr!("batch.summary", registered, failed, entries.len(), start.elapsed().as_secs());
+    for (entry, report) in entries.iter().zip(&entries_reports).filter(|(_, report)| !report.success) {
+        say_tr!("batch.failure", entry.line, entry.user_id, report.error.as_deref().unwrap_or("?"));
+    }
+    if output::json() {
+        let report = BatchReport {
+            command: "register-batch",
+            manifest: manifest.display().to_string(),
+            registered,
+            failed,
+            total_ms: start.elapsed().as_millis() as u64,
+            entries: entries_reports,
+        };
+        println!("{}", serde_json::to_string(&report)?);
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} registrations failed", failed, entries.len()).into());
+    }
+    Ok(())
+}
+
+/// Client key and, when the client key is new, the serialized server key
+type BatchKeys = (ClientKey, Option<Vec<u8>>);
+
+/// The client key to encrypt every entry under, and the server key to send
+/// with the first enrollment when the client key is new
+fn batch_keys() -> Result<BatchKeys, Box<dyn std::error::Error>> {
+    let client_key_path = get_client_key_path();
+    if client_key_path.exists() {
+        say_tr!("client.loading_key");
+        let client_key = load_client_key()?;
+        say_tr!("client.key_loaded", client_key_path.display());
+        return Ok((client_key, None));
+    }
+
+    say_tr!("client.generating_keys");
+    say_tr!("client.keygen_duration");
+    let params = advisor::configured_parameter_set();
+    say_tr!("client.parameter_set", params);
+    let (client_key, server_key) = api::generate_fhe_keys_with(params);
+    fs::create_dir_all(client_key_path.parent().unwrap())?;
+    fs::write(&client_key_path, bincode::serialize(&client_key)?)?;
+    say_tr!("client.key_saved", client_key_path.display());
+    let server_key_bytes = bincode::serialize(&server_key)?;
+    say_tr!("client.server_key_size", server_key_bytes.len());
+    Ok((client_key, Some(server_key_bytes)))
+}
+
+/// Extract, transform and encrypt one entry, as `register` does for a primary finger
+fn prepare(
+    entry: &ManifestEntry,
+    transform: &CancelableTransform,
+    settings: &Settings,
+) -> Result<Prepared, Box<dyn std::error::Error>> {
+    let mut timer = PhaseTimer::start();
+    let assessed = api::assess_capture(&entry.image_path)?;
+    capture_quality::check_min_quality(&assessed, settings.min_quality)?;
+    let (bits, quality, quality_mask) = api::extract_template_with_quality(&entry.image_path, settings.template_bits)?;
+    let bits = transform.apply(&bits);
+    let quality_mask = transform.permute(&quality_mask);
+    timer.lap("features");
+
+    let template = api::encrypt_template(&bits, settings.cipher)?;
+    timer.lap("trivium");
+
+    let client_key = settings.client_key;
+    let mut request = api::build_register_request(&entry.user_id, template, client_key, None)?
+        .with_api_key(api::api_key_from_env())
+        .with_transform_id(Some(transform.id.clone()))
+        .with_capture_quality(assessed.score);
+    if let Some((field_bits, errors)) = settings.fuzzy_code {
+        request = request.with_fuzzy_sketch(fuzzy::sketch(&bits, field_bits, errors, client_key)?.0);
+    } else {
+        request = request
+            .with_threshold(api::enrolled_threshold_from_env(settings.template_bits, client_key)?)
+            .with_quality_mask(api::quality_mask_from_env(&quality_mask, client_key)?);
+        if let Some(weights) = &settings.bit_weights {
+            request = request.with_bit_weights(Some(transform.permute(weights)));
+        }
+    }
+    timer.lap("fhe_encrypt");
+    Ok(Prepared { request, quality, timer })
+}
+
+/// Send a prepared request and wait for the server; the keys in `pending_keys`
+/// go along until an enrollment succeeds
+fn submit(
+    user_id: &str,
+    request: RegisterRequest,
+    quality: QualityReport,
+    credential: &ClientCredential,
+    pending_keys: &mut (Option<Vec<u8>>, Option<Vec<u8>>),
+) -> Result<RegisterResponse, Box<dyn std::error::Error>> {
+    let mut request = request
+        .with_credential_key(credential.public_key())
+        .with_session(open_session(user_id, credential)?)
+        .with_gpu_server_key(pending_keys.1.clone());
+    request.server_key_bytes = pending_keys.0.clone();
+    if request.server_key_bytes.is_some() {
+        profile::warn_shared_tenant();
+    }
+
+    let slot = user_exchange(user_id)?;
+    let _ = slot.delete(REGISTER_RESPONSE);
+    slot.put(REGISTER_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    let response: Result<RegisterResponse, _> = wait_for_response(slot.as_ref(), REGISTER_RESPONSE, Duration::from_secs(30));
+    let _ = slot.delete(REGISTER_RESPONSE);
+    let response = response?.with_quality(Some(quality));
+    if response.success {
+        if let Some(server_key_bytes) = &pending_keys.0 {
+            profile::record_server_key(server_key_fingerprint(server_key_bytes), true)?;
+        }
+        profile::check_reported_fingerprint(response.server_key_fingerprint.as_deref())?;
+        *pending_keys = (None, None);
+    }
+    Ok(response)
+}
+
+/// Record an entry's registration in the history and describe it for the report
+fn record(
+    user_id: &str,
+    timer: &PhaseTimer,
+    elapsed: Duration,
+    result: Result<RegisterResponse, Box<dyn std::error::Error>>,
+) -> CommandReport {
+    let mut entry = HistoryEntry::new("register", user_id, &server_label()).with_duration(elapsed);
+    let mut report = CommandReport::new("register", user_id).with_timings(timer, elapsed);
+    match result {
+        Ok(response) if response.success => {
+            entry.success = true;
+            report.success = true;
+        }
+        Ok(response) => {
+            entry.error = Some(response.message.clone());
+            report.error = Some(response.message);
+            report.error_code = response.error_code;
+        }
+        Err(e) => {
+            entry.error = Some(e.to_string());
+            report = report.with_error(e.as_ref());
+        }
+    }
+    history::record(&entry);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> Result<Vec<ManifestEntry>, String> {
+        parse_manifest(data, Path::new("/data/prints"))
+    }
+
+    #[test]
+    fn manifest_lines_become_entries() {
+        let entries = parse("user_id,image_path\n\n# first batch\nalice, alice.png\n\"bob\",\"/scans/bob.tif\"\n").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ManifestEntry { line: 4, user_id: "alice".into(), image_path: "/data/prints/alice.png".into() },
+                ManifestEntry { line: 5, user_id: "bob".into(), image_path: "/scans/bob.tif".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_first_line_can_be_a_header() {
+        assert_eq!(parse("USER_ID,IMAGE_PATH\nalice,a.png").unwrap().len(), 1);
+        assert_eq!(parse("# header follows\nuser_id,image_path\nalice,a.png").unwrap().len(), 1);
+        assert_eq!(parse("alice,a.png\nuser_id,u.png").unwrap()[1].user_id, "user_id");
+    }
+
+    #[test]
+    fn malformed_manifests_name_the_line() {
+        assert_eq!(parse("alice,a.png\nbob").unwrap_err(), "line 2: expected user_id,image_path");
+        assert_eq!(parse("alice,\n").unwrap_err(), "line 1: empty user_id or image_path");
+        assert_eq!(parse("\"\",a.png").unwrap_err(), "line 1: empty user_id or image_path");
+        assert_eq!(parse("alice,a.png\n\nalice,b.png").unwrap_err(), "line 3: user 'alice' is listed twice");
+    }
+
+    #[test]
+    fn an_empty_manifest_has_no_entries() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("user_id,image_path\n# nothing yet\n").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+//! `bench [--image <path>] [--rounds <n>]`: how fast this host runs each stage.
+//!
+//! Times feature extraction (of `--image`, when given), the plaintext
+//! cipher, FHE key generation and FheBool encryption, then calibrates the
+//! cost of a gate by running a sample of encrypted clocks of the configured
+//! cipher, as the parameter advisor does. A verification is estimated from
+//! that gate cost and the gate counts of transciphering and matching, for a
+//! server on the same hardware; `estimate <user_id>` reports the server's
+//! own calibration and queue instead. Keys are generated with the configured
+//! parameter set and thrown away; the client key on disk is not touched.
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tfhe::prelude::*;
+use tfhe::{set_server_key, FheBool};
+
+use client::{api, output, say};
+
+use crate::advisor::{self, format_secs};
+
+/// Plaintext keystream bits timed for the cipher's throughput
+const CIPHER_SAMPLE_BITS: usize = 1 << 20;
+
+/// Measurements of `bench`, printed with `--output json`
+#[derive(Serialize, Debug)]
+struct BenchReport {
+    parameter_set: String,
+    template_bits: usize,
+    cipher: String,
+    extraction_ms: Option<f64>,         // None without --image
+    cipher_mbit_per_s: f64,
+    keygen_ms: f64,
+    encrypt_bit_us: f64,                // One FheBool
+    gate_us: f64,                       // One bootstrapped gate, from the clock sample
+    verify_client_ms: f64,              // Extraction, cipher and key/IV encryption of a probe
+    verify_server_secs: f64,            // Transciphering both templates and matching them
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let image = args.windows(2).find(|w| w[0] == "--image").map(|w| w[1].clone());
+    let rounds = match args.windows(2).find(|w| w[0] == "--rounds") {
+        Some(w) => w[1].parse::<u32>().ok().filter(|&n| n > 0).ok_or_else(|| format!("Invalid --rounds value '{}'", w[1]))?,
+        None => 3,
+    };
+    let template_bits = api::template_bits_from_env()?;
+    let cipher = api::cipher_from_env()?;
+    let params = advisor::configured_parameter_set();
+
+    say!("⏱️  CLIENT BENCHMARK");
+    say!("{}", "─".repeat(70));
+    say!("🔧 {}, {}-bit templates, {}, {} round(s)", params, template_bits, cipher, rounds);
+
+    // 1. Feature extraction
+    let extraction = match &image {
+        Some(path) => {
+            let start = Instant::now();
+            for _ in 0..rounds {
+                api::extract_template_with(path, template_bits)?;
+            }
+            let per_template = start.elapsed() / rounds;
+            say!("🔍 Feature extraction:  {:?} per template", per_template);
+            Some(per_template)
+        }
+        None => {
+            say!("🔍 Feature extraction:  skipped (time it with --image <path>)");
+            None
+        }
+    };
+
+    // 2. Plaintext cipher
+    let key = vec![true; cipher.key_bits()];
+    let iv = vec![false; cipher.iv_bits()];
+    let data = vec![false; CIPHER_SAMPLE_BITS];
+    let start = Instant::now();
+    for _ in 0..rounds {
+        cipher.process(&key, &iv, 0, &data);
+    }
+    let per_sample = start.elapsed() / rounds;
+    let mbit_per_s = CIPHER_SAMPLE_BITS as f64 / per_sample.as_secs_f64().max(f64::EPSILON) / 1e6;
+    let per_template = per_sample.mul_f64(template_bits as f64 / CIPHER_SAMPLE_BITS as f64);
+    say!("🔐 {} throughput:  {:.1} Mbit/s ({:?} per template)", cipher, mbit_per_s, per_template);
+
+    // 3. Key generation
+    say!("🔑 Generating FHE keys...");
+    let start = Instant::now();
+    let (client_key, server_key) = api::generate_fhe_keys_with(params);
+    let keygen = start.elapsed();
+    say!("🔑 FHE key generation:  {:?}", keygen);
+
+    // 4. FheBool encryption, as many bits as a request's key and IV
+    let bits = cipher.key_bits() + cipher.iv_bits();
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for i in 0..bits {
+            let _ = FheBool::encrypt(i % 2 == 0, &client_key);
+        }
+    }
+    let per_bit = start.elapsed() / (rounds * bits as u32);
+    let key_iv = per_bit * bits as u32;
+    say!("🔒 FheBool encryption:  {:?} per bit ({:?} for the {}-bit key/IV of a request)", per_bit, key_iv, bits);
+
+    // 5. Gate cost, from a sample of encrypted clocks
+    say!("🧮 Calibrating the gate cost...");
+    set_server_key(server_key);
+    let per_clock = advisor::time_per_clock(&client_key, cipher)?;
+    let per_gate = per_clock / advisor::gates_per_clock(cipher) as u32;
+    say!("🧮 Bootstrapped gate:   {:?} ({:?} per {} clock)", per_gate, per_clock, cipher);
+
+    let client_side = extraction.unwrap_or_default() + per_template + key_iv;
+    let server_side = advisor::verify_estimate(per_clock, template_bits, cipher);
+    say!("\n{}", "─".repeat(70));
+    say!("🏁 Estimated verify:    ~{} ({:?} on the client, {} on a server like this host)",
+        format_secs(client_side + server_side), client_side, format_secs(server_side));
+    if extraction.is_none() {
+        say!("   (without feature extraction; pass --image to include it)");
+    }
+
+    if output::json() {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        let report = BenchReport {
+            parameter_set: params.to_string(),
+            template_bits,
+            cipher: cipher.to_string(),
+            extraction_ms: extraction.map(ms),
+            cipher_mbit_per_s: mbit_per_s,
+            keygen_ms: ms(keygen),
+            encrypt_bit_us: per_bit.as_secs_f64() * 1e6,
+            gate_us: per_gate.as_secs_f64() * 1e6,
+            verify_client_ms: ms(client_side),
+            verify_server_secs: server_side.as_secs_f64(),
+        };
+        println!("{}", serde_json::to_string(&report)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! Sensor profiles of this machine (see client/src/sensor.rs).
+//!
+//! `calibrate-sensor <name> <image>... [--dpi <n>]` derives a profile from
+//! sample captures and stores it in `sensors.json`. The profile applied to
+//! extraction is chosen with `--sensor <name>` (any mode) or
+//! `FINGERPRINT_SENSOR`; without either, images are used as captured.
+//! `--dpi <n>` (any mode) states the capture resolution when the images
+//! don't record it (see client/src/resolution.rs).
+
+use std::fs;
+use std::path::PathBuf;
+
+use client::{say, say_tr};
+use client::image_source::ImageSource;
+use client::sensor::{self, SensorProfile, REFERENCE_DPI};
+
+use crate::get_client_key_path;
+
+const PROFILES_FILE: &str = "sensors.json";
+
+/// Environment variable naming the sensor profile to apply
+pub const SENSOR_ENV: &str = "FINGERPRINT_SENSOR";
+
+pub fn profiles_path() -> PathBuf {
+    get_client_key_path().with_file_name(PROFILES_FILE)
+}
+
+fn load_profiles() -> Result<Vec<SensorProfile>, Box<dyn std::error::Error>> {
+    match fs::read_to_string(profiles_path()) {
+        Ok(data) => Ok(serde_json::from_str(&data)?),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Take a global `<flag> <value>` out of the arguments
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == flag)?;
+    let value = args.get(i + 1)?.clone();
+    args.drain(i..i + 2);
+    Some(value)
+}
+
+/// Take `--sensor <name>` and `--dpi <n>` out of the arguments (the sensor
+/// may also come from `FINGERPRINT_SENSOR`) and activate them
+pub fn init(args: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dpi) = take_flag(args, "--dpi") {
+        let dpi: u32 = dpi.parse().map_err(|_| format!("Invalid --dpi value '{}'", dpi))?;
+        if dpi == 0 {
+            return Err("--dpi must be positive".into());
+        }
+        sensor::set_resolution_override(dpi);
+    }
+    let chosen = take_flag(args, "--sensor");
+    let Some(name) = chosen.or_else(|| std::env::var(SENSOR_ENV).ok().filter(|n| !n.is_empty())) else {
+        return Ok(());
+    };
+    if name == SensorProfile::reference().name {
+        return Ok(());
+    }
+
+    let profile = load_profiles()?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("Unknown sensor profile '{}' (see {})", name, profiles_path().display()))?;
+    sensor::set_active(profile);
+    Ok(())
+}
+
+/// `calibrate-sensor <name> <image>... [--dpi <n>]` (`--dpi` was taken by `init`)
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (name, images) = args.split_first().ok_or("A profile name is required")?;
+    if images.is_empty() {
+        return Err("At least one sample image is required".into());
+    }
+    if *name == SensorProfile::reference().name {
+        return Err("The reference profile cannot be recalibrated".into());
+    }
+
+    say_tr!("calibration.title");
+    say!("{}", "─".repeat(70));
+    let mut samples = Vec::new();
+    let mut metadata_dpi = None;
+    for path in images {
+        let (sample, recorded_dpi) = ImageSource::from(path.as_str()).decode()?;
+        metadata_dpi = metadata_dpi.or(recorded_dpi);
+        samples.push(sample);
+        say_tr!("calibration.sample", path);
+    }
+    let dpi = sensor::resolution_override().or(metadata_dpi).unwrap_or(REFERENCE_DPI);
+
+    let profile = SensorProfile::calibrate(name, &samples, dpi)?;
+    say_tr!(
+        "calibration.levels",
+        profile.contrast.black_point,
+        profile.contrast.white_point,
+        format!("{:.2}", profile.contrast.gamma),
+        profile.resolution_dpi
+    );
+    say_tr!(
+        "calibration.preprocessing",
+        profile.preprocessing.equalize,
+        format!("{:.1}", profile.preprocessing.blur_sigma)
+    );
+    let enhance = &profile.preprocessing.enhance;
+    say_tr!("calibration.enhancement", enhance.segment, enhance.clahe, enhance.binarize);
+
+    let mut profiles = load_profiles()?;
+    profiles.retain(|p| p.name != profile.name);
+    profiles.push(profile);
+    let path = profiles_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&profiles)?)?;
+    say_tr!("calibration.written", path.display(), name);
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Live capture from a fingerprint sensor (`capture` feature).
+//!
+//! Instead of a saved image, `register`, `verify` and the other image
+//! arguments accept `live:<device>`: `live:fprint` takes frames from the
+//! first libfprint sensor, `live:/dev/video0` from a V4L2 (UVC) camera.
+//! Frames are taken until one reaches the minimum capture quality (see
+//! capture_quality.rs), at most `MAX_CAPTURE_FRAMES`, and the best one is
+//! written as a single PNG, its resolution included, which the extraction
+//! pipeline reads like any other capture (see image_source.rs). The file
+//! goes to a private directory of the command (`CaptureDir`), removed once
+//! the command has extracted its templates: captures for enrollment never
+//! land in the agent's spool, where they would be taken as verify probes.
+//! Built without the feature, live devices are refused.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::capture_quality::{self, CaptureQuality};
+use crate::feature_extraction::load_capture;
+use crate::image_source::{self, ImageSource};
+
+/// Prefix of image arguments naming a live device
+pub const LIVE_PREFIX: &str = "live:";
+
+/// Frames taken before giving up on reaching the minimum quality
+pub const MAX_CAPTURE_FRAMES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureDevice {
+    Fprint,                 // First sensor libfprint finds
+    Camera(String),         // V4L2 device node, e.g. /dev/video0
+}
+
+impl CaptureDevice {
+    /// Device of a `live:<device>` argument; None for image paths
+    pub fn from_arg(arg: &str) -> Option<Result<Self, String>> {
+        let device = arg.strip_prefix(LIVE_PREFIX)?;
+        Some(match device {
+            "fprint" => Ok(CaptureDevice::Fprint),
+            path if path.starts_with("/dev/") => Ok(CaptureDevice::Camera(path.to_string())),
+            _ => Err(format!("Unknown capture device '{}' (fprint or a /dev/video* node)", device)),
+        })
+    }
+}
+
+/// Best frame of an auto-capture
+pub struct Captured {
+    pub frame: ImageSource,
+    pub quality: CaptureQuality,
+    pub frames: usize,                  // Frames taken, the best one included
+}
+
+/// Take frames until one scores `min_quality`, at most `max_frames`
+pub fn auto_capture<F>(mut next_frame: F, min_quality: u8, max_frames: usize) -> Result<Captured, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Result<ImageSource, Box<dyn std::error::Error>>,
+{
+    let mut best: Option<(ImageSource, CaptureQuality)> = None;
+    let mut frames = 0;
+    while frames < max_frames {
+        frames += 1;
+        let frame = next_frame()?;
+        let (img, _) = load_capture(&frame)?;
+        let quality = capture_quality::assess(&img);
+        say!("📸 Frame {}: quality {}/100", frames, quality.score);
+        let good = quality.score >= min_quality;
+        if best.as_ref().is_none_or(|(_, b)| quality.score > b.score) {
+            best = Some((frame, quality));
+        }
+        if good {
+            break;
+        }
+    }
+    let (frame, quality) = best.ok_or("No frame captured")?;
+    capture_quality::check_min_quality(&quality, min_quality)
+        .map_err(|e| format!("None of {} frames was good enough. {}", frames, e))?;
+    Ok(Captured { frame, quality, frames })
+}
+
+/// Directory of one command's live captures, readable by its owner only and
+/// removed with everything in it when dropped
+pub struct CaptureDir {
+    path: PathBuf,
+}
+
+impl CaptureDir {
+    pub fn create() -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("fingerprint-capture-{}-{:016x}", std::process::id(), rand::random::<u64>()));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        // Fails if the path exists: a directory someone else created is never used
+        builder.create(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for CaptureDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Capture from `device` and write the frame to `dir` as `<stem>.png`
+pub fn capture_to(device: &CaptureDevice, min_quality: u8, dir: &CaptureDir, stem: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut source = backend::open(device)?;
+    let captured = auto_capture(|| source.next_frame(), min_quality, MAX_CAPTURE_FRAMES)?;
+    let (img, dpi) = captured.frame.decode()?;
+    let path = dir.path().join(format!("{}.png", stem));
+    image_source::save_png(&img, dpi, &path)?;
+    say!("📸 Captured after {} frame(s), quality {}/100", captured.frames, captured.quality.score);
+    Ok(path)
+}
+
+/// Frames of an opened device
+trait FrameSource {
+    fn next_frame(&mut self) -> Result<ImageSource, Box<dyn std::error::Error>>;
+}
+
+#[cfg(feature = "capture")]
+mod backend {
+    use super::{CaptureDevice, FrameSource};
+    use crate::image_source::ImageSource;
+    use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+    use std::ptr;
+    use v4l::buffer::Type;
+    use v4l::io::mmap::Stream;
+    use v4l::io::traits::CaptureStream;
+    use v4l::video::Capture;
+    use v4l::{Device, FourCC};
+
+    pub fn open(device: &CaptureDevice) -> Result<Box<dyn FrameSource>, Box<dyn std::error::Error>> {
+        match device {
+            CaptureDevice::Fprint => Ok(Box::new(Fprint::open()?)),
+            CaptureDevice::Camera(path) => Ok(Box::new(Camera::open(path)?)),
+        }
+    }
+
+    // ---- libfprint 2 (C API) ----
+
+    #[repr(C)]
+    struct GPtrArray {
+        pdata: *mut *mut c_void,
+        len: c_uint,
+    }
+
+    #[repr(C)]
+    struct GError {
+        domain: u32,
+        code: c_int,
+        message: *mut c_char,
+    }
+
+    #[link(name = "fprint-2")]
+    extern "C" {
+        fn fp_context_new() -> *mut c_void;
+        fn fp_context_get_devices(context: *mut c_void) -> *mut GPtrArray;
+        fn fp_device_get_name(device: *mut c_void) -> *const c_char;
+        fn fp_device_open_sync(device: *mut c_void, cancellable: *mut c_void, error: *mut *mut GError) -> c_int;
+        fn fp_device_close_sync(device: *mut c_void, cancellable: *mut c_void, error: *mut *mut GError) -> c_int;
+        fn fp_device_capture_sync(device: *mut c_void, wait_for_finger: c_int, cancellable: *mut c_void, error: *mut *mut GError) -> *mut c_void;
+        fn fp_image_get_width(image: *mut c_void) -> c_uint;
+        fn fp_image_get_height(image: *mut c_void) -> c_uint;
+        fn fp_image_get_ppmm(image: *mut c_void) -> f64;
+        fn fp_image_get_data(image: *mut c_void, len: *mut usize) -> *const u8;
+    }
+
+    #[link(name = "gobject-2.0")]
+    extern "C" {
+        fn g_object_unref(object: *mut c_void);
+    }
+
+    #[link(name = "glib-2.0")]
+    extern "C" {
+        fn g_error_free(error: *mut GError);
+    }
+
+    /// Message of a GError, which is freed
+    unsafe fn take_error(error: *mut GError, action: &str) -> String {
+        if error.is_null() {
+            return format!("libfprint: {} failed", action);
+        }
+        let message = CStr::from_ptr((*error).message).to_string_lossy().into_owned();
+        g_error_free(error);
+        format!("libfprint: {} failed: {}", action, message)
+    }
+
+    struct Fprint {
+        context: *mut c_void,
+        device: *mut c_void,    // Owned by the context
+    }
+
+    impl Fprint {
+        fn open() -> Result<Self, Box<dyn std::error::Error>> {
+            unsafe {
+                let context = fp_context_new();
+                let devices = fp_context_get_devices(context);
+                if devices.is_null() || (*devices).len == 0 {
+                    g_object_unref(context);
+                    return Err("libfprint found no fingerprint sensor".into());
+                }
+                let device = *(*devices).pdata;
+                let mut error = ptr::null_mut();
+                if fp_device_open_sync(device, ptr::null_mut(), &mut error) == 0 {
+                    g_object_unref(context);
+                    return Err(take_error(error, "opening the sensor").into());
+                }
+                say!("🖐️  Sensor: {}", CStr::from_ptr(fp_device_get_name(device)).to_string_lossy());
+                Ok(Fprint { context, device })
+            }
+        }
+    }
+
+    impl FrameSource for Fprint {
+        fn next_frame(&mut self) -> Result<ImageSource, Box<dyn std::error::Error>> {
+            say!("👆 Place your finger on the sensor");
+            unsafe {
+                let mut error = ptr::null_mut();
+                let image = fp_device_capture_sync(self.device, 1, ptr::null_mut(), &mut error);
+                if image.is_null() {
+                    return Err(take_error(error, "capture").into());
+                }
+                let (width, height) = (fp_image_get_width(image), fp_image_get_height(image));
+                let ppmm = fp_image_get_ppmm(image);
+                let mut len = 0usize;
+                let data = fp_image_get_data(image, &mut len);
+                let pixels = std::slice::from_raw_parts(data, len).to_vec();
+                g_object_unref(image);
+                let dpi = (ppmm > 0.0).then(|| (ppmm * 25.4).round() as u32);
+                Ok(ImageSource::Raw { pixels, width, height, dpi })
+            }
+        }
+    }
+
+    impl Drop for Fprint {
+        fn drop(&mut self) {
+            unsafe {
+                let mut error = ptr::null_mut();
+                if fp_device_close_sync(self.device, ptr::null_mut(), &mut error) == 0 && !error.is_null() {
+                    g_error_free(error);
+                }
+                g_object_unref(self.context);
+            }
+        }
+    }
+
+    // ---- V4L2 camera ----
+
+    struct Camera {
+        stream: Stream<'static>,
+        width: u32,
+        height: u32,
+        stride: u32,
+        yuyv: bool,     // Luma interleaved with chroma (YUYV) rather than plain GREY
+    }
+
+    impl Camera {
+        fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+            let device = Device::with_path(path)?;
+            let mut format = device.format()?;
+            format.fourcc = FourCC::new(b"GREY");
+            let format = match device.set_format(&format) {
+                Ok(set) if set.fourcc == FourCC::new(b"GREY") => set,
+                _ => {
+                    format.fourcc = FourCC::new(b"YUYV");
+                    let set = device.set_format(&format)?;
+                    if set.fourcc != FourCC::new(b"YUYV") {
+                        return Err(format!("{} offers neither GREY nor YUYV frames", path).into());
+                    }
+                    set
+                }
+            };
+            let yuyv = format.fourcc == FourCC::new(b"YUYV");
+            say!("📷 Camera {}: {}×{} {}", path, format.width, format.height, if yuyv { "YUYV" } else { "GREY" });
+            let stream = Stream::with_buffers(&device, Type::VideoCapture, 4)?;
+            Ok(Camera { stream, width: format.width, height: format.height, stride: format.stride, yuyv })
+        }
+    }
+
+    impl FrameSource for Camera {
+        fn next_frame(&mut self) -> Result<ImageSource, Box<dyn std::error::Error>> {
+            let (buffer, _) = self.stream.next()?;
+            let (step, stride) = (if self.yuyv { 2 } else { 1 }, self.stride as usize);
+            if buffer.len() < stride * self.height as usize {
+                return Err(format!("Short camera frame: {} bytes", buffer.len()).into());
+            }
+            let pixels = (0..self.height as usize)
+                .flat_map(|y| (0..self.width as usize).map(move |x| y * stride + x * step))
+                .map(|i| buffer[i])
+                .collect();
+            Ok(ImageSource::Raw { pixels, width: self.width, height: self.height, dpi: None })
+        }
+    }
+}
+
+#[cfg(not(feature = "capture"))]
+mod backend {
+    use super::{CaptureDevice, FrameSource};
+
+    pub fn open(_device: &CaptureDevice) -> Result<Box<dyn FrameSource>, Box<dyn std::error::Error>> {
+        Err("Live capture needs a client built with the `capture` feature (cargo build --features capture)".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn frame(img: GrayImage) -> ImageSource {
+        let (width, height) = img.dimensions();
+        ImageSource::Raw { pixels: img.into_raw(), width, height, dpi: None }
+    }
+
+    #[test]
+    fn live_arguments_name_devices() {
+        assert_eq!(CaptureDevice::from_arg("live:fprint"), Some(Ok(CaptureDevice::Fprint)));
+        assert_eq!(CaptureDevice::from_arg("live:/dev/video2"), Some(Ok(CaptureDevice::Camera("/dev/video2".into()))));
+        assert!(matches!(CaptureDevice::from_arg("live:webcam"), Some(Err(_))));
+        assert_eq!(CaptureDevice::from_arg("finger.tif"), None);
+    }
+
+    #[test]
+    fn capture_dirs_are_private_and_removed_with_their_files() {
+        let dir = CaptureDir::create().unwrap();
+        let path = dir.path().to_path_buf();
+        std::fs::write(path.join("live-0.png"), b"frame").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        let other = CaptureDir::create().unwrap();
+        assert_ne!(other.path(), path);
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn auto_capture_stops_at_the_first_good_frame() {
+        let blank = || frame(GrayImage::from_pixel(128, 128, Luma([200])));
+        let ridges = || {
+            let img = GrayImage::from_fn(128, 128, |x, _| {
+                Luma([(128.0 + 90.0 * (2.0 * std::f32::consts::PI * x as f32 / 8.0).cos()) as u8])
+            });
+            frame(img)
+        };
+        let mut taken = 0;
+        let captured = auto_capture(
+            || {
+                taken += 1;
+                Ok(if taken < 3 { blank() } else { ridges() })
+            },
+            60,
+            10,
+        )
+        .unwrap();
+        assert_eq!((captured.frames, taken), (3, 3));
+        assert!(captured.quality.score >= 60);
+
+        let mut never = 0;
+        assert!(auto_capture(|| { never += 1; Ok(blank()) }, 60, 4).is_err());
+        assert_eq!(never, 4);
+    }
+}
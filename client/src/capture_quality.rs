@@ -0,0 +1,114 @@
+//! Quality score of a capture, before anything is extracted from it.
+//!
+//! Smudged, faint or badly placed captures still produce templates, and
+//! enrolling one only shows up later as failed verifications. The score
+//! combines three measures of the normalized image, each 0..1:
+//! the foreground area (blocks with texture, see enhance.rs), the contrast
+//! of that foreground, and the ridge clarity (foreground blocks with a ridge
+//! spacing in the fingerprint range, see `ridge_field`). Enrollment refuses
+//! captures scoring below `FINGERPRINT_MIN_CAPTURE_QUALITY` and records the
+//! score with the template. Unlike the template report in
+//! shared/src/quality.rs it does not depend on the extractor.
+
+use image::GrayImage;
+
+use crate::enhance::{block_contrast, foreground_threshold, SEGMENT_BLOCK};
+use crate::feature_extraction::ridge_field;
+
+/// Captures below this score are refused at enrollment unless configured otherwise
+pub const DEFAULT_MIN_CAPTURE_QUALITY: u8 = 40;
+
+/// Foreground deviation counted as full contrast (grey levels)
+const FULL_CONTRAST_STDDEV: f32 = 48.0;
+
+/// Weights of ridge clarity, contrast and foreground area in the score
+const WEIGHTS: (f32, f32, f32) = (0.4, 0.3, 0.3);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureQuality {
+    pub score: u8,                      // 0..100
+    pub ridge_clarity: f32,             // Foreground blocks with a ridge spacing, 0..1
+    pub contrast: f32,                  // Mean foreground deviation over `FULL_CONTRAST_STDDEV`, 0..1
+    pub foreground: f32,                // Share of blocks holding texture, 0..1
+}
+
+/// Score a capture already mapped onto the reference sensor and density
+pub fn assess(img: &GrayImage) -> CaptureQuality {
+    let contrast = block_contrast(img);
+    let threshold = foreground_threshold(&contrast);
+    let is_foreground: Vec<bool> = contrast.iter().map(|&c| c > 0.0 && c >= threshold).collect();
+    let foreground_blocks = is_foreground.iter().filter(|&&f| f).count();
+    if foreground_blocks == 0 {
+        return CaptureQuality { score: 0, ridge_clarity: 0.0, contrast: 0.0, foreground: 0.0 };
+    }
+
+    let foreground_contrast = contrast
+        .iter()
+        .zip(&is_foreground)
+        .filter(|(_, &f)| f)
+        .map(|(&c, _)| c)
+        .sum::<f32>()
+        / foreground_blocks as f32;
+
+    // The ridge field drops partial blocks at the right and bottom edges
+    let contrast_columns = img.width().div_ceil(SEGMENT_BLOCK) as usize;
+    let field_columns = (img.width() / SEGMENT_BLOCK) as usize;
+    let (mut ridged, mut considered) = (0usize, 0usize);
+    for (i, block) in ridge_field(img, SEGMENT_BLOCK).iter().enumerate() {
+        if is_foreground[(i / field_columns) * contrast_columns + i % field_columns] {
+            considered += 1;
+            ridged += block.frequency.is_some() as usize;
+        }
+    }
+
+    let ridge_clarity = ridged as f32 / considered.max(1) as f32;
+    let contrast = (foreground_contrast / FULL_CONTRAST_STDDEV).min(1.0);
+    let foreground = foreground_blocks as f32 / is_foreground.len() as f32;
+    let score = WEIGHTS.0 * ridge_clarity + WEIGHTS.1 * contrast + WEIGHTS.2 * foreground;
+    CaptureQuality { score: (score * 100.0).round() as u8, ridge_clarity, contrast, foreground }
+}
+
+pub fn check_min_quality(quality: &CaptureQuality, min: u8) -> Result<(), String> {
+    if quality.score >= min {
+        Ok(())
+    } else {
+        Err(format!(
+            "Capture quality {} is below the minimum of {} (ridge clarity {:.0}%, contrast {:.0}%, foreground {:.0}%); recapture the finger",
+            quality.score,
+            min,
+            quality.ridge_clarity * 100.0,
+            quality.contrast * 100.0,
+            quality.foreground * 100.0
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn clear_ridges_outscore_faint_and_blank_captures() {
+        // Ridges with an 8 px period over the left `share` of the image, paper elsewhere
+        let capture = |amplitude: f32, share: f32| {
+            GrayImage::from_fn(256, 256, |x, _| {
+                let ridge = (2.0 * std::f32::consts::PI * x as f32 / 8.0).cos();
+                Luma([if (x as f32) < 256.0 * share { (128.0 + amplitude * ridge) as u8 } else { 230 }])
+            })
+        };
+        let clear = assess(&capture(90.0, 1.0));
+        assert!(clear.score >= 90, "{:?}", clear);
+        assert_eq!(clear.ridge_clarity, 1.0);
+
+        let partial = assess(&capture(90.0, 0.25));
+        assert!((partial.foreground - 0.25).abs() < 0.01 && partial.score < clear.score, "{:?}", partial);
+        let faint = assess(&capture(8.0, 1.0));
+        assert!(faint.contrast < 0.2 && faint.score < clear.score, "{:?}", faint);
+
+        let blank = assess(&GrayImage::from_pixel(128, 128, Luma([200])));
+        assert_eq!(blank.score, 0);
+        assert!(check_min_quality(&blank, DEFAULT_MIN_CAPTURE_QUALITY).is_err());
+        assert!(check_min_quality(&clear, DEFAULT_MIN_CAPTURE_QUALITY).is_ok());
+    }
+}
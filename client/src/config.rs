@@ -0,0 +1,169 @@
+//! Client configuration file (`fingerprint.toml`).
+//!
+//! Read once, from the working directory or the file `FINGERPRINT_CONFIG`
+//! names. Every key is optional and a missing file is an empty one, so a
+//! client without it behaves as before. The environment variables the
+//! client already read still win over the file, and a few new ones
+//! override single values. The match threshold is the server's policy; a
+//! stricter one is only sent when `FINGERPRINT_MATCH_THRESHOLD` asks for it.
+//!
+//! ```toml
+//! [paths]
+//! exchange = "https://auth.example.org"   # FINGERPRINT_EXCHANGE (or --server-url)
+//! data = "/var/lib/fingerprint/data"      # FINGERPRINT_DATA_DIR
+//! database = "/srv/database"              # FINGERPRINT_DATABASE_DIR; where tune-threshold --apply writes
+//!
+//! [fhe]
+//! parameter_set = "message2_carry2"       # FINGERPRINT_PARAMETER_SET; advise-params --apply still wins
+//!
+//! [logging]
+//! filter = "shared=debug"                 # FINGERPRINT_LOG (see shared::logging)
+//! ```
+
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use shared::ParameterSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Configuration file to read instead of `fingerprint.toml`
+pub const CONFIG_ENV: &str = "FINGERPRINT_CONFIG";
+const CONFIG_PATH: &str = "fingerprint.toml";
+
+const DATA_DIR_ENV: &str = "FINGERPRINT_DATA_DIR";
+const DATABASE_DIR_ENV: &str = "FINGERPRINT_DATABASE_DIR";
+const PARAMETER_SET_ENV: &str = "FINGERPRINT_PARAMETER_SET";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub paths: PathsConfig,
+    pub fhe: FheConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub exchange: String,   // Directory, s3://, http(s):// or grpc:// exchange
+    pub data: String,       // Working files of register and verify
+    pub database: String,   // The server's database directory, when it is shared
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            exchange: "../exchange".to_string(),
+            data: "../data".to_string(),
+            database: "../database".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FheConfig {
+    pub parameter_set: Option<ParameterSet>,        // For keys generated from now on (None = tfhe's default)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub filter: Option<String>,                     // `RUST_LOG`-style directives (default: warnings only)
+}
+
+impl Config {
+    /// The configuration file with the environment applied over it; a
+    /// missing file gives the defaults, an invalid one is reported and ignored
+    pub fn load() -> Self {
+        let path = std::env::var(CONFIG_ENV).unwrap_or_else(|_| CONFIG_PATH.to_string());
+        let mut config = if Path::new(&path).exists() {
+            match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| Self::parse(&data))
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("⚠️  Invalid configuration file {} ({}), using defaults", path, e);
+                    Self::default()
+                }
+            }
+        } else {
+            Self::default()
+        };
+        config.apply_env(|name| std::env::var(name).ok());
+        config
+    }
+
+    pub fn parse(data: &str) -> Result<Self, String> {
+        toml::from_str(data).map_err(|e| e.to_string())
+    }
+
+    /// Override values with the ones `var` finds; an unknown parameter set is reported and skipped
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(dir) = var(DATA_DIR_ENV) {
+            self.paths.data = dir;
+        }
+        if let Some(dir) = var(DATABASE_DIR_ENV) {
+            self.paths.database = dir;
+        }
+        if let Some(value) = var(PARAMETER_SET_ENV) {
+            let parsed: Result<ParameterSet, serde::de::value::Error> =
+                ParameterSet::deserialize(value.trim().into_deserializer());
+            match parsed {
+                Ok(params) => self.fhe.parameter_set = Some(params),
+                Err(e) => eprintln!("⚠️  Invalid {} ({}), ignored", PARAMETER_SET_ENV, e),
+            }
+        }
+    }
+
+    /// Where `tune-threshold --apply` records thresholds unless `--thresholds` names a file
+    pub fn thresholds_path(&self) -> PathBuf {
+        Path::new(&self.paths.database).join("thresholds.json")
+    }
+}
+
+/// The client's configuration, loaded on first use
+pub fn get() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(Config::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_keep_the_defaults() {
+        let config = Config::parse("[paths]\ndata = \"/srv/data\"\n").unwrap();
+        assert_eq!(config.paths.data, "/srv/data");
+        assert_eq!(config.paths.exchange, PathsConfig::default().exchange);
+        assert_eq!(config.fhe.parameter_set, None);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("[paths]\nexchnage = \"x\"\n").is_err());
+        // No match threshold in the file: the server's policy decides
+        assert!(Config::parse("[matching]\nthreshold_bits = 190\n").is_err());
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() {
+        let mut config = Config::parse("[paths]\ndata = \"/srv/data\"\n[fhe]\nparameter_set = \"message1_carry1\"\n").unwrap();
+        config.apply_env(|name| match name {
+            DATA_DIR_ENV => Some("/tmp/data".into()),
+            DATABASE_DIR_ENV => Some("/srv/db".into()),
+            PARAMETER_SET_ENV => Some(" message2_carry2 ".into()),
+            _ => None,
+        });
+        assert_eq!(config.paths.data, "/tmp/data");
+        assert_eq!(config.thresholds_path(), Path::new("/srv/db").join("thresholds.json"));
+        assert_eq!(config.fhe.parameter_set, Some(ParameterSet::Message2Carry2));
+    }
+
+    #[test]
+    fn unknown_parameter_set_is_ignored() {
+        let mut config = Config::parse("[fhe]\nparameter_set = \"message1_carry1\"\n").unwrap();
+        config.apply_env(|name| (name == PARAMETER_SET_ENV).then(|| "message9_carry9".to_string()));
+        assert_eq!(config.fhe.parameter_set, Some(ParameterSet::Message1Carry1));
+    }
+}
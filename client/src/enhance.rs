@@ -0,0 +1,233 @@
+//! Image enhancement before feature extraction.
+//!
+//! Raw captures of some sensors (the FVC TIFFs in particular) mix a bright
+//! background with ridge areas of very different contrast. `preprocess` can
+//! segment the finger from the background, equalize contrast locally
+//! (CLAHE) and binarize ridges against their neighbourhood. The steps are
+//! enabled per sensor profile (see sensor.rs): calibration recommends
+//! segmentation and CLAHE from the samples; binarization discards the grey
+//! levels the texture features use and is only applied when a profile asks
+//! for it.
+
+use image::{GrayImage, Luma};
+use serde::{Deserialize, Serialize};
+
+/// Side of the blocks segmentation classifies (pixels)
+pub const SEGMENT_BLOCK: u32 = 16;
+
+/// A block is foreground when its grey-level deviation reaches this share of
+/// the 90th percentile over all blocks
+const SEGMENT_STDDEV_FRACTION: f32 = 0.25;
+
+/// CLAHE tile grid (per side) and clip limit (multiples of the mean bin count)
+const CLAHE_TILES: u32 = 8;
+const CLAHE_CLIP_LIMIT: f32 = 4.0;
+
+/// Radius of the window a pixel is binarized against
+const BINARIZE_RADIUS: u32 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct EnhanceOptions {
+    #[serde(default)]
+    pub segment: bool,                  // Flatten the background outside the finger
+    #[serde(default)]
+    pub clahe: bool,                    // Contrast-limited adaptive histogram equalization
+    #[serde(default)]
+    pub binarize: bool,                 // Ridges black, valleys white, against the local mean
+}
+
+/// Run the enabled enhancement steps: segmentation, CLAHE, binarization
+pub fn preprocess(img: &GrayImage, options: &EnhanceOptions) -> GrayImage {
+    let foreground = options.segment.then(|| foreground_mask(img));
+    let mut out = match &foreground {
+        Some(mask) => fill_background(img, mask),
+        None => img.clone(),
+    };
+    if options.clahe {
+        out = clahe(&out);
+    }
+    if options.binarize {
+        out = binarize(&out);
+    }
+    // The local steps may have put texture back into the flat background
+    match &foreground {
+        Some(mask) if options.clahe || options.binarize => fill_background(&out, mask),
+        _ => out,
+    }
+}
+
+/// Grey-level standard deviation of each `SEGMENT_BLOCK` block, row by row
+pub fn block_contrast(img: &GrayImage) -> Vec<f32> {
+    let blocks_x = img.width().div_ceil(SEGMENT_BLOCK);
+    let blocks_y = img.height().div_ceil(SEGMENT_BLOCK);
+    let mut contrast = Vec::with_capacity((blocks_x * blocks_y) as usize);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let values: Vec<f32> = (by * SEGMENT_BLOCK..((by + 1) * SEGMENT_BLOCK).min(img.height()))
+                .flat_map(|y| (bx * SEGMENT_BLOCK..((bx + 1) * SEGMENT_BLOCK).min(img.width())).map(move |x| (x, y)))
+                .map(|(x, y)| img.get_pixel(x, y)[0] as f32)
+                .collect();
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+            contrast.push(variance.sqrt());
+        }
+    }
+    contrast
+}
+
+/// Deviation separating foreground blocks from the background
+pub fn foreground_threshold(contrast: &[f32]) -> f32 {
+    let mut sorted = contrast.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let p90 = sorted.get(sorted.len() * 9 / 10).copied().unwrap_or(0.0);
+    p90 * SEGMENT_STDDEV_FRACTION
+}
+
+/// Whether each pixel lies in a foreground block; everything when no block has texture
+fn foreground_mask(img: &GrayImage) -> Vec<bool> {
+    let contrast = block_contrast(img);
+    let threshold = foreground_threshold(&contrast);
+    let blocks_x = img.width().div_ceil(SEGMENT_BLOCK);
+    let blocks: Vec<bool> = contrast.iter().map(|&c| c > 0.0 && c >= threshold).collect();
+    if !blocks.contains(&true) {
+        return vec![true; (img.width() * img.height()) as usize];
+    }
+    (0..img.height())
+        .flat_map(|y| (0..img.width()).map(move |x| (x, y)))
+        .map(|(x, y)| blocks[((y / SEGMENT_BLOCK) * blocks_x + x / SEGMENT_BLOCK) as usize])
+        .collect()
+}
+
+/// Background pixels set to the mean grey level of the foreground
+fn fill_background(img: &GrayImage, mask: &[bool]) -> GrayImage {
+    let (sum, count) = img
+        .pixels()
+        .zip(mask)
+        .filter(|(_, &fg)| fg)
+        .fold((0u64, 0u64), |(sum, count), (p, _)| (sum + p[0] as u64, count + 1));
+    let fill = (sum / count.max(1)) as u8;
+    let width = img.width();
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        if mask[(y * width + x) as usize] { *img.get_pixel(x, y) } else { Luma([fill]) }
+    })
+}
+
+/// Contrast-limited adaptive histogram equalization: a clipped equalization
+/// per tile, blended bilinearly between tile centres
+fn clahe(img: &GrayImage) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let tiles_x = CLAHE_TILES.min(width).max(1);
+    let tiles_y = CLAHE_TILES.min(height).max(1);
+    let tile_w = width.div_ceil(tiles_x);
+    let tile_h = height.div_ceil(tiles_y);
+
+    let mut luts = Vec::with_capacity((tiles_x * tiles_y) as usize);
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let mut histogram = [0u32; 256];
+            for y in ty * tile_h..((ty + 1) * tile_h).min(height) {
+                for x in tx * tile_w..((tx + 1) * tile_w).min(width) {
+                    histogram[img.get_pixel(x, y)[0] as usize] += 1;
+                }
+            }
+            luts.push(clipped_equalization(&histogram));
+        }
+    }
+
+    // Position of a pixel between the centres of its neighbouring tiles
+    let neighbours = |pos: u32, size: u32, tiles: u32| {
+        let t = (pos as f32 + 0.5) / size as f32 - 0.5;
+        let low = t.floor().clamp(0.0, (tiles - 1) as f32) as u32;
+        let high = (low + 1).min(tiles - 1);
+        (low, high, (t - low as f32).clamp(0.0, 1.0))
+    };
+    GrayImage::from_fn(width, height, |x, y| {
+        let value = img.get_pixel(x, y)[0] as usize;
+        let (x0, x1, fx) = neighbours(x, tile_w, tiles_x);
+        let (y0, y1, fy) = neighbours(y, tile_h, tiles_y);
+        let lut = |tx: u32, ty: u32| luts[(ty * tiles_x + tx) as usize][value] as f32;
+        let top = lut(x0, y0) * (1.0 - fx) + lut(x1, y0) * fx;
+        let bottom = lut(x0, y1) * (1.0 - fx) + lut(x1, y1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round() as u8])
+    })
+}
+
+/// Equalization table of a histogram whose bins are clipped at `CLAHE_CLIP_LIMIT`,
+/// the excess spread evenly over all bins
+fn clipped_equalization(histogram: &[u32; 256]) -> [u8; 256] {
+    let total: u32 = histogram.iter().sum();
+    let limit = ((CLAHE_CLIP_LIMIT * total as f32 / 256.0).ceil() as u32).max(1);
+    let excess: u32 = histogram.iter().map(|&count| count.saturating_sub(limit)).sum();
+    let bonus = excess as f32 / 256.0;
+
+    let mut lut = [0u8; 256];
+    let mut seen = 0.0f32;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count.min(limit) as f32 + bonus;
+        lut[value] = (seen * 255.0 / total.max(1) as f32).round().min(255.0) as u8;
+    }
+    lut
+}
+
+/// Pixels above the mean of their `BINARIZE_RADIUS` window become white, the rest black
+fn binarize(img: &GrayImage) -> GrayImage {
+    let (width, height) = img.dimensions();
+    // Summed-area table, one row and column of zeros in front
+    let stride = width as usize + 1;
+    let mut integral = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row = 0u64;
+        for x in 0..width as usize {
+            row += img.get_pixel(x as u32, y as u32)[0] as u64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row;
+        }
+    }
+    GrayImage::from_fn(width, height, |x, y| {
+        let (left, top) = (x.saturating_sub(BINARIZE_RADIUS) as usize, y.saturating_sub(BINARIZE_RADIUS) as usize);
+        let right = (x + BINARIZE_RADIUS + 1).min(width) as usize;
+        let bottom = (y + BINARIZE_RADIUS + 1).min(height) as usize;
+        let sum = integral[bottom * stride + right] + integral[top * stride + left]
+            - integral[top * stride + right]
+            - integral[bottom * stride + left];
+        let count = ((right - left) * (bottom - top)) as u64;
+        let value = img.get_pixel(x, y)[0] as u64;
+        Luma([if value * count > sum { 255 } else { 0 }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ridges with an 8 px period on the left half (grey levels `low` to `high`),
+    /// blank paper on the right
+    fn capture(low: u8, high: u8) -> GrayImage {
+        let (mid, amplitude) = ((low as f32 + high as f32) / 2.0, (high as f32 - low as f32) / 2.0);
+        GrayImage::from_fn(256, 128, |x, _| {
+            let ridge = (2.0 * std::f32::consts::PI * x as f32 / 8.0).cos();
+            Luma([if x >= 128 { 230 } else { (mid + amplitude * ridge).round() as u8 }])
+        })
+    }
+
+    #[test]
+    fn segmentation_flattens_the_background() {
+        let img = capture(60, 180);
+        let segmented = preprocess(&img, &EnhanceOptions { segment: true, ..Default::default() });
+        assert_eq!(segmented.get_pixel(10, 10), img.get_pixel(10, 10));
+        assert_eq!(segmented.get_pixel(200, 10)[0], 120);  // Mean of the ridges
+        assert_eq!(preprocess(&img, &EnhanceOptions::default()), img);
+    }
+
+    #[test]
+    fn clahe_and_binarization_restore_faint_ridges() {
+        let faint = capture(120, 132);
+        let options = EnhanceOptions { segment: true, clahe: true, binarize: false };
+        let enhanced = preprocess(&faint, &options);
+        let spread = |img: &GrayImage| img.get_pixel(64, 40)[0].abs_diff(img.get_pixel(68, 40)[0]);
+        assert!(spread(&enhanced) > 2 * spread(&faint));
+
+        let binary = preprocess(&faint, &EnhanceOptions { binarize: true, ..options });
+        assert_eq!(binary.get_pixel(64, 40)[0], 255);
+        assert_eq!(binary.get_pixel(68, 40)[0], 0);
+    }
+}
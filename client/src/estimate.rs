@@ -0,0 +1,91 @@
+//! `estimate <user_id>`: what a verification would cost before starting it.
+//!
+//! Upload size is measured by building a request with the real client key;
+//! queue wait and computation time come from the calibration the server
+//! publishes in `server_status.json`, falling back to the parameter
+//! advisor's estimate when the server hasn't completed a verification yet.
+
+use shared::ServerStatus;
+use std::fs;
+use std::time::Duration;
+
+use client::api::{self, TriviumTemplate};
+use client::say;
+
+use crate::{advisor, exchange, load_client_key, template_bits_for, SERVER_STATUS};
+
+pub fn run(user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    say!("📐 VERIFICATION ESTIMATE: {}", user_id);
+    say!("{}", "─".repeat(70));
+    
+    // Same shape as a real probe; the bit values don't change the size
+    let client_key = load_client_key()?;
+    let template_bits = template_bits_for(user_id)?;
+    let cipher = api::cipher_from_env()?;
+    let template = TriviumTemplate {
+        ciphertext: vec![false; template_bits],
+        key_bits: vec![false; cipher.key_bits()],
+        iv_bits: vec![false; cipher.iv_bits()],
+        cipher,
+    };
+    let request = api::build_verify_request(user_id, template, &client_key)?
+        .with_api_key(api::api_key_from_env());
+    let upload = serde_json::to_string_pretty(&request)?.len();
+    say!("📤 Upload size:      {:.1} KB", upload as f64 / 1024.0);
+    
+    let exchange = exchange()?;
+    let status: Option<ServerStatus> = exchange
+        .get(SERVER_STATUS)?
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    let Some(status) = status else {
+        say!("⚠️  Server status unavailable ({}); is the server running?", exchange.describe());
+        return Ok(());
+    };
+    
+    if let Err(e) = shared::template::negotiate(template_bits, &status.supported_template_bits) {
+        say!("⚠️  {}", e);
+    }
+    if let Err(e) = cipher.negotiate(&status.supported_ciphers) {
+        say!("⚠️  {}", e);
+    }
+    
+    let job_secs = match status.calibration.verify_job_secs {
+        Some(secs) => {
+            say!("🧮 Computation time: {} (average of {} verifications on the server)",
+                format_secs(secs), status.calibration.samples);
+            Some(secs)
+        }
+        None => {
+            let advised = fs::read_to_string(advisor::params_path())
+                .ok()
+                .and_then(|data| serde_json::from_str::<advisor::ParamsConfig>(&data).ok())
+                .and_then(|c| c.estimated_verify_secs)
+                .map(|secs| secs as f64);
+            match advised {
+                Some(secs) => say!("🧮 Computation time: ~{} (parameter advisor estimate, server not calibrated yet)", format_secs(secs)),
+                None => say!("🧮 Computation time: unknown (server has not completed a verification yet)"),
+            }
+            advised
+        }
+    };
+    
+    let waves = status.verify_queue_jobs();
+    say!("🚦 Queue:            {} running, {} waiting ({} slots)",
+        status.verify.running, status.verify.queued, status.max_concurrent_verify);
+    if waves == 0.0 {
+        say!("⏳ Queue wait:       none, a slot is free");
+    } else if let Some(secs) = job_secs {
+        say!("⏳ Queue wait:       ~{}", format_secs(waves * secs));
+    } else {
+        say!("⏳ Queue wait:       {} job(s) ahead of you", status.verify.running + status.verify.queued);
+    }
+    if let Some(secs) = job_secs {
+        say!("🏁 Expected result in ~{}", format_secs(waves * secs + secs));
+    }
+    Ok(())
+}
+
+fn format_secs(secs: f64) -> String {
+    let d = Duration::from_secs_f64(secs.max(0.0));
+    format!("{}m {:02}s", d.as_secs() / 60, d.as_secs() % 60)
+}
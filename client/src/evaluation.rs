@@ -0,0 +1,500 @@
+//! FAR/FRR evaluation over a fingerprint dataset, in the clear.
+//!
+//! `eval <dataset_dir> [--out <csv>] [--step <bits>]` extracts a template
+//! from every image the way register and verify do (same extractor, sensor
+//! profile and template length), then compares every pair by Hamming
+//! distance: impressions of the same finger are genuine pairs, those of
+//! different fingers impostor pairs. Files follow the FVC naming,
+//! `<finger>_<impression>.<ext>` (`101_1.tif`). For each threshold of the
+//! sweep the CSV holds the false accept rate (impostor pairs within the
+//! threshold) and the false reject rate (genuine pairs beyond it); the
+//! encrypted match decides the same `distance <= threshold`.
+//!
+//! With `FINGERPRINT_FUZZY` set, `eval` also measures the fuzzy sketch: a
+//! pair matches it exactly when no block of the code differs in more than t
+//! bits, so scoring pairs by their most differing block gives the FAR and
+//! FRR of every t, and the smallest t within the target FRR (default 1%) is
+//! recommended for `FINGERPRINT_FUZZY_ERRORS`.
+//!
+//! `tune-threshold <dataset_dir> [--target-far <percent>] [--roc <csv>] [--apply]`
+//! writes ROC and DET points of the same comparison and recommends the
+//! widest threshold whose FAR stays within the target (default 0.1%). With
+//! `--apply` it is recorded for the extractor's template parameters in the
+//! server's `thresholds.json`, which verifications then match against
+//! instead of the fixed 80% similarity.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use shared::fuzzy;
+use shared::template::{self, TunedThreshold};
+
+use crate::{api, matching};
+
+/// Extensions of the captures a dataset is read from (`.raw` with its `.ncm` sidecar)
+const IMAGE_EXTENSIONS: [&str; 8] = ["tif", "tiff", "png", "bmp", "jpg", "jpeg", "pgm", "raw"];
+
+/// Default CSV written next to the working directory
+pub const DEFAULT_OUTPUT: &str = "eval.csv";
+
+/// Default CSV of ROC/DET points
+pub const DEFAULT_ROC_OUTPUT: &str = "roc.csv";
+
+/// Default false accept rate `tune-threshold` aims for (percent)
+pub const DEFAULT_TARGET_FAR_PERCENT: f64 = 0.1;
+
+/// Default false reject rate the recommended fuzzy error capacity stays within (percent)
+pub const DEFAULT_TARGET_FRR_PERCENT: f64 = 1.0;
+
+/// Captures of a dataset, by finger, impressions in order
+pub fn scan_dataset(dir: &Path) -> io::Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut fingers: BTreeMap<String, Vec<(u32, PathBuf)>> = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_image = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        let name = path.file_stem().and_then(|s| s.to_str()).and_then(parse_name);
+        if let Some((finger, impression)) = name.filter(|_| is_image) {
+            fingers.entry(finger.to_string()).or_default().push((impression, path.clone()));
+        }
+    }
+    Ok(fingers
+        .into_iter()
+        .map(|(finger, mut impressions)| {
+            impressions.sort();
+            (finger, impressions.into_iter().map(|(_, path)| path).collect())
+        })
+        .collect())
+}
+
+/// Finger and impression of an FVC file stem (`101_1` → ("101", 1))
+fn parse_name(stem: &str) -> Option<(&str, u32)> {
+    let (finger, impression) = stem.rsplit_once('_')?;
+    Some((finger, impression.parse().ok()?)).filter(|_| !finger.is_empty())
+}
+
+/// Hamming distances of all genuine and impostor pairs, each sorted
+#[derive(Debug, Default, PartialEq)]
+pub struct Scores {
+    pub genuine: Vec<usize>,
+    pub impostor: Vec<usize>,
+}
+
+/// Compare every pair of templates, grouped by finger
+pub fn score_pairs(fingers: &[(String, Vec<Vec<bool>>)]) -> Scores {
+    score_pairs_by(fingers, matching::hamming_distance)
+}
+
+/// Every pair of templates scored by `distance`
+fn score_pairs_by(fingers: &[(String, Vec<Vec<bool>>)], distance: impl Fn(&[bool], &[bool]) -> usize) -> Scores {
+    let templates: Vec<(usize, &Vec<bool>)> = fingers
+        .iter()
+        .enumerate()
+        .flat_map(|(finger, (_, impressions))| impressions.iter().map(move |bits| (finger, bits)))
+        .collect();
+    let mut scores = Scores::default();
+    for (i, (finger_a, a)) in templates.iter().enumerate() {
+        for (finger_b, b) in &templates[i + 1..] {
+            let distance = distance(a, b);
+            if finger_a == finger_b {
+                scores.genuine.push(distance);
+            } else {
+                scores.impostor.push(distance);
+            }
+        }
+    }
+    scores.genuine.sort_unstable();
+    scores.impostor.sort_unstable();
+    scores
+}
+
+/// Errors at one threshold (most differing bits still accepted)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorRates {
+    pub threshold: usize,
+    pub false_accepts: usize,
+    pub impostor_pairs: usize,
+    pub false_rejects: usize,
+    pub genuine_pairs: usize,
+}
+
+impl ErrorRates {
+    pub fn at(scores: &Scores, threshold: usize) -> Self {
+        let accepted = |distances: &[usize]| distances.partition_point(|&d| d <= threshold);
+        Self {
+            threshold,
+            false_accepts: accepted(&scores.impostor),
+            impostor_pairs: scores.impostor.len(),
+            false_rejects: scores.genuine.len() - accepted(&scores.genuine),
+            genuine_pairs: scores.genuine.len(),
+        }
+    }
+
+    pub fn far(&self) -> f64 {
+        ratio(self.false_accepts, self.impostor_pairs)
+    }
+
+    pub fn frr(&self) -> f64 {
+        ratio(self.false_rejects, self.genuine_pairs)
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// Error rates from threshold 0 to `template_bits`, every `step` bits
+pub fn sweep(scores: &Scores, template_bits: usize, step: usize) -> Vec<ErrorRates> {
+    (0..=template_bits).step_by(step.max(1)).map(|threshold| ErrorRates::at(scores, threshold)).collect()
+}
+
+/// Threshold of the sweep where FAR and FRR are closest (the equal error rate)
+pub fn equal_error(rates: &[ErrorRates]) -> Option<&ErrorRates> {
+    rates.iter().min_by(|a, b| (a.far() - a.frr()).abs().total_cmp(&(b.far() - b.frr()).abs()))
+}
+
+/// Widest threshold whose FAR stays within `target_far`, so the lowest FRR at that FAR
+pub fn recommend(rates: &[ErrorRates], target_far: f64) -> Option<&ErrorRates> {
+    rates.iter().filter(|r| r.far() <= target_far).max_by_key(|r| r.threshold)
+}
+
+/// Error rates of a fuzzy sketch over GF(2^field_bits) for each bit error capacity t
+/// it can have, with the key bits each leaves: a pair matches when no block differs
+/// in more than t bits. Stops at the first t no genuine pair is rejected at.
+pub fn fuzzy_sweep(fingers: &[(String, Vec<Vec<bool>>)], template_bits: usize, field_bits: u32) -> Vec<(ErrorRates, usize)> {
+    let scores = score_pairs_by(fingers, |a, b| fuzzy::max_block_errors(a, b, field_bits));
+    let mut rates = Vec::new();
+    for errors in 1..=fuzzy::capacity(field_bits, template_bits) {
+        let at = ErrorRates::at(&scores, errors);
+        rates.push((at, fuzzy::key_bits(field_bits, errors, template_bits)));
+        if at.false_rejects == 0 {
+            break;
+        }
+    }
+    rates
+}
+
+/// Smallest bit error capacity whose FRR stays within `target_frr`, so the lowest FAR and longest key
+pub fn recommend_errors(rates: &[(ErrorRates, usize)], target_frr: f64) -> Option<&(ErrorRates, usize)> {
+    rates.iter().find(|(r, _)| r.frr() <= target_frr)
+}
+
+/// Standard normal quantile, the axis scale of DET curves (Acklam's rational
+/// approximation, relative error below 1.2e-9); None at 0 and 1
+fn probit(p: f64) -> Option<f64> {
+    const A: [f64; 6] = [-39.696_830_286_653_76, 220.946_098_424_520_5, -275.928_510_446_968_7, 138.357_751_867_269, -30.664_798_066_147_16, 2.506_628_277_459_239];
+    const B: [f64; 5] = [-54.476_098_798_224_06, 161.585_836_858_040_9, -155.698_979_859_886_6, 66.801_311_887_719_72, -13.280_681_552_885_72];
+    const C: [f64; 6] = [-0.007_784_894_002_430_293, -0.322_396_458_041_136_5, -2.400_758_277_161_838, -2.549_732_539_343_734, 4.374_664_141_464_968, 2.938_163_982_698_783];
+    const D: [f64; 4] = [0.007_784_695_709_041_462, 0.322_467_129_070_039_8, 2.445_134_137_142_996, 3.754_408_661_907_416];
+    const LOW: f64 = 0.024_25;
+    let poly = |coefficients: &[f64], x: f64| coefficients.iter().fold(0.0, |acc, c| acc * x + c);
+    let tail = |q: f64| poly(&C, q) / (poly(&D, q) * q + 1.0);
+    if !(p > 0.0 && p < 1.0) {
+        return None;
+    }
+    Some(if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        poly(&A, q * q) * q / (poly(&B, q * q) * q * q + 1.0)
+    })
+}
+
+/// ROC (FAR against TAR) and DET (FAR against FRR, also on normal deviate scale) points
+pub fn write_roc_csv(rates: &[ErrorRates], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "threshold_bits,far,tar,frr,far_deviate,frr_deviate")?;
+    let deviate = |p: f64| probit(p).map_or(String::new(), |z| format!("{:.4}", z));
+    for r in rates {
+        writeln!(
+            out,
+            "{},{:.6},{:.6},{:.6},{},{}",
+            r.threshold,
+            r.far(),
+            1.0 - r.frr(),
+            r.frr(),
+            deviate(r.far()),
+            deviate(r.frr())
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_csv(rates: &[ErrorRates], template_bits: usize, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "threshold_bits,threshold_fraction,far,frr,false_accepts,impostor_pairs,false_rejects,genuine_pairs")?;
+    for r in rates {
+        writeln!(
+            out,
+            "{},{:.4},{:.6},{:.6},{},{},{},{}",
+            r.threshold,
+            r.threshold as f64 / template_bits as f64,
+            r.far(),
+            r.frr(),
+            r.false_accepts,
+            r.impostor_pairs,
+            r.false_rejects,
+            r.genuine_pairs
+        )?;
+    }
+    Ok(())
+}
+
+/// Templates of every capture, grouped by finger
+type Fingers = Vec<(String, Vec<Vec<bool>>)>;
+
+/// Extract every capture of the dataset, grouped by finger
+fn extract_dataset(dir: &str, template_bits: usize) -> Result<Fingers, Box<dyn std::error::Error>> {
+    let dataset = scan_dataset(Path::new(dir))?;
+    let captures: usize = dataset.values().map(Vec::len).sum();
+    say!("🗂️  {} captures of {} fingers in {}", captures, dataset.len(), dir);
+    if dataset.values().all(|impressions| impressions.len() < 2) {
+        return Err("The dataset needs several impressions of a finger (<finger>_<impression>.<ext>)".into());
+    }
+
+    let mut fingers = Vec::new();
+    let mut failed = 0;
+    for (finger, paths) in dataset {
+        let mut impressions = Vec::new();
+        for path in &paths {
+            match api::extract_template_with(&path.to_string_lossy(), template_bits) {
+                Ok(bits) => impressions.push(bits),
+                Err(e) => {
+                    failed += 1;
+                    say!("⚠️  Skipped {}: {}", path.display(), e);
+                }
+            }
+        }
+        fingers.push((finger, impressions));
+    }
+    say!("🧬 {} templates of {} bits extracted, {} captures failed", captures - failed, template_bits, failed);
+    Ok(fingers)
+}
+
+/// Extract every capture of the dataset and compare all pairs
+fn score_dataset(dir: &str, template_bits: usize) -> Result<Scores, Box<dyn std::error::Error>> {
+    Ok(score_fingers(&extract_dataset(dir, template_bits)?))
+}
+
+fn score_fingers(fingers: &[(String, Vec<Vec<bool>>)]) -> Scores {
+    let scores = score_pairs(fingers);
+    say!("🔍 {} genuine and {} impostor pairs compared", scores.genuine.len(), scores.impostor.len());
+    scores
+}
+
+/// FAR and FRR of each error capacity of the configured fuzzy sketch, and the one to set
+fn report_fuzzy(fingers: &[(String, Vec<Vec<bool>>)], template_bits: usize, field_bits: u32) {
+    say!("🧬 Fuzzy sketch over GF(2^{}), blocks of up to {} bits:", field_bits, (1 << field_bits) - 1);
+    let rates = fuzzy_sweep(fingers, template_bits, field_bits);
+    for (r, key_bits) in &rates {
+        say!("   t = {:>3}: {:>5}-bit key, FAR {:.4}%, FRR {:.4}%", r.threshold, key_bits, r.far() * 100.0, r.frr() * 100.0);
+    }
+    match recommend_errors(&rates, DEFAULT_TARGET_FRR_PERCENT / 100.0) {
+        Some((r, key_bits)) => say!(
+            "🎯 {}={} keeps the FRR within {}% ({:.4}%), FAR {:.4}%, {}-bit key",
+            api::FUZZY_ERRORS_ENV,
+            r.threshold,
+            DEFAULT_TARGET_FRR_PERCENT,
+            r.frr() * 100.0,
+            r.far() * 100.0,
+            key_bits
+        ),
+        None => say!(
+            "⚠️  No error capacity over GF(2^{}) keeps the FRR within {}%; try a larger {}",
+            field_bits,
+            DEFAULT_TARGET_FRR_PERCENT,
+            api::FUZZY_ENV
+        ),
+    }
+}
+
+/// `eval <dataset_dir> [--out <csv>] [--step <bits>]`
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = args.first().ok_or("A dataset directory is required")?;
+    let flag = |name: &str| args.windows(2).find(|w| w[0] == name).map(|w| w[1].as_str());
+    let out = PathBuf::from(flag("--out").unwrap_or(DEFAULT_OUTPUT));
+    let step = match flag("--step") {
+        Some(step) => step.parse::<usize>().ok().filter(|&s| s > 0).ok_or(format!("Invalid --step value '{}'", step))?,
+        None => 1,
+    };
+    let template_bits = api::template_bits_from_env()?;
+
+    let fuzzy_field_bits = api::fuzzy_field_bits_from_env()?;
+
+    say!("📊 FAR/FRR EVALUATION");
+    say!("{}", "─".repeat(70));
+    let fingers = extract_dataset(dir, template_bits)?;
+    let scores = score_fingers(&fingers);
+    let rates = sweep(&scores, template_bits, step);
+    let mut file = io::BufWriter::new(fs::File::create(&out)?);
+    write_csv(&rates, template_bits, &mut file)?;
+    file.flush()?;
+
+    let policy = ErrorRates::at(&scores, template::match_threshold(template_bits));
+    say!(
+        "🎯 Policy threshold {} bits: FAR {:.4}%, FRR {:.4}%",
+        policy.threshold,
+        policy.far() * 100.0,
+        policy.frr() * 100.0
+    );
+    if let Some(eer) = equal_error(&rates) {
+        say!(
+            "⚖️  Equal error at {} bits ({:.3} of the template): FAR {:.4}%, FRR {:.4}%",
+            eer.threshold,
+            eer.threshold as f64 / template_bits as f64,
+            eer.far() * 100.0,
+            eer.frr() * 100.0
+        );
+    }
+    if let Some(field_bits) = fuzzy_field_bits {
+        report_fuzzy(&fingers, template_bits, field_bits);
+    }
+    say!("💾 Written to {}", out.display());
+    Ok(())
+}
+
+/// `tune-threshold <dataset_dir> [--target-far <percent>] [--roc <csv>] [--apply [--thresholds <path>]]`
+pub fn tune(args: &[String], thresholds_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = args.first().ok_or("A dataset directory is required")?;
+    let flag = |name: &str| args.windows(2).find(|w| w[0] == name).map(|w| w[1].as_str());
+    let target_percent = match flag("--target-far") {
+        Some(target) => target
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .ok()
+            .filter(|t| (0.0..100.0).contains(t))
+            .ok_or(format!("Invalid --target-far value '{}' (percent, below 100)", target))?,
+        None => DEFAULT_TARGET_FAR_PERCENT,
+    };
+    let target_far = target_percent / 100.0;
+    let roc_path = PathBuf::from(flag("--roc").unwrap_or(DEFAULT_ROC_OUTPUT));
+    let template_bits = api::template_bits_from_env()?;
+    let params = api::template_params(template_bits)?;
+
+    say!("🎚️  THRESHOLD TUNING");
+    say!("{}", "─".repeat(70));
+    let scores = score_dataset(dir, template_bits)?;
+    let rates = sweep(&scores, template_bits, 1);
+    let mut file = io::BufWriter::new(fs::File::create(&roc_path)?);
+    write_roc_csv(&rates, &mut file)?;
+    file.flush()?;
+    say!("💾 ROC/DET points written to {}", roc_path.display());
+
+    let measurable = (scores.impostor.len() as f64) * target_far >= 1.0;
+    if !measurable {
+        say!(
+            "⚠️  {} impostor pairs can't measure a FAR of {}%; the recommendation is optimistic",
+            scores.impostor.len(),
+            target_percent
+        );
+    }
+    let best = recommend(&rates, target_far)
+        .ok_or(format!("Even an exact match exceeds a FAR of {}% on this dataset", target_percent))?;
+    say!(
+        "🎯 Recommended threshold {} bits ({:.3} of the template, default {}): FAR {:.4}%, FRR {:.4}%",
+        best.threshold,
+        best.threshold as f64 / template_bits as f64,
+        template::match_threshold(template_bits),
+        best.far() * 100.0,
+        best.frr() * 100.0
+    );
+
+    if !args.iter().any(|a| a == "--apply") {
+        say!("ℹ️  Rerun with --apply to record it for the server");
+        return Ok(());
+    }
+    if !measurable {
+        return Err(format!(
+            "Not applying: {} impostor pairs can't measure a FAR of {}%; use a larger dataset or a higher --target-far",
+            scores.impostor.len(),
+            target_percent
+        )
+        .into());
+    }
+    let path = flag("--thresholds").map(PathBuf::from).unwrap_or_else(|| thresholds_path.to_path_buf());
+    let mut tuned: Vec<TunedThreshold> = match fs::read_to_string(&path) {
+        Ok(data) => serde_json::from_str(&data)?,
+        Err(_) => Vec::new(),
+    };
+    tuned.retain(|t| t.params != params);
+    tuned.push(TunedThreshold {
+        params,
+        threshold_bits: best.threshold,
+        target_far,
+        far: best.far(),
+        frr: best.frr(),
+        genuine_pairs: best.genuine_pairs,
+        impostor_pairs: best.impostor_pairs,
+        tuned_at: chrono::Utc::now().to_rfc3339(),
+    });
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&tuned)?)?;
+    say!("✅ Written to {}; verifications of {} templates use it from now on", path.display(), params.quantization);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_rates_over_genuine_and_impostor_pairs() {
+        let template = |ones: usize| (0..8).map(|i| i < ones).collect::<Vec<bool>>();
+        let fingers = vec![
+            ("101".to_string(), vec![template(0), template(1)]),
+            ("102".to_string(), vec![template(6), template(8)]),
+        ];
+        let scores = score_pairs(&fingers);
+        assert_eq!(scores, Scores { genuine: vec![1, 2], impostor: vec![5, 6, 7, 8] });
+
+        let rates = sweep(&scores, 8, 1);
+        assert_eq!(rates.len(), 9);
+        assert_eq!((rates[0].far(), rates[0].frr()), (0.0, 1.0));
+        assert_eq!((rates[5].false_accepts, rates[5].false_rejects), (1, 0));
+        assert_eq!(rates[8].far(), 1.0);
+        let eer = equal_error(&rates).unwrap();
+        assert!((2..=4).contains(&eer.threshold) && eer.far() == 0.0 && eer.frr() == 0.0);
+
+        let mut csv = Vec::new();
+        write_csv(&rates[..2], 8, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(2), Some("1,0.1250,0.000000,0.500000,0,4,1,2"));
+        assert_eq!(parse_name("101_3"), Some(("101", 3)));
+
+        // 4 impostor pairs: FAR 0 up to 4 bits, 25% at 5
+        assert_eq!(recommend(&rates, 0.0).map(|r| r.threshold), Some(4));
+        assert_eq!(recommend(&rates, 0.25).map(|r| r.threshold), Some(5));
+        assert_eq!(probit(0.5), Some(0.0));
+        assert!((probit(0.001).unwrap() + 3.0902).abs() < 1e-4 && probit(1.0).is_none());
+        assert_eq!(parse_name("notes"), None);
+    }
+
+    #[test]
+    fn fuzzy_error_capacities_are_measured_by_the_most_differing_block() {
+        // 62 bits over GF(2^5): two blocks of 31
+        let flipped = |base: bool, positions: &[usize]| (0..62).map(|i| (base && i % 2 == 0) ^ positions.contains(&i)).collect::<Vec<bool>>();
+        let fingers = vec![
+            ("101".to_string(), vec![flipped(false, &[]), flipped(false, &[0, 1, 40])]),
+            ("102".to_string(), vec![flipped(true, &[]), flipped(true, &[33, 35, 37])]),
+        ];
+        let rates = fuzzy_sweep(&fingers, 62, 5);
+        // The genuine pairs differ in at most 2 and 3 bits of a block
+        assert_eq!(rates.len(), 3);
+        assert_eq!((rates[0].0.threshold, rates[0].0.false_rejects), (1, 2));
+        assert_eq!((rates[1].0.threshold, rates[1].0.false_rejects), (2, 1));
+        assert_eq!((rates[2].0.frr(), rates[2].0.far()), (0.0, 0.0));
+        assert!(rates.windows(2).all(|w| w[0].1 > w[1].1));
+        assert_eq!(recommend_errors(&rates, 0.5).map(|(r, _)| r.threshold), Some(2));
+        assert_eq!(recommend_errors(&rates[..2], 0.0), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! Fallback factor orchestration.
+//!
+//! When the fingerprint verification fails (or times out, if the policy says
+//! so) the client retries with alternate enrolled factors in the order the
+//! server-side policy dictates. The transport is supplied by the caller.
+//! A factor the server rejected under its fail-closed error policy ends the
+//! authentication without trying further factors.
+
+use shared::{Factor, FallbackPolicy};
+
+/// Input a factor's probe template is derived from
+#[derive(Debug, Clone)]
+pub enum FactorInput {
+    Image(String),  // Path to a fingerprint image
+    Pin(String),
+}
+
+/// Why a single factor attempt did not produce a decision
+#[derive(Debug)]
+pub enum AttemptError {
+    Timeout,
+    Failed(String),
+    Rejected(String),   // Server error policy denied the whole authentication
+}
+
+#[derive(Debug)]
+pub struct FactorAttempt {
+    pub factor: Factor,
+    pub matched: bool,
+    pub error: Option<String>,
+    pub timed_out: bool,
+    pub rejected: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct FallbackReport {
+    pub attempts: Vec<FactorAttempt>,
+    pub accepted: Option<Factor>,
+}
+
+impl FallbackReport {
+    pub fn succeeded(&self) -> bool {
+        self.accepted.is_some()
+    }
+
+    /// The last attempt was rejected by the server's error policy
+    pub fn rejected(&self) -> bool {
+        self.attempts.last().is_some_and(|a| a.rejected)
+    }
+}
+
+/// Try the primary fingerprint, then fallback factors allowed by `policy`.
+///
+/// `attempt` performs one verification and returns the decrypted match bit.
+/// Fallback factors without a matching entry in `fallbacks` are skipped.
+pub fn run_with_fallback<F>(
+    policy: &FallbackPolicy,
+    primary: &FactorInput,
+    fallbacks: &[(Factor, FactorInput)],
+    mut attempt: F,
+) -> FallbackReport
+where
+    F: FnMut(Factor, &FactorInput) -> Result<bool, AttemptError>,
+{
+    let mut report = FallbackReport::default();
+
+    let mut try_factor = |factor: Factor, input: &FactorInput, report: &mut FallbackReport| {
+        let (matched, error, timed_out, rejected) = match attempt(factor, input) {
+            Ok(matched) => (matched, None, false, false),
+            Err(AttemptError::Timeout) => (false, Some("timeout".to_string()), true, false),
+            Err(AttemptError::Failed(e)) => (false, Some(e), false, false),
+            Err(AttemptError::Rejected(e)) => (false, Some(e), false, true),
+        };
+        report.attempts.push(FactorAttempt { factor, matched, error, timed_out, rejected });
+        if matched {
+            report.accepted = Some(factor);
+        }
+        timed_out
+    };
+
+    let primary_timed_out = try_factor(Factor::Fingerprint, primary, &mut report);
+    if report.succeeded() || report.rejected() || !policy.enabled || (primary_timed_out && !policy.on_timeout) {
+        return report;
+    }
+
+    let mut used = 0;
+    for factor in &policy.order {
+        if used >= policy.max_fallbacks {
+            break;
+        }
+        let Some((_, input)) = fallbacks.iter().find(|(f, _)| f == factor) else {
+            continue;
+        };
+
+        used += 1;
+        try_factor(*factor, input, &mut report);
+        if report.succeeded() || report.rejected() {
+            break;
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> Vec<(Factor, FactorInput)> {
+        vec![
+            (Factor::Pin, FactorInput::Pin("1234".to_string())),
+            (Factor::SecondFinger, FactorInput::Image("b.tif".to_string())),
+        ]
+    }
+
+    #[test]
+    fn test_fallback_follows_policy_order() {
+        let policy = FallbackPolicy::default();
+        let primary = FactorInput::Image("a.tif".to_string());
+        let mut tried = Vec::new();
+
+        let report = run_with_fallback(&policy, &primary, &inputs(), |factor, _| {
+            tried.push(factor);
+            Ok(factor == Factor::Pin)
+        });
+
+        assert_eq!(tried, vec![Factor::Fingerprint, Factor::SecondFinger, Factor::Pin]);
+        assert_eq!(report.accepted, Some(Factor::Pin));
+    }
+
+    #[test]
+    fn test_timeout_without_fallback_on_timeout() {
+        let policy = FallbackPolicy { on_timeout: false, ..FallbackPolicy::default() };
+        let primary = FactorInput::Image("a.tif".to_string());
+
+        let report = run_with_fallback(&policy, &primary, &inputs(), |_, _| Err(AttemptError::Timeout));
+
+        assert_eq!(report.attempts.len(), 1);
+        assert!(!report.succeeded());
+    }
+
+    #[test]
+    fn test_rejection_stops_fallback() {
+        let policy = FallbackPolicy::default();
+        let primary = FactorInput::Image("a.tif".to_string());
+
+        let report = run_with_fallback(&policy, &primary, &inputs(), |_, _| {
+            Err(AttemptError::Rejected("server key missing".to_string()))
+        });
+
+        assert_eq!(report.attempts.len(), 1);
+        assert!(report.rejected());
+    }
+}
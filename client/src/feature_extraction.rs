@@ -1,26 +1,221 @@
 use image::{GrayImage, ImageError, imageops};
+use shared::{quality, template};
+pub use shared::template::Extractor;
 
-/// 1024-bit feature extraction using Local Binary Patterns
-/// 32×32 grid with LBP texture features
-pub fn extract_fingerprint_128bit(image_path: &str) -> Result<Vec<bool>, ImageError> {
-    // 1. Read and resize to 64×64
-    let img = image::open(image_path)?.to_luma8();
-    let resized = imageops::resize(&img, 64, 64, imageops::FilterType::Lanczos3);
+use crate::image_source::ImageSource;
+use crate::{alignment, gabor, resolution, sensor};
+
+/// Regions whose normalized grey levels vary less than this hold no ridges
+const MIN_REGION_STDDEV: f32 = 10.0;
+
+/// A bin this close to the cut between the selected patterns and the rest (pixels)
+/// can land on either side in the next capture
+const MIN_BIN_MARGIN: f32 = 0.5;
+
+/// Ridge frequencies outside this range (cycles per pixel) are not ridges:
+/// blank or smudged blocks, or noise
+const MIN_RIDGE_FREQUENCY: f32 = 1.0 / 16.0;
+const MAX_RIDGE_FREQUENCY: f32 = 1.0 / 3.0;
+
+/// Template bits, region coverage and per-bit quality mask of one capture
+pub type ProbeBits = (Vec<bool>, Vec<bool>, Vec<bool>);
+
+/// How template bits are computed from a capture; enrollment and verification
+/// must use the same options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExtractionOptions {
+    pub extractor: Extractor,
+    pub align_core: bool,       // Centre the core point first (see alignment.rs)
+    pub rotation_degrees: i32,  // Rotate the capture first, for an enrollment's rotated variants
+}
+
+/// Feature extraction: 16 bits per region of a grid sized for `template_bits`
+/// (8×8 regions for 1024 bits)
+pub fn extract_fingerprint_bits(
+    source: &ImageSource,
+    template_bits: usize,
+    options: ExtractionOptions,
+) -> Result<Vec<bool>, ImageError> {
+    extract_with_coverage(source, template_bits, options).map(|(bits, _)| bits)
+}
+
+/// Template bits plus one coverage flag per region: whether the region holds
+/// ridges at all (partial touches and small sensors leave blank regions)
+pub fn extract_with_coverage(
+    source: &ImageSource,
+    template_bits: usize,
+    options: ExtractionOptions,
+) -> Result<(Vec<bool>, Vec<bool>), ImageError> {
+    extract_with_quality(source, template_bits, options).map(|(bits, coverage, _)| (bits, coverage))
+}
+
+/// Capture mapped onto the reference sensor and density, with the resolution
+/// it was normalized from (`None` = used as captured)
+pub fn load_capture(source: &ImageSource) -> Result<(GrayImage, Option<u32>), ImageError> {
+    let (decoded, recorded_dpi) = source.decode()?;
+    let img = sensor::active().apply(&decoded);
+    match sensor::capture_dpi(recorded_dpi) {
+        Some(dpi) => Ok((resolution::normalize_scale(&img, dpi), Some(dpi))),
+        None => Ok((img, None)),
+    }
+}
+
+/// Template bits, region coverage and the per-bit quality mask (see shared/src/quality.rs)
+pub fn extract_with_quality(
+    source: &ImageSource,
+    template_bits: usize,
+    options: ExtractionOptions,
+) -> Result<ProbeBits, ImageError> {
+    // 1. Decode (see image_source.rs), map onto the reference sensor (see sensor.rs) and its density (see resolution.rs)
+    let (mut img, dpi) = load_capture(source)?;
+    let profile = sensor::active();
+    if !profile.is_reference() {
+        say!("🔬 Sensor profile: {}", profile.name);
+    }
+    if let Some(dpi) = dpi {
+        say!("📏 Normalized from {} dpi to {} dpi", dpi, sensor::REFERENCE_DPI);
+    }
     
-    // 2. Normalize
-    let normalized = normalize_image(&resized);
+    // 2. Rotate (rotated enrollment variants) and move the core point to the centre
+    if options.rotation_degrees != 0 {
+        img = alignment::rotate(&img, options.rotation_degrees);
+    }
+    if options.align_core {
+        let (aligned, core) = alignment::align_to_core(&img);
+        if let Some((x, y)) = core {
+            say!("🎯 Core point at ({:.0}, {:.0}), centred", x, y);
+        }
+        img = aligned;
+    }
     
-    // 3. Calculate LBP
-    let lbp_image = calculate_lbp(&normalized);
+    // 3. Resize to 64×64
+    let resized = imageops::resize(&img, 64, 64, imageops::FilterType::Lanczos3);
     
-    // 4. Extract regional histograms (16×16 regions, each with 64-bin histogram)
-    let bits = extract_lbp_features(&lbp_image, 8, 8); // 8×8 = 64 regions
+    // 4. Normalize
+    let normalized = normalize_image(&resized);
     
-    assert_eq!(bits.len(), 1024, "Expected 1024 bits");
+    // 5. Extract regional features (16 bits per region): LBP histograms or Gabor energies
+    let (grid_x, grid_y) = template::extractor_grid(template_bits);
+    let extractor = options.extractor;
+    let (bits, stable) = match extractor {
+        Extractor::Lbp => extract_lbp_features(&calculate_lbp(&normalized), grid_x, grid_y),
+        Extractor::Gabor => gabor::extract_gabor_features(&normalized, grid_x, grid_y),
+    };
+    let coverage = region_coverage(&normalized, grid_x, grid_y);
+    let quality_mask = quality::quality_mask(&stable, &coverage);
     
-    println!("✅ Extracted 1024 bits (LBP texture features)");
+    say!("✅ Extracted {} bits ({}, {}×{} regions)", bits.len(), extractor.description(), grid_x, grid_y);
     
-    Ok(bits)
+    Ok((bits, coverage, quality_mask))
+}
+
+/// Weight of each template bit for a weighted distance (see shared/src/template.rs):
+/// the bins of edge patterns (3-5 brighter neighbours) tell fingers apart better
+/// than corners, and those better than flat areas and isolated spots
+pub fn bit_weights(template_bits: usize) -> Vec<u8> {
+    let region: Vec<u8> = get_uniform_patterns()[..template::BITS_PER_REGION]
+        .iter()
+        .map(|&code| match code.count_ones() {
+            3..=5 => 3,
+            2 | 6 => 2,
+            _ => 1,
+        })
+        .collect();
+    region.iter().copied().cycle().take(template_bits).collect()
+}
+
+/// Whether each region (same order as the template bits) contains ridge texture
+fn region_coverage(img: &GrayImage, grid_x: usize, grid_y: usize) -> Vec<bool> {
+    let region_w = img.width() as usize / grid_x;
+    let region_h = img.height() as usize / grid_y;
+    let mut coverage = Vec::with_capacity(grid_x * grid_y);
+    for gy in 0..grid_y {
+        for gx in 0..grid_x {
+            let values: Vec<f32> = (gy * region_h..(gy + 1) * region_h)
+                .flat_map(|y| (gx * region_w..(gx + 1) * region_w).map(move |x| (x, y)))
+                .map(|(x, y)| img.get_pixel(x as u32, y as u32)[0] as f32)
+                .collect();
+            let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len().max(1) as f32;
+            coverage.push(variance.sqrt() >= MIN_REGION_STDDEV);
+        }
+    }
+    coverage
+}
+
+/// Ridge orientation and spacing of one block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RidgeBlock {
+    pub orientation: f32,         // Direction of the ridge lines, radians in [0, π)
+    pub frequency: Option<f32>,   // Ridges per pixel across them; None when no ridges were found
+}
+
+/// Block orientation field and ridge frequencies of a normalized image, row by
+/// row over `block`×`block` pixel blocks.
+///
+/// The orientation is the least-squares fit to the block's Sobel gradients
+/// (doubled angles, so opposite gradients reinforce instead of cancelling).
+/// The frequency comes from the grey levels projected onto the ridge normal:
+/// the mean distance between the peaks of that signature is the ridge period.
+pub fn ridge_field(img: &GrayImage, block: u32) -> Vec<RidgeBlock> {
+    let (width, height) = img.dimensions();
+    let pixel = |x: u32, y: u32| img.get_pixel(x, y)[0] as f32;
+    let mut field = Vec::new();
+
+    for by in 0..height / block {
+        for bx in 0..width / block {
+            let (x0, y0) = (bx * block, by * block);
+            let (mut vx, mut vy) = (0.0f32, 0.0f32);
+            for y in y0.max(1)..(y0 + block).min(height - 1) {
+                for x in x0.max(1)..(x0 + block).min(width - 1) {
+                    let gx = pixel(x + 1, y - 1) + 2.0 * pixel(x + 1, y) + pixel(x + 1, y + 1)
+                        - pixel(x - 1, y - 1) - 2.0 * pixel(x - 1, y) - pixel(x - 1, y + 1);
+                    let gy = pixel(x - 1, y + 1) + 2.0 * pixel(x, y + 1) + pixel(x + 1, y + 1)
+                        - pixel(x - 1, y - 1) - 2.0 * pixel(x, y - 1) - pixel(x + 1, y - 1);
+                    vx += 2.0 * gx * gy;
+                    vy += gx * gx - gy * gy;
+                }
+            }
+            // Mean gradient direction; the ridges run across it
+            let normal = 0.5 * vx.atan2(vy);
+            let orientation = (normal + std::f32::consts::FRAC_PI_2).rem_euclid(std::f32::consts::PI);
+            let frequency = ridge_frequency(img, x0, y0, block, normal);
+            field.push(RidgeBlock { orientation, frequency });
+        }
+    }
+    field
+}
+
+/// Ridges per pixel along `normal` around a block, from the peaks of the grey
+/// levels projected onto it; the window is twice the block so that it spans
+/// several ridges even at the widest spacing
+fn ridge_frequency(img: &GrayImage, x0: u32, y0: u32, block: u32, normal: f32) -> Option<f32> {
+    let (width, height) = img.dimensions();
+    let (sin, cos) = normal.sin_cos();
+    let (cx, cy) = (x0 as f32 + (block as f32 - 1.0) / 2.0, y0 as f32 + (block as f32 - 1.0) / 2.0);
+    let (left, top) = ((x0 + block / 2).saturating_sub(block), (y0 + block / 2).saturating_sub(block));
+    let (right, bottom) = ((x0 + block / 2 + block).min(width), (y0 + block / 2 + block).min(height));
+    let reach = (block as f32 * std::f32::consts::SQRT_2).ceil() as i32 + 1;
+    let mut sums = vec![0.0f32; 2 * reach as usize + 1];
+    let mut counts = vec![0u32; sums.len()];
+    for y in top..bottom {
+        for x in left..right {
+            let along = (x as f32 - cx) * cos + (y as f32 - cy) * sin;
+            let bin = (along.round() as i32 + reach) as usize;
+            sums[bin] += img.get_pixel(x, y)[0] as f32;
+            counts[bin] += 1;
+        }
+    }
+    let signature: Vec<f32> = sums.iter().zip(&counts).filter(|(_, &n)| n > 0).map(|(s, &n)| s / n as f32).collect();
+    let peaks: Vec<usize> = (1..signature.len().saturating_sub(1))
+        .filter(|&i| signature[i] > signature[i - 1] && signature[i] >= signature[i + 1])
+        .collect();
+    let (&first, &last) = (peaks.first()?, peaks.last()?);
+    if peaks.len() < 2 {
+        return None;
+    }
+    let frequency = (peaks.len() - 1) as f32 / (last - first) as f32;
+    (MIN_RIDGE_FREQUENCY..=MAX_RIDGE_FREQUENCY).contains(&frequency).then_some(frequency)
 }
 
 fn normalize_image(img: &GrayImage) -> GrayImage {
@@ -87,14 +282,16 @@ fn calculate_lbp(img: &GrayImage) -> Vec<u8> {
     lbp
 }
 
-/// Extract features from LBP image using regional histograms
-fn extract_lbp_features(lbp: &[u8], grid_x: usize, grid_y: usize) -> Vec<bool> {
+/// Extract features from LBP image using regional histograms; each bit comes
+/// with whether its bin is clear of the top-16 cut (`MIN_BIN_MARGIN`)
+fn extract_lbp_features(lbp: &[u8], grid_x: usize, grid_y: usize) -> (Vec<bool>, Vec<bool>) {
     let width = 64;
     let height = 64;
     let region_w = width / grid_x;
     let region_h = height / grid_y;
     
     let mut bits = Vec::new();
+    let mut stable = Vec::new();
     
     // For each region
     for gy in 0..grid_y {
@@ -126,13 +323,25 @@ fn extract_lbp_features(lbp: &[u8], grid_x: usize, grid_y: usize) -> Vec<bool> {
             
             // Convert histogram to 16 bits (quantize to most significant patterns)
             let top_patterns = get_top_k_indices(&uniform_histogram, 16);
+            let cut = selection_cut(&uniform_histogram, 16);
             for i in 0..16 {
                 bits.push(top_patterns.contains(&i));
+                stable.push((uniform_histogram[i] as f32 - cut).abs() >= MIN_BIN_MARGIN);
             }
         }
     }
     
-    bits
+    (bits, stable)
+}
+
+/// Midpoint between the k-th and the (k+1)-th largest value: bins on it are tied
+/// with an unselected one, and which is selected depends on their order alone
+fn selection_cut(arr: &[u32], k: usize) -> f32 {
+    let mut sorted = arr.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let last_in = sorted.get(k - 1).copied().unwrap_or(0);
+    let first_out = sorted.get(k).copied().unwrap_or(0);
+    (last_in + first_out) as f32 / 2.0
 }
 
 /// Get uniform LBP patterns (patterns with at most 2 transitions)
@@ -170,4 +379,36 @@ fn get_top_k_indices(arr: &[u32], k: usize) -> Vec<usize> {
     let mut indexed: Vec<(usize, u32)> = arr.iter().enumerate().map(|(i, &v)| (i, v)).collect();
     indexed.sort_by(|a, b| b.1.cmp(&a.1));
     indexed.iter().take(k).map(|&(i, _)| i).collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+    use std::f32::consts::PI;
+
+    /// Sinusoidal ridges running at `angle` with a `period` pixel spacing
+    fn ridges(angle: f32, period: f32) -> GrayImage {
+        let (sin, cos) = (angle + PI / 2.0).sin_cos();
+        GrayImage::from_fn(64, 64, |x, y| {
+            let across = x as f32 * cos + y as f32 * sin;
+            Luma([(128.0 + 90.0 * (2.0 * PI * across / period).cos()) as u8])
+        })
+    }
+
+    #[test]
+    fn field_recovers_orientation_and_frequency() {
+        for (angle, period) in [(0.0, 8.0), (PI / 4.0, 6.0), (PI / 2.0, 10.0), (2.0 * PI / 3.0, 7.0)] {
+            let field = ridge_field(&ridges(angle, period), 16);
+            assert_eq!(field.len(), 16);
+            for block in &field {
+                let error = (block.orientation - angle).rem_euclid(PI);
+                assert!(error.min(PI - error) < 0.1, "orientation {} for {}", block.orientation, angle);
+                let frequency = block.frequency.expect("ridges found");
+                assert!((frequency * period - 1.0).abs() < 0.2, "frequency {} for period {}", frequency, period);
+            }
+        }
+        let blank = GrayImage::from_pixel(64, 64, Luma([128]));
+        assert!(ridge_field(&blank, 16).iter().all(|b| b.frequency.is_none()));
+    }
+}
@@ -0,0 +1,156 @@
+//! Local append-only authentication history.
+//!
+//! Every register/verify attempt is appended as one JSON line next to the
+//! client key, so users can audit when and where their fingerprint was used.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use client::say;
+
+use crate::get_client_key_path;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub operation: String,           // "register" | "verify"
+    pub user_id: String,
+    pub server: String,
+    pub success: bool,
+    pub match_result: Option<bool>,  // verify only
+    pub distance: Option<usize>,     // verify only
+    pub duration_ms: Option<u64>,    // None when the result was collected later
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(operation: &str, user_id: &str, server: &str) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            operation: operation.to_string(),
+            user_id: user_id.to_string(),
+            server: server.to_string(),
+            success: false,
+            match_result: None,
+            distance: None,
+            duration_ms: None,
+            error: None,
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis() as u64);
+        self
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    let key_path = get_client_key_path();
+    key_path
+        .parent()
+        .map(|dir| dir.join(HISTORY_FILE))
+        .unwrap_or_else(|| PathBuf::from(HISTORY_FILE))
+}
+
+/// Append an entry to the log. A failing history write never fails the operation itself.
+pub fn record(entry: &HistoryEntry) {
+    if let Err(e) = try_record(entry) {
+        eprintln!("⚠️  Could not write history entry: {}", e);
+    }
+}
+
+fn try_record(entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Load all entries, skipping lines that cannot be parsed.
+pub fn load() -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = fs::File::open(&path)?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Re-attribute every entry of `from` to `to`. Returns the number of moved entries.
+pub fn reassign_user(from: &str, to: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut entries = load()?;
+    let mut moved = 0;
+    for entry in entries.iter_mut().filter(|e| e.user_id == from) {
+        entry.user_id = to.to_string();
+        moved += 1;
+    }
+    if moved == 0 {
+        return Ok(0);
+    }
+
+    let path = history_path();
+    let mut data = String::new();
+    for entry in &entries {
+        data.push_str(&serde_json::to_string(entry)?);
+        data.push('\n');
+    }
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(moved)
+}
+
+pub fn print_history(user_filter: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries: Vec<HistoryEntry> = load()?
+        .into_iter()
+        .filter(|e| user_filter.is_none_or(|u| e.user_id == u))
+        .collect();
+
+    say!("\n📜 AUTHENTICATION HISTORY ({})", history_path().display());
+    say!("{}", "─".repeat(70));
+
+    if entries.is_empty() {
+        say!("No entries.");
+        return Ok(());
+    }
+
+    for e in &entries {
+        let result = match (e.success, e.match_result) {
+            (false, _) => format!("ERROR ({})", e.error.as_deref().unwrap_or("unknown")),
+            (true, Some(true)) => "MATCH".to_string(),
+            (true, Some(false)) => "NO MATCH".to_string(),
+            (true, None) => "OK".to_string(),
+        };
+        let distance = e.distance.map(|d| format!(" d={}", d)).unwrap_or_default();
+        let duration = e
+            .duration_ms
+            .map(|ms| format!(" {:.1}s", ms as f64 / 1000.0))
+            .unwrap_or_default();
+
+        say!(
+            "{}  {:<8}  {:<16}  {}{}{}  [{}]",
+            e.timestamp, e.operation, e.user_id, result, distance, duration, e.server
+        );
+    }
+
+    say!("{}", "─".repeat(70));
+    say!("{} entries", entries.len());
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Where a capture's pixels come from.
+//!
+//! Files in the formats the `image` crate reads (PNG, TIFF, BMP, JPEG, ...)
+//! are decoded as before. Live sensors deliver raw 8-bit grayscale frames,
+//! passed in memory with their size. FVC and law-enforcement datasets ship as
+//! WSQ, which is decoded with NBIS `dwsq` (`dwsq raw finger.wsq -r`) into a
+//! `.raw` frame and a NIST comment sidecar (`.ncm`) holding its width,
+//! height and resolution; such a `.raw` file is read as a capture directly.
+//! WSQ files themselves are recognized and refused with that hint.
+
+use image::codecs::png::PngEncoder;
+use image::{GrayImage, ImageError};
+use std::io;
+use std::path::Path;
+
+use crate::resolution;
+
+/// Leading marker of a WSQ file (SOI)
+const WSQ_MAGIC: [u8; 2] = [0xFF, 0xA0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSource {
+    File(String),               // Image file, or a `.raw` frame with its `.ncm` sidecar
+    Raw {
+        pixels: Vec<u8>,        // 8-bit grayscale, row by row
+        width: u32,
+        height: u32,
+        dpi: Option<u32>,       // Sensor resolution, if known
+    },
+}
+
+impl From<&str> for ImageSource {
+    fn from(path: &str) -> Self {
+        ImageSource::File(path.to_string())
+    }
+}
+
+impl ImageSource {
+    /// Grayscale pixels and the resolution recorded with them: plausible image
+    /// metadata, the `.ncm` sidecar or the frame's own
+    pub fn decode(&self) -> Result<(GrayImage, Option<u32>), ImageError> {
+        match self {
+            ImageSource::File(path) if is_raw_path(path) => {
+                let info = read_ncm(&Path::new(path).with_extension("ncm"))?;
+                let pixels = std::fs::read(path).map_err(ImageError::IoError)?;
+                raw_frame(pixels, info.width, info.height, info.dpi)
+            }
+            ImageSource::File(path) => {
+                let data = std::fs::read(path).map_err(ImageError::IoError)?;
+                if data.starts_with(&WSQ_MAGIC) {
+                    return Err(invalid(format!(
+                        "{} is WSQ-compressed; decode it with NBIS first (dwsq raw {} -r) and pass the .raw file",
+                        path, path
+                    )));
+                }
+                let img = image::load_from_memory(&data)?.to_luma8();
+                Ok((img, resolution::from_metadata(&data)))
+            }
+            ImageSource::Raw { pixels, width, height, dpi } => raw_frame(pixels.clone(), *width, *height, *dpi),
+        }
+    }
+}
+
+/// Size and resolution of a raw frame, from its NIST comment sidecar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameInfo {
+    width: u32,
+    height: u32,
+    dpi: Option<u32>,
+}
+
+fn is_raw_path(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("raw"))
+}
+
+fn read_ncm(path: &Path) -> Result<FrameInfo, ImageError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| invalid(format!("Raw frame needs its NIST comment sidecar {}: {}", path.display(), e)))?;
+    parse_ncm(&text).map_err(invalid)
+}
+
+/// `NIST_COM` fields, one `KEY value` per line: PIX_WIDTH, PIX_HEIGHT, PIX_DEPTH, PPI
+fn parse_ncm(text: &str) -> Result<FrameInfo, String> {
+    let field = |key: &str| {
+        text.lines()
+            .filter_map(|line| line.trim().split_once(char::is_whitespace))
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.trim())
+    };
+    let number = |key: &str| -> Result<u32, String> {
+        let value = field(key).ok_or(format!("NIST comment has no {}", key))?;
+        value.parse().map_err(|_| format!("Invalid {} in NIST comment: {}", key, value))
+    };
+    if let Some(depth) = field("PIX_DEPTH").filter(|&d| d != "8") {
+        return Err(format!("Raw frames must be 8-bit grayscale, PIX_DEPTH is {}", depth));
+    }
+    // dwsq writes PPI -1 when the WSQ file did not record it
+    let dpi = field("PPI").and_then(|v| v.parse::<u32>().ok()).filter(|&ppi| ppi > 0);
+    Ok(FrameInfo { width: number("PIX_WIDTH")?, height: number("PIX_HEIGHT")?, dpi })
+}
+
+/// Write a frame as one PNG file, its resolution in a pHYs chunk `decode` reads back
+pub fn save_png(img: &GrayImage, dpi: Option<u32>, path: &Path) -> Result<(), ImageError> {
+    let mut png = Vec::new();
+    img.write_with_encoder(PngEncoder::new(&mut png))?;
+    if let Some(dpi) = dpi {
+        // pHYs goes right after IHDR (8-byte signature, 25-byte chunk), before the image data
+        let per_metre = (dpi as f64 / 0.0254).round() as u32;
+        let mut body = b"pHYs".to_vec();
+        body.extend(per_metre.to_be_bytes());
+        body.extend(per_metre.to_be_bytes());
+        body.push(1);       // Unit: metre
+        let mut chunk = 9u32.to_be_bytes().to_vec();
+        chunk.extend(&body);
+        chunk.extend(crc32(&body).to_be_bytes());
+        png.splice(33..33, chunk);
+    }
+    std::fs::write(path, png).map_err(ImageError::IoError)
+}
+
+/// CRC-32 (ISO 3309) of a PNG chunk's type and data
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 })
+    })
+}
+
+fn raw_frame(pixels: Vec<u8>, width: u32, height: u32, dpi: Option<u32>) -> Result<(GrayImage, Option<u32>), ImageError> {
+    let expected = width as u64 * height as u64;
+    if width == 0 || height == 0 || pixels.len() as u64 != expected {
+        return Err(invalid(format!("Raw frame of {}×{} needs {} bytes, got {}", width, height, expected, pixels.len())));
+    }
+    let img = GrayImage::from_raw(width, height, pixels).ok_or_else(|| invalid("Raw frame size mismatch".to_string()))?;
+    Ok((img, dpi))
+}
+
+fn invalid(message: String) -> ImageError {
+    ImageError::IoError(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nist_comment_describes_the_frame() {
+        let ncm = "NIST_COM 5\nPIX_WIDTH 3\nPIX_HEIGHT 2\nPIX_DEPTH 8\nPPI 500\nLOSSY 1\n";
+        assert_eq!(parse_ncm(ncm), Ok(FrameInfo { width: 3, height: 2, dpi: Some(500) }));
+        let unknown = parse_ncm("PIX_WIDTH 3\nPIX_HEIGHT 2\nPPI -1\n").unwrap();
+        assert_eq!(unknown.dpi, None);
+        assert!(parse_ncm("PIX_WIDTH 3\nPIX_HEIGHT 2\nPIX_DEPTH 16\n").is_err());
+        assert!(parse_ncm("PIX_HEIGHT 2\n").is_err());
+    }
+
+    #[test]
+    fn raw_frames_and_wsq_files() {
+        let frame = ImageSource::Raw { pixels: vec![0, 50, 100, 150, 200, 250], width: 3, height: 2, dpi: Some(1000) };
+        let (img, dpi) = frame.decode().unwrap();
+        assert_eq!((img.dimensions(), img.get_pixel(2, 1)[0], dpi), ((3, 2), 250, Some(1000)));
+        let short = ImageSource::Raw { pixels: vec![0; 5], width: 3, height: 2, dpi: None };
+        assert!(short.decode().is_err());
+
+        let dir = std::env::temp_dir().join(format!("image_source_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let raw = dir.join("finger.raw");
+        std::fs::write(&raw, [10u8, 20, 30, 40]).unwrap();
+        std::fs::write(dir.join("finger.ncm"), "NIST_COM 4\nPIX_WIDTH 2\nPIX_HEIGHT 2\nPIX_DEPTH 8\nPPI 500\n").unwrap();
+        let (img, dpi) = ImageSource::from(raw.to_str().unwrap()).decode().unwrap();
+        assert_eq!((img.get_pixel(1, 1)[0], dpi), (40, Some(500)));
+        let big = GrayImage::from_fn(40, 30, |x, y| image::Luma([(x * 6 + y) as u8]));
+        for dpi in [None, Some(500)] {
+            save_png(&big, dpi, &dir.join("saved.png")).unwrap();
+            let saved = ImageSource::from(dir.join("saved.png").to_str().unwrap()).decode().unwrap();
+            assert_eq!(saved, (big.clone(), dpi));
+        }
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+
+        let wsq = dir.join("finger.wsq");
+        std::fs::write(&wsq, [0xFF, 0xA0, 0xFF, 0xA8]).unwrap();
+        let error = ImageSource::from(wsq.to_str().unwrap()).decode().unwrap_err().to_string();
+        assert!(error.contains("dwsq"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
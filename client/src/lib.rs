@@ -0,0 +1,21 @@
+//! Client library: feature extraction, Trivium/FHE encryption, protocol
+//! building and result decryption. Used by the `client` binary and the
+//! Node.js bindings.
+
+#[macro_use]
+pub mod output;
+pub mod alignment;
+pub mod api;
+pub mod capture;
+pub mod capture_quality;
+pub mod enhance;
+pub mod evaluation;
+pub mod fallback;
+pub mod feature_extraction;
+pub mod gabor;
+pub mod image_source;
+pub mod matching;
+pub mod oidc;
+pub mod progress;
+pub mod resolution;
+pub mod sensor;
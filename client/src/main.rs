@@ -1,64 +1,471 @@
-mod feature_extraction;
-mod matching;
+mod advisor;
+mod agent;
+mod batch;
+mod bench;
+mod calibration;
+mod config;
+mod estimate;
+mod history;
+mod pinning;
+mod profile;
+mod recovery;
+mod revocation;
+mod rotation;
+mod rpc;
+mod update;
 
-use feature_extraction::extract_fingerprint_128bit;
-use matching::hamming_distance;
+use client::api::{self, VerifyOutcome};
+use client::fallback::{self, AttemptError, FactorInput};
+use client::capture::{self, CaptureDevice, CaptureDir};
+use client::output::CommandReport;
+use client::{alignment, capture_quality, evaluation, oidc, output, progress, say, say_tr};
+use history::HistoryEntry;
+use indicatif::ProgressBar;
 
+use shared::consensus;
+use shared::fusion::{self, FusionRule};
+use shared::fuzzy;
+use shared::etrln;
+use shared::protocol::{self, job_status_file, valid_job_id, JOB_TICKET_FILE};
+use shared::sealed;
+use shared::template;
+use shared::session::{self, ClientCredential, SessionBinding, SessionClaim, SessionRequest, SessionResponse};
+use shared::soft::{SoftAttributes, SoftProfile};
+use shared::telemetry::{self, PhaseTimer, TelemetryConfig, TelemetryRecord};
+use shared::transport::{self, Transport};
 use shared::{
-    RegisterRequest, RegisterResponse,
-    VerifyRequest, VerifyResponse,
-    Trivium, u64_to_bits_80,
+    AccountOperation, AccountRequest, AccountResponse, AdminCommand, AdminRequest, AdminResponse, CancelRequest, CancelResponse, Cipher, ConsentInfo, DeleteRequest, DeleteResponse, Factor, FailureAction, FallbackPolicy, PolicyRequest, PolicyResponse,
+    ErrorCode, FingerprintError, JobState, JobStatus, JobTicket, Kreyvium, QualityMask, ReceiptRequest, ReceiptResponse, RegisterResponse, ServerStatus, Trivium, VerifyResponse,
 };
 
-use tfhe::prelude::*;
-use tfhe::{generate_keys, ConfigBuilder, FheBool};
-
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
-// Paths
-const EXCHANGE_DIR: &str = "../exchange";
-const DATA_DIR: &str = "../data";
+// Request/Response files in the exchange
+const REGISTER_REQUEST: &str = "register_request.json";
+const REGISTER_RESPONSE: &str = "register_response.json";
+const VERIFY_REQUEST: &str = "verify_request.json";
+const VERIFY_RESPONSE: &str = "verify_response.json";
+const POLICY_REQUEST: &str = "policy_request.json";
+const POLICY_RESPONSE: &str = "policy_response.json";
+const SESSION_REQUEST: &str = "session_request.json";
+const SESSION_RESPONSE: &str = "session_response.json";
+const ACCOUNT_REQUEST: &str = "account_request.json";
+const ACCOUNT_RESPONSE: &str = "account_response.json";
+const CANCEL_REQUEST: &str = "cancel_request.json";
+const CANCEL_RESPONSE: &str = "cancel_response.json";
+const DELETE_REQUEST: &str = "delete_request.json";
+const DELETE_RESPONSE: &str = "delete_response.json";
+const RECEIPT_REQUEST: &str = "receipt_request.json";
+const RECEIPT_RESPONSE: &str = "receipt_response.json";
+const ADMIN_REQUEST: &str = "admin_request.json";
+const ADMIN_RESPONSE: &str = "admin_response.json";
+const SERVER_STATUS: &str = "server_status.json";
 
-// Request/Response paths
-const REGISTER_REQ_PATH: &str = "../exchange/register_request.json";
-const REGISTER_RESP_PATH: &str = "../exchange/register_response.json";
-const VERIFY_REQ_PATH: &str = "../exchange/verify_request.json";
-const VERIFY_RESP_PATH: &str = "../exchange/verify_response.json";
+/// How often the server status is read for a heartbeat while a verify has no job status
+const HEARTBEAT_REFRESH: Duration = Duration::from_secs(10);
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔐 FINGERPRINT AUTHENTICATION CLIENT");
-    println!("{}", "=".repeat(70));
+/// Derivation context of the soft attribute blinding key
+const SOFT_BLINDING_CONTEXT: &[u8] = b"soft attribute blinding";
+
+/// Options of an enrollment
+#[derive(Debug, Clone, Default)]
+pub struct EnrollOptions {
+    pub duress: bool,                   // Enroll the duress finger
+    pub consent: Option<ConsentInfo>,   // Consent record sent with the template
+    pub soft: Option<SoftAttributes>,   // Declared soft attributes (see shared/src/soft.rs)
+    pub replace: bool,                  // Confirm replacing an enrolled template (the server keeps the old one)
+    pub samples: Vec<String>,           // Further captures of the primary finger, majority-voted with the first
+    pub rotations: usize,               // Rotated variants of the primary finger to enroll as well
+    pub fingers: Vec<String>,           // Further fingers of a fused enrollment (see shared/src/fusion.rs)
+    pub fusion: FusionRule,             // How their decisions combine with the primary finger's
+}
+
+/// Per-capture options of a verification
+#[derive(Debug, Clone, Default)]
+pub struct ProbeOptions {
+    pub soft: Option<SoftAttributes>,   // Declared soft attributes (see shared/src/soft.rs)
+    pub partial: bool,                  // Send a coverage mask, compare covered regions only
+    pub ownership_challenge: bool,      // Ask for a proof of ownership (see shared/src/ownership.rs)
+    pub fingers: Vec<String>,           // Captures of a fused enrollment's further fingers, in enrollment order
+}
+
+impl ProbeOptions {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            soft: SoftAttributes::from_args(args)?,
+            partial: args.iter().any(|a| a == "--partial"),
+            ownership_challenge: false,
+            fingers: args.windows(2).filter(|w| w[0] == "--fused-finger").map(|w| w[1].clone()).collect(),
+        })
+    }
+}
+
+/// Exchange location: a directory, `s3://bucket/prefix`, an `http(s)://` or `grpc://` server (default: fingerprint.toml's, else ../exchange)
+const EXCHANGE_ENV: &str = "FINGERPRINT_EXCHANGE";
+
+/// HTTP or gRPC server given with `--server-url`, used instead of `FINGERPRINT_EXCHANGE`
+static SERVER_URL: OnceLock<String> = OnceLock::new();
+
+/// Take the global `--server-url <url>` out of the arguments
+fn take_server_url(args: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(i) = args.iter().position(|a| a == "--server-url") else { return Ok(()) };
+    let url = args.get(i + 1).cloned().ok_or("--server-url needs a URL")?;
+    if !["http://", "https://", "grpc://"].iter().any(|scheme| url.starts_with(scheme)) {
+        return Err(format!("--server-url must be an http(s):// or grpc:// URL, got '{}'", url).into());
+    }
+    args.drain(i..i + 2);
+    let _ = SERVER_URL.set(url);
+    Ok(())
+}
+
+/// Take the global `--output <human|json>` out of the arguments
+fn take_output_format(args: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(i) = args.iter().position(|a| a == "--output") else { return Ok(()) };
+    match args.get(i + 1).map(|s| s.as_str()) {
+        Some("json") => output::use_json(),
+        Some("human") => {}
+        other => return Err(format!("--output must be human or json, got '{}'", other.unwrap_or("")).into()),
+    }
+    args.drain(i..i + 2);
+    Ok(())
+}
+
+/// Replace `live:<device>` image arguments with a quality-gated capture from
+/// that sensor, written to a private directory that is removed when the
+/// returned guard drops, after the command (see client/src/capture.rs)
+fn capture_live_images(args: &mut [String]) -> Result<Option<CaptureDir>, Box<dyn std::error::Error>> {
+    if !args.iter().any(|arg| CaptureDevice::from_arg(arg).is_some()) {
+        return Ok(None);
+    }
+    let dir = CaptureDir::create()?;
+    for (i, arg) in args.iter_mut().enumerate() {
+        let Some(device) = CaptureDevice::from_arg(arg) else { continue };
+        let min_quality = api::min_capture_quality_from_env()?;
+        let path = capture::capture_to(&device?, min_quality, &dir, &format!("live-{}", i))?;
+        *arg = path.to_string_lossy().into_owned();
+    }
+    Ok(Some(dir))
+}
+
+/// Transport to the server, chosen once from `--server-url` or `FINGERPRINT_EXCHANGE`
+fn exchange() -> Result<&'static dyn Transport, Box<dyn std::error::Error>> {
+    static EXCHANGE: OnceLock<Result<Box<dyn Transport>, String>> = OnceLock::new();
+    let spec = match SERVER_URL.get() {
+        Some(url) => url.clone(),
+        None => std::env::var(EXCHANGE_ENV).unwrap_or_else(|_| config::get().paths.exchange.clone()),
+    };
+    match EXCHANGE.get_or_init(|| transport::from_spec(&spec).map_err(|e| e.to_string())) {
+        Ok(transport) => Ok(transport.as_ref()),
+        Err(e) => Err(format!("Invalid {}: {}", EXCHANGE_ENV, e).into()),
+    }
+}
+
+/// Private slot of a user in the exchange, so other accounts can't read their files.
+///
+/// Files are sealed to the key the server published in the handshake file;
+/// without one (older server) they are sent unencrypted.
+fn user_exchange(user_id: &str) -> Result<Box<dyn Transport>, Box<dyn std::error::Error>> {
+    let root = exchange()?;
+    let slot = root.user_area(api::api_key_from_env().as_deref(), user_id);
+    let Some(handshake) = &server_handshake()?.handshake else {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| say_tr!("client.exchange_unencrypted"));
+        return Ok(slot);
+    };
+    let own = sealed::ExchangeKey::load_or_create(&get_client_key_path().with_file_name("exchange_key.bin"))?;
+    Ok(Box::new(sealed::SealedTransport::new(slot, handshake.public_key()?, Arc::new(own))))
+}
+
+/// Handshake file of the exchange and the server identity it was checked against
+struct ServerHandshake {
+    handshake: Option<sealed::Handshake>,
+    identity: Option<[u8; 32]>,   // Pinned identity results must be signed with
+}
+
+/// Read the handshake once per run and check it against the pinned identity (see pinning.rs)
+fn server_handshake() -> Result<&'static ServerHandshake, Box<dyn std::error::Error>> {
+    static HANDSHAKE: OnceLock<Result<ServerHandshake, String>> = OnceLock::new();
+    let load = || -> Result<ServerHandshake, Box<dyn std::error::Error>> {
+        let root = exchange()?;
+        let handshake = match root.get(sealed::HANDSHAKE_FILE)? {
+            Some(data) => Some(serde_json::from_slice::<sealed::Handshake>(&data)?),
+            None => None,
+        };
+        let identity = pinning::check(&root.describe(), handshake.as_ref())?;
+        Ok(ServerHandshake { handshake, identity })
+    };
+    match HANDSHAKE.get_or_init(|| load().map_err(|e| e.to_string())) {
+        Ok(handshake) => Ok(handshake),
+        Err(e) => Err(e.clone().into()),
+    }
+}
 
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse CLI arguments
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    shared::i18n::init(&mut args);
+    // First: everything below may resolve the selected profile's key store
+    profile::take_profile(&mut args)?;
+    shared::logging::init(config::get().logging.filter.as_deref());
+    calibration::init(&mut args)?;
+    take_server_url(&mut args)?;
+    take_output_format(&mut args)?;
+
+    // Never transcipher with a Trivium or Kreyvium that disagrees with the spec
+    Trivium::self_test()
+        .and_then(|()| Kreyvium::self_test())
+        .map_err(|e| shared::tr!("client.trivium_self_test_failed", e))?;
+
+    // RPC mode: stdout is reserved for JSON-RPC responses, no banner
+    if args.len() >= 2 && (args[1] == "--rpc" || args[1] == "rpc") {
+        return rpc::run();
+    }
+
+    say_tr!("client.banner");
+    say!("{}", "=".repeat(70));
+    progress::set_visible(true);
     
     if args.len() < 2 {
         print_help();
         return Ok(());
     }
+    let _captures = capture_live_images(&mut args)?;
 
     let mode = args[1].as_str();
 
     match mode {
         "register" => {
             if args.len() < 4 {
-                eprintln!("❌ Usage: cargo run --release -- register <user_id> <image_path>");
+                etrln!("client.usage", "cargo run --release -- register <user_id> <image_path> [--duress | --second-finger] [--replace] [--sample <image_path>...] [--rotations <k>] [--fused-finger <image_path>... [--fusion <any|two-of-n>]] [--consent-ref <ref> --purpose <purpose> [--retain-days <n>]] [--hand <h> --finger <f> --pattern <p> [--blind-soft]]");
                 return Ok(());
             }
             let user_id = &args[2];
-            let image_path = &args[3];
-            handle_register(user_id, image_path)?;
+            let input = FactorInput::Image(args[3].clone());
+            let factor = if args[4..].iter().any(|a| a == "--second-finger") {
+                Factor::SecondFinger
+            } else {
+                Factor::Fingerprint
+            };
+            let options = reported_early("register", user_id, || {
+                let options = EnrollOptions {
+                    duress: args[4..].iter().any(|a| a == "--duress"),
+                    consent: parse_consent(&args[4..])?,
+                    soft: SoftAttributes::from_args(&args[4..])?,
+                    replace: args[4..].iter().any(|a| a == "--replace"),
+                    samples: args[4..].windows(2).filter(|w| w[0] == "--sample").map(|w| w[1].clone()).collect(),
+                    rotations: args[4..]
+                        .windows(2)
+                        .find(|w| w[0] == "--rotations")
+                        .map(|w| w[1].parse::<usize>().map_err(|_| format!("Invalid --rotations value '{}'", w[1])))
+                        .transpose()?
+                        .unwrap_or(0),
+                    fingers: args[4..].windows(2).filter(|w| w[0] == "--fused-finger").map(|w| w[1].clone()).collect(),
+                    fusion: args[4..]
+                        .windows(2)
+                        .find(|w| w[0] == "--fusion")
+                        .map(|w| w[1].parse::<FusionRule>())
+                        .transpose()?
+                        .unwrap_or_default(),
+                };
+                recovery::check(user_id)?;
+                Ok(options)
+            })?;
+            let (result, report) = register_reported(user_id, &input, factor, &options);
+            report.print();
+            result?;
+        }
+        "register-pin" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- register-pin <user_id> <pin> [--replace]");
+                return Ok(());
+            }
+            let input = FactorInput::Pin(args[3].clone());
+            let options = EnrollOptions { replace: args[4..].iter().any(|a| a == "--replace"), ..EnrollOptions::default() };
+            reported_early("register", &args[2], || recovery::check(&args[2]))?;
+            let (result, report) = register_reported(&args[2], &input, Factor::Pin, &options);
+            report.print();
+            result?;
+        }
+        "register-batch" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- register-batch <manifest.csv> [--concurrency <n>]");
+                return Ok(());
+            }
+            batch::run(&args[2..])?;
+        }
+        "update" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- update <user_id> <image_path> [--region-bits <n>]");
+                return Ok(());
+            }
+            let region_bits = args[4..]
+                .iter()
+                .position(|a| a == "--region-bits")
+                .and_then(|i| args.get(4 + i + 1))
+                .map(|n| n.parse::<usize>())
+                .transpose()?;
+            recovery::check(&args[2])?;
+            update::run(&args[2], &args[3], region_bits)?;
         }
         "verify" => {
             if args.len() < 4 {
-                eprintln!("❌ Usage: cargo run --release -- verify <user_id> <image_path>");
+                etrln!("client.usage", "cargo run --release -- verify <user_id> <image_path> [--fallback-finger <image_path>] [--fallback-pin <pin>] [--partial] [--fused-finger <image_path>...] [--hand <h> --finger <f> --pattern <p> [--blind-soft]]");
                 return Ok(());
             }
             let user_id = &args[2];
             let image_path = &args[3];
-            handle_verify(user_id, image_path)?;
+            let fallbacks = parse_fallbacks(&args[4..]);
+            let probe = reported_early("verify", user_id, || {
+                let probe = ProbeOptions::from_args(&args[4..])?;
+                recovery::check(user_id)?;
+                Ok(probe)
+            })?;
+            if fallbacks.is_empty() {
+                let (result, report) = verify_reported(user_id, Factor::Fingerprint, &FactorInput::Image(image_path.clone()), &probe);
+                report.print();
+                result?;
+            } else {
+                handle_verify_with_fallback(user_id, image_path, &probe, &fallbacks).print();
+            }
+        }
+        "login" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- login <user_id> <image_path> [--oidc-config <path>] [--partial] [--hand <h> --finger <f> --pattern <p> [--blind-soft]]");
+                return Ok(());
+            }
+            let config_path = args[4..]
+                .iter()
+                .position(|a| a == "--oidc-config")
+                .and_then(|i| args.get(4 + i + 1))
+                .map(PathBuf::from)
+                .unwrap_or_else(get_oidc_config_path);
+            let probe = ProbeOptions::from_args(&args[4..])?;
+            recovery::check(&args[2])?;
+            handle_login(&args[2], &args[3], &config_path, &probe)?;
+        }
+        "cancel" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- cancel <user_id>");
+                return Ok(());
+            }
+            handle_cancel(&args[2])?;
+        }
+        "delete" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- delete <user_id> [--prove <image_path>]");
+                return Ok(());
+            }
+            let prove = args[3..]
+                .iter()
+                .position(|a| a == "--prove")
+                .and_then(|i| args.get(3 + i + 1))
+                .map(|s| s.as_str());
+            recovery::check(&args[2])?;
+            handle_delete(&args[2], prove)?;
+        }
+        "revoke" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- revoke <user_id>");
+                return Ok(());
+            }
+            recovery::check(&args[2])?;
+            revocation::run(&args[2])?;
+        }
+        "keys" => match (args.get(2).map(|s| s.as_str()), args.get(3)) {
+            (Some("rotate"), user_id) => {
+                if let Some(user_id) = user_id {
+                    recovery::check(user_id)?;
+                }
+                rotation::run(user_id.map(|s| s.as_str()))?;
+            }
+            (Some("list"), _) => profile::list()?,
+            (Some("delete"), Some(name)) => profile::delete(name, args[4..].iter().any(|a| a == "--yes"))?,
+            _ => etrln!("client.usage", "cargo run --release -- keys <rotate [<user_id>]|list|delete <profile> [--yes]>"),
+        },
+        "admin" => {
+            let command = match args.get(2).map(|s| s.as_str()) {
+                Some("list") => AdminCommand::List,
+                Some("stats") => AdminCommand::Stats,
+                _ => {
+                    etrln!("client.usage", "cargo run --release -- admin <list|stats>");
+                    return Ok(());
+                }
+            };
+            handle_admin(command)?;
+        }
+        "rename-user" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- rename-user <old_user_id> <new_user_id>");
+                return Ok(());
+            }
+            handle_account(AccountOperation::Rename { from: args[2].clone(), to: args[3].clone() })?;
+        }
+        "merge-users" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- merge-users <source_user_id> <target_user_id>");
+                return Ok(());
+            }
+            handle_account(AccountOperation::Merge { source: args[2].clone(), target: args[3].clone() })?;
+        }
+        "agent" => {
+            let flag = |name: &str| {
+                args[2..]
+                    .iter()
+                    .position(|a| a == name)
+                    .and_then(|i| args.get(2 + i + 1))
+                    .map(PathBuf::from)
+            };
+            let socket_path = flag("--socket").unwrap_or_else(agent::default_socket_path);
+            let capture_dir = flag("--capture-dir").unwrap_or_else(agent::default_capture_dir);
+            agent::run(&socket_path, &capture_dir)?;
+        }
+        "recover" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- recover <user_id>");
+                return Ok(());
+            }
+            recovery::run(&args[2])?;
+        }
+        "estimate" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- estimate <user_id>");
+                return Ok(());
+            }
+            estimate::run(&args[2])?;
+        }
+        "calibrate-sensor" => {
+            if args.len() < 4 {
+                etrln!("client.usage", "cargo run --release -- calibrate-sensor <name> <image_path>... [--dpi <n>]");
+                return Ok(());
+            }
+            calibration::run(&args[2..])?;
+        }
+        "eval" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- eval <dataset_dir> [--out <csv>] [--step <bits>]");
+                return Ok(());
+            }
+            evaluation::run(&args[2..])?;
+        }
+        "tune-threshold" => {
+            if args.len() < 3 {
+                etrln!("client.usage", "cargo run --release -- tune-threshold <dataset_dir> [--target-far <percent>] [--roc <csv>] [--apply [--thresholds <path>]]");
+                return Ok(());
+            }
+            evaluation::tune(&args[2..], &config::get().thresholds_path())?;
+        }
+        "bench" => {
+            bench::run(&args[2..])?;
+        }
+        "advise-params" => {
+            advisor::run(&args[2..])?;
+        }
+        "history" => {
+            let user_filter = args.get(2).map(|s| s.as_str());
+            history::print_history(user_filter)?;
         }
         "help" | _ => {
             print_help();
@@ -70,412 +477,1416 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 // ==================== REGISTER MODE ====================
 
-fn handle_register(user_id: &str, image_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n📝 REGISTER MODE");
-    println!("{}", "─".repeat(70));
-    println!("👤 User ID: {}", user_id);
-    println!("🖼️  Image: {}", image_path);
+fn handle_register(
+    user_id: &str,
+    input: &FactorInput,
+    factor: Factor,
+    options: &EnrollOptions,
+) -> Result<RegisterResponse, Box<dyn std::error::Error>> {
+    register_reported(user_id, input, factor, options).0
+}
+
+/// Run what a `register`/`verify` does before it starts (arguments, recovery),
+/// describing a failure for `--output json` as the command's report
+fn reported_early<T>(
+    command: &str,
+    user_id: &str,
+    steps: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    steps().inspect_err(|e| CommandReport::new(command, user_id).with_error(e.as_ref()).print())
+}
+
+/// Register, record telemetry and history, and describe the run for `--output json`
+fn register_reported(
+    user_id: &str,
+    input: &FactorInput,
+    factor: Factor,
+    options: &EnrollOptions,
+) -> (Result<RegisterResponse, Box<dyn std::error::Error>>, CommandReport) {
+    let start = Instant::now();
+    let mut timer = PhaseTimer::start();
+    let result = register(user_id, input, factor, options, &mut timer);
+    settle_previous_key(&result);
+    record_telemetry("register", &timer, result.as_ref().is_ok_and(|r| r.success));
 
+    let mut entry = HistoryEntry::new("register", user_id, &server_label()).with_duration(start.elapsed());
+    let mut report = CommandReport::new("register", user_id).with_timings(&timer, start.elapsed());
+    match &result {
+        Ok(response) => {
+            entry.success = response.success;
+            report.success = response.success;
+            if !response.success {
+                entry.error = Some(response.message.clone());
+                report.error = Some(response.message.clone());
+                report.error_code = response.error_code;
+            }
+        }
+        Err(e) => {
+            entry.error = Some(e.to_string());
+            report = report.with_error(e.as_ref());
+        }
+    }
+    history::record(&entry);
+
+    (result, report)
+}
+
+fn register(
+    user_id: &str,
+    input: &FactorInput,
+    factor: Factor,
+    options: &EnrollOptions,
+    timer: &mut PhaseTimer,
+) -> Result<RegisterResponse, Box<dyn std::error::Error>> {
+    let duress = options.duress;
+    say_tr!("client.register_title");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.user_id", user_id);
+    describe_input(input);
+    say_tr!("client.factor", factor);
+    if duress {
+        say_tr!("client.enrolling_duress");
+    }
+
+    // Duress finger and fallback factors must be encrypted under the same key as the primary enrollment
     let client_key_path = get_client_key_path();
-    if client_key_path.exists() {
-        println!("🗑️  Removing old client key for testing...");
-        fs::remove_file(&client_key_path)?;
+    if !duress && factor == Factor::Fingerprint && client_key_path.exists() {
+        say_tr!("client.removing_old_key");
+        fs::rename(&client_key_path, previous_key_path())?;
     }
 
     // Setup directories
-    fs::create_dir_all(EXCHANGE_DIR)?;
-    fs::create_dir_all(DATA_DIR)?;
+    fs::create_dir_all(&config::get().paths.data)?;
 
     // 1. Feature Extraction
-    println!("\n🔬 FEATURE EXTRACTION:");
-    println!("{}", "─".repeat(70));
-    println!("📷 Extracting fingerprint features...");
+    say_tr!("client.section_features");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.extracting");
     
-    let fingerprint_bits = extract_fingerprint_128bit(image_path)?;
+    // A new primary enrollment negotiates its length; further templates must match it
+    let template_bits = if !duress && factor == Factor::Fingerprint {
+        registration_template_bits()?
+    } else {
+        template_bits_for(user_id)?
+    };
+    say_tr!("client.template_bits", template_bits);
+    
+    // Refuse captures too poor to enroll (see capture_quality.rs)
+    let capture_score = match input {
+        FactorInput::Image(path) => {
+            let assessed = api::assess_capture(path)?;
+            say_tr!(
+                "client.capture_quality",
+                assessed.score,
+                format!("{:.0}", assessed.ridge_clarity * 100.0),
+                format!("{:.0}", assessed.contrast * 100.0),
+                format!("{:.0}", assessed.foreground * 100.0)
+            );
+            capture_quality::check_min_quality(&assessed, api::min_capture_quality_from_env()?)?;
+            Some(assessed.score)
+        }
+        FactorInput::Pin(_) => None,
+    };
+    let (fingerprint_bits, quality, quality_mask) = match input {
+        FactorInput::Image(path) => {
+            let (bits, quality, quality_mask) = api::extract_template_with_quality(path, template_bits)?;
+            (bits, Some(quality), Some(quality_mask))
+        }
+        FactorInput::Pin(_) => (api::template_from_input(input, template_bits)?, None, None),
+    };
     
-    if fingerprint_bits.len() != 1024 {  // ⬅️
-        return Err(format!("Expected 1024 bits, got {}", fingerprint_bits.len()).into());
+    // 1a. Several captures: enroll their bitwise majority (see shared/src/consensus.rs)
+    let mut samples = Vec::new();
+    if !options.samples.is_empty() {
+        if duress || factor != Factor::Fingerprint {
+            return Err("--sample is only for the primary finger".into());
+        }
+        samples.push(fingerprint_bits.clone());
+        for path in &options.samples {
+            samples.push(api::extract_template_with(path, template_bits)?);
+        }
+        consensus::check_sample_count(samples.len())?;
     }
+    let (fingerprint_bits, reliability_mask) = if samples.is_empty() {
+        (fingerprint_bits, None)
+    } else {
+        let voted = consensus::majority_vote(&samples);
+        let mask = consensus::reliability_mask(&samples, &voted);
+        let reliable = consensus::check_reliability_mask(&mask, template_bits)?;
+        say_tr!("client.samples_voted", samples.len(), reliable, template_bits);
+        (voted, Some(mask))
+    };
     
-    println!("✅ Extracted {} bits", fingerprint_bits.len());
+    // 1b. Rotated variants of the primary capture (see alignment.rs)
+    let mut rotations = Vec::new();
+    if options.rotations > 0 {
+        let FactorInput::Image(path) = input else {
+            return Err("--rotations is only for fingerprint images".into());
+        };
+        if duress || factor != Factor::Fingerprint {
+            return Err("--rotations is only for the primary finger".into());
+        }
+        template::check_rotation_count(options.rotations)?;
+        let angles = alignment::rotation_angles(options.rotations);
+        for &degrees in &angles {
+            rotations.push(api::extract_rotated(path, template_bits, degrees)?);
+        }
+        say_tr!("client.rotations", rotations.len(), angles.iter().map(|a| a.abs()).max().unwrap_or(0));
+    }
 
-    // 2. Generate Random Trivium Key/IV
-    println!("\n🔑 TRIVIUM KEY GENERATION:");
-    println!("{}", "─".repeat(70));
-    
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    
-    let key_u64: u64 = rng.gen();
-    let iv_u64: u64 = rng.gen();
-    
-    let key_bits = u64_to_bits_80(key_u64);
-    let iv_bits = u64_to_bits_80(iv_u64);
-    
-    println!("✅ Random key generated: 80 bits");
-    println!("✅ Random IV generated: 80 bits");
+    // 1c. Further fingers of a fused enrollment, each capture gated like the primary one
+    let mut fingers = Vec::new();
+    if !options.fingers.is_empty() {
+        if duress || factor != Factor::Fingerprint {
+            return Err("--fused-finger is only for the primary finger".into());
+        }
+        if !samples.is_empty() {
+            return Err("--sample can't be combined with --fused-finger".into());
+        }
+        fusion::check_finger_count(options.fingers.len() + 1)?;
+        for path in &options.fingers {
+            capture_quality::check_min_quality(&api::assess_capture(path)?, api::min_capture_quality_from_env()?)?;
+            fingers.push(api::extract_template_with(path, template_bits)?);
+        }
+        say_tr!("client.fingers", fingers.len() + 1, options.fusion);
+    }
 
-    // 3. Trivium Encryption
-    println!("\n🔐 TRIVIUM ENCRYPTION:");
-    println!("{}", "─".repeat(70));
-    
-    let mut trivium = Trivium::new(&key_bits, &iv_bits);
-    let ciphertext = trivium.process(&fingerprint_bits);
+    // 1d. Finger templates go out under the user's cancellable transform (see revocation.rs);
+    //     the primary finger issues one, further fingers must be enrolled under it
+    let transform = match input {
+        FactorInput::Image(_) if !duress && factor == Factor::Fingerprint => Some(revocation::enrollment_transform(user_id)?),
+        FactorInput::Image(_) => revocation::transform(user_id)?,
+        FactorInput::Pin(_) => None,
+    };
+    let (fingerprint_bits, quality_mask, reliability_mask) = match &transform {
+        Some(transform) => {
+            say_tr!("client.transform", transform.id);
+            samples = samples.iter().map(|bits| transform.apply(bits)).collect();
+            rotations = rotations.iter().map(|bits| transform.apply(bits)).collect();
+            fingers = fingers.iter().map(|bits| transform.apply(bits)).collect();
+            (
+                transform.apply(&fingerprint_bits),
+                quality_mask.map(|mask| transform.permute(&mask)),
+                reliability_mask.map(|mask| transform.permute(&mask)),
+            )
+        }
+        None => (fingerprint_bits, quality_mask, reliability_mask),
+    };
+    timer.lap("features");
     
-    println!("✅ Fingerprint encrypted: {} bits", ciphertext.len());
+    say_tr!("client.extracted", fingerprint_bits.len());
+    if let Some(quality) = &quality {
+        say_tr!(
+            "client.quality",
+            format!("{:.0}", quality.bit_balance * 100.0),
+            format!("{:.2}", quality.distinctiveness),
+            quality.valid_regions,
+            quality.total_regions
+        );
+        for warning in &quality.warnings {
+            say_tr!("client.quality_warning", warning);
+        }
+    }
 
-    // Sanity check
-    let mut trivium2 = Trivium::new(&key_bits, &iv_bits);
-    let decrypted_local = trivium2.process(&ciphertext);
-    let errors = hamming_distance(&decrypted_local, &fingerprint_bits);
+    // 2-3. Random Trivium Key/IV + Trivium Encryption
+    say_tr!("client.section_trivium");
+    say!("{}", "─".repeat(70));
     
-    if errors != 0 {
-        return Err(format!("Trivium sanity check failed: {} errors", errors).into());
-    }
+    let cipher = negotiated_cipher()?;
+    let template = api::encrypt_template(&fingerprint_bits, cipher)?;
+    timer.lap("trivium");
+    // Only the primary finger can be refreshed with deltas later (`update`)
+    let enrollment = (!duress && factor == Factor::Fingerprint)
+        .then(|| update::EnrollmentState::new(user_id, &template, &fingerprint_bits));
     
-    println!("✅ Trivium sanity check passed");
+    say_tr!("client.random_key", template.key_bits.len());
+    say_tr!("client.random_iv", template.iv_bits.len());
+    say_tr!("client.fingerprint_encrypted", template.ciphertext.len());
+    say_tr!("client.sanity_passed");
 
     // 4. FHE Key Management
-    println!("\n🔐 FHE KEY MANAGEMENT:");
-    println!("{}", "─".repeat(70));
+    say_tr!("client.section_key_management");
+    say!("{}", "─".repeat(70));
     
     let client_key_path = get_client_key_path();
     let server_key_bytes_opt: Option<Vec<u8>>;
 
     let client_key = if client_key_path.exists() {
-        println!("📂 Loading existing client key...");
-        let key_bytes = fs::read(&client_key_path)?;
-        let key = bincode::deserialize(&key_bytes)?;
-        println!("✅ Client key loaded from: {}", client_key_path.display());
+        say_tr!("client.loading_key");
+        let key = load_client_key()?;
+        say_tr!("client.key_loaded", client_key_path.display());
         server_key_bytes_opt = None; // Server key zaten var
         key
     } else {
-        println!("🔑 Generating new FHE keys (first time)...");
-        println!("⏱️  This may take ~10 seconds...");
+        say_tr!("client.generating_keys");
+        say_tr!("client.keygen_duration");
         
-        let config = ConfigBuilder::default().build();
-        let (client_key, server_key) = generate_keys(config);
+        let params = advisor::configured_parameter_set();
+        say_tr!("client.parameter_set", params);
+        let (client_key, server_key) = api::generate_fhe_keys_with(params);
         
         // Save client key
         fs::create_dir_all(client_key_path.parent().unwrap())?;
         let client_key_bytes = bincode::serialize(&client_key)?;
         fs::write(&client_key_path, client_key_bytes)?;
-        println!("✅ Client key saved to: {}", client_key_path.display());
+        say_tr!("client.key_saved", client_key_path.display());
         
         // Prepare server key for sending
         let server_key_bytes = bincode::serialize(&server_key)?;
         server_key_bytes_opt = Some(server_key_bytes);
-        println!("✅ Server key will be sent to server: ({} bytes)", 
+        say_tr!("client.server_key_size", 
                      server_key_bytes_opt.as_ref().unwrap().len());
 
         
         client_key
     };
+    // Made from the client key, so it can also be sent for a key registered earlier
+    let gpu_key_bytes = api::gpu_key_from_env(&client_key)?;
+    if let Some(bytes) = &gpu_key_bytes {
+        say_tr!("client.gpu_key_size", bytes.len());
+    }
+    timer.lap(if server_key_bytes_opt.is_some() { "keygen" } else { "key_load" });
 
     // 5. FHE Encryption (Key & IV)
-    println!("\n🔒 FHE ENCRYPTION:");
-    println!("{}", "─".repeat(70));
-    println!("⏱️  Encrypting Trivium key and IV...");
-    
-    let encrypted_key: Vec<FheBool> = key_bits
-        .iter()
-        .map(|&b| FheBool::encrypt(b, &client_key))
-        .collect();
+    say_tr!("client.section_fhe_encryption");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.encrypting_key_iv");
     
-    let encrypted_iv: Vec<FheBool> = iv_bits
-        .iter()
-        .map(|&b| FheBool::encrypt(b, &client_key))
-        .collect();
-    
-    let encrypted_key_bytes = bincode::serialize(&encrypted_key)?;
-    let encrypted_iv_bytes = bincode::serialize(&encrypted_iv)?;
+    let credential = load_credential()?;
+    let mut request = api::build_register_request(user_id, template, &client_key, server_key_bytes_opt)?
+        .with_factor(factor)
+        .with_api_key(api::api_key_from_env())
+        .with_credential_key(credential.public_key())
+        .with_session(open_session(user_id, &credential)?)
+        .with_soft(soft_profile(options.soft.as_ref(), &credential))
+        .with_replace_existing(options.replace)
+        .with_transform_id(transform.as_ref().map(|transform| transform.id.clone()))
+        .with_gpu_server_key(gpu_key_bytes);
+    let fuzzy_code = api::fuzzy_from_env()?.filter(|_| !duress && factor == Factor::Fingerprint);
+    if let Some((field_bits, errors)) = fuzzy_code {
+        // The code's error capacity replaces the threshold, weights and masks (see shared/src/fuzzy.rs)
+        if !samples.is_empty() || !rotations.is_empty() || !fingers.is_empty() {
+            return Err(format!("--sample, --rotations and --fused-finger can't be combined with {}", api::FUZZY_ENV).into());
+        }
+        let (sketch, _) = fuzzy::sketch(&fingerprint_bits, field_bits, errors, &client_key)?;
+        say_tr!("client.fuzzy_sketch", sketch.key_bits(template_bits), errors, (1 << field_bits) - 1);
+        request = request.with_fuzzy_sketch(sketch);
+    } else if !duress && factor == Factor::Fingerprint {
+        request = request.with_threshold(api::enrolled_threshold_from_env(template_bits, &client_key)?);
+        // A voted template's mask is its reliability mask; the first capture's would not fit it,
+        // nor the further fingers of a fused enrollment
+        if let Some(mask) = quality_mask.as_deref().filter(|_| reliability_mask.is_none() && fingers.is_empty()) {
+            request = request.with_quality_mask(quality_mask_for(mask, &client_key)?);
+        }
+        if let Some(weights) = api::bit_weights_from_env(template_bits)? {
+            say_tr!("client.bit_weights", template::compared_weight(&weights, None), template_bits);
+            let weights = transform.as_ref().map_or(weights.clone(), |transform| transform.permute(&weights));
+            request = request.with_bit_weights(Some(weights));
+        }
+    }
+    if duress {
+        request = request.with_duress();
+    }
+    if let Some(mask) = reliability_mask {
+        let encrypted = samples
+            .iter()
+            .map(|bits| api::encrypt_sample(bits, cipher, &client_key))
+            .collect::<Result<_, _>>()?;
+        request = request.with_samples(encrypted, mask);
+    }
+    if let Some(score) = capture_score.filter(|_| !duress && factor == Factor::Fingerprint) {
+        request = request.with_capture_quality(score);
+    }
+    if !rotations.is_empty() {
+        let encrypted = rotations
+            .iter()
+            .map(|bits| api::encrypt_sample(bits, cipher, &client_key))
+            .collect::<Result<_, _>>()?;
+        request = request.with_rotations(encrypted);
+    }
+    if !fingers.is_empty() {
+        let encrypted = fingers
+            .iter()
+            .map(|bits| api::encrypt_sample(bits, cipher, &client_key))
+            .collect::<Result<_, _>>()?;
+        request = request.with_fingers(encrypted, options.fusion);
+    }
+    if let Some(consent) = &options.consent {
+        say_tr!("client.consent", consent.reference, consent.purpose);
+        request = request.with_consent(consent.clone());
+    }
     
-    println!("✅ Encrypted key:  {} bytes", encrypted_key_bytes.len());
-    println!("✅ Encrypted IV:   {} bytes", encrypted_iv_bytes.len());
+    timer.lap("fhe_encrypt");
+    say_tr!("client.encrypted_key", request.encrypted_key_bytes.len());
+    say_tr!("client.encrypted_iv", request.encrypted_iv_bytes.len());
 
     // 6. Build and Send Request
-    println!("\n📤 SENDING REQUEST:");
-    println!("{}", "─".repeat(70));
+    say_tr!("client.section_sending");
+    say!("{}", "─".repeat(70));
 
     // 🔍 DEBUG
-    println!("🔍 Debug info:");
-    println!("   user_id: {}", user_id);
-    println!("   ciphertext: {} bits", ciphertext.len());
-    println!("   encrypted_key_bytes: {} bytes", encrypted_key_bytes.len());
-    println!("   encrypted_iv_bytes: {} bytes", encrypted_iv_bytes.len());
-    println!("   server_key_bytes: {}", 
-            if server_key_bytes_opt.is_some() { "Some(...)" } else { "None" });
-    
-    let request = RegisterRequest::new(
-        user_id.to_string(),
-        ciphertext,
-        encrypted_key_bytes,
-        encrypted_iv_bytes,
-        server_key_bytes_opt,
-    );
+    say_tr!("client.debug_info");
+    say!("   user_id: {}", request.user_id);
+    say!("   ciphertext: {} bits", request.ciphertext.len());
+    say!("   encrypted_key_bytes: {} bytes", request.encrypted_key_bytes.len());
+    say!("   encrypted_iv_bytes: {} bytes", request.encrypted_iv_bytes.len());
+    say!("   server_key_bytes: {}", 
+            if request.server_key_bytes.is_some() { "Some(...)" } else { "None" });
     
     // let req_json = serde_json::to_string_pretty(&request)?;  // ⬅️ YORUM SATIRI YAP
-    // println!("\n📄 Request JSON:");
-    // println!("{}", req_json);  // ⬅️ YORUM SATIRI YAP
+    // say!("\n📄 Request JSON:");
+    // say!("{}", req_json);  // ⬅️ YORUM SATIRI YAP
 
+    // The server holds one server key per tenant (see profile.rs)
+    let sent_fingerprint = request.server_key_bytes.as_deref().map(protocol::server_key_fingerprint);
+    if sent_fingerprint.is_some() {
+        profile::warn_shared_tenant();
+    }
     let req_json = serde_json::to_string_pretty(&request)?;
-    fs::write(REGISTER_REQ_PATH, req_json)?;
+    let slot = user_exchange(user_id)?;
+    slot.put(REGISTER_REQUEST, req_json.as_bytes())?;
+    timer.lap("upload");
     
-    println!("✅ Request sent to server!");
+    say_tr!("client.request_sent");
 
     // 7. Wait for Response
-    println!("\n⏳ WAITING FOR RESPONSE:");
-    println!("{}", "─".repeat(70));
+    say_tr!("client.section_waiting");
+    say!("{}", "─".repeat(70));
     
-    let response: RegisterResponse = wait_for_response(REGISTER_RESP_PATH, Duration::from_secs(30))?;
+    let response: RegisterResponse = wait_for_response(slot.as_ref(), REGISTER_RESPONSE, Duration::from_secs(30))?;
+    let response = response.with_quality(quality);
+    timer.lap("server");
     
     if response.success {
-        println!("✅ REGISTRATION SUCCESSFUL!");
-        println!("   User ID: {}", response.user_id);
-        println!("   Message: {}", response.message);
-        println!("   Timestamp: {}", response.timestamp);
+        if let Some(enrollment) = enrollment {
+            enrollment.save()?;
+        }
+        if let Some(fingerprint) = sent_fingerprint {
+            profile::record_server_key(fingerprint, true)?;
+        }
+        profile::check_reported_fingerprint(response.server_key_fingerprint.as_deref())?;
+        say_tr!("client.registration_successful");
+        say_tr!("client.response_user_id", response.user_id);
+        say_tr!("client.response_message", response.message);
+        say_tr!("client.response_timestamp", response.timestamp);
+        if let Some(count) = response.enrollment_count.filter(|&n| n > 1 && !duress && factor == Factor::Fingerprint) {
+            say_tr!("client.reenrolled", count);
+        }
+        if response.quality.as_ref().is_some_and(|q| q.recapture_recommended) {
+            say_tr!("client.quality_recapture");
+        }
     } else {
-        println!("❌ REGISTRATION FAILED!");
-        println!("   Message: {}", response.message);
+        say_tr!("client.registration_failed");
+        say_tr!("client.response_message", response.message);
     }
 
     // Cleanup
-    let _ = fs::remove_file(REGISTER_RESP_PATH);
+    let _ = slot.delete(REGISTER_RESPONSE);
 
-    Ok(())
+    Ok(response)
+}
+
+/// Parse `--consent-ref`, `--purpose` and `--retain-days` into a consent record
+fn parse_consent(args: &[String]) -> Result<Option<ConsentInfo>, Box<dyn std::error::Error>> {
+    let value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+
+    let reference = match value("--consent-ref") {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    let purpose = value("--purpose").ok_or("--consent-ref requires --purpose")?;
+    let retention_until = match value("--retain-days") {
+        Some(days) => {
+            let days: i64 = days.parse().map_err(|_| format!("Invalid --retain-days: {}", days))?;
+            Some((chrono::Utc::now() + chrono::Duration::days(days)).to_rfc3339())
+        }
+        None => None,
+    };
+
+    Ok(Some(ConsentInfo { reference, purpose, retention_until }))
+}
+
+/// Where a primary enrollment moves the old client key until the server answers
+fn previous_key_path() -> PathBuf {
+    get_client_key_path().with_extension("previous")
+}
+
+/// Drop the key a primary enrollment replaced, or restore it if the server
+/// rejected the enrollment (e.g. an unconfirmed re-enrollment)
+fn settle_previous_key(result: &Result<RegisterResponse, Box<dyn std::error::Error>>) {
+    let previous = previous_key_path();
+    if !previous.exists() {
+        return;
+    }
+    match result {
+        Ok(response) if response.success => {
+            let _ = fs::remove_file(&previous);
+        }
+        Ok(_) => {
+            let _ = fs::rename(&previous, get_client_key_path());
+        }
+        Err(_) => {}    // Outcome unknown; `recover` may still collect the result
+    }
 }
 
 // ==================== VERIFY MODE ====================
 
-fn handle_verify(user_id: &str, image_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n🔍 VERIFY MODE");
-    println!("{}", "─".repeat(70));
-    println!("👤 User ID: {}", user_id);
-    println!("🖼️  Image: {}", image_path);
+fn handle_verify(user_id: &str, image_path: &str, probe: &ProbeOptions) -> Result<VerifyOutcome, Box<dyn std::error::Error>> {
+    verify_reported(user_id, Factor::Fingerprint, &FactorInput::Image(image_path.to_string()), probe).0
+}
 
-    // Setup directories
-    fs::create_dir_all(EXCHANGE_DIR)?;
+/// Verify one factor, append the attempt to the history log and describe it for `--output json`
+fn verify_reported(
+    user_id: &str,
+    factor: Factor,
+    input: &FactorInput,
+    probe: &ProbeOptions,
+) -> (Result<VerifyOutcome, Box<dyn std::error::Error>>, CommandReport) {
+    let start = Instant::now();
+    let mut timer = PhaseTimer::start();
+    let result = verify(user_id, factor, input, probe, &mut timer);
+    record_telemetry("verify", &timer, result.is_ok());
 
-    // 1. Feature Extraction
-    println!("\n🔬 FEATURE EXTRACTION:");
-    println!("{}", "─".repeat(70));
-    println!("📷 Extracting probe fingerprint features...");
+    let entry = HistoryEntry::new("verify", user_id, &server_label()).with_duration(start.elapsed());
+    record_verify(entry, &result);
+
+    let report = CommandReport::new("verify", user_id).with_timings(&timer, start.elapsed());
+    let report = match &result {
+        Ok(outcome) => CommandReport {
+            success: outcome.match_result,
+            factor: Some(factor),
+            distance: outcome.distance,
+            similarity: outcome.similarity,
+            ..report
+        },
+        Err(e) => report.with_error(e.as_ref()),
+    };
+    (result, report)
+}
+
+/// Verify the fingerprint, falling back to alternate factors per the server policy.
+///
+/// The report covers all attempts: their phases in order, and the result of
+/// the accepted one (else of the last).
+fn handle_verify_with_fallback(
+    user_id: &str,
+    image_path: &str,
+    probe: &ProbeOptions,
+    fallbacks: &[(Factor, FactorInput)],
+) -> CommandReport {
+    let start = Instant::now();
+    let mut combined = CommandReport::new("verify", user_id);
+    let policy = match fetch_policy(user_id) {
+        Ok(resp) => resp.policy,
+        Err(e) => {
+            say_tr!("client.policy_unavailable", e);
+            FallbackPolicy { enabled: false, ..FallbackPolicy::default() }
+        }
+    };
+
+    let primary = FactorInput::Image(image_path.to_string());
+    let report = fallback::run_with_fallback(&policy, &primary, fallbacks, |factor, input| {
+        if factor != Factor::Fingerprint {
+            say_tr!("client.fallback_trying", factor);
+        }
+        // Declared attributes describe the primary finger only
+        let probe = ProbeOptions {
+            soft: probe.soft.clone().filter(|_| factor == Factor::Fingerprint),
+            partial: probe.partial,
+            ownership_challenge: probe.ownership_challenge,
+            fingers: if factor == Factor::Fingerprint { probe.fingers.clone() } else { Vec::new() },
+        };
+        let (result, run) = verify_reported(user_id, factor, input, &probe);
+        let mut timings = std::mem::take(&mut combined.timings);
+        timings.extend(run.timings);
+        combined = CommandReport { timings, ..run };
+        match result {
+            Ok(outcome) => Ok(outcome.match_result),
+            Err(e) if e.downcast_ref::<ResponseTimeout>().is_some() => Err(AttemptError::Timeout),
+            Err(e) if e.downcast_ref::<ServerRejected>().is_some() => Err(AttemptError::Rejected(e.to_string())),
+            Err(e) => Err(AttemptError::Failed(e.to_string())),
+        }
+    });
+
+    say!("\n{}", "═".repeat(70));
+    match report.accepted {
+        Some(factor) => say_tr!("client.authenticated_via", factor),
+        None => say_tr!("client.authentication_failed_after", report.attempts.len()),
+    }
+    for attempt in &report.attempts {
+        say!(
+            "   {:<14} {}",
+            attempt.factor.to_string(),
+            match (&attempt.error, attempt.matched) {
+                (Some(e), _) => shared::tr!("client.attempt_error", e),
+                (None, true) => shared::tr!("client.attempt_match"),
+                (None, false) => shared::tr!("client.attempt_no_match"),
+            }
+        );
+    }
+    say!("{}", "═".repeat(70));
+
+    combined.total_ms = start.elapsed().as_millis() as u64;
+    combined
+}
+
+/// Ask the server for the fallback policy applicable to this user
+fn fetch_policy(user_id: &str) -> Result<PolicyResponse, Box<dyn std::error::Error>> {
+    let request = PolicyRequest { user_id: user_id.to_string(), api_key: api::api_key_from_env() };
+    let slot = user_exchange(user_id)?;
+    slot.put(POLICY_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+
+    let response = wait_for_response(slot.as_ref(), POLICY_RESPONSE, Duration::from_secs(30));
+    let _ = slot.delete(POLICY_RESPONSE);
+    response
+}
+
+/// Template length for a new enrollment: `FINGERPRINT_TEMPLATE_BITS` (default 1024),
+/// checked against the lengths the server advertises in its status file
+fn registration_template_bits() -> Result<usize, Box<dyn std::error::Error>> {
+    let wanted = api::template_bits_from_env()?;
+    let status: Option<ServerStatus> = exchange()?
+        .get(SERVER_STATUS)?
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    match status {
+        Some(status) => Ok(template::negotiate(wanted, &status.supported_template_bits)?),
+        None => Ok(wanted),   // Server not running yet; it validates the request itself
+    }
+}
+
+/// Transciphering cipher: `FINGERPRINT_CIPHER` (default Trivium), checked against
+/// the ciphers the server advertises in its status file
+fn negotiated_cipher() -> Result<Cipher, Box<dyn std::error::Error>> {
+    let wanted = api::cipher_from_env()?;
+    let status: Option<ServerStatus> = exchange()?
+        .get(SERVER_STATUS)?
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    match status {
+        Some(status) => Ok(wanted.negotiate(&status.supported_ciphers)?),
+        None => Ok(wanted),   // Server not running yet; it validates the request itself
+    }
+}
+
+/// Template length of this machine's enrollment of `user_id`, else the configured one
+fn template_bits_for(user_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    match update::enrolled_template_bits(user_id) {
+        Some(bits) => Ok(bits),
+        None => api::template_bits_from_env(),
+    }
+}
+
+/// Enrollment credential, created on first use next to the client key
+fn load_credential() -> Result<ClientCredential, Box<dyn std::error::Error>> {
+    ClientCredential::load_or_create(&get_client_key_path().with_file_name("credential_key.bin"))
+}
+
+/// Run the session handshake (see shared/src/session.rs) before a register/verify.
+///
+/// Only possible once a server identity is pinned; without one there is
+/// nothing to check the server's half against and no session is sent.
+fn open_session(user_id: &str, credential: &ClientCredential) -> Result<Option<SessionBinding>, Box<dyn std::error::Error>> {
+    let Some(identity_key) = server_handshake()?.identity else { return Ok(None) };
+
+    let request = SessionRequest::new(user_id, api::api_key_from_env());
+    let slot = user_exchange(user_id)?;
+    slot.put(SESSION_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    let response: Result<SessionResponse, _> = wait_for_response(slot.as_ref(), SESSION_RESPONSE, Duration::from_secs(30));
+    let _ = slot.delete(SESSION_RESPONSE);
+    let response = response?;
+    if !response.success {
+        return Err(format!("Session handshake failed: {}", response.message).into());
+    }
+
+    let claim = SessionClaim {
+        session_id: response.session_id,
+        challenge: response.challenge,
+        expires_at: response.expires_at,
+    };
+    let digest = session::transcript_digest(user_id, &request.client_nonce, &response.server_nonce, &claim);
+    session::verify_signature(&identity_key, &digest, &response.signature)
+        .map_err(|e| format!("Session not signed by the pinned server identity: {}", e))?;
+    if claim.is_expired() {
+        return Err(format!("Server issued an already expired session ({})", claim.expires_at).into());
+    }
+    say_tr!("client.session_established", claim.session_id, claim.expires_at);
+    Ok(Some(credential.bind(&claim, &digest)))
+}
+
+/// Soft attributes as sent to the server, blinded with a key derived from the enrollment credential
+fn soft_profile(soft: Option<&SoftAttributes>, credential: &ClientCredential) -> Option<SoftProfile> {
+    let soft = soft?;
+    say_tr!("client.soft_attributes", if soft.blind { "blinded" } else { "clear" });
+    Some(soft.profile(&credential.derive_key(SOFT_BLINDING_CONTEXT)))
+}
+
+fn parse_fallbacks(args: &[String]) -> Vec<(Factor, FactorInput)> {
+    let mut fallbacks = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--fallback-finger" => {
+                if let Some(path) = iter.next() {
+                    fallbacks.push((Factor::SecondFinger, FactorInput::Image(path.clone())));
+                }
+            }
+            "--fallback-pin" => {
+                if let Some(pin) = iter.next() {
+                    fallbacks.push((Factor::Pin, FactorInput::Pin(pin.clone())));
+                }
+            }
+            _ => {}
+        }
+    }
+    fallbacks
+}
+
+/// Fill in the verify-specific fields of a history entry and append it
+fn record_verify(mut entry: HistoryEntry, result: &Result<VerifyOutcome, Box<dyn std::error::Error>>) {
+    match result {
+        Ok(outcome) => {
+            entry.success = true;
+            entry.match_result = Some(outcome.match_result);
+            entry.distance = outcome.distance;
+        }
+        Err(e) => entry.error = Some(e.to_string()),
+    }
+    history::record(&entry);
+}
+
+fn verify(
+    user_id: &str,
+    factor: Factor,
+    input: &FactorInput,
+    probe: &ProbeOptions,
+    timer: &mut PhaseTimer,
+) -> Result<VerifyOutcome, Box<dyn std::error::Error>> {
+    say_tr!("client.verify_title");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.user_id", user_id);
+    describe_input(input);
+    say_tr!("client.factor", factor);
+
+    let submitted = submit_verify(user_id, factor, input, probe, timer)?;
+    let slot = user_exchange(user_id)?;
+
+    // 7. Wait for Response
+    say_tr!("client.section_waiting");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.long_wait");
     
-    let probe_bits = extract_fingerprint_128bit(image_path)?;
+    let response = wait_for_verify(slot.as_ref(), Duration::from_secs(7200))?; // 2 hours timeout
+    timer.lap("server");
     
-    if probe_bits.len() != 1024 {  // ⬅️
-        return Err(format!("Expected 1024 bits, got {}", probe_bits.len()).into());
+    if !response.success {
+        say_tr!("client.verification_failed");
+        let _ = slot.delete(VERIFY_RESPONSE);
+        return Err(match &response.failure {
+            Some(failure) if failure.action == FailureAction::Reject => ServerRejected(failure.message.clone()).into(),
+            Some(failure) => format!("Server could not verify ({}): {}", failure.condition, failure.message).into(),
+            None => FingerprintError::from_response(response.error_code, verify_failure_message(&response)).into(),
+        });
     }
+    if let Some(failure) = &response.failure {
+        say_tr!("client.fail_open", failure.condition);
+    }
+
+    // 8. Decrypt Results
+    let expected_bits = template_bits_for(user_id)?;
+    if response.template_bits.is_some_and(|bits| bits != expected_bits) {
+        return Err(format!(
+            "Server matched {}-bit templates, this client sent {}",
+            response.template_bits.unwrap_or_default(),
+            expected_bits
+        )
+        .into());
+    }
+    let outcome = decrypt_verify_response(user_id, &response, Some(&submitted))?;
+    timer.lap("decrypt");
     
-    println!("✅ Extracted {} bits", probe_bits.len());
+    // 9. Display Results
+    print_verify_outcome(user_id, &outcome, &response);
+
+    // Cleanup
+    let _ = slot.delete(VERIFY_RESPONSE);
 
-    // 2. Generate Random Trivium Key/IV (DIFFERENT from enrolled!)
-    println!("\n🔑 TRIVIUM KEY GENERATION:");
-    println!("{}", "─".repeat(70));
+    Ok(outcome)
+}
+
+fn print_verify_outcome(user_id: &str, outcome: &VerifyOutcome, response: &VerifyResponse) {
+    say!("\n{}", "═".repeat(70));
+    if outcome.match_result {
+        say_tr!("client.authentication_successful");
+    } else {
+        say_tr!("client.authentication_failed");
+    }
+    say!("{}", "═".repeat(70));
+    say_tr!("client.result_user_id", user_id);
+    say_tr!("client.result_match", outcome.match_result);
+    // A weighted distance is out of the compared bits' total weight
+    let scale = outcome.weighted_bits.unwrap_or(outcome.compared_bits);
+    match (outcome.distance, outcome.similarity) {
+        (Some(distance), Some(similarity)) => {
+            say_tr!("client.result_distance", distance, scale);
+            say_tr!("client.result_similarity", format!("{:.2}", similarity * 100.0));
+        }
+        _ => say_tr!("client.result_match_only"),
+    }
+    let threshold = template::partial_threshold(template::match_threshold(outcome.template_bits), outcome.compared_bits, outcome.template_bits);
+    say_tr!(
+        "client.result_threshold",
+        template::MATCH_SIMILARITY_PERCENT,
+        template::weighted_threshold(threshold, scale, outcome.compared_bits)
+    );
+    say_tr!("client.result_timestamp", response.timestamp);
+    if let Some(backend) = response.compute_backend {
+        say_tr!("client.result_backend", backend);
+    }
     
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
+    // Debug info (if available)
+    if let Some(debug_match) = response.debug_server_match {
+        say_tr!("client.debug_server_side");
+        say_tr!("client.debug_server_match", debug_match);
+        if let Some(debug_dist) = response.debug_server_distance {
+            say_tr!("client.debug_server_distance", debug_dist, scale);
+        }
+    }
     
-    let key_u64: u64 = rng.gen();
-    let iv_u64: u64 = rng.gen();
+    say!("{}", "═".repeat(70));
+}
+
+/// What the result of a verify request this client sent must be signed for
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubmittedVerify {
+    pub request_id: String,
+    pub session: Option<SessionClaim>,
+    pub threshold: Option<Vec<u8>>,  // Encrypted threshold sent, which the result must be bound to
+}
+
+/// Sent requests whose results have not been read yet, by user
+fn submitted_verify_path() -> PathBuf {
+    get_client_key_path().with_file_name("submitted_verify.json")
+}
+
+fn load_submitted() -> std::collections::BTreeMap<String, SubmittedVerify> {
+    fs::read(submitted_verify_path())
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Keep what the result of a sent request must be signed for, so a later run
+/// (`recover`, the RPC `decrypt-result`) checks it like the sending run does
+fn remember_submitted(user_id: &str, submitted: &SubmittedVerify) -> Result<(), Box<dyn std::error::Error>> {
+    let mut pending = load_submitted();
+    pending.insert(user_id.to_string(), submitted.clone());
+    fs::write(submitted_verify_path(), serde_json::to_vec(&pending)?)?;
+    Ok(())
+}
+
+/// The last request this client sent for `user_id`, if its result is still unread
+fn submitted_verify(user_id: &str) -> Option<SubmittedVerify> {
+    load_submitted().remove(user_id)
+}
+
+fn forget_submitted(user_id: &str) {
+    let mut pending = load_submitted();
+    if pending.remove(user_id).is_some() {
+        let _ = serde_json::to_vec(&pending).map(|data| fs::write(submitted_verify_path(), data));
+    }
+}
+
+/// Per-bit quality mask of a capture, if `FINGERPRINT_QUALITY_MASK` asks for one
+fn quality_mask_for(mask: &[bool], client_key: &tfhe::ClientKey) -> Result<Option<QualityMask>, Box<dyn std::error::Error>> {
+    let quality_mask = api::quality_mask_from_env(mask, client_key)?;
+    if let Some(quality_mask) = &quality_mask {
+        let mode = if matches!(quality_mask, QualityMask::Encrypted(_)) { "encrypted" } else { "plain" };
+        say_tr!("client.quality_mask", mask.iter().filter(|&&k| k).count(), mask.len(), mode);
+    }
+    Ok(quality_mask)
+}
+
+/// Extract, encrypt and write a verify request without waiting for the result.
+fn submit_verify(
+    user_id: &str,
+    factor: Factor,
+    input: &FactorInput,
+    probe: &ProbeOptions,
+    timer: &mut PhaseTimer,
+) -> Result<SubmittedVerify, Box<dyn std::error::Error>> {
+    // 1. Feature Extraction
+    say_tr!("client.section_features");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.extracting_probe");
     
-    let key_bits = u64_to_bits_80(key_u64);
-    let iv_bits = u64_to_bits_80(iv_u64);
+    let template_bits = template_bits_for(user_id)?;
+    let (probe_bits, mask, quality_mask) = match input {
+        FactorInput::Image(path) => {
+            let (bits, coverage, quality_mask) = api::extract_probe(path, template_bits)?;
+            let mask = if probe.partial {
+                template::check_mask(&coverage, template_bits)?;
+                say_tr!("client.partial_coverage", coverage.iter().filter(|&&c| c).count(), coverage.len());
+                Some(coverage)
+            } else {
+                None
+            };
+            (bits, mask, Some(quality_mask))
+        }
+        _ => (api::template_from_input(input, template_bits)?, None, None),
+    };
+    // Coverage masks are per region, which the transform keeps in place
+    let transform = match input {
+        FactorInput::Image(_) => revocation::transform(user_id)?,
+        _ => None,
+    };
+    // A fused enrollment takes whole probes of all its fingers (see shared/src/fusion.rs)
+    let mut fingers = Vec::new();
+    if !probe.fingers.is_empty() {
+        if mask.is_some() {
+            return Err("--partial can't be combined with --fused-finger".into());
+        }
+        for path in &probe.fingers {
+            let bits = api::extract_template_with(path, template_bits)?;
+            fingers.push(match &transform {
+                Some(transform) => transform.apply(&bits),
+                None => bits,
+            });
+        }
+        say_tr!("client.fingers_probed", fingers.len() + 1);
+    }
+    let (probe_bits, quality_mask) = match &transform {
+        Some(transform) => (transform.apply(&probe_bits), quality_mask.map(|mask| transform.permute(&mask))),
+        None => (probe_bits, quality_mask),
+    };
+    let quality_mask = quality_mask.filter(|_| fingers.is_empty());
+    timer.lap("features");
     
-    println!("✅ Random key generated: 80 bits");
-    println!("✅ Random IV generated: 80 bits");
+    say_tr!("client.extracted", probe_bits.len());
 
-    // 3. Trivium Encryption
-    println!("\n🔐 TRIVIUM ENCRYPTION:");
-    println!("{}", "─".repeat(70));
+    // 2-3. Random Trivium Key/IV (DIFFERENT from enrolled!) + Trivium Encryption
+    say_tr!("client.section_trivium");
+    say!("{}", "─".repeat(70));
     
-    let mut trivium = Trivium::new(&key_bits, &iv_bits);
-    let ciphertext = trivium.process(&probe_bits);
+    let cipher = negotiated_cipher()?;
+    let template = api::encrypt_template(&probe_bits, cipher)?;
+    timer.lap("trivium");
     
-    println!("✅ Probe encrypted: {} bits", ciphertext.len());
+    say_tr!("client.random_key", template.key_bits.len());
+    say_tr!("client.random_iv", template.iv_bits.len());
+    say_tr!("client.probe_encrypted", template.ciphertext.len());
 
     // 4. Load Client Key
-    println!("\n🔐 FHE KEY LOADING:");
-    println!("{}", "─".repeat(70));
-    
-    let client_key_path = get_client_key_path();
+    say_tr!("client.section_key_loading");
+    say!("{}", "─".repeat(70));
     
-    if !client_key_path.exists() {
-        return Err("Client key not found! Please register first.".into());
-    }
+    let client_key = load_client_key()?;
+    timer.lap("key_load");
     
-    let key_bytes = fs::read(&client_key_path)?;
-    let client_key = bincode::deserialize(&key_bytes)?;
-    
-    println!("✅ Client key loaded from: {}", client_key_path.display());
+    say_tr!("client.key_loaded", get_client_key_path().display());
 
     // 5. FHE Encryption
-    println!("\n🔒 FHE ENCRYPTION:");
-    println!("{}", "─".repeat(70));
-    println!("⏱️  Encrypting Trivium key, IV, and constant...");
-    
-    let encrypted_key: Vec<FheBool> = key_bits
-        .iter()
-        .map(|&b| FheBool::encrypt(b, &client_key))
-        .collect();
+    say_tr!("client.section_fhe_encryption");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.encrypting_key_iv_constant");
     
-    let encrypted_iv: Vec<FheBool> = iv_bits
+    let credential = load_credential()?;
+    // Only an explicit threshold is sent: it can narrow the server's policy, never widen it
+    let threshold = api::encrypted_threshold_from_env(template_bits, mask.as_deref(), &client_key)?;
+    let quality_mask = match &quality_mask {
+        Some(mask) => quality_mask_for(mask, &client_key)?,
+        None => None,
+    };
+    let fingers = fingers
         .iter()
-        .map(|&b| FheBool::encrypt(b, &client_key))
-        .collect();
-    
-    let encrypted_true = FheBool::encrypt(true, &client_key);
+        .map(|bits| api::encrypt_sample(bits, cipher, &client_key))
+        .collect::<Result<_, _>>()?;
+    let request = api::build_verify_request(user_id, template, &client_key)?
+        .with_factor(factor)
+        .with_api_key(api::api_key_from_env())
+        .with_session(open_session(user_id, &credential)?)
+        .with_soft(soft_profile(probe.soft.as_ref(), &credential))
+        .with_mask(mask)
+        .with_encrypted_threshold(threshold)
+        .with_ownership_challenge(probe.ownership_challenge.then(|| api::ownership_public_key(&client_key)).transpose()?)
+        .with_quality_mask(quality_mask)
+        .with_transform_id(transform.map(|transform| transform.id))
+        .with_fingers(fingers)
+        .with_server_key_fingerprint(profile::server_key_fingerprint())
+        .with_result_mode(api::result_mode_from_env()?);
+    timer.lap("fhe_encrypt");
     
-    let encrypted_key_bytes = bincode::serialize(&encrypted_key)?;
-    let encrypted_iv_bytes = bincode::serialize(&encrypted_iv)?;
-    let encrypted_true_bytes = bincode::serialize(&encrypted_true)?;
-    
-    println!("✅ Encrypted key:  {} bytes", encrypted_key_bytes.len());
-    println!("✅ Encrypted IV:   {} bytes", encrypted_iv_bytes.len());
-    println!("✅ Encrypted true: {} bytes", encrypted_true_bytes.len());
+    say_tr!("client.encrypted_key", request.encrypted_key_bytes.len());
+    say_tr!("client.encrypted_iv", request.encrypted_iv_bytes.len());
+    if let Some(threshold) = &request.encrypted_threshold_bytes {
+        say_tr!("client.encrypted_threshold", threshold.len());
+    }
 
     // 6. Build and Send Request
-    println!("\n📤 SENDING REQUEST:");
-    println!("{}", "─".repeat(70));
-    
-    let request = VerifyRequest::new(
-        user_id.to_string(),
-        ciphertext,
-        encrypted_key_bytes,
-        encrypted_iv_bytes,
-        encrypted_true_bytes,
-    );
+    say_tr!("client.section_sending");
+    say!("{}", "─".repeat(70));
     
     let req_json = serde_json::to_string_pretty(&request)?;
-    fs::write(VERIFY_REQ_PATH, req_json)?;
+    let slot = user_exchange(user_id)?;
+    // A ticket left by an earlier run must not be taken for this request's
+    let _ = slot.delete(JOB_TICKET_FILE);
+    slot.put(VERIFY_REQUEST, req_json.as_bytes())?;
+    timer.lap("upload");
     
-    println!("✅ Request sent to server!");
-    println!("⚠️  Server will perform FHE operations (~30-60 minutes)");
+    say_tr!("client.request_sent");
+    say_tr!("client.server_duration");
 
-    // 7. Wait for Response
-    println!("\n⏳ WAITING FOR RESPONSE:");
-    println!("{}", "─".repeat(70));
-    println!("This may take a very long time...");
-    
-    let response: VerifyResponse = wait_for_response(VERIFY_RESP_PATH, Duration::from_secs(7200))?; // 2 hours timeout
-    
-    if !response.success {
-        println!("❌ VERIFICATION FAILED!");
+    let submitted = SubmittedVerify {
+        session: request.session.as_ref().map(SessionBinding::claim),
+        threshold: request.encrypted_threshold_bytes,
+        request_id: request.request_id.unwrap_or_default(),
+    };
+    remember_submitted(user_id, &submitted)?;
+    Ok(submitted)
+}
+
+/// Check the server identity signature on a verify result.
+///
+/// Once an identity is pinned every result must carry a valid signature by
+/// it for this request, session and threshold, and the session must not have expired;
+/// servers that never presented one only get a warning.
+fn check_result_signature(
+    user_id: &str,
+    sent: Option<&SubmittedVerify>,
+    response: &VerifyResponse,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(identity_key) = server_handshake()?.identity else {
+        say_tr!("client.result_unsigned");
         return Ok(());
+    };
+    let attestation = response
+        .attestation
+        .as_ref()
+        .ok_or("Result is not signed by the pinned server identity")?;
+    attestation
+        .verify(&identity_key, &response.encrypted_match_bytes, &response.encrypted_distance_bytes)
+        .map_err(|e| format!("Result attestation invalid: {}", e))?;
+    if sent.is_some_and(|sent| sent.request_id != attestation.request_id) || attestation.user_id != user_id {
+        return Err("Result attestation was issued for a different request".into());
+    }
+    attestation
+        .check_session(sent.and_then(|sent| sent.session.as_ref()))
+        .map_err(|e| format!("Result rejected: {}", e))?;
+    // A fail-open answer (see `FailureAction::Allow`) compared nothing
+    if response.failure.is_none() {
+        attestation
+            .check_threshold(sent.and_then(|sent| sent.threshold.as_deref()))
+            .map_err(|e| format!("Result rejected: {}", e))?;
     }
+    say_tr!("client.result_signed", attestation.key_id);
+    if let Some(session) = &attestation.session {
+        say_tr!("client.result_session", session.session_id, session.expires_at);
+    }
+    Ok(())
+}
 
-    // 8. Decrypt Results
-    println!("\n🔓 DECRYPTING RESULTS:");
-    println!("{}", "─".repeat(70));
-    
-    let encrypted_match: FheBool = bincode::deserialize(&response.encrypted_match_bytes)?;
-    let encrypted_distance: Vec<FheBool> = bincode::deserialize(&response.encrypted_distance_bytes)?;
-    
-    let match_result: bool = encrypted_match.decrypt(&client_key);
-    let distance_bits: Vec<bool> = encrypted_distance
-        .iter()
-        .map(|b| b.decrypt(&client_key))
-        .collect();
-    
-    // Convert 9-bit binary to decimal
-    let distance = bits_to_usize(&distance_bits);
-    let similarity = 1.0 - (distance as f32 / 1024.0);  // ⬅️
-    
-    // 9. Display Results
-    println!("\n{}", "═".repeat(70));
-    if match_result {
-        println!("✅ AUTHENTICATION SUCCESSFUL!");
+/// Check the server's signature on a verify response, then decrypt the
+/// match bit and distance with the local client key.
+///
+/// `sent` is the request id and session the request was sent with, when this client sent it
+/// (see `submitted_verify`).
+fn decrypt_verify_response(
+    user_id: &str,
+    response: &VerifyResponse,
+    sent: Option<&SubmittedVerify>,
+) -> Result<VerifyOutcome, Box<dyn std::error::Error>> {
+    check_result_signature(user_id, sent, response)?;
+    forget_submitted(user_id);
+
+    say_tr!("client.section_decrypting");
+    say!("{}", "─".repeat(70));
+
+    let client_key = load_client_key()?;
+    api::decrypt_verify_result(user_id, response, &client_key)
+}
+
+// ==================== OIDC LOGIN ====================
+
+/// Verify with an ownership challenge, trade its proof for a server receipt,
+/// then exchange the receipt for tokens at the OIDC provider
+fn handle_login(
+    user_id: &str,
+    image_path: &str,
+    config_path: &Path,
+    probe: &ProbeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = oidc::load_config(config_path)?;
+    let probe = ProbeOptions { ownership_challenge: true, ..probe.clone() };
+    let outcome = handle_verify(user_id, image_path, &probe)?;
+
+    if !outcome.match_result {
+        return Err("Verification failed, no tokens requested".into());
+    }
+    let ownership_proof = outcome.ownership_proof.ok_or("The server sent no ownership challenge")?;
+
+    let slot = user_exchange(user_id)?;
+    let request = ReceiptRequest { user_id: user_id.to_string(), api_key: api::api_key_from_env(), ownership_proof };
+    slot.put(RECEIPT_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    let response: ReceiptResponse = wait_for_response(slot.as_ref(), RECEIPT_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(RECEIPT_RESPONSE);
+    let receipt = match response.receipt {
+        Some(receipt) if response.success => receipt,
+        _ => return Err(FingerprintError::from_response(response.error_code, format!("Server refused a receipt: {}", response.message)).into()),
+    };
+
+    say_tr!("client.section_oidc");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.oidc_endpoint", config.token_endpoint);
+
+    let tokens = oidc::exchange_receipt(&config, &receipt)?;
+
+    say_tr!("client.tokens_issued", tokens.token_type);
+    if let Some(expires_in) = tokens.expires_in {
+        say_tr!("client.tokens_expire", expires_in);
+    }
+    if output::stdout_reserved() {
+        return Ok(());
+    }
+    // Tokens alone on stdout so they can be piped
+    println!("{}", serde_json::to_string_pretty(&tokens)?);
+
+    Ok(())
+}
+
+// ==================== ACCOUNT MODE ====================
+
+fn handle_account(operation: AccountOperation) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("client.account_title", operation);
+    say!("{}", "─".repeat(70));
+
+    // Sent from the slot of the user being renamed or merged away
+    let owner = match &operation {
+        AccountOperation::Rename { from, .. } => from,
+        AccountOperation::Merge { source, .. } => source,
+    };
+    let slot = user_exchange(owner)?;
+    let request = AccountRequest { operation: operation.clone(), api_key: api::api_key_from_env() };
+    slot.put(ACCOUNT_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    say_tr!("client.account_sent");
+
+    let response: AccountResponse = wait_for_response(slot.as_ref(), ACCOUNT_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(ACCOUNT_RESPONSE);
+
+    if !response.success {
+        return Err(FingerprintError::from_response(response.error_code, format!("Server rejected account operation: {}", response.message)).into());
+    }
+    say!("✅ {}", response.message);
+
+    // Keep the local history under the new identifier as well
+    let (from, to) = match &operation {
+        AccountOperation::Rename { from, to } => (from, to),
+        AccountOperation::Merge { source, target } => (source, target),
+    };
+    let moved = history::reassign_user(from, to)?;
+    say_tr!("client.history_moved", moved);
+
+    Ok(())
+}
+
+// ==================== CANCEL MODE ====================
+
+/// Ask the server to stop the user's queued or running verify job
+fn handle_cancel(user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let slot = user_exchange(user_id)?;
+    let job_id = match current_job(slot.as_ref())? {
+        Some((job_id, Some(status))) if status.state.is_finished() => {
+            say_tr!("client.cancel_finished", job_id, status.state);
+            return Ok(());
+        }
+        Some((job_id, _)) => job_id,
+        None => {
+            say_tr!("client.cancel_no_job", user_id);
+            return Ok(());
+        }
+    };
+
+    let request = CancelRequest { job_id: job_id.clone() };
+    slot.put(CANCEL_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    say_tr!("client.cancel_sent", job_id);
+
+    let response: CancelResponse = wait_for_response(slot.as_ref(), CANCEL_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(CANCEL_RESPONSE);
+    if response.cancelled {
+        say_tr!("client.cancel_done", job_id);
     } else {
-        println!("❌ AUTHENTICATION FAILED!");
+        say_tr!("client.cancel_not_running", job_id);
     }
-    println!("{}", "═".repeat(70));
-    println!("User ID:          {}", user_id);
-    println!("Match Result:     {}", match_result);
-    println!("Hamming Distance: {}/1024 bits", distance);  // ⬅️
-    println!("Similarity:       {:.2}%", similarity * 100.0);
-    println!("Threshold:        80%");  // ⬅️
-    println!("Timestamp:        {}", response.timestamp);
-    
-    // Debug info (if available)
-    if let Some(debug_match) = response.debug_server_match {
-        println!("\n🚨 DEBUG INFO (Server-side):");
-        println!("   Server Match:    {}", debug_match);
-        if let Some(debug_dist) = response.debug_server_distance {
-            println!("   Server Distance: {}/1024", debug_dist);  // ⬅️
+    Ok(())
+}
+
+// ==================== DELETE MODE ====================
+
+/// Remove the user's enrollment, first proving ownership with a fresh
+/// verify of `prove` if given (required when the server demands it)
+fn handle_delete(user_id: &str, prove: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("client.delete_title", user_id);
+    say!("{}", "─".repeat(70));
+
+    let ownership_proof = match prove {
+        Some(image_path) => {
+            say_tr!("client.delete_proving");
+            let probe = ProbeOptions { ownership_challenge: true, ..ProbeOptions::default() };
+            let outcome = handle_verify(user_id, image_path, &probe)?;
+            if !outcome.match_result {
+                return Err("Fingerprint did not match; the enrollment was not deleted".into());
+            }
+            Some(outcome.ownership_proof.ok_or("The server sent no ownership challenge")?)
         }
+        None => None,
+    };
+
+    let slot = user_exchange(user_id)?;
+    // Without a proof of ownership the server takes a session instead
+    let session = match ownership_proof {
+        Some(_) => None,
+        None => open_session(user_id, &load_credential()?)?,
+    };
+    let request = DeleteRequest { user_id: user_id.to_string(), api_key: api::api_key_from_env(), ownership_proof, session };
+    slot.put(DELETE_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+
+    let response: DeleteResponse = wait_for_response(slot.as_ref(), DELETE_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(DELETE_RESPONSE);
+    if !response.success {
+        return Err(FingerprintError::from_response(response.error_code, format!("Server rejected delete: {}", response.message)).into());
     }
-    
-    println!("{}", "═".repeat(70));
+    say_tr!("client.deleted", response.message);
+    Ok(())
+}
 
-    // Cleanup
-    let _ = fs::remove_file(VERIFY_RESP_PATH);
+// ==================== ADMIN MODE ====================
+
+/// Send an operator query; the answer goes to stdout as JSON
+fn handle_admin(command: AdminCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let admin_key = std::env::var(api::ADMIN_KEY_ENV).map_err(|_| format!("{} is not set", api::ADMIN_KEY_ENV))?;
+    // Not tied to a user; admin requests share a slot no user can have
+    let slot = user_exchange(protocol::ADMIN_USER_ID)?;
+    let request = AdminRequest { command, admin_key };
+    slot.put(ADMIN_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
 
+    let response: AdminResponse = wait_for_response(slot.as_ref(), ADMIN_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(ADMIN_RESPONSE);
+    if !response.success {
+        return Err(format!("Server rejected admin request: {}", response.message).into());
+    }
+    match command {
+        AdminCommand::List => println!("{}", serde_json::to_string_pretty(&response.users)?),
+        AdminCommand::Stats => println!("{}", serde_json::to_string_pretty(&response.stats)?),
+    }
     Ok(())
 }
 
 // ==================== HELPERS ====================
 
+fn describe_input(input: &FactorInput) {
+    match input {
+        FactorInput::Image(path) => say_tr!("client.input_image", path),
+        FactorInput::Pin(_) => say_tr!("client.input_pin"),
+    }
+}
+
 fn print_help() {
-    println!(r#"
-🔐 TRANSCIPHERING FINGERPRINT AUTHENTICATION CLIENT
+    println!("{}", shared::tr!("client.help"));
+}
 
-USAGE:
-  cargo run --release -- <MODE> <USER_ID> <IMAGE_PATH>
+/// The client key of the selected profile (see profile.rs)
+fn get_client_key_path() -> PathBuf {
+    profile::dir().join(profile::KEY_FILE)
+}
 
-MODES:
-  register   Register a new fingerprint template
-  verify     Verify a fingerprint against enrolled template
-  help       Show this help message
+/// Where requests are sent, as recorded in the history log
+fn server_label() -> String {
+    exchange()
+        .map(|t| t.describe())
+        .unwrap_or_else(|_| config::get().paths.exchange.clone())
+}
 
-EXAMPLES:
-  # Register a new user
-  cargo run --release -- register user_123 ../data/fingerprints/101_1.tif
+fn get_oidc_config_path() -> PathBuf {
+    get_client_key_path().with_file_name("oidc.json")
+}
 
-  # Verify user authentication
-  cargo run --release -- verify user_123 ../data/fingerprints/101_2.tif
+/// Opt-in performance telemetry (see shared::telemetry)
+fn get_telemetry_config_path() -> PathBuf {
+    get_client_key_path().with_file_name("telemetry.json")
+}
 
-NOTES:
-  - Client key is stored at: ~/.fingerprint_client/client_key.bin
-  - Server key is sent only during first registration
-  - Verification can take 30-60 minutes due to FHE operations
-    "#);
+/// Record phase timings of a run if telemetry is enabled
+fn record_telemetry(operation: &str, timer: &PhaseTimer, success: bool) {
+    let config = TelemetryConfig::load(&get_telemetry_config_path());
+    if !config.enabled {
+        return;
+    }
+    let template_bits = api::template_bits_from_env().unwrap_or(api::TEMPLATE_BITS);
+    let record = TelemetryRecord::new("client", operation, template_bits, timer)
+        .with_parameter_set(advisor::configured_parameter_set())
+        .with_success(success);
+    telemetry::submit(&config, &get_telemetry_config_path().with_file_name("telemetry.jsonl"), &record);
 }
 
-fn get_client_key_path() -> PathBuf {
-    #[cfg(target_os = "windows")]
-    {
-        let appdata = std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string());
-        Path::new(&appdata).join("fingerprint_client").join("client_key.bin")
+fn load_client_key() -> Result<tfhe::ClientKey, Box<dyn std::error::Error>> {
+    let client_key_path = get_client_key_path();
+    
+    if !client_key_path.exists() {
+        return Err("Client key not found! Please register first.".into());
     }
     
-    #[cfg(not(target_os = "windows"))]
-    {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        Path::new(&home).join(".fingerprint_client").join("client_key.bin")
+    let key_bytes = fs::read(&client_key_path)?;
+    Ok(bincode::deserialize(&key_bytes)?)
+}
+
+/// A failed verify response carries no message, only the kind of failure
+fn verify_failure_message(response: &VerifyResponse) -> String {
+    match response.error_code {
+        Some(ErrorCode::KeyMismatch) => format!(
+            "Server reported verification failure ({}): the stored server key doesn't belong to this client; \
+             if the client key was replaced, register the primary finger again",
+            ErrorCode::KeyMismatch
+        ),
+        Some(code) => format!("Server reported verification failure ({}): {}", code, code.message()),
+        None => "Server reported verification failure".to_string(),
+    }
+}
+
+/// The server's error policy denied the authentication (fail-closed); no fallback
+#[derive(Debug)]
+struct ServerRejected(String);
+
+impl std::fmt::Display for ServerRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rejected by server policy: {}", self.0)
+    }
+}
+
+impl std::error::Error for ServerRejected {}
+
+/// No response arrived within the timeout
+#[derive(Debug)]
+struct ResponseTimeout(Duration);
+
+impl std::fmt::Display for ResponseTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timeout waiting for response ({}s)", self.0.as_secs())
+    }
+}
+
+impl std::error::Error for ResponseTimeout {}
+
+/// Job id and its status (if published yet)
+type IssuedJob = (String, Option<JobStatus>);
+
+/// Job the server issued for the last verify request of a slot, with its status (if published yet)
+fn current_job(slot: &dyn Transport) -> Result<Option<IssuedJob>, Box<dyn std::error::Error>> {
+    let Some(data) = slot.get(JOB_TICKET_FILE)? else { return Ok(None) };
+    let ticket: JobTicket = serde_json::from_slice(&data)?;
+    if !valid_job_id(&ticket.job_id) {
+        return Err(format!("Server issued an invalid job id '{}'", ticket.job_id).into());
+    }
+    let status = match slot.get(&job_status_file(&ticket.job_id))? {
+        Some(data) => Some(serde_json::from_slice::<JobStatus>(&data)?),
+        None => None,
+    };
+    Ok(Some((ticket.job_id, status)))
+}
+
+/// Remove the ticket, status and response of a job whose result was consumed
+fn clear_job(slot: &dyn Transport, job_id: &str) {
+    let _ = slot.delete(&job_status_file(job_id));
+    let _ = slot.delete(JOB_TICKET_FILE);
+    let _ = slot.delete(VERIFY_RESPONSE);
+}
+
+/// Wait for a verify result, following the job status once the server issued a
+/// ticket. Servers without job tickets only ever write `verify_response.json`.
+fn wait_for_verify(slot: &dyn Transport, timeout: Duration) -> Result<VerifyResponse, Box<dyn std::error::Error>> {
+    let spinner = progress::waiting_spinner();
+    let result = follow_verify(slot, timeout, &spinner);
+    spinner.finish_and_clear();
+    result
+}
+
+fn follow_verify(slot: &dyn Transport, timeout: Duration, spinner: &ProgressBar) -> Result<VerifyResponse, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let mut shown: Option<(JobState, Option<String>)> = None;
+    // Last sign of life: the job status, or the server status until there is one
+    let mut heartbeat: Option<String> = None;
+    let mut status_checked: Option<Instant> = None;
+    
+    loop {
+        let mut state = None;
+        match current_job(slot)? {
+            Some((job_id, Some(status))) => {
+                let progress = (status.state, status.phase.clone());
+                if shown.as_ref() != Some(&progress) {
+                    spinner.suspend(|| match &status.phase {
+                        Some(phase) => say_tr!("client.job_phase", job_id, status.state, phase),
+                        None => say_tr!("client.job_state", job_id, status.state),
+                    });
+                    shown = Some(progress);
+                }
+                if status.state.is_finished() {
+                    clear_job(slot, &job_id);
+                    return match status.result {
+                        // Failures decided by the error policy are handled like any response
+                        Some(result) if result.success || result.failure.is_some() => Ok(result),
+                        _ => Err(status.error.unwrap_or_else(|| "Server reported verification failure".to_string()).into()),
+                    };
+                }
+                state = Some(match &status.phase {
+                    Some(phase) => format!("{}, {}", status.state, phase),
+                    None => status.state.to_string(),
+                });
+                heartbeat = Some(status.updated_at);
+            }
+            Some((_, None)) => {}
+            None => {
+                if slot.exists(VERIFY_RESPONSE)? {
+                    return wait_for_response(slot, VERIFY_RESPONSE, Duration::from_secs(5));
+                }
+            }
+        }
+        if state.is_none() && status_checked.is_none_or(|checked| checked.elapsed() >= HEARTBEAT_REFRESH) {
+            // Only a sign of life: a failed read is tried again at the next refresh
+            let status: Option<ServerStatus> = exchange()
+                .ok()
+                .and_then(|exchange| exchange.get(SERVER_STATUS).ok().flatten())
+                .and_then(|data| serde_json::from_slice(&data).ok());
+            heartbeat = status.map(|status| status.updated_at).or(heartbeat);
+            status_checked = Some(Instant::now());
+        }
+        let age = heartbeat.as_deref().and_then(|at| progress::heartbeat_age(at, chrono::Utc::now()));
+        spinner.set_message(progress::waiting_message(start.elapsed(), age, state.as_deref()));
+        
+        if start.elapsed() > timeout {
+            return Err(ResponseTimeout(timeout).into());
+        }
+        
+        std::thread::sleep(slot.poll_interval());
     }
 }
 
 fn wait_for_response<T: for<'de> serde::Deserialize<'de>>(
-    path: &str,
+    exchange: &dyn Transport,
+    name: &str,
     timeout: Duration,
 ) -> Result<T, Box<dyn std::error::Error>> {
     let start = Instant::now();
-    let path = Path::new(path);
     
     loop {
-        if path.exists() {
-            // Wait a bit for file to be fully written
-            std::thread::sleep(Duration::from_millis(200));
-            
-            let data = fs::read_to_string(path)?;
-            
-            match serde_json::from_str::<T>(&data) {
+        if let Some(data) = exchange.get(name)? {
+            match serde_json::from_slice::<T>(&data) {
                 Ok(response) => return Ok(response),
                 Err(e) => {
-                    // Retry once
+                    // Retry once (older servers wrote responses in place)
                     std::thread::sleep(Duration::from_millis(200));
-                    let data2 = fs::read_to_string(path)?;
-                    let response2 = serde_json::from_str::<T>(&data2)
+                    let data2 = exchange.get(name)?.unwrap_or_default();
+                    let response2 = serde_json::from_slice::<T>(&data2)
                         .map_err(|_| format!("Failed to parse response: {}", e))?;
                     return Ok(response2);
                 }
@@ -483,19 +1894,9 @@ fn wait_for_response<T: for<'de> serde::Deserialize<'de>>(
         }
 
         if start.elapsed() > timeout {
-            return Err(format!("Timeout waiting for response ({}s)", timeout.as_secs()).into());
+            return Err(ResponseTimeout(timeout).into());
         }
 
-        std::thread::sleep(Duration::from_millis(500));
+        std::thread::sleep(exchange.poll_interval());
     }
 }
-
-fn bits_to_usize(bits: &[bool]) -> usize {
-    let mut result = 0;
-    for (i, &bit) in bits.iter().enumerate() {
-        if bit {
-            result += 1 << i;
-        }
-    }
-    result
-}
\ No newline at end of file
@@ -0,0 +1,94 @@
+//! OIDC token-exchange integration (RFC 8693).
+//!
+//! After a matching verification the client answers its ownership challenge
+//! and gets a server receipt for the result in return (see
+//! `shared::attestation`). The client presents it as the subject token of a
+//! token-exchange grant at the configured OIDC provider, which validates the
+//! receipt with the server's attestation key and issues tokens for the user.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const RECEIPT_TOKEN_TYPE: &str = "urn:fingerprint-fhe:params:oauth:token-type:verification-receipt";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcConfig {
+    pub token_endpoint: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,        // client_secret_basic if set
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub requested_token_type: Option<String>,
+    #[serde(default = "default_subject_token_type")]
+    pub subject_token_type: String,
+}
+
+fn default_subject_token_type() -> String {
+    RECEIPT_TOKEN_TYPE.to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub issued_token_type: Option<String>,
+    pub token_type: String,
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub id_token: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+pub fn load_config(path: &Path) -> Result<OidcConfig, Box<dyn std::error::Error>> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read OIDC config {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Exchange a verification receipt for tokens at the provider's token endpoint
+pub fn exchange_receipt(config: &OidcConfig, receipt: &str) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let mut form: Vec<(&str, &str)> = vec![
+        ("grant_type", TOKEN_EXCHANGE_GRANT),
+        ("subject_token", receipt),
+        ("subject_token_type", &config.subject_token_type),
+    ];
+    if let Some(audience) = &config.audience {
+        form.push(("audience", audience));
+    }
+    if let Some(scope) = &config.scope {
+        form.push(("scope", scope));
+    }
+    if let Some(requested) = &config.requested_token_type {
+        form.push(("requested_token_type", requested));
+    }
+
+    let mut request = ureq::post(&config.token_endpoint).set("Accept", "application/json");
+    match &config.client_secret {
+        Some(secret) => {
+            let credentials = STANDARD.encode(format!("{}:{}", config.client_id, secret));
+            request = request.set("Authorization", &format!("Basic {}", credentials));
+        }
+        None => form.push(("client_id", &config.client_id)),
+    }
+
+    match request.send_form(&form) {
+        Ok(response) => Ok(response.into_json()?),
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            Err(format!("Token exchange rejected (HTTP {}): {}", code, body).into())
+        }
+        Err(e) => Err(format!("Token exchange failed: {}", e).into()),
+    }
+}
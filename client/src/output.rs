@@ -0,0 +1,139 @@
+//! Human-readable console output.
+//!
+//! In RPC mode stdout carries JSON-RPC responses, so progress lines are
+//! redirected to stderr with `say!`. With `--output json` the same happens
+//! and `register`/`verify` end with one [`CommandReport`] on stdout
+//! (`register-batch` with a summary holding one per entry).
+
+use serde::Serialize;
+use shared::telemetry::{Phase, PhaseTimer};
+use shared::{ErrorCode, Factor};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Route all subsequent `say!` output to stderr
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+pub fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// `--output json`: progress to stderr, a [`CommandReport`] to stdout
+pub fn use_json() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+    reserve_stdout();
+}
+
+pub fn json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Outcome of a `register` or `verify`, printed with `--output json`
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CommandReport {
+    pub command: String,                // "register" / "verify"
+    pub success: bool,                  // Enrollment stored / user verified
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factor: Option<Factor>,         // Factor that verified the user, after fallbacks
+    pub distance: Option<usize>,
+    pub similarity: Option<f32>,
+    pub timings: Vec<Phase>,            // Client phases in order (ms), `server` = waiting for the result
+    pub total_ms: u64,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+impl CommandReport {
+    pub fn new(command: &str, user_id: &str) -> Self {
+        CommandReport { command: command.to_string(), user_id: user_id.to_string(), ..Self::default() }
+    }
+
+    pub fn with_timings(mut self, timer: &PhaseTimer, total: Duration) -> Self {
+        self.timings.extend(timer.phases().iter().cloned());
+        self.total_ms = total.as_millis() as u64;
+        self
+    }
+
+    pub fn with_error(mut self, error: &(dyn std::error::Error + 'static)) -> Self {
+        self.error = Some(error.to_string());
+        self.error_code = Some(ErrorCode::of(error));
+        self
+    }
+
+    /// Print as the command's only stdout line (`--output json` only)
+    pub fn print(&self) {
+        if json() {
+            match serde_json::to_string(self) {
+                Ok(line) => println!("{}", line),
+                Err(e) => eprintln!("⚠️  Could not write the JSON report: {}", e),
+            }
+        }
+    }
+}
+
+/// `println!` that moves to stderr when stdout is reserved
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::stdout_reserved() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `say!` of a message from the shared catalog: `say_tr!("client.user_id", user_id)`
+#[macro_export]
+macro_rules! say_tr {
+    ($($t:tt)*) => {
+        $crate::say!("{}", ::shared::tr!($($t)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::FingerprintError;
+
+    #[test]
+    fn reports_serialize_with_their_error_code() {
+        let error = FingerprintError::NotFound("no enrollment for alice".into());
+        let report = CommandReport {
+            timings: vec![Phase { name: "features".into(), ms: 12 }],
+            total_ms: 15,
+            ..CommandReport::new("verify", "alice")
+        }
+        .with_error(&error);
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "command": "verify",
+                "success": false,
+                "user_id": "alice",
+                "distance": null,
+                "similarity": null,
+                "timings": [{ "name": "features", "ms": 12 }],
+                "total_ms": 15,
+                "error": error.to_string(),
+                "error_code": "not_found",
+            })
+        );
+    }
+
+    #[test]
+    fn the_factor_is_only_reported_once_known() {
+        let report = CommandReport { success: true, factor: Some(Factor::SecondFinger), distance: Some(41), ..CommandReport::new("verify", "bob") };
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["factor"], "second_finger");
+        assert_eq!(json["distance"], 41);
+        assert!(json["error"].is_null());
+    }
+}
@@ -0,0 +1,128 @@
+//! Server identity pinning.
+//!
+//! The identity key a server publishes in its handshake is remembered per
+//! exchange location in `~/.fingerprint_client/known_servers.json` the first
+//! time the client talks to it (trust on first use), or configured up front
+//! with `FINGERPRINT_SERVER_IDENTITY` (base64 Ed25519 public key). From then
+//! on a handshake or verify result signed by any other key is rejected, so an
+//! impostor server can't feed the client its own encrypted "match" bits.
+//!
+//! After a legitimate key change, remove the entry from known_servers.json.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use client::say_tr;
+use shared::identity::{identity_key_id, verified_identity};
+use shared::sealed::Handshake;
+
+/// Pinned identity key from the environment (wins over known_servers.json)
+pub const IDENTITY_ENV: &str = "FINGERPRINT_SERVER_IDENTITY";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct KnownServer {
+    identity_key: String,   // base64 Ed25519 public key
+    key_id: String,
+    first_seen: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct KnownServers {
+    #[serde(default)]
+    servers: BTreeMap<String, KnownServer>,   // By exchange location
+}
+
+impl KnownServers {
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn known_servers_path() -> PathBuf {
+    crate::get_client_key_path().with_file_name("known_servers.json")
+}
+
+fn configured_identity() -> Result<Option<[u8; 32]>, Box<dyn std::error::Error>> {
+    let Ok(encoded) = std::env::var(IDENTITY_ENV) else { return Ok(None) };
+    let bytes = STANDARD.decode(encoded.trim()).map_err(|e| format!("Invalid {}: {}", IDENTITY_ENV, e))?;
+    let key: [u8; 32] = bytes.try_into().map_err(|_| format!("{} must be a 32-byte key", IDENTITY_ENV))?;
+    Ok(Some(key))
+}
+
+/// Check the handshake of `location` against the pinned identity, pinning it on first use.
+///
+/// A missing handshake counts as presenting no identity. Returns the
+/// identity results must be signed with, or `None` for a server without an
+/// identity that was never pinned.
+pub fn check(location: &str, handshake: Option<&Handshake>) -> Result<Option<[u8; 32]>, Box<dyn std::error::Error>> {
+    let presented = match handshake {
+        Some(handshake) => verified_identity(handshake)?,
+        None => None,
+    };
+    let path = known_servers_path();
+    let mut known = KnownServers::load(&path)?;
+
+    let pinned = match configured_identity()? {
+        Some(key) => Some(key),
+        None => match known.servers.get(location) {
+            Some(entry) => Some(decode_key(&entry.identity_key)?),
+            None => None,
+        },
+    };
+
+    match (pinned, presented) {
+        (Some(pinned), Some(presented)) if pinned == presented => Ok(Some(pinned)),
+        (Some(pinned), Some(presented)) => Err(format!(
+            "Server identity mismatch for {}: pinned {}, presented {} (possible impostor; \
+             if the server key was rotated, remove it from {})",
+            location,
+            identity_key_id(&pinned),
+            identity_key_id(&presented),
+            path.display()
+        )
+        .into()),
+        (Some(pinned), None) => Err(format!(
+            "Server at {} no longer presents its pinned identity {}",
+            location,
+            identity_key_id(&pinned)
+        )
+        .into()),
+        (None, Some(presented)) => {
+            let key_id = identity_key_id(&presented);
+            known.servers.insert(
+                location.to_string(),
+                KnownServer {
+                    identity_key: STANDARD.encode(presented),
+                    key_id: key_id.clone(),
+                    first_seen: chrono::Utc::now().to_rfc3339(),
+                },
+            );
+            known.save(&path)?;
+            say_tr!("client.identity_pinned", key_id, location);
+            Ok(Some(presented))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+fn decode_key(encoded: &str) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    let bytes = STANDARD.decode(encoded)?;
+    Ok(bytes.try_into().map_err(|_| "Pinned identity key must be 32 bytes")?)
+}
@@ -0,0 +1,424 @@
+//! `--profile <name>`: separate key stores for several users of one machine.
+//!
+//! Without a profile the client keeps its key, credential, transforms and
+//! logs in `~/.fingerprint_client/` as before. A named profile keeps all of
+//! them in `~/.fingerprint_client/<name>/` instead, so registering one user
+//! no longer replaces the key another user's enrollment was encrypted under.
+//!
+//! The server holds one server key per tenant, so each profile also records
+//! in `server_keys.json` where its server key was sent. Sending it to an
+//! exchange and tenant another profile's key was sent to replaces that key,
+//! which `register` warns about. Each record keeps the fingerprint of the key
+//! sent, which verify requests name; a key sent before fingerprints were
+//! recorded takes the first one a register response reports. `keys list` shows the profiles and their
+//! records; `keys delete <name>` removes a profile's store once confirmed,
+//! or with `--yes` where there is no terminal to ask on.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use client::api;
+use client::{say, say_tr};
+use shared::attestation::sha256_hex;
+
+use crate::server_label;
+
+/// Profile to use when `--profile` isn't given
+pub const PROFILE_ENV: &str = "FINGERPRINT_PROFILE";
+const SERVER_KEYS_FILE: &str = "server_keys.json";
+/// The client key, in the profile's directory
+pub const KEY_FILE: &str = "client_key.bin";
+/// Directories of the default store that aren't profiles (see agent.rs)
+const RESERVED_NAMES: [&str; 2] = ["default", "capture"];
+
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Where a profile's server key was sent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerKeyRecord {
+    pub server: String,             // Exchange location, as in the history log
+    pub tenant: Option<String>,     // SHA-256 prefix of the API key (None = default tenant)
+    pub sent_at: String,
+    #[serde(default)]
+    pub fingerprint: Option<String>, // Of the server key sent (see `shared::protocol::server_key_fingerprint`)
+}
+
+/// Take the global `--profile <name>` out of the arguments, or read `FINGERPRINT_PROFILE`
+pub fn take_profile(args: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let name = match args.iter().position(|a| a == "--profile") {
+        Some(i) => {
+            let name = args.get(i + 1).cloned().ok_or("--profile needs a name")?;
+            args.drain(i..i + 2);
+            Some(name)
+        }
+        None => std::env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty()),
+    };
+    let name = match name {
+        Some(name) if name == "default" => None,
+        Some(name) => {
+            check_name(&name)?;
+            Some(name)
+        }
+        None => None,
+    };
+    let _ = PROFILE.set(name);
+    Ok(())
+}
+
+/// Profile names are directory names: letters, digits, `-` and `_`
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid || RESERVED_NAMES.contains(&name) {
+        return Err(format!("Invalid profile name '{}' (letters, digits, - and _, at most 32)", name));
+    }
+    Ok(())
+}
+
+/// The selected profile (None = default)
+pub fn current() -> Option<&'static str> {
+    PROFILE.get().and_then(|name| name.as_deref())
+}
+
+/// Directory of the default store; named profiles live below it
+pub fn root() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        let appdata = std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string());
+        Path::new(&appdata).join("fingerprint_client")
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        Path::new(&home).join(".fingerprint_client")
+    }
+}
+
+fn dir_of(name: Option<&str>) -> PathBuf {
+    dir_in(&root(), name)
+}
+
+fn dir_in(root: &Path, name: Option<&str>) -> PathBuf {
+    match name {
+        Some(name) => root.join(name),
+        None => root.to_path_buf(),
+    }
+}
+
+/// Directory of the selected profile's store
+pub fn dir() -> PathBuf {
+    dir_of(current())
+}
+
+/// Named profiles, by directory name
+fn named_profiles() -> Vec<String> {
+    named_profiles_in(&root())
+}
+
+fn named_profiles_in(root: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(root) else { return Vec::new() };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| check_name(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Records of the store in `dir`; an unreadable file counts as none
+fn server_keys_in(dir: &Path) -> Vec<ServerKeyRecord> {
+    fs::read(dir.join(SERVER_KEYS_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Tenant label of an API key, without keeping the key itself
+fn tenant_label(api_key: Option<&str>) -> Option<String> {
+    api_key.map(|key| sha256_hex(key.as_bytes())[..12].to_string())
+}
+
+/// Record that the current exchange and tenant now hold the selected profile's
+/// server key, of `fingerprint`; `fresh` (a new key) drops the records of the old one
+pub fn record_server_key(fingerprint: String, fresh: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let record = ServerKeyRecord {
+        server: server_label(),
+        tenant: tenant_label(api::api_key_from_env().as_deref()),
+        sent_at: chrono::Utc::now().to_rfc3339(),
+        fingerprint: Some(fingerprint),
+    };
+    write_record(&dir(), record, fresh)
+}
+
+/// Replace the record of the same exchange and tenant in the store in `dir`
+fn write_record(dir: &Path, record: ServerKeyRecord, fresh: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut records = if fresh { Vec::new() } else { server_keys_in(dir) };
+    records.retain(|r| r.server != record.server || r.tenant != record.tenant);
+    records.push(record);
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(SERVER_KEYS_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(&records)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// First 12 hex digits, enough to tell fingerprints apart
+fn short(fingerprint: &str) -> &str {
+    fingerprint.get(..12).unwrap_or(fingerprint)
+}
+
+/// Record of the selected profile's server key at the current exchange and tenant
+fn current_record() -> Option<ServerKeyRecord> {
+    let server = server_label();
+    let tenant = tenant_label(api::api_key_from_env().as_deref());
+    record_in(&dir(), &server, tenant.as_deref())
+}
+
+fn record_in(dir: &Path, server: &str, tenant: Option<&str>) -> Option<ServerKeyRecord> {
+    server_keys_in(dir).into_iter().find(|r| r.server == server && r.tenant.as_deref() == tenant)
+}
+
+/// Fingerprint of the server key the current exchange and tenant should hold
+/// for the selected profile (None if it was sent before fingerprints were recorded)
+pub fn server_key_fingerprint() -> Option<String> {
+    current_record().and_then(|record| record.fingerprint)
+}
+
+/// Warn if the fingerprint a register response reports isn't the one of the
+/// server key this profile sent: verifications would then be refused
+pub fn check_reported_fingerprint(reported: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(reported) = reported else { return Ok(()) };
+    let record = ServerKeyRecord {
+        server: server_label(),
+        tenant: tenant_label(api::api_key_from_env().as_deref()),
+        sent_at: chrono::Utc::now().to_rfc3339(),
+        fingerprint: Some(reported.to_string()),
+    };
+    match compare_reported(&dir(), record)? {
+        Reported::Matches => {}
+        Reported::Recorded => say_tr!("profile.fingerprint_recorded", short(reported)),
+        Reported::Mismatch(expected) => say_tr!("profile.fingerprint_mismatch", short(reported), short(&expected)),
+    }
+    Ok(())
+}
+
+/// What a reported fingerprint says about the key this profile sent
+#[derive(Debug, PartialEq)]
+enum Reported {
+    Matches,
+    /// The key was sent before fingerprints were recorded; the reported one is kept from now on
+    Recorded,
+    /// The server holds another key than the recorded one
+    Mismatch(String),
+}
+
+/// Compare `reported` with the record of its exchange and tenant in `dir`,
+/// recording it if that has no fingerprint yet
+fn compare_reported(dir: &Path, mut reported: ServerKeyRecord) -> Result<Reported, Box<dyn std::error::Error>> {
+    let existing = record_in(dir, &reported.server, reported.tenant.as_deref());
+    match existing {
+        Some(ServerKeyRecord { fingerprint: Some(expected), .. }) if Some(&expected) == reported.fingerprint.as_ref() => Ok(Reported::Matches),
+        Some(ServerKeyRecord { fingerprint: Some(expected), .. }) => Ok(Reported::Mismatch(expected)),
+        existing => {
+            if let Some(existing) = existing {
+                reported.sent_at = existing.sent_at;
+            }
+            write_record(dir, reported, false)?;
+            Ok(Reported::Recorded)
+        }
+    }
+}
+
+/// Warn before the selected profile's server key replaces another profile's
+/// at the current exchange and tenant
+pub fn warn_shared_tenant() {
+    let server = server_label();
+    let tenant = tenant_label(api::api_key_from_env().as_deref());
+    for name in sharing_profiles(&root(), current(), &server, tenant.as_deref()) {
+        say_tr!("profile.shared_tenant", name.as_deref().unwrap_or("default"), server);
+    }
+}
+
+/// Profiles other than `current` under `root` whose server key went to `server` and `tenant`
+fn sharing_profiles(root: &Path, current: Option<&str>, server: &str, tenant: Option<&str>) -> Vec<Option<String>> {
+    std::iter::once(None)
+        .chain(named_profiles_in(root).into_iter().map(Some))
+        .filter(|name| name.as_deref() != current)
+        .filter(|name| {
+            server_keys_in(&dir_in(root, name.as_deref()))
+                .iter()
+                .any(|r| r.server == server && r.tenant.as_deref() == tenant)
+        })
+        .collect()
+}
+
+/// `keys list`: every profile, whether it has a client key and where its server key is
+pub fn list() -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("profile.list_title");
+    say!("{}", "─".repeat(70));
+    for name in std::iter::once(None).chain(named_profiles().into_iter().map(Some)) {
+        let dir = dir_of(name.as_deref());
+        let marker = if name.as_deref() == current() { "*" } else { " " };
+        let key = if dir.join(KEY_FILE).exists() { "✅" } else { "—" };
+        say_tr!("profile.entry", marker, name.as_deref().unwrap_or("default"), key, dir.display());
+        for record in server_keys_in(&dir) {
+            say_tr!(
+                "profile.server_key",
+                record.server,
+                record.tenant.as_deref().unwrap_or("default"),
+                record.fingerprint.as_deref().map_or("—", short),
+                record.sent_at
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `keys delete <name> [--yes]`: remove a named profile's store, once confirmed
+pub fn delete(name: &str, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if name == "default" {
+        return Err("The default profile can't be deleted; it holds the named ones".into());
+    }
+    check_name(name)?;
+    let dir = dir_of(Some(name));
+    if !dir.is_dir() {
+        return Err(format!("No profile '{}' in {}", name, root().display()).into());
+    }
+    if current() == Some(name) {
+        say_tr!("profile.delete_active", name);
+    }
+    if !yes && !confirm_delete(name, &dir)? {
+        say_tr!("profile.delete_kept", name);
+        return Ok(());
+    }
+    let records = server_keys_in(&dir);
+    fs::remove_dir_all(&dir)?;
+    say_tr!("profile.deleted", name, dir.display());
+    if !records.is_empty() {
+        say_tr!("profile.deleted_server_keys", records.len());
+    }
+    Ok(())
+}
+
+/// Ask before a client key is removed; without a terminal to ask on, `--yes` is required
+fn confirm_delete(name: &str, dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    if !io::stdin().is_terminal() {
+        return Err(format!("Deleting profile '{}' removes its client key; pass --yes to confirm", name).into());
+    }
+    eprint!(
+        "{} {} ",
+        shared::tr!("profile.delete_confirm", name, dir.display()),
+        shared::tr!("profile.delete_choices")
+    );
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    // y(es) / e(vet)
+    Ok(answer.trim().to_lowercase().starts_with(['y', 'e']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(server: &str, tenant: Option<&str>, fingerprint: &str) -> ServerKeyRecord {
+        ServerKeyRecord {
+            server: server.to_string(),
+            tenant: tenant.map(str::to_string),
+            sent_at: "2026-01-01T00:00:00Z".to_string(),
+            fingerprint: Some(fingerprint.to_string()),
+        }
+    }
+
+    fn fresh_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn profile_names_are_plain_directory_names() {
+        for name in ["alice", "work-2", "a_b", &"x".repeat(32)] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "default", "capture", "../alice", "a/b", "a b", "ä", &"x".repeat(33)] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_record_replaces_the_one_of_its_exchange_and_tenant() {
+        let dir = fresh_root("profile_records");
+        write_record(&dir, record("exchange-a", None, "aaa"), false).unwrap();
+        write_record(&dir, record("exchange-b", Some("tenant"), "aaa"), false).unwrap();
+        write_record(&dir, record("exchange-a", None, "bbb"), false).unwrap();
+        assert_eq!(
+            server_keys_in(&dir),
+            vec![record("exchange-b", Some("tenant"), "aaa"), record("exchange-a", None, "bbb")]
+        );
+
+        // A new key drops the records of the old one
+        write_record(&dir, record("exchange-c", None, "ccc"), true).unwrap();
+        assert_eq!(server_keys_in(&dir), vec![record("exchange-c", None, "ccc")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_reported_fingerprint_is_checked_against_the_record() {
+        let dir = fresh_root("profile_reported");
+        write_record(&dir, record("exchange-a", None, "aaa"), false).unwrap();
+        assert_eq!(compare_reported(&dir, record("exchange-a", None, "aaa")).unwrap(), Reported::Matches);
+        assert_eq!(
+            compare_reported(&dir, record("exchange-a", None, "bbb")).unwrap(),
+            Reported::Mismatch("aaa".to_string())
+        );
+        // A mismatch doesn't replace what was sent
+        assert_eq!(record_in(&dir, "exchange-a", None).unwrap().fingerprint.as_deref(), Some("aaa"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keys_sent_before_fingerprints_adopt_the_first_reported_one() {
+        let dir = fresh_root("profile_adopted");
+        let mut unfingerprinted = record("exchange-a", None, "");
+        unfingerprinted.fingerprint = None;
+        unfingerprinted.sent_at = "2025-06-01T00:00:00Z".to_string();
+        write_record(&dir, unfingerprinted, false).unwrap();
+
+        assert_eq!(compare_reported(&dir, record("exchange-a", None, "aaa")).unwrap(), Reported::Recorded);
+        let adopted = record_in(&dir, "exchange-a", None).unwrap();
+        assert_eq!(adopted.fingerprint.as_deref(), Some("aaa"));
+        assert_eq!(adopted.sent_at, "2025-06-01T00:00:00Z");
+        assert_eq!(compare_reported(&dir, record("exchange-a", None, "bbb")).unwrap(), Reported::Mismatch("aaa".to_string()));
+
+        // No record at all (installs from before profiles)
+        assert_eq!(compare_reported(&dir, record("exchange-b", Some("tenant"), "ccc")).unwrap(), Reported::Recorded);
+        assert_eq!(record_in(&dir, "exchange-b", Some("tenant")).unwrap().fingerprint.as_deref(), Some("ccc"));
+        assert!(record_in(&dir, "exchange-b", None).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn other_profiles_at_the_same_exchange_and_tenant_are_found() {
+        let root = fresh_root("profile_sharing");
+        write_record(&root, record("exchange-a", None, "default"), false).unwrap();
+        write_record(&root.join("bob"), record("exchange-a", None, "bob"), false).unwrap();
+        write_record(&root.join("carol"), record("exchange-b", None, "carol"), false).unwrap();
+        write_record(&root.join("dave"), record("exchange-a", Some("tenant"), "dave"), false).unwrap();
+
+        assert_eq!(sharing_profiles(&root, Some("alice"), "exchange-a", None), vec![None, Some("bob".to_string())]);
+        assert_eq!(sharing_profiles(&root, None, "exchange-a", None), vec![Some("bob".to_string())]);
+        assert_eq!(sharing_profiles(&root, Some("bob"), "exchange-a", Some("tenant")), vec![Some("dave".to_string())]);
+        assert!(sharing_profiles(&root, Some("carol"), "exchange-c", None).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
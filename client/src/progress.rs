@@ -0,0 +1,100 @@
+//! Progress bars for the slow steps of a request.
+//!
+//! FHE-encrypting a key and IV takes seconds and a verification can keep
+//! the client waiting for hours. Both get an indicatif bar on stderr: one
+//! counting encrypted bits, and a spinner with the time waited and the last
+//! sign of life from the server (its job status, or before the job has one,
+//! the server status file). Bars are hidden unless the binary turns them on
+//! with [`set_visible`], so library users and batch runs draw nothing.
+
+use chrono::{DateTime, Utc};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static VISIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Draw the bars made from now on (they still only appear on a terminal)
+pub fn set_visible(visible: bool) {
+    VISIBLE.store(visible, Ordering::Relaxed);
+}
+
+fn visible() -> bool {
+    VISIBLE.load(Ordering::Relaxed)
+}
+
+/// Bar over `bits` FheBool encryptions
+pub fn encryption_bar(bits: u64) -> ProgressBar {
+    if !visible() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(bits);
+    bar.set_style(
+        ProgressStyle::with_template("   {msg} [{bar:40}] {pos}/{len} bits ({eta})")
+            .expect("valid progress template")
+            .progress_chars("=> "),
+    );
+    bar.set_message(shared::tr!("progress.encrypting"));
+    bar
+}
+
+/// Spinner shown while waiting for the server; its message is a [`waiting_message`]
+pub fn waiting_spinner() -> ProgressBar {
+    if !visible() {
+        return ProgressBar::hidden();
+    }
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(ProgressStyle::with_template("   {spinner} {msg}").expect("valid progress template"));
+    spinner.enable_steady_tick(Duration::from_millis(200));
+    spinner
+}
+
+/// "Waiting for the server: 12m 04s, last heartbeat 8s ago (running, decrypt_probe)"
+pub fn waiting_message(elapsed: Duration, heartbeat: Option<Duration>, state: Option<&str>) -> String {
+    let heartbeat = match heartbeat {
+        Some(age) => shared::tr!("progress.heartbeat", format_elapsed(age)),
+        None => shared::tr!("progress.no_heartbeat"),
+    };
+    let message = shared::tr!("progress.waiting", format_elapsed(elapsed), heartbeat);
+    match state {
+        Some(state) => format!("{} ({})", message, state),
+        None => message,
+    }
+}
+
+/// `1h 02m 03s`, `2m 05s` or `12s`
+pub fn format_elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+/// Time since a status file's `updated_at` (RFC 3339); None if it doesn't parse,
+/// zero if the server's clock is ahead
+pub fn heartbeat_age(updated_at: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let updated = DateTime::parse_from_rfc3339(updated_at).ok()?;
+    Some((now - updated.with_timezone(&Utc)).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_times_read_as_clock_units() {
+        assert_eq!(format_elapsed(Duration::from_secs(12)), "12s");
+        assert_eq!(format_elapsed(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_elapsed(Duration::from_secs(3723)), "1h 02m 03s");
+    }
+
+    #[test]
+    fn heartbeat_age_is_measured_from_updated_at() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:30Z").unwrap().with_timezone(&Utc);
+        assert_eq!(heartbeat_age("2024-05-01T12:00:00+00:00", now), Some(Duration::from_secs(30)));
+        assert_eq!(heartbeat_age("2024-05-01T12:01:00Z", now), Some(Duration::ZERO));
+        assert_eq!(heartbeat_age("yesterday", now), None);
+    }
+}
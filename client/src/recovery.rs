@@ -0,0 +1,111 @@
+//! Results left behind by an interrupted run.
+//!
+//! A verification can take an hour; if the client is closed meanwhile, the
+//! server still finishes and writes `verify_response.json` into the user's
+//! exchange slot. Before a new register/verify for the same user (and on
+//! `recover <user_id>`) the client looks for such leftovers, offers to
+//! decrypt and show them, then removes them so they can't be mistaken for
+//! the answer to the next request. A verify job the server is still working
+//! on is reported with its progress and left alone.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::time::{Duration, SystemTime};
+
+use client::say_tr;
+use shared::{RegisterResponse, VerifyResponse};
+
+use crate::history::HistoryEntry;
+use crate::{
+    clear_job, current_job, decrypt_verify_response, print_verify_outcome, record_verify, server_label, submitted_verify, user_exchange,
+    wait_for_response,
+    REGISTER_RESPONSE, VERIFY_REQUEST, VERIFY_RESPONSE,
+};
+
+/// Startup check before a new request: asks before decrypting (when interactive)
+pub fn check(user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    scan(user_id, false).map(|_| ())
+}
+
+/// `recover <user_id>`: show whatever is left without asking
+pub fn run(user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if !scan(user_id, true)? {
+        say_tr!("recovery.nothing", user_id);
+    }
+    Ok(())
+}
+
+/// Returns whether anything was found
+fn scan(user_id: &str, explicit: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let slot = user_exchange(user_id)?;
+    let mut found = false;
+    
+    if slot.exists(VERIFY_REQUEST)? {
+        say_tr!("recovery.request_pending");
+    }
+    
+    if let Some(modified) = slot.modified(VERIFY_RESPONSE)? {
+        found = true;
+        say_tr!("recovery.found_verify", finished_at(modified));
+        if explicit || confirm(&shared::tr!("recovery.prompt")) {
+            let response: Result<VerifyResponse, _> = wait_for_response(slot.as_ref(), VERIFY_RESPONSE, Duration::from_secs(5));
+            match response {
+                Ok(response) if response.success => {
+                    let result = decrypt_verify_response(user_id, &response, submitted_verify(user_id).as_ref());
+                    match &result {
+                        Ok(outcome) => print_verify_outcome(user_id, outcome, &response),
+                        Err(e) => say_tr!("recovery.decrypt_failed", e),
+                    }
+                    record_verify(HistoryEntry::new("verify", user_id, &server_label()), &result);
+                }
+                Ok(_) => say_tr!("recovery.server_failed"),
+                Err(e) => say_tr!("recovery.unreadable", e),
+            }
+        } else {
+            say_tr!("recovery.discarded");
+        }
+        slot.delete(VERIFY_RESPONSE)?;
+    }
+    
+    match current_job(slot.as_ref()) {
+        Ok(Some((job_id, Some(status)))) if !status.state.is_finished() => {
+            found = true;
+            say_tr!("recovery.job_running", job_id, status.state);
+        }
+        Ok(Some((job_id, _))) => clear_job(slot.as_ref(), &job_id),
+        Ok(None) => {}
+        Err(e) => say_tr!("recovery.unreadable", e),
+    }
+    
+    if slot.modified(REGISTER_RESPONSE)?.is_some() {
+        found = true;
+        let response: Result<RegisterResponse, _> = wait_for_response(slot.as_ref(), REGISTER_RESPONSE, Duration::from_secs(5));
+        match response {
+            Ok(r) if r.success => say_tr!("recovery.register_completed", r.message, r.timestamp),
+            Ok(r) => say_tr!("recovery.register_failed", r.message),
+            Err(e) => say_tr!("recovery.register_unreadable", e),
+        }
+        slot.delete(REGISTER_RESPONSE)?;
+    }
+    
+    Ok(found)
+}
+
+fn finished_at(modified: SystemTime) -> String {
+    let modified: chrono::DateTime<chrono::Utc> = modified.into();
+    shared::tr!("recovery.finished_at", modified.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+/// Yes unless the user declines; scripts without a terminal get the result shown
+fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return true;
+    }
+    eprint!("{} {} ", question, shared::tr!("recovery.prompt_choices"));
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return true;
+    }
+    // n(o) / h(ayır)
+    !answer.trim().to_lowercase().starts_with(['n', 'h'])
+}
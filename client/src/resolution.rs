@@ -0,0 +1,126 @@
+//! Capture resolution and scale normalization.
+//!
+//! Ridge spacing in pixels depends on the sensor's resolution; resizing every
+//! image to 64×64 makes a 1000 dpi capture look like a coarse 500 dpi one. When
+//! the resolution is known (`--dpi`, image metadata or the sensor profile) the
+//! image is first rescaled to the reference density and a fixed physical
+//! window around its centre is used, so the LBP grid always covers the same
+//! area of the finger. Without a known resolution the image is used as a
+//! whole, as before.
+
+use image::{imageops, GrayImage, Luma};
+
+use crate::sensor::REFERENCE_DPI;
+
+/// Window extracted at the reference density (≈ 13 mm at 500 dpi)
+pub const CANONICAL_WINDOW: u32 = 256;
+
+/// Metadata below this is an editor default (72/96 dpi), not a sensor resolution
+const MIN_SENSOR_DPI: u32 = 250;
+const MAX_SENSOR_DPI: u32 = 4000;
+
+/// Resolution recorded in PNG (pHYs), JPEG (JFIF) or BMP headers, if plausible for a sensor
+pub fn from_metadata(data: &[u8]) -> Option<u32> {
+    let dpi = png_dpi(data).or_else(|| jfif_dpi(data)).or_else(|| bmp_dpi(data))?;
+    (MIN_SENSOR_DPI..=MAX_SENSOR_DPI).contains(&dpi).then_some(dpi)
+}
+
+/// Rescale a `dpi` capture to the reference density and cut the canonical window
+/// around its centre (padding with the mean grey level where the capture is smaller)
+pub fn normalize_scale(img: &GrayImage, dpi: u32) -> GrayImage {
+    let scale = REFERENCE_DPI as f32 / dpi as f32;
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+    let scaled = if (width, height) == img.dimensions() {
+        img.clone()
+    } else {
+        imageops::resize(img, width, height, imageops::FilterType::Lanczos3)
+    };
+
+    let pixels = (scaled.width() as u64 * scaled.height() as u64).max(1);
+    let mean = (scaled.pixels().map(|p| p[0] as u64).sum::<u64>() / pixels) as u8;
+    let mut window = GrayImage::from_pixel(CANONICAL_WINDOW, CANONICAL_WINDOW, Luma([mean]));
+    let offset = |size: u32| (size as i64 - CANONICAL_WINDOW as i64) / 2;
+    imageops::replace(&mut window, &scaled, -offset(scaled.width()), -offset(scaled.height()));
+    window
+}
+
+fn png_dpi(data: &[u8]) -> Option<u32> {
+    let mut chunks = data.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    while chunks.len() >= 12 {
+        let len = u32::from_be_bytes(chunks[0..4].try_into().ok()?) as usize;
+        let kind = &chunks[4..8];
+        let body = chunks.get(8..8 + len)?;
+        if kind == b"pHYs" && len == 9 {
+            let per_metre = u32::from_be_bytes(body[0..4].try_into().ok()?);
+            return (body[8] == 1).then(|| (per_metre as f64 * 0.0254).round() as u32);
+        }
+        if kind == b"IDAT" {
+            return None;    // pHYs must precede the image data
+        }
+        chunks = chunks.get(12 + len..)?;
+    }
+    None
+}
+
+fn jfif_dpi(data: &[u8]) -> Option<u32> {
+    let app0 = data.strip_prefix(&[0xFF, 0xD8, 0xFF, 0xE0])?;
+    let jfif = app0.get(2..14)?;
+    if &jfif[0..5] != b"JFIF\0" {
+        return None;
+    }
+    let density = u16::from_be_bytes([jfif[8], jfif[9]]) as u32;
+    match jfif[7] {
+        1 => Some(density),                                 // dots per inch
+        2 => Some((density as f64 * 2.54).round() as u32),  // dots per cm
+        _ => None,
+    }
+}
+
+fn bmp_dpi(data: &[u8]) -> Option<u32> {
+    if !data.starts_with(b"BM") {
+        return None;
+    }
+    let per_metre = i32::from_le_bytes(data.get(38..42)?.try_into().ok()?);
+    (per_metre > 0).then(|| (per_metre as f64 * 0.0254).round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_resolution_from_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend_from_slice(&9u32.to_be_bytes());
+        png.extend_from_slice(b"pHYs");
+        png.extend_from_slice(&19685u32.to_be_bytes());     // 500 dpi
+        png.extend_from_slice(&19685u32.to_be_bytes());
+        png.push(1);
+        png.extend_from_slice(&[0; 4]);
+        assert_eq!(from_metadata(&png), Some(500));
+
+        let jfif = |units: u8, density: u16| {
+            let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+            jpeg.extend_from_slice(b"JFIF\0\x01\x01");
+            jpeg.push(units);
+            jpeg.extend_from_slice(&density.to_be_bytes());
+            jpeg.extend_from_slice(&density.to_be_bytes());
+            jpeg
+        };
+        assert_eq!(from_metadata(&jfif(1, 1000)), Some(1000));
+        assert_eq!(from_metadata(&jfif(1, 72)), None);
+    }
+
+    #[test]
+    fn window_covers_the_same_area_at_any_resolution() {
+        // A 1000 dpi capture twice the size of a 500 dpi one ends up identical in scale
+        let fine = GrayImage::from_fn(400, 400, |x, _| Luma([if (x / 20) % 2 == 0 { 40 } else { 200 }]));
+        let coarse = GrayImage::from_fn(200, 200, |x, _| Luma([if (x / 10) % 2 == 0 { 40 } else { 200 }]));
+        let a = normalize_scale(&fine, 1000);
+        let b = normalize_scale(&coarse, 500);
+        assert_eq!(a.dimensions(), (CANONICAL_WINDOW, CANONICAL_WINDOW));
+        let mismatched = a.pixels().zip(b.pixels()).filter(|(p, q)| p[0].abs_diff(q[0]) > 40).count();
+        assert!(mismatched < (CANONICAL_WINDOW * CANONICAL_WINDOW / 20) as usize);
+    }
+}
@@ -0,0 +1,104 @@
+//! `revoke <user_id>`: cancellable templates (see shared/src/transform.rs).
+//!
+//! Fingerprint templates are transformed with a per-user secret before they
+//! are encrypted. The secrets live in `~/.fingerprint_client/transforms.json`
+//! (mode 0600), created when the primary finger is registered. Revoking asks
+//! the server to refuse matches until the user registers again, then replaces
+//! the local transform, so the new enrollment shares nothing with the old one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use client::api;
+use client::{say, say_tr};
+use shared::transform::CancelableTransform;
+use shared::{FingerprintError, RegisterResponse, RevokeRequest};
+
+use crate::{get_client_key_path, load_credential, open_session, user_exchange, wait_for_response};
+
+const REVOKE_REQUEST: &str = "revoke_request.json";
+const REVOKE_RESPONSE: &str = "revoke_response.json";
+
+fn path() -> PathBuf {
+    get_client_key_path().with_file_name("transforms.json")
+}
+
+/// Transforms by user id
+fn load() -> Result<HashMap<String, CancelableTransform>, Box<dyn std::error::Error>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+fn save(transforms: &HashMap<String, CancelableTransform>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path();
+    let tmp_path = path.with_extension("json.tmp");
+    let _ = fs::remove_file(&tmp_path);
+    shared::sealed::write_secret(&tmp_path, &serde_json::to_vec(transforms)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// The user's transform, if the primary finger was registered on this machine with one
+pub fn transform(user_id: &str) -> Result<Option<CancelableTransform>, Box<dyn std::error::Error>> {
+    Ok(load()?.remove(user_id))
+}
+
+/// The transform to register the primary finger under, issued on first use
+pub fn enrollment_transform(user_id: &str) -> Result<CancelableTransform, Box<dyn std::error::Error>> {
+    let transform = proposed_transform(user_id)?;
+    keep_transform(user_id, &transform)?;
+    Ok(transform)
+}
+
+/// The user's transform, or a new one that is only issued by `keep_transform`
+pub fn proposed_transform(user_id: &str) -> Result<CancelableTransform, Box<dyn std::error::Error>> {
+    Ok(load()?.remove(user_id).unwrap_or_else(CancelableTransform::generate))
+}
+
+/// Issue a transform from `proposed_transform` before a template made with it is sent
+pub fn keep_transform(user_id: &str, transform: &CancelableTransform) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transforms = load()?;
+    match transforms.get(user_id) {
+        Some(kept) if kept.id == transform.id => Ok(()),
+        Some(kept) => Err(format!("The transform of {} changed to {} meanwhile; register again", user_id, kept.id).into()),
+        None => {
+            transforms.insert(user_id.to_string(), transform.clone());
+            save(&transforms)
+        }
+    }
+}
+
+pub fn run(user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("revoke.title");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.user_id", user_id);
+
+    let request = RevokeRequest {
+        user_id: user_id.to_string(),
+        api_key: api::api_key_from_env(),
+        session: open_session(user_id, &load_credential()?)?,
+    };
+    let slot = user_exchange(user_id)?;
+    slot.put(REVOKE_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    say_tr!("client.request_sent");
+
+    let response: RegisterResponse = wait_for_response(slot.as_ref(), REVOKE_RESPONSE, Duration::from_secs(60))?;
+    let _ = slot.delete(REVOKE_RESPONSE);
+    if !response.success {
+        return Err(FingerprintError::from_response(response.error_code, format!("Server rejected revocation: {}", response.message)).into());
+    }
+
+    // Only replaced once the server stopped matching the old templates
+    let mut transforms = load()?;
+    let renewed = CancelableTransform::generate();
+    say_tr!("revoke.done", transforms.get(user_id).map_or("none", |t| t.id.as_str()), renewed.id);
+    transforms.insert(user_id.to_string(), renewed);
+    save(&transforms)?;
+    say_tr!("revoke.register_again", user_id);
+    Ok(())
+}
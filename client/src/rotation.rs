@@ -0,0 +1,125 @@
+//! `keys rotate [<user_id>]`: replace a compromised FHE client key.
+//!
+//! A new keypair is generated and kept next to the old key until the server
+//! answers. The server stores the new server key for the tenant and marks
+//! every enrollment made under the old one revoked, since their encrypted
+//! keys, thresholds and masks no longer decrypt. The primary finger whose
+//! template this machine keeps (see update.rs) is then registered again
+//! under the new key; every other user has to register again from a capture.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use client::api;
+use client::{say, say_tr};
+use shared::fuzzy;
+use shared::protocol::server_key_fingerprint;
+use shared::{FingerprintError, KeyRotationRequest, RegisterResponse};
+
+use crate::advisor;
+use crate::profile;
+use crate::revocation;
+use crate::update::EnrollmentState;
+use crate::{get_client_key_path, load_credential, open_session, user_exchange, wait_for_response, REGISTER_REQUEST, REGISTER_RESPONSE};
+
+const ROTATE_REQUEST: &str = "rotate_request.json";
+const ROTATE_RESPONSE: &str = "rotate_response.json";
+
+/// Where the new client key waits for the server to accept its server key
+fn pending_key_path() -> PathBuf {
+    get_client_key_path().with_extension("next")
+}
+
+pub fn run(user_id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("rotate.title");
+    say!("{}", "─".repeat(70));
+
+    let enrollment = EnrollmentState::load().ok();
+    let user_id = match (user_id, &enrollment) {
+        (Some(user_id), _) => user_id.to_string(),
+        (None, Some(state)) => state.user_id().to_string(),
+        (None, None) => return Err("No enrollment on this machine; name the user: keys rotate <user_id>".into()),
+    };
+    say_tr!("client.user_id", user_id);
+
+    // 1. New keypair, kept aside until the server holds its server key
+    let params = advisor::configured_parameter_set();
+    say_tr!("client.generating_keys");
+    say_tr!("client.parameter_set", params);
+    let (client_key, server_key) = api::generate_fhe_keys_with(params);
+    let pending = pending_key_path();
+    fs::create_dir_all(get_client_key_path().parent().unwrap())?;
+    shared::sealed::write_secret(&pending, &bincode::serialize(&client_key)?)?;
+
+    // 2. Replace the server key and invalidate the tenant's enrollments
+    let request = KeyRotationRequest {
+        user_id: user_id.clone(),
+        server_key_bytes: bincode::serialize(&server_key)?,
+        gpu_server_key_bytes: api::gpu_key_from_env(&client_key)?,
+        api_key: api::api_key_from_env(),
+        session: open_session(&user_id, &load_credential()?)?,
+        admin_key: std::env::var(api::ADMIN_KEY_ENV).ok(),
+    };
+    say_tr!("client.server_key_size", request.server_key_bytes.len());
+    profile::warn_shared_tenant();
+    let slot = user_exchange(&user_id)?;
+    slot.put(ROTATE_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    say_tr!("client.request_sent");
+
+    let response: Result<RegisterResponse, _> = wait_for_response(slot.as_ref(), ROTATE_RESPONSE, Duration::from_secs(60));
+    let _ = slot.delete(ROTATE_RESPONSE);
+    let response = match response {
+        Ok(response) if response.success => response,
+        Ok(response) => {
+            let _ = fs::remove_file(&pending);
+            return Err(FingerprintError::from_response(response.error_code, format!("Server rejected key rotation: {}", response.message)).into());
+        }
+        // Outcome unknown: the new key stays pending, the old one in place
+        Err(e) => return Err(format!("{} (new key left at {})", e, pending.display()).into()),
+    };
+    fs::rename(&pending, get_client_key_path())?;
+    profile::record_server_key(server_key_fingerprint(&request.server_key_bytes), true)?;
+    profile::check_reported_fingerprint(response.server_key_fingerprint.as_deref())?;
+    say_tr!("rotate.rotated", response.message);
+
+    // 3. Register the enrollment this machine keeps under the new key
+    match enrollment.filter(|state| state.user_id() == user_id) {
+        Some(state) => reregister(&user_id, &state, &client_key)?,
+        None => say_tr!("rotate.no_local_enrollment", user_id),
+    }
+    say_tr!("rotate.register_others");
+    Ok(())
+}
+
+/// Encrypt the kept template under a fresh cipher key/IV and register it as the primary finger
+fn reregister(user_id: &str, state: &EnrollmentState, client_key: &tfhe::ClientKey) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("rotate.reregistering", state.template().len());
+    let bits = state.template();
+    let template = api::encrypt_template(bits, state.cipher())?;
+    let renewed = EnrollmentState::new(user_id, &template, bits);
+
+    let credential = load_credential()?;
+    let transform_id = revocation::transform(user_id)?.map(|transform| transform.id);
+    let mut request = api::build_register_request(user_id, template, client_key, None)?
+        .with_api_key(api::api_key_from_env())
+        .with_credential_key(credential.public_key())
+        .with_session(open_session(user_id, &credential)?)
+        .with_transform_id(transform_id)
+        .with_replace_existing(true);
+    request = match api::fuzzy_from_env()? {
+        Some((field_bits, errors)) => request.with_fuzzy_sketch(fuzzy::sketch(bits, field_bits, errors, client_key)?.0),
+        None => request.with_threshold(api::enrolled_threshold_from_env(bits.len(), client_key)?),
+    };
+
+    let slot = user_exchange(user_id)?;
+    slot.put(REGISTER_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    let response: RegisterResponse = wait_for_response(slot.as_ref(), REGISTER_RESPONSE, Duration::from_secs(30))?;
+    let _ = slot.delete(REGISTER_RESPONSE);
+    if !response.success {
+        return Err(FingerprintError::from_response(response.error_code, format!("Server rejected re-registration: {}", response.message)).into());
+    }
+    renewed.save()?;
+    say_tr!("rotate.reregistered", user_id);
+    Ok(())
+}
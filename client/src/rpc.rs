@@ -0,0 +1,303 @@
+//! JSON-RPC 2.0 over stdio.
+//!
+//! One request per line on stdin, one response per line on stdout.
+//! Human-readable progress goes to stderr so wrappers (Electron, Python, Go)
+//! can embed the client as a child process without FFI.
+//!
+//! Methods:
+//! - `enroll`         { user_id, image_path, duress?, consent?, soft?, fingers?, fusion? } -> RegisterResponse (with quality report)
+//! - `verify`         { user_id, image_path, wait?, soft?, partial?, fingers? } -> { submitted } or VerifyOutcome
+//! - `status`         { user_id? }                      -> exchange/key status, server job queue, verify job progress
+//! - `decrypt-result` { user_id }                       -> VerifyOutcome
+//!
+//! Failed operations (-32000) carry `data.error_code`, the kind of failure
+//! (`protocol`, `key_mismatch`, `storage`, ...; see shared/src/error.rs).
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use client::fallback::FactorInput;
+use shared::fusion::FusionRule;
+use shared::soft::SoftAttributes;
+use shared::telemetry::PhaseTimer;
+use shared::{ConsentInfo, ErrorCode, Factor, VerifyResponse};
+
+use crate::history::HistoryEntry;
+use client::output;
+use crate::{
+    decrypt_verify_response, get_client_key_path, handle_register, handle_verify, record_verify, EnrollOptions, ProbeOptions,
+    exchange, server_label, user_exchange, submit_verify, submitted_verify, REGISTER_REQUEST, REGISTER_RESPONSE, SERVER_STATUS, VERIFY_REQUEST,
+    VERIFY_RESPONSE,
+};
+
+// Standard JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+// Application errors
+pub const OPERATION_FAILED: i64 = -32000;
+pub const NO_RESULT: i64 = -32001;
+
+#[derive(Deserialize, Debug)]
+struct RpcRequest {
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Debug)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,    // { "error_code": ... } for failures the client or server classified
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    pub fn with_error_code(mut self, code: Option<ErrorCode>) -> Self {
+        self.data = code.map(|code| json!({ "error_code": code }));
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct UserImageParams {
+    user_id: String,
+    image_path: String,
+    #[serde(default)]
+    wait: bool,
+    #[serde(default)]
+    duress: bool,
+    #[serde(default)]
+    consent: Option<ConsentInfo>,
+    #[serde(default)]
+    soft: Option<SoftAttributes>,
+    #[serde(default)]
+    partial: bool,
+    #[serde(default)]
+    replace: bool,
+    #[serde(default)]
+    samples: Vec<String>,
+    #[serde(default)]
+    fingers: Vec<String>,
+    #[serde(default)]
+    fusion: FusionRule,
+}
+
+#[derive(Deserialize, Default)]
+struct DecryptParams {
+    user_id: Option<String>,
+}
+
+/// Serve requests until stdin is closed.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    output::reserve_stdout();
+    eprintln!("🔌 JSON-RPC mode: reading requests from stdin");
+
+    let stdin = io::stdin();
+    serve(stdin.lock(), io::stdout(), dispatch)
+}
+
+/// Line-delimited JSON-RPC loop over any reader/writer pair
+pub fn serve<R, W, D>(reader: R, mut writer: W, dispatch: D) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: BufRead,
+    W: Write,
+    D: Fn(&str, Value) -> Result<Value, RpcError>,
+{
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = handle_line(&line, &dispatch);
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+fn handle_line<D>(line: &str, dispatch: &D) -> RpcResponse
+where
+    D: Fn(&str, Value) -> Result<Value, RpcError>,
+{
+    let request: RpcRequest = match serde_json::from_str(line) {
+        Ok(r) => r,
+        Err(e) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+    };
+
+    if request.jsonrpc.as_deref() != Some("2.0") {
+        return error_response(request.id, RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+
+    match dispatch(&request.method, request.params) {
+        Ok(result) => RpcResponse {
+            jsonrpc: "2.0",
+            id: request.id,
+            result: Some(result),
+            error: None,
+        },
+        Err(e) => error_response(request.id, e),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+pub fn dispatch(method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "enroll" => {
+            let p: UserImageParams = parse_params(params)?;
+            let input = FactorInput::Image(p.image_path);
+            let options = EnrollOptions {
+                duress: p.duress,
+                consent: p.consent,
+                soft: p.soft,
+                replace: p.replace,
+                samples: p.samples,
+                fingers: p.fingers,
+                fusion: p.fusion,
+                ..EnrollOptions::default()
+            };
+            let response = handle_register(&p.user_id, &input, Factor::Fingerprint, &options).map_err(failed)?;
+            to_value(&response)
+        }
+        "verify" => {
+            let p: UserImageParams = parse_params(params)?;
+            let probe = ProbeOptions { soft: p.soft, partial: p.partial, ownership_challenge: false, fingers: p.fingers };
+            if p.wait {
+                let outcome = handle_verify(&p.user_id, &p.image_path, &probe).map_err(failed)?;
+                return to_value(&outcome);
+            }
+            let input = FactorInput::Image(p.image_path);
+            let submitted = submit_verify(&p.user_id, Factor::Fingerprint, &input, &probe, &mut PhaseTimer::start()).map_err(failed)?;
+            Ok(json!({ "submitted": true, "user_id": p.user_id, "request_id": submitted.request_id }))
+        }
+        "status" => {
+            let p: DecryptParams = if params.is_null() {
+                DecryptParams::default()
+            } else {
+                parse_params(params)?
+            };
+            status(p.user_id.as_deref())
+        }
+        "decrypt-result" => {
+            let p: DecryptParams = if params.is_null() {
+                DecryptParams::default()
+            } else {
+                parse_params(params)?
+            };
+            decrypt_result(p.user_id)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+    }
+}
+
+/// Pending flags refer to the user's slot (exchange root without `user_id`)
+fn status(user_id: Option<&str>) -> Result<Value, RpcError> {
+    let exchange = exchange().map_err(failed)?;
+    let slot = match user_id {
+        Some(user_id) => Some(user_exchange(user_id).map_err(failed)?),
+        None => None,
+    };
+    let area = slot.as_deref().unwrap_or(exchange);
+    let exists = |name: &str| area.exists(name).unwrap_or(false);
+    // Progress of the user's last verify job (the result itself comes from decrypt-result)
+    let verify_job = match slot.as_deref().map(crate::current_job).transpose().map_err(failed)?.flatten() {
+        Some((job_id, status)) => json!({
+            "job_id": job_id,
+            "state": status.as_ref().map(|s| s.state),
+            "phase": status.as_ref().and_then(|s| s.phase.clone()),
+            "error": status.as_ref().and_then(|s| s.error.clone()),
+        }),
+        None => Value::Null,
+    };
+    Ok(json!({
+        "client_key_present": get_client_key_path().exists(),
+        "exchange": exchange.describe(),
+        "register_pending": exists(REGISTER_REQUEST),
+        "register_result_ready": exists(REGISTER_RESPONSE),
+        "verify_pending": exists(VERIFY_REQUEST),
+        "verify_result_ready": exists(VERIFY_RESPONSE),
+        "verify_job": verify_job,
+        // Running/queued job counts and limits published by the server
+        "server": exchange.get(SERVER_STATUS)
+            .ok()
+            .flatten()
+            .and_then(|data| serde_json::from_slice::<Value>(&data).ok()),
+    }))
+}
+
+fn decrypt_result(user_id: Option<String>) -> Result<Value, RpcError> {
+    // Results live in the user's private exchange slot
+    let Some(user_id) = user_id else {
+        return Err(RpcError::new(INVALID_PARAMS, "user_id is required to locate the result"));
+    };
+    let exchange = user_exchange(&user_id).map_err(failed)?;
+    if !exchange.exists(VERIFY_RESPONSE).map_err(failed)? {
+        return Err(RpcError::new(NO_RESULT, "No verification result available yet"));
+    }
+
+    let response: VerifyResponse = crate::wait_for_response(exchange.as_ref(), VERIFY_RESPONSE, Duration::from_secs(5))
+        .map_err(failed)?;
+    // The job's ticket and status go with its result
+    let consume = || match crate::current_job(exchange.as_ref()) {
+        Ok(Some((job_id, _))) => crate::clear_job(exchange.as_ref(), &job_id),
+        _ => {
+            let _ = exchange.delete(VERIFY_RESPONSE);
+        }
+    };
+
+    if !response.success {
+        consume();
+        return Err(RpcError::new(OPERATION_FAILED, "Server reported verification failure").with_error_code(response.error_code));
+    }
+
+    let result = decrypt_verify_response(&user_id, &response, submitted_verify(&user_id).as_ref());
+    record_verify(HistoryEntry::new("verify", &user_id, &server_label()), &result);
+    let outcome = result.map_err(failed)?;
+    consume();
+
+    to_value(&outcome)
+}
+
+pub fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(OPERATION_FAILED, e.to_string()))
+}
+
+/// Operation failure, with the error's code
+pub fn failed(e: Box<dyn std::error::Error>) -> RpcError {
+    RpcError::new(OPERATION_FAILED, e.to_string()).with_error_code(Some(ErrorCode::of(e.as_ref())))
+}
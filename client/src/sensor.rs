@@ -0,0 +1,259 @@
+//! Sensor calibration profiles.
+//!
+//! Scanners differ in resolution, contrast and noise. A profile describes one
+//! scanner; extraction maps its images onto the look of the reference sensor
+//! (levels and gamma, then the recommended preprocessing and enhancement, see
+//! enhance.rs) before computing texture features, so a template enrolled on one scanner matches probes
+//! captured on another. Profiles are derived from sample images by the
+//! `calibrate-sensor` command; the active one is chosen once per process.
+
+use image::{imageops, GrayImage, Luma};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::enhance::{self, EnhanceOptions};
+
+/// Resolution of the reference sensor templates are calibrated against
+pub const REFERENCE_DPI: u32 = 500;
+
+/// Below this spread between black and white point a sample is unusable
+const MIN_DYNAMIC_RANGE: u8 = 16;
+/// Normalized interquartile range below which equalization is recommended
+const EQUALIZE_BELOW_IQR: f32 = 0.25;
+/// Normalized neighbour difference above which smoothing is recommended
+const SMOOTH_ABOVE_NOISE: f32 = 0.2;
+const SMOOTH_SIGMA: f32 = 0.8;
+/// Share of background blocks above which segmentation is recommended
+const SEGMENT_ABOVE_BACKGROUND: f32 = 0.1;
+/// Spread of ridge contrast across the finger (90th over 10th percentile of the
+/// foreground block deviations) above which CLAHE is recommended
+const CLAHE_ABOVE_CONTRAST_RATIO: f32 = 3.0;
+
+static ACTIVE: OnceLock<SensorProfile> = OnceLock::new();
+static DPI_OVERRIDE: OnceLock<u32> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SensorProfile {
+    pub name: String,
+    pub resolution_dpi: u32,            // Native resolution of the scanner
+    pub contrast: ContrastCurve,
+    #[serde(default)]
+    pub preprocessing: Preprocessing,
+}
+
+/// Levels and gamma mapping the scanner's grey values onto the reference range
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ContrastCurve {
+    pub black_point: u8,
+    pub white_point: u8,
+    pub gamma: f32,                     // Applied after levels; puts the median ridge/valley level at mid-grey
+}
+
+/// Preprocessing recommended for the scanner
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Preprocessing {
+    #[serde(default)]
+    pub equalize: bool,                 // Histogram equalization (low-contrast sensors)
+    #[serde(default)]
+    pub blur_sigma: f32,                // Gaussian smoothing (noisy sensors); 0 = off
+    #[serde(default)]
+    pub enhance: EnhanceOptions,        // Segmentation, CLAHE, binarization
+}
+
+impl ContrastCurve {
+    pub fn identity() -> Self {
+        Self { black_point: 0, white_point: 255, gamma: 1.0 }
+    }
+
+    pub fn map(&self, value: u8) -> u8 {
+        let range = (self.white_point.saturating_sub(self.black_point)).max(1) as f32;
+        let level = ((value as f32 - self.black_point as f32) / range).clamp(0.0, 1.0);
+        (level.powf(self.gamma) * 255.0).round() as u8
+    }
+}
+
+impl SensorProfile {
+    /// The reference sensor: images are used as captured
+    pub fn reference() -> Self {
+        Self {
+            name: "reference".to_string(),
+            resolution_dpi: REFERENCE_DPI,
+            contrast: ContrastCurve::identity(),
+            preprocessing: Preprocessing::default(),
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        self.contrast == ContrastCurve::identity() && self.preprocessing == Preprocessing::default()
+    }
+
+    /// Map a capture of this sensor onto the reference look
+    pub fn apply(&self, img: &GrayImage) -> GrayImage {
+        if self.is_reference() {
+            return img.clone();
+        }
+        let lut: Vec<u8> = (0..=255u8).map(|v| self.contrast.map(v)).collect();
+        let mut out = GrayImage::from_fn(img.width(), img.height(), |x, y| Luma([lut[img.get_pixel(x, y)[0] as usize]]));
+        if self.preprocessing.equalize {
+            out = equalize(&out);
+        }
+        if self.preprocessing.blur_sigma > 0.0 {
+            out = imageops::blur(&out, self.preprocessing.blur_sigma);
+        }
+        enhance::preprocess(&out, &self.preprocessing.enhance)
+    }
+
+    /// Derive a profile from sample captures of one scanner
+    pub fn calibrate(name: &str, samples: &[GrayImage], resolution_dpi: u32) -> Result<Self, String> {
+        let mut histogram = [0u64; 256];
+        let mut neighbour_diff = 0u64;
+        let mut pairs = 0u64;
+        for img in samples {
+            for pixel in img.pixels() {
+                histogram[pixel[0] as usize] += 1;
+            }
+            for row in img.rows() {
+                let row: Vec<u8> = row.map(|p| p[0]).collect();
+                neighbour_diff += row.windows(2).map(|w| w[0].abs_diff(w[1]) as u64).sum::<u64>();
+                pairs += row.len().saturating_sub(1) as u64;
+            }
+        }
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return Err("No sample pixels to calibrate from".to_string());
+        }
+
+        let black_point = percentile(&histogram, total, 0.01);
+        let white_point = percentile(&histogram, total, 0.99);
+        if white_point < black_point.saturating_add(MIN_DYNAMIC_RANGE) {
+            return Err(format!("Samples have too little contrast ({}..{})", black_point, white_point));
+        }
+        let range = (white_point - black_point) as f32;
+        let level = |v: u8| ((v as f32 - black_point as f32) / range).clamp(0.01, 0.99);
+
+        let median = level(percentile(&histogram, total, 0.5));
+        let gamma = (0.5f32.ln() / median.ln()).clamp(0.25, 4.0);
+        let iqr = level(percentile(&histogram, total, 0.75)) - level(percentile(&histogram, total, 0.25));
+        let noise = neighbour_diff as f32 / pairs.max(1) as f32 / range;
+        let enhance = recommended_enhancement(samples);
+
+        Ok(Self {
+            name: name.to_string(),
+            resolution_dpi,
+            contrast: ContrastCurve { black_point, white_point, gamma },
+            preprocessing: Preprocessing {
+                equalize: iqr < EQUALIZE_BELOW_IQR,
+                blur_sigma: if noise > SMOOTH_ABOVE_NOISE { SMOOTH_SIGMA } else { 0.0 },
+                enhance,
+            },
+        })
+    }
+}
+
+/// Select the profile used by every extraction in this process (first call wins)
+pub fn set_active(profile: SensorProfile) {
+    let _ = ACTIVE.set(profile);
+}
+
+/// Active profile (the reference sensor if none was selected)
+pub fn active() -> &'static SensorProfile {
+    ACTIVE.get_or_init(SensorProfile::reference)
+}
+
+/// Resolution of every capture in this process, overriding metadata and the profile (`--dpi`)
+pub fn set_resolution_override(dpi: u32) {
+    let _ = DPI_OVERRIDE.set(dpi);
+}
+
+pub fn resolution_override() -> Option<u32> {
+    DPI_OVERRIDE.get().copied()
+}
+
+/// Resolution to normalize a capture with: `--dpi`, then the image metadata,
+/// then a calibrated profile. `None` leaves the scale as captured.
+pub fn capture_dpi(metadata: Option<u32>) -> Option<u32> {
+    let profile = active();
+    resolution_override()
+        .or(metadata)
+        .or_else(|| (!profile.is_reference()).then_some(profile.resolution_dpi))
+}
+
+/// Smallest grey value with at least `fraction` of the pixels at or below it
+fn percentile(histogram: &[u64; 256], total: u64, fraction: f64) -> u8 {
+    let target = (total as f64 * fraction).ceil() as u64;
+    let mut seen = 0;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= target.max(1) {
+            return value as u8;
+        }
+    }
+    255
+}
+
+/// Segmentation when the samples show much blank background, CLAHE when the
+/// ridge contrast varies strongly across the finger
+fn recommended_enhancement(samples: &[GrayImage]) -> EnhanceOptions {
+    let mut blocks = 0;
+    let mut foreground = Vec::new();
+    for img in samples {
+        let contrast = enhance::block_contrast(img);
+        let threshold = enhance::foreground_threshold(&contrast);
+        blocks += contrast.len();
+        foreground.extend(contrast.into_iter().filter(|&c| c > 0.0 && c >= threshold));
+    }
+    foreground.sort_by(|a, b| a.total_cmp(b));
+    let background = 1.0 - foreground.len() as f32 / blocks.max(1) as f32;
+    let contrast_ratio = match (foreground.get(foreground.len() / 10), foreground.get(foreground.len() * 9 / 10)) {
+        (Some(&low), Some(&high)) => high / low,
+        _ => 1.0,
+    };
+    EnhanceOptions {
+        segment: background > SEGMENT_ABOVE_BACKGROUND,
+        clahe: contrast_ratio > CLAHE_ABOVE_CONTRAST_RATIO,
+        binarize: false,
+    }
+}
+
+fn equalize(img: &GrayImage) -> GrayImage {
+    let mut histogram = [0u64; 256];
+    for pixel in img.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total = (img.width() as u64 * img.height() as u64).max(1);
+    let mut cdf = [0u8; 256];
+    let mut seen = 0;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count;
+        cdf[value] = (seen * 255 / total) as u8;
+    }
+    GrayImage::from_fn(img.width(), img.height(), |x, y| Luma([cdf[img.get_pixel(x, y)[0] as usize]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_stretches_a_washed_out_sensor() {
+        // Ridges at 100, valleys at 160: a narrow, bright capture
+        let sample = GrayImage::from_fn(64, 64, |x, _| Luma([if (x / 8) % 2 == 0 { 100 } else { 160 }]));
+        let profile = SensorProfile::calibrate("washed-out", std::slice::from_ref(&sample), 500).unwrap();
+
+        assert_eq!(profile.contrast.black_point, 100);
+        assert_eq!(profile.contrast.white_point, 160);
+        let mapped = profile.apply(&sample);
+        let values: Vec<u8> = mapped.pixels().map(|p| p[0]).collect();
+        assert_eq!(values.iter().min(), Some(&0));
+        assert_eq!(values.iter().max(), Some(&255));
+
+        assert!(SensorProfile::calibrate("flat", &[GrayImage::from_pixel(8, 8, Luma([90]))], 500).is_err());
+    }
+
+    #[test]
+    fn reference_profile_leaves_images_untouched() {
+        let img = GrayImage::from_fn(16, 16, |x, y| Luma([(x * 16 + y) as u8]));
+        assert_eq!(SensorProfile::reference().apply(&img), img);
+        assert_eq!(ContrastCurve::identity().map(77), 77);
+    }
+}
@@ -0,0 +1,155 @@
+//! `update <user_id> <image_path>`: delta re-enrollment (see shared/src/delta.rs).
+//!
+//! Registering the primary finger keeps its Trivium key/IV and template in
+//! `~/.fingerprint_client/enrollment.json` (mode 0600). An update extracts a
+//! fresh template, sends only the regions that changed, encrypted further
+//! down the enrolled keystream, and records the new template and keystream
+//! position locally once the server accepted it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use client::api::{self, TriviumTemplate};
+use client::fallback::FactorInput;
+use client::{say, say_tr};
+use shared::delta::{self, TemplateDelta, DEFAULT_REGION_BITS, MAX_DELTA_BITS};
+use shared::{Cipher, DeltaRequest, RegisterResponse};
+
+use crate::revocation;
+use crate::{get_client_key_path, load_credential, open_session, user_exchange, wait_for_response};
+
+const DELTA_REQUEST: &str = "delta_request.json";
+const DELTA_RESPONSE: &str = "delta_response.json";
+
+/// What the client needs to encrypt deltas for its enrollment
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EnrollmentState {
+    user_id: String,
+    key_bits: Vec<bool>,
+    iv_bits: Vec<bool>,
+    template: Vec<bool>,        // Current template as the server will reconstruct it
+    stream_offset: usize,       // Next unused keystream position
+    #[serde(default)]
+    cipher: Cipher,             // Cipher the enrollment was transciphered with
+}
+
+impl EnrollmentState {
+    /// State of a fresh primary enrollment
+    pub fn new(user_id: &str, template: &TriviumTemplate, bits: &[bool]) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            key_bits: template.key_bits.clone(),
+            iv_bits: template.iv_bits.clone(),
+            template: bits.to_vec(),
+            stream_offset: template.ciphertext.len(),
+            cipher: template.cipher,
+        }
+    }
+
+    fn path() -> PathBuf {
+        get_client_key_path().with_file_name("enrollment.json")
+    }
+
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = Self::path();
+        if !path.exists() {
+            return Err("No enrollment state found; register the fingerprint on this machine first".into());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path();
+        let tmp_path = path.with_extension("json.tmp");
+        let _ = fs::remove_file(&tmp_path);
+        shared::sealed::write_secret(&tmp_path, &serde_json::to_vec(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// Current template, under the user's transform
+    pub fn template(&self) -> &[bool] {
+        &self.template
+    }
+
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    /// Encrypt the changed regions of `new_template` at the next keystream position
+    fn delta(&self, new_template: &[bool], region_bits: usize) -> Option<TemplateDelta> {
+        let regions = delta::changed_regions(&self.template, new_template, region_bits);
+        if regions.is_empty() {
+            return None;
+        }
+        let changed_bits = delta::region_bits(new_template, &regions);
+        let ciphertext = self.cipher.process(&self.key_bits, &self.iv_bits, self.stream_offset, &changed_bits);
+        Some(TemplateDelta { regions, stream_offset: self.stream_offset, ciphertext })
+    }
+}
+
+/// Template length of the enrollment on this machine, if it belongs to `user_id`
+pub fn enrolled_template_bits(user_id: &str) -> Option<usize> {
+    EnrollmentState::load()
+        .ok()
+        .filter(|state| state.user_id == user_id)
+        .map(|state| state.template.len())
+}
+
+pub fn run(user_id: &str, image_path: &str, region_bits: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    say_tr!("update.title");
+    say!("{}", "─".repeat(70));
+    say_tr!("client.user_id", user_id);
+
+    let mut state = EnrollmentState::load()?;
+    if state.user_id != user_id {
+        return Err(format!("Enrollment state on this machine belongs to '{}'", state.user_id).into());
+    }
+
+    say_tr!("client.extracting");
+    let mut new_template = api::template_from_input(&FactorInput::Image(image_path.to_string()), state.template.len())?;
+    if let Some(transform) = revocation::transform(user_id)? {
+        new_template = transform.apply(&new_template);
+    }
+    let Some(delta) = state.delta(&new_template, region_bits.unwrap_or(DEFAULT_REGION_BITS)) else {
+        say_tr!("update.unchanged");
+        return Ok(());
+    };
+
+    let used = state.stream_offset - state.template.len();
+    say_tr!("update.regions", delta.regions.len(), delta.bits(), new_template.len());
+    if used + delta.bits() > MAX_DELTA_BITS {
+        return Err(format!(
+            "Delta would exceed the limit of {} bits ({} used); run a full register instead",
+            MAX_DELTA_BITS, used
+        )
+        .into());
+    }
+
+    let request = DeltaRequest::new(user_id.to_string(), delta.clone())
+        .with_api_key(api::api_key_from_env())
+        .with_session(open_session(user_id, &load_credential()?)?);
+    let slot = user_exchange(user_id)?;
+    slot.put(DELTA_REQUEST, serde_json::to_string_pretty(&request)?.as_bytes())?;
+    say_tr!("client.request_sent");
+
+    let response: Result<RegisterResponse, _> = wait_for_response(slot.as_ref(), DELTA_RESPONSE, Duration::from_secs(30));
+    let _ = slot.delete(DELTA_RESPONSE);
+    let response = response?;
+    if !response.success {
+        say_tr!("update.failed", response.message);
+        return Ok(());
+    }
+
+    delta::splice(&mut state.template, &delta.regions, &delta::region_bits(&new_template, &delta.regions));
+    state.stream_offset += delta.bits();
+    state.save()?;
+    say_tr!("update.done", used + delta.bits(), MAX_DELTA_BITS);
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Node.js bindings for the client library (napi-rs).
+//!
+//! Keys and FHE blobs cross the boundary as `Buffer`s (bincode), protocol
+//! messages as JSON strings, so a TypeScript backend can build requests,
+//! hand them to any transport and decrypt the results.
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+use client::api::{self, TriviumTemplate};
+use client::image_source::ImageSource;
+use shared::VerifyResponse;
+use tfhe::ClientKey;
+
+#[napi(object)]
+pub struct KeyPair {
+    pub client_key: Buffer,
+    pub server_key: Buffer,
+}
+
+#[napi(object)]
+pub struct EncryptedTemplate {
+    pub ciphertext: Vec<bool>,
+    pub key_bits: Vec<bool>,
+    pub iv_bits: Vec<bool>,
+}
+
+#[napi(object)]
+pub struct VerifyResult {
+    pub user_id: String,
+    pub matched: bool,
+    pub distance: Option<u32>,
+    pub similarity: Option<f64>,
+    pub timestamp: String,
+}
+
+/// Extract the binary fingerprint template from an image file.
+#[napi]
+pub fn extract_template(image_path: String) -> Result<Vec<bool>> {
+    api::extract_template(&image_path).map_err(to_napi)
+}
+
+/// Extract the binary fingerprint template from a raw 8-bit grayscale sensor frame.
+#[napi]
+pub fn extract_template_from_frame(pixels: Buffer, width: u32, height: u32, dpi: Option<u32>) -> Result<Vec<bool>> {
+    let source = ImageSource::Raw { pixels: pixels.to_vec(), width, height, dpi };
+    api::extract_template_from(&source, api::TEMPLATE_BITS).map_err(to_napi)
+}
+
+/// Encrypt template bits with Trivium under a fresh random key/IV.
+#[napi]
+pub fn trivium_encrypt(bits: Vec<bool>) -> Result<EncryptedTemplate> {
+    let template = api::trivium_encrypt(&bits).map_err(to_napi)?;
+    Ok(EncryptedTemplate {
+        ciphertext: template.ciphertext,
+        key_bits: template.key_bits,
+        iv_bits: template.iv_bits,
+    })
+}
+
+/// Generate a new FHE key pair (takes several seconds).
+#[napi]
+pub fn generate_keys() -> Result<KeyPair> {
+    let (client_key, server_key) = api::generate_fhe_keys();
+    Ok(KeyPair {
+        client_key: bincode::serialize(&client_key).map_err(to_napi)?.into(),
+        server_key: bincode::serialize(&server_key).map_err(to_napi)?.into(),
+    })
+}
+
+/// Build a RegisterRequest (JSON) from an image. `duress` enrolls the duress finger,
+/// `api_key` selects the tenant on multi-tenant servers, `replace` confirms
+/// re-enrollment of a template that is already enrolled.
+#[napi]
+pub fn build_register_request(
+    user_id: String,
+    image_path: String,
+    client_key: Buffer,
+    server_key: Option<Buffer>,
+    duress: Option<bool>,
+    api_key: Option<String>,
+    replace: Option<bool>,
+) -> Result<String> {
+    let client_key = load_client_key(&client_key)?;
+    let template = extract_and_encrypt(&image_path)?;
+    let mut request = api::build_register_request(
+        &user_id,
+        template,
+        &client_key,
+        server_key.map(|b| b.to_vec()),
+    )
+    .map_err(to_napi)?
+    .with_api_key(api_key)
+    .with_replace_existing(replace.unwrap_or(false));
+    if duress.unwrap_or(false) {
+        request = request.with_duress();
+    }
+    serde_json::to_string(&request).map_err(to_napi)
+}
+
+/// Build a VerifyRequest (JSON) from a probe image.
+#[napi]
+pub fn build_verify_request(
+    user_id: String,
+    image_path: String,
+    client_key: Buffer,
+    api_key: Option<String>,
+) -> Result<String> {
+    let client_key = load_client_key(&client_key)?;
+    let template = extract_and_encrypt(&image_path)?;
+    let request = api::build_verify_request(&user_id, template, &client_key)
+        .map_err(to_napi)?
+        .with_api_key(api_key);
+    serde_json::to_string(&request).map_err(to_napi)
+}
+
+/// Decrypt the match bit and distance of a VerifyResponse (JSON).
+#[napi]
+pub fn decrypt_verify_response(user_id: String, response_json: String, client_key: Buffer) -> Result<VerifyResult> {
+    let client_key = load_client_key(&client_key)?;
+    let response: VerifyResponse = serde_json::from_str(&response_json).map_err(to_napi)?;
+
+    if !response.success {
+        return Err(Error::from_reason("Server reported verification failure"));
+    }
+
+    let outcome = api::decrypt_verify_result(&user_id, &response, &client_key).map_err(to_napi)?;
+    Ok(VerifyResult {
+        user_id: outcome.user_id,
+        matched: outcome.match_result,
+        distance: outcome.distance.map(|distance| distance as u32),
+        similarity: outcome.similarity.map(f64::from),
+        timestamp: outcome.timestamp,
+    })
+}
+
+/// Decrypt the duress flag of a VerifyResponse (JSON); null if the server sent none.
+#[napi]
+pub fn decrypt_duress_flag(response_json: String, client_key: Buffer) -> Result<Option<bool>> {
+    let client_key = load_client_key(&client_key)?;
+    let response: VerifyResponse = serde_json::from_str(&response_json).map_err(to_napi)?;
+    api::decrypt_duress_flag(&response, &client_key).map_err(to_napi)
+}
+
+fn extract_and_encrypt(image_path: &str) -> Result<TriviumTemplate> {
+    let bits = api::extract_template(image_path).map_err(to_napi)?;
+    api::trivium_encrypt(&bits).map_err(to_napi)
+}
+
+fn load_client_key(bytes: &[u8]) -> Result<ClientKey> {
+    bincode::deserialize(bytes).map_err(to_napi)
+}
+
+fn to_napi<E: std::fmt::Display>(e: E) -> Error {
+    Error::from_reason(e.to_string())
+}
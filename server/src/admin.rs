@@ -0,0 +1,152 @@
+use shared::{trln, AdminCommand, AdminRequest, AdminResponse, DatabaseStats, UserSummary};
+use std::collections::HashSet;
+use std::fs;
+
+use crate::blob_store;
+use crate::database::{db_path, scoped_key, Database, StorageBackend, StorageConfig, TemplateEntry, DEFAULT_TENANT};
+use crate::sqlite_store::sqlite_path;
+use crate::tenant::{self, TenantRegistry};
+
+/// `server admin <command>`: inspect enrollment metadata (never template data) and manage tenants
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.first().map(|s| s.as_str()) {
+        Some("show") => {
+            let user_id = args.get(1).ok_or("Usage: server admin show <user_id> [--tenant <name>]")?;
+            let tenant = tenant_flag(args);
+            let db = Database::load()?;
+            let entry = db
+                .get(tenant, user_id)
+                .ok_or_else(|| format!("User '{}' not found in tenant '{}'", user_id, tenant))?;
+            print_entry(entry);
+        }
+        Some("tenant") => tenant::admin(&args[1..])?,
+        Some("list") => {
+            let db = Database::load()?;
+            trln!("admin.database_version", db.version);
+            for user in user_summaries(&db)? {
+                let key = scoped_key(&user.tenant, &user.user_id);
+                let retention = db
+                    .templates
+                    .get(&key)
+                    .and_then(|e| e.consent.as_ref())
+                    .and_then(|c| c.retention_until.as_deref())
+                    .unwrap_or("-");
+                trln!("admin.list_entry", format!("{:<32}", key), user.created_at, format!("{:>9}", user.stored_bytes), retention);
+            }
+        }
+        Some("stats") => print_stats(&database_stats(&Database::load()?)?),
+        Some("key") => {
+            let mut registry = TenantRegistry::load()?;
+            let key = registry.issue_admin_key();
+            registry.save()?;
+            trln!("admin.key_issued");
+            println!("{}", key);
+        }
+        _ => {
+            trln!("admin.usage");
+        }
+    }
+    Ok(())
+}
+
+fn tenant_flag(args: &[String]) -> &str {
+    args.windows(2)
+        .find(|w| w[0] == "--tenant")
+        .map(|w| w[1].as_str())
+        .unwrap_or(DEFAULT_TENANT)
+}
+
+fn print_entry(entry: &TemplateEntry) {
+    trln!("admin.user_id", entry.user_id);
+    trln!("admin.tenant", entry.tenant());
+    trln!("admin.created", entry.created_at);
+    trln!("admin.updated", entry.updated_at);
+    trln!("admin.enrolled", entry.enrollment_count, entry.history.len());
+    trln!("admin.factors", format!("{:?}", entry.enrolled_factors()));
+    if entry.duress.is_some() {
+        trln!("admin.duress_enrolled");
+    } else {
+        trln!("admin.duress_none");
+    }
+    match &entry.consent {
+        Some(consent) => {
+            trln!("admin.consent", consent.reference);
+            trln!("admin.purpose", consent.purpose);
+            match consent.retention_until.as_deref() {
+                Some(until) => trln!("admin.retention", until),
+                None => trln!("admin.retention", shared::tr!("admin.retention_unbounded")),
+            }
+        }
+        None => trln!("admin.no_consent"),
+    }
+}
+
+/// Answer an admin request from the exchange
+pub fn answer(req: &AdminRequest) -> AdminResponse {
+    let authorized = tenant::is_admin_key(&req.admin_key);
+    if !authorized {
+        return AdminResponse::error("Invalid admin key".to_string());
+    }
+    let result = Database::load().and_then(|db| {
+        let stats = database_stats(&db)?;
+        Ok(match req.command {
+            AdminCommand::List => AdminResponse::users(user_summaries(&db)?, stats),
+            AdminCommand::Stats => AdminResponse::stats(stats),
+        })
+    });
+    result.unwrap_or_else(|e| AdminResponse::error(format!("Database unreadable: {}", e)))
+}
+
+/// Enrolled users, ordered by tenant and ID
+pub fn user_summaries(db: &Database) -> Result<Vec<UserSummary>, Box<dyn std::error::Error>> {
+    let mut entries: Vec<&TemplateEntry> = db.templates.values().collect();
+    entries.sort_by_key(|e| e.key());
+    entries
+        .into_iter()
+        .map(|entry| {
+            let mut stored_bytes = 0;
+            for blob in entry.stored_blobs() {
+                stored_bytes += blob.stored_bytes()?;
+            }
+            Ok(UserSummary {
+                user_id: entry.user_id.clone(),
+                tenant: entry.tenant().to_string(),
+                created_at: entry.created_at.clone(),
+                updated_at: entry.updated_at.clone(),
+                factors: entry.enrolled_factors(),
+                template_bits: entry.template_bits,
+                enrollment_count: entry.enrollment_count,
+                stored_bytes,
+            })
+        })
+        .collect()
+}
+
+pub fn database_stats(db: &Database) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
+    let backend = StorageConfig::load().backend;
+    let database_bytes = match backend {
+        StorageBackend::Json => Some(fs::metadata(db_path()).map(|m| m.len()).unwrap_or(0)),
+        StorageBackend::Sqlite => Some(fs::metadata(sqlite_path()).map(|m| m.len()).unwrap_or(0)),
+        StorageBackend::Postgres => None,
+    };
+    Ok(DatabaseStats {
+        version: db.version.clone(),
+        backend: format!("{:?}", backend).to_lowercase(),
+        total_users: db.templates.len(),
+        total_templates: db.templates.values().map(|e| e.blobs().len()).sum(),
+        tenants: db.templates.values().map(|e| e.tenant()).collect::<HashSet<_>>().len(),
+        database_bytes,
+        blob_bytes: blob_store::files_size()?,
+    })
+}
+
+fn print_stats(stats: &DatabaseStats) {
+    trln!("admin.stats_version", stats.version, stats.backend);
+    trln!("admin.stats_users", stats.total_users, stats.tenants);
+    trln!("admin.stats_templates", stats.total_templates);
+    match stats.database_bytes {
+        Some(bytes) => trln!("admin.stats_database", bytes),
+        None => trln!("admin.stats_database_postgres"),
+    }
+    trln!("admin.stats_blobs", stats.blob_bytes);
+}
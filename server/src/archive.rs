@@ -0,0 +1,310 @@
+//! Encrypted, versioned archive of the whole server state.
+//!
+//! Layout: `FPFHEARC` | format version (u8) | PBKDF2 salt (16) | AES-GCM nonce (12) | ciphertext.
+//! The plaintext is a bincode-encoded `ArchivePayload` holding the raw
+//! database files. The passphrase is read from `FINGERPRINT_ARCHIVE_PASSPHRASE`
+//! or, if unset, from the first line of stdin.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+
+use crate::audit::{self, audit_path, AuditEvent};
+use crate::blob_store::{self, blobs_dir};
+use crate::database::{self, db_dir, db_path, scoped_key, storage_path, Database, StorageBackend, StorageConfig};
+use crate::policy::policy_path;
+use crate::sqlite_store::sqlite_path;
+use crate::tenant::{self, tenants_path};
+use crate::{attestation_key_path, default_server_key_path};
+
+const MAGIC: &[u8; 8] = b"FPFHEARC";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 600_000;
+const PASSPHRASE_ENV: &str = "FINGERPRINT_ARCHIVE_PASSPHRASE";
+
+/// Files that make up the server state (plus per-tenant `server_key.<tenant>.bin` and `gpu_key*.bin`)
+fn state_file_paths() -> [&'static str; 8] {
+    [
+        db_path(), sqlite_path(), storage_path(), default_server_key_path(), attestation_key_path(), audit_path(), policy_path(), tenants_path(),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ArchivePayload {
+    created_at: String,
+    files: Vec<ArchiveFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ArchiveFile {
+    name: String,       // File name inside the database directory (`blobs/<name>` for template blobs)
+    data: Vec<u8>,
+}
+
+impl ArchivePayload {
+    fn file(&self, path: &str) -> Option<&[u8]> {
+        self.file_named(&file_name(path))
+    }
+
+    fn file_named(&self, name: &str) -> Option<&[u8]> {
+        self.files.iter().find(|f| f.name == name).map(|f| f.data.as_slice())
+    }
+}
+
+/// `server export <archive>`
+pub fn export(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let archive_path = args.first().ok_or("Usage: server export <archive>")?;
+    if StorageConfig::load().backend == StorageBackend::Postgres {
+        println!("⚠️  Templates are kept in Postgres and not archived; back them up with pg_dump");
+    }
+    
+    let mut files = Vec::new();
+    for path in state_files()? {
+        files.push(ArchiveFile { name: file_name(&path), data: fs::read(&path)? });
+        println!("📦 {}", path);
+    }
+    let blobs = blob_files()?;
+    for name in &blobs {
+        files.push(ArchiveFile { name: format!("blobs/{}", name), data: fs::read(blob_store::blob_path(name))? });
+    }
+    if !blobs.is_empty() {
+        println!("📦 {} ({} template blobs)", blobs_dir(), blobs.len());
+    }
+    
+    let payload = ArchivePayload { created_at: chrono::Utc::now().to_rfc3339(), files };
+    let sealed = seal(&bincode::serialize(&payload)?, &read_passphrase()?)?;
+    fs::write(archive_path, &sealed)?;
+    
+    println!("✅ Archive written: {} ({} bytes)", archive_path, sealed.len());
+    Ok(())
+}
+
+/// `server import <archive> [--user <id>]... [--force]`
+///
+/// Users of non-default tenants are given as `tenant/user_id`.
+///
+/// Without `--user` the whole state is restored (refusing to overwrite a
+/// non-empty database unless `--force`). With `--user`, only those users'
+/// templates and audit events are merged into the current state.
+pub fn import(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let archive_path = args.first().ok_or("Usage: server import <archive> [--user <id>]... [--force]")?;
+    let force = args.iter().any(|a| a == "--force");
+    let users: Vec<&String> = args
+        .windows(2)
+        .filter(|w| w[0] == "--user")
+        .map(|w| &w[1])
+        .collect();
+    
+    let sealed = fs::read(archive_path)?;
+    let payload: ArchivePayload = bincode::deserialize(&open(&sealed, &read_passphrase()?)?)?;
+    println!("📂 Archive created at {} ({} files)", payload.created_at, payload.files.len());
+    
+    if users.is_empty() {
+        restore_all(&payload, force)
+    } else {
+        restore_users(&payload, &users)
+    }
+}
+
+fn restore_all(payload: &ArchivePayload, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let stored = Path::new(db_path()).exists() || Path::new(sqlite_path()).exists();
+    if !force && stored && !Database::load()?.templates.is_empty() {
+        return Err("Database is not empty; use --force to overwrite or --user to restore selectively".into());
+    }
+    
+    fs::create_dir_all(db_dir())?;
+    let mut blobs = 0;
+    for file in &payload.files {
+        let (dir, name) = match file.name.strip_prefix("blobs/") {
+            Some(name) => (blobs_dir(), name),
+            None => (db_dir(), file.name.as_str()),
+        };
+        if !safe_name(name) {
+            return Err(format!("Refusing to restore suspicious file name '{}'", file.name).into());
+        }
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(name);
+        fs::write(&path, &file.data)?;
+        if dir == blobs_dir() {
+            blobs += 1;
+        } else {
+            println!("♻️  Restored {}", path.display());
+        }
+    }
+    if blobs > 0 {
+        println!("♻️  Restored {} template blobs", blobs);
+    }
+    Ok(())
+}
+
+fn restore_users(payload: &ArchivePayload, users: &[&String]) -> Result<(), Box<dyn std::error::Error>> {
+    if payload.file(sqlite_path()).is_some() {
+        return Err("Archive holds a SQLite template store; restore it in full instead".into());
+    }
+    let archived: Database = serde_json::from_slice(payload.file(db_path()).ok_or("Archive has no template database")?)?;
+    let store = database::templates()?;
+    
+    for user_id in users {
+        let entry = archived
+            .templates
+            .get(user_id.as_str())
+            .ok_or_else(|| format!("User '{}' is not in the archive", user_id))?;
+        
+        // Templates are only usable with the server key they were evaluated under
+        let key_path = tenant::server_key_path(entry.tenant());
+        if let (Some(archived_key), true) = (payload.file(&key_path), Path::new(&key_path).exists()) {
+            if fs::read(&key_path)? != archived_key {
+                return Err(format!("Archive was made with a different server key ({}); restore it in full instead", key_path).into());
+            }
+        }
+        for blob in entry.stored_blobs() {
+            if let Some(name) = &blob.blob_file {
+                let data = payload
+                    .file_named(&format!("blobs/{}", name))
+                    .ok_or_else(|| format!("Archive is missing blob {} of user '{}'", name, user_id))?;
+                fs::create_dir_all(blobs_dir())?;
+                fs::write(blob_store::blob_path(name), data)?;
+            }
+        }
+        let replaced = store.exists(entry.tenant(), &entry.user_id)?;
+        store.put(entry.clone())?;
+        println!("♻️  Restored user {}{}", user_id, if replaced { " (replaced)" } else { "" });
+    }
+    
+    if let Some(audit_data) = payload.file(audit_path()) {
+        let events = String::from_utf8_lossy(audit_data)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEvent>(line).ok())
+            .filter(|e| users.iter().any(|u| **u == scoped_key(e.tenant(), &e.user_id)))
+            .collect::<Vec<_>>();
+        println!("📜 Restored {} audit events", events.len());
+        for event in events {
+            audit::record(event);
+        }
+    }
+    Ok(())
+}
+
+/// Existing state files, including per-tenant server keys and compressed GPU keys
+fn state_files() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut paths: Vec<String> = state_file_paths()
+        .iter()
+        .filter(|p| Path::new(p).exists())
+        .map(|p| p.to_string())
+        .collect();
+    
+    if Path::new(db_dir()).exists() {
+        for entry in fs::read_dir(db_dir())? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let tenant_key = name.starts_with("server_key.") && name != "server_key.bin";
+            if (tenant_key || name.starts_with("gpu_key.")) && name.ends_with(".bin") {
+                paths.push(format!("{}/{}", db_dir(), name));
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Names of the files in the blob directory
+fn blob_files() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut names = Vec::new();
+    if Path::new(blobs_dir()).exists() {
+        for entry in fs::read_dir(blobs_dir())? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.ends_with(".bin") {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Plain file name: no path separators, not hidden
+fn safe_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.starts_with('.')
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+fn seal(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+    
+    let key = derive_key(passphrase, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Archive encryption failed")?;
+    
+    let mut out = Vec::with_capacity(MAGIC.len() + 1 + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn open(sealed: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let header_len = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+    if sealed.len() < header_len || &sealed[..MAGIC.len()] != MAGIC {
+        return Err("Not a fingerprint-fhe archive".into());
+    }
+    let version = sealed[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(format!("Unsupported archive version {} (expected {})", version, FORMAT_VERSION).into());
+    }
+    
+    let salt = &sealed[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &sealed[MAGIC.len() + 1 + SALT_LEN..header_len];
+    let key = derive_key(passphrase, salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &sealed[header_len..])
+        .map_err(|_| "Archive decryption failed (wrong passphrase or corrupted archive)".into())
+}
+
+fn read_passphrase() -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    eprintln!("🔑 Archive passphrase ({} not set), reading from stdin:", PASSPHRASE_ENV);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let passphrase = line.trim_end_matches(['\r', '\n']).to_string();
+    if passphrase.is_empty() {
+        return Err("Empty archive passphrase".into());
+    }
+    Ok(passphrase)
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_roundtrip_and_wrong_passphrase() {
+        let sealed = seal(b"state", "correct horse").unwrap();
+        assert_eq!(open(&sealed, "correct horse").unwrap(), b"state");
+        assert!(open(&sealed, "wrong").is_err());
+    }
+}
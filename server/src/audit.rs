@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::config;
+use crate::database::DEFAULT_TENANT;
+
+pub fn audit_path() -> &'static str {
+    config::database_file!("audit.jsonl")
+}
+
+/// One line of the server-side audit log (`audit.jsonl` in the database directory)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEvent {
+    pub timestamp: String,
+    pub operation: String,          // "register" | "verify" | "rename" | "merge"
+    pub user_id: String,
+    pub success: bool,
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,     // None = default tenant
+    #[serde(default)]
+    pub origin: Option<String>,     // Exchange the request came from; None = main exchange
+}
+
+impl AuditEvent {
+    pub fn new(operation: &str, user_id: &str, success: bool) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            operation: operation.to_string(),
+            user_id: user_id.to_string(),
+            success,
+            detail: None,
+            tenant: None,
+            origin: None,
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = (tenant != DEFAULT_TENANT).then(|| tenant.to_string());
+        self
+    }
+
+    pub fn with_origin(mut self, origin: &str) -> Self {
+        self.origin = (origin != crate::exchange::DEFAULT_ORIGIN).then(|| origin.to_string());
+        self
+    }
+
+    pub fn tenant(&self) -> &str {
+        self.tenant.as_deref().unwrap_or(DEFAULT_TENANT)
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Append an event. Audit failures are reported but never fail the request.
+pub fn record(event: AuditEvent) {
+    let result = fs::create_dir_all(config::database_dir())
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(audit_path()))
+        .and_then(|mut file| {
+            let line = serde_json::to_string(&event).map_err(std::io::Error::other)?;
+            writeln!(file, "{}", line)
+        });
+    if let Err(e) = result {
+        eprintln!("⚠️  Could not write audit event: {}", e);
+    }
+}
+
+/// Load all events, skipping lines that cannot be parsed
+pub fn load() -> Result<Vec<AuditEvent>, Box<dyn std::error::Error>> {
+    if !Path::new(audit_path()).exists() {
+        return Ok(vec![]);
+    }
+
+    let file = fs::File::open(audit_path())?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(event) = serde_json::from_str::<AuditEvent>(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// Re-attribute every event of `from` to `to` within a tenant. Returns the number of moved events.
+pub fn reassign_user(tenant: &str, from: &str, to: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut events = load()?;
+    let mut moved = 0;
+    for event in events.iter_mut().filter(|e| e.user_id == from && e.tenant() == tenant) {
+        event.user_id = to.to_string();
+        moved += 1;
+    }
+    if moved == 0 {
+        return Ok(0);
+    }
+
+    let mut data = String::new();
+    for event in &events {
+        data.push_str(&serde_json::to_string(event)?);
+        data.push('\n');
+    }
+    let tmp_path = format!("{}.tmp", audit_path());
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, audit_path())?;
+    Ok(moved)
+}
@@ -0,0 +1,146 @@
+//! `server bench-popcount`: compare the popcount implementations on this machine.
+//!
+//! All three run on the same random diff vector, first with trivial
+//! ciphertexts (cheap, shows the circuit overhead) and then with real ones
+//! encrypted under a throwaway key pair. Results are checked against the
+//! plaintext count.
+
+use rand::Rng;
+use shared::{popcount, popcount_tree, popcount_uint16, ripple_gates, tree_gates, uint16_gates, CancellationToken, GateCount};
+use std::time::{Duration, Instant};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, set_server_key, ClientKey, ConfigBuilder, FheBool};
+
+/// Widths benchmarked (the supported template lengths)
+const SUPPORTED_BITS: [usize; 4] = [128, 256, 512, 1024];
+
+struct BenchResult {
+    name: &'static str,
+    gates: GateCount,
+    trivial: Option<Duration>,
+    real: Option<Duration>,
+}
+
+/// `server bench-popcount [--bits N] [--trivial-only]`
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let bits = match args.windows(2).find(|w| w[0] == "--bits") {
+        Some(w) => w[1].parse::<usize>().map_err(|_| format!("Invalid --bits value '{}'", w[1]))?,
+        None => 1024,
+    };
+    if !SUPPORTED_BITS.contains(&bits) {
+        return Err(format!("--bits must be one of {:?}", SUPPORTED_BITS).into());
+    }
+    let trivial_only = args.iter().any(|a| a == "--trivial-only");
+    
+    println!("⏱️  POPCOUNT BENCHMARK ({} bits)", bits);
+    println!("{}", "─".repeat(70));
+    
+    println!("🔑 Generating throwaway FHE keys...");
+    let (client_key, server_key) = generate_keys(ConfigBuilder::default().build());
+    set_server_key(server_key);
+    
+    let mut rng = rand::thread_rng();
+    let diff: Vec<bool> = (0..bits).map(|_| rng.gen_bool(0.2)).collect();
+    let expected = diff.iter().filter(|&&b| b).count();
+    println!("🎲 Random diff vector: {} of {} bits set", expected, bits);
+    
+    let mut results = vec![
+        BenchResult { name: "ripple", gates: ripple_gates(bits), trivial: None, real: None },
+        BenchResult { name: "tree/CSA", gates: tree_gates(bits), trivial: None, real: None },
+        BenchResult { name: "FheUint16", gates: uint16_gates(bits), trivial: None, real: None },
+    ];
+    
+    println!("\n🧪 Trivial ciphertexts");
+    let trivial: Vec<FheBool> = diff.iter().map(|&b| FheBool::encrypt_trivial(b)).collect();
+    for result in results.iter_mut() {
+        result.trivial = Some(time_one(result.name, &trivial, &client_key, expected)?);
+    }
+    
+    if !trivial_only {
+        println!("\n🔐 Real ciphertexts");
+        let real: Vec<FheBool> = diff.iter().map(|&b| FheBool::encrypt(b, &client_key)).collect();
+        for result in results.iter_mut() {
+            result.real = Some(time_one(result.name, &real, &client_key, expected)?);
+        }
+    }
+    
+    println!("\n{}", "─".repeat(70));
+    println!("{:<12} {:>8} {:>8} {:>8} {:>12} {:>12}", "impl", "AND", "XOR", "int ops", "trivial", "real");
+    for r in &results {
+        println!(
+            "{:<12} {:>8} {:>8} {:>8} {:>12} {:>12}",
+            r.name,
+            r.gates.and,
+            r.gates.xor,
+            r.gates.int_ops,
+            format_duration(r.trivial),
+            format_duration(r.real),
+        );
+    }
+    
+    let fastest = results
+        .iter()
+        .filter_map(|r| r.real.or(r.trivial).map(|d| (r.name, d)))
+        .min_by_key(|(_, d)| *d);
+    if let Some((name, _)) = fastest {
+        println!("\n🏆 Fastest on this machine: {}", name);
+    }
+    Ok(())
+}
+
+/// Run one implementation and check its decrypted count
+fn time_one(name: &str, diff: &[FheBool], client_key: &ClientKey, expected: usize) -> Result<Duration, Box<dyn std::error::Error>> {
+    check_input(diff.len())?;
+    let cancel = CancellationToken::new();
+    let start = Instant::now();
+    let count = match name {
+        "ripple" => decrypt_counter(&popcount(diff, &cancel)?, client_key),
+        "tree/CSA" => decrypt_counter(&popcount_tree(diff, &cancel)?, client_key),
+        _ => {
+            let count: u16 = popcount_uint16(diff, &cancel)?.decrypt(client_key);
+            count as usize
+        }
+    };
+    let elapsed = start.elapsed();
+    
+    if count != expected {
+        return Err(format!("{} returned {} instead of {}", name, count, expected).into());
+    }
+    println!("   ✅ {:<10} {:>10}", name, format_duration(Some(elapsed)));
+    Ok(elapsed)
+}
+
+/// Every implementation needs bits to count, and the FheUint16 sum must not wrap
+fn check_input(bits: usize) -> Result<(), String> {
+    if bits == 0 || bits >= u16::MAX as usize {
+        return Err(format!("Popcount needs 1 to {} bits, got {}", u16::MAX - 1, bits));
+    }
+    Ok(())
+}
+
+fn decrypt_counter(bits: &[FheBool], client_key: &ClientKey) -> usize {
+    bits.iter()
+        .enumerate()
+        .filter(|(_, b)| b.decrypt(client_key))
+        .map(|(i, _)| 1 << i)
+        .sum()
+}
+
+fn format_duration(d: Option<Duration>) -> String {
+    match d {
+        Some(d) => format!("{:.2?}", d),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_and_oversized_inputs_are_errors() {
+        assert!(check_input(0).is_err());
+        assert!(check_input(u16::MAX as usize).is_err());
+        assert!(SUPPORTED_BITS.iter().all(|&bits| check_input(bits).is_ok()));
+    }
+}
@@ -0,0 +1,122 @@
+//! Incremental deserialization of FHE blobs under a per-job memory budget.
+//!
+//! bincode encodes `Vec<FheBool>` as a u64 length followed by the elements,
+//! so the vector can be read element by element: the length is validated
+//! before anything is allocated, and every element is charged to the job's
+//! budget as it is materialized.
+
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use tfhe::FheBool;
+
+use shared::FingerprintError;
+
+/// Tracks an estimate of the FHE data a job holds in memory
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    used: usize,
+    peak: usize,
+}
+
+impl MemoryBudget {
+    /// `limit` in bytes; None disables enforcement (usage is still tracked)
+    pub fn new(limit: Option<usize>) -> Self {
+        Self { limit, used: 0, peak: 0 }
+    }
+
+    pub fn charge(&mut self, bytes: usize) -> Result<(), String> {
+        let used = self.used + bytes;
+        if let Some(limit) = self.limit {
+            if used > limit {
+                return Err(format!(
+                    "Job exceeds memory budget ({} MB > {} MB)",
+                    used / (1024 * 1024),
+                    limit / (1024 * 1024)
+                ));
+            }
+        }
+        self.used = used;
+        self.peak = self.peak.max(used);
+        Ok(())
+    }
+
+    pub fn release(&mut self, bytes: usize) {
+        self.used = self.used.saturating_sub(bytes);
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak
+    }
+}
+
+/// Counts bytes consumed from the underlying reader
+struct CountingReader<R> {
+    inner: R,
+    count: usize,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+}
+
+/// Deserialize a bincode `Vec<FheBool>` element by element (see `read_vec`)
+pub fn read_fhe_bits<R: Read>(
+    reader: R,
+    max_len: usize,
+    budget: &mut MemoryBudget,
+) -> Result<Vec<FheBool>, FingerprintError> {
+    read_vec(reader, max_len, budget)
+}
+
+/// Deserialize a bincode `Vec<T>` element by element.
+///
+/// Fails before allocating if the encoded length exceeds `max_len`, and as
+/// soon as the budget is exhausted.
+pub fn read_vec<T: DeserializeOwned, R: Read>(
+    reader: R,
+    max_len: usize,
+    budget: &mut MemoryBudget,
+) -> Result<Vec<T>, FingerprintError> {
+    let mut reader = CountingReader { inner: reader, count: 0 };
+    let len: u64 = bincode::deserialize_from(&mut reader)?;
+    if len > max_len as u64 {
+        return Err(FingerprintError::Protocol(format!("Encrypted vector has {} elements, at most {} allowed", len, max_len)));
+    }
+
+    let mut items = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        let before = reader.count;
+        let item: T = bincode::deserialize_from(&mut reader)?;
+        budget.charge(reader.count - before).map_err(FingerprintError::Protocol)?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_length_and_budget() {
+        let values: Vec<u64> = (0..80).collect();
+        let bytes = bincode::serialize(&values).unwrap();
+
+        let mut budget = MemoryBudget::new(None);
+        let decoded: Vec<u64> = read_vec(bytes.as_slice(), 80, &mut budget).unwrap();
+        assert_eq!(decoded, values);
+        assert_eq!(budget.peak(), 80 * 8);
+
+        assert!(read_vec::<u64, _>(bytes.as_slice(), 79, &mut MemoryBudget::new(None)).is_err());
+        assert!(read_vec::<u64, _>(bytes.as_slice(), 80, &mut MemoryBudget::new(Some(80 * 8 - 1))).is_err());
+    }
+}
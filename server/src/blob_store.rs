@@ -0,0 +1,292 @@
+//! Template blob files (`../database/blobs/<checksum>.bin`).
+//!
+//! Each file holds three length-prefixed sections (u64 LE length + bytes) in
+//! the order the FHE evaluation consumes them: encrypted key, encrypted IV,
+//! Trivium ciphertext. Opening a blob reads it whole, unseals it if a
+//! database key is set (see database.rs) and checks it against the checksum
+//! its entry recorded; a verification then reads the key and IV element by
+//! element and the ciphertext bits as the keystream is produced.
+//! Files are content-addressed by checksum, so renames and merges don't move
+//! them; `server compact` removes unreferenced ones. The SQLite store keeps
+//! the same bytes in a BLOB row instead of a file (see sqlite_store.rs).
+
+use std::fs;
+use std::io::{BufReader, Cursor, Read};
+use std::path::PathBuf;
+use tfhe::FheBool;
+
+use crate::blob::{self, MemoryBudget};
+use crate::config;
+use crate::database::{self, blob_checksum, Storage, TemplateBlob};
+
+pub fn blobs_dir() -> &'static str {
+    config::database_file!("blobs")
+}
+
+/// (ciphertext, encrypted key, encrypted IV) bytes
+pub type TemplateBytes = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+pub fn blob_path(name: &str) -> PathBuf {
+    PathBuf::from(blobs_dir()).join(name)
+}
+
+impl TemplateBlob {
+    /// Move inline bytes into a blob of the configured storage. No-op for blobs already stored.
+    pub fn externalize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.externalize_to(database::storage()?)
+    }
+
+    /// [`externalize`](Self::externalize) into `storage`
+    pub fn externalize_to(&mut self, storage: &dyn Storage) -> Result<(), Box<dyn std::error::Error>> {
+        if self.blob_file.is_some() {
+            return Ok(());
+        }
+        let checksum = self.checksum.clone().unwrap_or_else(|| {
+            blob_checksum(&self.ciphertext, &self.encrypted_key_bytes, &self.encrypted_iv_bytes)
+        });
+        let name = format!("{}.bin", checksum);
+
+        let sections = [&self.encrypted_key_bytes, &self.encrypted_iv_bytes, &self.ciphertext];
+        let mut data = Vec::with_capacity(24 + sections.iter().map(|s| s.len()).sum::<usize>());
+        for section in sections {
+            data.extend_from_slice(&(section.len() as u64).to_le_bytes());
+            data.extend_from_slice(section);
+        }
+        storage.put_blob(&name, &database::seal_blob(&name, &data)?)?;
+
+        self.checksum = Some(checksum);
+        self.blob_file = Some(name);
+        self.blob_in_database = storage.blobs_in_database();
+        self.ciphertext = Vec::new();
+        self.encrypted_key_bytes = Vec::new();
+        self.encrypted_iv_bytes = Vec::new();
+        Ok(())
+    }
+
+    /// Sequential reader over key, IV and ciphertext, once the stored bytes
+    /// match the recorded checksum
+    pub fn open(&self) -> Result<TemplateReader<'_>, Box<dyn std::error::Error>> {
+        let Some(name) = &self.blob_file else {
+            return Ok(TemplateReader::Inline(self));
+        };
+        let stored = if self.blob_in_database {
+            database::storage()?.get_blob(name).map_err(|e| format!("Blob {} unreadable: {}", name, e))?
+        } else {
+            fs::read(blob_path(name)).map_err(|e| format!("Blob file {} unreadable: {}", name, e))?
+        };
+        let data = database::open_blob(name, stored)?;
+        check_sections(name, &data, self.checksum.as_deref())?;
+        Ok(TemplateReader::Sections(Box::new(Cursor::new(data))))
+    }
+
+    /// Bytes this template occupies: inline fields, blob file or blob row
+    pub fn stored_bytes(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(match &self.blob_file {
+            Some(name) if self.blob_in_database => database::storage()?.blob_len(name)?,
+            Some(name) => fs::metadata(blob_path(name))?.len(),
+            None => (self.ciphertext.len() + self.encrypted_key_bytes.len() + self.encrypted_iv_bytes.len()) as u64,
+        })
+    }
+
+    /// Full (ciphertext, key, IV) bytes, for admin tools that check or copy templates
+    pub fn materialize(&self) -> Result<TemplateBytes, Box<dyn std::error::Error>> {
+        match self.open()? {
+            TemplateReader::Inline(b) => Ok((
+                b.ciphertext.clone(),
+                b.encrypted_key_bytes.clone(),
+                b.encrypted_iv_bytes.clone(),
+            )),
+            TemplateReader::Sections(mut r) => {
+                let key = read_section(&mut r)?;
+                let iv = read_section(&mut r)?;
+                let ciphertext = read_section(&mut r)?;
+                Ok((ciphertext, key, iv))
+            }
+        }
+    }
+}
+
+pub enum TemplateReader<'a> {
+    Inline(&'a TemplateBlob),
+    Sections(Box<dyn Read + Send>),    // Blob file or SQLite row
+}
+
+impl<'a> TemplateReader<'a> {
+    /// Encrypted Trivium key; must be read first
+    pub fn read_key(&mut self, max_len: usize, budget: &mut MemoryBudget) -> Result<Vec<FheBool>, Box<dyn std::error::Error>> {
+        match self {
+            TemplateReader::Inline(b) => Ok(blob::read_fhe_bits(b.encrypted_key_bytes.as_slice(), max_len, budget)?),
+            TemplateReader::Sections(r) => read_fhe_section(r, max_len, budget),
+        }
+    }
+
+    /// Encrypted Trivium IV; must be read after the key
+    pub fn read_iv(&mut self, max_len: usize, budget: &mut MemoryBudget) -> Result<Vec<FheBool>, Box<dyn std::error::Error>> {
+        match self {
+            TemplateReader::Inline(b) => Ok(blob::read_fhe_bits(b.encrypted_iv_bytes.as_slice(), max_len, budget)?),
+            TemplateReader::Sections(r) => read_fhe_section(r, max_len, budget),
+        }
+    }
+
+    /// Ciphertext bits (LSB first per byte), pulled from storage as they are consumed.
+    /// Must be called after the key and IV were read.
+    pub fn ciphertext_bits(self) -> Result<CiphertextBits<'a>, Box<dyn std::error::Error>> {
+        Ok(match self {
+            TemplateReader::Inline(b) => CiphertextBits {
+                remaining: b.ciphertext.len() as u64,
+                bytes: Box::new(b.ciphertext.iter().map(|&byte| Ok(byte))),
+                current: 0,
+                bit: 8,
+            },
+            TemplateReader::Sections(mut r) => {
+                let len = read_len(&mut r)?;
+                CiphertextBits {
+                    remaining: len,
+                    bytes: Box::new(BufReader::new(r.take(len)).bytes()),
+                    current: 0,
+                    bit: 8,
+                }
+            }
+        })
+    }
+}
+
+/// Iterator over ciphertext bits; a short file yields an `UnexpectedEof` error
+pub struct CiphertextBits<'a> {
+    bytes: Box<dyn Iterator<Item = std::io::Result<u8>> + 'a>,
+    remaining: u64,
+    current: u8,
+    bit: u8,
+}
+
+impl CiphertextBits<'_> {
+    /// Number of bits still to come
+    pub fn remaining_bits(&self) -> usize {
+        self.remaining as usize * 8 + (8 - self.bit as usize)
+    }
+}
+
+impl Iterator for CiphertextBits<'_> {
+    type Item = std::io::Result<bool>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bit == 8 {
+            if self.remaining == 0 {
+                return None;
+            }
+            self.current = match self.bytes.next() {
+                Some(Ok(byte)) => byte,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.remaining = 0;
+                    return Some(Err(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+            };
+            self.remaining -= 1;
+            self.bit = 0;
+        }
+        let value = (self.current >> self.bit) & 1 == 1;
+        self.bit += 1;
+        Some(Ok(value))
+    }
+}
+
+fn read_len<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut len = [0u8; 8];
+    reader.read_exact(&mut len)?;
+    Ok(u64::from_le_bytes(len))
+}
+
+fn read_section<R: Read>(reader: &mut R) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let len = read_len(reader)?;
+    let mut data = Vec::new();
+    reader.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        return Err("Blob file is truncated".into());
+    }
+    Ok(data)
+}
+
+/// Check blob `data` against the checksum of its entry (older entries have none)
+fn check_sections(name: &str, data: &[u8], checksum: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(expected) = checksum else { return Ok(()) };
+    let mut reader = data;
+    let key = read_section(&mut reader)?;
+    let iv = read_section(&mut reader)?;
+    let ciphertext = read_section(&mut reader)?;
+    if !reader.is_empty() || blob_checksum(&ciphertext, &key, &iv) != expected {
+        return Err(format!("Blob {} doesn't match its checksum (tampered or corrupt)", name).into());
+    }
+    Ok(())
+}
+
+fn read_fhe_section<R: Read>(reader: &mut R, max_len: usize, budget: &mut MemoryBudget) -> Result<Vec<FheBool>, Box<dyn std::error::Error>> {
+    let len = read_len(reader)?;
+    let mut section = reader.take(len);
+    let bits = blob::read_fhe_bits(&mut section, max_len, budget)?;
+    if section.limit() != 0 {
+        return Err("Trailing data in encrypted key/IV section".into());
+    }
+    Ok(bits)
+}
+
+/// Total size of the blob files
+pub fn files_size() -> Result<u64, Box<dyn std::error::Error>> {
+    if !std::path::Path::new(blobs_dir()).exists() {
+        return Ok(0);
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(blobs_dir())? {
+        total += entry?.metadata()?.len();
+    }
+    Ok(total)
+}
+
+/// Blob files not referenced by any template
+pub fn orphaned_files(referenced: &std::collections::HashSet<String>) -> Result<Vec<(PathBuf, u64)>, Box<dyn std::error::Error>> {
+    let mut orphans = Vec::new();
+    if !std::path::Path::new(blobs_dir()).exists() {
+        return Ok(orphans);
+    }
+    for entry in fs::read_dir(blobs_dir())? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !referenced.contains(&name) {
+            orphans.push((entry.path(), entry.metadata()?.len()));
+        }
+    }
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ciphertext_bits_are_lsb_first() {
+        let blob = TemplateBlob::new(vec![0b0000_0101, 0x80], vec![], vec![]);
+        let bits = TemplateReader::Inline(&blob).ciphertext_bits().unwrap();
+        assert_eq!(bits.remaining_bits(), 16);
+        let bits: Vec<bool> = bits.map(|b| b.unwrap()).collect();
+        assert_eq!(&bits[..3], &[true, false, true]);
+        assert!(bits[15]);
+        assert_eq!(bits.iter().filter(|&&b| b).count(), 3);
+    }
+
+    #[test]
+    fn blobs_are_checked_against_their_checksum() {
+        let sections = |key: &[u8], iv: &[u8], ciphertext: &[u8]| {
+            let mut data = Vec::new();
+            for section in [key, iv, ciphertext] {
+                data.extend_from_slice(&(section.len() as u64).to_le_bytes());
+                data.extend_from_slice(section);
+            }
+            data
+        };
+        let checksum = blob_checksum(&[3], &[1], &[2]);
+        assert!(check_sections("a.bin", &sections(&[1], &[2], &[3]), Some(&checksum)).is_ok());
+        assert!(check_sections("a.bin", &sections(&[1], &[2], &[4]), Some(&checksum)).is_err());
+        assert!(check_sections("a.bin", &sections(&[1], &[2], &[3])[..20], Some(&checksum)).is_err());
+        assert!(check_sections("a.bin", &sections(&[9], &[9], &[9]), None).is_ok());
+    }
+}
@@ -0,0 +1,169 @@
+//! Checkpoints of running verify jobs.
+//!
+//! A verification spends most of its time in FHE-Trivium decryptions and
+//! popcounts. While a job runs, each of those stages saves its state under
+//! `../database/checkpoints/<job>/` every `checkpoint_every_cycles` clocks
+//! or counted bits (limits.json, default 256, 0 disables): the Trivium state
+//! with the plaintext bits so far, the popcount accumulator, and finished
+//! match results. A job requeued after a restart (see jobs.rs) loads them
+//! and only redoes the work since the last checkpoint.
+//!
+//! Everything saved is ciphertext under the client's key. A job's directory
+//! is removed when it finishes; maintenance removes leftovers after
+//! `stale_after_hours` (exchanges.json).
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use shared::etrln;
+use shared::session::SessionClaim;
+use std::fs;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config;
+use crate::limits::LimitsConfig;
+
+pub fn checkpoint_dir() -> &'static str {
+    config::database_file!("checkpoints")
+}
+pub const DEFAULT_EVERY: usize = 256;
+const SESSION_STAGE: &str = "session";
+
+/// Saved stages of one job
+pub struct Checkpoints {
+    dir: PathBuf,
+    every: usize,   // Cycles between saves (0 = checkpointing off)
+}
+
+impl Checkpoints {
+    /// Checkpoints of the job claimed as `job_path`; the name survives restarts
+    pub fn for_job(job_path: &Path) -> Self {
+        let every = LimitsConfig::load().checkpoint_every_cycles.unwrap_or(DEFAULT_EVERY);
+        let stem = job_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        Self { dir: Path::new(checkpoint_dir()).join(stem), every }
+    }
+
+    pub fn every(&self) -> usize {
+        self.every
+    }
+
+    /// Saved state of a stage; unreadable checkpoints are ignored (the stage restarts)
+    pub fn load<T: DeserializeOwned>(&self, stage: &str) -> Option<T> {
+        if self.every == 0 {
+            return None;
+        }
+        let path = self.path(stage);
+        let file = fs::File::open(&path).ok()?;
+        match bincode::deserialize_from(BufReader::new(file)) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                etrln!("server.checkpoint_unreadable", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Save a stage, best effort: a failed save only costs work after a crash
+    pub fn save<T: Serialize>(&self, stage: &str, value: &T) {
+        if self.every == 0 {
+            return;
+        }
+        if let Err(e) = self.write(stage, value) {
+            etrln!("server.checkpoint_failed", stage, e);
+        }
+    }
+
+    fn write<T: Serialize>(&self, stage: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(stage);
+        let tmp = path.with_extension("bin.tmp");
+        bincode::serialize_into(BufWriter::new(fs::File::create(&tmp)?), value)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Keep the session the job was authenticated with at intake, whether or not
+    /// checkpointing is on: a resumed job can't present its used session again
+    pub fn save_session(&self, claim: &SessionClaim) -> Result<(), Box<dyn std::error::Error>> {
+        self.write(SESSION_STAGE, claim)
+    }
+
+    /// The session saved at intake, if any
+    pub fn session(&self) -> Option<SessionClaim> {
+        let file = fs::File::open(self.path(SESSION_STAGE)).ok()?;
+        bincode::deserialize_from(BufReader::new(file)).ok()
+    }
+
+    /// Drop every stage of the job
+    pub fn clear(&self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+
+    fn path(&self, stage: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", stage))
+    }
+}
+
+/// Remove checkpoint directories last written before `cutoff`
+pub fn purge(cutoff: SystemTime) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(checkpoint_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && entry.metadata()?.modified()? < cutoff {
+            fs::remove_dir_all(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_round_trip_and_clear() {
+        let dir = std::env::temp_dir().join(format!("checkpoint_test_{}", std::process::id()));
+        let checkpoints = Checkpoints { dir, every: 4 };
+        assert!(checkpoints.load::<Vec<u32>>("probe_decrypt").is_none());
+
+        checkpoints.save("probe_decrypt", &vec![1u32, 2, 3]);
+        assert_eq!(checkpoints.load::<Vec<u32>>("probe_decrypt"), Some(vec![1, 2, 3]));
+
+        checkpoints.clear();
+        assert!(checkpoints.load::<Vec<u32>>("probe_decrypt").is_none());
+    }
+
+    #[test]
+    fn disabled_checkpoints_neither_save_nor_load() {
+        let dir = std::env::temp_dir().join(format!("checkpoint_off_{}", std::process::id()));
+        let checkpoints = Checkpoints { dir: dir.clone(), every: 0 };
+        checkpoints.save("probe_decrypt", &1u8);
+        assert!(!dir.exists());
+        assert!(checkpoints.load::<u8>("probe_decrypt").is_none());
+    }
+
+    #[test]
+    fn intake_session_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("checkpoint_session_{}", std::process::id()));
+        let claim = SessionClaim {
+            session_id: "sid".to_string(),
+            challenge: "ch".to_string(),
+            expires_at: (chrono::Utc::now() + chrono::Duration::seconds(300)).to_rfc3339(),
+        };
+        // Kept even with checkpointing off
+        Checkpoints { dir: dir.clone(), every: 0 }.save_session(&claim).unwrap();
+
+        // The requeued job opens the same directory
+        let resumed = Checkpoints { dir, every: 0 };
+        assert_eq!(resumed.session(), Some(claim));
+        resumed.clear();
+        assert!(resumed.session().is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::blob_store;
+use crate::database::{self, db_dir, db_path, AuxTemplate, StorageBackend, StorageConfig, TemplateBlob, TemplateEntry};
+use std::collections::HashSet;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct CompactReport {
+    pub kept: usize,
+    pub tombstones: usize,          // null or unparseable entries
+    pub truncated: usize,           // entries dropped for short/empty blobs
+    pub aux_dropped: usize,         // duress/fallback templates dropped for short blobs
+    pub backups_removed: usize,
+    pub blobs_externalized: usize,  // inline templates moved to blob files
+    pub blobs_removed: usize,       // blob files no longer referenced by any template
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactReport {
+    pub fn reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// `server compact [--dry-run]`
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    
+    println!("🧹 COMPACTING TEMPLATE STORE{}", if dry_run { " (dry run)" } else { "" });
+    println!("{}", "─".repeat(70));
+    
+    let report = compact(dry_run)?;
+    
+    println!("✅ Entries kept:        {}", report.kept);
+    println!("🪦 Tombstones dropped:  {}", report.tombstones);
+    println!("✂️  Truncated dropped:   {}", report.truncated);
+    println!("✂️  Aux templates dropped: {}", report.aux_dropped);
+    println!("📦 Backups removed:     {}", report.backups_removed);
+    println!("🗃️  Blobs externalized:  {}", report.blobs_externalized);
+    println!("🗑️  Orphan blobs removed: {}", report.blobs_removed);
+    println!("💾 Size: {} -> {} bytes ({} bytes reclaimed)",
+        report.bytes_before, report.bytes_after, report.reclaimed());
+    Ok(())
+}
+
+pub fn compact(dry_run: bool) -> Result<CompactReport, Box<dyn std::error::Error>> {
+    let mut report = CompactReport::default();
+    let backend = StorageConfig::load().backend;
+    if backend != StorageBackend::Json {
+        // templates.json is only the import source then; leave it alone
+        println!("ℹ️  {:?} storage in use, nothing to compact", backend);
+        return Ok(report);
+    }
+    // Held until the rewrite is done: a template saved after the scan would
+    // otherwise lose its blob file as an orphan
+    let _db_guard = database::lock();
+    if !Path::new(db_path()).exists() {
+        return Ok(report);
+    }
+    
+    // Parse loosely so that single bad entries don't block the rewrite
+    let data = fs::read_to_string(db_path())?;
+    report.bytes_before = data.len() as u64;
+    let mut raw: Value = serde_json::from_str(&data)?;
+    
+    let templates = raw
+        .get_mut("templates")
+        .and_then(|t| t.as_object_mut())
+        .ok_or("Database has no templates object")?;
+    
+    let mut kept = serde_json::Map::new();
+    let mut referenced = HashSet::new();
+    for (user_id, value) in std::mem::take(templates) {
+        // A sealed entry that doesn't open is a key problem, not a tombstone
+        let sealed = database::is_sealed(&value);
+        let mut entry: TemplateEntry = match database::open_entry(&user_id, value) {
+            Ok(e) => e,
+            Err(e) if sealed => return Err(e),
+            Err(_) => {
+                report.tombstones += 1;
+                continue;
+            }
+        };
+        let template_bytes = entry.template_bytes();
+        if !blob_ok(&entry.blob, template_bytes) {
+            println!("   ✂️  {}: truncated template", user_id);
+            report.truncated += 1;
+            continue;
+        }
+        
+        if entry.duress.as_ref().is_some_and(|d| !aux_ok(d, template_bytes)) {
+            entry.duress = None;
+            report.aux_dropped += 1;
+        }
+        let before = entry.factors.len();
+        entry.factors.retain(|_, aux| aux_ok(aux, template_bytes));
+        report.aux_dropped += before - entry.factors.len();
+        
+        // Move legacy inline templates out of templates.json
+        for blob in entry.blobs_mut() {
+            if blob.blob_file.is_none() {
+                report.blobs_externalized += 1;
+                if !dry_run {
+                    blob.externalize()?;
+                } else if let Some(checksum) = &blob.checksum {
+                    referenced.insert(format!("{}.bin", checksum));
+                }
+            }
+            referenced.extend(blob.blob_file.clone());
+        }
+        
+        let stored = database::seal_entry(&user_id, &entry)?;
+        kept.insert(user_id, stored);
+    }
+    report.kept = kept.len();
+    *templates = kept;
+    
+    let json = serde_json::to_string_pretty(&raw)?;
+    report.bytes_after = json.len() as u64;
+    
+    let backups = backup_files()?;
+    report.backups_removed = backups.len();
+    for (_, size) in &backups {
+        report.bytes_before += size;
+    }
+    let orphans = blob_store::orphaned_files(&referenced)?;
+    report.blobs_removed = orphans.len();
+    for (_, size) in &orphans {
+        report.bytes_before += size;
+    }
+    
+    if !dry_run {
+        let tmp_path = format!("{}.tmp", db_path());
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, db_path())?;
+        for (path, _) in backups.iter().chain(&orphans) {
+            fs::remove_file(path)?;
+        }
+    }
+    
+    Ok(report)
+}
+
+/// Inline or file-backed; a missing or unreadable blob file counts as truncated
+fn blob_ok(blob: &TemplateBlob, template_bytes: usize) -> bool {
+    match blob.materialize() {
+        Ok((ciphertext, key_bytes, iv_bytes)) => {
+            ciphertext.len() == template_bytes && !key_bytes.is_empty() && !iv_bytes.is_empty()
+        }
+        Err(_) => false,
+    }
+}
+
+fn aux_ok(aux: &AuxTemplate, template_bytes: usize) -> bool {
+    blob_ok(&aux.blob, template_bytes)
+}
+
+/// `templates.json.backup.<ts>` files and leftover `.tmp` files
+fn backup_files() -> Result<Vec<(std::path::PathBuf, u64)>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(db_dir())? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("templates.json.backup.") || name.ends_with(".json.tmp") {
+            files.push((entry.path(), entry.metadata()?.len()));
+        }
+    }
+    Ok(files)
+}
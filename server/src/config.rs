@@ -0,0 +1,233 @@
+//! Server configuration file (`fingerprint.toml`).
+//!
+//! Read once, from the working directory or the file `FINGERPRINT_CONFIG`
+//! names. Every key is optional and a missing file is an empty one, so a
+//! server without it behaves as before: databases under `../database`, the
+//! exchange at `../exchange`, storage, thresholds and backends from the
+//! JSON files there. Single values can also be given in the environment,
+//! which wins over the file:
+//!
+//! ```toml
+//! [paths]
+//! database = "/var/lib/fingerprint"   # FINGERPRINT_DATABASE_DIR
+//! exchange = "/srv/exchange"          # FINGERPRINT_EXCHANGE_DIR
+//!
+//! [storage]
+//! backend = "sqlite"                  # FINGERPRINT_STORAGE; overrides storage.json
+//!
+//! [matching]
+//! threshold_bits = 204                # FINGERPRINT_THRESHOLD_BITS; tuned thresholds still win
+//! result_mode = "match_only"          # FINGERPRINT_RESULT_MODE; a tenant's (tenants.json) still wins
+//!
+//! [fhe]
+//! compute_backend = "cpu"             # FINGERPRINT_COMPUTE_BACKEND; limits.json per job still wins
+//!
+//! [logging]
+//! filter = "shared=debug"             # FINGERPRINT_LOG (see shared::logging)
+//! ```
+//!
+//! Files the server keeps are named relative to these directories; the
+//! accessors modules use for them are made with [`database_file`] and
+//! [`exchange_file`].
+
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use shared::{ComputeBackend, ResultMode};
+
+use crate::database::StorageBackend;
+
+/// Configuration file to read instead of `fingerprint.toml`
+pub const CONFIG_ENV: &str = "FINGERPRINT_CONFIG";
+const CONFIG_PATH: &str = "fingerprint.toml";
+
+const DEFAULT_DATABASE_DIR: &str = "../database";
+const DEFAULT_EXCHANGE_DIR: &str = "../exchange";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub paths: PathsConfig,
+    pub storage: StorageSection,
+    pub matching: MatchingConfig,
+    pub fhe: FheConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub database: String,   // Templates, keys, jobs and the JSON configuration files
+    pub exchange: String,   // Main exchange directory (more in exchanges.json)
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            database: DEFAULT_DATABASE_DIR.to_string(),
+            exchange: DEFAULT_EXCHANGE_DIR.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSection {
+    pub backend: Option<StorageBackend>,   // None = storage.json's
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchingConfig {
+    pub threshold_bits: Option<usize>,     // None = 20% of the template bits
+    pub result_mode: Option<ResultMode>,   // None = match_and_distance; clients can only narrow it
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FheConfig {
+    pub compute_backend: Option<ComputeBackend>,   // None = gpu when built with it and one is present
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub filter: Option<String>,            // `RUST_LOG`-style directives (default: warnings only)
+}
+
+impl Config {
+    /// The configuration file with the environment applied over it; a
+    /// missing file gives the defaults, an invalid one is reported and ignored
+    pub fn load() -> Self {
+        let path = std::env::var(CONFIG_ENV).unwrap_or_else(|_| CONFIG_PATH.to_string());
+        let mut config = if Path::new(&path).exists() {
+            match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| Self::parse(&data))
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("⚠️  Invalid configuration file {} ({}), using defaults", path, e);
+                    Self::default()
+                }
+            }
+        } else {
+            Self::default()
+        };
+        config.apply_env(|name| std::env::var(name).ok());
+        config
+    }
+
+    pub fn parse(data: &str) -> Result<Self, String> {
+        toml::from_str(data).map_err(|e| e.to_string())
+    }
+
+    /// Override values with the ones `var` finds; unparsable ones are reported and skipped
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(dir) = var("FINGERPRINT_DATABASE_DIR") {
+            self.paths.database = dir;
+        }
+        if let Some(dir) = var("FINGERPRINT_EXCHANGE_DIR") {
+            self.paths.exchange = dir;
+        }
+        env_value(&var, "FINGERPRINT_STORAGE", &mut self.storage.backend);
+        env_value(&var, "FINGERPRINT_THRESHOLD_BITS", &mut self.matching.threshold_bits);
+        env_value(&var, "FINGERPRINT_RESULT_MODE", &mut self.matching.result_mode);
+        env_value(&var, "FINGERPRINT_COMPUTE_BACKEND", &mut self.fhe.compute_backend);
+    }
+}
+
+/// Set `target` from variable `name`, parsed as its TOML value would be
+fn env_value<T: DeserializeOwned>(var: &impl Fn(&str) -> Option<String>, name: &str, target: &mut Option<T>) {
+    let Some(value) = var(name) else { return };
+    let value = value.trim();
+    let parsed: Result<T, serde::de::value::Error> = match value.parse::<u64>() {
+        Ok(number) => T::deserialize(number.into_deserializer()),
+        Err(_) => T::deserialize(value.into_deserializer()),
+    };
+    match parsed {
+        Ok(parsed) => *target = Some(parsed),
+        Err(e) => eprintln!("⚠️  Invalid {} ({}: {}), ignored", name, value, e),
+    }
+}
+
+/// The server's configuration, loaded on first use
+pub fn get() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(Config::load)
+}
+
+pub fn database_dir() -> &'static str {
+    &get().paths.database
+}
+
+pub fn exchange_dir() -> &'static str {
+    &get().paths.exchange
+}
+
+/// `name` inside the database directory
+pub fn database_path(name: &str) -> String {
+    Path::new(database_dir()).join(name).to_string_lossy().into_owned()
+}
+
+/// `name` inside the main exchange directory
+pub fn exchange_path(name: &str) -> String {
+    Path::new(exchange_dir()).join(name).to_string_lossy().into_owned()
+}
+
+/// Path of a file or directory under the database directory, resolved once:
+/// `database_file!("templates.json")` is a `&'static str`
+macro_rules! database_file {
+    ($name:literal) => {{
+        static PATH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        PATH.get_or_init(|| $crate::config::database_path($name)).as_str()
+    }};
+}
+
+/// Like [`database_file`], under the main exchange directory
+macro_rules! exchange_file {
+    ($name:literal) => {{
+        static PATH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        PATH.get_or_init(|| $crate::config::exchange_path($name)).as_str()
+    }};
+}
+
+pub(crate) use database_file;
+pub(crate) use exchange_file;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_keep_the_defaults() {
+        let config = Config::parse("[storage]\nbackend = \"sqlite\"\n").unwrap();
+        assert_eq!(config.paths, PathsConfig::default());
+        assert_eq!(config.storage.backend, Some(StorageBackend::Sqlite));
+        assert_eq!(config.matching.threshold_bits, None);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("[paths]\ndatabse = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() {
+        let mut config = Config::parse("[paths]\ndatabase = \"/srv/db\"\n[matching]\nthreshold_bits = 180\n").unwrap();
+        config.apply_env(|name| match name {
+            "FINGERPRINT_EXCHANGE_DIR" => Some("/srv/exchange".into()),
+            "FINGERPRINT_THRESHOLD_BITS" => Some("200".into()),
+            "FINGERPRINT_COMPUTE_BACKEND" => Some("gpu".into()),
+            "FINGERPRINT_STORAGE" => Some("mongo".into()),
+            "FINGERPRINT_RESULT_MODE" => Some("match_only".into()),
+            _ => None,
+        });
+        assert_eq!(config.paths.database, "/srv/db");
+        assert_eq!(config.paths.exchange, "/srv/exchange");
+        assert_eq!(config.matching.threshold_bits, Some(200));
+        assert_eq!(config.fhe.compute_backend, Some(ComputeBackend::Gpu));
+        assert_eq!(config.storage.backend, None);
+        assert_eq!(config.matching.result_mode, Some(ResultMode::MatchOnly));
+    }
+}
@@ -0,0 +1,447 @@
+//! File exchange directories watched by the server.
+//!
+//! The main `../exchange` directory is always watched. More workstations get
+//! their own directory, either listed in `../database/exchanges.json` or
+//! auto-discovered as subdirectories of `../exchange/clients` (each
+//! workstation mounts its directory as its local `../exchange`). Requests are
+//! tagged with the directory they came from and responses go back there.
+//!
+//! Clients write into their private slot `users/<slot>/` of an exchange
+//! (legacy clients into the exchange root); slots are polled like their
+//! exchange and answered in place. Slots readable by other accounts are skipped.
+//!
+//! Requests sealed to the server's exchange key (published as
+//! `exchange_key.json` in every exchange, see `shared::sealed`) are decrypted
+//! on claim and answered sealed to the client's key; plaintext requests from
+//! older clients are still served unless `require_encryption` is set.
+//!
+//! A configured `path` may also be an object-storage location
+//! (`s3://bucket/prefix`, see `shared::transport`) for workstations that
+//! can't share a directory with the server.
+//!
+//! ```json
+//! { "dirs": [{ "name": "lab-pc", "path": "/mnt/lab-pc/exchange" },
+//!            { "name": "branch", "path": "s3://fp-exchange/branch" }],
+//!   "discover": "../exchange/clients" }
+//! ```
+
+use serde::{Serialize, Deserialize};
+use shared::identity::ServerIdentity;
+use shared::sealed::{self, ExchangeKey};
+use shared::session::SESSION_EXPIRY_SECS;
+use shared::transport::{self, DirTransport, Transport};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::config;
+
+pub fn default_exchange_dir() -> &'static str {
+    config::exchange_dir()
+}
+
+/// Claimed jobs, one subdirectory per origin
+pub fn jobs_root() -> &'static str {
+    config::exchange_file!("jobs")
+}
+
+fn exchanges_path() -> &'static str {
+    config::database_file!("exchanges.json")
+}
+
+fn default_discover_dir() -> &'static str {
+    config::exchange_file!("clients")
+}
+
+fn exchange_key_path() -> &'static str {
+    config::database_file!("exchange_key.bin")
+}
+
+fn identity_key_path() -> &'static str {
+    config::database_file!("identity.key")
+}
+
+/// Origin of requests in the main exchange directory
+pub const DEFAULT_ORIGIN: &str = "default";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExchangeConfig {
+    #[serde(default)]
+    pub dirs: Vec<ExchangeDir>,
+    #[serde(default)]
+    pub discover: Option<String>,   // Root whose subdirectories are exchanges (default ../exchange/clients)
+    #[serde(default)]
+    pub require_encryption: bool,   // Reject plaintext requests
+    #[serde(default)]
+    pub require_session: bool,      // Reject register/verify without a session handshake (see session.rs)
+    #[serde(default)]
+    pub require_delete_proof: bool, // Delete only after a matching verify, not with a session alone (see ownership.rs)
+    #[serde(default)]
+    pub session_expiry_secs: Option<u64>, // Sessions and their signed results expire after this (default 7200)
+    #[serde(default)]
+    pub stale_after_hours: Option<u64>, // Unclaimed files and dead jobs are archived after this (default 24)
+}
+
+/// Default age after which exchange files and job records count as abandoned
+pub const DEFAULT_STALE_AFTER_HOURS: u64 = 24;
+
+impl ExchangeConfig {
+    pub fn session_expiry(&self) -> Duration {
+        Duration::from_secs(self.session_expiry_secs.unwrap_or(SESSION_EXPIRY_SECS))
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after_hours.unwrap_or(DEFAULT_STALE_AFTER_HOURS) * 3600)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExchangeDir {
+    pub name: String,
+    pub path: String,               // Directory or s3://bucket/prefix
+}
+
+/// One watched exchange (directory or bucket prefix)
+#[derive(Clone)]
+pub struct Exchange {
+    pub origin: String,
+    pub slot: Option<String>,       // User slot within the origin's exchange
+    transport: Arc<dyn Transport>,
+    location: String,
+}
+
+impl PartialEq for Exchange {
+    fn eq(&self, other: &Self) -> bool {
+        self.origin == other.origin && self.location == other.location
+    }
+}
+
+impl Exchange {
+    pub fn new(origin: &str, transport: Box<dyn Transport>) -> Self {
+        let location = transport.describe();
+        Self { origin: origin.to_string(), slot: None, transport: transport.into(), location }
+    }
+
+    pub fn dir(origin: &str, dir: impl Into<PathBuf>) -> Self {
+        Self::new(origin, Box::new(DirTransport::new(dir)))
+    }
+
+    /// Where the files live, for logs
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.transport.poll_interval()
+    }
+
+    /// Per-user slots of this exchange (same origin)
+    pub fn user_slots(&self) -> Vec<Exchange> {
+        let slots = match self.transport.user_slots() {
+            Ok(slots) => slots,
+            Err(e) => {
+                eprintln!("⚠️  Could not list user slots of '{}': {}", self.origin, e);
+                return Vec::new();
+            }
+        };
+        slots
+            .iter()
+            .filter(|slot| transport::valid_slot(slot))
+            .map(|slot| self.scoped(slot))
+            .filter(|exchange| {
+                let private = exchange.transport.is_private();
+                if !private && warned_insecure().lock().unwrap_or_else(|e| e.into_inner()).insert(exchange.location.clone()) {
+                    eprintln!("⚠️  Skipping {}: readable by other users (expected mode 0700)", exchange.location);
+                }
+                private
+            })
+            .collect()
+    }
+
+    /// A user's slot of this exchange
+    pub fn scoped(&self, slot: &str) -> Exchange {
+        let mut exchange = Exchange::new(&self.origin, self.transport.scoped(slot));
+        exchange.slot = Some(slot.to_string());
+        exchange
+    }
+
+    /// Kinds with a waiting `<kind>_request.json` (one listing per poll)
+    pub fn pending_requests(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self
+            .transport
+            .list()?
+            .iter()
+            .filter_map(|name| name.strip_suffix("_request.json"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Move a request into a local file; false if it was already gone
+    pub fn take_request(&self, kind: &str, dest: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        self.transport.take(&request_name(kind), dest)
+    }
+
+    /// Read, remove and decrypt a request
+    pub fn read_request(&self, kind: &str) -> Result<Request, Box<dyn std::error::Error>> {
+        let name = request_name(kind);
+        let data = self.transport.get(&name)?.ok_or_else(|| format!("{} disappeared", name))?;
+        self.transport.delete(&name)?;
+        open_request(data)
+    }
+
+    pub fn has_response(&self, kind: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.transport.exists(&response_name(kind))
+    }
+
+    /// Write a response, sealed to `reply_to` if the request carried a key.
+    /// Transports never expose partial files to the client.
+    pub fn write_response<T: Serialize>(
+        &self,
+        kind: &str,
+        response: &T,
+        reply_to: Option<&[u8; 32]>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_file(&response_name(kind), response, reply_to)
+    }
+
+    /// Write any JSON file for the client (job tickets and statuses), sealed like a response
+    pub fn write_file<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+        reply_to: Option<&[u8; 32]>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_vec_pretty(value)?;
+        let data = match reply_to {
+            Some(key) => sealed::seal(key, &json, None)?,
+            None => json,
+        };
+        self.put(name, &data)
+    }
+
+    /// Publish the server's handshake file
+    fn publish_handshake(&self) {
+        let Some(key) = server_key() else { return };
+        let mut handshake = key.handshake();
+        if let Some(identity) = IDENTITY.get() {
+            handshake = identity.sign_handshake(handshake);
+        }
+        let result = serde_json::to_vec_pretty(&handshake)
+            .map_err(|e| e.into())
+            .and_then(|json| self.put(sealed::HANDSHAKE_FILE, &json));
+        if let Err(e) = result {
+            eprintln!("⚠️  Could not publish exchange key to '{}': {}", self.origin, e);
+        }
+    }
+
+    pub fn put(&self, name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.transport.put(name, data)
+    }
+
+    /// Directory for jobs claimed from this exchange
+    pub fn jobs_dir(&self) -> PathBuf {
+        Path::new(jobs_root()).join(&self.origin)
+    }
+}
+
+/// Decrypted request body and the key its response must be sealed to
+pub struct Request {
+    pub data: Vec<u8>,
+    pub reply_to: Option<[u8; 32]>,
+}
+
+/// Decrypt a sealed request; plaintext passes unless encryption is required
+pub fn open_request(data: Vec<u8>) -> Result<Request, Box<dyn std::error::Error>> {
+    if sealed::is_sealed(&data) {
+        let key = server_key().ok_or("Exchange key not loaded")?;
+        let opened = sealed::open(key, &data)?;
+        return Ok(Request { data: opened.plaintext, reply_to: opened.reply_to });
+    }
+    check_plaintext_allowed()?;
+    Ok(Request { data, reply_to: None })
+}
+
+pub fn check_plaintext_allowed() -> Result<(), Box<dyn std::error::Error>> {
+    if ExchangeConfig::load().require_encryption {
+        return Err("Unencrypted request rejected (require_encryption is set)".into());
+    }
+    Ok(())
+}
+
+static SERVER_KEY: OnceLock<ExchangeKey> = OnceLock::new();
+
+/// Load (or create) the server's exchange key; call once before serving
+pub fn init_key() -> Result<&'static ExchangeKey, Box<dyn std::error::Error>> {
+    if let Some(key) = SERVER_KEY.get() {
+        return Ok(key);
+    }
+    let key = ExchangeKey::load_or_create(Path::new(exchange_key_path()))?;
+    Ok(SERVER_KEY.get_or_init(|| key))
+}
+
+pub fn server_key() -> Option<&'static ExchangeKey> {
+    SERVER_KEY.get()
+}
+
+static IDENTITY: OnceLock<ServerIdentity> = OnceLock::new();
+
+/// Load (or create) the key verify results are signed with; published in the handshake
+pub fn init_identity() -> Result<&'static ServerIdentity, Box<dyn std::error::Error>> {
+    if let Some(identity) = IDENTITY.get() {
+        return Ok(identity);
+    }
+    let identity = ServerIdentity::load_or_create(Path::new(identity_key_path()))?;
+    Ok(IDENTITY.get_or_init(|| identity))
+}
+
+fn request_name(kind: &str) -> String {
+    format!("{}_request.json", kind)
+}
+
+fn response_name(kind: &str) -> String {
+    format!("{}_response.json", kind)
+}
+
+impl ExchangeConfig {
+    pub fn load() -> Self {
+        if !Path::new(exchanges_path()).exists() {
+            return Self::default();
+        }
+        match fs::read_to_string(exchanges_path())
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("⚠️  Invalid exchanges file ({}), watching {} only", e, default_exchange_dir());
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Job files queued or running in this process; never garbage collected
+fn active_jobs() -> &'static Mutex<HashSet<PathBuf>> {
+    static ACTIVE: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+pub fn job_started(path: &Path) {
+    active_jobs().lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf());
+}
+
+pub fn job_finished(path: &Path) {
+    active_jobs().lock().unwrap_or_else(|e| e.into_inner()).remove(path);
+}
+
+pub fn is_active_job(path: &Path) -> bool {
+    active_jobs().lock().unwrap_or_else(|e| e.into_inner()).contains(path)
+}
+
+/// Insecure slots are reported once, not on every poll
+fn warned_insecure() -> &'static Mutex<HashSet<String>> {
+    static WARNED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    WARNED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Known exchanges, refreshed by `discover` (used to publish status everywhere)
+fn known() -> &'static Mutex<Vec<Exchange>> {
+    static KNOWN: OnceLock<Mutex<Vec<Exchange>>> = OnceLock::new();
+    KNOWN.get_or_init(|| Mutex::new(vec![Exchange::dir(DEFAULT_ORIGIN, default_exchange_dir())]))
+}
+
+/// Main directory, configured directories and discovered subdirectories.
+///
+/// Origins must be unique; a later duplicate is skipped with a warning.
+pub fn discover() -> Vec<Exchange> {
+    let unique = configured(&ExchangeConfig::load());
+    
+    let mut known = known().lock().unwrap_or_else(|e| e.into_inner());
+    for exchange in unique.iter().filter(|e| !known.contains(e)) {
+        println!("📂 Watching exchange '{}' ({})", exchange.origin, exchange.location());
+    }
+    // Re-published every discovery so a deleted handshake file comes back
+    for exchange in &unique {
+        exchange.publish_handshake();
+    }
+    *known = unique.clone();
+    unique
+}
+
+/// Exchanges described by `config`, without touching the watched list
+pub fn configured(config: &ExchangeConfig) -> Vec<Exchange> {
+    let mut exchanges = vec![Exchange::dir(DEFAULT_ORIGIN, default_exchange_dir())];
+    
+    for dir in &config.dirs {
+        match transport::from_spec(&dir.path) {
+            Ok(transport) => exchanges.push(Exchange::new(&dir.name, transport)),
+            Err(e) => eprintln!("⚠️  Skipping exchange '{}': {}", dir.name, e),
+        }
+    }
+    
+    let root = config.discover.as_deref().unwrap_or(default_discover_dir());
+    if let Ok(entries) = fs::read_dir(root) {
+        let mut found: Vec<Exchange> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|e| Exchange::dir(&e.file_name().to_string_lossy(), e.path()))
+            .collect();
+        found.sort_by(|a, b| a.origin.cmp(&b.origin));
+        exchanges.extend(found);
+    }
+    
+    let mut unique: Vec<Exchange> = Vec::new();
+    for exchange in exchanges {
+        if !valid_origin(&exchange.origin) {
+            eprintln!("⚠️  Ignoring exchange with invalid name '{}'", exchange.origin);
+        } else if unique.iter().any(|e| e.origin == exchange.origin) {
+            eprintln!("⚠️  Duplicate exchange name '{}' ({})", exchange.origin, exchange.location());
+        } else {
+            unique.push(exchange);
+        }
+    }
+    unique
+}
+
+/// Exchanges found by the last discovery
+pub fn known_exchanges() -> Vec<Exchange> {
+    known().lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Configured exchange of `origin`, scoped to `slot` (jobs reopened after a restart)
+pub fn find(origin: &str, slot: Option<&str>) -> Option<Exchange> {
+    let exchange = configured(&ExchangeConfig::load()).into_iter().find(|e| e.origin == origin)?;
+    match slot {
+        Some(slot) if transport::valid_slot(slot) => Some(exchange.scoped(slot)),
+        Some(_) => None,
+        None => Some(exchange),
+    }
+}
+
+/// Origins become job directory names
+fn valid_origin(origin: &str) -> bool {
+    !origin.is_empty()
+        && !origin.starts_with('.')
+        && origin != "jobs"
+        && origin.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_are_safe_directory_names() {
+        assert!(valid_origin("lab-pc_2"));
+        assert!(!valid_origin("../etc"));
+        assert!(!valid_origin(".hidden"));
+        assert!(!valid_origin("jobs"));
+        assert!(!valid_origin(""));
+    }
+}
@@ -0,0 +1,214 @@
+//! gRPC front end for the exchange (`server grpc [addr]`).
+//!
+//! The streaming counterpart of the HTTP front end: clients point
+//! `--server-url grpc://host:port` (or `FINGERPRINT_EXCHANGE`) at this
+//! server and upload registrations with their FHE server key, or download
+//! verify results, as chunk streams (see shared/src/grpc.rs). Files land in
+//! the default exchange directory exactly as a local client would write
+//! them, so the job loop picks them up unchanged. Which files a client may
+//! reach, and how large an upload may be, is up to remote.rs.
+
+// tonic handlers return `Status` by value
+#![allow(clippy::result_large_err)]
+
+use shared::grpc::{Chunk, Empty, Exchange, ExchangeServer, FileRef, FileStat, CHUNK_BYTES};
+use shared::transport::{Caller, DirTransport, ExchangeFile, Transport, USER_HEADER};
+use shared::{etrln, trln};
+use std::net::SocketAddr;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::exchange::default_exchange_dir;
+use crate::remote::{self, Access, Refusal};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8701";
+
+/// `grpc [addr]`: serve the exchange over gRPC, then run the job loop
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = args.first().map(String::as_str).unwrap_or(DEFAULT_ADDR).parse()?;
+    let service = ExchangeService { root: DirTransport::new(default_exchange_dir()) };
+
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    trln!("server.grpc_listening", addr);
+    std::thread::spawn(move || {
+        let server = tonic::transport::Server::builder().add_service(ExchangeServer::new(service)).serve(addr);
+        if let Err(e) = runtime.block_on(server) {
+            etrln!("server.grpc_stopped", e);
+        }
+    });
+
+    crate::serve()
+}
+
+struct ExchangeService {
+    root: DirTransport,
+}
+
+impl ExchangeService {
+    /// Check the access a call asks for and open the area of its file
+    fn open(
+        &self,
+        method: &str,
+        caller: Option<&Caller>,
+        file: &FileRef,
+        access: Access,
+    ) -> Result<(Box<dyn Transport>, ExchangeFile), Status> {
+        let refuse = |refusal| refused(method, file, refusal);
+        let exchange_file = ExchangeFile::parse(&file.name).ok_or_else(|| refuse(Refusal::Invalid(format!("No such file: {}", file.name))))?;
+        let area = remote::open(&self.root, caller, file.slot.as_deref(), &exchange_file, access).map_err(refuse)?;
+        Ok((area, exchange_file))
+    }
+}
+
+/// Caller named by the call's metadata, if any
+fn caller<T>(request: &Request<T>) -> Option<Caller> {
+    let metadata = request.metadata();
+    let user = metadata.get(USER_HEADER)?.to_str().ok()?;
+    let authorization = match metadata.get("authorization") {
+        Some(value) => Some(value.to_str().ok()?),
+        None => None,
+    };
+    Caller::from_headers(user, authorization)
+}
+
+fn refused(method: &str, file: &FileRef, refusal: Refusal) -> Status {
+    let path = match &file.slot {
+        Some(slot) => format!("{}/{}", slot, file.name),
+        None => file.name.clone(),
+    };
+    etrln!("server.remote_refused", method, path, refusal);
+    match refusal {
+        Refusal::Invalid(message) => Status::not_found(message),
+        Refusal::Unauthenticated(message) => Status::unauthenticated(message),
+        Refusal::Forbidden(message) => Status::permission_denied(message),
+        Refusal::TooLarge(message) => Status::resource_exhausted(message),
+        Refusal::Unavailable(message) => Status::unavailable(message),
+    }
+}
+
+fn internal(e: Box<dyn std::error::Error>) -> Status {
+    Status::internal(e.to_string())
+}
+
+/// Rest of an upload after its first chunk; `None` once it passes `limit` bytes
+async fn receive<S>(mut data: Vec<u8>, mut rest: S, limit: usize) -> Result<Option<Vec<u8>>, Status>
+where
+    S: Stream<Item = Result<Chunk, Status>> + Unpin,
+{
+    if data.len() > limit {
+        return Ok(None);
+    }
+    while let Some(chunk) = rest.next().await {
+        let chunk = chunk?;
+        if data.len() + chunk.data.len() > limit {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk.data);
+    }
+    Ok(Some(data))
+}
+
+#[tonic::async_trait]
+impl Exchange for ExchangeService {
+    async fn put(&self, request: Request<Streaming<Chunk>>) -> Result<Response<Empty>, Status> {
+        let caller = caller(&request);
+        let mut stream = request.into_inner();
+        let first = stream.message().await?.ok_or_else(|| Status::invalid_argument("Empty upload"))?;
+        let file = first.file.clone().ok_or_else(|| Status::invalid_argument("Upload doesn't name a file"))?;
+        let (area, exchange_file) = self.open("Put", caller.as_ref(), &file, Access::Submit)?;
+        let ExchangeFile::Request(kind) = &exchange_file else {
+            return Err(refused("Put", &file, Refusal::Invalid(file.name.clone())));
+        };
+
+        let limit = remote::request_limit(kind);
+        let Some(data) = receive(first.data, stream, limit).await? else {
+            return Err(refused("Put", &file, Refusal::TooLarge(format!("{} exceeds {} KB", file.name, limit / 1024))));
+        };
+        area.put(&file.name, &data).map_err(internal)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    type GetStream = tokio_stream::Iter<std::vec::IntoIter<Result<Chunk, Status>>>;
+
+    async fn get(&self, request: Request<FileRef>) -> Result<Response<Self::GetStream>, Status> {
+        let caller = caller(&request);
+        let file = request.into_inner();
+        let (area, _) = self.open("Get", caller.as_ref(), &file, Access::Read)?;
+        let data = area.get(&file.name).map_err(internal)?.ok_or_else(|| Status::not_found(format!("No {}", file.name)))?;
+        let chunks: Vec<_> = data.chunks(CHUNK_BYTES).map(|part| Ok(Chunk { file: None, data: part.to_vec() })).collect();
+        Ok(Response::new(tokio_stream::iter(chunks)))
+    }
+
+    async fn delete(&self, request: Request<FileRef>) -> Result<Response<Empty>, Status> {
+        let caller = caller(&request);
+        let file = request.into_inner();
+        let (area, _) = self.open("Delete", caller.as_ref(), &file, Access::Remove)?;
+        area.delete(&file.name).map_err(internal)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn stat(&self, request: Request<FileRef>) -> Result<Response<FileStat>, Status> {
+        let caller = caller(&request);
+        let file = request.into_inner();
+        let (area, _) = self.open("Stat", caller.as_ref(), &file, Access::Stat)?;
+        let modified = area.modified(&file.name).map_err(internal)?;
+        Ok(Response::new(FileStat { exists: modified.is_some(), modified }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    fn service(name: &str) -> ExchangeService {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        ExchangeService { root: DirTransport::new(dir) }
+    }
+
+    fn call(caller: Option<&Caller>, slot: &str, name: &str) -> Request<FileRef> {
+        let mut request = Request::new(FileRef { slot: Some(slot.to_string()), name: name.to_string() });
+        if let Some(caller) = caller {
+            request.metadata_mut().insert(USER_HEADER, caller.user_header().parse().unwrap());
+        }
+        request
+    }
+
+    #[test]
+    fn owners_collect_their_responses_only() {
+        let service = service("grpc_owner_test");
+        let alice = Caller { api_key: None, user_id: "alice".to_string() };
+        let mallory = Caller { api_key: None, user_id: "mallory".to_string() };
+        let slot = alice.slot();
+        service.root.scoped(&slot).put("verify_response.json", b"{}").unwrap();
+        service.root.scoped(&slot).put("verify_request.json", b"{}").unwrap();
+
+        let stat = block_on(service.stat(call(Some(&alice), &slot, "verify_response.json"))).unwrap();
+        assert!(stat.into_inner().exists);
+        let code = |result: Result<Response<Empty>, Status>| result.map(|_| ()).unwrap_err().code();
+        assert_eq!(code(block_on(service.delete(call(None, &slot, "verify_response.json")))), Code::Unauthenticated);
+        assert_eq!(code(block_on(service.delete(call(Some(&mallory), &slot, "verify_response.json")))), Code::PermissionDenied);
+        assert_eq!(code(block_on(service.delete(call(Some(&alice), &slot, "verify_request.json")))), Code::PermissionDenied);
+        assert_eq!(code(block_on(service.delete(call(Some(&alice), &slot, "database.json")))), Code::NotFound);
+
+        block_on(service.delete(call(Some(&alice), &slot, "verify_response.json"))).unwrap();
+        assert!(!service.root.scoped(&slot).exists("verify_response.json").unwrap());
+        assert!(service.root.scoped(&slot).exists("verify_request.json").unwrap());
+    }
+
+    #[test]
+    fn uploads_stop_at_their_limit() {
+        let part = |n: usize| Ok(Chunk { file: None, data: vec![0; n] });
+        let data = block_on(receive(vec![0; 10], tokio_stream::iter(vec![part(10), part(10)]), 30)).unwrap();
+        assert_eq!(data.map(|d| d.len()), Some(30));
+        let data = block_on(receive(vec![0; 10], tokio_stream::iter(vec![part(10), part(11)]), 30)).unwrap();
+        assert_eq!(data, None);
+        assert_eq!(block_on(receive(vec![0; 31], tokio_stream::iter(Vec::new()), 30)).unwrap(), None);
+    }
+}
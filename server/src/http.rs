@@ -0,0 +1,217 @@
+//! HTTP front end for the exchange (`server http [addr]`).
+//!
+//! Clients on other machines point `--server-url` (or `FINGERPRINT_EXCHANGE`)
+//! at this server instead of sharing a directory. Requests are written to
+//! the default exchange directory exactly as a local client would write
+//! them, so the job loop below picks them up unchanged; responses are read
+//! back from there. Routes (see `HttpTransport` in shared/src/transport.rs):
+//!
+//! - `GET handshake`, `GET status`: the exchange key and server status
+//! - below `users/<slot>/`, for the slot's owner only (see remote.rs):
+//!   - `POST requests/<kind>`: submit a request, at most its kind's size
+//!   - `HEAD requests/<kind>`: whether it is still pending
+//!   - `GET`/`DELETE responses/<kind>`, `verify_ticket`, `verify_status/<job_id>`
+//!   - `GET status`
+//!
+//! Bodies are passed through as-is, so sealed requests stay sealed end to end.
+
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::routing::{get, post};
+use axum::Router;
+use shared::transport::{Caller, DirTransport, ExchangeFile, Transport, USER_HEADER};
+use shared::{etrln, trln};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::exchange::default_exchange_dir;
+use crate::remote::{self, Access, Refusal};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8700";
+
+type Root = State<Arc<DirTransport>>;
+type Reply = Result<(StatusCode, HeaderMap, Vec<u8>), (StatusCode, String)>;
+
+/// `http [addr]`: serve the exchange over HTTP, then run the job loop
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let addr = args.first().map(String::as_str).unwrap_or(DEFAULT_ADDR).to_string();
+    let root = Arc::new(DirTransport::new(default_exchange_dir()));
+
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let listener = runtime.block_on(tokio::net::TcpListener::bind(&addr))?;
+    trln!("server.http_listening", listener.local_addr()?);
+    std::thread::spawn(move || {
+        if let Err(e) = runtime.block_on(async { axum::serve(listener, router(root)).await }) {
+            etrln!("server.http_stopped", e);
+        }
+    });
+
+    crate::serve()
+}
+
+fn router(root: Arc<DirTransport>) -> Router {
+    Router::new()
+        .route("/handshake", get(read))
+        .route("/status", get(read))
+        .route("/users/{slot}/status", get(read))
+        .route("/users/{slot}/requests/{kind}", post(submit).head(pending))
+        .route("/users/{slot}/responses/{kind}", get(read).delete(remove))
+        .route("/users/{slot}/verify_ticket", get(read).delete(remove))
+        .route("/users/{slot}/verify_status/{job_id}", get(read).delete(remove))
+        .layer(DefaultBodyLimit::max(remote::MAX_REQUEST_BYTES))
+        .with_state(root)
+}
+
+/// Caller named by the request headers, if any
+fn caller(headers: &HeaderMap) -> Option<Caller> {
+    let user = headers.get(USER_HEADER)?.to_str().ok()?;
+    let authorization = match headers.get(header::AUTHORIZATION) {
+        Some(value) => Some(value.to_str().ok()?),
+        None => None,
+    };
+    Caller::from_headers(user, authorization)
+}
+
+fn refused(method: &str, uri: &Uri, refusal: Refusal) -> (StatusCode, String) {
+    etrln!("server.remote_refused", method, uri.path(), refusal);
+    let status = match refusal {
+        Refusal::Invalid(_) => StatusCode::NOT_FOUND,
+        Refusal::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+        Refusal::Forbidden(_) => StatusCode::FORBIDDEN,
+        Refusal::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        Refusal::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, refusal.to_string())
+}
+
+/// Check the access a request asks for and open the area of its file
+fn open(
+    root: &DirTransport,
+    method: &str,
+    headers: &HeaderMap,
+    uri: &Uri,
+    access: Access,
+) -> Result<(Box<dyn Transport>, ExchangeFile), (StatusCode, String)> {
+    let (slot, file) = remote::locate(uri.path()).map_err(|r| refused(method, uri, r))?;
+    let area = remote::open(root, caller(headers).as_ref(), slot.as_deref(), &file, access).map_err(|r| refused(method, uri, r))?;
+    Ok((area, file))
+}
+
+fn internal(e: Box<dyn std::error::Error>) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn last_modified(modified: SystemTime) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let modified = chrono::DateTime::<chrono::Utc>::from(modified).to_rfc2822();
+    if let Ok(value) = HeaderValue::from_str(&modified) {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+    headers
+}
+
+async fn read(State(root): Root, headers: HeaderMap, uri: Uri) -> Reply {
+    let (area, file) = open(&root, "GET", &headers, &uri, Access::Read)?;
+    let name = file.name();
+    let data = area.get(&name).map_err(internal)?.ok_or((StatusCode::NOT_FOUND, format!("No {}", name)))?;
+    let headers = area.modified(&name).map_err(internal)?.map(last_modified).unwrap_or_default();
+    Ok((StatusCode::OK, headers, data))
+}
+
+async fn pending(State(root): Root, headers: HeaderMap, uri: Uri) -> Reply {
+    let (area, file) = open(&root, "HEAD", &headers, &uri, Access::Stat)?;
+    let name = file.name();
+    let modified = area.modified(&name).map_err(internal)?.ok_or((StatusCode::NOT_FOUND, format!("No {}", name)))?;
+    Ok((StatusCode::OK, last_modified(modified), Vec::new()))
+}
+
+async fn submit(State(root): Root, headers: HeaderMap, uri: Uri, body: Body) -> Reply {
+    let (area, file) = open(&root, "POST", &headers, &uri, Access::Submit)?;
+    let ExchangeFile::Request(kind) = &file else {
+        return Err(refused("POST", &uri, Refusal::Invalid(file.name())));
+    };
+    let limit = remote::request_limit(kind);
+    let data = axum::body::to_bytes(body, limit).await.map_err(|_| {
+        let refusal = Refusal::TooLarge(format!("{} exceeds {} KB", file.name(), limit / 1024));
+        refused("POST", &uri, refusal)
+    })?;
+    area.put(&file.name(), &data).map_err(internal)?;
+    Ok((StatusCode::ACCEPTED, HeaderMap::new(), Vec::new()))
+}
+
+async fn remove(State(root): Root, headers: HeaderMap, uri: Uri) -> Reply {
+    let (area, file) = open(&root, "DELETE", &headers, &uri, Access::Remove)?;
+    area.delete(&file.name()).map_err(internal)?;
+    Ok((StatusCode::NO_CONTENT, HeaderMap::new(), Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    fn root(name: &str) -> State<Arc<DirTransport>> {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        State(Arc::new(DirTransport::new(dir)))
+    }
+
+    fn headers_of(caller: &Caller) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_HEADER, HeaderValue::from_str(&caller.user_header()).unwrap());
+        headers
+    }
+
+    fn uri(caller: &Caller, route: &str) -> Uri {
+        format!("/users/{}/{}", caller.slot(), route).parse().unwrap()
+    }
+
+    #[test]
+    fn owners_submit_and_collect_through_typed_routes() {
+        let root = root("http_owner_test");
+        let alice = Caller { api_key: None, user_id: "alice".to_string() };
+        let headers = headers_of(&alice);
+
+        let reply = block_on(submit(root.clone(), headers.clone(), uri(&alice, "requests/verify"), Body::from("{}")));
+        assert_eq!(reply.unwrap().0, StatusCode::ACCEPTED);
+        assert_eq!(block_on(pending(root.clone(), headers.clone(), uri(&alice, "requests/verify"))).unwrap().0, StatusCode::OK);
+        assert_eq!(block_on(read(root.clone(), headers.clone(), uri(&alice, "requests/verify"))).unwrap_err().0, StatusCode::FORBIDDEN);
+
+        let area = root.scoped(&alice.slot());
+        area.put("verify_response.json", b"{\"success\":true}").unwrap();
+        let (status, _, data) = block_on(read(root.clone(), headers.clone(), uri(&alice, "responses/verify"))).unwrap();
+        assert_eq!((status, data.as_slice()), (StatusCode::OK, &b"{\"success\":true}"[..]));
+        assert_eq!(block_on(remove(root.clone(), headers, uri(&alice, "responses/verify"))).unwrap().0, StatusCode::NO_CONTENT);
+        assert!(!area.exists("verify_response.json").unwrap());
+    }
+
+    #[test]
+    fn other_slots_and_missing_credentials_are_refused() {
+        let root = root("http_refusal_test");
+        let alice = Caller { api_key: None, user_id: "alice".to_string() };
+        let mallory = Caller { api_key: None, user_id: "mallory".to_string() };
+
+        let reply = block_on(submit(root.clone(), HeaderMap::new(), uri(&alice, "requests/verify"), Body::from("{}")));
+        assert_eq!(reply.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let reply = block_on(read(root.clone(), headers_of(&mallory), uri(&alice, "responses/verify")));
+        assert_eq!(reply.unwrap_err().0, StatusCode::FORBIDDEN);
+        let reply = block_on(read(root.clone(), HeaderMap::new(), "/files/database.json".parse().unwrap()));
+        assert_eq!(reply.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn uploads_over_their_kind_limit_are_refused() {
+        let root = root("http_limit_test");
+        let alice = Caller { api_key: None, user_id: "alice".to_string() };
+        let body = Body::from(vec![b' '; remote::request_limit("cancel") + 1]);
+
+        let reply = block_on(submit(root.clone(), headers_of(&alice), uri(&alice, "requests/cancel"), body));
+        assert_eq!(reply.unwrap_err().0, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(!root.scoped(&alice.slot()).exists("cancel_request.json").unwrap());
+    }
+}
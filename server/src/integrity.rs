@@ -0,0 +1,80 @@
+use crate::database::{blob_checksum, Database};
+use shared::Cipher;
+use tfhe::FheBool;
+
+/// `server verify-db`: check every stored template without touching the server key
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔎 VERIFYING TEMPLATE STORE");
+    println!("{}", "─".repeat(70));
+    
+    let db = Database::load()?;
+    let mut users: Vec<&String> = db.templates.keys().collect();
+    users.sort();
+    
+    let mut corrupt = 0;
+    let mut unchecked = 0;
+    for user_id in users {
+        let entry = &db.templates[user_id];
+        let mut problems = Vec::new();
+        let mut has_checksum = true;
+        
+        for (label, blob) in entry.blobs() {
+            let (ciphertext, key_bytes, iv_bytes) = match blob.materialize() {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    problems.push(format!("{}: {}", label, e));
+                    continue;
+                }
+            };
+            match &blob.checksum {
+                Some(expected) if *expected != blob_checksum(&ciphertext, &key_bytes, &iv_bytes) => {
+                    problems.push(format!("{}: checksum mismatch", label));
+                }
+                Some(_) => {}
+                None => has_checksum = false,
+            }
+            problems.extend(check_template(&ciphertext, &key_bytes, &iv_bytes, entry.template_bytes(), blob.cipher).into_iter().map(|p| format!("{}: {}", label, p)));
+        }
+        
+        if !problems.is_empty() {
+            corrupt += 1;
+            println!("❌ {}", user_id);
+            for problem in problems {
+                println!("   - {}", problem);
+            }
+        } else if !has_checksum {
+            unchecked += 1;
+            println!("⚠️  {} (no checksum recorded, structure OK)", user_id);
+        } else {
+            println!("✅ {}", user_id);
+        }
+    }
+    
+    println!("{}", "─".repeat(70));
+    println!("📊 {} entries, {} corrupt, {} without checksum", db.templates.len(), corrupt, unchecked);
+    
+    if corrupt > 0 {
+        return Err(format!("{} corrupt entries found", corrupt).into());
+    }
+    Ok(())
+}
+
+/// Structural checks: blob lengths and bincode deserialization of the FHE vectors
+fn check_template(ciphertext: &[u8], key_bytes: &[u8], iv_bytes: &[u8], template_bytes: usize, cipher: Cipher) -> Vec<String> {
+    let mut problems = Vec::new();
+    
+    if ciphertext.len() != template_bytes {
+        problems.push(format!("ciphertext is {} bytes, expected {}", ciphertext.len(), template_bytes));
+    }
+    for (name, bytes, expected) in [("key", key_bytes, cipher.key_bits()), ("IV", iv_bytes, cipher.iv_bits())] {
+        match bincode::deserialize::<Vec<FheBool>>(bytes) {
+            Ok(bits) if bits.len() != expected => {
+                problems.push(format!("encrypted {} has {} bits, expected {}", name, bits.len(), expected));
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(format!("encrypted {} does not deserialize: {}", name, e)),
+        }
+    }
+    
+    problems
+}
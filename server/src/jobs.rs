@@ -0,0 +1,419 @@
+//! Verify jobs: ids, the persistent job store and the status files clients poll.
+//!
+//! A verification takes 30-60 minutes, so a claimed verify request is
+//! answered right away with `verify_ticket.json` carrying a job id. While the
+//! job waits for a verify slot and runs, `verify_status_<job_id>.json` in the
+//! client's slot tells where it is (queued, running and the current phase);
+//! once it has finished the status carries the `VerifyResponse` or the error.
+//! Until then the status is rewritten every `HEARTBEAT` even without a
+//! change, so its `updated_at` tells the client the server is still at it.
+//! `verify_response.json` is still written for older clients and recovery.
+//!
+//! Every job is recorded in `../database/jobs/<job_id>.json`. Jobs a previous
+//! server process left queued or running are requeued at startup if their
+//! claimed request is still on disk, resuming from their checkpoints (see
+//! checkpoint.rs); the others are marked failed and their clients told so.
+//! Maintenance removes finished records after `stale_after_hours`
+//! (exchanges.json).
+//!
+//! A client can cancel its queued or running job with `cancel_request.json`
+//! (`CancelRequest`) in the same slot: the job's cancellation token is set,
+//! the FHE evaluation stops at its next gate and the job ends `cancelled`.
+
+use serde::{Serialize, Deserialize};
+use shared::protocol::{job_status_file, JOB_TICKET_FILE};
+use shared::{CancellationToken, Cancelled, ErrorCode, JobState, JobStatus, JobTicket, VerifyResponse};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::checkpoint::Checkpoints;
+use crate::config;
+use crate::exchange::{self, Exchange};
+use crate::workers;
+
+/// How often an unfinished job's status is rewritten
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+pub fn job_store_dir() -> &'static str {
+    config::database_file!("jobs")
+}
+
+/// Stored state of one verify job
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub origin: String,
+    #[serde(default)]
+    pub slot: Option<String>,       // User slot the job came from (None = exchange root)
+    #[serde(default)]
+    pub reply_to: Option<String>,   // Client key statuses are sealed to (hex)
+    #[serde(default)]
+    pub request: Option<PathBuf>,   // Claimed request file, kept until the job finishes
+    pub state: JobState,
+    #[serde(default)]
+    pub phase: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub submitted_at: String,
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+}
+
+impl JobRecord {
+    fn path(job_id: &str) -> PathBuf {
+        Path::new(job_store_dir()).join(format!("{}.json", job_id))
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(job_store_dir())?;
+        let path = Self::path(&self.job_id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn reply_key(&self) -> Option<[u8; 32]> {
+        let hex = self.reply_to.as_deref()?;
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<_>>()?;
+        bytes.try_into().ok()
+    }
+
+    fn status(&self, result: Option<&VerifyResponse>) -> JobStatus {
+        JobStatus {
+            phase: self.phase.clone(),
+            error: self.error.clone(),
+            result: result.cloned(),
+            ..JobStatus::new(&self.job_id, self.state)
+        }
+    }
+}
+
+/// Records in the store with their file paths, unreadable ones skipped
+fn stored_jobs() -> Vec<(PathBuf, JobRecord)> {
+    let Ok(entries) = fs::read_dir(job_store_dir()) else { return Vec::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let record = fs::read(&path).ok().and_then(|data| serde_json::from_slice(&data).ok())?;
+            Some((path, record))
+        })
+        .collect()
+}
+
+pub fn new_job_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+/// Progress of one verify job, mirrored into the store and the client's slot.
+/// Updates are best effort: a failed write is reported but never fails the job.
+pub struct Tracker {
+    exchange: Exchange,
+    reply_to: Option<[u8; 32]>,
+    cancel: CancellationToken,      // Set by a cancel request or at shutdown
+    state: Arc<Mutex<(JobRecord, Option<VerifyResponse>)>>,
+    stopped: Arc<AtomicBool>,       // Ends the heartbeat
+}
+
+/// Token of a job that has not finished yet, found by cancel requests
+struct Cancellable {
+    job_id: String,
+    origin: String,
+    slot: Option<String>,
+    token: CancellationToken,
+}
+
+static CANCELLABLE: Mutex<Vec<Cancellable>> = Mutex::new(Vec::new());
+
+/// Cancel a job of the slot `exchange` is; false if no such job is queued or running
+pub fn cancel(exchange: &Exchange, job_id: &str) -> bool {
+    let cancellable = CANCELLABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let job = cancellable
+        .iter()
+        .find(|c| c.job_id == job_id && c.origin == exchange.origin && c.slot == exchange.slot);
+    match job {
+        Some(job) => {
+            job.token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+impl Tracker {
+    /// Record a claimed verify job as queued and hand the client its ticket
+    pub fn start(exchange: &Exchange, request: &Path, reply_to: Option<[u8; 32]>) -> Result<Self, Box<dyn std::error::Error>> {
+        let record = JobRecord {
+            job_id: new_job_id(),
+            origin: exchange.origin.clone(),
+            slot: exchange.slot.clone(),
+            reply_to: reply_to.map(|key| key.iter().map(|b| format!("{:02x}", b)).collect()),
+            request: Some(request.to_path_buf()),
+            state: JobState::Queued,
+            phase: None,
+            error: None,
+            submitted_at: now(),
+            started_at: None,
+            finished_at: None,
+        };
+        record.save()?;
+        let ticket = JobTicket { job_id: record.job_id.clone(), queued_at: record.submitted_at.clone() };
+        exchange.write_file(JOB_TICKET_FILE, &ticket, reply_to.as_ref())?;
+
+        let tracker = Self::track(exchange, record);
+        tracker.update(|_| {});
+        Ok(tracker)
+    }
+
+    /// Tracker of a stored job, cancellable until it is dropped
+    fn track(exchange: &Exchange, record: JobRecord) -> Self {
+        let cancel = workers::shutdown_token().child();
+        CANCELLABLE.lock().unwrap_or_else(|e| e.into_inner()).push(Cancellable {
+            job_id: record.job_id.clone(),
+            origin: record.origin.clone(),
+            slot: record.slot.clone(),
+            token: cancel.clone(),
+        });
+        let tracker = Self {
+            exchange: exchange.clone(),
+            reply_to: record.reply_key(),
+            cancel,
+            state: Arc::new(Mutex::new((record, None))),
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        tracker.heartbeat();
+        tracker
+    }
+
+    /// Rewrite the status every `HEARTBEAT` until the job is closed or dropped
+    fn heartbeat(&self) {
+        let (exchange, reply_to) = (self.exchange.clone(), self.reply_to);
+        let (state, stopped) = (Arc::clone(&self.state), Arc::clone(&self.stopped));
+        std::thread::spawn(move || loop {
+            std::thread::sleep(HEARTBEAT);
+            let state = state.lock().unwrap_or_else(|e| e.into_inner());
+            if stopped.load(Ordering::Relaxed) || state.0.state.is_finished() {
+                break;
+            }
+            publish_status(&exchange, reply_to.as_ref(), &state.0, state.1.as_ref());
+        });
+    }
+
+    pub fn job_id(&self) -> String {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).0.job_id.clone()
+    }
+
+    /// Token the job's FHE evaluation checks
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// The job got a verify slot
+    pub fn running(&self) {
+        self.update(|record| {
+            record.state = JobState::Running;
+            record.started_at = Some(now());
+        });
+    }
+
+    /// The running job entered a new phase
+    pub fn phase(&self, phase: &str) {
+        self.update(|record| record.phase = Some(phase.to_string()));
+    }
+
+    /// Keep the response for the final status (published by `close`)
+    pub fn result(&self, response: &VerifyResponse) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).1 = Some(response.clone());
+    }
+
+    /// The handler returned: publish the final status with the result or the error
+    pub fn close(&self, error: Option<&(dyn std::error::Error + 'static)>) {
+        let cancelled = error.is_some_and(|e| e.is::<Cancelled>());
+        // Clients read the record; the error's own text stays in the server log
+        let error = error.map(|e| ErrorCode::of(e).message().to_string());
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (record, result) = &mut *state;
+        let response = result.get_or_insert_with(|| VerifyResponse::error(error.clone().unwrap_or_default()));
+        record.state = match (cancelled, response.success) {
+            (true, _) => JobState::Cancelled,
+            (false, true) => JobState::Completed,
+            (false, false) => JobState::Failed,
+        };
+        record.phase = None;
+        record.error = error.or_else(|| {
+            (!response.success).then(|| response.failure.as_ref().map_or("Verification failed".to_string(), |f| f.message.clone()))
+        });
+        record.finished_at = Some(now());
+        self.stopped.store(true, Ordering::Relaxed);
+        self.publish(record, result.as_ref());
+    }
+
+    fn update(&self, change: impl FnOnce(&mut JobRecord)) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (record, result) = &mut *state;
+        change(record);
+        self.publish(record, result.as_ref());
+    }
+
+    fn publish(&self, record: &JobRecord, result: Option<&VerifyResponse>) {
+        if let Err(e) = record.save() {
+            eprintln!("⚠️  Could not save job {}: {}", record.job_id, e);
+        }
+        publish_status(&self.exchange, self.reply_to.as_ref(), record, result);
+    }
+}
+
+/// Write the job's status into its client's slot
+fn publish_status(exchange: &Exchange, reply_to: Option<&[u8; 32]>, record: &JobRecord, result: Option<&VerifyResponse>) {
+    let status = record.status(result);
+    if let Err(e) = exchange.write_file(&job_status_file(&record.job_id), &status, reply_to) {
+        eprintln!("⚠️  Could not publish status of job {}: {}", record.job_id, e);
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        let job_id = self.job_id();
+        CANCELLABLE.lock().unwrap_or_else(|e| e.into_inner()).retain(|c| c.job_id != job_id);
+    }
+}
+
+/// Job a previous process left unfinished whose claimed request is still there
+pub struct Interrupted {
+    pub exchange: Exchange,
+    pub request: PathBuf,
+    pub tracker: Tracker,
+}
+
+/// Requeue jobs a previous process left queued or running if their request survived;
+/// mark the others failed and tell their clients. Returns the requeued jobs and the
+/// number that failed.
+pub fn recover_interrupted() -> (Vec<Interrupted>, usize) {
+    let mut resumed = Vec::new();
+    let mut failed = 0;
+    for (_, mut record) in stored_jobs().into_iter().filter(|(_, r)| !r.state.is_finished()) {
+        let exchange = exchange::find(&record.origin, record.slot.as_deref());
+        let request = record.request.clone().filter(|path| path.exists());
+        if let (Some(exchange), Some(request)) = (&exchange, request) {
+            record.state = JobState::Queued;
+            record.phase = None;
+            record.started_at = None;
+            let tracker = Tracker::track(exchange, record);
+            tracker.update(|_| {});
+            resumed.push(Interrupted { exchange: exchange.clone(), request, tracker });
+            continue;
+        }
+
+        failed += 1;
+        if let Some(request) = &record.request {
+            Checkpoints::for_job(request).clear();
+        }
+        let message = format!("Server restarted while the job was {}", record.state);
+        record.state = JobState::Failed;
+        record.phase = None;
+        record.error = Some(message.clone());
+        record.finished_at = Some(now());
+        if let Err(e) = record.save() {
+            eprintln!("⚠️  Could not save job {}: {}", record.job_id, e);
+        }
+
+        let Some(exchange) = exchange else { continue };
+        let status = record.status(Some(&VerifyResponse::error(message)));
+        if let Err(e) = exchange.write_file(&job_status_file(&record.job_id), &status, record.reply_key().as_ref()) {
+            eprintln!("⚠️  Could not publish status of job {}: {}", record.job_id, e);
+        }
+    }
+    (resumed, failed)
+}
+
+/// Remove records of jobs that finished before `cutoff`
+pub fn purge_finished(cutoff: SystemTime) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut removed = 0;
+    for (path, record) in stored_jobs() {
+        let finished_before = record
+            .finished_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| SystemTime::from(t) < cutoff);
+        if record.state.is_finished() && finished_before {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::protocol::valid_job_id;
+
+    #[test]
+    fn job_ids_are_valid_file_name_parts() {
+        let id = new_job_id();
+        assert!(valid_job_id(&id));
+        assert_ne!(id, new_job_id());
+        assert!(!valid_job_id("../database/jobs"));
+    }
+
+    fn running_record(slot: Option<String>) -> JobRecord {
+        JobRecord {
+            job_id: new_job_id(),
+            origin: exchange::DEFAULT_ORIGIN.to_string(),
+            slot,
+            reply_to: None,
+            request: None,
+            state: JobState::Running,
+            phase: Some("decrypt_probe".to_string()),
+            error: None,
+            submitted_at: now(),
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn reply_key_round_trips_through_hex() {
+        let key = [0xabu8; 32];
+        let record = JobRecord {
+            reply_to: Some(key.iter().map(|b| format!("{:02x}", b)).collect()),
+            ..running_record(None)
+        };
+        assert_eq!(record.reply_key(), Some(key));
+        let status = record.status(None);
+        assert_eq!(status.state, JobState::Running);
+        assert_eq!(status.phase.as_deref(), Some("decrypt_probe"));
+    }
+
+    #[test]
+    fn jobs_are_cancelled_from_their_own_slot_only() {
+        let root = Exchange::dir(exchange::DEFAULT_ORIGIN, std::env::temp_dir().join("jobs_cancel_test"));
+        let slot = root.scoped("u-1");
+        let tracker = Tracker::track(&slot, running_record(slot.slot.clone()));
+        let job_id = tracker.job_id();
+
+        assert!(!cancel(&root, &job_id));
+        assert!(!tracker.cancel_token().is_cancelled());
+        assert!(cancel(&slot, &job_id));
+        assert!(tracker.cancel_token().is_cancelled());
+
+        drop(tracker);
+        assert!(!cancel(&slot, &job_id));
+    }
+}
@@ -0,0 +1,344 @@
+//! Server key loading.
+//!
+//! The stored key (bincode, same format the client sends) is memory-mapped
+//! and deserialized straight from the mapping, without first reading
+//! hundreds of megabytes into a buffer. Loaded keys are cached per tenant and
+//! reloaded only when the file changes; `ServerKey` is reference counted
+//! internally, so handing out clones is cheap.
+//!
+//! Built with the `gpu` feature, verifications evaluate on tfhe's CUDA
+//! backend when a device is present (or limits.json `compute_backend` asks
+//! for it) and the tenant's client sent a compressed server key to
+//! decompress onto it. Without either the job runs on the CPU as before.
+//! The compressed key is stored with the fingerprint of the server key it
+//! came with and is only used while the tenant still holds that server key.
+//!
+//! Each stored key also has a fingerprint (see
+//! `shared::protocol::server_key_fingerprint`), hashed from the mapping once
+//! per file change. Register responses report it; a verify request naming
+//! another one is refused before any evaluation.
+//!
+//! tfhe's server key is per thread. Worker threads remember the key they
+//! installed and only call `set_server_key` again when it changes.
+
+use memmap2::Mmap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tfhe::ServerKey;
+
+use shared::protocol::server_key_fingerprint as server_key_fingerprint_of;
+use shared::{compute, etrln, ComputeBackend, EvaluationKey, FingerprintError};
+
+use crate::tenant;
+
+struct CachedKey<K> {
+    modified: SystemTime,
+    len: u64,
+    key: K,
+}
+
+type KeyCache<K> = Mutex<HashMap<String, CachedKey<K>>>;
+
+/// CPU keys, as loaded and ready to install
+static CACHE: OnceLock<KeyCache<(Arc<ServerKey>, Arc<EvaluationKey>)>> = OnceLock::new();
+
+/// Fingerprints of the stored server keys
+static FINGERPRINT_CACHE: OnceLock<KeyCache<String>> = OnceLock::new();
+
+/// CUDA keys, decompressed onto the device
+static GPU_CACHE: OnceLock<KeyCache<Arc<EvaluationKey>>> = OnceLock::new();
+
+/// Why GPU evaluation was last unavailable, per tenant (warned once per reason)
+static GPU_WARNINGS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+thread_local! {
+    static INSTALLED: RefCell<Option<Arc<EvaluationKey>>> = const { RefCell::new(None) };
+}
+
+fn cache() -> &'static KeyCache<(Arc<ServerKey>, Arc<EvaluationKey>)> {
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn fingerprint_cache() -> &'static KeyCache<String> {
+    FINGERPRINT_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn gpu_cache() -> &'static KeyCache<Arc<EvaluationKey>> {
+    GPU_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The key loaded from `path` for `tenant`, from `cache` if the file is unchanged
+fn cached<K: Clone>(
+    cache: &KeyCache<K>,
+    tenant: &str,
+    path: &str,
+    metadata: fs::Metadata,
+    load: impl FnOnce(&[u8]) -> Result<K, FingerprintError>,
+) -> Result<K, FingerprintError> {
+    let modified = metadata.modified()?;
+    {
+        let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.get(tenant) {
+            if cached.modified == modified && cached.len == metadata.len() {
+                return Ok(cached.key.clone());
+            }
+        }
+    }
+
+    let file = fs::File::open(path)?;
+    // SAFETY: key files are only replaced (never modified in place) by the
+    // register handler; a concurrent replacement is detected by the mtime check.
+    let mmap = unsafe { Mmap::map(&file)? };
+    let key = load(&mmap[..])?;
+    drop(mmap);
+
+    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        tenant.to_string(),
+        CachedKey { modified, len: metadata.len(), key: key.clone() },
+    );
+    Ok(key)
+}
+
+/// Server key of a tenant and the CPU evaluation key made from it
+fn cpu_keys(tenant: &str) -> Result<(Arc<ServerKey>, Arc<EvaluationKey>), FingerprintError> {
+    let path = tenant::server_key_path(tenant);
+    let metadata = fs::metadata(&path)
+        .map_err(|_| FingerprintError::Storage(format!("Server key not found at {}! Register a user first.", path)))?;
+    cached(cache(), tenant, &path, metadata, |bytes| {
+        let key: ServerKey = bincode::deserialize(bytes)?;
+        let evaluation = Arc::new(EvaluationKey::Cpu(key.clone()));
+        Ok((Arc::new(key), evaluation))
+    })
+}
+
+/// Server key of a tenant, from cache if the file is unchanged
+pub fn server_key(tenant: &str) -> Result<Arc<ServerKey>, FingerprintError> {
+    Ok(cpu_keys(tenant)?.0)
+}
+
+/// Fingerprint of a tenant's stored server key, from cache if the file is unchanged
+pub fn server_key_fingerprint(tenant: &str) -> Result<String, FingerprintError> {
+    fingerprint_at(tenant, &tenant::server_key_path(tenant))
+}
+
+fn fingerprint_at(tenant: &str, path: &str) -> Result<String, FingerprintError> {
+    let metadata = fs::metadata(path)
+        .map_err(|_| FingerprintError::Storage(format!("Server key not found at {}! Register a user first.", path)))?;
+    cached(fingerprint_cache(), tenant, path, metadata, |bytes| Ok(server_key_fingerprint_of(bytes)))
+}
+
+/// The tenant's stored server key must be the one the client expects
+pub fn check_fingerprint(tenant: &str, expected: &str) -> Result<(), FingerprintError> {
+    compare_fingerprints(&server_key_fingerprint(tenant)?, expected)
+}
+
+fn compare_fingerprints(stored: &str, expected: &str) -> Result<(), FingerprintError> {
+    if stored != expected {
+        return Err(FingerprintError::KeyMismatch(format!(
+            "Stored server key {} is not the client's {}; the client key was replaced without rotating the server key, run `keys rotate`",
+            short(stored),
+            short(expected)
+        )));
+    }
+    Ok(())
+}
+
+/// First 12 hex digits, enough to tell fingerprints apart in a message
+fn short(fingerprint: &str) -> &str {
+    fingerprint.get(..12).unwrap_or(fingerprint)
+}
+
+/// CUDA key of a tenant, if this server can evaluate on a GPU and the tenant sent
+/// one with the server key it holds
+fn gpu_key(tenant: &str) -> Result<Arc<EvaluationKey>, FingerprintError> {
+    if !cfg!(feature = "gpu") {
+        return Err(FingerprintError::Fhe("server built without the `gpu` feature".into()));
+    }
+    if !compute::gpu_available() {
+        return Err(FingerprintError::Fhe("no CUDA device found".into()));
+    }
+    let path = tenant::gpu_key_path(tenant);
+    let metadata = fs::metadata(&path)
+        .map_err(|_| FingerprintError::Storage("no compressed server key registered; register with FINGERPRINT_GPU_KEY=on".into()))?;
+    let server_key = server_key_fingerprint(tenant)?;
+    cached(gpu_cache(), tenant, &path, metadata, |file| {
+        Ok(Arc::new(EvaluationKey::gpu_from_compressed(bound_gpu_key(file, &server_key)?)?))
+    })
+}
+
+/// Backend a job asks for: `preferred`, by default the GPU whenever a device is present
+fn requested_backend(preferred: Option<ComputeBackend>, gpu_present: bool) -> ComputeBackend {
+    preferred.unwrap_or(if gpu_present { ComputeBackend::Gpu } else { ComputeBackend::Cpu })
+}
+
+/// Key a verify job evaluates under: the GPU one when the job asks for it (see
+/// `requested_backend`) and it can be had, otherwise the CPU one
+pub fn evaluation_key(tenant: &str, preferred: Option<ComputeBackend>) -> Result<Arc<EvaluationKey>, FingerprintError> {
+    if requested_backend(preferred, compute::gpu_available()) == ComputeBackend::Gpu {
+        match gpu_key(tenant) {
+            Ok(key) => return Ok(key),
+            Err(e) => {
+                if first_gpu_warning(tenant, &e.to_string()) {
+                    etrln!("server.gpu_unavailable", e);
+                }
+            }
+        }
+    }
+    Ok(cpu_keys(tenant)?.1)
+}
+
+/// Whether `reason` is new for this tenant; every verify would otherwise repeat it
+fn first_gpu_warning(tenant: &str, reason: &str) -> bool {
+    let mut warnings = GPU_WARNINGS.get_or_init(|| Mutex::new(HashMap::new())).lock().unwrap_or_else(|e| e.into_inner());
+    warnings.insert(tenant.to_string(), reason.to_string()).as_deref() != Some(reason)
+}
+
+/// Make `key` this thread's tfhe server key unless it already is
+pub fn install(key: &Arc<EvaluationKey>) {
+    INSTALLED.with(|installed| {
+        let mut installed = installed.borrow_mut();
+        if !installed.as_ref().is_some_and(|current| Arc::ptr_eq(current, key)) {
+            key.install();
+            *installed = Some(Arc::clone(key));
+        }
+    });
+}
+
+/// Write-then-rename so an mmap of the old file never sees a partial key
+fn replace_file(path: &str, bytes: &[u8]) -> Result<(), FingerprintError> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Store a new server key for a tenant and drop the cached one
+pub fn store_server_key(tenant: &str, bytes: &[u8]) -> Result<String, FingerprintError> {
+    let path = tenant::server_key_path(tenant);
+    store_server_key_at(tenant, &path, bytes)?;
+    Ok(path)
+}
+
+fn store_server_key_at(tenant: &str, path: &str, bytes: &[u8]) -> Result<(), FingerprintError> {
+    replace_file(path, bytes)?;
+
+    cache().lock().unwrap_or_else(|e| e.into_inner()).remove(tenant);
+    fingerprint_cache().lock().unwrap_or_else(|e| e.into_inner()).remove(tenant);
+    gpu_cache().lock().unwrap_or_else(|e| e.into_inner()).remove(tenant);
+    Ok(())
+}
+
+/// Store a tenant's compressed server key for GPU evaluation, bound to the server
+/// key it was sent with, and drop the cached CUDA key
+pub fn store_gpu_key(tenant: &str, bytes: &[u8], server_key_fingerprint: &str) -> Result<String, FingerprintError> {
+    let path = tenant::gpu_key_path(tenant);
+    replace_file(&path, &bind_gpu_key(bytes, server_key_fingerprint))?;
+
+    gpu_cache().lock().unwrap_or_else(|e| e.into_inner()).remove(tenant);
+    Ok(path)
+}
+
+/// GPU key file: the fingerprint of its server key, a newline, the compressed key
+fn bind_gpu_key(bytes: &[u8], server_key_fingerprint: &str) -> Vec<u8> {
+    let mut file = Vec::with_capacity(server_key_fingerprint.len() + 1 + bytes.len());
+    file.extend_from_slice(server_key_fingerprint.as_bytes());
+    file.push(b'\n');
+    file.extend_from_slice(bytes);
+    file
+}
+
+/// Compressed key of a GPU key file, if it was stored with this server key
+fn bound_gpu_key<'a>(file: &'a [u8], server_key_fingerprint: &str) -> Result<&'a [u8], FingerprintError> {
+    let (bound, bytes) = match file.iter().position(|&b| b == b'\n') {
+        Some(end) => (&file[..end], &file[end + 1..]),
+        None => (&[][..], file),
+    };
+    if bound != server_key_fingerprint.as_bytes() {
+        return Err(FingerprintError::KeyMismatch(format!(
+            "compressed server key was not sent with the stored server key {}; register with FINGERPRINT_GPU_KEY=on again",
+            short(server_key_fingerprint)
+        )));
+    }
+    Ok(bytes)
+}
+
+/// Forget a tenant's compressed server key (its server key was replaced)
+pub fn remove_gpu_key(tenant: &str) -> Result<(), FingerprintError> {
+    let path = tenant::gpu_key_path(tenant);
+    if fs::metadata(&path).is_ok() {
+        fs::remove_file(&path)?;
+    }
+    gpu_cache().lock().unwrap_or_else(|e| e.into_inner()).remove(tenant);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("{}_{}.bin", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn a_stored_key_replaces_the_cached_fingerprint() {
+        let tenant = "fingerprint_cache_test";
+        let path = key_path(tenant);
+        store_server_key_at(tenant, &path, b"first server key").unwrap();
+        assert_eq!(fingerprint_at(tenant, &path).unwrap(), server_key_fingerprint_of(b"first server key"));
+
+        // Same length, likely the same mtime: only the invalidation tells them apart
+        store_server_key_at(tenant, &path, b"other server key").unwrap();
+        assert_eq!(fingerprint_at(tenant, &path).unwrap(), server_key_fingerprint_of(b"other server key"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn a_missing_key_has_no_fingerprint() {
+        let path = key_path("fingerprint_missing_test");
+        assert!(matches!(fingerprint_at("fingerprint_missing_test", &path), Err(FingerprintError::Storage(_))));
+    }
+
+    #[test]
+    fn gpu_key_is_used_only_with_its_server_key() {
+        let stored = server_key_fingerprint_of(b"stored server key");
+        let file = bind_gpu_key(b"compressed\nkey", &stored);
+        assert_eq!(bound_gpu_key(&file, &stored).unwrap(), b"compressed\nkey");
+
+        let replaced = server_key_fingerprint_of(b"replaced server key");
+        assert!(matches!(bound_gpu_key(&file, &replaced), Err(FingerprintError::KeyMismatch(_))));
+        // Stored before keys were bound
+        assert!(bound_gpu_key(b"compressed key", &stored).is_err());
+    }
+
+    #[test]
+    fn gpu_is_the_default_only_with_a_device() {
+        assert_eq!(requested_backend(None, false), ComputeBackend::Cpu);
+        assert_eq!(requested_backend(None, true), ComputeBackend::Gpu);
+        assert_eq!(requested_backend(Some(ComputeBackend::Cpu), true), ComputeBackend::Cpu);
+        assert_eq!(requested_backend(Some(ComputeBackend::Gpu), false), ComputeBackend::Gpu);
+        if cfg!(not(feature = "gpu")) {
+            assert!(matches!(gpu_key("gpu_key_test"), Err(FingerprintError::Fhe(_))));
+        }
+    }
+
+    #[test]
+    fn gpu_fallback_is_reported_once_per_reason() {
+        let tenant = "gpu_warning_test";
+        assert!(first_gpu_warning(tenant, "no CUDA device found"));
+        assert!(!first_gpu_warning(tenant, "no CUDA device found"));
+        assert!(first_gpu_warning(tenant, "no compressed server key registered"));
+        assert!(first_gpu_warning("other_tenant", "no CUDA device found"));
+    }
+
+    #[test]
+    fn another_servers_key_is_a_mismatch() {
+        let stored = server_key_fingerprint_of(b"stored server key");
+        assert!(compare_fingerprints(&stored, &stored).is_ok());
+        let err = compare_fingerprints(&stored, &server_key_fingerprint_of(b"sent server key")).unwrap_err();
+        assert!(matches!(&err, FingerprintError::KeyMismatch(message) if message.contains(short(&stored))));
+    }
+}
@@ -0,0 +1,351 @@
+//! Concurrency limits for FHE jobs.
+//!
+//! Verify and register jobs run on worker pools (see workers.rs) sized by
+//! these limits, each kind gated by its own counting semaphore. Limits come from `../database/limits.json`: either set
+//! explicitly or derived from a memory/CPU budget. Running and queued counts,
+//! together with the average duration of completed jobs, are published as
+//! `server_status.json` in every exchange directory for status queries and
+//! client estimates. `tfhe_threads` sizes the thread pool tfhe parallelizes
+//! on and `job_threads` the share of it one verify job gets (see threads.rs).
+//! `trivium_threads` lets each verify job clock FHE-Trivium in parallel
+//! batches (see `shared::trivium_fhe::CLOCK_BATCH`) on threads of its own
+//! share, not on top of it. A changed share applies from a worker's next
+//! job (see `threads::job_pool_outdated`). `matching_backend`
+//! picks the popcount and threshold circuit (see `shared::MatchingBackend`), and
+//! `compute_backend` whether it runs on the CPU or a GPU (see keys.rs).
+
+use serde::{Serialize, Deserialize};
+use shared::template::{DEFAULT_TEMPLATE_BITS, SUPPORTED_TEMPLATE_BITS};
+use shared::{Calibration, Cipher, ComputeBackend, JobCounts, MatchingBackend, ServerStatus};
+use std::fs;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::config;
+
+fn limits_path() -> &'static str {
+    config::database_file!("limits.json")
+}
+
+pub fn status_path() -> &'static str {
+    config::exchange_file!("server_status.json")
+}
+
+const STATUS_FILE: &str = "server_status.json";
+
+/// Weight of the newest job in the moving average of job durations
+const DURATION_SMOOTHING: f64 = 0.3;
+
+/// Rough peak memory of one job, excluding the shared server key. Further
+/// templates of a verify job (rotated variants, fused fingers) are matched one
+/// at a time and add time, not peak memory; see `JobLimiter::record_duration`.
+const VERIFY_JOB_MB: u64 = 512;     // Two FHE-Trivium evaluations + 1024-bit popcount
+const REGISTER_JOB_MB: u64 = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LimitsConfig {
+    #[serde(default)]
+    pub max_concurrent_verify: Option<usize>,
+    #[serde(default)]
+    pub max_concurrent_register: Option<usize>,
+    #[serde(default)]
+    pub memory_budget_mb: Option<u64>,      // Budget for all concurrent jobs
+    #[serde(default)]
+    pub cpu_budget: Option<usize>,          // Cores available to FHE jobs (default: all)
+    #[serde(default)]
+    pub job_memory_budget_mb: Option<u64>,  // Peak FHE data a single job may hold (default: unlimited)
+    #[serde(default)]
+    pub max_gate_ms: Option<f64>,           // Self-test fails above this per-gate time
+    #[serde(default)]
+    pub checkpoint_every_cycles: Option<usize>, // Verify checkpoint interval (see checkpoint.rs; 0 = off)
+    #[serde(default)]
+    pub trivium_threads: Option<usize>,     // Threads clocking one Trivium evaluation (default 1, 0 = one per core)
+    #[serde(default)]
+    pub matching_backend: Option<MatchingBackend>, // "boolean" (default) or "radix" (FheUint16 sums)
+    #[serde(default)]
+    pub compute_backend: Option<ComputeBackend>, // "cpu" or "gpu" (default: gpu when built with it and one is present)
+    #[serde(default)]
+    pub tfhe_threads: Option<usize>,        // Threads of tfhe's parallel operations, all jobs together (default: cpu_budget)
+    #[serde(default)]
+    pub job_threads: Option<usize>,         // Of those, threads one verify job may use (default: an even share)
+    #[serde(default)]
+    pub pin_cores: Option<bool>,            // Pin each verify worker's threads to cores of its own (default off)
+}
+
+/// Threads tfhe may use, and how they are split among concurrent verify jobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadBudget {
+    pub total: usize,       // Global pool (register jobs, startup) and the sum of the job pools
+    pub per_job: usize,     // Pool of one verify worker, its own thread included
+    pub pin_cores: bool,
+}
+
+impl ThreadBudget {
+    /// Threads clocking one Trivium evaluation: `trivium_threads` (0 = all), within the job's share
+    pub fn clock_threads(&self, trivium_threads: usize) -> usize {
+        match trivium_threads {
+            0 => self.per_job,
+            threads => threads.min(self.per_job),
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn load() -> Self {
+        if !Path::new(limits_path()).exists() {
+            return Self::default();
+        }
+        match fs::read_to_string(limits_path())
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("⚠️  Invalid limits file ({}), using defaults", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Per-job memory budget in bytes
+    pub fn job_memory_budget(&self) -> Option<usize> {
+        self.job_memory_budget_mb.map(|mb| (mb * 1024 * 1024) as usize)
+    }
+
+    fn cpus(&self) -> usize {
+        self.cpu_budget.unwrap_or_else(|| {
+            std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        })
+    }
+
+    /// Effective (verify, register) limits. Explicit values win over the budget.
+    pub fn resolve(&self) -> (usize, usize) {
+        let cpus = self.cpus();
+        // Each verify job already parallelizes internally; a handful of jobs saturates the CPU
+        let by_cpu = (cpus / 4).max(1);
+        let by_memory = |job_mb: u64| {
+            self.memory_budget_mb
+                .map(|budget| ((budget / job_mb) as usize).max(1))
+                .unwrap_or(usize::MAX)
+        };
+
+        let verify = self
+            .max_concurrent_verify
+            .unwrap_or_else(|| by_cpu.min(by_memory(VERIFY_JOB_MB)));
+        let register = self
+            .max_concurrent_register
+            .unwrap_or_else(|| cpus.min(by_memory(REGISTER_JOB_MB)));
+        (verify.max(1), register.max(1))
+    }
+
+    /// tfhe threads, shared evenly by the verify jobs that may run at once unless `job_threads` is set
+    pub fn thread_budget(&self) -> ThreadBudget {
+        let total = self.tfhe_threads.unwrap_or_else(|| self.cpus()).max(1);
+        let (verify, _) = self.resolve();
+        let per_job = self.job_threads.unwrap_or(total / verify).clamp(1, total);
+        ThreadBudget { total, per_job, pin_cores: self.pin_cores.unwrap_or(false) }
+    }
+}
+
+/// Counting semaphore that tracks how many jobs are running and waiting
+pub struct JobLimiter {
+    max: usize,
+    state: Mutex<JobCounts>,
+    available: Condvar,
+    timing: Mutex<JobTiming>,
+}
+
+/// Moving average of completed job durations
+#[derive(Debug, Clone, Copy, Default)]
+struct JobTiming {
+    average_secs: Option<f64>,
+    samples: u64,
+}
+
+impl JobTiming {
+    fn record(&mut self, secs: f64) {
+        self.average_secs = Some(match self.average_secs {
+            Some(avg) => avg + DURATION_SMOOTHING * (secs - avg),
+            None => secs,
+        });
+        self.samples += 1;
+    }
+}
+
+/// Held while a job runs; releases its slot on drop
+pub struct JobPermit<'a> {
+    limiter: &'a JobLimiter,
+}
+
+impl JobLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            state: Mutex::new(JobCounts::default()),
+            available: Condvar::new(),
+            timing: Mutex::new(JobTiming::default()),
+        }
+    }
+
+    /// Continue from a previously published average
+    fn with_timing(self, average_secs: Option<f64>, samples: u64) -> Self {
+        *self.timing.lock().unwrap_or_else(|e| e.into_inner()) = JobTiming { average_secs, samples };
+        self
+    }
+
+    /// Fold the duration of a successfully completed job into the average, per unit
+    /// of its `cost` (templates matched by a verify job, so a user's rotated
+    /// variants and fused fingers don't pass for a slower server)
+    pub fn record_duration(&self, duration: Duration, cost: usize) {
+        self.timing.lock().unwrap_or_else(|e| e.into_inner()).record(duration.as_secs_f64() / cost.max(1) as f64);
+    }
+
+    fn timing(&self) -> JobTiming {
+        *self.timing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Count a job handed to a worker pool as queued until it `acquire`s
+    pub fn enqueue(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).queued += 1;
+        publish_status();
+    }
+
+    /// Block until a slot is free, moving an `enqueue`d job from queued to running
+    pub fn acquire(&self) -> JobPermit<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.running >= self.max {
+            state = self.available.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.queued -= 1;
+        state.running += 1;
+        drop(state);
+        publish_status();
+        JobPermit { limiter: self }
+    }
+
+    pub fn counts(&self) -> JobCounts {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for JobPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.running -= 1;
+        drop(state);
+        self.limiter.available.notify_one();
+        publish_status();
+    }
+}
+
+pub struct Limiters {
+    pub verify: JobLimiter,
+    pub register: JobLimiter,
+}
+
+static LIMITERS: OnceLock<Limiters> = OnceLock::new();
+
+/// Process-wide limiters, configured from `limits.json` on first use
+pub fn limiters() -> &'static Limiters {
+    LIMITERS.get_or_init(|| {
+        let (verify, register) = LimitsConfig::load().resolve();
+        println!("🚦 Concurrency limits: {} verify, {} register", verify, register);
+        // Calibration survives restarts through the last published status
+        let previous = fs::read_to_string(status_path())
+            .ok()
+            .and_then(|data| serde_json::from_str::<ServerStatus>(&data).ok())
+            .map(|status| status.calibration)
+            .unwrap_or_default();
+        Limiters {
+            verify: JobLimiter::new(verify).with_timing(previous.verify_job_secs, previous.samples),
+            register: JobLimiter::new(register).with_timing(previous.register_job_secs, 0),
+        }
+    })
+}
+
+/// Write the status file (must not be called with a limiter lock held)
+fn publish_status() {
+    let Some(limiters) = LIMITERS.get() else { return };
+    let (verify_timing, register_timing) = (limiters.verify.timing(), limiters.register.timing());
+    let status = ServerStatus {
+        verify: limiters.verify.counts(),
+        register: limiters.register.counts(),
+        max_concurrent_verify: limiters.verify.max,
+        max_concurrent_register: limiters.register.max,
+        supported_template_bits: SUPPORTED_TEMPLATE_BITS.to_vec(),
+        supported_ciphers: Cipher::ALL.to_vec(),
+        calibration: Calibration {
+            template_bits: Some(DEFAULT_TEMPLATE_BITS),
+            verify_job_secs: verify_timing.average_secs,
+            register_job_secs: register_timing.average_secs,
+            samples: verify_timing.samples,
+        },
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let Ok(json) = serde_json::to_string_pretty(&status) else { return };
+    // Every workstation sees the same queue
+    for exchange in crate::exchange::known_exchanges() {
+        if let Err(e) = exchange.put(STATUS_FILE, json.as_bytes()) {
+            eprintln!("⚠️  Could not publish status to '{}': {}", exchange.origin, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_derives_limits_unless_explicit() {
+        let config = LimitsConfig {
+            memory_budget_mb: Some(1024),
+            cpu_budget: Some(64),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve(), (2, 16));
+
+        let config = LimitsConfig {
+            max_concurrent_verify: Some(5),
+            memory_budget_mb: Some(100),
+            cpu_budget: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve(), (5, 1));
+    }
+
+    #[test]
+    fn thread_budget_splits_among_verify_jobs() {
+        let config = LimitsConfig { cpu_budget: Some(32), ..Default::default() };
+        let budget = config.thread_budget();
+        assert_eq!((budget.total, budget.per_job), (32, 4));
+        assert_eq!((budget.clock_threads(0), budget.clock_threads(2), budget.clock_threads(16)), (4, 2, 4));
+
+        let config = LimitsConfig { tfhe_threads: Some(6), job_threads: Some(16), max_concurrent_verify: Some(2), ..Default::default() };
+        assert_eq!(config.thread_budget().per_job, 6);
+    }
+
+    #[test]
+    fn timing_average_follows_recent_jobs() {
+        let mut timing = JobTiming::default();
+        timing.record(100.0);
+        assert_eq!(timing.average_secs, Some(100.0));
+        timing.record(200.0);
+        assert!((timing.average_secs.unwrap() - 130.0).abs() < 1e-9);
+        assert_eq!(timing.samples, 2);
+    }
+
+    #[test]
+    fn job_durations_are_averaged_per_template() {
+        let limiter = JobLimiter::new(1);
+        // The enrolled template, eight rotated variants and the duress finger
+        limiter.record_duration(Duration::from_secs(100), 10);
+        assert_eq!(limiter.timing().average_secs, Some(10.0));
+        limiter.record_duration(Duration::from_secs(10), 0);
+        assert_eq!(limiter.timing().average_secs, Some(10.0));
+    }
+}
@@ -1,274 +1,2479 @@
+mod admin;
+mod archive;
+mod audit;
+mod bench;
+mod blob;
+mod blob_store;
+mod checkpoint;
+mod compact;
+mod config;
 mod database;
+mod exchange;
+mod grpc;
+mod http;
+mod integrity;
+mod jobs;
+mod keys;
+mod limits;
+mod maintenance;
+#[cfg(test)]
+mod memory_store;
+mod ownership;
+mod policy;
+mod postgres_store;
+mod remote;
+mod selftest;
+mod session;
+mod sqlite_store;
+mod stale;
+mod tenant;
+mod threads;
+mod workers;
 
-use database::{Database, TemplateEntry};
+use audit::AuditEvent;
+use blob::MemoryBudget;
+use checkpoint::Checkpoints;
+use database::{Database, AuxTemplate, StorageBackend, StorageConfig, TemplateBlob, TemplateEntry, TemplateStore};
+use exchange::{Exchange, ExchangeConfig};
 use shared::{
-    RegisterRequest, RegisterResponse,
-    VerifyRequest, VerifyResponse,
-    decrypt_homomorphic,
-    diff_bits, popcount_1024, leq_constant,  // ⬅️ popcount_512 → popcount_1024
+    Cipher, Factor, ConsentInfo, EnrolledThreshold, QualityMask, ErrorCondition, FailureAction,
+    RegisterRequest, RegisterResponse, DeltaRequest,
+    VerifyRequest, VerifyResponse, ResultMode,
+    PolicyRequest, PolicyResponse,
+    AccountOperation, AccountRequest, AccountResponse,
+    DeleteRequest, DeleteResponse, ReceiptRequest, ReceiptResponse, RevokeRequest, KeyRotationRequest,
+    AdminCommand, AdminRequest,
+    CancelRequest, CancelResponse, CancellationToken, Cancelled,
+    ErrorCode, FingerprintError,
+    decrypt_homomorphic_resumable, ConsoleProgress, DecryptState,
+    diff_bits, masked_diff_bits, counter_width, popcount_tree, weighted_popcount, leq_constant, leq_encrypted, leq_scaled,
+    match_distance, uses_ripple_counter,
+    EvaluationKey, MatchingBackend, PopcountAccumulator,
+    select_bits,
+    min_distance,
+    fused_distance,
+    fhe_constant,
+    fhe_false_like,
 };
 
-use tfhe::{set_server_key, ServerKey, FheBool};
+use shared::attestation::{sha256_hex, sign_receipt, ReceiptClaims};
+use shared::identity::AppliedThreshold;
+use tfhe::{CompactPublicKey, FheBool};
+use shared::consensus;
+use shared::delta::{self, TemplateDelta, MAX_DELTA_BITS};
+use shared::fusion;
+use shared::fuzzy::{self, FuzzyMatch, FuzzySketch};
+use shared::ownership::{encrypt_bit, mask_with_match};
+use shared::quality;
+use shared::protocol::{self, ADMIN_USER_ID};
+use shared::sealed;
+use shared::session::SessionClaim;
+use shared::soft::{self, SoftProfile};
+use shared::template::{self, TemplateParams};
+use shared::telemetry::{self, PhaseTimer, TelemetryConfig, TelemetryRecord};
+use shared::{etrln, trln};
 use std::fs;
-use std::path::Path;
-use std::time::Duration;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-const EXCHANGE_DIR: &str = "../exchange";
-const SERVER_KEY_PATH: &str = "../database/server_key.bin";
+fn default_server_key_path() -> &'static str {
+    config::database_file!("server_key.bin")
+}
+
+fn attestation_key_path() -> &'static str {
+    config::database_file!("attestation.key")
+}
+
+fn telemetry_config_path() -> &'static str {
+    config::database_file!("telemetry.json")
+}
+
+fn telemetry_log_path() -> &'static str {
+    config::database_file!("telemetry.jsonl")
+}
+
+const SERVER_ISSUER: &str = "fingerprint-fhe-server";
+
+/// How often exchange directories are re-discovered
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args: Vec<String> = std::env::args().collect();
+    shared::i18n::init(&mut args);
+    shared::logging::init(config::get().logging.filter.as_deref());
+    
+    match args.get(1).map(|s| s.as_str()) {
+        Some("admin") => admin::run(&args[2..]),
+        Some("maintenance") => maintenance::run(),
+        Some("compact") => compact::run(&args[2..]),
+        Some("verify-db") => integrity::run(),
+        Some("seal-db") => database::seal_existing(),
+        Some("export") => archive::export(&args[2..]),
+        Some("import") => archive::import(&args[2..]),
+        Some("bench-popcount") => bench::run(&args[2..]),
+        Some("self-test") => selftest::run(&args[2..]),
+        Some("http") => http::run(&args[2..]),
+        Some("grpc") => grpc::run(&args[2..]),
+        _ => serve(),
+    }
+}
+
+fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    trln!("server.banner");
+    println!("{}", "=".repeat(70));
+    
+    fs::create_dir_all(exchange::default_exchange_dir())?;
+    fs::create_dir_all(config::database_dir())?;
+
+    // Before the self-test, the first thing to run tfhe operations
+    threads::configure(&limits::LimitsConfig::load().thread_budget());
+
+    selftest::on_startup()?;
+    workers::handle_signals()?;
+
+    let exchange_key = exchange::init_key()?;
+    trln!("server.exchange_key", exchange_key.handshake().key_id);
+    let identity = exchange::init_identity()?;
+    trln!("server.identity_key", identity.key_id());
+    let (resumed, failed) = jobs::recover_interrupted();
+    if failed > 0 {
+        etrln!("server.jobs_interrupted", failed);
+    }
+    for interrupted in resumed {
+        let jobs::Interrupted { exchange, request, tracker } = interrupted;
+        match open_job(&exchange, "verify", request) {
+            Ok(mut job) => {
+                trln!("server.job_resumed", tracker.job_id());
+                // The session was used at intake; the claim verified then stands
+                job.session = Checkpoints::for_job(&job.path).session();
+                job.cancel = tracker.cancel_token();
+                job.tracker = Some(tracker);
+                spawn_job(job, "Verify", &limits::limiters().verify, &workers::pools().verify, handle_verify);
+            }
+            Err(e) => {
+                etrln!("server.job_failed", tracker.job_id(), e);
+                tracker.close(Some(e.as_ref()));
+            }
+        }
+    }
+
+    let mut exchanges = exchange::discover();
+    let mut last_discovery = Instant::now();
+    let mut last_poll: std::collections::HashMap<String, Instant> = std::collections::HashMap::new();
+
+    trln!("server.waiting");
+
+    let mut last_maintenance: Option<Instant> = None;
+
+    while !workers::shutting_down() {
+        // Periodic maintenance (retention purge, stale exchange files)
+        if last_maintenance.is_none_or(|t| t.elapsed() >= maintenance::MAINTENANCE_INTERVAL) {
+            if let Err(e) = maintenance::run() {
+                etrln!("server.maintenance_failed", e);
+            }
+            last_maintenance = Some(Instant::now());
+        }
+
+        // Pick up workstations added since the last scan
+        if last_discovery.elapsed() >= DISCOVERY_INTERVAL {
+            exchanges = exchange::discover();
+            last_discovery = Instant::now();
+        }
+
+        // Object-storage exchanges are polled less often than directories
+        for exchange in &exchanges {
+            let due = last_poll
+                .get(&exchange.origin)
+                .is_none_or(|t: &Instant| t.elapsed() >= exchange.poll_interval());
+            if due {
+                poll_exchange(exchange);
+                for slot in exchange.user_slots() {
+                    poll_exchange(&slot);
+                }
+                last_poll.insert(exchange.origin.clone(), Instant::now());
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(500));
+    }
+
+    // Running jobs stop at their next gate; queued ones are skipped (both resume after a restart)
+    let limiters = limits::limiters();
+    loop {
+        let (verify, register) = (limiters.verify.counts(), limiters.register.counts());
+        if verify.running + verify.queued + register.running + register.queued == 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    trln!("server.stopped");
+    Ok(())
+}
+
+/// Handle or dispatch the pending requests of one exchange directory
+fn poll_exchange(exchange: &Exchange) {
+    let origin = if exchange.origin == exchange::DEFAULT_ORIGIN {
+        String::new()
+    } else {
+        format!(" [{}]", exchange.origin)
+    };
+    let pending = match exchange.pending_requests() {
+        Ok(pending) => pending,
+        Err(e) => {
+            etrln!("server.poll_failed", exchange.origin, e);
+            return;
+        }
+    };
+    let has_request = |kind: &str| pending.iter().any(|k| k == kind);
+
+    // Session handshakes are answered first: a register/verify that follows may need one
+    if has_request("session") {
+        match session::handle(exchange) {
+            Ok(_) => trln!("server.session_issued", origin),
+            Err(e) => etrln!("server.session_failed", e),
+        }
+    }
+
+    // Check for register request (runs on a register worker, see workers.rs)
+    if has_request("register") {
+        trln!("server.register_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match claim_job(exchange, "register") {
+            Ok(job) => spawn_job(job, "Register", &limits::limiters().register, &workers::pools().register, handle_register),
+            Err(e) => etrln!("server.register_claim_failed", e),
+        }
+    }
 
-// Request/Response paths
-const REGISTER_REQ_PATH: &str = "../exchange/register_request.json";
-const REGISTER_RESP_PATH: &str = "../exchange/register_response.json";
-const VERIFY_REQ_PATH: &str = "../exchange/verify_request.json";
-const VERIFY_RESP_PATH: &str = "../exchange/verify_response.json";
+    // Check for delta re-enrollment (shares the register limiter)
+    if has_request("delta") {
+        trln!("server.delta_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match claim_job(exchange, "delta") {
+            Ok(job) => spawn_job(job, "Delta", &limits::limiters().register, &workers::pools().register, handle_delta),
+            Err(e) => etrln!("server.delta_claim_failed", e),
+        }
+    }
+
+    // Check for verify request (runs on a verify worker)
+    if has_request("verify") {
+        trln!("server.verify_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match claim_job(exchange, "verify") {
+            Ok(job) => spawn_job(job, "Verify", &limits::limiters().verify, &workers::pools().verify, handle_verify),
+            Err(e) => etrln!("server.verify_claim_failed", e),
+        }
+    }
+
+    // Cancel requests only set the job's token; its worker answers the job itself
+    if has_request("cancel") {
+        match handle_cancel(exchange) {
+            Ok((job_id, true)) => trln!("server.cancel_requested", job_id, origin),
+            Ok((job_id, false)) => trln!("server.cancel_unknown", job_id, origin),
+            Err(e) => etrln!("server.cancel_failed", e),
+        }
+    }
+
+    // Check for policy request
+    if has_request("policy") {
+        match handle_policy(exchange) {
+            Ok(_) => trln!("server.policy_sent", origin),
+            Err(e) => etrln!("server.policy_failed", e),
+        }
+    }
+
+    // Check for admin request
+    if has_request("admin") {
+        match handle_admin(exchange) {
+            Ok(command) => trln!("server.admin_sent", command, origin),
+            Err(e) => etrln!("server.admin_failed", e),
+        }
+    }
+
+    // Check for account management request
+    if has_request("account") {
+        trln!("server.account_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match handle_account(exchange) {
+            Ok(_) => trln!("server.account_completed"),
+            Err(e) => etrln!("server.account_failed", e),
+        }
+        
+        trln!("server.waiting_next");
+    }
+
+    // Check for delete request
+    if has_request("delete") {
+        trln!("server.delete_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match handle_delete(exchange) {
+            Ok(_) => trln!("server.delete_completed"),
+            Err(e) => etrln!("server.delete_failed", e),
+        }
+        
+        trln!("server.waiting_next");
+    }
+
+    // Check for receipt request
+    if has_request("receipt") {
+        trln!("server.receipt_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match handle_receipt(exchange) {
+            Ok(_) => trln!("server.receipt_completed"),
+            Err(e) => etrln!("server.receipt_failed", e),
+        }
+        
+        trln!("server.waiting_next");
+    }
+
+    // Check for transform revocation
+    if has_request("revoke") {
+        trln!("server.revoke_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match handle_revoke(exchange) {
+            Ok(_) => trln!("server.revoke_completed"),
+            Err(e) => etrln!("server.revoke_failed", e),
+        }
+        
+        trln!("server.waiting_next");
+    }
+
+    // Check for client key rotation
+    if has_request("rotate") {
+        trln!("server.rotate_detected", origin);
+        println!("{}", "─".repeat(70));
+        
+        match handle_rotate(exchange) {
+            Ok(_) => trln!("server.rotate_completed"),
+            Err(e) => etrln!("server.rotate_failed", e),
+        }
+        
+        trln!("server.waiting_next");
+    }
+}
+
+// ==================== JOBS ====================
+
+/// A request moved out of its exchange slot, remembering where to answer
+struct Job {
+    kind: String,
+    path: PathBuf,
+    exchange: Exchange,
+    sealed: bool,
+    reply_to: Option<[u8; 32]>,   // Client key the response is sealed to
+    tracker: Option<jobs::Tracker>, // Verify jobs: ticket and status files (see jobs.rs)
+    cancel: CancellationToken,      // The tracker's token; never set for untracked jobs
+    session: Option<SessionClaim>,  // Verify jobs: the session verified at intake
+    cost: usize,                    // Verify jobs: templates matched (see `TemplateEntry::verify_templates`)
+}
+
+impl Job {
+    /// Request body, decrypted if it was sealed
+    fn request(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(exchange::open_request(fs::read(&self.path)?)?.data)
+    }
+
+    fn respond<T: serde::Serialize>(&self, kind: &str, response: &T) -> Result<(), Box<dyn std::error::Error>> {
+        self.exchange.write_response(kind, response, self.reply_to.as_ref())
+    }
+
+    /// Answer a verify job; the final job status carries the same response
+    fn respond_verify(&self, response: &VerifyResponse) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(tracker) = &self.tracker {
+            tracker.result(response);
+        }
+        self.respond("verify", response)
+    }
+
+    /// Short name for log lines: the job id of verify jobs, else the request file
+    fn tag(&self) -> String {
+        match &self.tracker {
+            Some(tracker) => format!("[{} {}]", self.kind, &tracker.job_id()[..8]),
+            None => format!("[{}]", self.path.file_stem().unwrap_or_default().to_string_lossy()),
+        }
+    }
+
+    /// Answer a job that died without answering (a panic in its handler, or an error)
+    /// with the generic message of `code`; the error itself may name server paths
+    fn respond_failure(&self, code: ErrorCode) -> Result<(), Box<dyn std::error::Error>> {
+        if self.exchange.has_response(&self.kind)? {
+            return Ok(());
+        }
+        let message = code.message().to_string();
+        match self.kind.as_str() {
+            "verify" => self.respond_verify(&VerifyResponse::error(message).with_error_code(code)),
+            kind => self.respond(kind, &RegisterResponse::error(String::new(), message).with_error_code(code)),
+        }
+    }
+
+    /// Report the phase a verify job entered to its client
+    fn progress(&self, phase: &str) {
+        if let Some(tracker) = &self.tracker {
+            tracker.phase(phase);
+        }
+    }
+}
+
+/// Move a request out of its exchange slot so the next one can be submitted
+fn claim_job(exchange: &Exchange, kind: &str) -> Result<Job, Box<dyn std::error::Error>> {
+    let jobs_dir = exchange.jobs_dir();
+    fs::create_dir_all(&jobs_dir)?;
+    let job_path = jobs_dir.join(format!(
+        "{}_{}.json",
+        kind,
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    if !exchange.take_request(kind, &job_path)? {
+        return Err(format!("{} request disappeared", kind).into());
+    }
+    let mut job = open_job(exchange, kind, job_path)?;
+    
+    // A verify job can wait in the queue longer than a session lives: its session is
+    // checked now and the verified claim kept with the job's checkpoints for a resume
+    if kind == "verify" {
+        (job.session, job.cost) = authenticate_intake(&job)?;
+        if let Some(claim) = &job.session {
+            Checkpoints::for_job(&job.path).save_session(claim)?;
+        }
+    }
+    
+    // Verify jobs get an id right away; the client polls its status instead of blocking
+    job.tracker = match kind {
+        "verify" => match jobs::Tracker::start(exchange, &job.path, job.reply_to) {
+            Ok(tracker) => {
+                trln!("server.job_ticket", tracker.job_id());
+                job.cancel = tracker.cancel_token();
+                Some(tracker)
+            }
+            Err(e) => {
+                etrln!("server.job_ticket_failed", e);
+                None
+            }
+        },
+        _ => None,
+    };
+    Ok(job)
+}
+
+/// Fields of a verify request its session and cost are checked with
+#[derive(serde::Deserialize)]
+struct VerifyIntake {
+    user_id: String,
+    #[serde(default)]
+    factor: Factor,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    session: Option<shared::session::SessionBinding>,
+}
+
+/// Check the session of a claimed verify request against the user's enrollment credential,
+/// and count the templates the job will match. A request for an unknown tenant or user
+/// passes; the job itself answers it.
+fn authenticate_intake(job: &Job) -> Result<(Option<SessionClaim>, usize), Box<dyn std::error::Error>> {
+    let req: VerifyIntake = serde_json::from_slice(&job.request()?)?;
+    let Ok(tenant) = tenant::resolve(req.api_key.as_deref()) else { return Ok((None, 1)) };
+    let Some(enrolled) = database::templates()?.get(&tenant, &req.user_id)? else { return Ok((None, 1)) };
+    match session::authenticate(req.session.as_ref(), &req.user_id, &tenant, enrolled.credential_key.as_deref()) {
+        Ok(verified) => {
+            if let Some(claim) = &verified {
+                trln!("server.session_verified");
+                trln!("server.session_expires", claim.expires_at);
+            }
+            Ok((verified, enrolled.verify_templates(req.factor)))
+        }
+        Err(message) => {
+            audit::record(
+                AuditEvent::new("verify", &req.user_id, false)
+                    .with_tenant(&tenant)
+                    .with_origin(&job.exchange.origin)
+                    .with_detail(format!("session: {}", message)),
+            );
+            let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Unauthorized);
+            job.respond_verify(&resp)?;
+            let _ = fs::remove_file(&job.path);
+            exchange::job_finished(&job.path);
+            Err(format!("Session rejected for '{}': {}", req.user_id, message).into())
+        }
+    }
+}
+
+/// A job for a claimed request file (fresh or left over from before a restart)
+fn open_job(exchange: &Exchange, kind: &str, job_path: PathBuf) -> Result<Job, Box<dyn std::error::Error>> {
+    // The header alone tells whether the request is sealed and where to answer
+    let mut header = Vec::with_capacity(sealed::HEADER_LEN);
+    fs::File::open(&job_path)?.take(sealed::HEADER_LEN as u64).read_to_end(&mut header)?;
+    let is_sealed = sealed::is_sealed(&header);
+    if !is_sealed {
+        if let Err(e) = exchange::check_plaintext_allowed() {
+            let _ = fs::remove_file(&job_path);
+            return Err(e);
+        }
+    }
+    exchange::job_started(&job_path);
+    Ok(Job {
+        kind: kind.to_string(),
+        path: job_path,
+        exchange: exchange.clone(),
+        sealed: is_sealed,
+        reply_to: sealed::reply_to(&header),
+        tracker: None,
+        cancel: CancellationToken::new(),
+        session: None,
+        cost: 1,
+    })
+}
+
+/// Hand a claimed job to a worker pool; it runs once the limiter grants a slot.
+/// A panic in the handler fails only this job: the client gets an error response.
+fn spawn_job(
+    job: Job,
+    label: &'static str,
+    limiter: &'static limits::JobLimiter,
+    pool: &'static workers::WorkerPool,
+    handler: fn(&Job) -> Result<(), Box<dyn std::error::Error>>,
+) {
+    let counts = limiter.counts();
+    if counts.running > 0 || counts.queued > 0 {
+        trln!("server.job_queued", label, counts.running, counts.queued);
+    }
+    
+    limiter.enqueue();
+    pool.submit(move || {
+        let _permit = limiter.acquire();
+        let log = workers::log_job(&job.path, job.tag());
+        trln!("server.job_started", label, workers::job_log_path(&job.path).display());
+        if let Some(tracker) = &job.tracker {
+            tracker.running();
+        }
+        let started = Instant::now();
+        let outcome = if job.cancel.is_cancelled() {
+            Ok(Err(Cancelled.into()))
+        } else {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handler(&job)))
+        };
+        let result = match outcome {
+            Ok(result) => result,
+            Err(panic) => {
+                let message = format!("Internal error: {}", workers::panic_message(panic.as_ref()));
+                if let Err(e) = job.respond_failure(ErrorCode::Internal) {
+                    etrln!("server.job_failure_unanswered", label, e);
+                }
+                Err(message.into())
+            }
+        };
+        
+        // Stopped by the shutdown: request file, record and checkpoints stay for the restart
+        if stopped_for_shutdown(&result) {
+            trln!("server.job_interrupted", label);
+            exchange::job_finished(&job.path);
+            return;
+        }
+        match &result {
+            Ok(_) => {
+                limiter.record_duration(started.elapsed(), job.cost);
+                trln!("server.job_completed", label);
+            }
+            Err(e) if e.is::<Cancelled>() => {
+                trln!("server.job_cancelled", label);
+                // A job cancelled while queued has not been answered yet
+                if let Err(e) = job.respond_failure(ErrorCode::Cancelled) {
+                    etrln!("server.job_failure_unanswered", label, e);
+                }
+            }
+            Err(e) => {
+                etrln!("server.job_failed", label, e);
+                // Errors before the handler answered (unreadable request, key storage) reach the
+                // client as their code's generic message; the detail is only logged above
+                if let Err(e) = job.respond_failure(ErrorCode::of(e.as_ref())) {
+                    etrln!("server.job_failure_unanswered", label, e);
+                }
+            }
+        }
+        if let Some(tracker) = &job.tracker {
+            tracker.close(result.as_ref().err().map(|e| e.as_ref()));
+        }
+        let _ = fs::remove_file(&job.path);
+        exchange::job_finished(&job.path);
+        drop(log);
+        trln!("server.waiting_next");
+    });
+}
+
+/// Cancelled by the shutdown rather than on request; the job resumes after a restart
+fn stopped_for_shutdown(result: &Result<(), Box<dyn std::error::Error>>) -> bool {
+    workers::shutting_down() && result.as_ref().is_err_and(|e| e.is::<Cancelled>())
+}
+
+// ==================== REGISTER HANDLER ====================
+
+fn handle_register(job: &Job) -> Result<(), Box<dyn std::error::Error>> {
+    register_job(job, database::templates()?)
+}
+
+fn register_job(job: &Job, store: &dyn TemplateStore) -> Result<(), Box<dyn std::error::Error>> {
+    let req_path = job.path.as_path();
+    
+    // 1. Read request
+    let req: RegisterRequest = serde_json::from_slice(&job.request()?)?;
+    
+    // 1a. Resolve tenant namespace from the API key
+    let tenant = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(t) => t,
+        Err(message) => {
+            let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Unauthorized);
+            job.respond("register", &resp)?;
+            let _ = fs::remove_file(req_path);
+            return Err(message.into());
+        }
+    };
+    
+    trln!("server.user_id", req.user_id);
+    trln!("server.tenant", tenant);
+    
+    // The admin slot's user id can't be enrolled (see `ADMIN_USER_ID`)
+    if req.user_id == ADMIN_USER_ID {
+        let message = shared::tr!("server.admin_user_id", req.user_id);
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    trln!("server.ciphertext", req.ciphertext.len());
+    trln!("server.cipher", req.cipher);
+    
+    // 1b. Template length must be one the server supports and match the ciphertext (and
+    // the parameters, if sent)
+    let size_check = template::check_ciphertext(req.template_bits, req.ciphertext.len())
+        .and_then(|_| check_template_params(req.params.as_ref(), req.template_bits));
+    if let Err(message) = size_check {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1c. A per-user threshold must fit the template length
+    if let Some(Err(message)) = req.threshold_bits.as_ref().map(|t| check_enrolled_threshold(t, req.template_bits)) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1d. Captures of a multi-sample enrollment must fit the template voted from them
+    let sample_check = (!req.samples.is_empty() || req.reliability_mask.is_some()).then(|| check_samples(&req));
+    if let Some(Err(message)) = sample_check {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1e. A quality mask covers the primary finger's template bit by bit
+    if let Some(Err(message)) = req.quality_mask.as_ref().map(|mask| check_enrolled_quality_mask(mask, &req)) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1f. Bit weights come with the primary finger, one per template bit
+    let weight_check = req.bit_weights.as_deref().map(|weights| {
+        if req.duress || req.factor != Factor::Fingerprint {
+            return Err("Only the primary finger is enrolled with bit weights".to_string());
+        }
+        template::check_weights(weights, req.template_bits).map(drop)
+    });
+    if let Some(Err(message)) = weight_check {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1g. Rotated variants come with the primary finger, in the request's cipher and length
+    if let Err(message) = check_rotations(&req) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1h. A capture quality score is a percentage, recorded for the primary finger
+    let quality_check = req.capture_quality.map(|score| {
+        if req.duress || req.factor != Factor::Fingerprint {
+            Err("Only the primary finger is enrolled with a capture quality score".to_string())
+        } else if score > 100 {
+            Err(format!("Capture quality score {} is out of range (0-100)", score))
+        } else {
+            Ok(())
+        }
+    });
+    if let Some(Err(message)) = quality_check {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1i. A fuzzy sketch covers the primary template; the code decides the match, not a threshold
+    if let Err(message) = check_fuzzy_sketch(&req) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1j. Further fingers of a fused enrollment come with the primary one, under a fusion rule
+    if let Err(message) = check_fingers(&req) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 1k. A retention deadline must parse, or the enrollment would never be purged
+    if let Err(message) = check_consent(req.consent.as_ref()) {
+        let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond("register", &resp)?;
+        let _ = fs::remove_file(req_path);
+        return Err(message.into());
+    }
+    
+    // 2. Load the user's enrollment - ✅ HATA YAKALA
+    let _db_guard = database::lock();
+    let existing = match store.get(&tenant, &req.user_id) {
+        Ok(existing) => existing,
+        Err(e) => {
+            etrln!("server.db_load_failed", e);
+            // Only a corrupt templates.json is replaced; other stores keep their data
+            if StorageConfig::load().backend != StorageBackend::Json {
+                let _ = fs::remove_file(req_path);
+                return Err(format!("Database load failed: {}", e).into());
+            }
+            etrln!("server.db_creating");
+            
+            // Backup corrupt database
+            if Path::new(database::db_path()).exists() {
+                let backup_path = format!("{}.backup.{}", database::db_path(),
+                    chrono::Utc::now().timestamp());
+                let _ = fs::rename(database::db_path(), &backup_path);
+                trln!("server.db_backed_up", backup_path);
+            }
+            
+            // Create fresh database
+            let fresh_db = Database {
+                version: "1.0".to_string(),
+                templates: std::collections::HashMap::new(),
+            };
+            fresh_db.save()?;
+            None
+        }
+    };
+    
+    // 3. Session proof: the registered credential, or the one a first enrollment registers.
+    // An enrolled user's credential is never taken from the request.
+    let first_enrollment = existing.is_none();
+    let credential = match &existing {
+        Some(e) => e.credential_key.clone(),
+        None => req.credential_key.clone(),
+    };
+    match session::authenticate(req.session.as_ref(), &req.user_id, &tenant, credential.as_deref()) {
+        Ok(verified) => {
+            if verified.is_some() {
+                trln!("server.session_verified");
+            }
+        }
+        Err(message) => {
+            audit::record(
+                AuditEvent::new("register", &req.user_id, false)
+                    .with_tenant(&tenant)
+                    .with_origin(&job.exchange.origin)
+                    .with_detail(format!("session: {}", message)),
+            );
+            let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Unauthorized);
+            job.respond("register", &resp)?;
+            let _ = fs::remove_file(req_path);
+            return Err(message.into());
+        }
+    }
+    
+    // 4. Replacing an enrolled template takes the client's confirmation
+    let replaces = replaces_template(existing.as_ref(), &req);
+    if replaces {
+        trln!("server.user_exists", req.replace_existing);
+        if !req.replace_existing {
+            let message = format!("User '{}' is already enrolled; confirm the re-enrollment to replace the template", req.user_id);
+            let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+            job.respond("register", &resp)?;
+            let _ = fs::remove_file(req_path);
+            return Err(message.into());
+        }
+    }
+    
+    // 4a. Save the server key (one per tenant) if the tenant has none yet, only for an
+    // accepted enrollment. Replacing it invalidates the whole tenant, so only `keys rotate` may.
+    let stored_key = Path::new(&tenant::server_key_path(&tenant))
+        .exists()
+        .then(|| keys::server_key_fingerprint(&tenant))
+        .transpose()?;
+    let sent_key = req.server_key_bytes.as_deref().map(protocol::server_key_fingerprint);
+    match register_key_action(&tenant, stored_key.as_deref(), sent_key.as_deref()) {
+        Ok(true) => {
+            trln!("server.saving_server_key");
+            let saved_path = keys::store_server_key(&tenant, req.server_key_bytes.as_deref().unwrap_or_default())?;
+            trln!("server.server_key_saved", saved_path);
+        }
+        Ok(false) => trln!("server.server_key_exists"),
+        Err(e) => {
+            let resp = RegisterResponse::error(req.user_id.clone(), e.to_string()).with_error_code(e.code());
+            job.respond("register", &resp)?;
+            let _ = fs::remove_file(req_path);
+            return Err(e.into());
+        }
+    }
+    
+    // 4b. Compressed server key for GPU evaluation; one sent with an older server key no longer fits
+    if let Some(ref gpu_key_bytes) = req.gpu_server_key_bytes {
+        let saved_path = keys::store_gpu_key(&tenant, gpu_key_bytes, &keys::server_key_fingerprint(&tenant)?)?;
+        trln!("server.gpu_key_saved", saved_path);
+    } else if req.server_key_bytes.is_some() {
+        keys::remove_gpu_key(&tenant)?;
+    }
+    
+    // 5. Vec<bool> -> Vec<u8> dönüşümü
+    let ciphertext_bytes = bools_to_bytes(&req.ciphertext);
+    
+    // 6. Create template entry; templates it drops are removed once it is saved
+    let mut dropped = Vec::new();
+    let mut entry = if req.duress || req.factor != Factor::Fingerprint {
+        // Duress finger and fallback factors are attached to an existing enrollment
+        let mut entry = match existing {
+            Some(e) => e,
+            None => {
+                let resp = RegisterResponse::error(
+                    req.user_id.clone(),
+                    "Duress finger and fallback factors require an existing enrollment".to_string(),
+                )
+                .with_error_code(ErrorCode::Protocol);
+                job.respond("register", &resp)?;
+                let _ = fs::remove_file(req_path);
+                return Err(format!("User '{}' must register a fingerprint first", req.user_id).into());
+            }
+        };
+        let params_check = check_probe_params(&entry, req.factor, req.params.as_ref())
+            .and_then(|_| check_transform(&entry, req.factor, req.transform_id.as_deref()));
+        if entry.template_bits != req.template_bits || params_check.is_err() {
+            let message = params_check.err().unwrap_or_else(|| format!(
+                "Template has {} bits but the enrollment uses {}-bit templates",
+                req.template_bits, entry.template_bits
+            ));
+            let resp = RegisterResponse::error(req.user_id.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+            job.respond("register", &resp)?;
+            let _ = fs::remove_file(req_path);
+            return Err(message.into());
+        }
+        let mut aux = AuxTemplate::new(
+            ciphertext_bytes,
+            req.encrypted_key_bytes,
+            req.encrypted_iv_bytes,
+        );
+        aux.blob.cipher = req.cipher;
+        aux.blob.externalize()?;
+        aux.soft = req.soft.clone();
+        if req.consent.is_some() {
+            entry.consent = req.consent.clone();
+        }
+        if req.duress {
+            entry.duress = Some(aux);
+            trln!("server.duress_enrolled");
+        } else {
+            entry.factors.insert(req.factor, aux);
+            trln!("server.factor_enrolled", req.factor);
+        }
+        entry.updated_at = chrono::Utc::now().to_rfc3339();
+        entry
+    } else {
+        let mut entry = TemplateEntry::new(
+            req.user_id.clone(),
+            ciphertext_bytes,
+            req.encrypted_key_bytes,
+            req.encrypted_iv_bytes,
+        )
+        .with_tenant(&tenant)
+        .with_template_bits(req.template_bits)
+        .with_params(req.params);
+        entry.blob.cipher = req.cipher;
+        entry.blob.externalize()?;
+        entry.soft = req.soft.clone();
+        entry.threshold_bits = req.threshold_bits.clone();
+        entry.reliability_mask = req.reliability_mask.clone();
+        entry.quality_mask = req.quality_mask.clone();
+        entry.bit_weights = req.bit_weights.clone();
+        entry.capture_quality = req.capture_quality;
+        if let Some(score) = entry.capture_quality {
+            trln!("server.capture_quality", score);
+        }
+        entry.fuzzy = req.fuzzy.clone();
+        if let Some(sketch) = &entry.fuzzy {
+            trln!("server.fuzzy_enrolled", sketch.key_bits(entry.template_bits), sketch.errors, sketch.field_bits);
+        }
+        for sample in req.samples {
+            let mut blob = TemplateBlob::new(
+                bools_to_bytes(&sample.ciphertext),
+                sample.encrypted_key_bytes,
+                sample.encrypted_iv_bytes,
+            );
+            blob.cipher = req.cipher;
+            blob.externalize()?;
+            entry.samples.push(blob);
+        }
+        if !entry.samples.is_empty() {
+            trln!("server.samples_enrolled", entry.samples.len(), req.reliability_mask.as_ref().map_or(0, |m| m.iter().filter(|&&r| r).count()));
+        }
+        for rotation in req.rotations {
+            let mut blob = TemplateBlob::new(
+                bools_to_bytes(&rotation.ciphertext),
+                rotation.encrypted_key_bytes,
+                rotation.encrypted_iv_bytes,
+            );
+            blob.cipher = req.cipher;
+            blob.externalize()?;
+            entry.rotations.push(blob);
+        }
+        if !entry.rotations.is_empty() {
+            trln!("server.rotations_enrolled", entry.rotations.len());
+        }
+        for finger in req.fingers {
+            let mut blob = TemplateBlob::new(
+                bools_to_bytes(&finger.ciphertext),
+                finger.encrypted_key_bytes,
+                finger.encrypted_iv_bytes,
+            );
+            blob.cipher = req.cipher;
+            blob.externalize()?;
+            entry.fingers.push(blob);
+        }
+        entry.fusion = req.fusion;
+        if let Some(rule) = entry.fusion {
+            trln!("server.fingers_enrolled", entry.fingers.len() + 1, rule);
+        }
+        entry.transform_id = req.transform_id.clone();
+        // Keep the duress finger, fallback factors, consent and threshold across re-enrollment of the primary finger;
+        // the replaced finger goes to the history. Fingers enrolled under a revoked transform are dropped, and
+        // nothing of a revoked enrollment (a rotated server key revokes them all) is kept in the history.
+        if let Some(mut existing) = existing {
+            if existing.revoked_at.is_some() {
+                dropped.extend(entry.supersede_discarding(&mut existing));
+            } else {
+                entry.supersede(&existing);
+            }
+            if existing.transform_id != entry.transform_id {
+                let duress = existing.duress.take();
+                let second_finger = existing.factors.remove(&Factor::SecondFinger);
+                trln!("server.transform_renewed", duress.is_some() as usize + second_finger.is_some() as usize);
+                dropped.extend(duress.into_iter().chain(second_finger).map(|aux| aux.blob));
+            }
+            entry.duress = existing.duress;
+            entry.factors = existing.factors;
+            entry.consent = existing.consent;
+            entry.credential_key = existing.credential_key;
+            if entry.threshold_bits.is_none() {
+                entry.threshold_bits = existing.threshold_bits;
+            }
+        }
+        if req.consent.is_some() {
+            entry.consent = req.consent.clone();
+        }
+        entry
+    };
+    
+    // A credential is bound at the first enrollment only; it sticks afterwards
+    if first_enrollment {
+        entry.credential_key = credential;
+    }
+    
+    // 7. Insert into database
+    // ✅ SAVE BEFORE RESPONSE
+    let enrollment_count = entry.enrollment_count;
+    match store.put(entry) {
+        Ok(_) => {
+            trln!("server.template_saved");
+            trln!("server.total_templates", store.list()?.len());
+            release_dropped(dropped);
+        }
+        Err(e) => {
+            etrln!("server.db_save_failed", e);
+            // ❌ CLEANUP AND RETURN ERROR
+            let _ = fs::remove_file(req_path);
+            return Err(format!("Database save failed: {}", e).into());
+        }
+    }
+    
+    let mut operation = if req.duress { "register-duress".to_string() } else { format!("register-{}", req.factor) };
+    if replaces {
+        operation.push_str(" (replaced)");
+    }
+    audit::record(
+        AuditEvent::new("register", &req.user_id, true)
+            .with_tenant(&tenant)
+            .with_origin(&job.exchange.origin)
+            .with_detail(operation),
+    );
+    
+    // 8. Send response, with the fingerprint of the server key the enrollment will be matched under
+    let resp = RegisterResponse::success(req.user_id)
+        .with_enrollment_count(enrollment_count)
+        .with_server_key_fingerprint(keys::server_key_fingerprint(&tenant).ok());
+    job.respond("register", &resp)?;
+    
+    trln!("server.response_sent");
+    
+    // 9. Cleanup - ✅ HER DURUMDA SİL
+    let _ = fs::remove_file(req_path);
+    
+    Ok(())
+}
+
+// ==================== DELTA HANDLER ====================
+
+/// Append a region update to the primary finger (see shared/src/delta.rs)
+fn handle_delta(job: &Job) -> Result<(), Box<dyn std::error::Error>> {
+    let req: DeltaRequest = serde_json::from_slice(&job.request()?)?;
+    let result = apply_delta(&req);
+    
+    let resp = match &result {
+        Ok(_) => RegisterResponse::success(req.user_id.clone()),
+        Err(e) => RegisterResponse::error(req.user_id.clone(), e.to_string()).with_error_code(ErrorCode::of(e.as_ref())),
+    };
+    job.respond("delta", &resp)?;
+    if let Ok(tenant) = &result {
+        audit::record(
+            AuditEvent::new("register", &req.user_id, true)
+                .with_tenant(tenant)
+                .with_origin(&job.exchange.origin)
+                .with_detail(format!("delta {} bits", req.delta.bits())),
+        );
+    }
+    result.map(|_| ())
+}
+
+/// Validate and store a delta; returns the tenant it was stored under
+fn apply_delta(req: &DeltaRequest) -> Result<String, Box<dyn std::error::Error>> {
+    let tenant = tenant::resolve(req.api_key.as_deref()).map_err(FingerprintError::Unauthorized)?;
+    trln!("server.user_id", req.user_id);
+    trln!("server.tenant", tenant);
+    
+    let _db_guard = database::lock();
+    let store = database::templates()?;
+    let mut entry = store
+        .get(&tenant, &req.user_id)?
+        .ok_or_else(|| FingerprintError::NotFound(format!("User '{}' not found in database", req.user_id)))?;
+    
+    if session::authenticate(req.session.as_ref(), &req.user_id, &tenant, entry.credential_key.as_deref())
+        .map_err(FingerprintError::Unauthorized)?
+        .is_some()
+    {
+        trln!("server.session_verified");
+    }
+    
+    req.delta.validate(entry.template_bits)?;
+    if let Some(revoked_at) = &entry.revoked_at {
+        return Err(format!("Enrollment was revoked at {}; register the primary finger again", revoked_at).into());
+    }
+    // The sketch's helper data was computed from the enrolled bits
+    if entry.fuzzy.is_some() {
+        return Err("A fuzzy enrollment can't be updated with deltas; re-register the fingerprint".into());
+    }
+    // A reused keystream position would leak the XOR of old and new bits
+    if req.delta.stream_offset != entry.delta_stream_end() {
+        return Err(format!(
+            "Delta starts at keystream position {}, expected {}; re-register the fingerprint",
+            req.delta.stream_offset,
+            entry.delta_stream_end()
+        )
+        .into());
+    }
+    if entry.delta_bits() + req.delta.bits() > MAX_DELTA_BITS {
+        return Err(format!("Delta limit of {} bits reached; re-register the fingerprint", MAX_DELTA_BITS).into());
+    }
+    
+    entry.deltas.push(req.delta.clone());
+    // Rotated variants still show the finger as enrolled; matching them would undo the update
+    if !entry.rotations.is_empty() {
+        trln!("server.rotations_dropped", entry.rotations.len());
+        entry.rotations.clear();
+    }
+    entry.updated_at = chrono::Utc::now().to_rfc3339();
+    trln!("server.delta_applied", req.delta.regions.len(), req.delta.bits(), entry.delta_bits(), MAX_DELTA_BITS);
+    store.put(entry)?;
+    
+    Ok(tenant)
+}
+
+// ==================== POLICY HANDLER ====================
+
+fn handle_policy(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("policy")?;
+    let req: PolicyRequest = serde_json::from_slice(&request.data)?;
+    let tenant = tenant::resolve(req.api_key.as_deref()).map_err(FingerprintError::Unauthorized)?;
+    
+    let enrolled = database::templates()?
+        .get(&tenant, &req.user_id)?
+        .map(|e| e.enrolled_factors())
+        .unwrap_or_default();
+    
+    let policy = policy::policy_for(&policy::load_policy(), &enrolled);
+    let resp = PolicyResponse::new(req.user_id, policy, enrolled);
+    exchange.write_response("policy", &resp, request.reply_to.as_ref())?;
+    
+    Ok(())
+}
+
+// ==================== CANCEL HANDLER ====================
+
+/// Cancel a job of this slot; returns the job id and whether it was still running
+fn handle_cancel(exchange: &Exchange) -> Result<(String, bool), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("cancel")?;
+    let req: CancelRequest = serde_json::from_slice(&request.data)?;
+    let cancelled = jobs::cancel(exchange, &req.job_id);
+    let resp = CancelResponse::new(req.job_id.clone(), cancelled);
+    exchange.write_response("cancel", &resp, request.reply_to.as_ref())?;
+    Ok((req.job_id, cancelled))
+}
+
+// ==================== ADMIN HANDLER ====================
+
+fn handle_admin(exchange: &Exchange) -> Result<AdminCommand, Box<dyn std::error::Error>> {
+    let request = exchange.read_request("admin")?;
+    let req: AdminRequest = serde_json::from_slice(&request.data)?;
+    let resp = admin::answer(&req);
+    exchange.write_response("admin", &resp, request.reply_to.as_ref())?;
+    if !resp.success {
+        return Err(resp.message.into());
+    }
+    Ok(req.command)
+}
+
+// ==================== ACCOUNT HANDLER ====================
+
+fn handle_account(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("account")?;
+    let req: AccountRequest = serde_json::from_slice(&request.data)?;
+    
+    trln!("server.operation", req.operation);
+    
+    let tenant = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(t) => t,
+        Err(message) => {
+            let resp = AccountResponse::error(String::new(), message.clone()).with_error_code(ErrorCode::Unauthorized);
+            exchange.write_response("account", &resp, request.reply_to.as_ref())?;
+            return Err(message.into());
+        }
+    };
+    
+    let _db_guard = database::lock();
+    let mut db = Database::load()?;
+    let (op, from, to, result) = match &req.operation {
+        AccountOperation::Rename { from, to } => ("rename", from, to, db.rename_user(&tenant, from, to)),
+        AccountOperation::Merge { source, target } => {
+            ("merge", source, target, db.merge_users(&tenant, source, target))
+        }
+    };
+    
+    if let Err(message) = result {
+        audit::record(
+            AuditEvent::new(op, from, false)
+                .with_tenant(&tenant)
+                .with_origin(&exchange.origin)
+                .with_detail(message.clone()),
+        );
+        let resp = AccountResponse::error(from.clone(), message.clone()).with_error_code(ErrorCode::Protocol);
+        exchange.write_response("account", &resp, request.reply_to.as_ref())?;
+        return Err(message.into());
+    }
+    
+    if let Err(e) = db.save() {
+        let resp = AccountResponse::error(from.clone(), format!("Database save failed: {}", e)).with_error_code(ErrorCode::Storage);
+        exchange.write_response("account", &resp, request.reply_to.as_ref())?;
+        return Err(format!("Database save failed: {}", e).into());
+    }
+    trln!("server.templates_moved", from, to);
+    
+    // Audit history follows the templates; the saved rename stands even if it can't
+    let moved = match audit::reassign_user(&tenant, from, to) {
+        Ok(moved) => {
+            trln!("server.audit_moved", moved);
+            moved
+        }
+        Err(e) => {
+            etrln!("server.audit_move_failed", from, e);
+            0
+        }
+    };
+    audit::record(
+        AuditEvent::new(op, to, true)
+            .with_tenant(&tenant)
+            .with_origin(&exchange.origin)
+            .with_detail(format!("from {}", from)),
+    );
+    
+    let resp = AccountResponse::success(
+        to.clone(),
+        format!("{} completed ({} audit events moved)", req.operation, moved),
+    );
+    exchange.write_response("account", &resp, request.reply_to.as_ref())?;
+    trln!("server.response_sent");
+    
+    Ok(())
+}
+
+// ==================== DELETE HANDLER ====================
+
+fn handle_delete(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("delete")?;
+    let req: DeleteRequest = serde_json::from_slice(&request.data)?;
+    trln!("server.user_id", req.user_id);
+    
+    let reject = |message: String, code: ErrorCode| -> Result<(), Box<dyn std::error::Error>> {
+        let resp = DeleteResponse::error(req.user_id.clone(), message.clone()).with_error_code(code);
+        exchange.write_response("delete", &resp, request.reply_to.as_ref())?;
+        Err(message.into())
+    };
+    let tenant = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(t) => t,
+        Err(message) => return reject(message, ErrorCode::Unauthorized),
+    };
+    trln!("server.tenant", tenant);
+    
+    let _db_guard = database::lock();
+    let store = database::templates()?;
+    let entry = match store.get(&tenant, &req.user_id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return reject(format!("User '{}' not found in database", req.user_id), ErrorCode::NotFound),
+        Err(e) => return reject(format!("Database load failed: {}", e), ErrorCode::Storage),
+    };
+    let proven = match authorize_delete(&req, &tenant, &entry, ExchangeConfig::load().require_delete_proof) {
+        Ok(proven) => proven,
+        Err(message) => {
+            audit::record(
+                AuditEvent::new("delete", &req.user_id, false)
+                    .with_tenant(&tenant)
+                    .with_origin(&exchange.origin)
+                    .with_detail(format!("unauthorized: {}", message)),
+            );
+            return reject(message, ErrorCode::Unauthorized);
+        }
+    };
+    
+    match store.delete(&tenant, &req.user_id) {
+        Ok(true) => release_dropped(entry.into_blobs()),
+        Ok(false) => return reject(format!("User '{}' not found in database", req.user_id), ErrorCode::NotFound),
+        Err(e) => return reject(format!("Database save failed: {}", e), ErrorCode::Storage),
+    }
+    
+    audit::record(
+        AuditEvent::new("delete", &req.user_id, true)
+            .with_tenant(&tenant)
+            .with_origin(&exchange.origin)
+            .with_detail(proven),
+    );
+    let resp = DeleteResponse::success(req.user_id.clone(), format!("Enrollment of '{}' deleted", req.user_id));
+    exchange.write_response("delete", &resp, request.reply_to.as_ref())?;
+    trln!("server.response_sent");
+    
+    Ok(())
+}
+
+/// A delete is proven by the ownership proof of a matching verify or, unless
+/// `require_proof`, by a verified session; returns how, for the audit log
+fn authorize_delete(req: &DeleteRequest, tenant: &str, entry: &TemplateEntry, require_proof: bool) -> Result<&'static str, String> {
+    if require_proof || req.ownership_proof.is_some() {
+        ownership::redeem(tenant, &req.user_id, req.ownership_proof.as_deref())?;
+        trln!("server.ownership_proven");
+        return Ok("ownership proven");
+    }
+    session::require(req.session.as_ref(), &req.user_id, tenant, entry.credential_key.as_deref())?;
+    trln!("server.session_verified");
+    Ok("session verified")
+}
+
+// ==================== RECEIPT HANDLER ====================
+
+/// Sign a receipt (see shared/src/attestation.rs) for the verify whose
+/// ownership challenge the client answered: only a matching probe decrypts
+/// the nonce, so the receipt attests a match the client saw
+fn handle_receipt(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("receipt")?;
+    let req: ReceiptRequest = serde_json::from_slice(&request.data)?;
+    trln!("server.user_id", req.user_id);
+    
+    let reject = |message: String, code: ErrorCode| -> Result<(), Box<dyn std::error::Error>> {
+        let resp = ReceiptResponse::error(req.user_id.clone(), message.clone()).with_error_code(code);
+        exchange.write_response("receipt", &resp, request.reply_to.as_ref())?;
+        Err(message.into())
+    };
+    let tenant = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(t) => t,
+        Err(message) => return reject(message, ErrorCode::Unauthorized),
+    };
+    trln!("server.tenant", tenant);
+    
+    let claims = match receipt_claims(&req, &tenant) {
+        Ok(claims) => claims,
+        Err(message) => {
+            audit::record(
+                AuditEvent::new("receipt", &req.user_id, false)
+                    .with_tenant(&tenant)
+                    .with_origin(&exchange.origin)
+                    .with_detail(message.clone()),
+            );
+            return reject(message, ErrorCode::Unauthorized);
+        }
+    };
+    trln!("server.ownership_proven");
+    let receipt = sign_receipt(&claims, &load_or_create_attestation_key()?)?;
+    
+    audit::record(
+        AuditEvent::new("receipt", &req.user_id, true)
+            .with_tenant(&tenant)
+            .with_origin(&exchange.origin)
+            .with_detail(claims.factor.to_string()),
+    );
+    let resp = ReceiptResponse::success(req.user_id.clone(), receipt);
+    exchange.write_response("receipt", &resp, request.reply_to.as_ref())?;
+    trln!("server.response_sent");
+    
+    Ok(())
+}
+
+/// Claims of the receipt for a redeemed ownership proof
+fn receipt_claims(req: &ReceiptRequest, tenant: &str) -> Result<ReceiptClaims, String> {
+    let redeemed = ownership::redeem(tenant, &req.user_id, Some(&req.ownership_proof))?;
+    Ok(ReceiptClaims::new(SERVER_ISSUER, &req.user_id, redeemed.factor, redeemed.result_hash))
+}
+
+// ==================== REVOKE HANDLER ====================
+
+/// Revoke the cancellable transform of an enrollment (see shared/src/transform.rs);
+/// nothing matches until the primary finger is registered again
+fn handle_revoke(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("revoke")?;
+    let req: RevokeRequest = serde_json::from_slice(&request.data)?;
+    trln!("server.user_id", req.user_id);
+    let result = revoke_transform(&req);
+    
+    let resp = match &result {
+        Ok(_) => RegisterResponse::success(req.user_id.clone()),
+        Err(e) => RegisterResponse::error(req.user_id.clone(), e.to_string()).with_error_code(ErrorCode::of(e.as_ref())),
+    };
+    exchange.write_response("revoke", &resp, request.reply_to.as_ref())?;
+    if let Ok(tenant) = &result {
+        audit::record(
+            AuditEvent::new("revoke", &req.user_id, true)
+                .with_tenant(tenant)
+                .with_origin(&exchange.origin),
+        );
+    }
+    trln!("server.response_sent");
+    result.map(|_| ())
+}
+
+/// Mark the enrollment revoked; returns the tenant it is stored under
+fn revoke_transform(req: &RevokeRequest) -> Result<String, Box<dyn std::error::Error>> {
+    let tenant = tenant::resolve(req.api_key.as_deref()).map_err(FingerprintError::Unauthorized)?;
+    trln!("server.tenant", tenant);
+    
+    let _db_guard = database::lock();
+    let store = database::templates()?;
+    let mut entry = store
+        .get(&tenant, &req.user_id)?
+        .ok_or_else(|| FingerprintError::NotFound(format!("User '{}' not found in database", req.user_id)))?;
+    session::require(req.session.as_ref(), &req.user_id, &tenant, entry.credential_key.as_deref())
+        .map_err(FingerprintError::Unauthorized)?;
+    trln!("server.session_verified");
+    
+    trln!("server.transform_revoked", entry.transform_id.as_deref().unwrap_or("none"));
+    let dropped = revoke_entry(&mut entry, &chrono::Utc::now().to_rfc3339());
+    store.put(entry)?;
+    release_dropped(dropped);
+    Ok(tenant)
+}
+
+/// Revoke an enrollment's transform: the replaced primary fingers kept in the
+/// history were made under it too and are dropped; returns their templates.
+/// The primary finger stays until it is registered again.
+fn revoke_entry(entry: &mut TemplateEntry, at: &str) -> Vec<TemplateBlob> {
+    let dropped = entry.history.drain(..).map(|version| version.blob).collect();
+    entry.revoked_at = Some(at.to_string());
+    entry.updated_at = at.to_string();
+    dropped
+}
+
+/// Remove the blobs of dropped templates after the change is saved; a
+/// failure leaves them for `server compact`
+fn release_dropped(dropped: Vec<TemplateBlob>) {
+    match database::release_blobs(dropped) {
+        Ok(0) => {}
+        Ok(released) => trln!("server.blobs_released", released),
+        Err(e) => etrln!("server.blobs_release_failed", e),
+    }
+}
+
+fn handle_rotate(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("rotate")?;
+    let req: KeyRotationRequest = serde_json::from_slice(&request.data)?;
+    trln!("server.user_id", req.user_id);
+    let result = rotate_keys(&req);
+    
+    let resp = match &result {
+        Ok((tenant, invalidated)) => {
+            let mut resp = RegisterResponse::success(req.user_id.clone())
+                .with_server_key_fingerprint(keys::server_key_fingerprint(tenant).ok());
+            resp.message = format!("Server key replaced, {} enrollments invalidated", invalidated);
+            resp
+        }
+        Err(e) => RegisterResponse::error(req.user_id.clone(), e.to_string()).with_error_code(ErrorCode::of(e.as_ref())),
+    };
+    exchange.write_response("rotate", &resp, request.reply_to.as_ref())?;
+    if let Ok((tenant, invalidated)) = &result {
+        audit::record(
+            AuditEvent::new("rotate", &req.user_id, true)
+                .with_tenant(tenant)
+                .with_origin(&exchange.origin)
+                .with_detail(format!("{} enrollments invalidated", invalidated)),
+        );
+    }
+    trln!("server.response_sent");
+    result.map(|_| ())
+}
+
+/// Replace the tenant's server key and invalidate every enrollment made under
+/// the old one; returns the tenant and how many enrollments were invalidated
+fn rotate_keys(req: &KeyRotationRequest) -> Result<(String, usize), Box<dyn std::error::Error>> {
+    let tenant = tenant::resolve(req.api_key.as_deref()).map_err(FingerprintError::Unauthorized)?;
+    trln!("server.tenant", tenant);
+    if req.server_key_bytes.is_empty() {
+        return Err(FingerprintError::Protocol("Key rotation needs the new server key".into()).into());
+    }
+    
+    let _db_guard = database::lock();
+    let mut db = Database::load()?;
+    let entry = db
+        .get(&tenant, &req.user_id)
+        .ok_or_else(|| FingerprintError::NotFound(format!("User '{}' not found in database", req.user_id)))?;
+    authorize_rotation(req, &tenant, entry)?;
+    
+    // The invalidated enrollments are saved before the key is swapped; a key
+    // that can't be stored puts them back
+    let previous = db.templates.clone();
+    let invalidated = invalidate_tenant(&mut db, &tenant, &chrono::Utc::now().to_rfc3339());
+    db.save()?;
+    if let Err(e) = store_rotated_keys(&tenant, req) {
+        db.templates = previous;
+        db.save()?;
+        return Err(e.into());
+    }
+    trln!("server.keys_rotated", invalidated);
+    Ok((tenant, invalidated))
+}
+
+/// A key rotation is proven by a verified session of the user, or the admin key
+fn authorize_rotation(req: &KeyRotationRequest, tenant: &str, entry: &TemplateEntry) -> Result<(), FingerprintError> {
+    if req.admin_key.as_deref().is_some_and(tenant::is_admin_key) {
+        return Ok(());
+    }
+    session::require(req.session.as_ref(), &req.user_id, tenant, entry.credential_key.as_deref())
+        .map_err(FingerprintError::Unauthorized)?;
+    trln!("server.session_verified");
+    Ok(())
+}
+
+/// Mark every enrollment of the tenant revoked; returns how many there were
+fn invalidate_tenant(db: &mut Database, tenant: &str, at: &str) -> usize {
+    let mut invalidated = 0;
+    for entry in db.templates.values_mut().filter(|e| e.tenant() == tenant) {
+        entry.invalidate_key(at);
+        invalidated += 1;
+    }
+    invalidated
+}
+
+/// Store the rotated server key and its GPU key. The GPU key goes first, so a
+/// failure leaves the old server key; a compressed key of the new client key
+/// is dropped again if the server key can't be stored.
+fn store_rotated_keys(tenant: &str, req: &KeyRotationRequest) -> Result<(), FingerprintError> {
+    match &req.gpu_server_key_bytes {
+        Some(gpu_key_bytes) => {
+            let server_key = protocol::server_key_fingerprint(&req.server_key_bytes);
+            let saved_path = keys::store_gpu_key(tenant, gpu_key_bytes, &server_key)?;
+            trln!("server.gpu_key_saved", saved_path);
+        }
+        None => keys::remove_gpu_key(tenant)?,
+    }
+    trln!("server.saving_server_key");
+    match keys::store_server_key(tenant, &req.server_key_bytes) {
+        Ok(saved_path) => {
+            trln!("server.server_key_saved", saved_path);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = keys::remove_gpu_key(tenant);
+            Err(e)
+        }
+    }
+}
+
+/// Whether a register request replaces an enrolled template, which takes the
+/// client's confirmation. A revoked primary finger counts: its history goes too.
+fn replaces_template(existing: Option<&TemplateEntry>, req: &RegisterRequest) -> bool {
+    match existing {
+        Some(e) if req.duress => e.duress.is_some(),
+        Some(e) if req.factor != Factor::Fingerprint => e.factors.contains_key(&req.factor),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Samples come with a reliability mask, for the primary finger only, in the request's cipher and length
+fn check_samples(req: &RegisterRequest) -> Result<(), String> {
+    let Some(mask) = &req.reliability_mask else {
+        return Err("Enrollment samples need a reliability mask".to_string());
+    };
+    if req.duress || req.factor != Factor::Fingerprint {
+        return Err("Only the primary finger is enrolled from several samples".to_string());
+    }
+    consensus::check_sample_count(req.samples.len())?;
+    for sample in &req.samples {
+        template::check_ciphertext(req.template_bits, sample.ciphertext.len())?;
+    }
+    consensus::check_reliability_mask(mask, req.template_bits).map(|_| ())
+}
+
+/// Rotated variants come with the primary finger only, at most `template::MAX_ROTATIONS`
+fn check_rotations(req: &RegisterRequest) -> Result<(), String> {
+    if req.rotations.is_empty() {
+        return Ok(());
+    }
+    if req.duress || req.factor != Factor::Fingerprint {
+        return Err("Only the primary finger is enrolled with rotated variants".to_string());
+    }
+    template::check_rotation_count(req.rotations.len())?;
+    for rotation in &req.rotations {
+        template::check_ciphertext(req.template_bits, rotation.ciphertext.len())?;
+    }
+    Ok(())
+}
+
+/// Further fingers come with the primary one and a fusion rule, `fusion::MIN_FINGERS`
+/// to `fusion::MAX_FINGERS` in all. Each is matched on its own probe, so the primary
+/// finger's samples, quality mask and fuzzy sketch would not fit them.
+fn check_fingers(req: &RegisterRequest) -> Result<(), String> {
+    if req.fingers.is_empty() {
+        return match req.fusion {
+            Some(_) => Err("A fusion rule needs further fingers to fuse".to_string()),
+            None => Ok(()),
+        };
+    }
+    if req.duress || req.factor != Factor::Fingerprint {
+        return Err("Only the primary finger is enrolled with further fingers".to_string());
+    }
+    if req.fusion.is_none() {
+        return Err("Further fingers need a fusion rule".to_string());
+    }
+    if req.fuzzy.is_some() || req.reliability_mask.is_some() || req.quality_mask.is_some() {
+        return Err("Further fingers can't be combined with a fuzzy sketch, samples or a quality mask".to_string());
+    }
+    fusion::check_finger_count(req.fingers.len() + 1)?;
+    for finger in &req.fingers {
+        template::check_ciphertext(req.template_bits, finger.ciphertext.len())?;
+    }
+    Ok(())
+}
+
+/// `retention_until` is RFC 3339; maintenance skips deadlines it can't read
+fn check_consent(consent: Option<&ConsentInfo>) -> Result<(), String> {
+    match consent.and_then(|c| c.retention_until.as_deref()) {
+        Some(until) => chrono::DateTime::parse_from_rfc3339(until)
+            .map(drop)
+            .map_err(|e| format!("Retention deadline '{}' is not an RFC 3339 timestamp: {}", until, e)),
+        None => Ok(()),
+    }
+}
+
+/// A probe per further finger of a fused enrollment, whole (no coverage or quality
+/// masks of the primary probe) and in the request's cipher and length
+fn check_finger_probes(req: &VerifyRequest, enrolled_fingers: usize) -> Result<(), String> {
+    if req.fingers.len() != enrolled_fingers {
+        return Err(format!(
+            "Enrollment has {} further fingers, the request probes {}",
+            enrolled_fingers,
+            req.fingers.len()
+        ));
+    }
+    if !req.fingers.is_empty() && (req.mask.is_some() || req.quality_mask.is_some()) {
+        return Err("A fused verification takes whole probes, without coverage or quality masks".to_string());
+    }
+    for probe in &req.fingers {
+        template::check_ciphertext(req.template_bits, probe.ciphertext.len())?;
+    }
+    Ok(())
+}
+
+/// A client threshold or a partial probe would measure the distance a match-only
+/// result withholds: each answer tells whether it is below a chosen bound
+fn check_result_mode(req: &VerifyRequest, mode: ResultMode) -> Result<(), String> {
+    if mode != ResultMode::MatchOnly {
+        return Ok(());
+    }
+    if req.encrypted_threshold_bytes.is_some() {
+        return Err("Match-only results don't take a client threshold".to_string());
+    }
+    if req.mask.is_some() {
+        return Err("Match-only results don't take partial probes".to_string());
+    }
+    Ok(())
+}
+
+/// Serialized encrypted distance of a response; empty for a match-only one
+fn distance_bytes<T: serde::Serialize>(distance: &T, mode: ResultMode) -> Result<Vec<u8>, bincode::Error> {
+    match mode {
+        ResultMode::MatchOnly => Ok(Vec::new()),
+        ResultMode::MatchAndDistance => bincode::serialize(distance),
+    }
+}
+
+/// Fingers are matched under the enrollment's cancellable transform (a PIN is not
+/// transformed); a revoked enrollment takes nothing but a new primary finger
+fn check_transform(entry: &TemplateEntry, factor: Factor, transform_id: Option<&str>) -> Result<(), String> {
+    if let Some(revoked_at) = &entry.revoked_at {
+        return Err(format!("Enrollment was revoked at {}; register the primary finger again", revoked_at));
+    }
+    if factor != Factor::Pin && transform_id != entry.transform_id.as_deref() {
+        return Err(format!(
+            "Template was made under transform {}, the enrollment uses {}; it was revoked or comes from another machine",
+            transform_id.unwrap_or("none"),
+            entry.transform_id.as_deref().unwrap_or("none")
+        ));
+    }
+    Ok(())
+}
+
+/// A finger must come from the enrollment's extractor and alignment: its
+/// parameters are required once the enrollment recorded its own (a PIN is
+/// expanded to the length whatever the extractor)
+fn check_probe_params(entry: &TemplateEntry, factor: Factor, params: Option<&TemplateParams>) -> Result<(), String> {
+    match params {
+        _ if factor == Factor::Pin => Ok(()),
+        Some(params) => params.check_compatible(&entry.params()),
+        None if entry.params.is_some() => Err(format!(
+            "Template carries no extraction parameters, the enrollment was made with {}{}",
+            entry.params().extractor().description(),
+            if entry.params().align_core { " on the centred core point" } else { "" }
+        )),
+        None => Ok(()),
+    }
+}
+
+/// A fuzzy sketch comes with the primary finger alone: its error capacity replaces
+/// thresholds, weights, reliable bits and quality masks, and rotated variants
+/// would match without recovering its key
+fn check_fuzzy_sketch(req: &RegisterRequest) -> Result<(), String> {
+    let Some(sketch) = &req.fuzzy else { return Ok(()) };
+    if req.duress || req.factor != Factor::Fingerprint {
+        return Err("Only the primary finger is enrolled with a fuzzy sketch".to_string());
+    }
+    if req.threshold_bits.is_some()
+        || req.bit_weights.is_some()
+        || req.reliability_mask.is_some()
+        || req.quality_mask.is_some()
+        || !req.rotations.is_empty()
+    {
+        return Err("A fuzzy sketch can't be combined with thresholds, bit weights, samples, quality masks or rotations".to_string());
+    }
+    sketch.validate(req.template_bits)?;
+    read_fuzzy_helper(sketch, req.template_bits, &mut MemoryBudget::new(None)).map(drop).map_err(|e| e.to_string())
+}
+
+/// The encrypted helper of a fuzzy sketch, which must have exactly `template_bits` FheBools
+fn read_fuzzy_helper(sketch: &FuzzySketch, template_bits: usize, budget: &mut MemoryBudget) -> Result<Vec<FheBool>, Box<dyn std::error::Error>> {
+    let helper = blob::read_fhe_bits(sketch.encrypted_helper_bytes.as_slice(), template_bits, budget)
+        .map_err(|e| format!("Invalid encrypted fuzzy helper: {}", e))?;
+    if helper.len() != template_bits {
+        return Err(format!("Encrypted fuzzy helper has {} bits, the template {}", helper.len(), template_bits).into());
+    }
+    Ok(helper)
+}
+
+/// Quality masks come with the primary finger and have one bit (or FheBool) per template bit
+fn check_enrolled_quality_mask(mask: &QualityMask, req: &RegisterRequest) -> Result<(), String> {
+    if req.duress || req.factor != Factor::Fingerprint {
+        return Err("Only the primary finger is enrolled with a quality mask".to_string());
+    }
+    match mask {
+        QualityMask::Plain(bits) => quality::check_quality_mask(bits, req.template_bits).map(drop),
+        QualityMask::Encrypted(bytes) => read_quality_mask(bytes, req.template_bits, &mut MemoryBudget::new(None))
+            .map(drop)
+            .map_err(|e| e.to_string()),
+    }
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🖥️  FINGERPRINT AUTHENTICATION SERVER");
-    println!("{}", "=".repeat(70));
-    
-    fs::create_dir_all(EXCHANGE_DIR)?;
-    fs::create_dir_all("../database")?;
+/// An encrypted quality mask, which must have exactly `template_bits` FheBools
+fn read_quality_mask(bytes: &[u8], template_bits: usize, budget: &mut MemoryBudget) -> Result<Vec<FheBool>, Box<dyn std::error::Error>> {
+    let mask = blob::read_fhe_bits(bytes, template_bits, budget).map_err(|e| format!("Invalid encrypted quality mask: {}", e))?;
+    if mask.len() != template_bits {
+        return Err(format!("Encrypted quality mask has {} bits, the template {}", mask.len(), template_bits).into());
+    }
+    Ok(mask)
+}
 
-    println!("\n⏳ Waiting for requests...\n");
+/// Parameters sent with a template must be valid and describe its length
+fn check_template_params(params: Option<&TemplateParams>, template_bits: usize) -> Result<(), String> {
+    match params {
+        Some(params) if params.bits != template_bits => {
+            Err(format!("Template parameters describe {} bits, the template has {}", params.bits, template_bits))
+        }
+        Some(params) => params.validate(),
+        None => Ok(()),
+    }
+}
 
-    loop {
-        // Check for register request
-        if Path::new(REGISTER_REQ_PATH).exists() {
-            println!("\n📥 REGISTER REQUEST DETECTED");
-            println!("{}", "─".repeat(70));
-            
-            match handle_register() {
-                Ok(_) => println!("✅ Register completed successfully!"),
-                Err(e) => eprintln!("❌ Register failed: {}", e),
+/// Plaintext thresholds fit the template; encrypted ones are `distance_width` FheBools
+fn check_enrolled_threshold(threshold: &EnrolledThreshold, template_bits: usize) -> Result<(), String> {
+    match threshold {
+        EnrolledThreshold::Plain(bits) if *bits > template_bits => {
+            Err(format!("Threshold of {} bits exceeds the {}-bit template", bits, template_bits))
+        }
+        EnrolledThreshold::Plain(_) => Ok(()),
+        EnrolledThreshold::Encrypted(bytes) => {
+            let width = template::distance_width(template_bits);
+            let bits = blob::read_fhe_bits(bytes.as_slice(), width, &mut MemoryBudget::new(None))
+                .map_err(|e| format!("Invalid encrypted threshold: {}", e))?;
+            if bits.len() != width {
+                return Err(format!("Encrypted threshold has {} bits, the distance {}", bits.len(), width));
             }
-            
-            println!("\n⏳ Waiting for next request...\n");
+            Ok(())
         }
+    }
+}
 
-        // Check for verify request
-        if Path::new(VERIFY_REQ_PATH).exists() {
-            println!("\n📥 VERIFY REQUEST DETECTED");
-            println!("{}", "─".repeat(70));
-            
-            match handle_verify() {
-                Ok(_) => println!("✅ Verify completed successfully!"),
-                Err(e) => eprintln!("❌ Verify failed: {}", e),
-            }
-            
-            println!("\n⏳ Waiting for next request...\n");
+/// Whether a register stores the server key it sends (by fingerprint): only when the
+/// tenant has none. Another key than the stored one would leave the tenant's other
+/// enrollments under a dead key, so it is refused and left to `keys rotate`.
+fn register_key_action(tenant: &str, stored: Option<&str>, sent: Option<&str>) -> Result<bool, FingerprintError> {
+    match (stored, sent) {
+        (None, Some(_)) => Ok(true),
+        (None, None) => Err(FingerprintError::Storage("Server key not found and not provided in request!".into())),
+        (Some(stored), Some(sent)) if stored != sent => {
+            Err(FingerprintError::KeyMismatch(shared::tr!("server.server_key_differs", tenant)))
         }
+        (Some(_), _) => Ok(false),
+    }
+}
 
-        std::thread::sleep(Duration::from_millis(500));
+// ==================== VERIFY HANDLER ====================
+
+/// Key and IV must have the lengths of the cipher they were declared for
+fn check_key_iv(cipher: Cipher, key: &[FheBool], iv: &[FheBool]) -> Result<(), FingerprintError> {
+    if key.len() != cipher.key_bits() || iv.len() != cipher.iv_bits() {
+        return Err(FingerprintError::KeyMismatch(format!(
+            "{} key/IV have {}/{} bits, expected {}/{}",
+            cipher, key.len(), iv.len(), cipher.key_bits(), cipher.iv_bits()
+        )));
     }
+    Ok(())
 }
 
-// ==================== REGISTER HANDLER ====================
+/// Homomorphic decryption (the key length picks the cipher), checkpointed under `stage`
+fn decrypt_checkpointed<I, E>(
+    ciphertext: I,
+    encrypted_key: &[FheBool],
+    encrypted_iv: &[FheBool],
+    server_key: &Arc<EvaluationKey>,
+    checkpoints: &Checkpoints,
+    stage: &str,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, E>
+where
+    I: IntoIterator<Item = Result<bool, E>>,
+    E: From<Cancelled> + From<FingerprintError>,
+{
+    decrypt_homomorphic_resumable(
+        ciphertext,
+        encrypted_key,
+        encrypted_iv,
+        server_key,
+        checkpoints.load::<DecryptState>(stage),
+        checkpoints.every(),
+        |state| checkpoints.save(stage, state),
+        &ConsoleProgress,
+        cancel,
+    )
+}
 
-fn handle_register() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Read request
-    let req_json = fs::read_to_string(REGISTER_REQ_PATH)?;
-    let req: RegisterRequest = serde_json::from_str(&req_json)?;
-    
-    println!("👤 User ID: {}", req.user_id);
-    println!("📊 Ciphertext: {} bits", req.ciphertext.len());
-    
-    // 2. Load/Save server key
-    if let Some(ref server_key_bytes) = req.server_key_bytes {
-        println!("🔑 Saving server key (first registration)...");
-        fs::write(SERVER_KEY_PATH, server_key_bytes)?;
-        println!("✅ Server key saved to: {}", SERVER_KEY_PATH);
-    } else {
-        if !Path::new(SERVER_KEY_PATH).exists() {
-            // ❌ CLEANUP BEFORE ERROR
-            let _ = fs::remove_file(REGISTER_REQ_PATH);
-            return Err("Server key not found and not provided in request!".into());
-        }
-        println!("✅ Server key already exists");
+/// Inputs shared by every template match within one verify job
+struct MatchContext<'a> {
+    probe: &'a [FheBool],
+    server_key: &'a Arc<EvaluationKey>,
+    threshold: usize,
+    encrypted_threshold: Option<&'a [FheBool]>, // Client-chosen or enrolled threshold, the smaller of both
+    policy_bound: bool,         // `threshold` applies next to `encrypted_threshold` (no enrolled one replaced it)
+    template_bits: usize,
+    positions: Option<&'a [usize]>, // Template bits compared for a partial probe (None = all)
+    quality_mask: Option<&'a [FheBool]>, // Encrypted quality mask over all template bits (None = all kept)
+    quality_kept: Option<&'a [FheBool]>, // Its set bits among `positions`, LSB-first (weighted if `weights`)
+    weights: Option<&'a [u8]>,  // Bit weights of the compared bits (None = unweighted)
+    bit_size: usize,            // Estimated in-memory size of one FheBool
+    checkpoints: &'a Checkpoints,
+    cancel: &'a CancellationToken,
+    backend: MatchingBackend,   // Counting and comparison circuit (limits.json `matching_backend`)
+}
+
+fn handle_verify(job: &Job) -> Result<(), Box<dyn std::error::Error>> {
+    // tfhe's parallel operations stay within this job's share of threads
+    threads::join_job_pool();
+    // A job requeued after a restart picks up from its last checkpoints
+    let checkpoints = Checkpoints::for_job(&job.path);
+    let result = database::templates().and_then(|store| verify_job(job, store, &checkpoints));
+    if stopped_for_shutdown(&result) {
+        return result;
     }
+    checkpoints.clear();
     
-    // 3. Load database - ✅ HATA YAKALA
-    let mut db = match Database::load() {
-        Ok(db) => db,
-        Err(e) => {
-            eprintln!("❌ Database load failed: {}", e);
-            eprintln!("🔧 Creating fresh database...");
-            
-            // Backup corrupt database
-            if Path::new("../database/templates.json").exists() {
-                let backup_path = format!("../database/templates.json.backup.{}", 
-                    chrono::Utc::now().timestamp());
-                let _ = fs::rename("../database/templates.json", &backup_path);
-                println!("📦 Corrupt database backed up to: {}", backup_path);
-            }
-            
-            // Create fresh database
-            let fresh_db = Database {
-                version: "1.0".to_string(),
-                templates: std::collections::HashMap::new(),
+    // Never leave the client waiting on a job that died half-way
+    if let Err(e) = &result {
+        if !job.exchange.has_response("verify").unwrap_or(false) {
+            let resp = match e.downcast_ref::<policy::JobFailure>() {
+                Some(failure) => apply_error_policy(job, failure)?,
+                None => VerifyResponse::error(e.to_string()).with_error_code(ErrorCode::of(e.as_ref())),
             };
-            fresh_db.save()?;
-            fresh_db
+            job.respond_verify(&resp)?;
         }
-    };
-    
-    // 4. Check if user already exists
-    if db.exists(&req.user_id) {
-        println!("⚠️  User already exists, updating...");
     }
+    result
+}
+
+/// Answer a job that hit a policy-covered error the way `on_error` says, and audit it
+fn apply_error_policy(job: &Job, failure: &policy::JobFailure) -> Result<VerifyResponse, Box<dyn std::error::Error>> {
+    let error_policy = policy::load_policy().on_error;
+    let resp = policy::failure_response(failure, &error_policy)?;
+    let action = error_policy.action(failure.condition);
     
-    // 5. Vec<bool> -> Vec<u8> dönüşümü
-    let ciphertext_bytes = bools_to_bytes(&req.ciphertext);
-    
-    // 6. Create template entry
-    let entry = TemplateEntry::new(
-        req.user_id.clone(),
-        ciphertext_bytes,
-        req.encrypted_key_bytes,
-        req.encrypted_iv_bytes,
+    let (allowed, label) = match action {
+        FailureAction::Allow => (true, "fail_open"),
+        FailureAction::Reject => (false, "fail_closed"),
+        FailureAction::Fallback => (false, "fallback"),
+    };
+    etrln!("server.error_policy", failure.condition, label);
+    audit::record(
+        AuditEvent::new("verify", &failure.user_id, allowed)
+            .with_tenant(&failure.tenant)
+            .with_origin(&job.exchange.origin)
+            .with_detail(format!("{} {}: {} ({})", label, failure.factor, failure.condition, failure.message)),
     );
+    Ok(resp)
+}
+
+fn verify_job(job: &Job, store: &dyn TemplateStore, checkpoints: &Checkpoints) -> Result<(), Box<dyn std::error::Error>> {
+    let req_path = job.path.as_path();
+    let mut timer = PhaseTimer::start();
     
-    // 7. Insert into database
-    db.insert(entry);
+    // 1. Read request (plaintext is streamed, no intermediate copy of the JSON text)
+    let mut req: VerifyRequest = if job.sealed {
+        serde_json::from_slice(&job.request()?)?
+    } else {
+        serde_json::from_reader(std::io::BufReader::new(fs::File::open(req_path)?))?
+    };
+    let job_limits = limits::LimitsConfig::load();
+    let mut budget = MemoryBudget::new(job_limits.job_memory_budget());
     
-    // ✅ SAVE BEFORE RESPONSE
-    match db.save() {
-        Ok(_) => {
-            println!("💾 Template saved to database");
-            println!("📈 Total templates: {}", db.templates.len());
-        }
-        Err(e) => {
-            eprintln!("❌ Failed to save database: {}", e);
-            // ❌ CLEANUP AND RETURN ERROR
-            let _ = fs::remove_file(REGISTER_REQ_PATH);
-            return Err(format!("Database save failed: {}", e).into());
+    let tenant = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(t) => t,
+        Err(message) => {
+            let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Unauthorized);
+            job.respond_verify(&resp)?;
+            fs::remove_file(req_path)?;
+            return Err(message.into());
         }
-    }
-    
-    // 8. Send response
-    let resp = RegisterResponse::success(req.user_id);
-    let resp_json = serde_json::to_string_pretty(&resp)?;
-    fs::write(REGISTER_RESP_PATH, resp_json)?;
-    
-    println!("📤 Response sent!");
-    
-    // 9. Cleanup - ✅ HER DURUMDA SİL
-    let _ = fs::remove_file(REGISTER_REQ_PATH);
-    
-    Ok(())
-}
-
-// ==================== VERIFY HANDLER ====================
-
-fn handle_verify() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Read request
-    let req_json = fs::read_to_string(VERIFY_REQ_PATH)?;
-    let req: VerifyRequest = serde_json::from_str(&req_json)?;
+    };
+    let failures = policy::FailureContext::new(&req, &tenant, &policy::load_policy().on_error);
     
-    println!("👤 User ID: {}", req.user_id);
-    println!("📊 Probe ciphertext: {} bits", req.ciphertext.len());
+    trln!("server.user_id", req.user_id);
+    trln!("server.tenant", tenant);
+    trln!("server.factor", req.factor);
+    trln!("server.probe_ciphertext", req.ciphertext.len());
+    trln!("server.cipher", req.cipher);
     
-    // 2. Load server key
-    if !Path::new(SERVER_KEY_PATH).exists() {
-        return Err("Server key not found! Register a user first.".into());
+    // 1a. A probe made under another client key than the stored server key's would decrypt to garbage
+    if let Some(expected) = &req.server_key_fingerprint {
+        if let Err(e) = keys::check_fingerprint(&tenant, expected) {
+            let resp = VerifyResponse::error(e.to_string()).with_error_code(e.code());
+            job.respond_verify(&resp)?;
+            fs::remove_file(req_path)?;
+            return Err(e.into());
+        }
+        trln!("server.server_key_fingerprint_ok");
     }
     
-    let server_key_bytes = fs::read(SERVER_KEY_PATH)?;
-    let server_key: ServerKey = bincode::deserialize(&server_key_bytes)?;
-    set_server_key(server_key.clone());
-    
-    println!("✅ Server key loaded");
+    // 2. Load server key (memory-mapped, cached across jobs), decompressed onto a GPU if one is used
+    let server_key = keys::evaluation_key(&tenant, job_limits.compute_backend.or(config::get().fhe.compute_backend))
+        .map_err(|e| failures.fail(ErrorCondition::ServerKeyMissing, e))?;
+    keys::install(&server_key);
     
-    // 3. Load database and find enrolled template
-    let db = Database::load()?;
+    trln!("server.server_key_loaded");
+    trln!("server.compute_backend", server_key.backend());
     
-    let enrolled = match db.get(&req.user_id) {
+    // 3. Find the enrolled template
+    let enrolled = match store.get(&tenant, &req.user_id)? {
         Some(e) => e,
         None => {
-            let resp = VerifyResponse::error(format!("User '{}' not found in database", req.user_id));
-            let resp_json = serde_json::to_string_pretty(&resp)?;
-            fs::write(VERIFY_RESP_PATH, resp_json)?;
-            fs::remove_file(VERIFY_REQ_PATH)?;
+            let resp = VerifyResponse::error(format!("User '{}' not found in database", req.user_id)).with_error_code(ErrorCode::NotFound);
+            job.respond_verify(&resp)?;
+            fs::remove_file(req_path)?;
             return Err(format!("User '{}' not registered", req.user_id).into());
         }
     };
     
-    println!("✅ Enrolled template found");
-    println!("   Created: {}", enrolled.created_at);
-    
-    // 4. Deserialize FHE data
-    println!("\n🔓 Deserializing FHE data...");
+    // 3a. Session proof against the user's enrollment credential, checked at intake
+    // (see claim_job); a request without a session is checked again in case the user
+    // enrolled a credential while the job waited
+    let authenticated = match &job.session {
+        Some(claim) => Ok(Some(claim.clone())),
+        None => session::authenticate(req.session.as_ref(), &req.user_id, &tenant, enrolled.credential_key.as_deref()),
+    };
+    let verified_session = match authenticated {
+        Ok(verified) if job.session.is_some() => verified,
+        Ok(verified) => {
+            if let Some(claim) = &verified {
+                trln!("server.session_verified");
+                trln!("server.session_expires", claim.expires_at);
+            }
+            verified
+        }
+        Err(message) => {
+            audit::record(
+                AuditEvent::new("verify", &req.user_id, false)
+                    .with_tenant(&tenant)
+                    .with_origin(&job.exchange.origin)
+                    .with_detail(format!("session: {}", message)),
+            );
+            let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Unauthorized);
+            job.respond_verify(&resp)?;
+            fs::remove_file(req_path)?;
+            return Err(format!("Session rejected for '{}': {}", req.user_id, message).into());
+        }
+    };
+    let failures = failures.with_session(verified_session.clone());
     
-    let encrypted_key_enrolled: Vec<FheBool> = bincode::deserialize(&enrolled.encrypted_key_bytes)?;
-    let encrypted_iv_enrolled: Vec<FheBool> = bincode::deserialize(&enrolled.encrypted_iv_bytes)?;
-    let encrypted_key_probe: Vec<FheBool> = bincode::deserialize(&req.encrypted_key_bytes)?;
-    let encrypted_iv_probe: Vec<FheBool> = bincode::deserialize(&req.encrypted_iv_bytes)?;
-    let encrypted_true: FheBool = bincode::deserialize(&req.encrypted_true_bytes)?;
+    // 3b. Probe must have the enrolled template length, parameters and transform (and a
+    // usable coverage mask if partial); a PIN is expanded to the length whatever the extractor
+    let size_check = check_transform(&enrolled, req.factor, req.transform_id.as_deref())
+        .and_then(|_| template::check_ciphertext(req.template_bits, req.ciphertext.len()))
+        .and_then(|_| check_template_params(req.params.as_ref(), req.template_bits))
+        .and_then(|_| check_probe_params(&enrolled, req.factor, req.params.as_ref()))
+        .and_then(|_| if req.template_bits == enrolled.template_bits {
+            Ok(())
+        } else {
+            Err(format!("Probe has {} bits, enrolled template has {}", req.template_bits, enrolled.template_bits))
+        })
+        .and_then(|_| req.mask.as_deref().map(|mask| template::check_mask(mask, enrolled.template_bits)).transpose());
+    let compared_bits = match size_check {
+        Ok(compared_bits) => compared_bits,
+        Err(message) => {
+            let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+            job.respond_verify(&resp)?;
+            fs::remove_file(req_path)?;
+            return Err(message.into());
+        }
+    };
+    // A fuzzy enrollment corrects whole probes against the code's capacity: no partial
+    // probes or client thresholds, and the probe's quality mask is ignored
+    let sketch = enrolled.fuzzy.as_ref().filter(|_| req.factor == Factor::Fingerprint);
+    if sketch.is_some() && (req.mask.is_some() || req.encrypted_threshold_bytes.is_some()) {
+        let message = "A fuzzy enrollment matches whole probes, without a client threshold".to_string();
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    // The nonce of an ownership challenge is encrypted under the client's public key
+    if req.ownership_challenge && req.ownership_public_key.is_none() {
+        let message = "An ownership challenge needs the client's compact public key".to_string();
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    // Match-only results (the tenant's or server's policy, narrowed by the client) leave no
+    // distance to hill-climb on
+    let result_mode = tenant::result_mode(&tenant).narrowed_by(req.result_mode);
+    if let Err(message) = check_result_mode(&req, result_mode) {
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    // A fused enrollment takes a probe of every finger (see shared/src/fusion.rs)
+    let fused_fingers = if req.factor == Factor::Fingerprint { enrolled.fingers.as_slice() } else { &[] };
+    if let Err(message) = check_finger_probes(&req, fused_fingers.len()) {
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    let positions = req.mask.as_deref().map(template::masked_positions);
+    if let Some(compared_bits) = compared_bits {
+        trln!("server.partial_probe", compared_bits, enrolled.template_bits);
+    }
+    // A multi-sample enrollment is compared on its reliable bits only (see consensus.rs);
+    // the duress finger on the same bits, so both distances stay comparable
+    let reliable = enrolled.reliability_mask.as_deref().filter(|_| req.factor == Factor::Fingerprint);
+    let (positions, compared_bits) = match reliable {
+        Some(mask) => {
+            let positions = consensus::reliable_positions(mask, positions.as_deref());
+            trln!("server.reliable_bits", positions.len(), enrolled.template_bits);
+            let compared_bits = positions.len();
+            (Some(positions), Some(compared_bits))
+        }
+        None => (positions, compared_bits),
+    };
+    // Quality masks (see shared/src/quality.rs), the enrolled one for the primary finger only:
+    // plain ones narrow the compared bits further, encrypted ones are ANDed into the diff
+    let quality_masks: Vec<&QualityMask> = enrolled
+        .quality_mask
+        .as_ref()
+        .filter(|_| req.factor == Factor::Fingerprint)
+        .into_iter()
+        .chain(req.quality_mask.as_ref().filter(|_| sketch.is_none()))
+        .collect();
+    let before_quality = compared_bits.unwrap_or(enrolled.template_bits);
+    let encrypted_quality: Vec<&[u8]> = quality_masks
+        .iter()
+        .filter_map(|mask| match mask {
+            QualityMask::Encrypted(bytes) => Some(bytes.as_slice()),
+            QualityMask::Plain(_) => None,
+        })
+        .collect();
+    let quality_check = match &req.quality_mask {
+        // A client's encrypted threshold can't be scaled to the bits the masks keep
+        _ if !quality_masks.is_empty() && req.encrypted_threshold_bytes.is_some() => {
+            Err("Quality masks can't be combined with an encrypted threshold".to_string())
+        }
+        Some(QualityMask::Plain(mask)) => quality::check_quality_mask(mask, enrolled.template_bits).map(drop),
+        _ => Ok(()),
+    };
+    let (mut positions, mut compared_bits) = (positions, compared_bits);
+    let quality_check = quality_check.and_then(|_| {
+        for mask in &quality_masks {
+            if let QualityMask::Plain(mask) = mask {
+                let kept = consensus::reliable_positions(mask, positions.as_deref());
+                compared_bits = Some(kept.len());
+                positions = Some(kept);
+            }
+        }
+        match compared_bits.filter(|&c| c < before_quality) {
+            Some(compared) if compared * 100 < before_quality * quality::MIN_QUALITY_MASK_PERCENT => {
+                Err(format!("Quality masks leave only {} of {} bits to compare", compared, before_quality))
+            }
+            Some(compared) => {
+                trln!("server.quality_bits", compared, before_quality);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    });
+    if let Err(message) = quality_check {
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    // Enrolled bit weights (see shared/src/template.rs) apply to every finger compared, not to a PIN
+    let bit_weights = enrolled.bit_weights.as_deref().filter(|_| req.factor != Factor::Pin);
+    if bit_weights.is_some() && req.encrypted_threshold_bytes.is_some() {
+        let message = "An encrypted threshold can't be scaled to a weighted distance".to_string();
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    let compared_weights: Option<Vec<u8>> = bit_weights.map(|weights| match &positions {
+        Some(positions) => positions.iter().map(|&i| weights[i]).collect(),
+        None => weights.to_vec(),
+    });
     
-    println!("✅ FHE data deserialized:");
-    println!("   Enrolled key: {} bits", encrypted_key_enrolled.len());
-    println!("   Enrolled IV:  {} bits", encrypted_iv_enrolled.len());
-    println!("   Probe key:    {} bits", encrypted_key_probe.len());
-    println!("   Probe IV:     {} bits", encrypted_iv_probe.len());
+    // 3c. Declared soft attributes must not contradict the enrolled finger (rejects before any FHE work)
+    if let Some(attribute) = soft_conflict(&enrolled, req.factor, req.soft.as_ref()) {
+        let message = format!("Probe {} does not match the enrolled finger", attribute);
+        trln!("server.soft_mismatch", attribute);
+        audit::record(
+            AuditEvent::new("verify", &req.user_id, false)
+                .with_tenant(&tenant)
+                .with_origin(&job.exchange.origin)
+                .with_detail(format!("soft: {}", attribute)),
+        );
+        let resp = VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Protocol);
+        job.respond_verify(&resp)?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
     
-    // 5. FHE-Trivium decrypt (ENROLLED)
-    println!("\n🔐 FHE-Trivium decrypting ENROLLED fingerprint...");
-    println!("⚠️  This will take ~15-30 minutes!");
+    trln!("server.template_found");
+    trln!("server.template_created", enrolled.created_at);
     
-    // Vec<u8> -> Vec<bool> dönüşümü
-    let enrolled_ciphertext_bools = bytes_to_bools(&enrolled.ciphertext);
+    // 4. Deserialize FHE data (probe), element by element; the byte vectors are freed right after
+    trln!("server.deserializing");
+    job.progress("deserialize");
     
-    let plaintext_enrolled_fhe = decrypt_homomorphic(
-        &enrolled_ciphertext_bools,
-        &encrypted_key_enrolled,
-        &encrypted_iv_enrolled,
-        &encrypted_true,
-        &server_key,
-    );
+    let key_bytes = std::mem::take(&mut req.encrypted_key_bytes);
+    let encrypted_key_probe = blob::read_fhe_bits(key_bytes.as_slice(), req.cipher.key_bits(), &mut budget)?;
+    drop(key_bytes);
+    let iv_bytes = std::mem::take(&mut req.encrypted_iv_bytes);
+    let encrypted_iv_probe = blob::read_fhe_bits(iv_bytes.as_slice(), req.cipher.iv_bits(), &mut budget)?;
+    drop(iv_bytes);
+    check_key_iv(req.cipher, &encrypted_key_probe, &encrypted_iv_probe)?;
+    // Size of one serialized FheBool, the unit encrypted thresholds are counted in
+    let bit_size = bincode::serialized_size(&fhe_constant(true))? as usize;
+    // The server's bound is an encrypted threshold stored at enrollment, else the policy's;
+    // a client-chosen one only narrows it, so the smaller of the encrypted ones is taken
+    // under FHE and the policy's still applies next to the client's. Both are no wider
+    // than the distance. A stored one can't be scaled to a partial probe, quality masks
+    // or bit weights, which use the policy's.
+    let threshold_width = enrolled.params().distance_width();
+    let unscaled = compared_bits.is_none() && encrypted_quality.is_empty() && bit_weights.is_none();
+    let enrolled_threshold = match (&enrolled.threshold_bits, req.factor) {
+        (Some(EnrolledThreshold::Encrypted(bytes)), Factor::Fingerprint) if unscaled => Some(bytes),
+        _ => None,
+    };
+    let client_threshold = req.encrypted_threshold_bytes.take();
+    let mut applied_threshold = AppliedThreshold {
+        enrolled: enrolled_threshold.map(|bytes| sha256_hex(bytes)),
+        client: client_threshold.as_deref().map(sha256_hex),
+        ..Default::default()
+    };
+    let mut thresholds = Vec::new();
+    for bytes in client_threshold.iter().chain(enrolled_threshold) {
+        thresholds.push(blob::read_fhe_bits(bytes.as_slice(), threshold_width, &mut budget)?);
+    }
+    drop(client_threshold);
+    let encrypted_threshold = match thresholds.len() {
+        0 => None,
+        count => {
+            let threshold = min_distance(thresholds, &job.cancel)?;
+            budget.release((count - 1) * threshold_width * bit_size);
+            Some(threshold)
+        }
+    };
+    // Encrypted quality masks, ANDed into one
+    let mut quality_mask_fhe: Option<Vec<FheBool>> = None;
+    for bytes in &encrypted_quality {
+        let mask = read_quality_mask(bytes, enrolled.template_bits, &mut budget)?;
+        quality_mask_fhe = Some(match quality_mask_fhe {
+            Some(previous) => {
+                budget.release(mask.len() * bit_size);
+                previous.iter().zip(&mask).map(|(a, b)| a & b).collect()
+            }
+            None => mask,
+        });
+    }
+    timer.lap("deserialize");
+    failures.check_deadline()?;
     
-    println!("✅ Enrolled fingerprint decrypted (still encrypted!)");
+    trln!("server.deserialized");
+    trln!("server.probe_key", encrypted_key_probe.len());
+    trln!("server.probe_iv", encrypted_iv_probe.len());
+    if let Some(threshold) = &encrypted_threshold {
+        trln!("server.encrypted_threshold", threshold.len());
+    }
     
-    // 6. FHE-Trivium decrypt (PROBE)
-    println!("\n🔐 FHE-Trivium decrypting PROBE fingerprint...");
-    println!("⚠️  This will take another ~15-30 minutes!");
+    // 5. FHE-Trivium decrypt (PROBE)
+    trln!("server.decrypting_probe");
+    job.progress("decrypt_probe");
+    trln!("server.takes_long");
     
-    let plaintext_probe_fhe = decrypt_homomorphic(
-        &req.ciphertext,
+    let probe_bits = req.ciphertext.iter().map(|&b| Ok::<bool, Box<dyn std::error::Error>>(b));
+    let plaintext_probe_fhe = decrypt_checkpointed(
+        probe_bits,
         &encrypted_key_probe,
         &encrypted_iv_probe,
-        &encrypted_true,
         &server_key,
-    );
+        checkpoints,
+        "probe_decrypt",
+        &job.cancel,
+    )?;
     
-    println!("✅ Probe fingerprint decrypted (still encrypted!)");
+    budget.charge(plaintext_probe_fhe.len() * bit_size)?;
+    drop(encrypted_key_probe);
+    drop(encrypted_iv_probe);
+    budget.release((req.cipher.key_bits() + req.cipher.iv_bits()) * bit_size);
     
-    // 7. FHE Matching
-    println!("\n🧬 FHE Matching (computing Hamming distance)...");
+    timer.lap("decrypt_probe");
+    failures.check_deadline()?;
+    trln!("server.probe_decrypted");
     
-    // 7a. XOR difference
-    let diff = diff_bits(&plaintext_enrolled_fhe, &plaintext_probe_fhe);
-    println!("   ✅ Difference bits computed");
+    // 6. Match against ENROLLED template (primary finger or requested fallback factor).
+    // One "match" phase covers the duress template too, so progress doesn't reveal it.
+    job.progress("match");
+    let threshold = policy::user_threshold(req.factor, enrolled.threshold_bits.as_ref(), &enrolled.params());
+    // Bits an encrypted quality mask keeps among the compared ones
+    let quality_kept = match &quality_mask_fhe {
+        Some(mask) => {
+            let kept: Vec<FheBool> = match &positions {
+                Some(positions) => positions.iter().map(|&i| mask[i].clone()).collect(),
+                None => mask.clone(),
+            };
+            trln!("server.quality_encrypted", kept.len());
+            Some(match &compared_weights {
+                Some(weights) => weighted_popcount(&kept, weights, &job.cancel)?,
+                None => popcount_tree(&kept, &job.cancel)?,
+            })
+        }
+        None => None,
+    };
+    if enrolled_threshold.is_none() {
+        applied_threshold.policy = Some(match compared_bits {
+            Some(compared_bits) => template::partial_threshold(threshold, compared_bits, enrolled.template_bits),
+            None => threshold,
+        });
+    }
+    let ctx = MatchContext {
+        probe: &plaintext_probe_fhe,
+        server_key: &server_key,
+        threshold: match compared_bits {
+            Some(compared_bits) => template::partial_threshold(threshold, compared_bits, enrolled.template_bits),
+            None => threshold,
+        },
+        encrypted_threshold: encrypted_threshold.as_deref(),
+        policy_bound: enrolled_threshold.is_none(),
+        template_bits: enrolled.template_bits,
+        positions: positions.as_deref(),
+        quality_mask: quality_mask_fhe.as_deref(),
+        quality_kept: quality_kept.as_deref(),
+        weights: compared_weights.as_deref(),
+        bit_size,
+        checkpoints,
+        cancel: &job.cancel,
+        backend: job_limits.matching_backend.unwrap_or_default(),
+    };
+    let mut fuzzy_key_fhe = None;
+    let (match_enrolled_fhe, distance_enrolled_fhe) = if let Some(sketch) = sketch {
+        let (matched, distance, key) =
+            match_sketch_enrolled("ENROLLED", &enrolled.blob, &enrolled.deltas, sketch, &ctx, &failures, &mut budget)?;
+        fuzzy_key_fhe = Some(key);
+        (matched, distance)
+    } else if req.factor == Factor::Fingerprint {
+        match_against_enrolled(
+            "ENROLLED",
+            &enrolled.blob,
+            &enrolled.deltas,
+            &ctx,
+            &failures,
+            &mut budget,
+        )?
+    } else {
+        let aux = match enrolled.factors.get(&req.factor) {
+            Some(aux) => aux,
+            None => {
+                let resp = VerifyResponse::error(format!("Factor '{}' not enrolled", req.factor)).with_error_code(ErrorCode::Protocol);
+                job.respond_verify(&resp)?;
+                fs::remove_file(req_path)?;
+                return Err(format!("User '{}' has no '{}' factor", req.user_id, req.factor).into());
+            }
+        };
+        match_against_enrolled(
+            &req.factor.to_string().to_uppercase(),
+            &aux.blob,
+            &[],
+            &ctx,
+            &failures,
+            &mut budget,
+        )?
+    };
     
-    // 7b. Popcount (Hamming distance)
-    let distance_fhe = popcount_1024(&diff, &encrypted_true);  // ⬅️
-    println!("   ✅ Hamming distance computed (11-bit encrypted counter)");
+    timer.lap("match_enrolled");
+    failures.check_deadline()?;
     
-    // 7c. Threshold comparison (80% similarity = max 204 bits difference)
-    let threshold = (1024.0 * 0.2) as usize;  // ⬅️ 204 bits
-    let match_result_fhe = leq_constant(&distance_fhe, threshold, &encrypted_true);
-    println!("   ✅ Threshold comparison done (threshold: {} bits)", threshold);
+    // 6a. Rotated variants of the primary finger: any match counts and the smallest
+    // distance is returned, picked by an encrypted comparison tree
+    let rotations = if req.factor == Factor::Fingerprint { enrolled.rotations.as_slice() } else { &[] };
+    let (match_enrolled_fhe, distance_enrolled_fhe) = if rotations.is_empty() {
+        (match_enrolled_fhe, distance_enrolled_fhe)
+    } else {
+        let mut matched = match_enrolled_fhe;
+        let mut distances = vec![distance_enrolled_fhe];
+        // Each variant's distance is held until the comparison tree has picked the smallest
+        let mut held = 0;
+        for (i, rotation) in rotations.iter().enumerate() {
+            let label = format!("ROTATION{}", i + 1);
+            let (match_rotation_fhe, distance_rotation_fhe) =
+                match_against_enrolled(&label, rotation, &[], &ctx, &failures, &mut budget)?;
+            matched = &matched | &match_rotation_fhe;
+            budget.charge(distance_rotation_fhe.len() * bit_size)?;
+            held += distance_rotation_fhe.len() * bit_size;
+            distances.push(distance_rotation_fhe);
+            failures.check_deadline()?;
+        }
+        let distance = min_distance(distances, &job.cancel)?;
+        budget.release(held);
+        trln!("server.rotations_matched", rotations.len());
+        timer.lap("match_rotations");
+        (matched, distance)
+    };
     
-    // 8. Serialize encrypted results
-    println!("\n📦 Serializing results...");
+    // 6b. Further fingers of a fused enrollment: each probe is decrypted and matched against
+    // its finger, the fusion rule combines the decisions and the distance it decided on is
+    // returned (the smallest for any finger, the second smallest for two of them)
+    let (match_enrolled_fhe, distance_enrolled_fhe) = match enrolled.fusion.filter(|_| !fused_fingers.is_empty()) {
+        None => (match_enrolled_fhe, distance_enrolled_fhe),
+        Some(rule) => {
+            let mut matches = vec![match_enrolled_fhe];
+            let mut distances = vec![distance_enrolled_fhe];
+            let mut held = 0;
+            for (i, (finger, probe)) in fused_fingers.iter().zip(std::mem::take(&mut req.fingers)).enumerate() {
+                let label = format!("FINGER{}", i + 2);
+                let key = blob::read_fhe_bits(probe.encrypted_key_bytes.as_slice(), req.cipher.key_bits(), &mut budget)?;
+                let iv = blob::read_fhe_bits(probe.encrypted_iv_bytes.as_slice(), req.cipher.iv_bits(), &mut budget)?;
+                check_key_iv(req.cipher, &key, &iv)?;
+                let plaintext = decrypt_checkpointed(
+                    probe.ciphertext.iter().map(|&b| Ok::<bool, Box<dyn std::error::Error>>(b)),
+                    &key,
+                    &iv,
+                    &server_key,
+                    checkpoints,
+                    &format!("{}_decrypt", label.to_lowercase()),
+                    &job.cancel,
+                )?;
+                budget.charge(plaintext.len() * bit_size)?;
+                drop(key);
+                drop(iv);
+                budget.release((req.cipher.key_bits() + req.cipher.iv_bits()) * bit_size);
+                let finger_ctx = MatchContext { probe: &plaintext, ..ctx };
+                let (matched, distance) = match_against_enrolled(&label, finger, &[], &finger_ctx, &failures, &mut budget)?;
+                budget.release(plaintext.len() * bit_size);
+                budget.charge(distance.len() * bit_size)?;
+                held += distance.len() * bit_size;
+                matches.push(matched);
+                distances.push(distance);
+                failures.check_deadline()?;
+            }
+            let matched = fusion::fuse(&matches, rule).ok_or("A fused enrollment has at least two fingers")?;
+            let distance = fused_distance(distances, rule, &job.cancel)?;
+            budget.release(held);
+            trln!("server.fingers_fused", matches.len(), rule);
+            timer.lap("match_fingers");
+            (matched, distance)
+        }
+    };
+    
+    // 7. Match against DURESS template (if enrolled, fingerprint factor only)
+    // The duress flag is always returned so its presence reveals nothing.
+    let duress_template = enrolled.duress.as_ref().filter(|_| req.factor == Factor::Fingerprint);
+    let (match_result_fhe, distance_fhe, duress_fhe) = match duress_template {
+        Some(duress) => {
+            let (match_duress_fhe, distance_duress_fhe) = match_against_enrolled(
+                "DURESS",
+                &duress.blob,
+                &[],
+                &ctx,
+                &failures,
+                &mut budget,
+            )?;
+            
+            // Duress match looks like an ordinary success to the client
+            let matched = &match_enrolled_fhe | &match_duress_fhe;
+            let distance = select_bits(&match_duress_fhe, &distance_duress_fhe, &distance_enrolled_fhe);
+            // Not a phase of its own, nor added to the next one: its time would tell that a
+            // duress finger is enrolled
+            timer.skip();
+            failures.check_deadline()?;
+            (matched, distance, match_duress_fhe)
+        }
+        None => {
+            // A trivial `false` would be recognisable and reveal that no duress finger is enrolled
+            let no_duress = fhe_false_like(std::slice::from_ref(&match_enrolled_fhe));
+            (match_enrolled_fhe, distance_enrolled_fhe, no_duress)
+        }
+    };
     
+    // 7a. Ownership challenge: a nonce only a matching probe decrypts (see ownership.rs).
+    // Its proof also buys a receipt for this result, so the hash of the match bit is kept with it
     let encrypted_match_bytes = bincode::serialize(&match_result_fhe)?;
-    let encrypted_distance_bytes = bincode::serialize(&distance_fhe)?;
+    let ownership_fhe = match &req.ownership_public_key {
+        Some(bytes) if req.ownership_challenge => {
+            let public_key: CompactPublicKey = bincode::deserialize(bytes)?;
+            let nonce = ownership::issue(&tenant, &req.user_id, req.factor, sha256_hex(&encrypted_match_bytes));
+            Some(mask_with_match(&nonce, &match_result_fhe, |bit| encrypt_bit(bit, &public_key))?)
+        }
+        _ => None,
+    };
+    
+    // 8. Serialize encrypted results
+    trln!("server.serializing");
+    job.progress("serialize");
+    
+    let encrypted_distance_bytes = distance_bytes(&distance_fhe, result_mode)?;
+    let encrypted_duress_bytes = bincode::serialize(&duress_fhe)?;
+    let encrypted_ownership_bytes = ownership_fhe.as_ref().map(bincode::serialize).transpose()?;
+    let encrypted_fuzzy_key_bytes = fuzzy_key_fhe.as_ref().map(bincode::serialize).transpose()?;
+    timer.lap("serialize");
     
-    println!("✅ Results serialized:");
-    println!("   Match bytes:    {} bytes", encrypted_match_bytes.len());
-    println!("   Distance bytes: {} bytes", encrypted_distance_bytes.len());
+    trln!("server.serialized");
+    trln!("server.match_bytes", encrypted_match_bytes.len());
+    trln!("server.distance_bytes", encrypted_distance_bytes.len());
+    trln!("server.peak_memory", budget.peak() / (1024 * 1024));
     
     // 9. Create response
-    let resp = VerifyResponse::success(encrypted_match_bytes, encrypted_distance_bytes);
+    let mut resp = VerifyResponse::success(encrypted_match_bytes, encrypted_distance_bytes)
+        .with_duress(encrypted_duress_bytes)
+        .with_template_bits(enrolled.template_bits)
+        .with_compute_backend(server_key.backend());
+    if let Some(compared_bits) = compared_bits {
+        resp = resp.with_compared_bits(compared_bits);
+    }
+    if let Some(weights) = &compared_weights {
+        resp = resp.with_weighted_bits(template::compared_weight(weights, None));
+    }
+    if let Some(ownership_bytes) = encrypted_ownership_bytes {
+        resp = resp.with_ownership(ownership_bytes);
+    }
+    if let (Some(key_bytes), Some(sketch)) = (encrypted_fuzzy_key_bytes, sketch) {
+        resp = resp.with_fuzzy_key(key_bytes, sketch.key_hash.clone());
+    }
+    
+    // 9b. Sign (request, user, encrypted result, time, session) with the server identity key.
+    // A session that expired during matching gets no result: it could not be presented anyway.
+    if let Some(claim) = verified_session.as_ref().filter(|claim| claim.is_expired()) {
+        let message = format!("Session expired at {} before the result was ready", claim.expires_at);
+        audit::record(
+            AuditEvent::new("verify", &req.user_id, false)
+                .with_tenant(&tenant)
+                .with_origin(&job.exchange.origin)
+                .with_detail(format!("session: {}", message)),
+        );
+        job.respond_verify(&VerifyResponse::error(message.clone()).with_error_code(ErrorCode::Unauthorized))?;
+        fs::remove_file(req_path)?;
+        return Err(message.into());
+    }
+    let attestation = exchange::init_identity()?.sign_result(
+        req.request_id.as_deref().unwrap_or_default(),
+        &req.user_id,
+        &resp.encrypted_match_bytes,
+        &resp.encrypted_distance_bytes,
+        &resp.timestamp,
+        verified_session.as_ref(),
+        Some(applied_threshold),
+    );
+    let resp = resp.with_attestation(attestation);
     
     // 🚫 DEBUG MODE - UNCOMMENT ONLY FOR TESTING
     // WARNING: This reveals plaintext to server!
@@ -279,21 +2484,300 @@ fn handle_verify() -> Result<(), Box<dyn std::error::Error>> {
         .collect();
     let debug_distance = bits_to_usize(&debug_distance_bits);
     let resp = resp.with_debug(debug_match, debug_distance);
-    println!("🚨 DEBUG: Match = {}, Distance = {}", debug_match, debug_distance);
+    trln!("server.debug_result", debug_match, debug_distance);
     */
     
+    // The decision is encrypted: the server only records that a match was computed
+    audit::record(
+        AuditEvent::new("verify", &req.user_id, true)
+            .with_tenant(&tenant)
+            .with_origin(&job.exchange.origin)
+            .with_detail(req.factor.to_string()),
+    );
+    
     // 10. Send response
-    let resp_json = serde_json::to_string_pretty(&resp)?;
-    fs::write(VERIFY_RESP_PATH, resp_json)?;
+    job.respond_verify(&resp)?;
     
-    println!("\n📤 Response sent!");
+    trln!("server.response_sent_nl");
     
     // 11. Cleanup
-    fs::remove_file(VERIFY_REQ_PATH)?;
+    fs::remove_file(req_path)?;
+    timer.lap("respond");
+    
+    record_telemetry("verify", &timer, req.ciphertext.len(), bit_size);
     
     Ok(())
 }
 
+/// Soft attribute on which the probe contradicts the enrolled finger.
+///
+/// With a duress finger enrolled the probe must contradict it as well, so a
+/// rejection never tells the duress finger apart from the primary one.
+fn soft_conflict(enrolled: &TemplateEntry, factor: Factor, probe: Option<&SoftProfile>) -> Option<&'static str> {
+    let probe = probe?;
+    let attribute = soft::conflict(enrolled.soft_profile(factor)?, probe)?;
+    match enrolled.duress.as_ref().filter(|_| factor == Factor::Fingerprint) {
+        Some(duress) => duress.soft.as_ref().and_then(|d| soft::conflict(d, probe)).map(|_| attribute),
+        None => Some(attribute),
+    }
+}
+
+/// Append anonymous phase timings of a job if telemetry is enabled (see shared::telemetry)
+fn record_telemetry(operation: &str, timer: &PhaseTimer, template_bits: usize, bit_size: usize) {
+    let config = TelemetryConfig::load(Path::new(telemetry_config_path()));
+    if !config.enabled {
+        return;
+    }
+    let record = TelemetryRecord::new("server", operation, template_bits, timer).with_fhe_bool_bytes(bit_size);
+    telemetry::submit(&config, Path::new(telemetry_log_path()), &record);
+}
+
+/// FHE-Trivium decrypt a stored template and compare it with the decrypted probe.
+///
+/// The template is read from storage as the evaluation consumes it: key and
+/// IV first, then the ciphertext bit by bit alongside the keystream.
+/// Returns (encrypted match bit, encrypted 11-bit Hamming distance). The
+/// template's FHE data is charged to `budget` while it is alive. Decryption,
+/// popcount and the result are checkpointed under the lowercased `label`.
+fn match_against_enrolled(
+    label: &str,
+    template: &TemplateBlob,
+    deltas: &[TemplateDelta],
+    ctx: &MatchContext,
+    failures: &policy::FailureContext,
+    budget: &mut MemoryBudget,
+) -> Result<(FheBool, Vec<FheBool>), Box<dyn std::error::Error>> {
+    let stage = label.to_lowercase();
+    if let Some(result) = ctx.checkpoints.load::<(FheBool, Vec<FheBool>)>(&format!("{}_result", stage)) {
+        trln!("server.checkpoint_result", label);
+        return Ok(result);
+    }
+    let (plaintext_fhe, charged) = decrypt_enrolled(label, template, deltas, ctx, failures, budget)?;
+    
+    // FHE Matching
+    trln!("server.matching", label);
+    
+    // XOR difference (partial probes: covered regions only; encrypted quality mask: kept bits only)
+    let mut diff = match ctx.quality_mask {
+        Some(mask) => masked_diff_bits(&plaintext_fhe, ctx.probe, mask),
+        None => diff_bits(&plaintext_fhe, ctx.probe),
+    };
+    drop(plaintext_fhe);
+    if let Some(positions) = ctx.positions {
+        diff = positions.iter().map(|&i| diff[i].clone()).collect();
+    }
+    trln!("server.diff_done");
+    
+    // Popcount (Hamming distance), always as wide as a full comparison's counter, and
+    // threshold comparison (fingerprints: 80% similarity, 1024 bits = max 204 bits difference).
+    // Weighted distances sum the weights of the differing bits against a threshold scaled to
+    // their total, always on the boolean circuit.
+    let total_weight = ctx.weights.map(|weights| template::compared_weight(weights, None));
+    let width = template::result_width(ctx.template_bits, total_weight);
+    let threshold = match total_weight {
+        Some(total) => template::weighted_threshold(ctx.threshold, total, diff.len()),
+        None => ctx.threshold,
+    };
+    let (match_fhe, distance_fhe) = match ctx.backend {
+        MatchingBackend::Radix if ctx.weights.is_none() => {
+            let (policy_match, distance_fhe) =
+                match_distance(&diff, ctx.threshold, width, ctx.backend, ctx.cancel)?;
+            trln!("server.distance_done");
+            // The radix `le` only knows the policy threshold; an encrypted one, or one scaled
+            // to an encrypted quality mask, is compared bitwise
+            let match_fhe = match (ctx.encrypted_threshold, ctx.quality_kept) {
+                (Some(encrypted), _) => {
+                    let within = leq_encrypted(&distance_fhe, encrypted, ctx.cancel)?;
+                    if ctx.policy_bound { &within & &policy_match } else { within }
+                }
+                (None, Some(kept)) => leq_quality(&distance_fhe, kept, diff.len(), diff.len(), ctx)?,
+                (None, None) => policy_match,
+            };
+            (match_fhe, distance_fhe)
+        }
+        _ => {
+            let mut distance_fhe = match ctx.weights {
+                Some(weights) => weighted_popcount(&diff, weights, ctx.cancel)?,
+                None => popcount_template(&diff, ctx, &format!("{}_popcount", stage))?,
+            };
+            distance_fhe.resize(width, fhe_false_like(&diff));
+            trln!("server.distance_done");
+            let match_fhe = match (ctx.encrypted_threshold, ctx.quality_kept) {
+                (Some(encrypted), _) => {
+                    let within = leq_encrypted(&distance_fhe, encrypted, ctx.cancel)?;
+                    if ctx.policy_bound { &within & &leq_constant(&distance_fhe, threshold, ctx.cancel)? } else { within }
+                }
+                (None, Some(kept)) => leq_quality(&distance_fhe, kept, diff.len(), total_weight.unwrap_or(diff.len()), ctx)?,
+                (None, None) => leq_constant(&distance_fhe, threshold, ctx.cancel)?,
+            };
+            (match_fhe, distance_fhe)
+        }
+    };
+    match ctx.encrypted_threshold {
+        Some(_) => trln!("server.encrypted_threshold_done"),
+        None => trln!("server.threshold_done", threshold),
+    }
+    ctx.checkpoints.save(&format!("{}_result", stage), &(&match_fhe, &distance_fhe));
+    
+    budget.release(charged);
+    Ok((match_fhe, distance_fhe))
+}
+
+/// Match bit, corrected-bit distance and key bits of a fuzzy match
+type SketchMatch = (FheBool, Vec<FheBool>, Vec<FheBool>);
+
+/// Match against a fuzzy enrollment (see shared/src/fuzzy.rs): the probe corrected with the
+/// sketch must equal the enrolled codeword. Returns the match bit, the number of corrected
+/// bits as the distance and the key bits of the corrected probe.
+fn match_sketch_enrolled(
+    label: &str,
+    template: &TemplateBlob,
+    deltas: &[TemplateDelta],
+    sketch: &FuzzySketch,
+    ctx: &MatchContext,
+    failures: &policy::FailureContext,
+    budget: &mut MemoryBudget,
+) -> Result<SketchMatch, Box<dyn std::error::Error>> {
+    let stage = format!("{}_fuzzy_result", label.to_lowercase());
+    if let Some(result) = ctx.checkpoints.load::<SketchMatch>(&stage) {
+        trln!("server.checkpoint_result", label);
+        return Ok(result);
+    }
+    let (plaintext_fhe, charged) = decrypt_enrolled(label, template, deltas, ctx, failures, budget)?;
+    let used = budget.used();
+    let helper = read_fuzzy_helper(sketch, ctx.template_bits, budget)
+        .map_err(|e| failures.fail(ErrorCondition::CorruptTemplate, format!("{} fuzzy sketch: {}", label, e)))?;
+    let helper_charged = budget.used() - used;
+    
+    trln!("server.fuzzy_matching", label, ctx.template_bits.div_ceil((1 << sketch.field_bits) - 1), sketch.errors);
+    let FuzzyMatch { matched, flips, key } = fuzzy::match_sketch(&plaintext_fhe, ctx.probe, &helper, sketch, ctx.cancel)?;
+    drop(plaintext_fhe);
+    drop(helper);
+    budget.release(helper_charged);
+    let mut distance_fhe = popcount_tree(&flips, ctx.cancel)?;
+    distance_fhe.resize(template::result_width(ctx.template_bits, None), fhe_false_like(&flips));
+    trln!("server.distance_done");
+    ctx.checkpoints.save(&stage, &(&matched, &distance_fhe, &key));
+    
+    budget.release(charged);
+    Ok((matched, distance_fhe, key))
+}
+
+/// Decrypt an enrolled template (and its deltas) homomorphically; returns the plaintext
+/// FheBools and the memory charged for them, released by the caller once matched
+fn decrypt_enrolled(
+    label: &str,
+    template: &TemplateBlob,
+    deltas: &[TemplateDelta],
+    ctx: &MatchContext,
+    failures: &policy::FailureContext,
+    budget: &mut MemoryBudget,
+) -> Result<(Vec<FheBool>, usize), Box<dyn std::error::Error>> {
+    let corrupt = |e: Box<dyn std::error::Error>| failures.fail(ErrorCondition::CorruptTemplate, format!("{} template: {}", label, e));
+    let stage = label.to_lowercase();
+    let mut reader = template.open().map_err(corrupt)?;
+    let encrypted_key = reader.read_key(template.cipher.key_bits(), budget).map_err(corrupt)?;
+    let encrypted_iv = reader.read_iv(template.cipher.iv_bits(), budget).map_err(corrupt)?;
+    check_key_iv(template.cipher, &encrypted_key, &encrypted_iv).map_err(|e| corrupt(e.into()))?;
+    
+    trln!("server.decrypting_template", label);
+    trln!("server.key_iv_bits", encrypted_key.len(), encrypted_iv.len());
+    trln!("server.takes_long_again");
+    
+    let ciphertext_bits = reader.ciphertext_bits().map_err(corrupt)?;
+    let template_bits = ciphertext_bits.remaining_bits();
+    
+    // Cipher state + plaintext are alive together during decryption
+    let delta_bits: usize = deltas.iter().map(|d| d.ciphertext.len()).sum();
+    let working_set = (template_bits + delta_bits + template.cipher.state_bits()) * ctx.bit_size;
+    budget.charge(working_set)?;
+    
+    // Deltas continue the enrolled keystream, so one pass decrypts them all
+    let template_ciphertext = ciphertext_bits.map(|bit| bit.map_err(Box::<dyn std::error::Error>::from));
+    let delta_ciphertext = deltas.iter().flat_map(|d| d.ciphertext.iter().map(|&b| Ok(b)));
+    let decrypt_stage = format!("{}_decrypt", stage);
+    let mut plaintext_fhe = decrypt_checkpointed(
+        template_ciphertext.chain(delta_ciphertext),
+        &encrypted_key,
+        &encrypted_iv,
+        ctx.server_key,
+        ctx.checkpoints,
+        &decrypt_stage,
+        ctx.cancel,
+    )
+    .map_err(|e| if e.is::<Cancelled>() { e } else { corrupt(e) })?;
+    drop(encrypted_key);
+    drop(encrypted_iv);
+    
+    if !deltas.is_empty() {
+        let mut delta_plaintext = plaintext_fhe.split_off(template_bits);
+        for d in deltas {
+            let rest = delta_plaintext.split_off(d.ciphertext.len());
+            delta::splice(&mut plaintext_fhe, &d.regions, &delta_plaintext);
+            delta_plaintext = rest;
+        }
+        trln!("server.deltas_spliced", deltas.len(), delta_bits);
+    }
+    
+    if plaintext_fhe.len() != ctx.template_bits {
+        return Err(corrupt(format!("{} bits stored, expected {}", plaintext_fhe.len(), ctx.template_bits).into()));
+    }
+    trln!("server.template_decrypted", label);
+    Ok((plaintext_fhe, working_set + (template.cipher.key_bits() + template.cipher.iv_bits()) * ctx.bit_size))
+}
+
+/// Threshold comparison of a distance over the bits an encrypted quality mask keeps
+/// among `compared`, which must keep `quality::MIN_QUALITY_MASK_PERCENT` of their
+/// `total` (their weight, for a weighted distance; `kept` is weighted then too)
+fn leq_quality(
+    distance: &[FheBool],
+    kept: &[FheBool],
+    compared: usize,
+    total: usize,
+    ctx: &MatchContext,
+) -> Result<FheBool, Cancelled> {
+    let min_kept = (total * quality::MIN_QUALITY_MASK_PERCENT).div_ceil(100);
+    leq_scaled(distance, kept, compared, ctx.threshold, min_kept, ctx.cancel)
+}
+
+/// Popcount of a template-sized diff; the counter is `template::distance_width` bits.
+/// Full 512/1024-bit diffs use the ripple counter of `popcount`, checkpointed
+/// as `stage`; partial diffs use the (shorter) tree.
+fn popcount_template(diff: &[FheBool], ctx: &MatchContext, stage: &str) -> Result<Vec<FheBool>, Cancelled> {
+    if !uses_ripple_counter(diff.len()) {
+        return popcount_tree(diff, ctx.cancel);
+    }
+    let width = counter_width(diff.len());
+    let checkpoints = ctx.checkpoints;
+    let mut acc = checkpoints
+        .load::<PopcountAccumulator>(stage)
+        .unwrap_or_else(|| PopcountAccumulator::new(width));
+    for bit in diff.iter().skip(acc.counted()) {
+        ctx.cancel.check()?;
+        acc.add(bit);
+        if checkpoints.every() > 0 && acc.counted().is_multiple_of(checkpoints.every()) {
+            checkpoints.save(stage, &acc);
+        }
+    }
+    Ok(acc.finish())
+}
+
+/// Load the HMAC key used to sign verification receipts, generating it on first use.
+///
+/// Relying parties (e.g. the OIDC provider) must be given the same key.
+fn load_or_create_attestation_key() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if Path::new(attestation_key_path()).exists() {
+        return Ok(fs::read(attestation_key_path())?);
+    }
+    
+    use rand::RngCore;
+    let mut key = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    fs::write(attestation_key_path(), &key)?;
+    trln!("server.attestation_created", attestation_key_path());
+    Ok(key)
+}
+
 // Helper: Convert 8-bit binary to usize (for debug)
 #[allow(dead_code)]
 fn bits_to_usize(bits: &[bool]) -> usize {
@@ -321,12 +2805,231 @@ fn bools_to_bytes(bools: &[bool]) -> Vec<u8> {
     bytes
 }
 
-fn bytes_to_bools(bytes: &[u8]) -> Vec<bool> {
-    let mut bools = Vec::new();
-    for &byte in bytes {
-        for i in 0..8 {
-            bools.push((byte & (1 << i)) != 0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::fusion::FusionRule;
+    use shared::protocol::EncryptedSample;
+    use shared::template::Extractor;
+    use std::collections::HashMap;
+
+    fn entry(tenant: &str, user_id: &str) -> TemplateEntry {
+        TemplateEntry::new(user_id.to_string(), vec![1], vec![2], vec![3]).with_tenant(tenant)
+    }
+
+    fn rotation(user_id: &str) -> KeyRotationRequest {
+        KeyRotationRequest {
+            user_id: user_id.to_string(),
+            server_key_bytes: vec![1, 2, 3],
+            gpu_server_key_bytes: None,
+            api_key: None,
+            session: None,
+            admin_key: None,
         }
     }
-    bools
-}
\ No newline at end of file
+
+    #[test]
+    fn rotation_invalidates_only_the_tenant() {
+        let mut db = Database { version: "1.0".to_string(), templates: HashMap::new() };
+        db.insert(entry("acme", "alice"));
+        db.insert(entry("acme", "bob"));
+        db.insert(entry("globex", "alice"));
+
+        assert_eq!(invalidate_tenant(&mut db, "acme", "2026-01-01T00:00:00Z"), 2);
+        assert!(db.get("acme", "alice").unwrap().revoked_at.is_some());
+        assert!(db.get("acme", "bob").unwrap().revoked_at.is_some());
+        assert!(db.get("globex", "alice").unwrap().revoked_at.is_none());
+    }
+
+    #[test]
+    fn rotation_without_a_session_is_refused() {
+        // Also for a user enrolled before credentials, whom `authenticate` lets through
+        let legacy = entry("default", "alice");
+        let err = authorize_rotation(&rotation("alice"), "default", &legacy).unwrap_err();
+        assert!(matches!(err, FingerprintError::Unauthorized(_)));
+
+        let mut enrolled = entry("default", "alice");
+        enrolled.credential_key = Some("credential".to_string());
+        assert!(authorize_rotation(&rotation("alice"), "default", &enrolled).is_err());
+    }
+
+    #[test]
+    fn revocation_drops_the_history() {
+        let mut entry = entry("acme", "alice");
+        let previous = entry.clone();
+        entry.supersede(&previous);
+        assert_eq!(entry.history.len(), 1);
+
+        let dropped = revoke_entry(&mut entry, "2026-01-01T00:00:00Z");
+        assert_eq!(dropped.len(), 1);
+        assert!(entry.history.is_empty());
+        assert_eq!(entry.revoked_at.as_deref(), Some("2026-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn revoked_enrollment_is_replaced_only_when_confirmed() {
+        let mut revoked = entry("acme", "alice");
+        revoke_entry(&mut revoked, "2026-01-01T00:00:00Z");
+        let req = RegisterRequest::new("alice".to_string(), vec![true; 8], vec![], vec![], None);
+
+        assert!(replaces_template(Some(&revoked), &req));
+        assert!(!replaces_template(None, &req));
+    }
+
+    #[test]
+    fn revocation_without_a_session_is_refused() {
+        let err = session::require(None, "alice", "default", None).unwrap_err();
+        assert!(err.contains("verified session"));
+    }
+
+    fn deletion(user_id: &str, ownership_proof: Option<String>) -> DeleteRequest {
+        DeleteRequest { user_id: user_id.to_string(), api_key: None, ownership_proof, session: None }
+    }
+
+    #[test]
+    fn deletion_needs_a_session_or_a_proof() {
+        let enrolled = entry("acme", "carol");
+        assert!(authorize_delete(&deletion("carol", None), "acme", &enrolled, false).is_err());
+        assert!(authorize_delete(&deletion("carol", None), "acme", &enrolled, true).is_err());
+        assert!(authorize_delete(&deletion("carol", Some("00".repeat(16))), "acme", &enrolled, false).is_err());
+    }
+
+    #[test]
+    fn deletion_with_the_issued_proof_is_allowed_once() {
+        let enrolled = entry("acme", "dave");
+        let proof = shared::ownership::proof_hex(&ownership::issue("acme", "dave", Factor::Fingerprint, sha256_hex(b"match")));
+        let request = deletion("dave", Some(proof));
+        assert_eq!(authorize_delete(&request, "acme", &enrolled, true), Ok("ownership proven"));
+        assert!(authorize_delete(&request, "acme", &enrolled, true).is_err());
+    }
+
+    #[test]
+    fn receipts_need_the_proof_of_a_challenged_verify() {
+        let request = |proof: String| ReceiptRequest { user_id: "erin".to_string(), api_key: None, ownership_proof: proof };
+        assert!(receipt_claims(&request("00".repeat(16)), "acme").is_err());
+
+        let nonce = ownership::issue("acme", "erin", Factor::SecondFinger, sha256_hex(b"match"));
+        let wrong: Vec<bool> = nonce.iter().map(|bit| !bit).collect();
+        assert!(receipt_claims(&request(shared::ownership::proof_hex(&wrong)), "acme").is_err());
+
+        // A failed proof consumed the challenge; a fresh one is good for one receipt
+        let nonce = ownership::issue("acme", "erin", Factor::SecondFinger, sha256_hex(b"match"));
+        let claims = receipt_claims(&request(shared::ownership::proof_hex(&nonce)), "acme").unwrap();
+        assert_eq!((claims.sub.as_str(), claims.factor), ("erin", Factor::SecondFinger));
+        assert_eq!(claims.result_hash, sha256_hex(b"match"));
+        assert!(receipt_claims(&request(shared::ownership::proof_hex(&nonce)), "acme").is_err());
+    }
+
+    #[test]
+    fn match_only_withholds_the_distance() {
+        let policy = ResultMode::MatchOnly;
+        assert_eq!(policy.narrowed_by(ResultMode::MatchAndDistance), ResultMode::MatchOnly);
+        assert_eq!(ResultMode::MatchAndDistance.narrowed_by(ResultMode::MatchOnly), ResultMode::MatchOnly);
+
+        let distance = vec![true, false, true];
+        assert!(distance_bytes(&distance, ResultMode::MatchOnly).unwrap().is_empty());
+        assert!(!distance_bytes(&distance, ResultMode::MatchAndDistance).unwrap().is_empty());
+    }
+
+    #[test]
+    fn match_only_refuses_thresholds_and_partial_probes() {
+        let probe = VerifyRequest::new("alice".to_string(), vec![true; 8], vec![], vec![], vec![]);
+        assert!(check_result_mode(&probe, ResultMode::MatchOnly).is_ok());
+
+        let thresholded = probe.clone().with_encrypted_threshold(Some(vec![1, 2, 3]));
+        assert!(check_result_mode(&thresholded, ResultMode::MatchOnly).is_err());
+        assert!(check_result_mode(&thresholded, ResultMode::MatchAndDistance).is_ok());
+
+        let partial = probe.with_mask(Some(vec![true; 8]));
+        assert!(check_result_mode(&partial, ResultMode::MatchOnly).is_err());
+    }
+
+    #[test]
+    fn rotation_with_a_wrong_admin_key_is_refused() {
+        let request = KeyRotationRequest { admin_key: Some("fpa_bogus".to_string()), ..rotation("alice") };
+        assert!(authorize_rotation(&request, "default", &entry("default", "alice")).is_err());
+    }
+
+    #[test]
+    fn enrolled_thresholds_must_fit_the_template() {
+        assert!(check_enrolled_threshold(&EnrolledThreshold::Plain(204), 1024).is_ok());
+        assert!(check_enrolled_threshold(&EnrolledThreshold::Plain(1024), 1024).is_ok());
+        assert!(check_enrolled_threshold(&EnrolledThreshold::Plain(1025), 1024).is_err());
+        assert!(check_enrolled_threshold(&EnrolledThreshold::Encrypted(vec![0; 4]), 1024).is_err());
+        // An empty vector is well-formed bincode but holds no distance bits
+        assert!(check_enrolled_threshold(&EnrolledThreshold::Encrypted(vec![0; 8]), 1024).is_err());
+    }
+
+    #[test]
+    fn only_a_tenant_without_a_server_key_takes_one_from_a_register() {
+        assert!(register_key_action("acme", None, Some("aa")).unwrap());
+        assert!(!register_key_action("acme", Some("aa"), Some("aa")).unwrap());
+        assert!(!register_key_action("acme", Some("aa"), None).unwrap());
+        let replaced = register_key_action("acme", Some("aa"), Some("bb")).unwrap_err();
+        assert_eq!(replaced.code(), ErrorCode::KeyMismatch);
+        assert_eq!(register_key_action("acme", None, None).unwrap_err().code(), ErrorCode::Storage);
+    }
+
+    fn sample(bits: usize) -> EncryptedSample {
+        EncryptedSample { ciphertext: vec![false; bits], encrypted_key_bytes: vec![1], encrypted_iv_bytes: vec![2] }
+    }
+
+    #[test]
+    fn further_fingers_need_a_rule_and_fit_the_finger_count() {
+        let primary = || RegisterRequest::new("alice".to_string(), vec![false; 1024], vec![1], vec![2], None);
+        assert!(check_fingers(&primary()).is_ok());
+        assert!(check_fingers(&primary().with_fingers(vec![sample(1024)], FusionRule::TwoOfN)).is_ok());
+        assert!(check_fingers(&primary().with_fingers(vec![sample(1024); 3], FusionRule::AnyFinger)).is_ok());
+        assert!(check_fingers(&primary().with_fingers(vec![sample(1024); 4], FusionRule::AnyFinger)).is_err());
+        assert!(check_fingers(&primary().with_fingers(vec![sample(512)], FusionRule::AnyFinger)).is_err());
+        assert!(check_fingers(&primary().with_fingers(Vec::new(), FusionRule::AnyFinger)).is_err());
+
+        let mut unruled = primary().with_fingers(vec![sample(1024)], FusionRule::AnyFinger);
+        unruled.fusion = None;
+        assert!(check_fingers(&unruled).is_err());
+        let duress = RegisterRequest { duress: true, ..primary().with_fingers(vec![sample(1024)], FusionRule::AnyFinger) };
+        assert!(check_fingers(&duress).is_err());
+    }
+
+    #[test]
+    fn retention_deadlines_must_parse() {
+        let consent = |until: Option<&str>| ConsentInfo {
+            reference: "c-1".to_string(),
+            purpose: "access".to_string(),
+            retention_until: until.map(str::to_string),
+        };
+        assert!(check_consent(None).is_ok());
+        assert!(check_consent(Some(&consent(None))).is_ok());
+        assert!(check_consent(Some(&consent(Some("2027-01-01T00:00:00Z")))).is_ok());
+        assert!(check_consent(Some(&consent(Some("2027-01-01")))).is_err());
+        assert!(check_consent(Some(&consent(Some("next year")))).is_err());
+    }
+
+    #[test]
+    fn a_fused_verification_probes_every_finger() {
+        let probe = || VerifyRequest::new("alice".to_string(), vec![false; 1024], vec![1], vec![2], vec![3]);
+        assert!(check_finger_probes(&probe(), 0).is_ok());
+        assert!(check_finger_probes(&probe().with_fingers(vec![sample(1024); 2]), 2).is_ok());
+        assert!(check_finger_probes(&probe().with_fingers(vec![sample(1024)]), 2).is_err());
+        assert!(check_finger_probes(&probe().with_fingers(vec![sample(512); 2]), 2).is_err());
+        let masked = VerifyRequest { mask: Some(vec![true; 64]), ..probe().with_fingers(vec![sample(1024)]) };
+        assert!(check_finger_probes(&masked, 1).is_err());
+    }
+
+    #[test]
+    fn probes_must_come_from_the_enrolled_extractor() {
+        let lbp = TemplateParams::new(1024, Extractor::Lbp).unwrap();
+        let gabor = TemplateParams::new(1024, Extractor::Gabor).unwrap();
+        let enrolled = entry("acme", "alice").with_params(Some(gabor));
+        assert!(check_probe_params(&enrolled, Factor::Fingerprint, Some(&gabor)).is_ok());
+        assert!(check_probe_params(&enrolled, Factor::Fingerprint, Some(&lbp)).is_err());
+        assert!(check_probe_params(&enrolled, Factor::SecondFinger, None).is_err());
+        assert!(check_probe_params(&enrolled, Factor::Pin, None).is_ok());
+        assert!(check_probe_params(&enrolled, Factor::Fingerprint, Some(&gabor.with_core_alignment(true))).is_err());
+
+        // Enrolled before parameters were recorded: LBP, and probes need not carry them
+        let legacy = entry("acme", "alice");
+        assert!(check_probe_params(&legacy, Factor::Fingerprint, None).is_ok());
+        assert!(check_probe_params(&legacy, Factor::Fingerprint, Some(&gabor)).is_err());
+    }
+}
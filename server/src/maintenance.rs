@@ -0,0 +1,86 @@
+use crate::audit::{self, AuditEvent};
+use crate::database::{Database, TemplateEntry};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// How often the serve loop runs maintenance
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Remove enrollments whose retention deadline has passed. Returns the purged entries.
+pub fn purge_expired(db: &mut Database, now: DateTime<Utc>) -> Vec<TemplateEntry> {
+    let expired: Vec<String> = db
+        .templates
+        .iter()
+        .filter(|(_, e)| e.retention_deadline().is_some_and(|deadline| deadline <= now))
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    expired
+        .iter()
+        .filter_map(|key| db.templates.remove(key))
+        .collect()
+}
+
+/// Periodic maintenance: archive abandoned exchange files, enforce retention deadlines
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let stale = crate::stale::run()?;
+    if stale.exchange_files > 0 || stale.job_records > 0 {
+        println!(
+            "🧹 Stale cleanup: {} exchange files, {} job records archived",
+            stale.exchange_files, stale.job_records
+        );
+    }
+
+    let _db_guard = crate::database::lock();
+    let mut db = Database::load()?;
+    let store = crate::database::templates()?;
+    let mut dropped = Vec::new();
+    for entry in purge_expired(&mut db, Utc::now()) {
+        store.delete(entry.tenant(), &entry.user_id)?;
+        println!("🗑️  Retention expired, enrollment purged: {}", entry.key());
+        audit::record(
+            AuditEvent::new("purge", &entry.user_id, true)
+                .with_tenant(entry.tenant())
+                .with_detail("retention deadline reached"),
+        );
+        dropped.extend(entry.into_blobs());
+    }
+    // The purged templates' blob files or rows go with them
+    let released = crate::database::release_blobs(dropped)?;
+    if released > 0 {
+        println!("🗑️  {} blobs of purged enrollments removed", released);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::ConsentInfo;
+    use std::collections::HashMap;
+
+    fn entry(user_id: &str, retention_until: Option<&str>) -> TemplateEntry {
+        let mut entry = TemplateEntry::new(user_id.to_string(), vec![], vec![], vec![]);
+        entry.consent = Some(ConsentInfo {
+            reference: "consent-1".to_string(),
+            purpose: "login".to_string(),
+            retention_until: retention_until.map(str::to_string),
+        });
+        entry
+    }
+
+    #[test]
+    fn purges_only_expired_enrollments() {
+        let mut db = Database { version: "1.0".to_string(), templates: HashMap::new() };
+        db.insert(entry("expired", Some("2020-01-01T00:00:00Z")));
+        db.insert(entry("current", Some("2999-01-01T00:00:00Z")));
+        db.insert(entry("unbounded", None));
+        db.insert(TemplateEntry::new("no-consent".to_string(), vec![], vec![], vec![]));
+
+        let purged = purge_expired(&mut db, Utc::now());
+
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].user_id, "expired");
+        assert_eq!(db.templates.len(), 3);
+    }
+}
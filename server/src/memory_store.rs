@@ -0,0 +1,91 @@
+//! In-memory [`TemplateStore`] for tests of code written against the trait.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::database::{scoped_key, TemplateEntry, TemplateStore};
+
+#[derive(Default)]
+pub struct MemoryStore {
+    templates: Mutex<HashMap<String, TemplateEntry>>,
+}
+
+impl MemoryStore {
+    fn templates(&self) -> std::sync::MutexGuard<'_, HashMap<String, TemplateEntry>> {
+        self.templates.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TemplateStore for MemoryStore {
+    fn get(&self, tenant: &str, user_id: &str) -> Result<Option<TemplateEntry>, Box<dyn std::error::Error>> {
+        Ok(self.templates().get(&scoped_key(tenant, user_id)).cloned())
+    }
+
+    fn put(&self, entry: TemplateEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.templates().insert(entry.key(), entry);
+        Ok(())
+    }
+
+    fn delete(&self, tenant: &str, user_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.templates().remove(&scoped_key(tenant, user_id)).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self.templates().keys().cloned().collect())
+    }
+}
+
+/// What every [`TemplateStore`] must do, run against each backend from its own tests
+#[cfg(test)]
+pub mod contract {
+    use super::*;
+
+    fn entry(tenant: &str, user_id: &str) -> TemplateEntry {
+        TemplateEntry::new(user_id.to_string(), vec![1], vec![2], vec![3]).with_tenant(tenant)
+    }
+
+    /// Exercise `store` in `tenant`, which must hold no enrollments yet; leaves it empty
+    pub fn check(store: &dyn TemplateStore, tenant: &str) {
+        let other = format!("{}-other", tenant);
+        let keys = || {
+            let mut keys: Vec<String> = store.list().unwrap().into_iter().filter(|k| k.starts_with(&scoped_key(tenant, ""))).collect();
+            keys.sort();
+            keys
+        };
+        assert!(keys().is_empty());
+        assert!(store.get(tenant, "alice").unwrap().is_none());
+        assert!(!store.delete(tenant, "alice").unwrap());
+
+        // Tenants are separate namespaces
+        store.put(entry(tenant, "alice")).unwrap();
+        assert!(store.exists(tenant, "alice").unwrap());
+        assert!(!store.exists(&other, "alice").unwrap());
+        assert!(!store.delete(&other, "alice").unwrap());
+        assert_eq!(keys(), vec![scoped_key(tenant, "alice")]);
+
+        // Put replaces the entry
+        let mut updated = entry(tenant, "alice");
+        updated.credential_key = Some("key".to_string());
+        store.put(updated).unwrap();
+        assert_eq!(keys().len(), 1);
+        assert_eq!(store.get(tenant, "alice").unwrap().unwrap().credential_key.as_deref(), Some("key"));
+
+        store.put(entry(tenant, "bob")).unwrap();
+        assert_eq!(keys(), vec![scoped_key(tenant, "alice"), scoped_key(tenant, "bob")]);
+        assert!(store.delete(tenant, "alice").unwrap());
+        assert!(!store.delete(tenant, "alice").unwrap());
+        assert!(store.get(tenant, "alice").unwrap().is_none());
+        assert!(store.delete(tenant, "bob").unwrap());
+        assert!(keys().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_store_keeps_the_store_contract() {
+        contract::check(&MemoryStore::default(), "acme");
+    }
+}
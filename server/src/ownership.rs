@@ -0,0 +1,66 @@
+//! Ownership challenges issued with verify results (protocol in
+//! shared/src/ownership.rs).
+//!
+//! The nonce is kept in memory per user until a delete or receipt request
+//! presents it or it expires, and can be used once. A new challenge for the
+//! same user replaces the previous one. It remembers the factor and the hash
+//! of the encrypted match bit it was issued with, which a receipt attests.
+
+use rand::Rng;
+use shared::ownership::{proof_hex, CHALLENGE_BITS};
+use shared::protocol::Factor;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::database::scoped_key;
+
+/// How long a proof can be presented after the verify result was sent
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(300);
+
+struct Challenge {
+    proof: String,
+    issued: Instant,
+    redeemed: Redeemed,
+}
+
+/// The verify result a redeemed proof was issued with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redeemed {
+    pub factor: Factor,
+    pub result_hash: String,  // hex SHA-256 of the encrypted match bytes
+}
+
+fn pending() -> MutexGuard<'static, HashMap<String, Challenge>> {
+    static PENDING: OnceLock<Mutex<HashMap<String, Challenge>>> = OnceLock::new();
+    PENDING
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Draw the nonce for a challenged verify of this user, whose encrypted match bit hashes to `result_hash`
+pub fn issue(tenant: &str, user_id: &str, factor: Factor, result_hash: String) -> Vec<bool> {
+    let mut rng = rand::thread_rng();
+    let nonce: Vec<bool> = (0..CHALLENGE_BITS).map(|_| rng.gen()).collect();
+    let mut pending = pending();
+    pending.retain(|_, c| c.issued.elapsed() < CHALLENGE_LIFETIME);
+    let challenge = Challenge { proof: proof_hex(&nonce), issued: Instant::now(), redeemed: Redeemed { factor, result_hash } };
+    pending.insert(scoped_key(tenant, user_id), challenge);
+    nonce
+}
+
+/// Check and consume the proof for a delete or receipt request
+pub fn redeem(tenant: &str, user_id: &str, proof: Option<&str>) -> Result<Redeemed, String> {
+    let proof = proof.ok_or("Deleting an enrollment requires a proof of ownership (verify first)")?;
+    let challenge = pending()
+        .remove(&scoped_key(tenant, user_id))
+        .ok_or("No ownership challenge pending for this user")?;
+    if challenge.issued.elapsed() >= CHALLENGE_LIFETIME {
+        return Err("Ownership challenge expired; verify again".to_string());
+    }
+    if !proof.eq_ignore_ascii_case(&challenge.proof) {
+        return Err("Ownership proof invalid: the fingerprint did not match".to_string());
+    }
+    Ok(challenge.redeemed)
+}
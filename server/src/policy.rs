@@ -0,0 +1,331 @@
+use shared::{EnrolledThreshold, ErrorCode, ErrorCondition, ErrorPolicy, Factor, FailureAction, FailureNotice, FallbackPolicy, VerifyRequest, VerifyResponse};
+use shared::session::SessionClaim;
+use shared::template::{TemplateParams, TunedThreshold};
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config;
+
+pub fn policy_path() -> &'static str {
+    config::database_file!("policy.json")
+}
+
+/// Thresholds tuned on a dataset, written by the client's `tune-threshold --apply`
+pub fn thresholds_path() -> &'static str {
+    config::database_file!("thresholds.json")
+}
+
+/// Load the fallback policy, falling back to defaults if the file is missing or invalid
+pub fn load_policy() -> FallbackPolicy {
+    if !Path::new(policy_path()).exists() {
+        return FallbackPolicy::default();
+    }
+
+    match fs::read_to_string(policy_path())
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+    {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("⚠️  Invalid policy file ({}), using defaults", e);
+            FallbackPolicy::default()
+        }
+    }
+}
+
+/// Restrict the policy's fallback order to factors the user actually enrolled
+pub fn policy_for(policy: &FallbackPolicy, enrolled: &[Factor]) -> FallbackPolicy {
+    let mut policy = policy.clone();
+    policy.order.retain(|f| *f != Factor::Fingerprint && enrolled.contains(f));
+    policy
+}
+
+/// The thresholds file as last parsed, with the mtime and length it had then
+struct TunedFile {
+    modified: SystemTime,
+    len: u64,
+    tuned: Vec<TunedThreshold>,
+}
+
+static TUNED: OnceLock<Mutex<Option<TunedFile>>> = OnceLock::new();
+
+/// Threshold tuned for templates of these parameters, if one was recorded (and fits them).
+/// The file is parsed once and again only after `tune-threshold --apply` replaced it.
+pub fn tuned_threshold(params: &TemplateParams) -> Option<usize> {
+    let metadata = fs::metadata(thresholds_path()).ok()?;
+    let modified = metadata.modified().ok()?;
+    let mut cache = TUNED.get_or_init(|| Mutex::new(None)).lock().unwrap_or_else(|e| e.into_inner());
+    if !cache.as_ref().is_some_and(|file| file.modified == modified && file.len == metadata.len()) {
+        let tuned = match fs::read_to_string(thresholds_path())
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(tuned) => tuned,
+            Err(e) => {
+                eprintln!("⚠️  Invalid thresholds file ({}), using defaults", e);
+                Vec::new()
+            }
+        };
+        *cache = Some(TunedFile { modified, len: metadata.len(), tuned });
+    }
+    cache
+        .as_ref()?
+        .tuned
+        .iter()
+        .find(|t| t.params.check_compatible(params).is_ok())
+        .map(|t| t.threshold_bits)
+        .filter(|&bits| bits <= params.bits)
+}
+
+/// Threshold set in `fingerprint.toml`, if it fits templates of these parameters
+fn configured_threshold(params: &TemplateParams) -> Option<usize> {
+    config::get().matching.threshold_bits.filter(|&bits| bits <= params.bits)
+}
+
+/// Hamming threshold for a factor: PINs must match exactly, fingers at the
+/// tuned threshold if any, else the configured one
+pub fn factor_threshold(factor: Factor, params: &TemplateParams) -> usize {
+    match factor {
+        Factor::Pin => 0,
+        Factor::Fingerprint | Factor::SecondFinger => tuned_threshold(params)
+            .or_else(|| configured_threshold(params))
+            .unwrap_or_else(|| params.match_threshold()),  // 1024 bits: 204
+    }
+}
+
+/// Plaintext threshold of a verification: the user's own for the primary finger, else the factor's
+pub fn user_threshold(factor: Factor, enrolled: Option<&EnrolledThreshold>, params: &TemplateParams) -> usize {
+    match (factor, enrolled) {
+        (Factor::Fingerprint, Some(EnrolledThreshold::Plain(bits))) => *bits,
+        _ => factor_threshold(factor, params),
+    }
+}
+
+// ==================== ERROR POLICY ====================
+
+/// A verify job stopped on one of the conditions covered by `ErrorPolicy`.
+///
+/// Carries what is needed to answer the client once the policy has decided,
+/// including the client's encrypted `true` for fail-open answers.
+#[derive(Debug)]
+pub struct JobFailure {
+    pub condition: ErrorCondition,
+    pub message: String,
+    pub user_id: String,
+    pub tenant: String,
+    pub factor: Factor,
+    request_id: String,
+    encrypted_true_bytes: Vec<u8>,
+    session: Option<SessionClaim>,
+}
+
+impl std::fmt::Display for JobFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.condition, self.message)
+    }
+}
+
+impl std::error::Error for JobFailure {}
+
+impl JobFailure {
+    pub fn code(&self) -> ErrorCode {
+        match self.condition {
+            ErrorCondition::ServerKeyMissing | ErrorCondition::CorruptTemplate => ErrorCode::Storage,
+            ErrorCondition::JobTimeout => ErrorCode::Fhe,
+        }
+    }
+}
+
+/// Request details a verify job attaches to its `JobFailure`s
+pub struct FailureContext {
+    user_id: String,
+    tenant: String,
+    factor: Factor,
+    request_id: String,
+    encrypted_true_bytes: Vec<u8>,
+    session: Option<SessionClaim>,
+    started: Instant,
+    timeout: Option<Duration>,
+}
+
+impl FailureContext {
+    pub fn new(req: &VerifyRequest, tenant: &str, policy: &ErrorPolicy) -> Self {
+        Self {
+            user_id: req.user_id.clone(),
+            tenant: tenant.to_string(),
+            factor: req.factor,
+            request_id: req.request_id.clone().unwrap_or_default(),
+            encrypted_true_bytes: req.encrypted_true_bytes.clone(),
+            session: None,
+            started: Instant::now(),
+            timeout: policy.job_timeout_secs.map(Duration::from_secs),
+        }
+    }
+
+    /// Session the request was verified in; fail-open answers are signed for it
+    pub fn with_session(mut self, session: Option<SessionClaim>) -> Self {
+        self.session = session;
+        self
+    }
+
+    pub fn fail(&self, condition: ErrorCondition, error: impl std::fmt::Display) -> Box<dyn std::error::Error> {
+        Box::new(JobFailure {
+            condition,
+            message: error.to_string(),
+            user_id: self.user_id.clone(),
+            tenant: self.tenant.clone(),
+            factor: self.factor,
+            request_id: self.request_id.clone(),
+            encrypted_true_bytes: self.encrypted_true_bytes.clone(),
+            session: self.session.clone(),
+        })
+    }
+
+    /// Checked between FHE phases; a running evaluation can't be interrupted
+    pub fn check_deadline(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.timeout {
+            Some(timeout) if self.started.elapsed() > timeout => Err(self.fail(
+                ErrorCondition::JobTimeout,
+                format!("verify job exceeded {}s", timeout.as_secs()),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Response for a failed job as decided by the error policy
+pub fn failure_response(failure: &JobFailure, policy: &ErrorPolicy) -> Result<VerifyResponse, Box<dyn std::error::Error>> {
+    let action = policy.action(failure.condition);
+    let notice = FailureNotice {
+        condition: failure.condition,
+        action,
+        message: failure.message.clone(),
+    };
+    Ok(match action {
+        // The match bit is the client's own encrypted `true`, distance empty (0).
+        // No server key is needed, so this works when the key is what failed.
+        // No receipt: nothing was verified. Still signed, so pinned clients
+        // can tell it came from this server.
+        FailureAction::Allow => {
+            let resp = VerifyResponse::success(
+                failure.encrypted_true_bytes.clone(),
+                bincode::serialize(&Vec::<tfhe::FheBool>::new())?,
+            )
+            .with_failure(notice);
+            let attestation = crate::exchange::init_identity()?.sign_result(
+                &failure.request_id,
+                &failure.user_id,
+                &resp.encrypted_match_bytes,
+                &resp.encrypted_distance_bytes,
+                &resp.timestamp,
+                failure.session.as_ref(),
+                None,
+            );
+            resp.with_attestation(attestation)
+        }
+        FailureAction::Reject | FailureAction::Fallback => {
+            VerifyResponse::error(failure.to_string()).with_failure(notice).with_error_code(failure.code())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_threshold_applies_to_the_primary_finger_only() {
+        let params = TemplateParams::legacy(1024);
+        let own = EnrolledThreshold::Plain(150);
+        assert_eq!(user_threshold(Factor::Fingerprint, Some(&own), &params), 150);
+        assert_eq!(user_threshold(Factor::Pin, Some(&own), &params), 0);
+        assert_eq!(
+            user_threshold(Factor::SecondFinger, Some(&own), &params),
+            factor_threshold(Factor::SecondFinger, &params)
+        );
+        // An encrypted threshold is compared under FHE; the plaintext one is the policy's
+        let encrypted = EnrolledThreshold::Encrypted(vec![0; 4]);
+        assert_eq!(
+            user_threshold(Factor::Fingerprint, Some(&encrypted), &params),
+            factor_threshold(Factor::Fingerprint, &params)
+        );
+        assert_eq!(user_threshold(Factor::Fingerprint, None, &params), factor_threshold(Factor::Fingerprint, &params));
+    }
+
+    const CONDITIONS: [ErrorCondition; 3] =
+        [ErrorCondition::ServerKeyMissing, ErrorCondition::JobTimeout, ErrorCondition::CorruptTemplate];
+
+    fn request() -> VerifyRequest {
+        serde_json::from_value(serde_json::json!({
+            "user_id": "alice",
+            "ciphertext": [],
+            "encrypted_key_bytes": [],
+            "encrypted_iv_bytes": [],
+            "encrypted_true_bytes": [1, 2, 3],
+        }))
+        .unwrap()
+    }
+
+    fn failure(condition: ErrorCondition) -> JobFailure {
+        let context = FailureContext::new(&request(), "acme", &ErrorPolicy::default());
+        *context.fail(condition, "boom").downcast::<JobFailure>().unwrap()
+    }
+
+    #[test]
+    fn every_condition_rejects_by_default() {
+        let policy: ErrorPolicy = serde_json::from_str("{}").unwrap();
+        for condition in CONDITIONS {
+            assert_eq!(policy.action(condition), FailureAction::Reject);
+        }
+    }
+
+    #[test]
+    fn each_condition_reads_its_own_action() {
+        let policy: ErrorPolicy = serde_json::from_value(serde_json::json!({
+            "server_key_missing": "allow",
+            "job_timeout": "fallback",
+        }))
+        .unwrap();
+        assert_eq!(policy.action(ErrorCondition::ServerKeyMissing), FailureAction::Allow);
+        assert_eq!(policy.action(ErrorCondition::JobTimeout), FailureAction::Fallback);
+        assert_eq!(policy.action(ErrorCondition::CorruptTemplate), FailureAction::Reject);
+    }
+
+    #[test]
+    fn failures_carry_the_request_and_an_error_code() {
+        let corrupt = failure(ErrorCondition::CorruptTemplate);
+        assert_eq!((corrupt.user_id.as_str(), corrupt.tenant.as_str()), ("alice", "acme"));
+        assert_eq!(corrupt.to_string(), "corrupted template: boom");
+        assert_eq!(corrupt.code(), ErrorCode::Storage);
+        assert_eq!(failure(ErrorCondition::ServerKeyMissing).code(), ErrorCode::Storage);
+        assert_eq!(failure(ErrorCondition::JobTimeout).code(), ErrorCode::Fhe);
+    }
+
+    #[test]
+    fn rejecting_and_falling_back_answer_with_the_notice() {
+        for action in [FailureAction::Reject, FailureAction::Fallback] {
+            let policy = ErrorPolicy { job_timeout: action, ..ErrorPolicy::default() };
+            let resp = failure_response(&failure(ErrorCondition::JobTimeout), &policy).unwrap();
+            assert!(!resp.success);
+            assert!(resp.encrypted_match_bytes.is_empty());
+            assert_eq!(resp.error_code, Some(ErrorCode::Fhe));
+            let notice = resp.failure.expect("failure notice");
+            assert_eq!((notice.condition, notice.action), (ErrorCondition::JobTimeout, action));
+            assert_eq!(notice.message, "boom");
+        }
+    }
+
+    #[test]
+    fn the_deadline_only_applies_when_configured() {
+        let req = request();
+        assert!(FailureContext::new(&req, "acme", &ErrorPolicy::default()).check_deadline().is_ok());
+        
+        let policy = ErrorPolicy { job_timeout_secs: Some(0), ..ErrorPolicy::default() };
+        let context = FailureContext::new(&req, "acme", &policy);
+        std::thread::sleep(Duration::from_millis(5));
+        let err = context.check_deadline().unwrap_err();
+        assert_eq!(err.downcast_ref::<JobFailure>().map(|f| f.condition), Some(ErrorCondition::JobTimeout));
+    }
+}
@@ -0,0 +1,405 @@
+//! Postgres template storage (`"backend": "postgres"` in storage.json), for
+//! several servers sharing one set of enrollments.
+//!
+//! Same layout as the SQLite store: one row per enrollment with the entry as
+//! JSON, template blobs as BYTEA rows. The server lock only covers this
+//! process, so writes are optimistic: `put` only replaces the row if its
+//! `updated_at` is still what the preceding `get` of that key saw, and `save`
+//! only writes the entries changed since `load`, each guarded the same way,
+//! and only removes the ones `load` saw. Either fails if another server got
+//! there first, so the request can be retried. `templates.json` is imported
+//! once, by whichever server first claims the `json_import` marker. sqlx is
+//! async; the store drives its pool on a runtime of its own and must not be
+//! called from async code.
+
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::database::{self, db_path, scoped_key, Database, Storage, TemplateEntry, TemplateStore};
+
+const SCHEMA: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS templates (key TEXT PRIMARY KEY, entry TEXT NOT NULL, updated_at TEXT NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS blobs (name TEXT PRIMARY KEY, data BYTEA NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS store_meta (name TEXT PRIMARY KEY, value TEXT NOT NULL)",
+];
+
+const UPSERT: &str = "INSERT INTO templates (key, entry, updated_at) VALUES ($1, $2, $3)
+    ON CONFLICT (key) DO UPDATE SET entry = excluded.entry, updated_at = excluded.updated_at";
+const INSERT: &str = "INSERT INTO templates (key, entry, updated_at) VALUES ($1, $2, $3) ON CONFLICT (key) DO NOTHING";
+const UPDATE: &str = "UPDATE templates SET entry = $2, updated_at = $3 WHERE key = $1 AND updated_at = $4";
+
+/// Marker row of the one-time import of `templates.json`
+const IMPORT_MARKER: &str = "json_import";
+
+/// A row as `load` read it
+#[derive(Debug, Clone)]
+struct Loaded {
+    updated_at: String,
+    row: String,
+}
+
+/// One statement of a `save`
+#[derive(Debug, PartialEq)]
+enum Write {
+    Insert(String),             // New since the load: only if still absent
+    Update(String, String),     // Changed since the load: only if still at that `updated_at`
+    Delete(String, String),     // Removed since the load: likewise
+}
+
+pub struct PostgresStorage {
+    runtime: tokio::runtime::Runtime,
+    pool: PgPool,
+    versions: Mutex<HashMap<String, Option<String>>>,  // `updated_at` each key was last read at (None = absent)
+    loaded: Mutex<HashMap<String, Loaded>>,            // Rows the last `load` (or `save`) left in the table
+}
+
+impl PostgresStorage {
+    pub fn open(url: &str, max_connections: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).enable_all().build()?;
+        let pool = runtime.block_on(PgPoolOptions::new().max_connections(max_connections).connect(url))?;
+        let storage = Self { runtime, pool, versions: Mutex::new(HashMap::new()), loaded: Mutex::new(HashMap::new()) };
+
+        for statement in SCHEMA {
+            storage.run(sqlx::query(statement).execute(&storage.pool))?;
+        }
+        storage.import_once()?;
+        Ok(storage)
+    }
+
+    /// Import `templates.json` into an empty table, once across all servers
+    /// sharing the database: only the one that claims the marker imports
+    fn import_once(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let claimed = self.run(
+            sqlx::query("INSERT INTO store_meta (name, value) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING")
+                .bind(IMPORT_MARKER)
+                .bind(chrono::Utc::now().to_rfc3339())
+                .execute(&self.pool),
+        )?;
+        if claimed.rows_affected() == 0 {
+            return Ok(());
+        }
+        let count: i64 = self.run(sqlx::query_scalar("SELECT COUNT(*) FROM templates").fetch_one(&self.pool))?;
+        if count > 0 || !Path::new(db_path()).exists() {
+            return Ok(());
+        }
+        match database::import_json(self) {
+            Ok(imported) => {
+                println!("📥 Imported {} templates from {} into Postgres", imported, db_path());
+                Ok(())
+            }
+            Err(e) => {
+                // Let the next server to start try again
+                let _ = self.run(sqlx::query("DELETE FROM store_meta WHERE name = $1").bind(IMPORT_MARKER).execute(&self.pool));
+                Err(e)
+            }
+        }
+    }
+
+    fn run<T>(&self, query: impl Future<Output = Result<T, sqlx::Error>>) -> Result<T, Box<dyn std::error::Error>> {
+        Ok(self.runtime.block_on(query)?)
+    }
+
+    fn versions(&self) -> MutexGuard<'_, HashMap<String, Option<String>>> {
+        self.versions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn loaded(&self) -> MutexGuard<'_, HashMap<String, Loaded>> {
+        self.loaded.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// What a save of `db` writes, given the rows the last load saw: the entries
+/// changed or added since, and the removal of the ones dropped since
+fn plan(loaded: &HashMap<String, Loaded>, db: &Database) -> Vec<Write> {
+    let mut writes: Vec<Write> = db
+        .templates
+        .iter()
+        .filter_map(|(key, entry)| match loaded.get(key) {
+            Some(read) if database::row_is_current(key, &read.row, entry) => None,
+            Some(read) => Some(Write::Update(key.clone(), read.updated_at.clone())),
+            None => Some(Write::Insert(key.clone())),
+        })
+        .collect();
+    writes.extend(
+        loaded
+            .iter()
+            .filter(|(key, _)| !db.templates.contains_key(*key))
+            .map(|(key, read)| Write::Delete(key.clone(), read.updated_at.clone())),
+    );
+    writes.sort_by(|a, b| write_key(a).cmp(write_key(b)));
+    writes
+}
+
+fn write_key(write: &Write) -> &str {
+    match write {
+        Write::Insert(key) | Write::Update(key, _) | Write::Delete(key, _) => key,
+    }
+}
+
+impl TemplateStore for PostgresStorage {
+    fn get(&self, tenant: &str, user_id: &str) -> Result<Option<TemplateEntry>, Box<dyn std::error::Error>> {
+        let key = scoped_key(tenant, user_id);
+        let row: Option<(String, String)> = self.run(
+            sqlx::query_as("SELECT entry, updated_at FROM templates WHERE key = $1")
+                .bind(&key)
+                .fetch_optional(&self.pool),
+        )?;
+        self.versions().insert(key.clone(), row.as_ref().map(|(_, updated_at)| updated_at.clone()));
+        row.map(|(entry, _)| database::entry_from_row(&key, &entry)).transpose()
+    }
+
+    fn put(&self, entry: TemplateEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let key = entry.key();
+        let json = database::entry_to_row(&key, &entry)?;
+        let read = self.versions().remove(&key);
+        let query = match &read {
+            Some(Some(version)) => sqlx::query(UPDATE)
+                .bind(&key)
+                .bind(&json)
+                .bind(&entry.updated_at)
+                .bind(version),
+            Some(None) => sqlx::query(INSERT).bind(&key).bind(&json).bind(&entry.updated_at),
+            None => sqlx::query(UPSERT).bind(&key).bind(&json).bind(&entry.updated_at),
+        };
+        let written = self.run(query.execute(&self.pool))?.rows_affected();
+        if read.is_some() && written == 0 {
+            return Err(format!("Enrollment {} was changed by another server since it was read; retry", key).into());
+        }
+        Ok(())
+    }
+
+    fn delete(&self, tenant: &str, user_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let key = scoped_key(tenant, user_id);
+        self.versions().remove(&key);
+        let deleted = self.run(sqlx::query("DELETE FROM templates WHERE key = $1").bind(&key).execute(&self.pool))?;
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.run(sqlx::query_scalar("SELECT key FROM templates ORDER BY key").fetch_all(&self.pool))
+    }
+
+    fn exists(&self, tenant: &str, user_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let found: Option<i32> = self.run(
+            sqlx::query_scalar("SELECT 1 FROM templates WHERE key = $1")
+                .bind(scoped_key(tenant, user_id))
+                .fetch_optional(&self.pool),
+        )?;
+        Ok(found.is_some())
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn load(&self) -> Result<Database, Box<dyn std::error::Error>> {
+        let rows: Vec<(String, String, String)> =
+            self.run(sqlx::query_as("SELECT key, entry, updated_at FROM templates").fetch_all(&self.pool))?;
+        let mut templates = HashMap::new();
+        let mut loaded = HashMap::new();
+        for (key, row, updated_at) in rows {
+            let entry = database::entry_from_row(&key, &row)?;
+            templates.insert(key.clone(), entry);
+            loaded.insert(key, Loaded { updated_at, row });
+        }
+        *self.loaded() = loaded;
+        println!("✅ Database loaded: {} templates", templates.len());
+        Ok(Database { version: "1.0".to_string(), templates })
+    }
+
+    /// One transaction of the writes `plan` finds; any of them finding its row
+    /// changed by another server since the load rolls the whole save back
+    fn save(&self, db: &Database) -> Result<(), Box<dyn std::error::Error>> {
+        self.versions().clear();
+        let mut loaded = self.loaded().clone();
+        let writes = plan(&loaded, db);
+        let conflict = self.run(async {
+            let mut tx = self.pool.begin().await?;
+            for write in &writes {
+                let key = write_key(write);
+                let written = match write {
+                    Write::Delete(_, read) => {
+                        let deleted = sqlx::query("DELETE FROM templates WHERE key = $1 AND updated_at = $2")
+                            .bind(key)
+                            .bind(read)
+                            .execute(&mut *tx)
+                            .await?;
+                        let gone = sqlx::query_scalar::<_, i32>("SELECT 1 FROM templates WHERE key = $1")
+                            .bind(key)
+                            .fetch_optional(&mut *tx)
+                            .await?
+                            .is_none();
+                        loaded.remove(key);
+                        deleted.rows_affected() > 0 || gone
+                    }
+                    Write::Insert(_) | Write::Update(_, _) => {
+                        let entry = &db.templates[key];
+                        let json = database::entry_to_row(key, entry).map_err(|e| sqlx::Error::Encode(e.to_string().into()))?;
+                        let query = match write {
+                            Write::Update(_, read) => sqlx::query(UPDATE).bind(key).bind(&json).bind(&entry.updated_at).bind(read),
+                            _ => sqlx::query(INSERT).bind(key).bind(&json).bind(&entry.updated_at),
+                        };
+                        let written = query.execute(&mut *tx).await?.rows_affected() > 0;
+                        loaded.insert(key.to_string(), Loaded { updated_at: entry.updated_at.clone(), row: json });
+                        written
+                    }
+                };
+                if !written {
+                    return Ok(Some(key.to_string()));
+                }
+            }
+            tx.commit().await?;
+            Ok::<_, sqlx::Error>(None)
+        })?;
+        if let Some(key) = conflict {
+            return Err(format!("Enrollment {} was changed by another server since it was loaded; retry", key).into());
+        }
+        *self.loaded() = loaded;
+        Ok(())
+    }
+
+    fn put_blob(&self, name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.run(
+            sqlx::query("INSERT INTO blobs (name, data) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING")
+                .bind(name)
+                .bind(data)
+                .execute(&self.pool),
+        )?;
+        Ok(())
+    }
+
+    fn get_blob(&self, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let data: Option<Vec<u8>> = self.run(
+            sqlx::query_scalar("SELECT data FROM blobs WHERE name = $1")
+                .bind(name)
+                .fetch_optional(&self.pool),
+        )?;
+        data.ok_or_else(|| format!("No blob named {}", name).into())
+    }
+
+    fn blob_len(&self, name: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let len: Option<i32> = self.run(
+            sqlx::query_scalar("SELECT octet_length(data) FROM blobs WHERE name = $1")
+                .bind(name)
+                .fetch_optional(&self.pool),
+        )?;
+        Ok(len.ok_or_else(|| format!("No blob named {}", name))? as u64)
+    }
+
+    fn delete_blob(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.run(sqlx::query("DELETE FROM blobs WHERE name = $1").bind(name).execute(&self.pool))?;
+        Ok(())
+    }
+
+    fn blobs_in_database(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Live tests run against the database this names; without it they are skipped
+    const TEST_URL_ENV: &str = "FINGERPRINT_TEST_POSTGRES_URL";
+
+    fn entry(user_id: &str) -> TemplateEntry {
+        TemplateEntry::new(user_id.to_string(), vec![1], vec![2], vec![3]).with_tenant("pgtest")
+    }
+
+    fn loaded(entries: &[&TemplateEntry]) -> HashMap<String, Loaded> {
+        entries
+            .iter()
+            .map(|entry| {
+                let row = database::entry_to_row(&entry.key(), entry).unwrap();
+                (entry.key(), Loaded { updated_at: entry.updated_at.clone(), row })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn save_writes_only_what_changed_since_the_load() {
+        let (alice, bob, carol) = (entry("alice"), entry("bob"), entry("carol"));
+        let read = loaded(&[&alice, &bob]);
+        let mut db = Database { version: "1.0".to_string(), templates: HashMap::new() };
+        let mut changed = alice.clone();
+        changed.credential_key = Some("key".to_string());
+        db.templates.insert(changed.key(), changed);
+        db.templates.insert(carol.key(), carol.clone());
+
+        assert_eq!(
+            plan(&read, &db),
+            vec![
+                Write::Update(alice.key(), alice.updated_at.clone()),
+                Write::Delete(bob.key(), bob.updated_at.clone()),
+                Write::Insert(carol.key()),
+            ]
+        );
+        db.templates.insert(alice.key(), alice.clone());
+        db.templates.insert(bob.key(), bob.clone());
+        db.templates.remove(&carol.key());
+        assert!(plan(&read, &db).is_empty());
+    }
+
+    #[test]
+    fn concurrent_saves_do_not_overwrite_each_other() {
+        let Ok(url) = std::env::var(TEST_URL_ENV) else { return };
+        let (first, second) = (PostgresStorage::open(&url, 2).unwrap(), PostgresStorage::open(&url, 2).unwrap());
+        let (alice, bob, carol) = (entry("alice"), entry("bob"), entry("carol"));
+        for key in [alice.key(), bob.key(), carol.key()] {
+            first.run(sqlx::query("DELETE FROM templates WHERE key = $1").bind(key).execute(&first.pool)).unwrap();
+        }
+        first.put(alice.clone()).unwrap();
+
+        let mut mine = first.load().unwrap();
+        let mut theirs = second.load().unwrap();
+        let changed = theirs.templates.get_mut(&alice.key()).unwrap();
+        changed.credential_key = Some("theirs".to_string());
+        changed.updated_at = "2026-01-01T00:00:00Z".to_string();
+        theirs.templates.insert(bob.key(), bob.clone());
+        second.save(&theirs).unwrap();
+
+        // Bob, added by the other server since the load, is left alone
+        mine.templates.insert(carol.key(), carol.clone());
+        first.save(&mine).unwrap();
+        assert!(first.exists("pgtest", "bob").unwrap());
+        assert!(first.exists("pgtest", "carol").unwrap());
+
+        // A stale copy of alice is refused as a whole
+        mine.templates.get_mut(&alice.key()).unwrap().credential_key = Some("mine".to_string());
+        mine.templates.remove(&carol.key());
+        assert!(first.save(&mine).is_err());
+        assert!(first.exists("pgtest", "carol").unwrap());
+        assert_eq!(first.get("pgtest", "alice").unwrap().unwrap().credential_key.as_deref(), Some("theirs"));
+    }
+
+    #[test]
+    fn postgres_storage_keeps_the_store_contract() {
+        let Ok(url) = std::env::var(TEST_URL_ENV) else { return };
+        let storage = PostgresStorage::open(&url, 2).unwrap();
+        let stale = format!("{}%", scoped_key("pgcontract", ""));
+        storage.run(sqlx::query("DELETE FROM templates WHERE key LIKE $1").bind(stale).execute(&storage.pool)).unwrap();
+        crate::memory_store::contract::check(&storage, "pgcontract");
+    }
+
+    #[test]
+    fn json_is_imported_once() {
+        let Ok(url) = std::env::var(TEST_URL_ENV) else { return };
+        let storage = PostgresStorage::open(&url, 2).unwrap();
+        let marker: Option<String> = storage
+            .run(sqlx::query_scalar("SELECT value FROM store_meta WHERE name = $1").bind(IMPORT_MARKER).fetch_optional(&storage.pool))
+            .unwrap();
+        assert!(marker.is_some());
+        // A second server finds the marker and doesn't import again
+        let claimed = storage
+            .run(
+                sqlx::query("INSERT INTO store_meta (name, value) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING")
+                    .bind(IMPORT_MARKER)
+                    .bind("later")
+                    .execute(&storage.pool),
+            )
+            .unwrap();
+        assert_eq!(claimed.rows_affected(), 0);
+    }
+}
@@ -0,0 +1,188 @@
+//! Access rules of the remote front ends (`server http`, `server grpc`).
+//!
+//! A client on another machine reaches only the files it needs (see
+//! `ExchangeFile` in shared/src/transport.rs): the handshake and server
+//! status at the root, and in its own slot the requests it submits and the
+//! responses, job ticket and job status it polls for. Requests can't be read
+//! back, responses can't be written and nothing can be listed.
+//!
+//! A slot is served only to the user it belongs to: the API key the caller
+//! sends must resolve to a tenant, and `user_slot(api_key, user_id)` must
+//! name the slot. Uploads are capped at the size of their request kind.
+
+use shared::transport::{Caller, DirTransport, ExchangeFile, Transport};
+use std::fmt;
+
+use crate::tenant::TenantRegistry;
+
+/// Largest upload: a registration carries the FHE server key
+pub const MAX_REQUEST_BYTES: usize = 1024 * 1024 * 1024;
+
+/// What a remote client wants to do with a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,                       // Content, modification time
+    Stat,                       // Modification time only
+    Submit,
+    Remove,
+}
+
+/// Why a remote access was refused
+#[derive(Debug, PartialEq)]
+pub enum Refusal {
+    Invalid(String),            // Not an exchange file a client may name
+    Unauthenticated(String),    // Unknown or missing credentials
+    Forbidden(String),          // Someone else's slot, or an access the file doesn't allow
+    TooLarge(String),
+    Unavailable(String),        // Tenant registry unreadable
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Refusal::Invalid(message)
+            | Refusal::Unauthenticated(message)
+            | Refusal::Forbidden(message)
+            | Refusal::TooLarge(message)
+            | Refusal::Unavailable(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Largest request of a kind a client may submit
+pub fn request_limit(kind: &str) -> usize {
+    match kind {
+        "register" | "rotate" => MAX_REQUEST_BYTES,     // FHE server key
+        "verify" | "delta" => 256 * 1024 * 1024,        // Encrypted key/IV, threshold and mask
+        _ => 1024 * 1024,
+    }
+}
+
+/// Whether `access` to `file` is allowed in the root area (`slot: None`) or a slot
+pub fn permit(slot: Option<&str>, file: &ExchangeFile, access: Access) -> Result<(), Refusal> {
+    use ExchangeFile::*;
+    let allowed = match (slot, file) {
+        (None, Handshake | Status) => matches!(access, Access::Read | Access::Stat),
+        (Some(_), Status) => matches!(access, Access::Read | Access::Stat),
+        (Some(_), Request(_)) => matches!(access, Access::Submit | Access::Stat),
+        (Some(_), Response(_) | Ticket | JobStatus(_)) => matches!(access, Access::Read | Access::Stat | Access::Remove),
+        _ => false,
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(Refusal::Forbidden(format!("{:?} of {} not allowed here", access, file.name())))
+    }
+}
+
+/// Check the caller owns `slot`: its API key resolves to a tenant and its
+/// user id and key derive the slot
+pub fn authorize(registry: &TenantRegistry, caller: Option<&Caller>, slot: &str) -> Result<(), Refusal> {
+    let caller = caller.ok_or_else(|| Refusal::Unauthenticated("No user named".to_string()))?;
+    registry.resolve(caller.api_key.as_deref()).map_err(Refusal::Unauthenticated)?;
+    if caller.slot() != slot {
+        return Err(Refusal::Forbidden(format!("Slot {} isn't {}'s", slot, caller.user_id)));
+    }
+    Ok(())
+}
+
+/// Area and file of a front end path (`<route>` or `users/<slot>/<route>`)
+pub fn locate(path: &str) -> Result<(Option<String>, ExchangeFile), Refusal> {
+    let path = path.trim_start_matches('/');
+    let (slot, route) = match path.strip_prefix("users/") {
+        Some(rest) => match rest.split_once('/') {
+            Some((slot, route)) => (Some(slot.to_string()), route),
+            None => return Err(Refusal::Invalid(format!("No file in {}", path))),
+        },
+        None => (None, path),
+    };
+    let file = ExchangeFile::from_route(route).ok_or_else(|| Refusal::Invalid(format!("No such file: {}", route)))?;
+    Ok((slot, file))
+}
+
+/// Check a remote access and open the area it is for
+pub fn open(
+    root: &DirTransport,
+    caller: Option<&Caller>,
+    slot: Option<&str>,
+    file: &ExchangeFile,
+    access: Access,
+) -> Result<Box<dyn Transport>, Refusal> {
+    permit(slot, file, access)?;
+    match slot {
+        None => Ok(Box::new(DirTransport::new(root.dir()))),
+        Some(slot) => {
+            let registry = TenantRegistry::load().map_err(|e| Refusal::Unavailable(format!("Tenant registry unreadable: {}", e)))?;
+            authorize(&registry, caller, slot)?;
+            Ok(root.scoped(slot))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(api_key: Option<&str>, user_id: &str) -> Caller {
+        Caller { api_key: api_key.map(str::to_string), user_id: user_id.to_string() }
+    }
+
+    #[test]
+    fn clients_submit_requests_and_collect_responses_only() {
+        let slot = Some("u-0123456789abcdef01234567");
+        let request = ExchangeFile::Request("verify".to_string());
+        let response = ExchangeFile::Response("verify".to_string());
+
+        assert!(permit(slot, &request, Access::Submit).is_ok());
+        assert!(permit(slot, &request, Access::Read).is_err());
+        assert!(permit(slot, &request, Access::Remove).is_err());
+        assert!(permit(slot, &response, Access::Read).is_ok());
+        assert!(permit(slot, &response, Access::Remove).is_ok());
+        assert!(permit(slot, &response, Access::Submit).is_err());
+        assert!(permit(slot, &ExchangeFile::Ticket, Access::Submit).is_err());
+        assert!(permit(slot, &ExchangeFile::Handshake, Access::Read).is_err());
+
+        assert!(permit(None, &ExchangeFile::Handshake, Access::Read).is_ok());
+        assert!(permit(None, &ExchangeFile::Handshake, Access::Remove).is_err());
+        assert!(permit(None, &ExchangeFile::Status, Access::Submit).is_err());
+        assert!(permit(None, &request, Access::Submit).is_err());
+    }
+
+    #[test]
+    fn slots_are_served_to_their_owner_only() {
+        let mut registry = TenantRegistry::default();
+        let key = registry.issue_key("acme");
+        let alice = caller(Some(&key), "alice");
+
+        assert_eq!(authorize(&registry, Some(&alice), &alice.slot()), Ok(()));
+        assert!(matches!(authorize(&registry, None, &alice.slot()), Err(Refusal::Unauthenticated(_))));
+        let bob = caller(Some(&key), "bob");
+        assert!(matches!(authorize(&registry, Some(&bob), &alice.slot()), Err(Refusal::Forbidden(_))));
+        let forged = caller(Some("fpk_bogus"), "alice");
+        assert!(matches!(authorize(&registry, Some(&forged), &forged.slot()), Err(Refusal::Unauthenticated(_))));
+
+        let anonymous = caller(None, "alice");
+        assert_eq!(authorize(&registry, Some(&anonymous), &anonymous.slot()), Ok(()));
+        registry.require_api_key = true;
+        assert!(matches!(authorize(&registry, Some(&anonymous), &anonymous.slot()), Err(Refusal::Unauthenticated(_))));
+    }
+
+    #[test]
+    fn paths_name_typed_files_only() {
+        let (slot, file) = locate("/users/u-0123456789abcdef01234567/requests/register").unwrap();
+        assert_eq!(slot.as_deref(), Some("u-0123456789abcdef01234567"));
+        assert_eq!(file, ExchangeFile::Request("register".to_string()));
+        assert_eq!(locate("/handshake").unwrap(), (None, ExchangeFile::Handshake));
+        for path in ["/files/database.json", "/users/u-1", "/users/u-1/files/verify_request.json", "/requests/unknown"] {
+            assert!(matches!(locate(path), Err(Refusal::Invalid(_))), "{}", path);
+        }
+    }
+
+    #[test]
+    fn uploads_are_capped_per_request_kind() {
+        assert_eq!(request_limit("register"), MAX_REQUEST_BYTES);
+        assert!(request_limit("verify") < MAX_REQUEST_BYTES);
+        assert!(request_limit("session") <= 1024 * 1024);
+        assert!(request_limit("cancel") <= 1024 * 1024);
+    }
+}
@@ -0,0 +1,137 @@
+//! Startup self-test: a known-answer FHE computation on this machine.
+//!
+//! The server never holds the tenant's client key, so the circuit runs twice:
+//! on inputs encrypted under a throwaway key pair, which exercises
+//! bootstrapping and measures the real per-gate time, and on trivially
+//! encrypted inputs under the loaded server key, whose results stay trivial
+//! and can be checked without the client key. The latter only shows that the
+//! stored key deserializes and its gates evaluate; trivial inputs can't tell
+//! whether it belongs to the tenant's client key (see `keys::check_fingerprint`
+//! for that). Loading the key also warms the key cache for the first verification.
+
+use shared::{diff_bits, leq_constant, popcount_tree, select_bits, tree_gates, CancellationToken, ParameterSet};
+use std::time::{Duration, Instant};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, set_server_key, FheBool};
+
+use crate::database::DEFAULT_TENANT;
+use crate::keys;
+use crate::limits::LimitsConfig;
+use crate::tenant;
+
+/// Width of the known-answer popcount
+const SAMPLE_BITS: usize = 64;
+/// Per-gate time above which a warning is printed (unless a hard limit is configured)
+const GATE_WARN: Duration = Duration::from_millis(50);
+
+/// `server self-test [--tenant <name>]`
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let tenant = match args.windows(2).find(|w| w[0] == "--tenant") {
+        Some(w) => w[1].clone(),
+        None => DEFAULT_TENANT.to_string(),
+    };
+    self_test(&tenant)
+}
+
+/// Self-test at startup; skipped until the first registration has uploaded a server key
+pub fn on_startup() -> Result<(), Box<dyn std::error::Error>> {
+    if !std::path::Path::new(&tenant::server_key_path(DEFAULT_TENANT)).exists() {
+        println!("ℹ️  No server key yet, self-test skipped");
+        return Ok(());
+    }
+    self_test(DEFAULT_TENANT)
+        .map_err(|e| format!("Self-test failed, refusing to serve: {}", e).into())
+}
+
+pub fn self_test(tenant: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🩺 SELF-TEST ({})", tenant);
+    let started = Instant::now();
+    
+    // Known answer: two patterns differing in every third bit
+    let a: Vec<bool> = (0..SAMPLE_BITS).map(|i| i % 2 == 0).collect();
+    let b: Vec<bool> = a.iter().enumerate().map(|(i, &bit)| bit ^ (i % 3 == 0)).collect();
+    
+    let (client_key, throwaway_key) = generate_keys(ParameterSet::Default.config());
+    set_server_key(throwaway_key);
+    let a_fhe: Vec<FheBool> = a.iter().map(|&bit| FheBool::encrypt(bit, &client_key)).collect();
+    let b_fhe: Vec<FheBool> = b.iter().map(|&bit| FheBool::encrypt(bit, &client_key)).collect();
+    let elapsed = known_answer(&a_fhe, &b_fhe, &b, |bit| Ok(bit.decrypt(&client_key)))?;
+    let gates = tree_gates(SAMPLE_BITS);
+    let gate_time = elapsed / (SAMPLE_BITS + gates.and + gates.xor) as u32;
+    println!("   ✅ Bootstrapped gates under a throwaway key");
+    
+    let server_key = keys::server_key(tenant)?;
+    set_server_key((*server_key).clone());
+    println!("   ✅ Server key loaded");
+    
+    let a_fhe: Vec<FheBool> = a.iter().map(|&bit| FheBool::encrypt_trivial(bit)).collect();
+    let b_fhe: Vec<FheBool> = b.iter().map(|&bit| FheBool::encrypt_trivial(bit)).collect();
+    known_answer(&a_fhe, &b_fhe, &b, decrypt_trivial)?;
+    println!("   ✅ Server key evaluates the known-answer gates");
+    
+    println!("   ⏱️  {:?} per gate", gate_time);
+    match LimitsConfig::load().max_gate_ms {
+        Some(limit) if gate_time.as_secs_f64() * 1000.0 > limit => {
+            return Err(format!("{:?} per gate exceeds the configured limit of {} ms", gate_time, limit).into());
+        }
+        None if gate_time > GATE_WARN => {
+            println!("   ⚠️  Slower than expected ({:?} per gate); verifications will take long", gate_time);
+        }
+        _ => {}
+    }
+    
+    println!("✅ Self-test passed in {:.2?}", started.elapsed());
+    Ok(())
+}
+
+/// Hamming distance, threshold comparison and select on the known-answer
+/// inputs under the current server key; returns the popcount's duration
+fn known_answer(
+    a_fhe: &[FheBool],
+    b_fhe: &[FheBool],
+    b: &[bool],
+    decrypt: impl Fn(&FheBool) -> Result<bool, Box<dyn std::error::Error>>,
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    let expected = (0..SAMPLE_BITS).filter(|i| i % 3 == 0).count();
+    let cancel = CancellationToken::new();
+    let gate_start = Instant::now();
+    let diff = diff_bits(a_fhe, b_fhe);
+    let count = popcount_tree(&diff, &cancel)?;
+    let elapsed = gate_start.elapsed();
+    
+    let distance = decrypt_bits(&count, &decrypt)?;
+    if distance != expected {
+        return Err(format!("popcount returned {} instead of {}", distance, expected).into());
+    }
+    
+    let below = leq_constant(&count, expected, &cancel)?;
+    let above = leq_constant(&count, expected - 1, &cancel)?;
+    let selected = select_bits(&above, &a_fhe[..8], &b_fhe[..8]);
+    if !decrypt(&below)? || decrypt(&above)? || decrypt_bits(&selected, &decrypt)? != bits_value(&b[..8]) {
+        return Err("threshold comparison returned wrong answers".into());
+    }
+    Ok(elapsed)
+}
+
+fn decrypt_trivial(bit: &FheBool) -> Result<bool, Box<dyn std::error::Error>> {
+    bit.try_decrypt_trivial()
+        .map_err(|_| "result is not a trivial ciphertext".into())
+}
+
+/// LSB-first bits to a number
+fn decrypt_bits(
+    bits: &[FheBool],
+    decrypt: impl Fn(&FheBool) -> Result<bool, Box<dyn std::error::Error>>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut value = 0;
+    for (i, bit) in bits.iter().enumerate() {
+        if decrypt(bit)? {
+            value |= 1 << i;
+        }
+    }
+    Ok(value)
+}
+
+fn bits_value(bits: &[bool]) -> usize {
+    bits.iter().enumerate().filter(|(_, &b)| b).map(|(i, _)| 1 << i).sum()
+}
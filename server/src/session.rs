@@ -0,0 +1,163 @@
+//! Mutual authentication sessions (protocol in shared/src/session.rs).
+//!
+//! A `session` request is answered right away with a server nonce and a
+//! challenge signed by the identity key. The session is kept in memory until
+//! the register or verify request that presents it, and can be used once.
+//! It expires after `session_expiry_secs` (exchanges.json): a verify result
+//! is signed for the session and only while it is still valid. Without
+//! `require_session` in exchanges.json, requests without a session are still
+//! accepted for older clients whose users have no enrollment credential; a
+//! session that is presented must be valid.
+
+use shared::session::{
+    decode_public_key, new_nonce, new_session_id, token, transcript_digest, verify_signature,
+    SessionBinding, SessionClaim, SessionRequest, SessionResponse, SESSION_LIFETIME_SECS,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::exchange::{self, Exchange, ExchangeConfig};
+use crate::tenant;
+
+const SESSION_LIFETIME: Duration = Duration::from_secs(SESSION_LIFETIME_SECS);
+
+/// Issued session waiting for the request that uses it
+struct PendingSession {
+    user_id: String,
+    tenant: String,
+    claim: SessionClaim,
+    digest: [u8; 32],
+    issued: Instant,
+}
+
+fn pending() -> &'static Mutex<HashMap<String, PendingSession>> {
+    static PENDING: OnceLock<Mutex<HashMap<String, PendingSession>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Answer a session request of one exchange
+pub fn handle(exchange: &Exchange) -> Result<(), Box<dyn std::error::Error>> {
+    let request = exchange.read_request("session")?;
+    let req: SessionRequest = serde_json::from_slice(&request.data)?;
+    let resp = match tenant::resolve(req.api_key.as_deref()) {
+        Ok(tenant) => issue(&req, &tenant)?,
+        Err(message) => SessionResponse::error(message),
+    };
+    exchange.write_response("session", &resp, request.reply_to.as_ref())
+}
+
+fn issue(req: &SessionRequest, tenant: &str) -> Result<SessionResponse, Box<dyn std::error::Error>> {
+    let identity = exchange::init_identity()?;
+    let server_nonce = new_nonce();
+    let expiry = chrono::Duration::from_std(ExchangeConfig::load().session_expiry())?;
+    let claim = SessionClaim {
+        session_id: new_session_id(),
+        challenge: new_nonce(),
+        expires_at: (chrono::Utc::now() + expiry).to_rfc3339(),
+    };
+    let digest = transcript_digest(&req.user_id, &req.client_nonce, &server_nonce, &claim);
+
+    let mut sessions = pending().lock().unwrap_or_else(|e| e.into_inner());
+    sessions.retain(|_, s| s.issued.elapsed() < SESSION_LIFETIME);
+    sessions.insert(
+        claim.session_id.clone(),
+        PendingSession {
+            user_id: req.user_id.clone(),
+            tenant: tenant.to_string(),
+            claim: claim.clone(),
+            digest,
+            issued: Instant::now(),
+        },
+    );
+    Ok(SessionResponse::success(claim, server_nonce, identity.sign_digest(&digest)))
+}
+
+/// Check the session a register/verify request presents.
+///
+/// `credential_key` is the user's registered enrollment credential (for a
+/// first enrollment, the one the request registers). A user with a
+/// credential has to present a session. Returns the verified session, to be
+/// signed into the result; `None` means none was presented and none is
+/// required.
+pub fn authenticate(
+    binding: Option<&SessionBinding>,
+    user_id: &str,
+    tenant: &str,
+    credential_key: Option<&str>,
+) -> Result<Option<SessionClaim>, String> {
+    let Some(binding) = binding else {
+        if credential_key.is_some() {
+            return Err("Session handshake required (the user has an enrollment credential)".to_string());
+        }
+        if ExchangeConfig::load().require_session {
+            return Err("Session handshake required (require_session is set)".to_string());
+        }
+        return Ok(None);
+    };
+
+    let session = pending()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&binding.session_id)
+        .ok_or("Unknown or already used session")?;
+    if session.issued.elapsed() >= SESSION_LIFETIME || session.claim.is_expired() {
+        return Err("Session expired".to_string());
+    }
+    if session.user_id != user_id || session.tenant != tenant {
+        return Err("Session was issued for a different user".to_string());
+    }
+    if binding.token != token(&session.digest) {
+        return Err("Session token mismatch".to_string());
+    }
+    if binding.challenge != session.claim.challenge {
+        return Err("Session challenge mismatch".to_string());
+    }
+
+    let credential = credential_key.ok_or("No enrollment credential registered for this user")?;
+    verify_signature(&decode_public_key(credential)?, &session.digest, &binding.proof)
+        .map_err(|e| format!("Enrollment credential proof invalid: {}", e))?;
+    Ok(Some(session.claim))
+}
+
+/// Like [`authenticate`] for requests that change an enrollment (delete,
+/// revoke, key rotation): a verified session is required whatever
+/// `require_session` says
+pub fn require(
+    binding: Option<&SessionBinding>,
+    user_id: &str,
+    tenant: &str,
+    credential_key: Option<&str>,
+) -> Result<SessionClaim, String> {
+    authenticate(binding, user_id, tenant, credential_key)?
+        .ok_or_else(|| "A verified session is required for this request".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_session_is_refused_for_a_user_with_a_credential() {
+        let err = authenticate(None, "alice", "default", Some("credential")).unwrap_err();
+        assert!(err.contains("Session handshake required"));
+    }
+
+    #[test]
+    fn changes_need_a_session_even_without_a_credential() {
+        let err = require(None, "legacy", "default", None).unwrap_err();
+        assert!(err.contains("verified session"));
+    }
+
+    #[test]
+    fn unknown_session_is_refused() {
+        let binding = SessionBinding {
+            session_id: "unknown".to_string(),
+            token: String::new(),
+            proof: String::new(),
+            challenge: String::new(),
+            expires_at: String::new(),
+        };
+        assert!(authenticate(Some(&binding), "alice", "default", Some("credential")).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! SQLite template storage (`"backend": "sqlite"` in storage.json).
+//!
+//! One row per enrollment, keyed like `Database::templates`, with the entry
+//! as JSON; template blobs are BLOB rows named like the files of
+//! blob_store.rs. A save only rewrites entries that changed, in a single
+//! transaction, so a crash leaves either the old or the new enrollments.
+//! An existing templates.json is imported once, behind the `json_import`
+//! marker, so deleting every enrollment doesn't bring them back on the next
+//! start. A save never deletes blob rows, since a request may have stored one
+//! it hasn't saved yet; `database::release_blobs` removes those of dropped
+//! templates once the deletion or replacement is saved.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::config;
+use crate::database::{self, db_dir, db_path, scoped_key, Database, Storage, TemplateEntry, TemplateStore};
+
+pub fn sqlite_path() -> &'static str {
+    config::database_file!("templates.sqlite")
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS templates (key TEXT PRIMARY KEY, entry TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS blobs (name TEXT PRIMARY KEY, data BLOB NOT NULL);
+    CREATE TABLE IF NOT EXISTS store_meta (name TEXT PRIMARY KEY, value TEXT NOT NULL);
+";
+
+/// Marker row of the one-time import of `templates.json`
+const IMPORT_MARKER: &str = "json_import";
+
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(db_dir())?;
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        let storage = Self { conn: Mutex::new(conn) };
+        storage.import_once(path)?;
+        Ok(storage)
+    }
+
+    /// Import `templates.json` into an empty table, then set the marker. A
+    /// failed import sets none, so the next start tries again; a store that
+    /// already had enrollments is only marked.
+    fn import_once(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (marked, empty) = self.with_conn(|conn| {
+            let marked = conn
+                .query_row("SELECT 1 FROM store_meta WHERE name = ?1", params![IMPORT_MARKER], |_| Ok(()))
+                .optional()?
+                .is_some();
+            let empty = conn.query_row("SELECT COUNT(*) FROM templates", [], |row| row.get::<_, i64>(0))? == 0;
+            Ok((marked, empty))
+        })?;
+        if marked {
+            return Ok(());
+        }
+        if empty && Path::new(db_path()).exists() {
+            let imported = database::import_json(self)?;
+            println!("📥 Imported {} templates from {} into {}", imported, db_path(), path);
+        }
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO store_meta (name, value) VALUES (?1, ?2)",
+                params![IMPORT_MARKER, chrono::Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut conn)
+    }
+}
+
+/// Row-level: only the addressed enrollment is read or written
+impl TemplateStore for SqliteStorage {
+    fn get(&self, tenant: &str, user_id: &str) -> Result<Option<TemplateEntry>, Box<dyn std::error::Error>> {
+        let key = scoped_key(tenant, user_id);
+        let entry: Option<String> = self.with_conn(|conn| {
+            Ok(conn
+                .query_row("SELECT entry FROM templates WHERE key = ?1", params![key], |row| row.get(0))
+                .optional()?)
+        })?;
+        entry.map(|entry| database::entry_from_row(&key, &entry)).transpose()
+    }
+
+    fn put(&self, entry: TemplateEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let json = database::entry_to_row(&entry.key(), &entry)?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO templates (key, entry) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET entry = excluded.entry",
+                params![entry.key(), json],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, tenant: &str, user_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            Ok(conn.execute("DELETE FROM templates WHERE key = ?1", params![scoped_key(tenant, user_id)])? > 0)
+        })
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT key FROM templates ORDER BY key")?;
+            let keys = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(keys)
+        })
+    }
+
+    fn exists(&self, tenant: &str, user_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row("SELECT 1 FROM templates WHERE key = ?1", params![scoped_key(tenant, user_id)], |_| Ok(()))
+                .optional()?
+                .is_some())
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Database, Box<dyn std::error::Error>> {
+        let templates = self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT key, entry FROM templates")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+            let mut templates = HashMap::new();
+            for row in rows {
+                let (key, entry) = row?;
+                let entry = database::entry_from_row(&key, &entry)?;
+                templates.insert(key, entry);
+            }
+            Ok(templates)
+        })?;
+        println!("✅ Database loaded: {} templates", templates.len());
+        Ok(Database { version: "1.0".to_string(), templates })
+    }
+
+    fn save(&self, db: &Database) -> Result<(), Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let stored: HashMap<String, String> = {
+                let mut stmt = tx.prepare("SELECT key, entry FROM templates")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<Result<_, _>>()?
+            };
+
+            for (key, entry) in &db.templates {
+                if !stored.get(key).is_some_and(|row| database::row_is_current(key, row, entry)) {
+                    let json = database::entry_to_row(key, entry)?;
+                    tx.execute(
+                        "INSERT INTO templates (key, entry) VALUES (?1, ?2)
+                         ON CONFLICT(key) DO UPDATE SET entry = excluded.entry",
+                        params![key, json],
+                    )?;
+                }
+            }
+            for key in stored.keys().filter(|k| !db.templates.contains_key(*k)) {
+                tx.execute("DELETE FROM templates WHERE key = ?1", params![key])?;
+            }
+
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn put_blob(&self, name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            conn.execute("INSERT OR IGNORE INTO blobs (name, data) VALUES (?1, ?2)", params![name, data])?;
+            Ok(())
+        })
+    }
+
+    fn get_blob(&self, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT data FROM blobs WHERE name = ?1", params![name], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| format!("No blob named {}", name).into())
+        })
+    }
+
+    fn blob_len(&self, name: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT length(data) FROM blobs WHERE name = ?1", params![name], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| format!("No blob named {}", name).into())
+        })
+    }
+
+    fn delete_blob(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM blobs WHERE name = ?1", params![name])?;
+            Ok(())
+        })
+    }
+
+    fn blobs_in_database(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user_id: &str) -> TemplateEntry {
+        TemplateEntry::new(user_id.to_string(), vec![1, 2, 3], vec![4], vec![5]).with_tenant("sqlitetest")
+    }
+
+    /// A fresh database file with the schema, and the import marker if `marked`
+    fn fresh_path(name: &str, marked: bool) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.sqlite", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        if marked {
+            conn.execute("INSERT INTO store_meta (name, value) VALUES (?1, 'test')", params![IMPORT_MARKER]).unwrap();
+        }
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn deleted_enrollments_are_not_imported_again() {
+        // A store that had enrollments before the marker existed is only marked
+        let path = fresh_path("sqlite_import", false);
+        let alice = entry("alice");
+        Connection::open(&path)
+            .unwrap()
+            .execute(
+                "INSERT INTO templates (key, entry) VALUES (?1, ?2)",
+                params![alice.key(), database::entry_to_row(&alice.key(), &alice).unwrap()],
+            )
+            .unwrap();
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.list().unwrap(), vec![alice.key()]);
+
+        // Emptied, it stays empty on the next start instead of importing templates.json
+        assert!(storage.delete("sqlitetest", "alice").unwrap());
+        drop(storage);
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert!(storage.list().unwrap().is_empty());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn save_writes_changes_and_removes_dropped_entries() {
+        let path = fresh_path("sqlite_save", true);
+        let storage = SqliteStorage::open(&path).unwrap();
+        storage.put(entry("alice")).unwrap();
+        storage.put(entry("bob")).unwrap();
+
+        let mut db = storage.load().unwrap();
+        db.templates.remove(&entry("bob").key());
+        db.templates.get_mut(&entry("alice").key()).unwrap().credential_key = Some("key".to_string());
+        storage.save(&db).unwrap();
+
+        assert_eq!(storage.list().unwrap(), vec![entry("alice").key()]);
+        assert_eq!(storage.get("sqlitetest", "alice").unwrap().unwrap().credential_key.as_deref(), Some("key"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sqlite_storage_keeps_the_store_contract() {
+        let path = fresh_path("sqlite_contract", true);
+        crate::memory_store::contract::check(&SqliteStorage::open(&path).unwrap(), "sqlitetest");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn blob_rows_are_measured_and_deleted() {
+        let path = fresh_path("sqlite_blobs", true);
+        let storage = SqliteStorage::open(&path).unwrap();
+        let mut alice = entry("alice");
+        alice.blob.externalize_to(&storage).unwrap();
+        let name = alice.blob.blob_file.clone().unwrap();
+
+        // Three length-prefixed sections: key, IV, ciphertext
+        assert_eq!(storage.blob_len(&name).unwrap(), 24 + 1 + 1 + 3);
+        assert_eq!(storage.get_blob(&name).unwrap().len(), 29);
+        storage.delete_blob(&name).unwrap();
+        assert!(storage.blob_len(&name).is_err());
+        storage.delete_blob(&name).unwrap();
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,162 @@
+//! Garbage collection of abandoned exchange files and job records.
+//!
+//! A client that crashes before consuming its response, or a server that
+//! dies mid-job, leaves files behind forever. Maintenance moves request,
+//! response and job files older than `stale_after_hours` (exchanges.json,
+//! default 24) into `../database/exchange_archive/<origin>/` rather than
+//! deleting them, so an operator can still see what was abandoned.
+//!
+//! Records of finished verify jobs (see jobs.rs) are deleted after the same
+//! age. Jobs queued or running in the serving process are skipped. A separate
+//! `server maintenance` run can't see those, so the age must exceed the
+//! longest queue wait plus verification time. Job logs (see workers.rs)
+//! and leftover verify checkpoints (see checkpoint.rs) are deleted after
+//! the same age.
+
+use crate::config;
+use crate::exchange::{self, ExchangeConfig};
+use shared::protocol::JOB_TICKET_FILE;
+use shared::transport::{self, Transport};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+fn archive_dir() -> &'static str {
+    config::database_file!("exchange_archive")
+}
+
+#[derive(Debug, Default)]
+pub struct StaleReport {
+    pub exchange_files: usize,
+    pub job_records: usize,
+}
+
+/// Archive stale files of every configured exchange
+pub fn run() -> Result<StaleReport, Box<dyn std::error::Error>> {
+    let config = ExchangeConfig::load();
+    let cutoff = SystemTime::now() - config.stale_after();
+    let mut report = StaleReport::default();
+    
+    for exchange in exchange::configured(&config) {
+        let archive = Path::new(archive_dir()).join(&exchange.origin);
+        let root = exchange.transport();
+        
+        // One failing exchange (e.g. an unreachable bucket) doesn't stop the others
+        let mut areas: Vec<(String, Box<dyn Transport>)> = Vec::new();
+        match root.user_slots() {
+            Ok(slots) => {
+                for slot in slots.into_iter().filter(|s| transport::valid_slot(s)) {
+                    areas.push((format!("{}_", slot), root.scoped(&slot)));
+                }
+            }
+            Err(e) => eprintln!("⚠️  Could not list user slots of '{}': {}", exchange.origin, e),
+        }
+        match collect_stale(root, &archive, "", cutoff) {
+            Ok(moved) => report.exchange_files += moved,
+            Err(e) => eprintln!("⚠️  Stale file cleanup failed for '{}': {}", exchange.origin, e),
+        }
+        for (label, area) in &areas {
+            match collect_stale(area.as_ref(), &archive, label, cutoff) {
+                Ok(moved) => report.exchange_files += moved,
+                Err(e) => eprintln!("⚠️  Stale file cleanup failed for '{}': {}", exchange.origin, e),
+            }
+        }
+        
+        report.job_records += collect_stale_jobs(&exchange.jobs_dir(), &archive, cutoff)?;
+    }
+    report.job_records += crate::jobs::purge_finished(cutoff)?;
+    report.job_records += crate::workers::purge_logs(cutoff)?;
+    report.job_records += crate::checkpoint::purge(cutoff)?;
+    Ok(report)
+}
+
+/// Move request/response files last modified before `cutoff` out of an exchange area
+fn collect_stale(
+    transport: &dyn Transport,
+    archive: &Path,
+    label: &str,
+    cutoff: SystemTime,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut moved = 0;
+    for name in transport.list()? {
+        if !is_exchange_file(&name) {
+            continue;
+        }
+        let Some(modified) = transport.modified(&name)? else { continue };
+        if modified > cutoff {
+            continue;
+        }
+        // Copy out, then delete: the archive is usually on another filesystem
+        let Some(data) = transport.get(&name)? else { continue };
+        fs::create_dir_all(archive)?;
+        fs::write(archive.join(archived_name(label, &name, modified)), data)?;
+        transport.delete(&name)?;
+        println!("🧹 Archived stale exchange file: {}{}", label, name);
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Move job records of jobs that are no longer queued or running in this process
+fn collect_stale_jobs(jobs_dir: &Path, archive: &Path, cutoff: SystemTime) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(jobs_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut moved = 0;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let modified = entry.metadata()?.modified()?;
+        if !entry.file_type()?.is_file() || modified > cutoff || exchange::is_active_job(&path) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let dest = archive.join(archived_name("job_", &name, modified));
+        fs::create_dir_all(archive)?;
+        if fs::rename(&path, &dest).is_err() {
+            fs::copy(&path, &dest)?;
+            fs::remove_file(&path)?;
+        }
+        println!("🧹 Archived abandoned job: {}", name);
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Files the protocol writes, including job tickets and job statuses;
+/// the server status and handshake files are always current
+fn is_exchange_file(name: &str) -> bool {
+    name.ends_with("_request.json")
+        || name.ends_with("_response.json")
+        || name.ends_with(".tmp")
+        || name == JOB_TICKET_FILE
+        || name.starts_with("verify_status_")
+}
+
+/// `<modified>_<label><name>`, sortable by age
+fn archived_name(label: &str, name: &str, modified: SystemTime) -> String {
+    let modified: chrono::DateTime<chrono::Utc> = modified.into();
+    format!("{}_{}{}", modified.format("%Y%m%dT%H%M%SZ"), label, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_protocol_files_are_collected() {
+        assert!(is_exchange_file("verify_response.json"));
+        assert!(is_exchange_file("register_request.json.tmp"));
+        assert!(is_exchange_file(&shared::protocol::job_status_file("0123456789abcdef0123456789abcdef")));
+        assert!(!is_exchange_file("server_status.json"));
+        assert!(!is_exchange_file(shared::sealed::HANDSHAKE_FILE));
+    }
+
+    #[test]
+    fn archived_names_sort_by_age() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        assert_eq!(archived_name("job_", "verify_1.json", epoch), "19700101T000000Z_job_verify_1.json");
+    }
+}
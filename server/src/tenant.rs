@@ -0,0 +1,250 @@
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use shared::attestation::sha256_hex;
+use shared::ResultMode;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+use crate::database::DEFAULT_TENANT;
+use crate::default_server_key_path;
+
+pub fn tenants_path() -> &'static str {
+    config::database_file!("tenants.json")
+}
+
+/// API key -> tenant namespace mapping (`tenants.json` in the database directory)
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TenantRegistry {
+    #[serde(default)]
+    pub require_api_key: bool,              // Reject requests without a key instead of using the default tenant
+    #[serde(default)]
+    pub tenants: BTreeMap<String, Tenant>,
+    #[serde(default)]
+    pub admin_key_sha256: Option<String>,   // Key of admin requests over the exchange (None = disabled)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tenant {
+    pub api_key_sha256: String,             // Only the hash of the key is stored
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_mode: Option<ResultMode>,    // Overrides fingerprint.toml's `[matching] result_mode`
+}
+
+impl TenantRegistry {
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        if !Path::new(tenants_path()).exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(tenants_path())?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(config::database_dir())?;
+        fs::write(tenants_path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Map a request's API key to its tenant namespace
+    pub fn resolve(&self, api_key: Option<&str>) -> Result<String, String> {
+        match api_key {
+            None if self.require_api_key => Err("API key required".to_string()),
+            None => Ok(DEFAULT_TENANT.to_string()),
+            Some(key) => {
+                let hash = sha256_hex(key.as_bytes());
+                self.tenants
+                    .iter()
+                    .find(|(_, t)| t.api_key_sha256 == hash)
+                    .map(|(name, _)| name.clone())
+                    .ok_or_else(|| "Invalid API key".to_string())
+            }
+        }
+    }
+
+    /// Create a tenant (or rotate its key) and return the new plaintext API key
+    pub fn issue_key(&mut self, name: &str) -> String {
+        let key = new_key("fpk");
+        let result_mode = self.tenants.get(name).and_then(|tenant| tenant.result_mode);
+        self.tenants.insert(name.to_string(), Tenant {
+            api_key_sha256: sha256_hex(key.as_bytes()),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            result_mode,
+        });
+        key
+    }
+
+    /// What a tenant's verify responses return at most: its own policy, else the server's
+    pub fn result_mode(&self, tenant: &str, server: Option<ResultMode>) -> ResultMode {
+        self.tenants
+            .get(tenant)
+            .and_then(|t| t.result_mode)
+            .or(server)
+            .unwrap_or_default()
+    }
+
+    /// Set (or rotate) the admin key and return it in plaintext
+    pub fn issue_admin_key(&mut self) -> String {
+        let key = new_key("fpa");
+        self.admin_key_sha256 = Some(sha256_hex(key.as_bytes()));
+        key
+    }
+
+    pub fn is_admin(&self, key: &str) -> bool {
+        self.admin_key_sha256.as_deref() == Some(sha256_hex(key.as_bytes()).as_str())
+    }
+}
+
+fn new_key(prefix: &str) -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}_{}", prefix, bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// Each tenant brings its own client key, so server keys are stored per tenant
+pub fn server_key_path(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        default_server_key_path().to_string()
+    } else {
+        config::database_path(&format!("server_key.{}.bin", tenant))
+    }
+}
+
+/// Compressed server key a tenant's client sent for GPU evaluation (see keys.rs)
+pub fn gpu_key_path(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        config::database_path("gpu_key.bin")
+    } else {
+        config::database_path(&format!("gpu_key.{}.bin", tenant))
+    }
+}
+
+/// Resolve the tenant of an incoming request
+pub fn resolve(api_key: Option<&str>) -> Result<String, String> {
+    TenantRegistry::load()
+        .map_err(|e| format!("Tenant registry unreadable: {}", e))?
+        .resolve(api_key)
+}
+
+/// Result mode policy of a tenant (see `TenantRegistry::result_mode`); an
+/// unreadable registry leaves the server's
+pub fn result_mode(tenant: &str) -> ResultMode {
+    let server = config::get().matching.result_mode;
+    match TenantRegistry::load() {
+        Ok(registry) => registry.result_mode(tenant, server),
+        Err(_) => server.unwrap_or_default(),
+    }
+}
+
+/// Whether `key` is the server's admin key (an unreadable registry has none)
+pub fn is_admin_key(key: &str) -> bool {
+    TenantRegistry::load().map(|r| r.is_admin(key)).unwrap_or(false)
+}
+
+/// `server admin tenant <add|rotate|remove|list|require-key>`
+pub fn admin(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut registry = TenantRegistry::load()?;
+
+    match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("add"), Some(name)) | (Some("rotate"), Some(name)) => {
+            if name == DEFAULT_TENANT || name.contains('/') {
+                return Err(format!("Invalid tenant name '{}'", name).into());
+            }
+            if args[0] == "add" && registry.tenants.contains_key(name) {
+                return Err(format!("Tenant '{}' already exists (use rotate)", name).into());
+            }
+            if args[0] == "rotate" && !registry.tenants.contains_key(name) {
+                return Err(format!("Tenant '{}' not found", name).into());
+            }
+            let key = registry.issue_key(name);
+            registry.save()?;
+            println!("🔑 API key for tenant '{}' (shown once):", name);
+            println!("{}", key);
+        }
+        (Some("remove"), Some(name)) => {
+            if registry.tenants.remove(name.as_str()).is_none() {
+                return Err(format!("Tenant '{}' not found", name).into());
+            }
+            registry.save()?;
+            println!("🗑️  Tenant '{}' removed (its templates are kept until purged)", name);
+        }
+        (Some("require-key"), Some(flag)) => {
+            registry.require_api_key = matches!(flag.as_str(), "on" | "true" | "yes");
+            registry.save()?;
+            println!("🔒 API key required: {}", registry.require_api_key);
+        }
+        (Some("result-mode"), Some(name)) => {
+            let mode = match args.get(2).map(|s| s.as_str()) {
+                Some("match_only") => Some(ResultMode::MatchOnly),
+                Some("match_and_distance") => Some(ResultMode::MatchAndDistance),
+                Some("server") => None,
+                _ => return Err("Result mode must be match_only, match_and_distance or server".into()),
+            };
+            let tenant = registry.tenants.get_mut(name.as_str()).ok_or_else(|| format!("Tenant '{}' not found", name))?;
+            tenant.result_mode = mode;
+            registry.save()?;
+            println!("🔒 Result mode of tenant '{}': {}", name, args[2]);
+        }
+        (Some("list"), _) => {
+            println!("🔒 API key required: {}", registry.require_api_key);
+            for (name, tenant) in &registry.tenants {
+                match tenant.result_mode {
+                    Some(mode) => println!("{:<24} created {} ({:?})", name, tenant.created_at, mode),
+                    None => println!("{:<24} created {}", name, tenant.created_at),
+                }
+            }
+        }
+        _ => {
+            println!("Usage: server admin tenant <add <name> | rotate <name> | remove <name> | list | require-key <on|off> | result-mode <name> <match_only|match_and_distance|server>>");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_issued_keys_only() {
+        let mut registry = TenantRegistry::default();
+        let key = registry.issue_key("acme");
+
+        assert_eq!(registry.resolve(Some(&key)).unwrap(), "acme");
+        assert!(registry.resolve(Some("fpk_bogus")).is_err());
+        assert_eq!(registry.resolve(None).unwrap(), DEFAULT_TENANT);
+
+        registry.require_api_key = true;
+        assert!(registry.resolve(None).is_err());
+    }
+
+    #[test]
+    fn tenant_result_mode_overrides_the_server() {
+        let mut registry = TenantRegistry::default();
+        registry.issue_key("acme");
+        registry.issue_key("globex");
+        registry.tenants.get_mut("acme").unwrap().result_mode = Some(ResultMode::MatchAndDistance);
+
+        assert_eq!(registry.result_mode("acme", Some(ResultMode::MatchOnly)), ResultMode::MatchAndDistance);
+        assert_eq!(registry.result_mode("globex", Some(ResultMode::MatchOnly)), ResultMode::MatchOnly);
+        assert_eq!(registry.result_mode(DEFAULT_TENANT, None), ResultMode::MatchAndDistance);
+
+        // Rotating the key keeps the policy
+        registry.issue_key("acme");
+        assert_eq!(registry.tenants["acme"].result_mode, Some(ResultMode::MatchAndDistance));
+    }
+
+    #[test]
+    fn admin_key_is_separate_from_tenant_keys() {
+        let mut registry = TenantRegistry::default();
+        let tenant_key = registry.issue_key("acme");
+        assert!(!registry.is_admin(&tenant_key));
+
+        let admin_key = registry.issue_admin_key();
+        assert!(registry.is_admin(&admin_key));
+        assert!(!registry.is_admin(&tenant_key));
+        assert!(registry.resolve(Some(&admin_key)).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Thread pools for tfhe's parallel operations.
+//!
+//! tfhe parallelizes bootstrapping-heavy operations (radix sums, key
+//! switching) with rayon, on the global pool unless the calling thread
+//! belongs to another one. The global pool gets `tfhe_threads` threads and
+//! serves register jobs and startup. Each verify worker joins a pool of its
+//! own with the job's share (`job_threads`), so several verifications at
+//! once don't each spread over every core. The worker thread is one of the
+//! pool's threads: rayon calls made while a job runs stay in its pool, and
+//! thread-local state (job log, installed server key) stays where it was.
+//! FHE-Trivium clocks in parallel on the same pool (see
+//! `shared::set_clock_threads`), so they are part of the job's share.
+//!
+//! A thread stays in the first pool it joins, so a share changed in
+//! limits.json can't resize a worker's pool: the worker is replaced after
+//! its job instead (see `job_pool_outdated`).
+//!
+//! With `pin_cores`, verify worker n (from 1) and its pool threads are
+//! pinned to cores `(n-1) * job_threads ..`, wrapping around the machine.
+
+use shared::{etrln, set_clock_threads, trln};
+use std::cell::OnceCell;
+
+use crate::limits::{LimitsConfig, ThreadBudget};
+use crate::workers;
+
+thread_local! {
+    static JOB_POOL: OnceCell<(ThreadBudget, Option<rayon::ThreadPool>)> = const { OnceCell::new() };
+}
+
+/// Size the global pool; call before anything runs on it
+pub fn configure(budget: &ThreadBudget) {
+    let built = rayon::ThreadPoolBuilder::new()
+        .num_threads(budget.total)
+        .thread_name(|n| format!("tfhe-{}", n))
+        .build_global();
+    match built {
+        Ok(()) if budget.pin_cores => trln!("server.tfhe_threads_pinned", budget.total, budget.per_job),
+        Ok(()) => trln!("server.tfhe_threads", budget.total, budget.per_job),
+        Err(e) => etrln!("server.tfhe_pool_failed", e),
+    }
+}
+
+/// Make this verify worker's rayon calls run on its own pool of `per_job` threads,
+/// `trivium_threads` of them clocking FHE-Trivium
+pub fn join_job_pool() {
+    let limits = LimitsConfig::load();
+    JOB_POOL.with(|pool| {
+        let (budget, _) = pool.get_or_init(|| {
+            let budget = limits.thread_budget();
+            let cores = budget.pin_cores.then(|| worker_cores(&budget)).flatten();
+            let name = current_name();
+            let mut builder = rayon::ThreadPoolBuilder::new()
+                .num_threads(budget.per_job)
+                .use_current_thread()
+                .thread_name(move |n| format!("{}-tfhe-{}", name, n));
+            if let Some(cores) = cores.clone() {
+                // Thread 0 is this one, pinned below
+                builder = builder.start_handler(move |n| {
+                    if n > 0 {
+                        pin(cores[n % cores.len()]);
+                    }
+                });
+            }
+            let pool = match builder.build() {
+                Ok(pool) => {
+                    if let Some(cores) = cores {
+                        pin(cores[0]);
+                    }
+                    Some(pool)
+                }
+                Err(e) => {
+                    etrln!("server.job_pool_failed", e);
+                    None
+                }
+            };
+            (budget, pool)
+        });
+        set_clock_threads(budget.clock_threads(limits.trivium_threads.unwrap_or(1)));
+    });
+}
+
+/// Whether limits.json now gives a verify job another share than this
+/// worker's pool was built with; the worker should then make way for a new one
+pub fn job_pool_outdated() -> bool {
+    JOB_POOL.with(|pool| {
+        let Some((built, _)) = pool.get() else { return false };
+        let budget = LimitsConfig::load().thread_budget();
+        let outdated = shares_differ(built, &budget);
+        if outdated {
+            trln!("server.job_pool_outdated", current_name(), budget.per_job);
+        }
+        outdated
+    })
+}
+
+/// A pool built for `built` doesn't fit `now`: another size or pinning
+fn shares_differ(built: &ThreadBudget, now: &ThreadBudget) -> bool {
+    (built.per_job, built.pin_cores) != (now.per_job, now.pin_cores)
+}
+
+/// Cores of this verify worker (None if the cores can't be listed)
+fn worker_cores(budget: &ThreadBudget) -> Option<Vec<core_affinity::CoreId>> {
+    let all = core_affinity::get_core_ids().filter(|ids| !ids.is_empty())?;
+    let first = workers::worker_index() * budget.per_job;
+    Some((first..first + budget.per_job).map(|i| all[i % all.len()]).collect())
+}
+
+fn pin(core: core_affinity::CoreId) {
+    if !core_affinity::set_for_current(core) {
+        etrln!("server.core_pin_failed", current_name(), core.id);
+    }
+}
+
+fn current_name() -> String {
+    std::thread::current().name().unwrap_or("worker").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_another_share_outdates_a_job_pool() {
+        let built = ThreadBudget { total: 16, per_job: 4, pin_cores: false };
+        assert!(!shares_differ(&built, &built));
+        // The global pool can't be resized anyway; the job's share is what counts
+        assert!(!shares_differ(&built, &ThreadBudget { total: 32, ..built }));
+        assert!(shares_differ(&built, &ThreadBudget { per_job: 8, ..built }));
+        assert!(shares_differ(&built, &ThreadBudget { pin_cores: true, ..built }));
+    }
+
+    #[test]
+    fn threads_outside_a_job_pool_are_never_outdated() {
+        std::thread::spawn(|| assert!(!job_pool_outdated())).join().unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+//! Worker pools for FHE jobs.
+//!
+//! Each job kind has a fixed pool of long-lived worker threads, sized by the
+//! concurrency limits (`max_concurrent_verify` / `max_concurrent_register`
+//! in limits.json), that take claimed jobs from a queue. A worker keeps the
+//! tfhe server key it installed (see `keys::install`), so consecutive jobs of
+//! the same tenant don't clone it again. A panicking job is caught: the
+//! worker answers the client, logs the panic and takes the next job. A
+//! verify worker whose share of tfhe threads changed in limits.json hands
+//! over to a new thread after its job (see threads.rs).
+//!
+//! While a job runs, its console lines are tagged with the job and copied to
+//! `../database/job_logs/<job>.log`; maintenance removes logs after
+//! `stale_after_hours` (exchanges.json).
+//!
+//! On SIGINT/SIGTERM the server stops taking requests and cancels the
+//! shutdown token every verify job's token descends from: running jobs stop
+//! at the next gate and, like queued ones, keep their request file and
+//! checkpoints so the next start resumes them. A second signal exits at once.
+
+use shared::i18n::{self, LineSink};
+use shared::{etrln, trln, CancellationToken};
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::config;
+use crate::limits;
+use crate::threads;
+
+pub fn job_log_dir() -> &'static str {
+    config::database_file!("job_logs")
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    static WORKER_INDEX: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Index of this worker within its pool, from 0 (0 outside a pool)
+pub fn worker_index() -> usize {
+    WORKER_INDEX.with(|index| index.get())
+}
+
+/// Fixed set of threads running submitted tasks in order
+pub struct WorkerPool {
+    queue: Mutex<Sender<Task>>,
+}
+
+impl WorkerPool {
+    pub fn new(name: &str, workers: usize) -> Self {
+        let (queue, tasks) = mpsc::channel::<Task>();
+        let tasks = Arc::new(Mutex::new(tasks));
+        for n in 1..=workers.max(1) {
+            spawn_worker(name, n, Arc::clone(&tasks));
+        }
+        Self { queue: Mutex::new(queue) }
+    }
+
+    /// Queue a task for the next free worker
+    pub fn submit(&self, task: impl FnOnce() + Send + 'static) {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(mpsc::SendError(task)) = queue.send(Box::new(task)) {
+            // Every worker is gone; run it here rather than dropping the job
+            task();
+        }
+    }
+}
+
+/// Start worker `n` (from 1) of pool `name`. A worker whose job pool no longer
+/// fits limits.json is replaced by a fresh thread after its job.
+fn spawn_worker(name: &str, n: usize, tasks: Arc<Mutex<Receiver<Task>>>) {
+    let pool = name.to_string();
+    let spawned = std::thread::Builder::new()
+        .name(format!("{}-{}", name, n))
+        .spawn(move || {
+            WORKER_INDEX.with(|index| index.set(n - 1));
+            if work(&tasks) {
+                spawn_worker(&pool, n, tasks);
+            }
+        });
+    if let Err(e) = spawned {
+        etrln!("server.worker_spawn_failed", name, e);
+    }
+}
+
+/// Worker loop: run tasks until the pool is dropped (false) or this worker's
+/// job pool is outdated (true), surviving panics
+fn work(tasks: &Mutex<Receiver<Task>>) -> bool {
+    loop {
+        let task = tasks.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok(task) = task else { return false };
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(task)) {
+            let worker = std::thread::current().name().unwrap_or("worker").to_string();
+            etrln!("server.worker_panicked", worker, panic_message(panic.as_ref()));
+        }
+        if threads::job_pool_outdated() {
+            return true;
+        }
+    }
+}
+
+/// Text of a panic payload
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+pub struct Pools {
+    pub verify: WorkerPool,
+    pub register: WorkerPool,
+}
+
+static POOLS: OnceLock<Pools> = OnceLock::new();
+
+/// Process-wide pools, one worker per job the limiters let run at once
+pub fn pools() -> &'static Pools {
+    POOLS.get_or_init(|| {
+        let limiters = limits::limiters();
+        Pools {
+            verify: WorkerPool::new("verify", limiters.verify.max()),
+            register: WorkerPool::new("register", limiters.register.max()),
+        }
+    })
+}
+
+/// Parent of every verify job's cancellation token
+pub fn shutdown_token() -> &'static CancellationToken {
+    static SHUTDOWN: OnceLock<CancellationToken> = OnceLock::new();
+    SHUTDOWN.get_or_init(CancellationToken::new)
+}
+
+pub fn shutting_down() -> bool {
+    shutdown_token().is_cancelled()
+}
+
+/// Cancel running jobs on the first SIGINT/SIGTERM, exit on the second
+pub fn handle_signals() -> Result<(), Box<dyn std::error::Error>> {
+    ctrlc::set_handler(|| {
+        if shutting_down() {
+            std::process::exit(130);
+        }
+        trln!("server.shutting_down");
+        shutdown_token().cancel();
+    })?;
+    Ok(())
+}
+
+/// Log file of a job, named after its claimed request file
+pub fn job_log_path(job_path: &Path) -> PathBuf {
+    let stem = job_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    Path::new(job_log_dir()).join(format!("{}.log", stem))
+}
+
+/// Tag this thread's output with `tag` and copy it to the job's log until the guard drops
+pub fn log_job(job_path: &Path, tag: String) -> JobLogGuard {
+    let path = job_log_path(job_path);
+    let log = fs::create_dir_all(job_log_dir())
+        .and_then(|_| fs::OpenOptions::new().create(true).append(true).open(&path));
+    let log = match log {
+        Ok(log) => Some(log),
+        Err(e) => {
+            eprintln!("⚠️  Could not open job log {}: {}", path.display(), e);
+            None
+        }
+    };
+    i18n::set_sink(Some(LineSink { tag, log }));
+    JobLogGuard
+}
+
+/// Removes the thread's line sink on drop
+pub struct JobLogGuard;
+
+impl Drop for JobLogGuard {
+    fn drop(&mut self) {
+        i18n::set_sink(None);
+    }
+}
+
+/// Remove job logs last written before `cutoff`
+pub fn purge_logs(cutoff: SystemTime) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(job_log_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        let is_log = entry.path().extension().is_some_and(|ext| ext == "log");
+        if is_log && entry.metadata()?.modified()? < cutoff {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        trln!("server.job_logs_purged", removed);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn panicking_task_does_not_stop_the_worker() {
+        let pool = WorkerPool::new("test", 1);
+        let (done, finished) = channel();
+        pool.submit(|| panic!("job exploded"));
+        pool.submit(move || done.send(()).unwrap());
+        assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn panic_messages_are_readable() {
+        let caught = panic::catch_unwind(|| panic!("bad {}", "template")).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "bad template");
+        let caught = panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "static");
+    }
+}
@@ -0,0 +1,97 @@
+//! Benchmarks of the FHE primitives a verification is made of.
+//!
+//! `cargo bench -p shared --bench fhe_primitives`
+//!
+//! Everything runs under one fixed parameter set (`Message2Carry2`, not the
+//! tfhe default, which may change between releases) so results stay
+//! comparable across refactors. Keys are generated once per run. The
+//! Trivium warmup alone takes minutes, so every group keeps criterion's
+//! smallest sample size.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, ClientKey, FheBool};
+
+use shared::template::{self, DEFAULT_TEMPLATE_BITS};
+#[allow(deprecated)]
+use shared::popcount_1024;
+use shared::{counter_width, leq_constant, popcount_tree, CancellationToken, EvaluationKey, ParameterSet, Progress, TriviumFhe};
+
+/// Keystream bits generated per `keystream_bit` sample
+const KEYSTREAM_BITS: usize = 64;
+
+struct Setup {
+    client_key: ClientKey,
+    server_key: Arc<EvaluationKey>,
+}
+
+fn setup() -> Setup {
+    let (client_key, server_key) = generate_keys(ParameterSet::Message2Carry2.config());
+    let server_key = Arc::new(EvaluationKey::from(server_key));
+    server_key.install();
+    Setup { client_key, server_key }
+}
+
+fn encrypt(bits: impl IntoIterator<Item = bool>, client_key: &ClientKey) -> Vec<FheBool> {
+    bits.into_iter().map(|b| FheBool::encrypt(b, client_key)).collect()
+}
+
+fn trivium(c: &mut Criterion, s: &Setup) {
+    let key = encrypt((0..80).map(|i| i % 3 == 0), &s.client_key);
+    let iv = encrypt((0..80).map(|i| i % 5 == 0), &s.client_key);
+    let quiet = |_: Progress| {};
+    let cancel = CancellationToken::new();
+
+    let mut group = c.benchmark_group("trivium");
+    group.sample_size(10).measurement_time(Duration::from_secs(60));
+    group.bench_function("warmup", |b| {
+        b.iter(|| TriviumFhe::new(&key, &iv, &s.server_key, &quiet, &cancel).unwrap())
+    });
+
+    let warm = TriviumFhe::new(&key, &iv, &s.server_key, &quiet, &cancel).unwrap();
+    group.throughput(Throughput::Elements(KEYSTREAM_BITS as u64));
+    group.bench_function("keystream_bit", |b| {
+        b.iter_batched(
+            || warm.clone(),
+            |mut trivium| trivium.keystream(KEYSTREAM_BITS, &quiet, &cancel).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn matching(c: &mut Criterion, s: &Setup) {
+    let cancel = CancellationToken::new();
+    // About a genuine pair's share of differing bits (the circuits don't branch on it)
+    let diff = encrypt((0..DEFAULT_TEMPLATE_BITS).map(|i| i % 7 == 0), &s.client_key);
+
+    let mut group = c.benchmark_group("matching");
+    group.sample_size(10);
+    #[allow(deprecated)]
+    group.bench_function("popcount_1024", |b| {
+        b.iter(|| popcount_1024(black_box(&diff), &cancel).unwrap())
+    });
+    group.bench_function("popcount_tree_1024", |b| {
+        b.iter(|| popcount_tree(black_box(&diff), &cancel).unwrap())
+    });
+
+    let distance = encrypt((0..counter_width(DEFAULT_TEMPLATE_BITS)).map(|i| 150 >> i & 1 == 1), &s.client_key);
+    let threshold = template::match_threshold(DEFAULT_TEMPLATE_BITS);
+    group.bench_function("leq_constant", |b| {
+        b.iter(|| leq_constant(black_box(&distance), threshold, &cancel).unwrap())
+    });
+    group.finish();
+}
+
+fn fhe_primitives(c: &mut Criterion) {
+    let s = setup();
+    trivium(c, &s);
+    matching(c, &s);
+}
+
+criterion_group!(benches, fhe_primitives);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+//! Generates the gRPC exchange service (shared/src/grpc.rs) without protobuf:
+//! messages are the serde structs defined there, encoded with bincode.
+
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route: &str, input: &str, output: &str) -> tonic_build::manual::MethodBuilder {
+    Method::builder()
+        .name(name)
+        .route_name(route)
+        .input_type(format!("crate::grpc::{}", input))
+        .output_type(format!("crate::grpc::{}", output))
+        .codec_path("crate::grpc::BincodeCodec")
+}
+
+fn main() {
+    let exchange = Service::builder()
+        .name("Exchange")
+        .package("fingerprint")
+        .method(method("put", "Put", "Chunk", "Empty").client_streaming().build())
+        .method(method("get", "Get", "FileRef", "Chunk").server_streaming().build())
+        .method(method("delete", "Delete", "FileRef", "Empty").build())
+        .method(method("stat", "Stat", "FileRef", "FileStat").build())
+        .build();
+    Builder::new().compile(&[exchange]);
+}
@@ -0,0 +1,111 @@
+// shared/src/attestation.rs
+
+//! Verification receipts.
+//!
+//! After a verification the server issues a receipt binding the user, the
+//! factor, the time and a hash of the encrypted match bit it produced, once
+//! the client proves it decrypted that bit as a match (see ownership.rs). The
+//! receipt is authenticated with HMAC-SHA256 under the server's attestation
+//! key, which is shared with the relying party (e.g. the OIDC provider that
+//! accepts receipts in a token exchange).
+//!
+//! Format: `base64url(claims JSON) "." base64url(HMAC(claims JSON))`
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::protocol::Factor;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a receipt can be exchanged after issuance
+pub const RECEIPT_LIFETIME_SECS: i64 = 300;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReceiptClaims {
+    pub iss: String,          // Issuing server
+    pub sub: String,          // User ID
+    pub iat: i64,             // Issued at (unix seconds)
+    pub exp: i64,             // Expiry (unix seconds)
+    pub factor: Factor,
+    pub result_hash: String,  // hex SHA-256 of the encrypted match bytes
+}
+
+impl ReceiptClaims {
+    /// `result_hash` is `sha256_hex` of the encrypted match bytes
+    pub fn new(issuer: &str, user_id: &str, factor: Factor, result_hash: String) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            iss: issuer.to_string(),
+            sub: user_id.to_string(),
+            iat: now,
+            exp: now + RECEIPT_LIFETIME_SECS,
+            factor,
+            result_hash,
+        }
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Serialize and authenticate claims into a receipt token
+pub fn sign_receipt(claims: &ReceiptClaims, key: &[u8]) -> Result<String, String> {
+    let payload = serde_json::to_vec(claims).map_err(|e| e.to_string())?;
+
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(&payload);
+    let tag = mac.finalize().into_bytes();
+
+    Ok(format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), URL_SAFE_NO_PAD.encode(tag)))
+}
+
+/// Check a receipt's MAC and expiry and return its claims
+pub fn verify_receipt(token: &str, key: &[u8]) -> Result<ReceiptClaims, String> {
+    let (payload_b64, tag_b64) = token.split_once('.').ok_or("Malformed receipt")?;
+    let payload = URL_SAFE_NO_PAD.decode(payload_b64).map_err(|e| e.to_string())?;
+    let tag = URL_SAFE_NO_PAD.decode(tag_b64).map_err(|e| e.to_string())?;
+
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(&payload);
+    mac.verify_slice(&tag).map_err(|_| "Receipt MAC mismatch".to_string())?;
+
+    let claims: ReceiptClaims = serde_json::from_slice(&payload).map_err(|e| e.to_string())?;
+    if chrono::Utc::now().timestamp() > claims.exp {
+        return Err("Receipt expired".to_string());
+    }
+
+    Ok(claims)
+}
+
+/// Read the claims of a receipt without checking the MAC (client-side display only)
+pub fn peek_receipt(token: &str) -> Result<ReceiptClaims, String> {
+    let (payload_b64, _) = token.split_once('.').ok_or("Malformed receipt")?;
+    let payload = URL_SAFE_NO_PAD.decode(payload_b64).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&payload).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_roundtrip_and_tamper() {
+        let key = [7u8; 32];
+        let claims = ReceiptClaims::new("test-server", "user_101", Factor::Fingerprint, sha256_hex(b"match"));
+        let token = sign_receipt(&claims, &key).unwrap();
+
+        assert_eq!(verify_receipt(&token, &key).unwrap(), claims);
+        assert!(verify_receipt(&token, &[8u8; 32]).is_err());
+
+        let forged = format!("{}x", token);
+        assert!(verify_receipt(&forged, &key).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Where the server evaluates its circuits: tfhe's CPU backend, or with the
+//! `gpu` feature its CUDA backend.
+//!
+//! Gate bootstrapping dominates a verification, and on a GPU it runs many
+//! times faster. A CUDA server key can only be made from a compressed server
+//! key, so a client that wants GPU evaluation also sends one when it
+//! registers (`FINGERPRINT_GPU_KEY`). Ciphertexts move to the device the
+//! first time an operation runs under a CUDA key; the circuits themselves
+//! are the same on both backends.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tfhe::{set_server_key, ServerKey};
+
+use crate::error::FingerprintError;
+
+#[cfg(feature = "gpu")]
+use tfhe::{CompressedServerKey, CudaServerKey};
+
+/// Backend a job's FHE operations run on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputeBackend {
+    #[default]
+    Cpu,
+    Gpu,    // tfhe's CUDA backend
+}
+
+impl fmt::Display for ComputeBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ComputeBackend::Cpu => "cpu",
+            ComputeBackend::Gpu => "gpu",
+        })
+    }
+}
+
+impl FromStr for ComputeBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cpu" => Ok(ComputeBackend::Cpu),
+            "gpu" => Ok(ComputeBackend::Gpu),
+            other => Err(format!("Unknown compute backend '{}' (cpu, gpu)", other)),
+        }
+    }
+}
+
+/// Server key of the backend that evaluates a job
+#[derive(Clone)]
+pub enum EvaluationKey {
+    Cpu(ServerKey),
+    #[cfg(feature = "gpu")]
+    Gpu(CudaServerKey),
+}
+
+impl EvaluationKey {
+    /// CUDA server key from a serialized `CompressedServerKey` (see [`gpu_available`])
+    pub fn gpu_from_compressed(bytes: &[u8]) -> Result<Self, FingerprintError> {
+        #[cfg(feature = "gpu")]
+        {
+            let compressed: CompressedServerKey = bincode::deserialize(bytes)?;
+            Ok(EvaluationKey::Gpu(compressed.decompress_to_gpu()))
+        }
+        #[cfg(not(feature = "gpu"))]
+        {
+            Err(FingerprintError::Fhe(format!("Built without the `gpu` feature, can't use a {}-byte compressed key", bytes.len())))
+        }
+    }
+
+    pub fn backend(&self) -> ComputeBackend {
+        match self {
+            EvaluationKey::Cpu(_) => ComputeBackend::Cpu,
+            #[cfg(feature = "gpu")]
+            EvaluationKey::Gpu(_) => ComputeBackend::Gpu,
+        }
+    }
+
+    /// Make this the calling thread's tfhe server key
+    pub fn install(&self) {
+        match self {
+            EvaluationKey::Cpu(key) => set_server_key(key.clone()),
+            #[cfg(feature = "gpu")]
+            EvaluationKey::Gpu(key) => set_server_key(key.clone()),
+        }
+    }
+}
+
+impl From<ServerKey> for EvaluationKey {
+    fn from(key: ServerKey) -> Self {
+        EvaluationKey::Cpu(key)
+    }
+}
+
+/// Whether this build can evaluate on a GPU and a CUDA device is present
+pub fn gpu_available() -> bool {
+    #[cfg(feature = "gpu")]
+    {
+        tfhe::core_crypto::gpu::get_number_of_gpus() > 0
+    }
+    #[cfg(not(feature = "gpu"))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_names_round_trip() {
+        for backend in [ComputeBackend::Cpu, ComputeBackend::Gpu] {
+            assert_eq!(backend.to_string().parse::<ComputeBackend>(), Ok(backend));
+            assert_eq!(serde_json::to_string(&backend).unwrap(), format!("\"{}\"", backend));
+        }
+        assert!("cuda".parse::<ComputeBackend>().is_err());
+        if cfg!(not(feature = "gpu")) {
+            assert!(!gpu_available());
+        }
+    }
+}
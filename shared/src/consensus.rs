@@ -0,0 +1,117 @@
+// shared/src/consensus.rs
+
+//! Multi-sample enrollment.
+//!
+//! The client captures several images of the finger and enrolls the bitwise
+//! majority of their templates. Bits the samples disagree on flip between
+//! captures; the reliability mask flags the others, and verifications of the
+//! primary finger compare only those. Like a partial probe's coverage mask
+//! the reliability mask travels in the clear: it tells the server which bits
+//! are stable, not their values. The individual samples are enrolled too,
+//! each under its own key/IV, so the consensus can be recomputed later.
+
+/// Captures a multi-sample enrollment needs at least (one primary and two more)
+pub const MIN_SAMPLES: usize = 3;
+
+/// Captures accepted at most (each carries its own FHE-encrypted key/IV)
+pub const MAX_SAMPLES: usize = 9;
+
+/// A bit is reliable when at least this share of the samples has the majority value (percent)
+pub const RELIABLE_AGREEMENT_PERCENT: usize = 80;
+
+/// Masks must keep at least this share of the template bits (percent)
+pub const MIN_RELIABLE_PERCENT: usize = 50;
+
+pub fn check_sample_count(count: usize) -> Result<(), String> {
+    if (MIN_SAMPLES..=MAX_SAMPLES).contains(&count) {
+        Ok(())
+    } else {
+        Err(format!("Multi-sample enrollment takes {} to {} captures, got {}", MIN_SAMPLES, MAX_SAMPLES, count))
+    }
+}
+
+/// Bitwise majority of equally long samples; a tie keeps the first sample's bit
+pub fn majority_vote(samples: &[Vec<bool>]) -> Vec<bool> {
+    let Some(first) = samples.first() else { return Vec::new() };
+    (0..first.len())
+        .map(|i| {
+            let set = samples.iter().filter(|s| s[i]).count();
+            match (set * 2).cmp(&samples.len()) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal => first[i],
+            }
+        })
+        .collect()
+}
+
+/// Bits on which enough samples agree with `consensus`
+pub fn reliability_mask(samples: &[Vec<bool>], consensus: &[bool]) -> Vec<bool> {
+    consensus
+        .iter()
+        .enumerate()
+        .map(|(i, &bit)| {
+            let agreeing = samples.iter().filter(|s| s[i] == bit).count();
+            agreeing * 100 >= samples.len() * RELIABLE_AGREEMENT_PERCENT
+        })
+        .collect()
+}
+
+/// Check a reliability mask against the template length; returns the reliable bits
+pub fn check_reliability_mask(mask: &[bool], bits: usize) -> Result<usize, String> {
+    if mask.len() != bits {
+        return Err(format!("Reliability mask has {} bits, the template {}", mask.len(), bits));
+    }
+    let reliable = mask.iter().filter(|&&r| r).count();
+    if reliable * 100 < bits * MIN_RELIABLE_PERCENT {
+        return Err(format!(
+            "Only {} of {} bits are reliable, at least {}% are needed; recapture the samples",
+            reliable, bits, MIN_RELIABLE_PERCENT
+        ));
+    }
+    Ok(reliable)
+}
+
+/// Template bits compared: the reliable ones, within `covered` for a partial probe
+pub fn reliable_positions(mask: &[bool], covered: Option<&[usize]>) -> Vec<usize> {
+    match covered {
+        Some(covered) => covered.iter().copied().filter(|&i| mask[i]).collect(),
+        None => (0..mask.len()).filter(|&i| mask[i]).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn majority_and_reliability() {
+        let samples = vec![
+            vec![true, true, false, true, false],
+            vec![true, false, false, true, true],
+            vec![true, true, false, false, false],
+            vec![true, true, false, true, true],
+            vec![true, true, false, true, false],
+        ];
+        let consensus = majority_vote(&samples);
+        assert_eq!(consensus, vec![true, true, false, true, false]);
+        // 5/5, 4/5, 5/5, 4/5 agree; the last bit only 3/5
+        assert_eq!(reliability_mask(&samples, &consensus), vec![true, true, true, true, false]);
+
+        // Ties keep the first sample
+        assert_eq!(majority_vote(&[vec![false, true], vec![true, false]]), vec![false, true]);
+    }
+
+    #[test]
+    fn masks_restrict_the_compared_bits() {
+        let mask = [true, false, true, true];
+        assert_eq!(check_reliability_mask(&mask, 4), Ok(3));
+        assert!(check_reliability_mask(&mask, 8).is_err());
+        assert!(check_reliability_mask(&[true, false, false, false], 4).is_err());
+
+        assert_eq!(reliable_positions(&mask, None), vec![0, 2, 3]);
+        assert_eq!(reliable_positions(&mask, Some(&[0, 1, 2])), vec![0, 2]);
+        assert!(check_sample_count(2).is_err());
+        assert!(check_sample_count(MIN_SAMPLES).is_ok());
+    }
+}
@@ -0,0 +1,146 @@
+// shared/src/delta.rs
+
+//! Delta re-enrollment: refresh only the regions of a template that drifted.
+//!
+//! The client keeps the Trivium key/IV of its enrollment and encrypts the new
+//! bits of the changed regions further down the same keystream, starting
+//! where the enrolled ciphertext (and earlier deltas) ended. No keystream
+//! position is ever used twice, and the FHE-encrypted key/IV the server
+//! already holds decrypt the delta too, so an update uploads no FHE data.
+//!
+//! At verify time the server transciphers the stored ciphertext followed by
+//! all deltas in one pass and splices each delta's bits over its regions.
+//! Region offsets are visible to the server; the bits are not.
+
+use serde::{Deserialize, Serialize};
+
+/// Granularity of changed regions
+pub const DEFAULT_REGION_BITS: usize = 64;
+
+/// Accepted delta bits per enrollment before a full re-registration is needed.
+/// Each delta bit adds one keystream step to every verification.
+pub const MAX_DELTA_BITS: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaRegion {
+    pub offset: usize,                  // First template bit replaced
+    pub len: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateDelta {
+    pub regions: Vec<DeltaRegion>,      // Ascending, non-overlapping
+    pub stream_offset: usize,           // Keystream position of the first ciphertext bit
+    pub ciphertext: Vec<bool>,          // New bits of all regions, concatenated, Trivium encrypted
+}
+
+impl TemplateDelta {
+    pub fn bits(&self) -> usize {
+        self.regions.iter().map(|r| r.len).sum()
+    }
+
+    /// Check the regions against the template length and the ciphertext
+    pub fn validate(&self, template_bits: usize) -> Result<(), String> {
+        if self.regions.is_empty() {
+            return Err("Delta has no regions".to_string());
+        }
+        let mut end = 0;
+        for region in &self.regions {
+            // Checked: offsets come from the client and a wrapped end would pass the bound
+            match region.offset.checked_add(region.len) {
+                Some(region_end) if region.len > 0 && region.offset >= end && region_end <= template_bits => {
+                    end = region_end;
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid delta region {}+{} (template has {} bits)",
+                        region.offset, region.len, template_bits
+                    ));
+                }
+            }
+        }
+        if self.bits() != self.ciphertext.len() {
+            return Err(format!("Delta covers {} bits but carries {}", self.bits(), self.ciphertext.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Regions of `region_bits` where `new` differs from `old`
+pub fn changed_regions(old: &[bool], new: &[bool], region_bits: usize) -> Vec<DeltaRegion> {
+    let region_bits = region_bits.max(1);
+    (0..old.len().min(new.len()))
+        .step_by(region_bits)
+        .filter_map(|offset| {
+            let end = (offset + region_bits).min(old.len()).min(new.len());
+            (old[offset..end] != new[offset..end]).then_some(DeltaRegion { offset, len: end - offset })
+        })
+        .collect()
+}
+
+/// Bits of `template` inside `regions`, concatenated
+pub fn region_bits(template: &[bool], regions: &[DeltaRegion]) -> Vec<bool> {
+    regions
+        .iter()
+        .flat_map(|r| template[r.offset..r.offset + r.len].iter().copied())
+        .collect()
+}
+
+/// Overwrite `regions` of `template` with `bits` (works on plaintext and FHE bits alike)
+pub fn splice<T: Clone>(template: &mut [T], regions: &[DeltaRegion], bits: &[T]) {
+    let mut source = bits.iter();
+    for region in regions {
+        for (slot, bit) in template[region.offset..region.offset + region.len].iter_mut().zip(&mut source) {
+            *slot = bit.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splicing_changed_regions_reproduces_new_template() {
+        let old: Vec<bool> = (0..256).map(|i| i % 3 == 0).collect();
+        let mut new = old.clone();
+        new[5] = !new[5];
+        new[200] = !new[200];
+
+        let regions = changed_regions(&old, &new, 64);
+        assert_eq!(regions, [DeltaRegion { offset: 0, len: 64 }, DeltaRegion { offset: 192, len: 64 }]);
+
+        let bits = region_bits(&new, &regions);
+        let mut spliced = old.clone();
+        splice(&mut spliced, &regions, &bits);
+        assert_eq!(spliced, new);
+    }
+
+    #[test]
+    fn rejects_overlapping_or_out_of_range_regions() {
+        let delta = |regions: Vec<DeltaRegion>| {
+            let bits = regions.iter().map(|r| r.len).sum();
+            TemplateDelta { regions, stream_offset: 1024, ciphertext: vec![false; bits] }
+        };
+        assert!(delta(vec![DeltaRegion { offset: 0, len: 8 }, DeltaRegion { offset: 64, len: 8 }]).validate(128).is_ok());
+        assert!(delta(vec![DeltaRegion { offset: 0, len: 8 }, DeltaRegion { offset: 4, len: 8 }]).validate(128).is_err());
+        assert!(delta(vec![DeltaRegion { offset: 120, len: 16 }]).validate(128).is_err());
+        assert!(delta(vec![]).validate(128).is_err());
+    }
+
+    #[test]
+    fn rejects_regions_whose_end_overflows() {
+        let delta = TemplateDelta {
+            regions: vec![DeltaRegion { offset: usize::MAX, len: 2 }],
+            stream_offset: 1024,
+            ciphertext: vec![false; 2],
+        };
+        assert!(delta.validate(128).is_err());
+        let delta = TemplateDelta {
+            regions: vec![DeltaRegion { offset: 0, len: 8 }, DeltaRegion { offset: usize::MAX, len: 1 }],
+            stream_offset: 1024,
+            ciphertext: vec![false; 9],
+        };
+        assert!(delta.validate(128).is_err());
+    }
+}
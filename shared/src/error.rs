@@ -0,0 +1,205 @@
+//! Typed errors shared by client and server, and the codes responses carry.
+//!
+//! Functions that can tell what went wrong return [`FingerprintError`];
+//! most callers still propagate `Box<dyn Error>`, which keeps the variant
+//! for [`ErrorCode::of`] to find. Responses carry the code next to their
+//! message, so a client can react to the kind of failure (re-register
+//! after a key mismatch, retry after a storage error, register first when
+//! the user is not found, fix the API key when unauthorized) without
+//! parsing text. Failures nothing answered in detail are sent as the code's
+//! [`ErrorCode::message`]; the detail stays in the server log.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::cancel::Cancelled;
+
+/// What went wrong, with the message shown to the user
+#[derive(Debug, thiserror::Error)]
+pub enum FingerprintError {
+    /// A request or response that breaks the protocol: sizes, parameters, combinations of fields
+    #[error("{0}")]
+    Protocol(String),
+    /// Bytes that don't decode (bincode, JSON)
+    #[error("{0}")]
+    Serialization(String),
+    /// Material that doesn't belong together: server key, transform, key/IV sizes
+    #[error("{0}")]
+    KeyMismatch(String),
+    /// Keys, templates or job files missing or unreadable
+    #[error("{0}")]
+    Storage(String),
+    /// Homomorphic evaluation or the backend it runs on
+    #[error("{0}")]
+    Fhe(String),
+    /// No enrollment for the user the request names
+    #[error("{0}")]
+    NotFound(String),
+    /// API key or proof of the caller not accepted
+    #[error("{0}")]
+    Unauthorized(String),
+    /// The job was cancelled before it finished
+    #[error("{0}")]
+    Cancelled(String),
+    /// A failure the server didn't classify
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl FingerprintError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            FingerprintError::Protocol(_) => ErrorCode::Protocol,
+            FingerprintError::Serialization(_) => ErrorCode::Serialization,
+            FingerprintError::KeyMismatch(_) => ErrorCode::KeyMismatch,
+            FingerprintError::Storage(_) => ErrorCode::Storage,
+            FingerprintError::Fhe(_) => ErrorCode::Fhe,
+            FingerprintError::NotFound(_) => ErrorCode::NotFound,
+            FingerprintError::Unauthorized(_) => ErrorCode::Unauthorized,
+            FingerprintError::Cancelled(_) => ErrorCode::Cancelled,
+            FingerprintError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// Error of a failed response, from its code (None for servers that send none)
+    pub fn from_response(code: Option<ErrorCode>, message: String) -> Self {
+        match code {
+            Some(ErrorCode::Serialization) => FingerprintError::Serialization(message),
+            Some(ErrorCode::KeyMismatch) => FingerprintError::KeyMismatch(message),
+            Some(ErrorCode::Storage) => FingerprintError::Storage(message),
+            Some(ErrorCode::Fhe) => FingerprintError::Fhe(message),
+            Some(ErrorCode::NotFound) => FingerprintError::NotFound(message),
+            Some(ErrorCode::Unauthorized) => FingerprintError::Unauthorized(message),
+            Some(ErrorCode::Cancelled) => FingerprintError::Cancelled(message),
+            Some(ErrorCode::Internal) => FingerprintError::Internal(message),
+            Some(ErrorCode::Protocol) | None => FingerprintError::Protocol(message),
+        }
+    }
+}
+
+impl From<bincode::Error> for FingerprintError {
+    fn from(e: bincode::Error) -> Self {
+        FingerprintError::Serialization(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for FingerprintError {
+    fn from(e: serde_json::Error) -> Self {
+        FingerprintError::Serialization(e.to_string())
+    }
+}
+
+impl From<Cancelled> for FingerprintError {
+    fn from(e: Cancelled) -> Self {
+        FingerprintError::Cancelled(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for FingerprintError {
+    fn from(e: std::io::Error) -> Self {
+        FingerprintError::Storage(e.to_string())
+    }
+}
+
+/// Kind of a failure, as sent in responses
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Protocol,
+    Serialization,
+    KeyMismatch,
+    Storage,
+    Fhe,
+    NotFound,       // No enrollment for the user
+    Unauthorized,   // API key or caller proof not accepted
+    Cancelled,      // The job was cancelled (on request or at shutdown)
+    Internal,       // Anything not classified above
+}
+
+impl ErrorCode {
+    /// Code of any error, looking through the error types the code base propagates
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(e) = error.downcast_ref::<FingerprintError>() {
+            e.code()
+        } else if error.is::<Cancelled>() {
+            ErrorCode::Cancelled
+        } else if error.is::<std::io::Error>() {
+            ErrorCode::Storage
+        } else if error.is::<bincode::Error>() || error.is::<serde_json::Error>() {
+            ErrorCode::Serialization
+        } else {
+            ErrorCode::Internal
+        }
+    }
+
+    /// What a client is told when only the code is sent: no paths, keys or
+    /// other server detail
+    pub fn message(self) -> &'static str {
+        match self {
+            ErrorCode::Protocol => "The request was not valid for this server",
+            ErrorCode::Serialization => "The request could not be decoded",
+            ErrorCode::KeyMismatch => "The keys do not match the enrollment; register again",
+            ErrorCode::Storage => "The server could not read or write its storage; try again later",
+            ErrorCode::Fhe => "The encrypted evaluation failed; try again later",
+            ErrorCode::NotFound => "No enrollment was found for this user",
+            ErrorCode::Unauthorized => "The API key or proof was not accepted",
+            ErrorCode::Cancelled => "The job was cancelled",
+            ErrorCode::Internal => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorCode::Protocol => "protocol",
+            ErrorCode::Serialization => "serialization",
+            ErrorCode::KeyMismatch => "key_mismatch",
+            ErrorCode::Storage => "storage",
+            ErrorCode::Fhe => "fhe",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Cancelled => "cancelled",
+            ErrorCode::Internal => "internal",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxed_errors_keep_their_code() {
+        let boxed: Box<dyn std::error::Error> = FingerprintError::KeyMismatch("wrong key".into()).into();
+        assert_eq!(ErrorCode::of(boxed.as_ref()), ErrorCode::KeyMismatch);
+        assert_eq!(boxed.to_string(), "wrong key");
+
+        let io: Box<dyn std::error::Error> = std::io::Error::other("gone").into();
+        assert_eq!(ErrorCode::of(io.as_ref()), ErrorCode::Storage);
+        let json: Box<dyn std::error::Error> = serde_json::from_str::<u8>("x").unwrap_err().into();
+        assert_eq!(ErrorCode::of(json.as_ref()), ErrorCode::Serialization);
+        let bincode: Box<dyn std::error::Error> = bincode::deserialize::<u64>(&[1]).unwrap_err().into();
+        assert_eq!(ErrorCode::of(bincode.as_ref()), ErrorCode::Serialization);
+        assert_eq!(ErrorCode::of(Box::<dyn std::error::Error>::from("text").as_ref()), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn codes_round_trip_through_responses() {
+        for code in [
+            ErrorCode::Protocol,
+            ErrorCode::Serialization,
+            ErrorCode::KeyMismatch,
+            ErrorCode::Storage,
+            ErrorCode::Fhe,
+            ErrorCode::NotFound,
+            ErrorCode::Unauthorized,
+            ErrorCode::Cancelled,
+            ErrorCode::Internal,
+        ] {
+            assert_eq!(serde_json::to_string(&code).unwrap(), format!("\"{}\"", code));
+            assert_eq!(FingerprintError::from_response(Some(code), "m".into()).code(), code);
+        }
+        assert_eq!(FingerprintError::from_response(None, "m".into()).code(), ErrorCode::Protocol);
+    }
+}
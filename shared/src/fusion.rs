@@ -0,0 +1,108 @@
+//! Multi-finger enrollment: two to four fingers decided on together.
+//!
+//! The primary finger is enrolled as before and the others come along with
+//! it, each under its own key/IV. A verification then carries a probe of
+//! every enrolled finger, in enrollment order (one slap capture, say). The
+//! server matches each probe against its finger and fuses the encrypted
+//! decisions with the enrollment's rule, so the client decrypts one decision
+//! and nobody learns which fingers matched. Unlike a `second_finger`
+//! fallback factor, which is tried after the primary one fails, all fingers
+//! are compared in the same verification.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{BitAnd, BitOr};
+use std::str::FromStr;
+
+/// Fewest fingers of a fused enrollment, the primary one included
+pub const MIN_FINGERS: usize = 2;
+
+/// Most fingers of a fused enrollment (each one more decryption and match per verification)
+pub const MAX_FINGERS: usize = 4;
+
+/// How the per-finger decisions make the verification's
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionRule {
+    #[default]
+    AnyFinger,  // Any finger matching is enough
+    TwoOfN,     // At least two fingers must match
+}
+
+impl fmt::Display for FusionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FusionRule::AnyFinger => "any",
+            FusionRule::TwoOfN => "two-of-n",
+        })
+    }
+}
+
+impl FromStr for FusionRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(FusionRule::AnyFinger),
+            "two-of-n" => Ok(FusionRule::TwoOfN),
+            other => Err(format!("Unknown fusion rule '{}' (any, two-of-n)", other)),
+        }
+    }
+}
+
+/// Check the number of fingers of a fused enrollment, the primary one included
+pub fn check_finger_count(count: usize) -> Result<(), String> {
+    if !(MIN_FINGERS..=MAX_FINGERS).contains(&count) {
+        return Err(format!("A fused enrollment has {} to {} fingers, got {}", MIN_FINGERS, MAX_FINGERS, count));
+    }
+    Ok(())
+}
+
+/// The fused decision over per-finger decisions (plain or FHE-encrypted bits),
+/// in one pass: `two` holds "two matched so far" once a second finger was seen.
+/// None for fewer than `MIN_FINGERS` decisions.
+pub fn fuse<T>(matches: &[T], rule: FusionRule) -> Option<T>
+where
+    T: Clone,
+    for<'a> &'a T: BitAnd<&'a T, Output = T> + BitOr<&'a T, Output = T>,
+{
+    let (first, rest) = matches.split_first()?;
+    if rest.is_empty() {
+        return None;
+    }
+    let mut any = first.clone();
+    let mut two: Option<T> = None;
+    for matched in rest {
+        let pair = &any & matched;
+        two = Some(match two {
+            Some(two) => &two | &pair,
+            None => pair,
+        });
+        any = &any | matched;
+    }
+    match rule {
+        FusionRule::AnyFinger => Some(any),
+        FusionRule::TwoOfN => two,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fusion_rules_over_every_combination() {
+        for fingers in MIN_FINGERS..=MAX_FINGERS {
+            for combination in 0..1u32 << fingers {
+                let matches: Vec<bool> = (0..fingers).map(|i| combination >> i & 1 == 1).collect();
+                let matched = combination.count_ones();
+                assert_eq!(fuse(&matches, FusionRule::AnyFinger), Some(matched >= 1));
+                assert_eq!(fuse(&matches, FusionRule::TwoOfN), Some(matched >= 2));
+            }
+        }
+        assert_eq!(fuse(&[true], FusionRule::AnyFinger), None);
+        assert!(check_finger_count(1).is_err() && check_finger_count(5).is_err());
+        assert_eq!("two-of-n".parse::<FusionRule>(), Ok(FusionRule::TwoOfN));
+        assert_eq!(FusionRule::AnyFinger.to_string().parse::<FusionRule>(), Ok(FusionRule::AnyFinger));
+    }
+}
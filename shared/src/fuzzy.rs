@@ -0,0 +1,701 @@
+//! Fuzzy extractor mode: a secure sketch with BCH error correction instead
+//! of a Hamming threshold.
+//!
+//! At enrollment the client picks a random key, encodes it with a binary BCH
+//! code and sends the template XOR that codeword as helper data (the
+//! code-offset sketch), FHE-encrypted under its client key, with the SHA-256
+//! of the key. In the clear the helper would give away the template's
+//! syndromes and link enrollments of the same finger. The template is cut
+//! into blocks of at most 2^m - 1 bits, each a shortened BCH code over
+//! GF(2^m) correcting t errors, whose first bits are parity and the rest key
+//! bits.
+//!
+//! t is chosen per enrollment (`FINGERPRINT_FUZZY_ERRORS`) from a measurement:
+//! genuine captures differ in 10-25% of their bits, far more than the 6.5%
+//! two errors per 31-bit block correct. `eval` counts, for every genuine and
+//! impostor pair of a dataset, the most differing bits of any block
+//! (`max_block_errors`); a pair matches exactly when that count is at most t,
+//! so it gives the FRR and FAR of each t.
+//!
+//! At verification the server XORs the decrypted probe with the decrypted
+//! helper and corrects every block homomorphically. The syndromes S1..S2t are
+//! XORs of bits; an inversionless Berlekamp-Massey run over them, with every
+//! branch taken obliviously, gives the error locator for 3(t + 1) encrypted
+//! field multiplications per step, and a Chien search flips each bit whose
+//! position is a root of it, m ANDs per bit. The probe matches when every
+//! block corrects back to the enrolled codeword and the corrected key bits
+//! hash to the enrolled key hash, SHA-256 evaluated gate by gate; the number
+//! of corrected bits is the distance. The key bits also go back to the
+//! client, which checks the hash again.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ops::{Range, RangeInclusive};
+use tfhe::prelude::*;
+use tfhe::{ClientKey, FheBool};
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::matching_fhe::fhe_false_like;
+
+/// Field size of the code unless configured otherwise: blocks of up to 31 bits
+pub const DEFAULT_FIELD_BITS: u32 = 5;
+
+/// Field sizes with a primitive polynomial below
+pub const FIELD_BITS: RangeInclusive<u32> = 4..=10;
+
+/// Helper data of a fuzzy enrollment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FuzzySketch {
+    pub field_bits: u32,        // m: blocks of at most 2^m - 1 bits, coded over GF(2^m)
+    #[serde(default)]
+    pub errors: usize,          // t: bit errors each block corrects
+    #[serde(default)]
+    pub encrypted_helper_bytes: Vec<u8>, // Vec<FheBool>: template XOR the codeword of the key
+    pub key_hash: String,       // Hex SHA-256 of the key bits
+}
+
+impl FuzzySketch {
+    /// Check a sketch received with a template of `template_bits`; the helper's
+    /// length is checked where it is deserialized
+    pub fn validate(&self, template_bits: usize) -> Result<(), String> {
+        if self.encrypted_helper_bytes.is_empty() {
+            return Err("Fuzzy sketch has no encrypted helper data".to_string());
+        }
+        if digest_words(&self.key_hash).is_none() {
+            return Err("Fuzzy sketch key hash is not a hex SHA-256".to_string());
+        }
+        Code::new(self.field_bits, self.errors, template_bits).map(drop)
+    }
+
+    /// Key bits the sketch protects in a template of `template_bits`
+    pub fn key_bits(&self, template_bits: usize) -> usize {
+        key_bits(self.field_bits, self.errors, template_bits)
+    }
+}
+
+/// Sketch of `template` under a fresh random key, correcting `errors` bits per
+/// block, its helper encrypted under `client_key`; returns the key too
+pub fn sketch(
+    template: &[bool],
+    field_bits: u32,
+    errors: usize,
+    client_key: &ClientKey,
+) -> Result<(FuzzySketch, Vec<bool>), String> {
+    let (helper, key) = code_offset(template, field_bits, errors)?;
+    let helper: Vec<FheBool> = helper.iter().map(|&h| FheBool::encrypt(h, client_key)).collect();
+    let encrypted_helper_bytes = bincode::serialize(&helper).map_err(|e| e.to_string())?;
+    Ok((FuzzySketch { field_bits, errors, encrypted_helper_bytes, key_hash: key_hash(&key) }, key))
+}
+
+/// Template XOR the codeword of a fresh random key, and the key
+fn code_offset(template: &[bool], field_bits: u32, errors: usize) -> Result<(Vec<bool>, Vec<bool>), String> {
+    let code = Code::new(field_bits, errors, template.len())?;
+    let key: Vec<bool> = (0..code.key_bits()).map(|_| rand::random()).collect();
+    let helper = code.encode(&key).iter().zip(template).map(|(c, w)| c ^ w).collect();
+    Ok((helper, key))
+}
+
+/// Most bits two templates differ in within one block over GF(2^field_bits):
+/// a probe matches a fuzzy enrollment exactly when this is at most its t
+pub fn max_block_errors(a: &[bool], b: &[bool], field_bits: u32) -> usize {
+    blocks(field_bits, a.len().min(b.len()))
+        .into_iter()
+        .map(|block| block.filter(|&i| a[i] != b[i]).count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Largest t a template of `template_bits` can be coded with over GF(2^field_bits) (0 = none)
+pub fn capacity(field_bits: u32, template_bits: usize) -> usize {
+    (1..).take_while(|&errors| Code::new(field_bits, errors, template_bits).is_ok()).last().unwrap_or(0)
+}
+
+/// Key bits a sketch correcting `errors` bits per block protects (0 = no such code)
+pub fn key_bits(field_bits: u32, errors: usize, template_bits: usize) -> usize {
+    Code::new(field_bits, errors, template_bits).map_or(0, |code| code.key_bits())
+}
+
+/// The key a verification returned is the enrolled one
+pub fn check_key(key: &[bool], sketch_hash: &str) -> bool {
+    key_hash(key) == sketch_hash
+}
+
+pub fn key_hash(key: &[bool]) -> String {
+    let bytes: Vec<u8> = key.chunks(8).map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, &b)| acc | ((b as u8) << i))).collect();
+    crate::sealed::hex(&Sha256::digest(&bytes))
+}
+
+/// Encrypted result of matching a probe against a fuzzy enrollment
+pub struct FuzzyMatch {
+    pub matched: FheBool,       // Every block corrected back to the enrolled codeword, whose key hashes right
+    pub flips: Vec<FheBool>,    // Bits the correction flipped (their count is the distance)
+    pub key: Vec<FheBool>,      // Key bits of the corrected probe
+}
+
+/// Correct the decrypted probe with the decrypted helper and compare it with the
+/// decrypted enrolled template, then hash the corrected key bits against the sketch's key hash
+pub fn match_sketch(
+    enrolled: &[FheBool],
+    probe: &[FheBool],
+    helper: &[FheBool],
+    sketch: &FuzzySketch,
+    cancel: &CancellationToken,
+) -> Result<FuzzyMatch, Cancelled> {
+    assert_eq!(enrolled.len(), probe.len());
+    assert_eq!(probe.len(), helper.len());
+    let code = Code::new(sketch.field_bits, sketch.errors, probe.len()).expect("sketch validated at enrollment");
+    let fhe_true = &!fhe_false_like(probe);
+    let word: Vec<FheBool> = probe.iter().zip(helper).map(|(p, h)| p ^ h).collect();
+    let Corrected { corrected, flips } = code.correct(&word, fhe_true, cancel)?;
+
+    // Enrolled codeword = enrolled template XOR helper
+    let mut matched = fhe_true.clone();
+    for ((c, w), h) in corrected.iter().zip(enrolled).zip(helper) {
+        cancel.check()?;
+        let differs = &(c ^ w) ^ h;
+        matched = &matched & &!&differs;
+    }
+    let key = code.key(&corrected);
+    let hashed = hashes_to(&key, &sketch.key_hash, fhe_true, cancel)?.expect("sketch validated at enrollment");
+    Ok(FuzzyMatch { matched: &matched & &hashed, flips, key })
+}
+
+/// Gates the correction circuit needs, in the clear or encrypted
+trait Bit: Clone {
+    fn xor(&self, other: &Self) -> Self;
+    fn and(&self, other: &Self) -> Self;
+}
+
+impl Bit for bool {
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn and(&self, other: &Self) -> Self {
+        self & other
+    }
+}
+
+impl Bit for FheBool {
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn and(&self, other: &Self) -> Self {
+        self & other
+    }
+}
+
+struct Corrected<B> {
+    corrected: Vec<B>,
+    flips: Vec<B>,
+}
+
+/// GF(2^m) from a primitive polynomial: α^i for i < 2^m - 1, as m-bit vectors
+struct Field {
+    m: u32,
+    exp: Vec<u32>,
+    log: Vec<usize>,            // log[α^i] = i; log[0] unused
+}
+
+impl Field {
+    fn new(m: u32) -> Self {
+        let poly = match m {
+            4 => 0x13,      // x^4 + x + 1
+            5 => 0x25,      // x^5 + x^2 + 1
+            6 => 0x43,      // x^6 + x + 1
+            7 => 0x89,      // x^7 + x^3 + 1
+            8 => 0x11d,     // x^8 + x^4 + x^3 + x^2 + 1
+            9 => 0x211,     // x^9 + x^4 + 1
+            10 => 0x409,    // x^10 + x^3 + 1
+            _ => unreachable!("field size checked by Code::new"),
+        };
+        let mut exp = Vec::with_capacity((1 << m) - 1);
+        let mut log = vec![0; 1 << m];
+        let mut x = 1u32;
+        for i in 0..(1usize << m) - 1 {
+            exp.push(x);
+            log[x as usize] = i;
+            x <<= 1;
+            if x & (1 << m) != 0 {
+                x ^= poly;
+            }
+        }
+        Self { m, exp, log }
+    }
+
+    fn alpha(&self, i: usize) -> u32 {
+        self.exp[i % self.exp.len()]
+    }
+
+    fn mul(&self, a: u32, b: u32) -> u32 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.alpha(self.log[a as usize] + self.log[b as usize])
+    }
+
+    /// Minimal polynomial of α^i over GF(2), bit k = coefficient of x^k
+    fn minimal_polynomial(&self, i: usize) -> u64 {
+        let mut conjugates = vec![i % self.exp.len()];
+        loop {
+            let next = conjugates.last().unwrap() * 2 % self.exp.len();
+            if next == conjugates[0] {
+                break;
+            }
+            conjugates.push(next);
+        }
+        // Product of (x + α^c) with field coefficients, which all end up 0 or 1
+        let mut poly = vec![1u32];
+        for c in conjugates {
+            let root = self.alpha(c);
+            let mut next = vec![0u32; poly.len() + 1];
+            for (k, &coefficient) in poly.iter().enumerate() {
+                next[k + 1] ^= coefficient;
+                next[k] ^= self.mul(coefficient, root);
+            }
+            poly = next;
+        }
+        poly.iter().enumerate().fold(0, |acc, (k, &c)| acc | ((c as u64 & 1) << k))
+    }
+
+    /// Output bit j of x·c is the XOR of the bits of x whose mask has bit j set
+    fn constant_masks(&self, c: u32) -> Vec<u32> {
+        let products: Vec<u32> = (0..self.m as usize).map(|k| self.mul(c, self.alpha(k))).collect();
+        (0..self.m).map(|j| products.iter().enumerate().fold(0, |acc, (k, &p)| acc | (((p >> j) & 1) << k))).collect()
+    }
+}
+
+/// Blocks of nearly equal length, each at most 2^m - 1 bits
+fn blocks(field_bits: u32, template_bits: usize) -> Vec<Range<usize>> {
+    let count = template_bits.div_ceil((1 << field_bits) - 1).max(1);
+    let (base, extra) = (template_bits / count, template_bits % count);
+    let mut start = 0;
+    (0..count)
+        .map(|b| {
+            let len = base + (b < extra) as usize;
+            start += len;
+            start - len..start
+        })
+        .collect()
+}
+
+/// The blocks of a template and their shortened BCH code
+struct Code {
+    field: Field,
+    errors: usize,              // t
+    generator: Vec<bool>,       // g(x) = lcm of the minimal polynomials of α, α^3, ..., α^(2t-1)
+    parity: usize,              // deg g
+    blocks: Vec<Range<usize>>,
+}
+
+impl Code {
+    fn new(field_bits: u32, errors: usize, template_bits: usize) -> Result<Self, String> {
+        if !FIELD_BITS.contains(&field_bits) {
+            return Err(format!(
+                "Fuzzy sketch field size must be {} to {} bits, not {}",
+                FIELD_BITS.start(), FIELD_BITS.end(), field_bits
+            ));
+        }
+        if errors == 0 {
+            return Err("A fuzzy sketch must correct at least one bit error per block".to_string());
+        }
+        let field = Field::new(field_bits);
+        let mut minimal = Vec::new();
+        for i in (1..2 * errors).step_by(2) {
+            let poly = field.minimal_polynomial(i);
+            if !minimal.contains(&poly) {
+                minimal.push(poly);
+            }
+        }
+        let generator = minimal.iter().fold(vec![true], |g, &poly| poly_mul(&g, poly));
+        let parity = generator.len() - 1;
+
+        let blocks = blocks(field_bits, template_bits);
+        let shortest = blocks.iter().map(|block| block.len()).min().unwrap_or(0);
+        if 2 * errors >= field.exp.len() || shortest <= parity {
+            return Err(format!(
+                "A {}-bit template is too short for fuzzy blocks over GF(2^{}) correcting {} errors",
+                template_bits, field_bits, errors
+            ));
+        }
+        Ok(Self { field, errors, generator, parity, blocks })
+    }
+
+    fn key_bits(&self) -> usize {
+        self.blocks.iter().map(|block| block.len() - self.parity).sum()
+    }
+
+    /// Systematic codeword: per block the parity of x^r·d(x) mod g(x), then the key bits d
+    fn encode(&self, key: &[bool]) -> Vec<bool> {
+        let mut codeword = Vec::with_capacity(self.blocks.last().map_or(0, |b| b.end));
+        let mut key = key.iter();
+        for block in &self.blocks {
+            let data: Vec<bool> = key.by_ref().take(block.len() - self.parity).copied().collect();
+            let mut remainder = vec![false; self.parity];
+            remainder.extend(&data);
+            for k in (self.parity..remainder.len()).rev() {
+                if remainder[k] {
+                    for (j, &g) in self.generator.iter().enumerate() {
+                        remainder[k - self.parity + j] ^= g;
+                    }
+                }
+            }
+            codeword.extend(&remainder[..self.parity]);
+            codeword.extend(data);
+        }
+        codeword
+    }
+
+    fn key<B: Clone>(&self, codeword: &[B]) -> Vec<B> {
+        self.blocks.iter().flat_map(|block| codeword[block.start + self.parity..block.end].iter().cloned()).collect()
+    }
+
+    /// Correct up to t errors per block of `word`
+    fn correct<B: Bit>(&self, word: &[B], one: &B, cancel: &CancellationToken) -> Result<Corrected<B>, Cancelled> {
+        let m = self.field.m as usize;
+        let n = self.field.exp.len();
+        let zero = one.xor(one);
+        let xor_masked = |bits: &[B], mask: u32| {
+            (0..bits.len()).filter(|&k| (mask >> k) & 1 == 1).fold(zero.clone(), |acc, k| acc.xor(&bits[k]))
+        };
+        let times = |x: &[B], c: u32| -> Vec<B> { self.field.constant_masks(c).iter().map(|&mask| xor_masked(x, mask)).collect() };
+
+        let mut corrected = Vec::with_capacity(word.len());
+        let mut flips = Vec::with_capacity(word.len());
+        for block in &self.blocks {
+            cancel.check()?;
+            let bits = &word[block.clone()];
+            // S_p = Σ bits_i α^(p·i) for p = 1..2t
+            let syndromes: Vec<Vec<B>> = (1..=2 * self.errors)
+                .map(|power| {
+                    (0..m)
+                        .map(|j| {
+                            bits.iter()
+                                .enumerate()
+                                .filter(|&(i, _)| (self.field.alpha(power * i) >> j) & 1 == 1)
+                                .fold(zero.clone(), |acc, (_, b)| acc.xor(b))
+                        })
+                        .collect()
+                })
+                .collect();
+            let locator = self.locator(&syndromes, one, cancel)?;
+
+            // Position i is in error exactly when Λ(α^-i) = 0
+            for (i, bit) in bits.iter().enumerate() {
+                cancel.check()?;
+                let mut value = locator[0].clone();
+                for (j, coefficient) in locator.iter().enumerate().skip(1) {
+                    value = add(&value, &times(coefficient, self.field.alpha(n - i * j % n)));
+                }
+                let flip = is_zero(&value, one);
+                corrected.push(bit.xor(&flip));
+                flips.push(flip);
+            }
+        }
+        Ok(Corrected { corrected, flips })
+    }
+
+    /// Error locator Λ(x) of a block from its syndromes S1..S2t: the inversionless
+    /// Berlekamp-Massey algorithm, each branch taken obliviously. Its coefficients
+    /// come out scaled by a nonzero factor, which keeps its roots; with at most t
+    /// errors no step needs a degree above t.
+    fn locator<B: Bit>(&self, syndromes: &[Vec<B>], one: &B, cancel: &CancellationToken) -> Result<Vec<Vec<B>>, Cancelled> {
+        let (m, t) = (self.field.m as usize, self.errors);
+        let zero = one.xor(one);
+        let nothing = vec![zero.clone(); m];
+        let mut unit = nothing.clone();
+        unit[0] = one.clone();
+
+        let mut lambda = vec![nothing.clone(); t + 1];
+        lambda[0] = unit.clone();
+        let mut previous = lambda.clone();
+        let mut gamma = unit;
+        // k (2L against the step, as in the algorithm) one-hot over -(2t + 1)..=2t + 1
+        let offset = 2 * t + 1;
+        let mut k = vec![zero.clone(); 2 * offset + 1];
+        k[offset] = one.clone();
+
+        for r in 0..2 * t {
+            cancel.check()?;
+            let delta = (0..=t.min(r)).fold(nothing.clone(), |acc, i| add(&acc, &self.multiply(&lambda[i], &syndromes[r - i], &zero)));
+            let next: Vec<Vec<B>> = (0..=t)
+                .map(|i| {
+                    let scaled = self.multiply(&gamma, &lambda[i], &zero);
+                    match i {
+                        0 => scaled,
+                        _ => add(&scaled, &self.multiply(&delta, &previous[i - 1], &zero)),
+                    }
+                })
+                .collect();
+
+            // δ ≠ 0 and k ≥ 0: B(x) = Λ(x), γ = δ, k = -k - 1; otherwise B(x) = x·B(x), k = k + 1
+            let nonnegative = k[offset..].iter().fold(zero.clone(), |acc, slot| acc.xor(slot));
+            let grows = is_zero(&delta, one).xor(one).and(&nonnegative);
+            previous = (0..=t).map(|i| select(&grows, &lambda[i], if i == 0 { &nothing } else { &previous[i - 1] })).collect();
+            gamma = select(&grows, &delta, &gamma);
+            let slot = |value: isize| usize::try_from(value + offset as isize).ok().and_then(|s| k.get(s)).unwrap_or(&zero);
+            k = (0..k.len() as isize)
+                .map(|s| {
+                    let value = s - offset as isize;
+                    let (flipped, stepped) = (slot(-value - 1), slot(value - 1));
+                    stepped.xor(&grows.and(&flipped.xor(stepped)))
+                })
+                .collect();
+            lambda = next;
+        }
+        Ok(lambda)
+    }
+
+    /// Product of two field elements held as bits: m² ANDs, then a linear reduction
+    fn multiply<B: Bit>(&self, x: &[B], y: &[B], zero: &B) -> Vec<B> {
+        let m = self.field.m as usize;
+        let mut product = vec![zero.clone(); 2 * m - 1];
+        for (i, a) in x.iter().enumerate() {
+            for (j, b) in y.iter().enumerate() {
+                product[i + j] = product[i + j].xor(&a.and(b));
+            }
+        }
+        (0..m)
+            .map(|j| {
+                product
+                    .iter()
+                    .enumerate()
+                    .filter(|&(k, _)| (self.field.alpha(k) >> j) & 1 == 1)
+                    .fold(zero.clone(), |acc, (_, p)| acc.xor(p))
+            })
+            .collect()
+    }
+}
+
+fn add<B: Bit>(x: &[B], y: &[B]) -> Vec<B> {
+    x.iter().zip(y).map(|(a, b)| a.xor(b)).collect()
+}
+
+fn is_zero<B: Bit>(x: &[B], one: &B) -> B {
+    x.iter().fold(one.clone(), |acc, b| acc.and(&b.xor(one)))
+}
+
+/// `x` where `choice` is set, `y` elsewhere: one AND per bit
+fn select<B: Bit>(choice: &B, x: &[B], y: &[B]) -> Vec<B> {
+    x.iter().zip(y).map(|(a, b)| b.xor(&choice.and(&a.xor(b)))).collect()
+}
+
+/// Product of two GF(2) polynomials, the first as coefficients from x^0 up
+fn poly_mul(a: &[bool], b: u64) -> Vec<bool> {
+    let degree = 63 - b.leading_zeros() as usize;
+    let mut product = vec![false; a.len() + degree];
+    for k in (0..=degree).filter(|&k| (b >> k) & 1 == 1) {
+        for (i, &c) in a.iter().enumerate() {
+            product[i + k] ^= c;
+        }
+    }
+    product
+}
+
+/// SHA-256 digest as eight big-endian words, from its hex form
+fn digest_words(hash: &str) -> Option<[u32; 8]> {
+    if hash.len() != 64 || !hash.is_ascii() {
+        return None;
+    }
+    let mut words = [0u32; 8];
+    for (word, hex) in words.iter_mut().zip(hash.as_bytes().chunks(8)) {
+        *word = u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+    Some(words)
+}
+
+/// Whether `key` hashes to `hash` as `key_hash` computes it, SHA-256 evaluated gate
+/// by gate with ripple-carry additions (about 40,000 ANDs per 512-bit block).
+/// None if `hash` isn't a hex SHA-256.
+fn hashes_to<B: Bit>(key: &[B], hash: &str, one: &B, cancel: &CancellationToken) -> Result<Option<B>, Cancelled> {
+    let Some(expected) = digest_words(hash) else { return Ok(None) };
+    let zero = one.xor(one);
+    let constant = |word: u32| -> Vec<B> { (0..32).map(|i| if (word >> i) & 1 == 1 { one.clone() } else { zero.clone() }).collect() };
+    let rotate = |x: &[B], n: usize| -> Vec<B> { (0..32).map(|i| x[(i + n) % 32].clone()).collect() };
+    let shift = |x: &[B], n: usize| -> Vec<B> { (0..32).map(|i| x.get(i + n).unwrap_or(&zero).clone()).collect() };
+    let sum = |x: &[B], y: &[B]| -> Vec<B> {
+        let mut carry = zero.clone();
+        x.iter()
+            .zip(y)
+            .map(|(a, b)| {
+                let half = a.xor(b);
+                let bit = half.xor(&carry);
+                carry = a.and(b).xor(&carry.and(&half));
+                bit
+            })
+            .collect()
+    };
+
+    // Message bits, most significant first: the key packed into bytes from bit 0 up, then padding
+    let mut message = Vec::new();
+    for byte in 0..key.len().div_ceil(8) {
+        for bit in (0..8).rev() {
+            message.push(key.get(8 * byte + bit).unwrap_or(&zero).clone());
+        }
+    }
+    let length = message.len() as u64;
+    message.push(one.clone());
+    while message.len() % 512 != 448 {
+        message.push(zero.clone());
+    }
+    message.extend((0..64).rev().map(|i| if (length >> i) & 1 == 1 { one.clone() } else { zero.clone() }));
+
+    // Words hold their bits least significant first
+    let mut state: Vec<Vec<B>> = SHA256_INIT.iter().map(|&h| constant(h)).collect();
+    for chunk in message.chunks(512) {
+        let mut w: Vec<Vec<B>> = (0..16).map(|j| (0..32).map(|i| chunk[32 * j + 31 - i].clone()).collect()).collect();
+        for j in 16..64 {
+            let s0 = add(&add(&rotate(&w[j - 15], 7), &rotate(&w[j - 15], 18)), &shift(&w[j - 15], 3));
+            let s1 = add(&add(&rotate(&w[j - 2], 17), &rotate(&w[j - 2], 19)), &shift(&w[j - 2], 10));
+            let word = sum(&sum(&w[j - 16], &s0), &sum(&w[j - 7], &s1));
+            w.push(word);
+        }
+        let mut v = state.clone();
+        for (j, &round) in SHA256_ROUNDS.iter().enumerate() {
+            cancel.check()?;
+            let (a, b, c, e, f, g) = (&v[0], &v[1], &v[2], &v[4], &v[5], &v[6]);
+            let sigma1 = add(&add(&rotate(e, 6), &rotate(e, 11)), &rotate(e, 25));
+            let choice: Vec<B> = (0..32).map(|i| g[i].xor(&e[i].and(&f[i].xor(&g[i])))).collect();
+            let t1 = sum(&sum(&sum(&v[7], &sigma1), &sum(&choice, &constant(round))), &w[j]);
+            let sigma0 = add(&add(&rotate(a, 2), &rotate(a, 13)), &rotate(a, 22));
+            let majority: Vec<B> = (0..32).map(|i| a[i].xor(&a[i].xor(&b[i]).and(&a[i].xor(&c[i])))).collect();
+            let t2 = sum(&sigma0, &majority);
+            v = vec![sum(&t1, &t2), v[0].clone(), v[1].clone(), v[2].clone(), sum(&v[3], &t1), v[4].clone(), v[5].clone(), v[6].clone()];
+        }
+        state = state.iter().zip(&v).map(|(h, x)| sum(h, x)).collect();
+    }
+
+    let mut equal = one.clone();
+    for (word, expected) in state.iter().zip(expected) {
+        for (i, bit) in word.iter().enumerate() {
+            equal = equal.and(&if (expected >> i) & 1 == 1 { bit.clone() } else { bit.xor(one) });
+        }
+    }
+    Ok(Some(equal))
+}
+
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_ROUNDS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random positions for error patterns
+    fn positions(seed: usize, count: usize, len: usize) -> Vec<usize> {
+        let mut picked = Vec::new();
+        let mut x = seed * 2654435761 + 12345;
+        while picked.len() < count {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let i = (x >> 33) % len;
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+        picked
+    }
+
+    #[test]
+    fn every_block_corrects_up_to_its_configured_errors() {
+        let code = Code::new(5, 2, 256).unwrap();
+        assert_eq!((code.parity, code.blocks.len(), code.key_bits()), (10, 9, 256 - 90));
+
+        for (field_bits, errors, template_bits) in [(4, 1, 60), (5, 2, 256), (5, 3, 256), (6, 5, 256), (8, 12, 512)] {
+            let code = Code::new(field_bits, errors, template_bits).unwrap();
+            let key: Vec<bool> = (0..code.key_bits()).map(|i| (i * 7 + 3) % 5 < 2).collect();
+            let codeword = code.encode(&key);
+            let clean = code.correct(&codeword, &true, &CancellationToken::new()).unwrap();
+            assert_eq!(clean.corrected, codeword);
+            assert!(clean.flips.iter().all(|&f| !f));
+
+            for seed in 0..6 {
+                let mut word = codeword.clone();
+                for (b, block) in code.blocks.iter().enumerate() {
+                    for i in positions(seed * 31 + b, (seed + b) % (errors + 1), block.len()) {
+                        word[block.start + i] ^= true;
+                    }
+                }
+                let fixed = code.correct(&word, &true, &CancellationToken::new()).unwrap();
+                assert_eq!(fixed.corrected, codeword, "GF(2^{}), t = {}, seed {}", field_bits, errors, seed);
+                assert_eq!(code.key(&fixed.corrected), key);
+                let differing = word.iter().zip(&codeword).filter(|(a, b)| a != b).count();
+                assert_eq!(fixed.flips.iter().filter(|&&f| f).count(), differing);
+                assert!(max_block_errors(&word, &codeword, field_bits) <= errors);
+            }
+        }
+        assert!(Code::new(11, 2, 1024).is_err() && Code::new(8, 2, 16).is_err() && Code::new(5, 0, 256).is_err());
+    }
+
+    #[test]
+    fn probes_within_the_capacity_recover_the_key_and_its_hash() {
+        let template: Vec<bool> = (0..1024).map(|i| (i * 13 + i / 7) % 3 == 0).collect();
+        let (helper, key) = code_offset(&template, 6, 6).unwrap();
+        let code = Code::new(6, 6, 1024).unwrap();
+        assert_eq!(code.key_bits(), key.len());
+        let recover = |probe: &[bool]| {
+            let word: Vec<bool> = probe.iter().zip(&helper).map(|(p, h)| p ^ h).collect();
+            let corrected = code.correct(&word, &true, &CancellationToken::new()).unwrap().corrected;
+            let recovered = code.key(&corrected);
+            let hashed = hashes_to(&recovered, &key_hash(&key), &true, &CancellationToken::new()).unwrap().unwrap();
+            (recovered, hashed)
+        };
+
+        // 6 errors in every 63-bit block: about 9.5% of the bits
+        let mut probe = template.clone();
+        for (b, block) in code.blocks.iter().enumerate() {
+            for i in positions(b, 6, block.len()) {
+                probe[block.start + i] ^= true;
+            }
+        }
+        assert_eq!(max_block_errors(&template, &probe, 6), 6);
+        assert_eq!(recover(&probe), (key.clone(), true));
+
+        // A seventh error in the first block
+        let extra = (0..code.blocks[0].len()).find(|&i| probe[i] == template[i]).unwrap();
+        probe[extra] ^= true;
+        assert_eq!(max_block_errors(&template, &probe, 6), 7);
+        let (recovered, hashed) = recover(&probe);
+        assert!(recovered != key && !hashed);
+    }
+
+    #[test]
+    fn the_hash_circuit_agrees_with_sha256() {
+        let cancel = CancellationToken::new();
+        for len in [0, 5, 8, 166, 440, 447, 448, 512, 934] {
+            let key: Vec<bool> = (0..len).map(|i| (i * 5 + i / 3) % 7 < 3).collect();
+            assert_eq!(hashes_to(&key, &key_hash(&key), &true, &cancel).unwrap(), Some(true), "{} bits", len);
+            let mut other = key.clone();
+            other.push(true);
+            assert_eq!(hashes_to(&other, &key_hash(&key), &true, &cancel).unwrap(), Some(false), "{} bits", len);
+        }
+        assert_eq!(hashes_to(&[true], "not a hash", &true, &cancel).unwrap(), None);
+    }
+
+    #[test]
+    fn capacity_and_block_errors_follow_the_block_layout() {
+        assert_eq!(capacity(5, 256), 7);
+        assert!(capacity(8, 1024) > capacity(5, 1024));
+        assert_eq!(capacity(4, 4), 0);
+
+        let a = vec![false; 62];
+        let mut b = a.clone();
+        for i in [0, 1, 2, 40] {
+            b[i] = true;
+        }
+        assert_eq!(max_block_errors(&a, &b, 5), 3);
+        assert_eq!(max_block_errors(&a, &a, 5), 0);
+    }
+}
@@ -0,0 +1,305 @@
+// shared/src/grpc.rs
+//! gRPC exchange transport (`grpc://host:port`).
+//!
+//! Registrations carry the serialized FHE `ServerKey` and verify requests
+//! the encrypted key/IV blobs, tens of megabytes that a file-per-request
+//! HTTP body or a JSON-RPC line handles poorly. This service moves the same
+//! exchange files as the other transports (the serialized `RegisterRequest`,
+//! `VerifyResponse`, ... stay the canonical messages), but as streams of
+//! [`CHUNK_BYTES`] chunks: uploads are client-streamed, downloads such as
+//! verify results are server-streamed.
+//!
+//! The service is generated by build.rs with tonic's manual builder, so no
+//! protobuf toolchain is needed; messages are the serde structs below,
+//! encoded with bincode. The server side is `server grpc [addr]`; it serves
+//! the same files as the HTTP front end, a slot only to its owner, whose
+//! [`Caller`] goes in the `x-fingerprint-user` and `authorization` metadata.
+
+use bytes::{Buf, BufMut};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+use crate::transport::{user_slot, Caller, Transport, USERS_DIR, USER_HEADER};
+
+include!(concat!(env!("OUT_DIR"), "/fingerprint.Exchange.rs"));
+
+pub use exchange_client::ExchangeClient;
+pub use exchange_server::{Exchange, ExchangeServer};
+
+/// Size of one streamed chunk, well below tonic's 4 MB message limit
+pub const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Don't poll a remote server as fast as a directory
+const GRPC_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// ==================== MESSAGES ====================
+
+/// A file in the root exchange area (`slot: None`) or a user's slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRef {
+    pub slot: Option<String>,
+    pub name: String,
+}
+
+/// Part of a file; the first chunk of an upload names the file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub file: Option<FileRef>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStat {
+    pub exists: bool,
+    pub modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Empty {}
+
+/// Split a file into upload chunks, naming it in the first one
+pub fn chunks(file: Option<FileRef>, data: &[u8]) -> Vec<Chunk> {
+    let mut file = file;
+    let mut chunks: Vec<Chunk> = data
+        .chunks(CHUNK_BYTES)
+        .map(|part| Chunk { file: file.take(), data: part.to_vec() })
+        .collect();
+    if chunks.is_empty() {
+        chunks.push(Chunk { file, data: Vec::new() });
+    }
+    chunks
+}
+
+// ==================== CODEC ====================
+
+/// bincode codec for the serde message types
+pub struct BincodeCodec<T, U>(PhantomData<(T, U)>);
+
+impl<T, U> Default for BincodeCodec<T, U> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T, U> Codec for BincodeCodec<T, U>
+where
+    T: Serialize + Send + 'static,
+    U: DeserializeOwned + Send + 'static,
+{
+    type Encode = T;
+    type Decode = U;
+    type Encoder = BincodeEncoder<T>;
+    type Decoder = BincodeDecoder<U>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BincodeEncoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BincodeDecoder(PhantomData)
+    }
+}
+
+pub struct BincodeEncoder<T>(PhantomData<T>);
+
+impl<T: Serialize> Encoder for BincodeEncoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: T, buf: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        bincode::serialize_into(buf.writer(), &item).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+pub struct BincodeDecoder<U>(PhantomData<U>);
+
+impl<U: DeserializeOwned> Decoder for BincodeDecoder<U> {
+    type Item = U;
+    type Error = Status;
+
+    fn decode(&mut self, buf: &mut DecodeBuf<'_>) -> Result<Option<U>, Status> {
+        if !buf.has_remaining() {
+            return Ok(None);
+        }
+        bincode::deserialize_from(buf.reader())
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+}
+
+// ==================== TRANSPORT ====================
+
+/// Files held by a server running `server grpc`
+pub struct GrpcTransport {
+    url: String,                // grpc://host:port as given
+    slot: Option<String>,
+    caller: Option<Caller>,     // Credentials of a user's area
+    client: ExchangeClient<Channel>,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+
+impl GrpcTransport {
+    /// `spec` is `grpc://host:port`; the connection is made on first use
+    pub fn new(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let address = spec.strip_prefix("grpc://").ok_or("gRPC exchange must be grpc://host:port")?;
+        let address = address.trim_end_matches('/');
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let channel = {
+            let _guard = runtime.enter();
+            Endpoint::from_shared(format!("http://{}", address))?.connect_lazy()
+        };
+        Ok(Self {
+            url: format!("grpc://{}", address),
+            slot: None,
+            caller: None,
+            client: ExchangeClient::new(channel),
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    fn file(&self, name: &str) -> FileRef {
+        FileRef { slot: self.slot.clone(), name: name.to_string() }
+    }
+
+    /// Call of the service carrying the caller's credentials, if any
+    fn request<T>(&self, message: T) -> Result<tonic::Request<T>, Box<dyn std::error::Error>> {
+        let mut request = tonic::Request::new(message);
+        if let Some(caller) = &self.caller {
+            request.metadata_mut().insert(USER_HEADER, caller.user_header().parse()?);
+            if let Some(api_key) = &caller.api_key {
+                request.metadata_mut().insert("authorization", format!("Bearer {}", api_key).parse()?);
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl Transport for GrpcTransport {
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let chunks = chunks(Some(self.file(name)), data);
+        let mut client = self.client.clone();
+        self.runtime.block_on(client.put(self.request(tokio_stream::iter(chunks))?))?;
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let mut client = self.client.clone();
+        self.runtime.block_on(async {
+            let mut stream = match client.get(self.request(self.file(name))?).await {
+                Ok(response) => response.into_inner(),
+                Err(status) if status.code() == Code::NotFound => return Ok(None),
+                Err(status) => return Err(status.into()),
+            };
+            let mut data = Vec::new();
+            while let Some(chunk) = stream.message().await? {
+                data.extend_from_slice(&chunk.data);
+            }
+            Ok(Some(data))
+        })
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.client.clone();
+        self.runtime.block_on(client.delete(self.request(self.file(name))?))?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Err("The gRPC front end doesn't list files".into())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut client = self.client.clone();
+        Ok(self.runtime.block_on(client.stat(self.request(self.file(name))?))?.into_inner().exists)
+    }
+
+    fn modified(&self, name: &str) -> Result<Option<SystemTime>, Box<dyn std::error::Error>> {
+        let mut client = self.client.clone();
+        Ok(self.runtime.block_on(client.stat(self.request(self.file(name))?))?.into_inner().modified)
+    }
+
+    fn poll_interval(&self) -> Duration {
+        GRPC_POLL_INTERVAL
+    }
+
+    fn describe(&self) -> String {
+        match &self.slot {
+            Some(slot) => format!("{}/{}/{}/", self.url, USERS_DIR, slot),
+            None => format!("{}/", self.url),
+        }
+    }
+
+    fn scoped(&self, slot: &str) -> Box<dyn Transport> {
+        Box::new(GrpcTransport {
+            url: self.url.clone(),
+            slot: Some(slot.to_string()),
+            caller: self.caller.clone(),
+            client: self.client.clone(),
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    fn user_slots(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Err("The gRPC front end doesn't list user slots".into())
+    }
+
+    fn user_area(&self, api_key: Option<&str>, user_id: &str) -> Box<dyn Transport> {
+        Box::new(GrpcTransport {
+            url: self.url.clone(),
+            slot: Some(user_slot(api_key, user_id)),
+            caller: Some(Caller { api_key: api_key.map(str::to_string), user_id: user_id.to_string() }),
+            client: self.client.clone(),
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_files_are_split_and_named_once() {
+        let data = vec![7u8; CHUNK_BYTES * 2 + 10];
+        let file = FileRef { slot: None, name: "register_request.json".to_string() };
+        let parts = chunks(Some(file), &data);
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].file.is_some());
+        assert!(parts[1..].iter().all(|c| c.file.is_none()));
+        assert_eq!(parts.iter().map(|c| c.data.len()).sum::<usize>(), data.len());
+
+        let empty = chunks(Some(FileRef { slot: None, name: "x".to_string() }), &[]);
+        assert_eq!(empty.len(), 1);
+        assert!(empty[0].file.is_some());
+    }
+
+    #[test]
+    fn scoped_transport_describes_its_slot() {
+        let grpc = GrpcTransport::new("grpc://server:8701/").unwrap().scoped("u-abc");
+        assert_eq!(grpc.describe(), "grpc://server:8701/users/u-abc/");
+    }
+
+    #[test]
+    fn user_areas_send_their_caller() {
+        let root = GrpcTransport::new("grpc://server:8701").unwrap();
+        let area = GrpcTransport {
+            url: root.url.clone(),
+            slot: Some(user_slot(Some("fpk_1"), "zoë")),
+            caller: Some(Caller { api_key: Some("fpk_1".to_string()), user_id: "zoë".to_string() }),
+            client: root.client.clone(),
+            runtime: Arc::clone(&root.runtime),
+        };
+        let request = area.request(Empty {}).unwrap();
+        let user = request.metadata().get(USER_HEADER).unwrap().to_str().unwrap();
+        let authorization = request.metadata().get("authorization").unwrap().to_str().unwrap();
+        assert_eq!(Caller::from_headers(user, Some(authorization)), area.caller);
+        assert!(root.request(Empty {}).unwrap().metadata().is_empty());
+        assert!(area.list().is_err());
+    }
+}
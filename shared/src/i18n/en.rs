@@ -0,0 +1,501 @@
+//! English messages (see mod.rs)
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    // ==================== Client ====================
+    ("client.banner", "🔐 FINGERPRINT AUTHENTICATION CLIENT"),
+    ("client.trivium_self_test_failed", "Cipher self-test failed, refusing to run: {}"),
+    ("client.exchange_unencrypted", "⚠️  Server published no exchange key; requests are not encrypted"),
+    ("client.register_title", "\n📝 REGISTER MODE"),
+    ("client.user_id", "👤 User ID: {}"),
+    ("client.factor", "🔑 Factor: {}"),
+    ("client.enrolling_duress", "🚨 Enrolling as DURESS finger"),
+    ("client.removing_old_key", "🗑️  Removing old client key for testing..."),
+    ("client.section_features", "\n🔬 FEATURE EXTRACTION:"),
+    ("client.extracting", "📷 Extracting fingerprint features..."),
+    ("client.template_bits", "📏 Template length: {} bits"),
+    ("client.soft_attributes", "🏷️  Soft attributes attached ({})"),
+    ("client.partial_coverage", "🧩 Partial probe: {} of {} regions covered"),
+    ("client.quality", "📊 Quality: {}% bits set, distinctiveness {}, {}/{} regions valid"),
+    ("client.quality_warning", "⚠️  {}"),
+    ("client.quality_recapture", "🔁 Enrollment quality is low; consider registering again with a better capture"),
+    ("client.samples_voted", "🗳️  {} captures voted into one template, {} of {} bits reliable"),
+    ("client.capture_quality", "📷 Capture quality {}/100 (ridge clarity {}%, contrast {}%, foreground {}%)"),
+    ("client.fingers", "🖐️  Fused enrollment of {} fingers, rule: {}"),
+    ("client.fingers_probed", "🖐️  Probing all {} enrolled fingers"),
+    ("client.rotations", "🔄 {} rotated variants enrolled, up to ±{}°"),
+    ("client.transform", "🎭 Cancellable transform {} applied to the template"),
+    ("client.fuzzy_sketch", "🧬 Fuzzy sketch: {}-bit key, up to {} bit errors corrected per block of {} bits"),
+    ("client.quality_mask", "🎚️  Quality mask: {} of {} bits kept ({})"),
+    ("client.bit_weights", "⚖️  Weighted distance: total weight {} over {} bits"),
+    ("client.extracted", "✅ Extracted {} bits"),
+    ("client.section_trivium", "\n🔐 TRIVIUM ENCRYPTION:"),
+    ("client.random_key", "✅ Random key generated: {} bits"),
+    ("client.random_iv", "✅ Random IV generated: {} bits"),
+    ("client.fingerprint_encrypted", "✅ Fingerprint encrypted: {} bits"),
+    ("client.sanity_passed", "✅ Trivium sanity check passed"),
+    ("client.section_key_management", "\n🔐 FHE KEY MANAGEMENT:"),
+    ("client.loading_key", "📂 Loading existing client key..."),
+    ("client.key_loaded", "✅ Client key loaded from: {}"),
+    ("client.generating_keys", "🔑 Generating new FHE keys (first time)..."),
+    ("client.keygen_duration", "⏱️  This may take ~10 seconds..."),
+    ("client.parameter_set", "⚙️  Parameter set: {}"),
+    ("client.key_saved", "✅ Client key saved to: {}"),
+    ("client.server_key_size", "✅ Server key will be sent to server: ({} bytes)"),
+    ("client.gpu_key_size", "✅ Compressed server key for GPU evaluation will be sent: ({} bytes)"),
+    ("client.section_fhe_encryption", "\n🔒 FHE ENCRYPTION:"),
+    ("client.encrypting_key_iv", "⏱️  Encrypting Trivium key and IV..."),
+    ("client.consent", "📄 Consent: {} ({})"),
+    ("client.encrypted_key", "✅ Encrypted key:  {} bytes"),
+    ("client.encrypted_iv", "✅ Encrypted IV:   {} bytes"),
+    ("client.section_sending", "\n📤 SENDING REQUEST:"),
+    ("client.debug_info", "🔍 Debug info:"),
+    ("client.request_sent", "✅ Request sent to server!"),
+    ("client.section_waiting", "\n⏳ WAITING FOR RESPONSE:"),
+    ("client.registration_successful", "✅ REGISTRATION SUCCESSFUL!"),
+    ("client.response_user_id", "   User ID: {}"),
+    ("client.response_message", "   Message: {}"),
+    ("client.response_timestamp", "   Timestamp: {}"),
+    ("client.reenrolled", "🔁 Enrollment #{} of this finger; the previous template is kept in the history"),
+    ("client.registration_failed", "❌ REGISTRATION FAILED!"),
+    ("client.policy_unavailable", "⚠️  Could not fetch fallback policy ({}), fallback disabled"),
+    ("client.fallback_trying", "\n🔁 FALLBACK: trying factor '{}'"),
+    ("client.authenticated_via", "✅ AUTHENTICATED via {}"),
+    ("client.authentication_failed_after", "❌ AUTHENTICATION FAILED after {} attempt(s)"),
+    ("client.attempt_error", "error: {}"),
+    ("client.attempt_match", "match"),
+    ("client.attempt_no_match", "no match"),
+    ("client.verify_title", "\n🔍 VERIFY MODE"),
+    ("client.long_wait", "This may take a very long time..."),
+    ("client.job_state", "🎫 Job {}: {}"),
+    ("client.job_phase", "🎫 Job {}: {} ({})"),
+    ("client.verification_failed", "❌ VERIFICATION FAILED!"),
+    ("client.fail_open", "⚠️  Server allowed this attempt under its fail-open policy ({}); no match was computed"),
+    ("client.identity_pinned", "🔑 Trusting server identity {} for {} (first use)"),
+    ("client.session_established", "🤝 Session {} established with the pinned server identity (expires {})"),
+    ("client.result_signed", "🔏 Result signed by server identity {}"),
+    ("client.result_session", "🔗 Result bound to session {} (valid until {})"),
+    ("client.result_unsigned", "⚠️  Result is not signed by a published server identity"),
+    ("client.authentication_successful", "✅ AUTHENTICATION SUCCESSFUL!"),
+    ("client.authentication_failed", "❌ AUTHENTICATION FAILED!"),
+    ("client.result_user_id", "User ID:          {}"),
+    ("client.result_match", "Match Result:     {}"),
+    ("client.result_distance", "Hamming Distance: {}/{} bits"),
+    ("client.result_match_only", "Hamming Distance: not returned (match-only result)"),
+    ("client.result_similarity", "Similarity:       {}%"),
+    ("client.result_threshold", "Threshold:        {}% (max {} bits)"),
+    ("client.result_timestamp", "Timestamp:        {}"),
+    ("client.result_backend", "Evaluated on:     {}"),
+    ("client.debug_server_side", "\n🚨 DEBUG INFO (Server-side):"),
+    ("client.debug_server_match", "   Server Match:    {}"),
+    ("client.debug_server_distance", "   Server Distance: {}/{}"),
+    ("client.extracting_probe", "📷 Extracting probe fingerprint features..."),
+    ("client.probe_encrypted", "✅ Probe encrypted: {} bits"),
+    ("client.section_key_loading", "\n🔐 FHE KEY LOADING:"),
+    ("client.encrypting_key_iv_constant", "⏱️  Encrypting Trivium key, IV, and constant..."),
+    ("client.encrypted_threshold", "✅ Encrypted threshold: {} bytes"),
+    ("client.server_duration", "⚠️  Server will perform FHE operations (~30-60 minutes)"),
+    ("client.section_decrypting", "\n🔓 DECRYPTING RESULTS:"),
+    ("client.section_oidc", "\n🎫 OIDC TOKEN EXCHANGE:"),
+    ("client.oidc_endpoint", "Endpoint: {}"),
+    ("client.tokens_issued", "✅ Tokens issued ({})"),
+    ("client.tokens_expire", "   Expires in: {}s"),
+    ("client.account_title", "\n👥 ACCOUNT MODE: {}"),
+    ("client.account_sent", "📤 Request sent, waiting for server..."),
+    ("client.delete_title", "\n🗑️  DELETE MODE: {}"),
+    ("client.delete_proving", "🔑 Proving ownership with a verification first..."),
+    ("client.deleted", "✅ {}"),
+    ("client.history_moved", "📜 Local history entries moved: {}"),
+    ("client.input_image", "🖼️  Image: {}"),
+    ("client.input_pin", "🔢 PIN: ****"),
+    ("client.usage", "❌ Usage: {}"),
+    ("client.cancel_no_job", "ℹ️  No verify job to cancel for {}"),
+    ("client.cancel_finished", "ℹ️  Job {} has already finished ({})"),
+    ("client.cancel_sent", "🛑 Cancelling job {}, waiting for server..."),
+    ("client.cancel_done", "✅ Job {} cancelled"),
+    ("client.cancel_not_running", "ℹ️  Job {} was no longer queued or running on the server"),
+    ("update.title", "\n🩹 DELTA RE-ENROLLMENT"),
+    ("update.unchanged", "✅ Template unchanged, nothing to update"),
+    ("update.regions", "🩹 {} changed regions, {} of {} bits sent"),
+    ("update.failed", "❌ Update rejected: {}"),
+    ("update.done", "✅ Template updated ({}/{} delta bits used)"),
+    ("progress.encrypting", "Encrypting key/IV"),
+    ("progress.waiting", "⏳ Waiting for the server: {}, {}"),
+    ("progress.heartbeat", "last heartbeat {} ago"),
+    ("progress.no_heartbeat", "no heartbeat yet"),
+    ("batch.title", "\n📋 BATCH REGISTRATION"),
+    ("batch.entries", "📋 {} entries in {}, {} encrypted at a time"),
+    ("batch.entry_registered", "✅ [{}/{}] {} registered ({} ms)"),
+    ("batch.entry_failed", "❌ [{}/{}] {} failed: {}"),
+    ("batch.summary", "\n📊 {} registered, {} failed of {} ({} s)"),
+    ("batch.failure", "   line {}: {}: {}"),
+    ("calibration.title", "🔬 SENSOR CALIBRATION"),
+    ("calibration.sample", "🖼️  Sample: {}"),
+    ("calibration.levels", "🎚️  Levels {}..{}, gamma {}, {} dpi"),
+    ("calibration.preprocessing", "🧹 Preprocessing: equalize {}, blur σ {}"),
+    ("calibration.enhancement", "✨ Enhancement: segment {}, CLAHE {}, binarize {}"),
+    ("calibration.written", "💾 Written to {} (use with --sensor {})"),
+    ("revoke.title", "\n🎭 TRANSFORM REVOCATION"),
+    ("revoke.done", "✅ Enrollment revoked; transform {} replaced by {}"),
+    ("revoke.register_again", "ℹ️  Register the primary finger again to sign in: register {} <image_path> --replace"),
+    ("rotate.title", "\n🔑 CLIENT KEY ROTATION"),
+    ("rotate.rotated", "✅ New client key in place: {}"),
+    ("rotate.reregistering", "🔁 Registering the {}-bit template kept on this machine under the new key..."),
+    ("rotate.reregistered", "✅ {} registered again under the new key"),
+    ("rotate.no_local_enrollment", "ℹ️  No template of {} kept on this machine; register the primary finger again with --replace"),
+    ("rotate.register_others", "ℹ️  Other enrollments of the tenant were invalidated; each user must register the primary finger again with --replace"),
+    ("profile.list_title", "\n👤 CLIENT KEY PROFILES"),
+    ("profile.entry", "{} {}  client key: {}  ({})"),
+    ("profile.server_key", "     server key at {} (tenant {}, fingerprint {}) since {}"),
+    ("profile.fingerprint_mismatch", "⚠️  The server holds server key {}, this profile sent {}; verifications will be refused until the primary finger is registered again"),
+    ("profile.fingerprint_recorded", "🔑 The server reported server key {}; this profile checks it from now on"),
+    ("profile.shared_tenant", "⚠️  Profile {} sent its server key to {} for this tenant; the server keeps one per tenant and refuses another one until it is rotated (`keys rotate`)"),
+    ("profile.deleted", "🗑️  Profile {} deleted ({})"),
+    ("profile.deleted_server_keys", "ℹ️  {} server(s) still hold its server key; its enrollments there can no longer be verified"),
+    ("profile.delete_active", "⚠️  {} is the profile in use"),
+    ("profile.delete_confirm", "Delete profile {} and its client key ({})? Its enrollments can no longer be verified"),
+    ("profile.delete_choices", "[y/N]"),
+    ("profile.delete_kept", "ℹ️  Profile {} kept"),
+    ("recovery.nothing", "✅ Nothing to recover for {}"),
+    ("recovery.request_pending", "⏳ A verification request from an earlier run has not been picked up by the server yet"),
+    ("recovery.found_verify", "\n📬 Found a verification result from an interrupted run ({})"),
+    ("recovery.decrypt_failed", "❌ Could not decrypt the earlier result (client key changed?): {}"),
+    ("recovery.server_failed", "❌ The earlier verification failed on the server"),
+    ("recovery.unreadable", "❌ Could not read the earlier result: {}"),
+    ("recovery.job_running", "⏳ Verify job {} is still {} on the server; its result will be offered next time"),
+    ("recovery.discarded", "🗑️  Earlier result discarded"),
+    ("recovery.register_completed", "\n📬 An interrupted registration completed: {} ({})"),
+    ("recovery.register_failed", "\n📬 An interrupted registration failed: {}"),
+    ("recovery.register_unreadable", "\n📬 Unreadable registration result from an interrupted run: {}"),
+    ("recovery.finished_at", "finished {}"),
+    ("recovery.prompt", "   Decrypt and show it now?"),
+    ("recovery.prompt_choices", "[Y/n]"),
+    ("client.help", r#"
+🔐 TRANSCIPHERING FINGERPRINT AUTHENTICATION CLIENT
+
+USAGE:
+  cargo run --release -- <MODE> <USER_ID> <IMAGE_PATH>
+
+MODES:
+  register   Register a new fingerprint template
+             --duress: enroll the duress finger, --second-finger: enroll a fallback finger
+             --consent-ref <REF> --purpose <PURPOSE> [--retain-days <N>]: consent record
+             --replace: confirm replacing an enrolled template (the server keeps the old one)
+             --sample <IMAGE_PATH>: another capture (repeatable, 3-9 in all); enrolls their majority
+             --fused-finger <IMAGE_PATH>: another finger (repeatable, 2-4 in all), decided on together
+             --fusion <any|two-of-n>: any finger or at least two must match (default any)
+  register-pin  Enroll a PIN as fallback factor: register-pin <USER_ID> <PIN>
+  register-batch  Register every user_id,image_path line of a CSV manifest under one client key:
+             register-batch <MANIFEST.CSV> [--concurrency <N> (default: CPU count)]
+  update     Refresh the enrolled fingerprint by sending only its changed regions
+             --region-bits <N> (default 64)
+  verify     Verify a fingerprint against enrolled template
+             --fallback-finger <IMAGE_PATH>, --fallback-pin <PIN>: fallback factors
+             --partial: compare only the regions the capture covers (partial touches)
+             --fused-finger <IMAGE_PATH>: the further fingers of a fused enrollment, in order
+             register/verify/login: --hand <left|right> --finger <thumb|index|middle|ring|little>
+             --pattern <arch|loop|whorl> [--blind-soft]: soft attributes checked before matching
+  login      Verify, then exchange the server receipt for OIDC tokens
+             --oidc-config <PATH> (default: ~/.fingerprint_client/oidc.json)
+  recover    Show and clean up a result left by an interrupted register/verify
+  estimate   Show upload size, queue wait and computation time of a verification
+  history    Show local authentication history (optionally for one user)
+  bench      Time extraction, the cipher, key generation and FheBool encryption on this host
+             and estimate a verification from the measured gate cost
+             --image <IMAGE_PATH>: also time feature extraction, --rounds <N> (default 3)
+  advise-params  Benchmark TFHE parameter sets and recommend one for key generation
+             --security <BITS> (default 128), --pfail <LOG2> (default 40)
+             --latency-budget <SECS> (default 1800), --apply: write fhe_params.json
+  calibrate-sensor  Derive a sensor profile from sample images: <NAME> <IMAGE>... [--dpi <N>]
+  eval       FAR/FRR of the plaintext matcher over a dataset (<FINGER>_<IMPRESSION>.tif files):
+             eval <DATASET_DIR> [--out <CSV> (default eval.csv)] [--step <BITS> (default 1)]
+  tune-threshold  Write ROC/DET points of a dataset and recommend the threshold of a target FAR:
+             tune-threshold <DATASET_DIR> [--target-far <PERCENT> (default 0.1)] [--roc <CSV> (default roc.csv)]
+             --apply: record it in the server's database directory, thresholds.json (or --thresholds <PATH>)
+  rename-user  Rename a user: rename-user <OLD_USER_ID> <NEW_USER_ID>
+  merge-users  Merge a user into another: merge-users <SOURCE_USER_ID> <TARGET_USER_ID>
+  delete     Delete the user's enrollment: delete <USER_ID> [--prove <IMAGE_PATH>]
+             --prove: verify first and present the proof of ownership; without it the
+             request is proven by a session (needs a pinned server identity)
+  revoke     Revoke the user's cancellable transform and issue a new one: revoke <USER_ID>;
+             matching is refused until the primary finger is registered again
+  keys       Manage FHE client keys: keys <rotate [<USER_ID>]|list|delete <PROFILE> [--yes]>
+             rotate: replace the key (default user: the enrollment on this machine), push the
+             new server key, invalidate the tenant's enrollments and register the template
+             kept on this machine again; proven by the user's session, or by the admin key
+             in FINGERPRINT_ADMIN_KEY
+             list: show the profiles, whether they have a key and where their server key is
+             delete: remove a named profile's key store
+  admin      Query the server as operator: admin <list|stats> (JSON on stdout)
+             Needs the server's admin key in FINGERPRINT_ADMIN_KEY
+  cancel     Cancel the user's queued or running verification: cancel <USER_ID>
+  agent      Run the local agent for desktop applications (Unix socket)
+             --socket <PATH> (default: ~/.fingerprint_client/agent.sock)
+             --capture-dir <DIR> (default: ~/.fingerprint_client/capture)
+  --rpc      Serve JSON-RPC 2.0 requests on stdin/stdout (one per line)
+  --lang <en|tr>  Language of console output (also FINGERPRINT_LANG or LANG)
+  --sensor <NAME>  Sensor profile applied during extraction (also FINGERPRINT_SENSOR)
+  --dpi <N>  Capture resolution, if the images don't record it; captures of known
+             resolution are rescaled to 500 dpi before extraction
+  --server-url <URL>  Send requests to a server's HTTP front end (`server http`) or, with
+             grpc://host:port, its streaming gRPC front end (`server grpc`) instead
+             of the exchange directory
+  --profile <NAME>  Keep keys, credential and logs in ~/.fingerprint_client/<NAME>/ so
+             several users of one machine don't replace each other's key
+             (also FINGERPRINT_PROFILE; default: ~/.fingerprint_client/)
+  --output <human|json>  With json, register and verify print one JSON document (success,
+             user_id, distance, similarity, timings, error code) on stdout, progress on stderr
+  help       Show this help message
+
+EXAMPLES:
+  # Register a new user
+  cargo run --release -- register user_123 ../data/fingerprints/101_1.tif
+
+  # Verify user authentication
+  cargo run --release -- verify user_123 ../data/fingerprints/101_2.tif
+
+  # Show when and where your fingerprint was used
+  cargo run --release -- history user_123
+
+NOTES:
+  - Client key is stored at: ~/.fingerprint_client/client_key.bin
+  - The enrollment's Trivium key and template are kept in ~/.fingerprint_client/enrollment.json
+    for `update`; after about 1024 updated bits a full register is required
+  - History log is stored at: ~/.fingerprint_client/history.jsonl
+  - New keys use the parameter set in ~/.fingerprint_client/fhe_params.json (if present)
+  - Server key is sent only during first registration
+  - fingerprint.toml (or the file FINGERPRINT_CONFIG names) sets directories, the match threshold,
+    the parameter set of new keys and the log filter; environment variables win over it
+  - FINGERPRINT_EXCHANGE selects the exchange: a directory (default ../exchange),
+    an http(s):// or grpc:// server URL (same as --server-url) or s3://<bucket>/<prefix> (AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION,
+    FINGERPRINT_S3_ENDPOINT for S3-compatible stores)
+  - Requests go to a private per-user slot (users/<hash>/, mode 0700) in the exchange
+  - A verify request gets a job id right away; its progress is read from
+    verify_status_<job_id>.json (or GET verify_status/<job_id> over HTTP)
+  - The server identity key is pinned on first use in ~/.fingerprint_client/known_servers.json
+    (or set with FINGERPRINT_SERVER_IDENTITY); results signed by another key are rejected
+  - Once the server identity is pinned, register/verify first run a session handshake proving
+    the enrollment credential (~/.fingerprint_client/credential_key.bin, registered at enrollment)
+  - Verify results are signed for their session's challenge and are refused once the session
+    expires (session_expiry_secs in the server's exchanges.json, default 2 hours)
+  - Exchange files are encrypted to the server's key from exchange_key.json
+    (client exchange key: ~/.fingerprint_client/exchange_key.bin)
+  - Verification can take 30-60 minutes due to FHE operations
+  - RPC methods: enroll, verify, status, decrypt-result
+  - Set FINGERPRINT_API_KEY to send a tenant API key with every request
+  - FINGERPRINT_TEMPLATE_BITS (128, 256, 512, 1024; default 1024) sets the template length
+    used at registration; it must be one the server lists in server_status.json
+  - FINGERPRINT_CIPHER=kreyvium transciphers with Kreyvium (128-bit key and IV)
+    instead of Trivium (80 bits). The server lists the ciphers it accepts in server_status.json
+  - FINGERPRINT_MATCH_THRESHOLD (max differing bits) is sent FHE-encrypted with each
+    verification and replaces the server's threshold, which then never learns it
+  - FINGERPRINT_RESULT_MODE=match_only asks verify for the encrypted match bit alone;
+    the server then returns no distance a client could hill-climb on
+  - FINGERPRINT_ENROLL_THRESHOLD stores a per-user threshold with a new enrollment,
+    FHE-encrypted ("plain:<bits>" stores it readable by the server)
+  - FINGERPRINT_QUALITY_MASK=plain sends each capture's per-bit quality mask, so bits
+    extraction flags as unreliable are not compared; =encrypted sends it FHE-encrypted
+  - FINGERPRINT_BIT_WEIGHTS=lbp enrolls the extractor's bit weights: differing edge-pattern
+    bits then count more towards the distance than flat-area ones
+  - FINGERPRINT_EXTRACTOR=gabor builds templates from Gabor filterbank energies instead
+    of LBP histograms; register and verify with the same extractor
+  - FINGERPRINT_ALIGN=core shifts each capture so its core point sits at the centre
+    before extraction; set it for register and verify alike
+  - FINGERPRINT_MIN_CAPTURE_QUALITY (0-100, default 40) is the lowest capture quality
+    score register accepts; lower scores ask for a recapture
+  - FINGERPRINT_FUZZY=on enrolls the primary finger as a fuzzy extractor: an encrypted BCH
+    sketch instead of a threshold, matching while every block has at most
+    FINGERPRINT_FUZZY_ERRORS differing bits; eval measures the FRR and FAR of each choice
+  - FINGERPRINT_GPU_KEY=on also sends a compressed server key when registering, so a server
+    built with the gpu feature verifies on its GPU; results show the backend that ran the job
+  - Finger templates are permuted and salted with a per-user secret before encryption
+    (~/.fingerprint_client/transforms.json); the server only stores the transform's id
+  - Images are PNG, TIFF, BMP, JPEG and the like; decode WSQ files with NBIS first
+    (dwsq raw finger.wsq -r) and pass the .raw frame, read with its .ncm sidecar
+  - live:fprint (libfprint sensor) or live:/dev/video0 (UVC camera) in place of an image
+    captures until a frame reaches the minimum quality; needs --features capture
+  - Anonymous timing telemetry is off unless ~/.fingerprint_client/telemetry.json has
+    {"enabled": true} (records go to telemetry.jsonl or an "endpoint" URL)
+    "#),
+    // ==================== Server ====================
+    ("server.banner", "🖥️  FINGERPRINT AUTHENTICATION SERVER"),
+    ("server.exchange_key", "🔐 Exchange encryption key: {}"),
+    ("server.identity_key", "🔏 Server identity key: {}"),
+    ("server.jobs_interrupted", "⚠️  {} verify job(s) interrupted by the last shutdown could not be resumed, marked failed"),
+    ("server.waiting", "\n⏳ Waiting for requests...\n"),
+    ("server.maintenance_failed", "⚠️  Maintenance failed: {}"),
+    ("server.poll_failed", "⚠️  Could not poll exchange '{}': {}"),
+    ("server.register_detected", "\n📥 REGISTER REQUEST DETECTED{}"),
+    ("server.register_claim_failed", "❌ Could not claim register request: {}"),
+    ("server.verify_detected", "\n📥 VERIFY REQUEST DETECTED{}"),
+    ("server.verify_claim_failed", "❌ Could not claim verify request: {}"),
+    ("server.session_issued", "🤝 Session handshake answered{}"),
+    ("server.session_failed", "❌ Session request failed: {}"),
+    ("server.session_expires", "⏳ Session expires at {}"),
+    ("server.session_verified", "🤝 Session verified (enrollment credential proven)"),
+    ("server.soft_mismatch", "🚫 Probe {} contradicts the enrolled finger, matcher skipped"),
+    ("server.partial_probe", "🧩 Partial probe: comparing {} of {} bits"),
+    ("server.reliable_bits", "🎯 Multi-sample enrollment: comparing {} reliable of {} bits"),
+    ("server.samples_enrolled", "🎯 {} samples enrolled, {} reliable bits"),
+    ("server.rotations_enrolled", "🔄 {} rotated variants enrolled"),
+    ("server.capture_quality", "📷 Capture quality score: {}/100"),
+    ("server.fuzzy_enrolled", "🧬 Fuzzy enrollment: {}-bit key, {} errors per block corrected over GF(2^{})"),
+    ("server.fuzzy_matching", "🧬 Correcting the probe against the {} sketch ({} blocks, {} errors each)..."),
+    ("server.fingers_enrolled", "🖐️  {} fingers enrolled, fused by rule {}"),
+    ("server.fingers_fused", "🖐️  Decisions of {} fingers fused by rule {}"),
+    ("server.rotations_matched", "🔄 Smallest distance selected over the template and {} rotated variants"),
+    ("server.rotations_dropped", "🔄 {} rotated variants dropped: they predate the update"),
+    ("server.quality_bits", "🎚️  Quality masks: comparing {} of {} bits"),
+    ("server.quality_encrypted", "🎚️  Encrypted quality mask over {} bits, threshold scaled under FHE"),
+    ("server.delta_detected", "\n🩹 DELTA REQUEST DETECTED{}"),
+    ("server.delta_claim_failed", "❌ Could not claim delta request: {}"),
+    ("server.delta_applied", "🩹 Delta stored: {} regions, {} bits ({}/{} delta bits used)"),
+    ("server.deltas_spliced", "🩹 {} deltas spliced into the template ({} bits)"),
+    ("server.policy_sent", "📋 Fallback policy sent{}"),
+    ("server.policy_failed", "❌ Policy request failed: {}"),
+    ("server.admin_sent", "🗄️  Admin {} answered{}"),
+    ("server.admin_failed", "❌ Admin request failed: {}"),
+    ("server.account_detected", "\n📥 ACCOUNT REQUEST DETECTED{}"),
+    ("server.account_completed", "✅ Account operation completed successfully!"),
+    ("server.account_failed", "❌ Account operation failed: {}"),
+    ("server.transform_renewed", "🎭 New cancellable transform: {} fingers enrolled under the old one dropped"),
+    ("server.transform_revoked", "🎭 Transform {} revoked; matching refused until re-enrollment"),
+    ("server.blobs_released", "🧹 {} template blobs of dropped templates removed"),
+    ("server.blobs_release_failed", "⚠️  Blobs of dropped templates not removed (server compact will): {}"),
+    ("server.revoke_detected", "\n📥 REVOKE REQUEST DETECTED{}"),
+    ("server.revoke_completed", "✅ Enrollment revoked"),
+    ("server.revoke_failed", "❌ Revoke failed: {}"),
+    ("server.keys_rotated", "🔑 Server key replaced; {} enrollments of the tenant must be registered again"),
+    ("server.rotate_detected", "\n📥 KEY ROTATION REQUEST DETECTED{}"),
+    ("server.rotate_completed", "✅ Client key rotated"),
+    ("server.rotate_failed", "❌ Key rotation failed: {}"),
+    ("server.server_key_fingerprint_ok", "🔑 Server key fingerprint matches the client's"),
+    ("server.delete_detected", "\n📥 DELETE REQUEST DETECTED{}"),
+    ("server.delete_completed", "✅ Enrollment deleted"),
+    ("server.delete_failed", "❌ Delete failed: {}"),
+    ("server.ownership_proven", "🔑 Ownership proven by a matching verify"),
+    ("server.receipt_detected", "\n📥 RECEIPT REQUEST DETECTED{}"),
+    ("server.receipt_completed", "✅ Receipt issued"),
+    ("server.receipt_failed", "❌ Receipt refused: {}"),
+    ("server.waiting_next", "\n⏳ Waiting for next request...\n"),
+    ("server.job_queued", "🚦 {} job queued ({} running, {} waiting)"),
+    ("server.job_completed", "✅ {} completed successfully!"),
+    ("server.job_failed", "❌ {} failed: {}"),
+    ("server.job_started", "▶️  {} started (log: {})"),
+    ("server.job_failure_unanswered", "⚠️  Could not answer the failed {} job: {}"),
+    ("server.job_cancelled", "🛑 {} cancelled"),
+    ("server.job_interrupted", "⏸️  {} stopped for shutdown, it resumes after the restart"),
+    ("server.cancel_requested", "🛑 Cancel requested for job {}{}"),
+    ("server.cancel_unknown", "ℹ️  Cancel request for job {}{}: not queued or running"),
+    ("server.cancel_failed", "❌ Cancel request failed: {}"),
+    ("server.shutting_down", "\n🛑 Shutting down: stopping running jobs, they resume from their checkpoints after the restart (press Ctrl+C again to exit now)"),
+    ("server.stopped", "👋 Server stopped"),
+    ("server.http_listening", "🌐 HTTP exchange listening on http://{}"),
+    ("server.http_stopped", "❌ HTTP front end stopped: {}"),
+    ("server.grpc_listening", "🌐 gRPC exchange listening on grpc://{}"),
+    ("server.grpc_stopped", "❌ gRPC front end stopped: {}"),
+    ("server.remote_refused", "🚫 Refused {} {}: {}"),
+    ("server.worker_spawn_failed", "⚠️  Could not start a {} worker: {}"),
+    ("server.worker_panicked", "💥 Worker {} recovered from a panic: {}"),
+    ("server.tfhe_threads", "🧵 tfhe threads: {} ({} per verify job)"),
+    ("server.tfhe_threads_pinned", "🧵 tfhe threads: {} ({} per verify job, pinned)"),
+    ("server.tfhe_pool_failed", "⚠️  Could not size the tfhe thread pool ({}), using rayon's default"),
+    ("server.job_pool_failed", "⚠️  Could not start the job's tfhe threads ({}), using the global pool"),
+    ("server.core_pin_failed", "⚠️  Could not pin {} to core {}"),
+    ("server.job_pool_outdated", "🧵 {}: job threads changed to {}, starting a new worker"),
+    ("server.job_logs_purged", "🧹 Removed {} old job logs"),
+    ("server.job_resumed", "⏩ Verify job {} requeued after restart, resuming from its checkpoints"),
+    ("server.checkpoint_result", "⏩ {} match restored from checkpoint"),
+    ("server.checkpoint_failed", "⚠️  Could not save checkpoint {}: {}"),
+    ("server.checkpoint_unreadable", "⚠️  Ignoring unreadable checkpoint {}: {}"),
+    ("server.job_ticket", "🎫 Verify job {} queued"),
+    ("server.job_ticket_failed", "⚠️  Could not issue a job ticket ({}); the client will wait for the response"),
+    ("server.user_id", "👤 User ID: {}"),
+    ("server.tenant", "🏢 Tenant: {}"),
+    ("server.ciphertext", "📊 Ciphertext: {} bits"),
+    ("server.cipher", "🔐 Cipher: {}"),
+    ("server.saving_server_key", "🔑 Saving server key (first registration)..."),
+    ("server.server_key_saved", "✅ Server key saved to: {}"),
+    ("server.gpu_key_saved", "✅ Compressed server key for GPU evaluation saved to: {}"),
+    ("server.gpu_unavailable", "⚠️  GPU evaluation unavailable ({}), using the CPU"),
+    ("server.server_key_exists", "✅ Server key already exists"),
+    ("server.server_key_differs", "A different server key is already in place for tenant '{}'; replace it with `keys rotate`"),
+    ("server.db_load_failed", "❌ Database load failed: {}"),
+    ("server.db_creating", "🔧 Creating fresh database..."),
+    ("server.db_backed_up", "📦 Corrupt database backed up to: {}"),
+    ("server.user_exists", "⚠️  Template already enrolled (replacement confirmed: {})"),
+    ("server.duress_enrolled", "🚨 Duress finger enrolled"),
+    ("server.factor_enrolled", "🔁 Fallback factor enrolled: {}"),
+    ("server.template_saved", "💾 Template saved to database"),
+    ("server.total_templates", "📈 Total templates: {}"),
+    ("server.db_save_failed", "❌ Failed to save database: {}"),
+    ("server.response_sent", "📤 Response sent!"),
+    ("server.operation", "👥 Operation: {}"),
+    ("server.templates_moved", "💾 Templates moved: {} -> {}"),
+    ("server.audit_moved", "📜 Audit events moved: {}"),
+    ("server.audit_move_failed", "⚠️  Audit events of '{}' not moved: {}"),
+    ("server.factor", "🔑 Factor: {}"),
+    ("server.probe_ciphertext", "📊 Probe ciphertext: {} bits"),
+    ("server.server_key_loaded", "✅ Server key loaded"),
+    ("server.compute_backend", "⚙️  Evaluating on: {}"),
+    ("server.template_found", "✅ Enrolled template found"),
+    ("server.template_created", "   Created: {}"),
+    ("server.deserializing", "\n🔓 Deserializing FHE data..."),
+    ("server.deserialized", "✅ FHE data deserialized:"),
+    ("server.probe_key", "   Probe key:    {} bits"),
+    ("server.probe_iv", "   Probe IV:     {} bits"),
+    ("server.encrypted_threshold", "   Threshold:    {} bits (encrypted)"),
+    ("server.decrypting_probe", "\n🔐 FHE-Trivium decrypting PROBE fingerprint..."),
+    ("server.takes_long", "⚠️  This will take ~15-30 minutes!"),
+    ("server.probe_decrypted", "✅ Probe fingerprint decrypted (still encrypted!)"),
+    ("server.serializing", "\n📦 Serializing results..."),
+    ("server.serialized", "✅ Results serialized:"),
+    ("server.match_bytes", "   Match bytes:    {} bytes"),
+    ("server.distance_bytes", "   Distance bytes: {} bytes"),
+    ("server.peak_memory", "   Peak FHE data:  {} MB (estimated)"),
+    ("server.debug_result", "🚨 DEBUG: Match = {}, Distance = {}"),
+    ("server.response_sent_nl", "\n📤 Response sent!"),
+    ("server.decrypting_template", "\n🔐 FHE-Trivium decrypting {} fingerprint..."),
+    ("server.key_iv_bits", "   Key: {} bits, IV: {} bits"),
+    ("server.takes_long_again", "⚠️  This will take another ~15-30 minutes!"),
+    ("server.template_decrypted", "✅ {} fingerprint decrypted (still encrypted!)"),
+    ("server.matching", "\n🧬 FHE Matching against {} (computing Hamming distance)..."),
+    ("server.diff_done", "   ✅ Difference bits computed"),
+    ("server.distance_done", "   ✅ Hamming distance computed (11-bit encrypted counter)"),
+    ("server.threshold_done", "   ✅ Threshold comparison done (threshold: {} bits)"),
+    ("server.encrypted_threshold_done", "   ✅ Threshold comparison done (encrypted threshold)"),
+    ("server.error_policy", "⚠️  Error policy: {} -> {}"),
+    ("server.attestation_created", "🔏 Attestation key created: {}"),
+    ("admin.database_version", "🗄️  Database version {}"),
+    ("admin.list_entry", "{} created {}  {} bytes  retain until {}"),
+    ("admin.key_issued", "🔑 Admin key (shown once, replaces any previous one):"),
+    ("admin.usage", "Usage: server admin <show <user_id> [--tenant <name>] | list | stats | key | tenant ...>"),
+    ("admin.user_id", "👤 User ID:   {}"),
+    ("admin.tenant", "   Tenant:    {}"),
+    ("admin.created", "   Created:   {}"),
+    ("admin.updated", "   Updated:   {}"),
+    ("admin.enrolled", "   Enrolled:  {} times, {} previous templates kept"),
+    ("admin.factors", "   Factors:   {}"),
+    ("admin.duress_enrolled", "   Duress:    enrolled"),
+    ("admin.duress_none", "   Duress:    none"),
+    ("admin.consent", "   Consent:   {}"),
+    ("admin.purpose", "   Purpose:   {}"),
+    ("admin.retention", "   Retention: {}"),
+    ("admin.retention_unbounded", "unbounded"),
+    ("admin.no_consent", "   Consent:   not recorded"),
+    ("admin.stats_version", "🗄️  Database version {} ({})"),
+    ("admin.stats_users", "   Users:     {} in {} tenants"),
+    ("admin.stats_templates", "   Templates: {}"),
+    ("admin.stats_database", "   Database:  {} bytes"),
+    ("admin.stats_database_postgres", "   Database:  on the Postgres server"),
+    ("admin.stats_blobs", "   Blobs:     {} bytes"),
+    ("server.admin_user_id", "The user id '{}' is reserved for admin requests"),
+    ("fhe.init_state", "   🔧 Initializing Trivium state (288 bits)..."),
+    ("fhe.warmup", "   ⏳ Warmup phase (1152 cycles)..."),
+    ("fhe.warmup_progress", "      Progress: {}/1152"),
+    ("fhe.warmup_done", "   ✅ Warmup complete!"),
+    ("fhe.keystream", "   🔑 Generating {} keystream bits..."),
+    ("fhe.decryption_title", "\n🔓 Homomorphic Trivium Decryption:"),
+    ("fhe.xoring", "   ⚙️  XORing ciphertext with keystream..."),
+    ("fhe.resuming", "   ⏩ Resuming from checkpoint at cycle {}..."),
+    ("fhe.decryption_done", "   ✅ Decryption complete!"),
+];
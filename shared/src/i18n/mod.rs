@@ -0,0 +1,206 @@
+//! Message catalog for user-facing console output.
+//!
+//! Every progress/result line printed by the client and server is looked up
+//! here by key, so the same run can be shown in English or Turkish. The
+//! locale comes from `--lang <en|tr>`, then `FINGERPRINT_LANG`, then the
+//! usual `LC_ALL` / `LC_MESSAGES` / `LANG` variables; English is the default.
+//!
+//! Error values, audit records and files written for other programs stay in
+//! English: only what a person reads on the console is translated.
+//!
+//! Messages use `{}` placeholders that are filled in order. A key missing in
+//! a locale falls back to English, and a key missing everywhere is printed
+//! as-is so a typo is visible instead of silently dropping the line.
+//!
+//! A thread can install a [`LineSink`]: its `trln!`/`etrln!` lines are then
+//! tagged and copied to a log file (the server's per-job logs).
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::OnceLock;
+
+mod en;
+mod tr;
+
+pub const LANG_ENV: &str = "FINGERPRINT_LANG";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Tr,
+}
+
+impl Locale {
+    /// `en`, `tr`, `tr_TR.UTF-8`, ... (case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        let lang = s.split(['_', '.', '-']).next()?.to_lowercase();
+        match lang.as_str() {
+            "en" | "c" | "posix" => Some(Locale::En),
+            "tr" => Some(Locale::Tr),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => en::MESSAGES,
+            Locale::Tr => tr::MESSAGES,
+        }
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Pick the locale and remove `--lang <x>` / `--lang=<x>` from the arguments
+pub fn init(args: &mut Vec<String>) {
+    let mut chosen = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--lang" && i + 1 < args.len() {
+            chosen = Locale::parse(&args[i + 1]);
+            args.drain(i..i + 2);
+        } else if let Some(value) = args[i].strip_prefix("--lang=") {
+            chosen = Locale::parse(value);
+            args.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    let _ = LOCALE.set(chosen.unwrap_or_else(from_env));
+}
+
+fn from_env() -> Locale {
+    [LANG_ENV, "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .and_then(|v| Locale::parse(&v))
+        .unwrap_or(Locale::En)
+}
+
+/// Active locale (environment-based if `init` was never called)
+pub fn locale() -> Locale {
+    *LOCALE.get_or_init(from_env)
+}
+
+fn lookup(locale: Locale, key: &str) -> Option<&'static str> {
+    locale.catalog().iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+/// Message template for `key` in the active locale
+pub fn text(key: &str) -> &str {
+    lookup(locale(), key)
+        .or_else(|| lookup(Locale::En, key))
+        .unwrap_or(key)
+}
+
+/// Fill the `{}` placeholders of a template in order
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+pub fn render(key: &str, args: &[&dyn Display]) -> String {
+    fill(text(key), args)
+}
+
+/// Where the current thread's console lines also go
+pub struct LineSink {
+    pub tag: String,                // Prepended to every line, e.g. "[verify 1a2b3c4d]"
+    pub log: Option<std::fs::File>, // Copy of the lines (untagged)
+}
+
+thread_local! {
+    static SINK: RefCell<Option<LineSink>> = const { RefCell::new(None) };
+}
+
+/// Install (or with None remove) the current thread's line sink
+pub fn set_sink(sink: Option<LineSink>) {
+    SINK.with(|s| *s.borrow_mut() = sink);
+}
+
+/// Print a rendered line to stdout (or stderr), through the thread's sink if any
+pub fn emit(stderr: bool, line: &str) {
+    SINK.with(|sink| match sink.borrow_mut().as_mut() {
+        Some(sink) => {
+            // Keep leading blank separator lines in front of the tag
+            let body = line.trim_start_matches('\n');
+            let lead = &line[..line.len() - body.len()];
+            if let Some(log) = sink.log.as_mut() {
+                let _ = writeln!(log, "{}", body);
+            }
+            if stderr {
+                eprintln!("{}{} {}", lead, sink.tag, body);
+            } else {
+                println!("{}{} {}", lead, sink.tag, body);
+            }
+        }
+        None if stderr => eprintln!("{}", line),
+        None => println!("{}", line),
+    });
+}
+
+/// Translated message: `tr!("client.user_id", user_id)`
+#[macro_export]
+macro_rules! tr {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::render($key, &[$(&$arg as &dyn ::std::fmt::Display),*])
+    };
+}
+
+/// `println!` of a translated message
+#[macro_export]
+macro_rules! trln {
+    ($($t:tt)*) => {
+        $crate::i18n::emit(false, &$crate::tr!($($t)*))
+    };
+}
+
+/// `eprintln!` of a translated message
+#[macro_export]
+macro_rules! etrln {
+    ($($t:tt)*) => {
+        $crate::i18n::emit(true, &$crate::tr!($($t)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_placeholders_in_order() {
+        assert_eq!(fill("{} of {}", &[&1, &"two"]), "1 of two");
+        assert_eq!(fill("no args", &[]), "no args");
+    }
+
+    #[test]
+    fn parses_locale_names() {
+        assert_eq!(Locale::parse("tr_TR.UTF-8"), Some(Locale::Tr));
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse("de_DE"), None);
+    }
+
+    #[test]
+    fn catalogs_agree() {
+        for (key, message) in tr::MESSAGES {
+            let english = lookup(Locale::En, key).unwrap_or_else(|| panic!("'{}' missing in en", key));
+            assert_eq!(message.matches("{}").count(), english.matches("{}").count(), "placeholders of '{}'", key);
+        }
+        for (key, _) in en::MESSAGES {
+            assert!(lookup(Locale::Tr, key).is_some(), "'{}' missing in tr", key);
+        }
+    }
+}
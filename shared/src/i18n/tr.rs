@@ -0,0 +1,501 @@
+//! Turkish messages (see mod.rs)
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    // ==================== Client ====================
+    ("client.banner", "🔐 PARMAK İZİ KİMLİK DOĞRULAMA İSTEMCİSİ"),
+    ("client.trivium_self_test_failed", "Şifre öz testi başarısız, çalıştırılmıyor: {}"),
+    ("client.exchange_unencrypted", "⚠️  Sunucu değişim anahtarı yayınlamadı; istekler şifrelenmeden gönderiliyor"),
+    ("client.register_title", "\n📝 KAYIT MODU"),
+    ("client.user_id", "👤 Kullanıcı ID: {}"),
+    ("client.factor", "🔑 Faktör: {}"),
+    ("client.enrolling_duress", "🚨 ZORLAMA (duress) parmağı olarak kaydediliyor"),
+    ("client.removing_old_key", "🗑️  Test için eski istemci anahtarı siliniyor..."),
+    ("client.section_features", "\n🔬 ÖZNİTELİK ÇIKARIMI:"),
+    ("client.extracting", "📷 Parmak izi öznitelikleri çıkarılıyor..."),
+    ("client.template_bits", "📏 Şablon uzunluğu: {} bit"),
+    ("client.soft_attributes", "🏷️  Yumuşak biyometrik özellikler eklendi ({})"),
+    ("client.partial_coverage", "🧩 Kısmi örnek: {} / {} bölge kapsanıyor"),
+    ("client.quality", "📊 Kalite: bitlerin %{}'i 1, ayırt edicilik {}, {}/{} bölge geçerli"),
+    ("client.quality_warning", "⚠️  {}"),
+    ("client.quality_recapture", "🔁 Kayıt kalitesi düşük; daha iyi bir görüntüyle yeniden kaydetmeyi düşünün"),
+    ("client.samples_voted", "🗳️  {} görüntü tek şablonda oylandı, {} / {} bit güvenilir"),
+    ("client.capture_quality", "📷 Görüntü kalitesi {}/100 (sırt netliği %{}, kontrast %{}, ön plan %{})"),
+    ("client.fingers", "🖐️  {} parmaklı birleşik kayıt, kural: {}"),
+    ("client.fingers_probed", "🖐️  Kayıtlı {} parmağın tümü sorgulanıyor"),
+    ("client.rotations", "🔄 {} döndürülmüş varyant kaydedildi, en fazla ±{}°"),
+    ("client.transform", "🎭 Şablona {} iptal edilebilir dönüşümü uygulandı"),
+    ("client.fuzzy_sketch", "🧬 Bulanık taslak: {} bitlik anahtar, blok başına en fazla {} bit hatası düzeltilir ({} bitlik bloklar)"),
+    ("client.quality_mask", "🎚️  Kalite maskesi: {} / {} bit tutuldu ({})"),
+    ("client.bit_weights", "⚖️  Ağırlıklı uzaklık: toplam ağırlık {}, {} bit üzerinde"),
+    ("client.extracted", "✅ {} bit çıkarıldı"),
+    ("client.section_trivium", "\n🔐 TRIVIUM ŞİFRELEME:"),
+    ("client.random_key", "✅ Rastgele anahtar üretildi: {} bit"),
+    ("client.random_iv", "✅ Rastgele IV üretildi: {} bit"),
+    ("client.fingerprint_encrypted", "✅ Parmak izi şifrelendi: {} bit"),
+    ("client.sanity_passed", "✅ Trivium doğrulama kontrolü geçti"),
+    ("client.section_key_management", "\n🔐 FHE ANAHTAR YÖNETİMİ:"),
+    ("client.loading_key", "📂 Mevcut istemci anahtarı yükleniyor..."),
+    ("client.key_loaded", "✅ İstemci anahtarı yüklendi: {}"),
+    ("client.generating_keys", "🔑 Yeni FHE anahtarları üretiliyor (ilk kez)..."),
+    ("client.keygen_duration", "⏱️  Bu işlem ~10 saniye sürebilir..."),
+    ("client.parameter_set", "⚙️  Parametre seti: {}"),
+    ("client.key_saved", "✅ İstemci anahtarı kaydedildi: {}"),
+    ("client.server_key_size", "✅ Sunucu anahtarı sunucuya gönderilecek: ({} bayt)"),
+    ("client.gpu_key_size", "✅ GPU değerlendirmesi için sıkıştırılmış sunucu anahtarı gönderilecek: ({} bayt)"),
+    ("client.section_fhe_encryption", "\n🔒 FHE ŞİFRELEME:"),
+    ("client.encrypting_key_iv", "⏱️  Trivium anahtarı ve IV şifreleniyor..."),
+    ("client.consent", "📄 Onay: {} ({})"),
+    ("client.encrypted_key", "✅ Şifreli anahtar: {} bayt"),
+    ("client.encrypted_iv", "✅ Şifreli IV:      {} bayt"),
+    ("client.section_sending", "\n📤 İSTEK GÖNDERİLİYOR:"),
+    ("client.debug_info", "🔍 Hata ayıklama bilgisi:"),
+    ("client.request_sent", "✅ İstek sunucuya gönderildi!"),
+    ("client.section_waiting", "\n⏳ YANIT BEKLENİYOR:"),
+    ("client.registration_successful", "✅ KAYIT BAŞARILI!"),
+    ("client.response_user_id", "   Kullanıcı ID: {}"),
+    ("client.response_message", "   Mesaj: {}"),
+    ("client.response_timestamp", "   Zaman: {}"),
+    ("client.reenrolled", "🔁 {}. kayıt; önceki şablon geçmişte tutuluyor"),
+    ("client.registration_failed", "❌ KAYIT BAŞARISIZ!"),
+    ("client.policy_unavailable", "⚠️  Yedek politika alınamadı ({}), yedek faktörler devre dışı"),
+    ("client.fallback_trying", "\n🔁 YEDEK: '{}' faktörü deneniyor"),
+    ("client.authenticated_via", "✅ {} ile KİMLİK DOĞRULANDI"),
+    ("client.authentication_failed_after", "❌ {} denemeden sonra KİMLİK DOĞRULAMA BAŞARISIZ"),
+    ("client.attempt_error", "hata: {}"),
+    ("client.attempt_match", "eşleşme"),
+    ("client.attempt_no_match", "eşleşme yok"),
+    ("client.verify_title", "\n🔍 DOĞRULAMA MODU"),
+    ("client.long_wait", "Bu işlem çok uzun sürebilir..."),
+    ("client.job_state", "🎫 İş {}: {}"),
+    ("client.job_phase", "🎫 İş {}: {} ({})"),
+    ("client.verification_failed", "❌ DOĞRULAMA BAŞARISIZ!"),
+    ("client.fail_open", "⚠️  Sunucu bu denemeye hata politikası gereği izin verdi ({}); eşleştirme yapılmadı"),
+    ("client.identity_pinned", "🔑 {} sunucu kimliğine {} için güveniliyor (ilk kullanım)"),
+    ("client.session_established", "🤝 Sabitlenmiş sunucu kimliğiyle {} oturumu kuruldu (sona erme: {})"),
+    ("client.result_signed", "🔏 Sonuç {} sunucu kimliğiyle imzalanmış"),
+    ("client.result_session", "🔗 Sonuç {} oturumuna bağlı ({} tarihine kadar geçerli)"),
+    ("client.result_unsigned", "⚠️  Sonuç, yayınlanmış bir sunucu kimliğiyle imzalanmamış"),
+    ("client.authentication_successful", "✅ KİMLİK DOĞRULAMA BAŞARILI!"),
+    ("client.authentication_failed", "❌ KİMLİK DOĞRULAMA BAŞARISIZ!"),
+    ("client.result_user_id", "Kullanıcı ID:     {}"),
+    ("client.result_match", "Eşleşme Sonucu:   {}"),
+    ("client.result_distance", "Hamming Uzaklığı: {}/{} bit"),
+    ("client.result_match_only", "Hamming Uzaklığı: döndürülmedi (yalnızca eşleşme sonucu)"),
+    ("client.result_similarity", "Benzerlik:        %{}"),
+    ("client.result_threshold", "Eşik:             %{} (en fazla {} bit)"),
+    ("client.result_timestamp", "Zaman:            {}"),
+    ("client.result_backend", "Hesaplama:        {}"),
+    ("client.debug_server_side", "\n🚨 HATA AYIKLAMA (Sunucu tarafı):"),
+    ("client.debug_server_match", "   Sunucu Eşleşmesi: {}"),
+    ("client.debug_server_distance", "   Sunucu Uzaklığı:  {}/{}"),
+    ("client.extracting_probe", "📷 Sorgu parmak izi öznitelikleri çıkarılıyor..."),
+    ("client.probe_encrypted", "✅ Sorgu şifrelendi: {} bit"),
+    ("client.section_key_loading", "\n🔐 FHE ANAHTARI YÜKLENİYOR:"),
+    ("client.encrypting_key_iv_constant", "⏱️  Trivium anahtarı, IV ve sabit şifreleniyor..."),
+    ("client.encrypted_threshold", "✅ Şifreli eşik:    {} bayt"),
+    ("client.server_duration", "⚠️  Sunucu FHE işlemlerini yürütecek (~30-60 dakika)"),
+    ("client.section_decrypting", "\n🔓 SONUÇLARIN ŞİFRESİ ÇÖZÜLÜYOR:"),
+    ("client.section_oidc", "\n🎫 OIDC TOKEN DEĞİŞİMİ:"),
+    ("client.oidc_endpoint", "Uç nokta: {}"),
+    ("client.tokens_issued", "✅ Token'lar verildi ({})"),
+    ("client.tokens_expire", "   Geçerlilik: {} sn"),
+    ("client.account_title", "\n👥 HESAP MODU: {}"),
+    ("client.account_sent", "📤 İstek gönderildi, sunucu bekleniyor..."),
+    ("client.delete_title", "\n🗑️  SİLME MODU: {}"),
+    ("client.delete_proving", "🔑 Önce bir doğrulamayla sahiplik kanıtlanıyor..."),
+    ("client.deleted", "✅ {}"),
+    ("client.history_moved", "📜 Taşınan yerel geçmiş kayıtları: {}"),
+    ("client.input_image", "🖼️  Görüntü: {}"),
+    ("client.input_pin", "🔢 PIN: ****"),
+    ("client.usage", "❌ Kullanım: {}"),
+    ("client.cancel_no_job", "ℹ️  {} için iptal edilecek doğrulama işi yok"),
+    ("client.cancel_finished", "ℹ️  {} işi zaten bitti ({})"),
+    ("client.cancel_sent", "🛑 {} işi iptal ediliyor, sunucu bekleniyor..."),
+    ("client.cancel_done", "✅ {} işi iptal edildi"),
+    ("client.cancel_not_running", "ℹ️  {} işi sunucuda artık kuyrukta ya da çalışır durumda değildi"),
+    ("update.title", "\n🩹 KISMİ YENİDEN KAYIT"),
+    ("update.unchanged", "✅ Şablon değişmemiş, güncellenecek bir şey yok"),
+    ("update.regions", "🩹 {} bölge değişmiş, {} / {} bit gönderiliyor"),
+    ("update.failed", "❌ Güncelleme reddedildi: {}"),
+    ("update.done", "✅ Şablon güncellendi ({}/{} kısmi bit kullanıldı)"),
+    ("progress.encrypting", "Anahtar/IV şifreleniyor"),
+    ("progress.waiting", "⏳ Sunucu bekleniyor: {}, {}"),
+    ("progress.heartbeat", "son sinyal {} önce"),
+    ("progress.no_heartbeat", "henüz sinyal yok"),
+    ("batch.title", "\n📋 TOPLU KAYIT"),
+    ("batch.entries", "📋 {} kayıt ({}), aynı anda {} şifreleniyor"),
+    ("batch.entry_registered", "✅ [{}/{}] {} kaydedildi ({} ms)"),
+    ("batch.entry_failed", "❌ [{}/{}] {} başarısız: {}"),
+    ("batch.summary", "\n📊 {} kaydedildi, {} başarısız, toplam {} ({} s)"),
+    ("batch.failure", "   satır {}: {}: {}"),
+    ("calibration.title", "🔬 SENSÖR KALİBRASYONU"),
+    ("calibration.sample", "🖼️  Örnek: {}"),
+    ("calibration.levels", "🎚️  Seviyeler {}..{}, gama {}, {} dpi"),
+    ("calibration.preprocessing", "🧹 Ön işleme: eşitleme {}, bulanıklaştırma σ {}"),
+    ("calibration.enhancement", "✨ İyileştirme: bölütleme {}, CLAHE {}, ikilileştirme {}"),
+    ("calibration.written", "💾 {} dosyasına yazıldı (--sensor {} ile kullanın)"),
+    ("revoke.title", "\n🎭 DÖNÜŞÜM İPTALİ"),
+    ("revoke.done", "✅ Kayıt iptal edildi; {} dönüşümünün yerine {} geldi"),
+    ("revoke.register_again", "ℹ️  Giriş için birincil parmağı yeniden kaydedin: register {} <görüntü_yolu> --replace"),
+    ("rotate.title", "\n🔑 İSTEMCİ ANAHTARI YENİLEME"),
+    ("rotate.rotated", "✅ Yeni istemci anahtarı yerinde: {}"),
+    ("rotate.reregistering", "🔁 Bu makinede tutulan {} bitlik şablon yeni anahtarla kaydediliyor..."),
+    ("rotate.reregistered", "✅ {} yeni anahtarla yeniden kaydedildi"),
+    ("rotate.no_local_enrollment", "ℹ️  Bu makinede {} için şablon tutulmuyor; birincil parmağı --replace ile yeniden kaydedin"),
+    ("rotate.register_others", "ℹ️  Kiracının diğer kayıtları geçersiz kılındı; her kullanıcı birincil parmağını --replace ile yeniden kaydetmeli"),
+    ("profile.list_title", "\n👤 İSTEMCİ ANAHTARI PROFİLLERİ"),
+    ("profile.entry", "{} {}  istemci anahtarı: {}  ({})"),
+    ("profile.server_key", "     sunucu anahtarı {} üzerinde (kiracı {}, parmak izi {}), {} tarihinden beri"),
+    ("profile.fingerprint_mismatch", "⚠️  Sunucu {} sunucu anahtarını tutuyor, bu profil {} gönderdi; birincil parmak yeniden kaydedilene kadar doğrulamalar reddedilir"),
+    ("profile.fingerprint_recorded", "🔑 Sunucu {} sunucu anahtarını bildirdi; bu profil bundan sonra onu denetler"),
+    ("profile.shared_tenant", "⚠️  {} profili sunucu anahtarını bu kiracı için {} adresine gönderdi; sunucu kiracı başına bir anahtar tutar ve anahtar yenilenene kadar (`keys rotate`) başkasını reddeder"),
+    ("profile.deleted", "🗑️  {} profili silindi ({})"),
+    ("profile.deleted_server_keys", "ℹ️  {} sunucu hâlâ sunucu anahtarını tutuyor; oradaki kayıtları artık doğrulanamaz"),
+    ("profile.delete_active", "⚠️  {} şu an kullanılan profil"),
+    ("profile.delete_confirm", "{} profili ve istemci anahtarı ({}) silinsin mi? Kayıtları artık doğrulanamaz"),
+    ("profile.delete_choices", "[e/H]"),
+    ("profile.delete_kept", "ℹ️  {} profili korundu"),
+    ("recovery.nothing", "✅ {} için kurtarılacak sonuç yok"),
+    ("recovery.request_pending", "⏳ Önceki bir çalıştırmadan kalan doğrulama isteği henüz sunucu tarafından alınmadı"),
+    ("recovery.found_verify", "\n📬 Yarıda kalan bir çalıştırmadan doğrulama sonucu bulundu ({})"),
+    ("recovery.decrypt_failed", "❌ Önceki sonucun şifresi çözülemedi (istemci anahtarı değişti mi?): {}"),
+    ("recovery.server_failed", "❌ Önceki doğrulama sunucuda başarısız oldu"),
+    ("recovery.unreadable", "❌ Önceki sonuç okunamadı: {}"),
+    ("recovery.job_running", "⏳ {} doğrulama işi sunucuda hâlâ {} durumunda; sonucu bir sonraki sefer sunulacak"),
+    ("recovery.discarded", "🗑️  Önceki sonuç silindi"),
+    ("recovery.register_completed", "\n📬 Yarıda kalan kayıt tamamlanmış: {} ({})"),
+    ("recovery.register_failed", "\n📬 Yarıda kalan kayıt başarısız olmuş: {}"),
+    ("recovery.register_unreadable", "\n📬 Yarıda kalan çalıştırmadan okunamayan kayıt sonucu: {}"),
+    ("recovery.finished_at", "bitiş {}"),
+    ("recovery.prompt", "   Şimdi çözülüp gösterilsin mi?"),
+    ("recovery.prompt_choices", "[E/h]"),
+    ("client.help", r#"
+🔐 TRANSŞİFRELEMELİ PARMAK İZİ KİMLİK DOĞRULAMA İSTEMCİSİ
+
+KULLANIM:
+  cargo run --release -- <MOD> <KULLANICI_ID> <GÖRÜNTÜ_YOLU>
+
+MODLAR:
+  register   Yeni bir parmak izi şablonu kaydet
+             --duress: zorlama parmağını kaydet, --second-finger: yedek parmak kaydet
+             --consent-ref <REF> --purpose <AMAÇ> [--retain-days <N>]: onay kaydı
+             --replace: kayıtlı bir şablonun değiştirilmesini onayla (sunucu eskisini saklar)
+             --sample <GÖRÜNTÜ_YOLU>: başka bir görüntü (tekrarlanabilir, toplam 3-9); çoğunluğu kaydedilir
+             --fused-finger <GÖRÜNTÜ_YOLU>: başka bir parmak (tekrarlanabilir, toplam 2-4), birlikte karar verilir
+             --fusion <any|two-of-n>: herhangi bir parmak veya en az ikisi eşleşmeli (varsayılan any)
+  register-pin  Yedek faktör olarak PIN kaydet: register-pin <KULLANICI_ID> <PIN>
+  register-batch  CSV manifestosundaki her user_id,image_path satırını tek istemci anahtarıyla kaydet:
+             register-batch <MANIFEST.CSV> [--concurrency <N> (varsayılan: CPU sayısı)]
+  update     Kayıtlı parmak izini yalnızca değişen bölgelerini göndererek tazele
+             --region-bits <N> (varsayılan 64)
+  verify     Parmak izini kayıtlı şablonla doğrula
+             --fallback-finger <GÖRÜNTÜ_YOLU>, --fallback-pin <PIN>: yedek faktörler
+             --partial: yalnızca görüntünün kapsadığı bölgeleri karşılaştır (kısmi dokunuş)
+             --fused-finger <GÖRÜNTÜ_YOLU>: birleşik kaydın diğer parmakları, kayıt sırasıyla
+             register/verify/login: --hand <left|right> --finger <thumb|index|middle|ring|little>
+             --pattern <arch|loop|whorl> [--blind-soft]: eşleştirmeden önce denetlenen yumuşak özellikler
+  login      Doğrula, ardından sunucu makbuzunu OIDC token'larıyla değiştir
+             --oidc-config <YOL> (varsayılan: ~/.fingerprint_client/oidc.json)
+  recover    Yarıda kalan register/verify çalıştırmasından kalan sonucu göster ve temizle
+  estimate   Bir doğrulamanın yükleme boyutunu, kuyruk bekleme ve hesaplama süresini göster
+  history    Yerel kimlik doğrulama geçmişini göster (isteğe bağlı tek kullanıcı için)
+  bench      Bu makinede çıkarım, şifre, anahtar üretimi ve FheBool şifrelemesini ölç,
+             ölçülen kapı maliyetinden doğrulama süresini tahmin et
+             --image <GÖRÜNTÜ_YOLU>: öznitelik çıkarımını da ölç, --rounds <N> (varsayılan 3)
+  advise-params  TFHE parametre setlerini ölç ve anahtar üretimi için birini öner
+             --security <BİT> (varsayılan 128), --pfail <LOG2> (varsayılan 40)
+             --latency-budget <SN> (varsayılan 1800), --apply: fhe_params.json dosyasına yaz
+  calibrate-sensor  Örnek görüntülerden sensör profili çıkar: <AD> <GÖRÜNTÜ>... [--dpi <N>]
+  eval       Bir veri kümesinde şifresiz eşleştiricinin FAR/FRR değerleri (<PARMAK>_<ÖRNEK>.tif dosyaları):
+             eval <VERİ_DİZİNİ> [--out <CSV> (varsayılan eval.csv)] [--step <BİT> (varsayılan 1)]
+  tune-threshold  Bir veri kümesinin ROC/DET noktalarını yaz ve hedef FAR için eşik öner:
+             tune-threshold <VERİ_DİZİNİ> [--target-far <YÜZDE> (varsayılan 0.1)] [--roc <CSV> (varsayılan roc.csv)]
+             --apply: sunucunun veritabanı dizinindeki thresholds.json dosyasına kaydet (veya --thresholds <YOL>)
+  rename-user  Kullanıcıyı yeniden adlandır: rename-user <ESKİ_KULLANICI_ID> <YENİ_KULLANICI_ID>
+  merge-users  Bir kullanıcıyı diğerine birleştir: merge-users <KAYNAK_KULLANICI_ID> <HEDEF_KULLANICI_ID>
+  delete     Kullanıcının kaydını sil: delete <KULLANICI_ID> [--prove <GÖRÜNTÜ_YOLU>]
+             --prove: önce doğrula ve sahiplik kanıtını sun; o olmadan istek bir oturumla
+             kanıtlanır (sabitlenmiş bir sunucu kimliği gerekir)
+  revoke     Kullanıcının iptal edilebilir dönüşümünü iptal et ve yenisini ver: revoke <KULLANICI_ID>;
+             birincil parmak yeniden kaydedilene kadar eşleştirme reddedilir
+  keys       FHE istemci anahtarlarını yönet: keys <rotate [<KULLANICI_ID>]|list|delete <PROFİL> [--yes]>
+             rotate: anahtarı değiştir (varsayılan kullanıcı: bu makinedeki kayıt), yeni sunucu
+             anahtarını gönder, kiracının kayıtlarını geçersiz kıl ve bu makinede tutulan
+             şablonu yeniden kaydet; kullanıcının oturumuyla ya da FINGERPRINT_ADMIN_KEY
+             içindeki yönetici anahtarıyla kanıtlanır
+             list: profilleri, anahtarları olup olmadığını ve sunucu anahtarlarının nerede olduğunu göster
+             delete: adlandırılmış bir profilin anahtar deposunu sil
+  admin      Sunucuyu operatör olarak sorgula: admin <list|stats> (stdout'a JSON)
+             Sunucunun yönetici anahtarı FINGERPRINT_ADMIN_KEY içinde olmalı
+  cancel     Kullanıcının kuyruktaki ya da çalışan doğrulamasını iptal et: cancel <KULLANICI_ID>
+  agent      Masaüstü uygulamaları için yerel ajanı çalıştır (Unix soketi)
+             --socket <YOL> (varsayılan: ~/.fingerprint_client/agent.sock)
+             --capture-dir <DİZİN> (varsayılan: ~/.fingerprint_client/capture)
+  --rpc      stdin/stdout üzerinden JSON-RPC 2.0 isteklerine yanıt ver (satır başına bir)
+  --lang <en|tr>  Konsol çıktısının dili (FINGERPRINT_LANG veya LANG ile de seçilir)
+  --sensor <AD>  Çıkarımda uygulanan sensör profili (FINGERPRINT_SENSOR ile de seçilir)
+  --dpi <N>  Görüntüler kaydetmiyorsa yakalama çözünürlüğü; çözünürlüğü bilinen
+             görüntüler çıkarımdan önce 500 dpi'ye ölçeklenir
+  --server-url <URL>  İstekleri değişim dizini yerine sunucunun HTTP arayüzüne
+             (`server http`) ya da grpc://host:port ile akışlı gRPC arayüzüne
+             (`server grpc`) gönder
+  --profile <AD>  Anahtarları, kimlik bilgisini ve kayıtları ~/.fingerprint_client/<AD>/ içinde tut;
+             böylece bir makinenin kullanıcıları birbirinin anahtarını değiştirmez
+             (ayrıca FINGERPRINT_PROFILE; varsayılan: ~/.fingerprint_client/)
+  --output <human|json>  json ile register ve verify stdout'a tek bir JSON belgesi (success,
+             user_id, distance, similarity, timings, hata kodu) yazar, ilerleme stderr'e gider
+  help       Bu yardım mesajını göster
+
+ÖRNEKLER:
+  # Yeni kullanıcı kaydet
+  cargo run --release -- register user_123 ../data/fingerprints/101_1.tif
+
+  # Kullanıcı kimliğini doğrula
+  cargo run --release -- verify user_123 ../data/fingerprints/101_2.tif
+
+  # Parmak izinin ne zaman ve nerede kullanıldığını göster
+  cargo run --release -- history user_123
+
+NOTLAR:
+  - İstemci anahtarı: ~/.fingerprint_client/client_key.bin
+  - Kaydın Trivium anahtarı ve şablonu `update` için ~/.fingerprint_client/enrollment.json
+    dosyasında tutulur; yaklaşık 1024 güncellenmiş bitten sonra tam kayıt gerekir
+  - Geçmiş kaydı: ~/.fingerprint_client/history.jsonl
+  - Yeni anahtarlar ~/.fingerprint_client/fhe_params.json içindeki parametre setini kullanır (varsa)
+  - Sunucu anahtarı yalnızca ilk kayıtta gönderilir
+  - fingerprint.toml (veya FINGERPRINT_CONFIG ile verilen dosya) dizinleri, eşleşme eşiğini,
+    yeni anahtarların parametre setini ve log filtresini ayarlar; ortam değişkenleri ondan önce gelir
+  - FINGERPRINT_EXCHANGE değişim alanını seçer: bir dizin (varsayılan ../exchange),
+    bir http(s):// ya da grpc:// sunucu adresi (--server-url ile aynı) veya s3://<bucket>/<prefix> (AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION,
+    S3 uyumlu depolar için FINGERPRINT_S3_ENDPOINT)
+  - İstekler değişim alanındaki kullanıcıya özel bir klasöre gider (users/<hash>/, mod 0700)
+  - Doğrulama isteği hemen bir iş numarası alır; ilerleme verify_status_<job_id>.json
+    dosyasından (HTTP ile GET verify_status/<job_id>) okunur
+  - Sunucu kimlik anahtarı ilk kullanımda ~/.fingerprint_client/known_servers.json dosyasına sabitlenir
+    (veya FINGERPRINT_SERVER_IDENTITY ile ayarlanır); başka bir anahtarla imzalı sonuçlar reddedilir
+  - Sunucu kimliği sabitlendikten sonra kayıt/doğrulama önce kayıt kimlik bilgisini kanıtlayan bir
+    oturum el sıkışması yapar (~/.fingerprint_client/credential_key.bin, kayıtta sunucuya bildirilir)
+  - Doğrulama sonuçları oturumun meydan okumasıyla (challenge) imzalanır ve oturum sona erdikten
+    sonra reddedilir (sunucunun exchanges.json dosyasında session_expiry_secs, varsayılan 2 saat)
+  - Değişim dosyaları sunucunun exchange_key.json içindeki anahtarına şifrelenir
+    (istemci değişim anahtarı: ~/.fingerprint_client/exchange_key.bin)
+  - FHE işlemleri nedeniyle doğrulama 30-60 dakika sürebilir
+  - RPC metotları: enroll, verify, status, decrypt-result
+  - Her istekle bir kiracı API anahtarı göndermek için FINGERPRINT_API_KEY ayarlayın
+  - FINGERPRINT_TEMPLATE_BITS (128, 256, 512, 1024; varsayılan 1024) kayıtta kullanılan
+    şablon uzunluğunu belirler; sunucunun server_status.json içinde listelediği bir değer olmalıdır
+  - FINGERPRINT_CIPHER=kreyvium, Trivium (80 bit) yerine Kreyvium (128 bit anahtar ve IV)
+    ile şifreler. Sunucu kabul ettiği şifreleri server_status.json içinde listeler
+  - FINGERPRINT_MATCH_THRESHOLD (en fazla farklı bit) her doğrulamayla FHE ile şifreli
+    gönderilir ve sunucunun eşiğinin yerine geçer; sunucu değeri hiç öğrenmez
+  - FINGERPRINT_RESULT_MODE=match_only doğrulamadan yalnızca şifreli eşleşme bitini ister;
+    sunucu bu durumda bir istemcinin tepe tırmanma saldırısında kullanabileceği uzaklığı döndürmez
+  - FINGERPRINT_ENROLL_THRESHOLD yeni kayıtla birlikte kullanıcıya özel bir eşik saklar,
+    FHE ile şifreli ("plain:<bit>" sunucunun okuyabileceği şekilde saklar)
+  - FINGERPRINT_QUALITY_MASK=plain her görüntünün bit bazında kalite maskesini gönderir,
+    böylece çıkarımın güvenilmez bulduğu bitler karşılaştırılmaz; =encrypted FHE ile şifreli gönderir
+  - FINGERPRINT_BIT_WEIGHTS=lbp çıkarıcının bit ağırlıklarını kaydeder: farklı kenar deseni
+    bitleri uzaklığa düz alan bitlerinden daha çok katkı yapar
+  - FINGERPRINT_EXTRACTOR=gabor şablonları LBP histogramları yerine Gabor filtre bankası
+    enerjilerinden oluşturur; kayıt ve doğrulamada aynı çıkarıcıyı kullanın
+  - FINGERPRINT_ALIGN=core her görüntüyü çıkarımdan önce çekirdek noktası merkeze gelecek
+    şekilde kaydırır; kayıt ve doğrulamada aynı şekilde ayarlayın
+  - FINGERPRINT_MIN_CAPTURE_QUALITY (0-100, varsayılan 40) kaydın kabul ettiği en düşük
+    görüntü kalitesi puanıdır; daha düşük puanlarda yeniden çekim istenir
+  - FINGERPRINT_FUZZY=on birincil parmağı bulanık çıkarıcı olarak kaydeder: eşik yerine
+    şifreli bir BCH taslağı; her blokta en fazla FINGERPRINT_FUZZY_ERRORS bit farklıysa
+    eşleşir; eval her seçimin FRR ve FAR değerini ölçer
+  - FINGERPRINT_GPU_KEY=on kayıtta sıkıştırılmış bir sunucu anahtarı da gönderir; gpu özelliğiyle
+    derlenmiş bir sunucu doğrulamayı GPU'da yapar. Sonuçlar işi çalıştıran birimi gösterir
+  - Parmak şablonları şifrelemeden önce kullanıcıya özel bir sırla karıştırılır ve tuzlanır
+    (~/.fingerprint_client/transforms.json); sunucu yalnızca dönüşümün kimliğini saklar
+  - Görüntüler PNG, TIFF, BMP, JPEG ve benzerleridir; WSQ dosyalarını önce NBIS ile çözün
+    (dwsq raw finger.wsq -r) ve .ncm eşlik dosyasıyla okunan .raw görüntüyü verin
+  - Görüntü yerine live:fprint (libfprint sensörü) veya live:/dev/video0 (UVC kamera),
+    bir kare en düşük kaliteye ulaşana kadar çekim yapar; --features capture gerektirir
+  - Anonim süre telemetrisi, ~/.fingerprint_client/telemetry.json içinde {"enabled": true}
+    olmadıkça kapalıdır (kayıtlar telemetry.jsonl dosyasına veya bir "endpoint" URL'sine gider)
+    "#),
+    // ==================== Server ====================
+    ("server.banner", "🖥️  PARMAK İZİ KİMLİK DOĞRULAMA SUNUCUSU"),
+    ("server.exchange_key", "🔐 Değişim şifreleme anahtarı: {}"),
+    ("server.identity_key", "🔏 Sunucu kimlik anahtarı: {}"),
+    ("server.jobs_interrupted", "⚠️  Son kapanışta yarıda kalan {} doğrulama işine devam edilemedi, başarısız olarak işaretlendi"),
+    ("server.waiting", "\n⏳ İstekler bekleniyor...\n"),
+    ("server.maintenance_failed", "⚠️  Bakım başarısız: {}"),
+    ("server.poll_failed", "⚠️  '{}' değişim alanı yoklanamadı: {}"),
+    ("server.register_detected", "\n📥 KAYIT İSTEĞİ ALINDI{}"),
+    ("server.register_claim_failed", "❌ Kayıt isteği alınamadı: {}"),
+    ("server.verify_detected", "\n📥 DOĞRULAMA İSTEĞİ ALINDI{}"),
+    ("server.verify_claim_failed", "❌ Doğrulama isteği alınamadı: {}"),
+    ("server.session_issued", "🤝 Oturum el sıkışması yanıtlandı{}"),
+    ("server.session_failed", "❌ Oturum isteği başarısız: {}"),
+    ("server.session_expires", "⏳ Oturumun sona erme zamanı: {}"),
+    ("server.session_verified", "🤝 Oturum doğrulandı (kayıt kimlik bilgisi kanıtlandı)"),
+    ("server.soft_mismatch", "🚫 Örneğin {} özelliği kayıtlı parmakla çelişiyor, eşleştirme atlandı"),
+    ("server.partial_probe", "🧩 Kısmi örnek: {} / {} bit karşılaştırılıyor"),
+    ("server.reliable_bits", "🎯 Çok örnekli kayıt: {} / {} güvenilir bit karşılaştırılıyor"),
+    ("server.samples_enrolled", "🎯 {} örnek kaydedildi, {} güvenilir bit"),
+    ("server.rotations_enrolled", "🔄 {} döndürülmüş varyant kaydedildi"),
+    ("server.capture_quality", "📷 Görüntü kalitesi puanı: {}/100"),
+    ("server.fuzzy_enrolled", "🧬 Bulanık kayıt: {} bitlik anahtar, blok başına {} hata düzeltilir (GF(2^{}))"),
+    ("server.fuzzy_matching", "🧬 Örnek {} taslağına göre düzeltiliyor ({} blok, her biri {} hata)..."),
+    ("server.fingers_enrolled", "🖐️  {} parmak kaydedildi, {} kuralıyla birleştirilir"),
+    ("server.fingers_fused", "🖐️  {} parmağın kararları {} kuralıyla birleştirildi"),
+    ("server.rotations_matched", "🔄 Şablon ve {} döndürülmüş varyant arasından en küçük uzaklık seçildi"),
+    ("server.rotations_dropped", "🔄 {} döndürülmüş varyant silindi: güncellemeden önceye ait"),
+    ("server.quality_bits", "🎚️  Kalite maskeleri: {} / {} bit karşılaştırılıyor"),
+    ("server.quality_encrypted", "🎚️  {} bit üzerinde şifreli kalite maskesi, eşik FHE altında ölçekleniyor"),
+    ("server.delta_detected", "\n🩹 KISMİ KAYIT İSTEĞİ ALINDI{}"),
+    ("server.delta_claim_failed", "❌ Kısmi kayıt isteği alınamadı: {}"),
+    ("server.delta_applied", "🩹 Kısmi güncelleme kaydedildi: {} bölge, {} bit ({}/{} kısmi bit kullanıldı)"),
+    ("server.deltas_spliced", "🩹 {} kısmi güncelleme şablona eklendi ({} bit)"),
+    ("server.policy_sent", "📋 Yedek politika gönderildi{}"),
+    ("server.policy_failed", "❌ Politika isteği başarısız: {}"),
+    ("server.admin_sent", "🗄️  Yönetici {} isteği yanıtlandı{}"),
+    ("server.admin_failed", "❌ Yönetici isteği başarısız: {}"),
+    ("server.account_detected", "\n📥 HESAP İSTEĞİ ALINDI{}"),
+    ("server.account_completed", "✅ Hesap işlemi başarıyla tamamlandı!"),
+    ("server.account_failed", "❌ Hesap işlemi başarısız: {}"),
+    ("server.transform_renewed", "🎭 Yeni iptal edilebilir dönüşüm: eskisiyle kaydedilmiş {} parmak silindi"),
+    ("server.transform_revoked", "🎭 {} dönüşümü iptal edildi; yeniden kayda kadar eşleştirme reddedilir"),
+    ("server.blobs_released", "🧹 Bırakılan şablonların {} şablon blob'u silindi"),
+    ("server.blobs_release_failed", "⚠️  Bırakılan şablonların blob'ları silinemedi (server compact silecek): {}"),
+    ("server.revoke_detected", "\n📥 İPTAL İSTEĞİ ALGILANDI{}"),
+    ("server.revoke_completed", "✅ Kayıt iptal edildi"),
+    ("server.revoke_failed", "❌ İptal başarısız: {}"),
+    ("server.keys_rotated", "🔑 Sunucu anahtarı değiştirildi; kiracının {} kaydı yeniden yapılmalı"),
+    ("server.rotate_detected", "\n📥 ANAHTAR YENİLEME İSTEĞİ ALGILANDI{}"),
+    ("server.rotate_completed", "✅ İstemci anahtarı yenilendi"),
+    ("server.rotate_failed", "❌ Anahtar yenileme başarısız: {}"),
+    ("server.server_key_fingerprint_ok", "🔑 Sunucu anahtarı parmak izi istemcininkiyle eşleşiyor"),
+    ("server.delete_detected", "\n📥 SİLME İSTEĞİ ALGILANDI{}"),
+    ("server.delete_completed", "✅ Kayıt silindi"),
+    ("server.delete_failed", "❌ Silme başarısız: {}"),
+    ("server.ownership_proven", "🔑 Sahiplik eşleşen bir doğrulamayla kanıtlandı"),
+    ("server.receipt_detected", "\n📥 MAKBUZ İSTEĞİ ALGILANDI{}"),
+    ("server.receipt_completed", "✅ Makbuz verildi"),
+    ("server.receipt_failed", "❌ Makbuz reddedildi: {}"),
+    ("server.waiting_next", "\n⏳ Sonraki istek bekleniyor...\n"),
+    ("server.job_queued", "🚦 {} işi kuyruğa alındı ({} çalışıyor, {} bekliyor)"),
+    ("server.job_completed", "✅ {} başarıyla tamamlandı!"),
+    ("server.job_failed", "❌ {} başarısız: {}"),
+    ("server.job_started", "▶️  {} başladı (kayıt: {})"),
+    ("server.job_failure_unanswered", "⚠️  Başarısız {} işi yanıtlanamadı: {}"),
+    ("server.job_cancelled", "🛑 {} iptal edildi"),
+    ("server.job_interrupted", "⏸️  {} kapanış için durduruldu, yeniden başlatmada devam edecek"),
+    ("server.cancel_requested", "🛑 {} işi için iptal istendi{}"),
+    ("server.cancel_unknown", "ℹ️  {} işi için iptal isteği{}: kuyrukta ya da çalışır durumda değil"),
+    ("server.cancel_failed", "❌ İptal isteği başarısız: {}"),
+    ("server.shutting_down", "\n🛑 Kapanıyor: çalışan işler durduruluyor, yeniden başlatmada kontrol noktalarından devam edecekler (hemen çıkmak için tekrar Ctrl+C)"),
+    ("server.stopped", "👋 Sunucu durduruldu"),
+    ("server.http_listening", "🌐 HTTP değişim alanı http://{} adresinde dinliyor"),
+    ("server.http_stopped", "❌ HTTP ön ucu durdu: {}"),
+    ("server.grpc_listening", "🌐 gRPC değişim alanı grpc://{} adresinde dinliyor"),
+    ("server.grpc_stopped", "❌ gRPC ön ucu durdu: {}"),
+    ("server.remote_refused", "🚫 {} {} reddedildi: {}"),
+    ("server.worker_spawn_failed", "⚠️  {} çalışanı başlatılamadı: {}"),
+    ("server.worker_panicked", "💥 {} çalışanı bir çökmeden kurtarıldı: {}"),
+    ("server.tfhe_threads", "🧵 tfhe iş parçacıkları: {} (doğrulama işi başına {})"),
+    ("server.tfhe_threads_pinned", "🧵 tfhe iş parçacıkları: {} (doğrulama işi başına {}, çekirdeklere sabitlenmiş)"),
+    ("server.tfhe_pool_failed", "⚠️  tfhe iş parçacığı havuzu boyutlandırılamadı ({}), rayon varsayılanı kullanılıyor"),
+    ("server.job_pool_failed", "⚠️  İşin tfhe iş parçacıkları başlatılamadı ({}), genel havuz kullanılıyor"),
+    ("server.core_pin_failed", "⚠️  {} {} numaralı çekirdeğe sabitlenemedi"),
+    ("server.job_pool_outdated", "🧵 {}: iş parçacığı sayısı {} olarak değişti, yeni bir çalışan başlatılıyor"),
+    ("server.job_logs_purged", "🧹 {} eski iş kaydı silindi"),
+    ("server.job_resumed", "⏩ {} doğrulama işi yeniden başlatma sonrası kuyruğa alındı, kontrol noktalarından devam edecek"),
+    ("server.checkpoint_result", "⏩ {} eşleşmesi kontrol noktasından geri yüklendi"),
+    ("server.checkpoint_failed", "⚠️  {} kontrol noktası kaydedilemedi: {}"),
+    ("server.checkpoint_unreadable", "⚠️  Okunamayan kontrol noktası yok sayılıyor {}: {}"),
+    ("server.job_ticket", "🎫 {} doğrulama işi kuyruğa alındı"),
+    ("server.job_ticket_failed", "⚠️  İş bileti verilemedi ({}); istemci yanıtı bekleyecek"),
+    ("server.user_id", "👤 Kullanıcı ID: {}"),
+    ("server.tenant", "🏢 Kiracı: {}"),
+    ("server.ciphertext", "📊 Şifreli metin: {} bit"),
+    ("server.cipher", "🔐 Akış şifresi: {}"),
+    ("server.saving_server_key", "🔑 Sunucu anahtarı kaydediliyor (ilk kayıt)..."),
+    ("server.server_key_saved", "✅ Sunucu anahtarı kaydedildi: {}"),
+    ("server.gpu_key_saved", "✅ GPU değerlendirmesi için sıkıştırılmış sunucu anahtarı kaydedildi: {}"),
+    ("server.gpu_unavailable", "⚠️  GPU değerlendirmesi kullanılamıyor ({}), CPU kullanılıyor"),
+    ("server.server_key_exists", "✅ Sunucu anahtarı zaten mevcut"),
+    ("server.server_key_differs", "'{}' kiracısı için başka bir sunucu anahtarı zaten mevcut; değiştirmek için `keys rotate` kullanın"),
+    ("server.db_load_failed", "❌ Veritabanı yüklenemedi: {}"),
+    ("server.db_creating", "🔧 Yeni veritabanı oluşturuluyor..."),
+    ("server.db_backed_up", "📦 Bozuk veritabanı yedeklendi: {}"),
+    ("server.user_exists", "⚠️  Şablon zaten kayıtlı (değiştirme onaylandı: {})"),
+    ("server.duress_enrolled", "🚨 Zorlama (duress) parmağı kaydedildi"),
+    ("server.factor_enrolled", "🔁 Yedek faktör kaydedildi: {}"),
+    ("server.template_saved", "💾 Şablon veritabanına kaydedildi"),
+    ("server.total_templates", "📈 Toplam şablon: {}"),
+    ("server.db_save_failed", "❌ Veritabanı kaydedilemedi: {}"),
+    ("server.response_sent", "📤 Yanıt gönderildi!"),
+    ("server.operation", "👥 İşlem: {}"),
+    ("server.templates_moved", "💾 Şablonlar taşındı: {} -> {}"),
+    ("server.audit_moved", "📜 Taşınan denetim kayıtları: {}"),
+    ("server.audit_move_failed", "⚠️  '{}' kullanıcısının denetim kayıtları taşınamadı: {}"),
+    ("server.factor", "🔑 Faktör: {}"),
+    ("server.probe_ciphertext", "📊 Sorgu şifreli metni: {} bit"),
+    ("server.server_key_loaded", "✅ Sunucu anahtarı yüklendi"),
+    ("server.compute_backend", "⚙️  Değerlendirme birimi: {}"),
+    ("server.template_found", "✅ Kayıtlı şablon bulundu"),
+    ("server.template_created", "   Oluşturulma: {}"),
+    ("server.deserializing", "\n🔓 FHE verisi çözümleniyor..."),
+    ("server.deserialized", "✅ FHE verisi çözümlendi:"),
+    ("server.probe_key", "   Sorgu anahtarı: {} bit"),
+    ("server.probe_iv", "   Sorgu IV:       {} bit"),
+    ("server.encrypted_threshold", "   Eşik:           {} bit (şifreli)"),
+    ("server.decrypting_probe", "\n🔐 FHE-Trivium ile SORGU parmak izinin şifresi çözülüyor..."),
+    ("server.takes_long", "⚠️  Bu işlem ~15-30 dakika sürecek!"),
+    ("server.probe_decrypted", "✅ Sorgu parmak izinin şifresi çözüldü (hâlâ şifreli!)"),
+    ("server.serializing", "\n📦 Sonuçlar serileştiriliyor..."),
+    ("server.serialized", "✅ Sonuçlar serileştirildi:"),
+    ("server.match_bytes", "   Eşleşme verisi:  {} bayt"),
+    ("server.distance_bytes", "   Uzaklık verisi:  {} bayt"),
+    ("server.peak_memory", "   En yüksek FHE verisi: {} MB (tahmini)"),
+    ("server.debug_result", "🚨 HATA AYIKLAMA: Eşleşme = {}, Uzaklık = {}"),
+    ("server.response_sent_nl", "\n📤 Yanıt gönderildi!"),
+    ("server.decrypting_template", "\n🔐 FHE-Trivium ile {} parmak izinin şifresi çözülüyor..."),
+    ("server.key_iv_bits", "   Anahtar: {} bit, IV: {} bit"),
+    ("server.takes_long_again", "⚠️  Bu işlem ~15-30 dakika daha sürecek!"),
+    ("server.template_decrypted", "✅ {} parmak izinin şifresi çözüldü (hâlâ şifreli!)"),
+    ("server.matching", "\n🧬 {} ile FHE eşleştirme (Hamming uzaklığı hesaplanıyor)..."),
+    ("server.diff_done", "   ✅ Fark bitleri hesaplandı"),
+    ("server.distance_done", "   ✅ Hamming uzaklığı hesaplandı (11 bit şifreli sayaç)"),
+    ("server.threshold_done", "   ✅ Eşik karşılaştırması tamamlandı (eşik: {} bit)"),
+    ("server.encrypted_threshold_done", "   ✅ Eşik karşılaştırması tamamlandı (şifreli eşik)"),
+    ("server.error_policy", "⚠️  Hata politikası: {} -> {}"),
+    ("server.attestation_created", "🔏 Tasdik anahtarı oluşturuldu: {}"),
+    ("admin.database_version", "🗄️  Veritabanı sürümü {}"),
+    ("admin.list_entry", "{} oluşturuldu {}  {} bayt  saklama sonu {}"),
+    ("admin.key_issued", "🔑 Yönetici anahtarı (bir kez gösterilir, öncekinin yerini alır):"),
+    ("admin.usage", "Kullanım: server admin <show <kullanıcı_id> [--tenant <ad>] | list | stats | key | tenant ...>"),
+    ("admin.user_id", "👤 Kullanıcı:  {}"),
+    ("admin.tenant", "   Kiracı:    {}"),
+    ("admin.created", "   Oluşturma: {}"),
+    ("admin.updated", "   Güncelleme: {}"),
+    ("admin.enrolled", "   Kayıt:     {} kez, {} önceki şablon tutuluyor"),
+    ("admin.factors", "   Faktörler: {}"),
+    ("admin.duress_enrolled", "   Zorlama:   kayıtlı"),
+    ("admin.duress_none", "   Zorlama:   yok"),
+    ("admin.consent", "   Onay:      {}"),
+    ("admin.purpose", "   Amaç:      {}"),
+    ("admin.retention", "   Saklama:   {}"),
+    ("admin.retention_unbounded", "süresiz"),
+    ("admin.no_consent", "   Onay:      kaydedilmemiş"),
+    ("admin.stats_version", "🗄️  Veritabanı sürümü {} ({})"),
+    ("admin.stats_users", "   Kullanıcılar: {}, {} kiracıda"),
+    ("admin.stats_templates", "   Şablonlar: {}"),
+    ("admin.stats_database", "   Veritabanı: {} bayt"),
+    ("admin.stats_database_postgres", "   Veritabanı: Postgres sunucusunda"),
+    ("admin.stats_blobs", "   Bloblar:   {} bayt"),
+    ("server.admin_user_id", "'{}' kullanıcı kimliği yönetici istekleri için ayrılmıştır"),
+    ("fhe.init_state", "   🔧 Trivium durumu hazırlanıyor (288 bit)..."),
+    ("fhe.warmup", "   ⏳ Isınma aşaması (1152 döngü)..."),
+    ("fhe.warmup_progress", "      İlerleme: {}/1152"),
+    ("fhe.warmup_done", "   ✅ Isınma tamamlandı!"),
+    ("fhe.keystream", "   🔑 {} anahtar akışı biti üretiliyor..."),
+    ("fhe.decryption_title", "\n🔓 Homomorfik Trivium Şifre Çözme:"),
+    ("fhe.xoring", "   ⚙️  Şifreli metin anahtar akışıyla XOR'lanıyor..."),
+    ("fhe.resuming", "   ⏩ {}. döngüdeki kontrol noktasından devam ediliyor..."),
+    ("fhe.decryption_done", "   ✅ Şifre çözme tamamlandı!"),
+];
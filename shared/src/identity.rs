@@ -0,0 +1,325 @@
+// shared/src/identity.rs
+
+//! Server identity and signed verification results.
+//!
+//! The server holds a long-lived Ed25519 identity key
+//! (`../database/identity.key`) and publishes its public half in the
+//! handshake file. Every verify response carries a `ResultAttestation`: a
+//! signature over the request id, the user id, a hash of the encrypted
+//! result, the response timestamp, the threshold the match was decided
+//! against and, when the request presented one, the session (id, challenge
+//! and expiry, see session.rs). Unlike the HMAC receipts in
+//! attestation.rs, anyone holding the public key can check it, so a relying
+//! party can prove that a given encrypted decision came from the authentic
+//! matching server and was produced for that request.
+//!
+//! The identity key also signs the handshake, binding the X25519 exchange
+//! key to it: a client that pins the identity (first use or configuration)
+//! can't be handed an impostor's exchange key or results.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::sealed::{hex, key_id, write_secret, Handshake};
+use crate::session::SessionClaim;
+
+/// Domain separation for the signed digests
+const RESULT_CONTEXT: &[u8] = b"fingerprint-fhe verify result v1";
+const HANDSHAKE_CONTEXT: &[u8] = b"fingerprint-fhe handshake v1";
+
+/// Signature over one verify result
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResultAttestation {
+    pub request_id: String,
+    pub user_id: String,
+    pub result_hash: String,    // hex SHA-256 over the encrypted match and distance bytes
+    pub timestamp: String,      // Same as the response timestamp
+    pub key_id: String,         // hex, first 8 bytes of SHA-256(identity public key)
+    pub signature: String,      // base64 Ed25519 signature over `digest()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionClaim>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<AppliedThreshold>,
+}
+
+/// Threshold a match bit was decided against: the server's bound (the policy
+/// or the one stored at enrollment), narrowed by the client's when it sent one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AppliedThreshold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<usize>,      // Plain maximum distance over the compared bits (None = the enrolled one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enrolled: Option<String>,   // hex SHA-256 of the encrypted threshold stored at enrollment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,     // hex SHA-256 of the client's encrypted threshold
+}
+
+impl ResultAttestation {
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(RESULT_CONTEXT);
+        let mut fields = vec![self.request_id.clone(), self.user_id.clone(), self.result_hash.clone(), self.timestamp.clone()];
+        if let Some(session) = &self.session {
+            fields.extend([session.session_id.clone(), session.challenge.clone(), session.expires_at.clone()]);
+        }
+        if let Some(threshold) = &self.threshold {
+            fields.extend([
+                threshold.policy.map(|policy| policy.to_string()).unwrap_or_default(),
+                threshold.enrolled.clone().unwrap_or_default(),
+                threshold.client.clone().unwrap_or_default(),
+            ]);
+        }
+        for field in fields {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    /// Check the signature and that it covers these encrypted result bytes
+    pub fn verify(
+        &self,
+        identity_public: &[u8; 32],
+        encrypted_match_bytes: &[u8],
+        encrypted_distance_bytes: &[u8],
+    ) -> Result<(), String> {
+        if self.result_hash != result_hash(encrypted_match_bytes, encrypted_distance_bytes) {
+            return Err("Attestation does not cover this result".to_string());
+        }
+        let key = VerifyingKey::from_bytes(identity_public).map_err(|e| format!("Invalid identity key: {}", e))?;
+        let signature = STANDARD.decode(&self.signature).map_err(|e| format!("Invalid signature encoding: {}", e))?;
+        let signature = Signature::from_slice(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+        key.verify(&self.digest(), &signature)
+            .map_err(|_| "Result signature mismatch".to_string())
+    }
+
+    /// Check that the result belongs to the session the request was sent in
+    /// (if any) and that this session hasn't expired
+    pub fn check_session(&self, expected: Option<&SessionClaim>) -> Result<(), String> {
+        match (&self.session, expected) {
+            (Some(session), Some(expected)) if session != expected => {
+                Err("Result was signed for a different session".to_string())
+            }
+            (None, Some(_)) => Err("Result is not bound to the request's session".to_string()),
+            (Some(session), _) if session.is_expired() => {
+                Err(format!("Session {} expired at {}", session.session_id, session.expires_at))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the match was decided against the encrypted threshold the
+    /// request sent (if any), so the server can't have dropped or swapped it
+    pub fn check_threshold(&self, sent: Option<&[u8]>) -> Result<(), String> {
+        let Some(sent) = sent else { return Ok(()) };
+        let bound = self.threshold.as_ref().and_then(|threshold| threshold.client.as_deref());
+        if bound != Some(hex(&Sha256::digest(sent)).as_str()) {
+            return Err("Result was not decided against the request's threshold".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hash of an encrypted decision (length-prefixed match and distance bytes)
+pub fn result_hash(encrypted_match_bytes: &[u8], encrypted_distance_bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [encrypted_match_bytes, encrypted_distance_bytes] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hex(&hasher.finalize())
+}
+
+fn handshake_digest(handshake: &Handshake) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(HANDSHAKE_CONTEXT);
+    for field in [&handshake.public_key, &handshake.key_id, &handshake.created_at] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Identity key of a handshake, after checking its signature over the exchange key.
+///
+/// `None` for servers that publish no identity; an identity without a valid
+/// signature is an error.
+pub fn verified_identity(handshake: &Handshake) -> Result<Option<[u8; 32]>, String> {
+    let Some(identity) = handshake.identity_key()? else { return Ok(None) };
+    let signature = handshake.identity_signature.as_ref().ok_or("Handshake identity is not signed")?;
+    let signature = STANDARD.decode(signature).map_err(|e| format!("Invalid signature encoding: {}", e))?;
+    let signature = Signature::from_slice(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+    VerifyingKey::from_bytes(&identity)
+        .map_err(|e| format!("Invalid identity key: {}", e))?
+        .verify(&handshake_digest(handshake), &signature)
+        .map_err(|_| "Handshake signature mismatch".to_string())?;
+    Ok(Some(identity))
+}
+
+/// Key id (hex) of an identity public key, as shown to users
+pub fn identity_key_id(identity_public: &[u8; 32]) -> String {
+    hex(&key_id(identity_public))
+}
+
+/// Long-lived Ed25519 signing key of the server
+pub struct ServerIdentity {
+    signing: SigningKey,
+}
+
+impl ServerIdentity {
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        Self { signing: SigningKey::from_bytes(&seed) }
+    }
+
+    /// Load the key at `path`, creating it (mode 0600) on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Ok(bytes) = fs::read(path) {
+            let seed: [u8; 32] = bytes.try_into().map_err(|_| format!("{} must hold 32 bytes", path.display()))?;
+            return Ok(Self { signing: SigningKey::from_bytes(&seed) });
+        }
+        let identity = Self::generate();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_secret(path, &identity.signing.to_bytes())?;
+        Ok(identity)
+    }
+
+    pub fn public_bytes(&self) -> [u8; 32] {
+        self.signing.verifying_key().to_bytes()
+    }
+
+    pub fn key_id(&self) -> String {
+        identity_key_id(&self.public_bytes())
+    }
+
+    /// Attach the identity key and its signature over the exchange key
+    pub fn sign_handshake(&self, handshake: Handshake) -> Handshake {
+        let mut handshake = handshake.with_identity(&self.public_bytes());
+        let signature = self.signing.sign(&handshake_digest(&handshake));
+        handshake.identity_signature = Some(STANDARD.encode(signature.to_bytes()));
+        handshake
+    }
+
+    /// Sign a 32-byte digest (base64 signature), e.g. a session transcript
+    pub fn sign_digest(&self, digest: &[u8; 32]) -> String {
+        STANDARD.encode(self.signing.sign(digest).to_bytes())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign_result(
+        &self,
+        request_id: &str,
+        user_id: &str,
+        encrypted_match_bytes: &[u8],
+        encrypted_distance_bytes: &[u8],
+        timestamp: &str,
+        session: Option<&SessionClaim>,
+        threshold: Option<AppliedThreshold>,
+    ) -> ResultAttestation {
+        let mut attestation = ResultAttestation {
+            request_id: request_id.to_string(),
+            user_id: user_id.to_string(),
+            result_hash: result_hash(encrypted_match_bytes, encrypted_distance_bytes),
+            timestamp: timestamp.to_string(),
+            key_id: self.key_id(),
+            signature: String::new(),
+            session: session.cloned(),
+            threshold,
+        };
+        attestation.signature = STANDARD.encode(self.signing.sign(&attestation.digest()).to_bytes());
+        attestation
+    }
+}
+
+/// Random id a client attaches to a request, echoed in the signed result
+pub fn new_request_id() -> String {
+    let mut id = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut id);
+    hex(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_result_verifies_and_detects_tampering() {
+        let identity = ServerIdentity::generate();
+        let att = identity.sign_result("req-1", "alice", b"match", b"distance", "2024-01-01T00:00:00Z", None, None);
+        assert!(att.verify(&identity.public_bytes(), b"match", b"distance").is_ok());
+
+        // Different result bytes
+        assert!(att.verify(&identity.public_bytes(), b"other", b"distance").is_err());
+        // Different signer
+        assert!(att.verify(&ServerIdentity::generate().public_bytes(), b"match", b"distance").is_err());
+        // Claims changed after signing
+        let forged = ResultAttestation { user_id: "mallory".to_string(), ..att };
+        assert!(forged.verify(&identity.public_bytes(), b"match", b"distance").is_err());
+    }
+
+    #[test]
+    fn result_is_bound_to_its_session() {
+        let identity = ServerIdentity::generate();
+        let session = SessionClaim {
+            session_id: "sid".to_string(),
+            challenge: "ch".to_string(),
+            expires_at: (chrono::Utc::now() + chrono::Duration::minutes(5)).to_rfc3339(),
+        };
+        let att = identity.sign_result("req-1", "alice", b"match", b"distance", "t", Some(&session), None);
+        assert!(att.check_session(Some(&session)).is_ok());
+
+        // Detached from its session: the signature no longer covers it
+        let detached = ResultAttestation { session: None, ..att.clone() };
+        assert!(detached.verify(&identity.public_bytes(), b"match", b"distance").is_err());
+        assert!(detached.check_session(Some(&session)).is_err());
+
+        let other = SessionClaim { challenge: "other".to_string(), ..session.clone() };
+        assert!(att.check_session(Some(&other)).is_err());
+
+        let expired = SessionClaim { expires_at: "2020-01-01T00:00:00Z".to_string(), ..session };
+        let stale = identity.sign_result("req-1", "alice", b"match", b"distance", "t", Some(&expired), None);
+        assert!(stale.check_session(None).is_err());
+    }
+
+    #[test]
+    fn result_is_bound_to_its_threshold() {
+        let identity = ServerIdentity::generate();
+        let threshold = AppliedThreshold { policy: Some(204), client: Some(hex(&Sha256::digest(b"threshold"))), ..Default::default() };
+        let att = identity.sign_result("req-1", "alice", b"match", b"distance", "t", None, Some(threshold));
+        assert!(att.verify(&identity.public_bytes(), b"match", b"distance").is_ok());
+        assert!(att.check_threshold(Some(b"threshold")).is_ok());
+        assert!(att.check_threshold(Some(b"other")).is_err());
+
+        // A looser policy, or no threshold at all, after signing
+        let loosened = AppliedThreshold { policy: Some(1024), ..att.threshold.clone().unwrap() };
+        let forged = ResultAttestation { threshold: Some(loosened), ..att.clone() };
+        assert!(forged.verify(&identity.public_bytes(), b"match", b"distance").is_err());
+        let dropped = ResultAttestation { threshold: None, ..att };
+        assert!(dropped.verify(&identity.public_bytes(), b"match", b"distance").is_err());
+        assert!(dropped.check_threshold(Some(b"threshold")).is_err());
+    }
+
+    #[test]
+    fn handshake_binds_exchange_key_to_identity() {
+        let identity = ServerIdentity::generate();
+        let exchange = crate::sealed::ExchangeKey::generate();
+        let handshake = identity.sign_handshake(exchange.handshake());
+        assert_eq!(verified_identity(&handshake).unwrap(), Some(identity.public_bytes()));
+
+        // Impostor swaps in its own exchange key but keeps the identity
+        let impostor = crate::sealed::ExchangeKey::generate().handshake();
+        let swapped = Handshake { public_key: impostor.public_key, key_id: impostor.key_id, ..handshake };
+        assert!(verified_identity(&swapped).is_err());
+
+        assert_eq!(verified_identity(&exchange.handshake()).unwrap(), None);
+    }
+}
@@ -2,21 +2,87 @@ pub mod trivium;
 pub mod trivium_fhe;
 pub mod protocol;
 pub mod matching_fhe;
+pub mod attestation;
+pub mod params;
+pub mod transport;
+pub mod grpc;
+pub mod sealed;
+pub mod i18n;
+pub mod telemetry;
+pub mod identity;
+pub mod session;
+pub mod delta;
+pub mod template;
+pub mod soft;
+pub mod quality;
+pub mod cancel;
+pub mod error;
+pub mod compute;
+pub mod ownership;
+pub mod consensus;
+pub mod fusion;
+pub mod fuzzy;
+pub mod transform;
+#[cfg(feature = "subscriber")]
+pub mod logging;
 
 // Re-exports
-pub use trivium::{Trivium, u64_to_bits_80};
-pub use trivium_fhe::decrypt_homomorphic;
+#[allow(deprecated)]
+pub use trivium::{bytes_to_bits_80, random_bits_80, Cipher, Kreyvium, Trivium, u64_to_bits_80};
+pub use params::ParameterSet;
+pub use cancel::{CancellationToken, Cancelled};
+pub use error::{ErrorCode, FingerprintError};
+pub use compute::{ComputeBackend, EvaluationKey};
+pub use trivium_fhe::{
+    decrypt_homomorphic, decrypt_homomorphic_resumable, decrypt_homomorphic_stream, set_clock_threads, DecryptState,
+    ConsoleProgress, KreyviumFhe, Progress, ProgressSink, TracingProgress, TriviumFhe,
+};
+#[allow(deprecated)]
 pub use matching_fhe::{
     diff_bits,
+    masked_diff_bits,
+    popcount,
     popcount_128,
     popcount_256,
     popcount_512,
     popcount_1024,
+    popcount_tree,
+    popcount_masked,
+    weighted_popcount,
+    popcount_uint16,
+    counter_width,
+    ripple_gates,
+    tree_gates,
+    uint16_gates,
+    template_popcount_gates,
+    uses_ripple_counter,
+    GateCount,
     leq_constant,
+    leq_encrypted,
+    leq_scaled,
+    min_distance,
+    fused_distance,
+    select_bits,
+    match_distance,
+    fhe_constant,
+    fhe_false_like,
+    MatchingBackend,
+    PopcountAccumulator,
 };
 pub use protocol::{
-    RegisterRequest, RegisterResponse,
-    VerifyRequest, VerifyResponse,
+    Factor, FallbackPolicy, ConsentInfo, EnrolledThreshold, QualityMask,
+    ErrorCondition, ErrorPolicy, FailureAction, FailureNotice,
+    RegisterRequest, RegisterResponse, EncryptedSample,
+    DeltaRequest,
+    VerifyRequest, VerifyResponse, ResultMode,
+    PolicyRequest, PolicyResponse,
+    AccountOperation, AccountRequest, AccountResponse,
+    DeleteRequest, DeleteResponse,
+    ReceiptRequest, ReceiptResponse,
+    RevokeRequest, KeyRotationRequest,
+    AdminCommand, AdminRequest, AdminResponse, UserSummary, DatabaseStats,
+    JobTicket, JobState, JobStatus, CancelRequest, CancelResponse,
+    ServerStatus, JobCounts, Calibration,
     // Legacy
     AuthRequest, AuthResponse,
 };
\ No newline at end of file
@@ -0,0 +1,33 @@
+//! Log output of the binaries.
+//!
+//! Library code reports through `tracing`: spans per FHE phase (warmup,
+//! keystream, popcount, threshold) with their sizes as fields, closed with
+//! the time spent in them, and warnings. The console lines of the message
+//! catalog stay as they were; this subscriber writes to stderr, so a
+//! client's RPC stdout stays clean.
+//!
+//! `FINGERPRINT_LOG` takes `RUST_LOG`-style directives, warnings only by
+//! default; e.g. `FINGERPRINT_LOG=shared=debug` times every phase. The
+//! binaries can also set them in their `fingerprint.toml`.
+
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+pub const LOG_ENV: &str = "FINGERPRINT_LOG";
+
+/// Install the global subscriber (once; later calls are no-ops), filtered by
+/// `FINGERPRINT_LOG`, else `configured`, else warnings only
+pub fn init(configured: Option<&str>) {
+    let filter = match EnvFilter::try_from_env(LOG_ENV) {
+        Ok(filter) => filter,
+        Err(_) => configured
+            .and_then(|directives| EnvFilter::try_new(directives).ok())
+            .unwrap_or_else(|| EnvFilter::new("warn")),
+    };
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_thread_names(true)
+        .with_span_events(FmtSpan::CLOSE)
+        .try_init();
+}
@@ -1,16 +1,29 @@
 // shared/src/matching_fhe.rs
+use serde::{Deserialize, Serialize};
 use tfhe::prelude::*;
-use tfhe::FheBool;
+use tfhe::{FheBool, FheUint16};
+use std::collections::VecDeque;
+use std::ops::{BitAnd, BitXor, Not};
 
-#[inline]
-fn fhe_not(x: &FheBool, fhe_true: &FheBool) -> FheBool {
-    x ^ fhe_true
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::fusion::FusionRule;
+
+/// Trivially encrypted constant, made on the server under the installed key
+/// instead of being sent by the client. It hides nothing, but any gate with
+/// a real ciphertext among its inputs yields a real ciphertext.
+pub fn fhe_constant(value: bool) -> FheBool {
+    FheBool::try_encrypt_trivial(value).expect("server key installed before building constants")
 }
 
-#[inline]
-fn fhe_or(a: &FheBool, b: &FheBool) -> FheBool {
-    // a | b = a ^ b ^ (a & b)
-    a ^ b ^ (a & b)
+/// Encrypted `false` derived from the first of `bits` (`x ^ x`, a bootstrapped
+/// gate like any other). A trivial constant's zero mask gives it away, so bits
+/// that can reach a response are padded with this instead of [`fhe_constant`].
+#[allow(clippy::eq_op)]
+pub fn fhe_false_like(bits: &[FheBool]) -> FheBool {
+    match bits.first() {
+        Some(bit) => bit ^ bit,
+        None => fhe_constant(false),
+    }
 }
 
 /// XOR-diff bits: 1 => different
@@ -19,92 +32,346 @@ pub fn diff_bits(a: &[FheBool], b: &[FheBool]) -> Vec<FheBool> {
     a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
 }
 
-/// Popcount for 512 bits, 10 bits are enough (0..512).
-pub fn popcount_512(diff: &[FheBool], fhe_true: &FheBool) -> Vec<FheBool> {
-    assert_eq!(diff.len(), 512, "Expected 512 bits for popcount_512");
+/// XOR-diff restricted to a per-bit quality mask: 1 => different and kept.
+/// Masked-out positions are always 0, so they never add to the distance.
+pub fn masked_diff_bits(a: &[FheBool], b: &[FheBool], mask: &[FheBool]) -> Vec<FheBool> {
+    assert_eq!(a.len(), mask.len());
+    diff_bits(a, b).iter().zip(mask.iter()).map(|(d, m)| d & m).collect()
+}
 
-    let fhe_false = fhe_true ^ fhe_true;
-    let mut acc = vec![fhe_false.clone(); 10]; // 10-bit counter (0-512 range)
+/// Popcount of `diff` over the positions `mask` keeps (carry-save tree,
+/// `counter_width(diff.len())` bits LSB-first)
+pub fn popcount_masked(
+    diff: &[FheBool],
+    mask: &[FheBool],
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, Cancelled> {
+    assert_eq!(diff.len(), mask.len());
+    let kept: Vec<FheBool> = diff.iter().zip(mask.iter()).map(|(d, m)| d & m).collect();
+    popcount_tree(&kept, cancel)
+}
 
+/// Ripple-carry bit counter behind `popcount`.
+///
+/// Bits are added one at a time, so a partial count can be saved and the
+/// remaining bits added after a restart.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PopcountAccumulator {
+    acc: Vec<FheBool>,   // LSB-first counter
+    counted: usize,      // Bits added so far
+}
+
+impl PopcountAccumulator {
+    pub fn new(width: usize) -> Self {
+        Self { acc: vec![fhe_constant(false); width], counted: 0 }
+    }
+
+    pub fn add(&mut self, bit: &FheBool) {
+        ripple_add(&mut self.acc, bit);
+        self.counted += 1;
+    }
+
+    /// Number of bits added so far
+    pub fn counted(&self) -> usize {
+        self.counted
+    }
+
+    pub fn finish(self) -> Vec<FheBool> {
+        self.acc
+    }
+}
+
+/// Add one bit to an LSB-first counter (generic so it can be checked on plain bools)
+fn ripple_add<B: Clone>(acc: &mut [B], bit: &B)
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    let mut carry = bit.clone();
+    for slot in acc.iter_mut() {
+        let sum = &*slot ^ &carry;
+        let new_carry = &*slot & &carry;
+        *slot = sum;
+        carry = new_carry;
+    }
+}
+
+/// Ripple-carry popcount of any number of bits; the LSB-first counter is
+/// `counter_width(diff.len())` bits wide.
+#[tracing::instrument(name = "popcount", skip_all, fields(bits = diff.len(), method = "ripple"))]
+pub fn popcount(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    let mut acc = PopcountAccumulator::new(counter_width(diff.len()));
     for bit in diff.iter() {
-        let mut carry = bit.clone();
-        for i in 0..acc.len() {
-            let sum = &acc[i] ^ &carry;
-            let new_carry = &acc[i] & &carry;
-            acc[i] = sum;
-            carry = new_carry;
-        }
+        cancel.check()?;
+        acc.add(bit);
     }
+    Ok(acc.finish())
+}
 
-    acc
+/// Popcount for 512 bits, 10 bits are enough (0..512).
+#[deprecated(note = "use `popcount`, which sizes the counter from the input")]
+pub fn popcount_512(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    assert_eq!(diff.len(), 512, "Expected 512 bits for popcount_512");
+    popcount(diff, cancel)
 }
 
 /// Popcount for 1024 bits, 11 bits are enough (0..1024).
-pub fn popcount_1024(diff: &[FheBool], fhe_true: &FheBool) -> Vec<FheBool> {
+#[deprecated(note = "use `popcount`, which sizes the counter from the input")]
+pub fn popcount_1024(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
     assert_eq!(diff.len(), 1024, "Expected 1024 bits for popcount_1024");
+    popcount(diff, cancel)
+}
 
-    let fhe_false = fhe_true ^ fhe_true;
-    let mut acc = vec![fhe_false.clone(); 11]; // 11-bit counter (0-1024 range)
+/// Popcount of 256 (or 512) bits with a 10-bit counter, as `popcount_512` of the padded input
+#[deprecated(note = "use `popcount`, which sizes the counter from the input")]
+pub fn popcount_256(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    assert!(diff.len() == 256 || diff.len() == 512, "Expected 256 bits for popcount_256");
+    popcount_widened(diff, 10, cancel)
+}
 
-    for bit in diff.iter() {
-        let mut carry = bit.clone();
-        for i in 0..acc.len() {
-            let sum = &acc[i] ^ &carry;
-            let new_carry = &acc[i] & &carry;
-            acc[i] = sum;
-            carry = new_carry;
+/// Popcount of 128 (or 512) bits with a 10-bit counter, as `popcount_512` of the padded input
+#[deprecated(note = "use `popcount`, which sizes the counter from the input")]
+pub fn popcount_128(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    assert!(diff.len() == 128 || diff.len() == 512, "Expected 128 bits for popcount_128");
+    popcount_widened(diff, 10, cancel)
+}
+
+/// `popcount` with the counter zero-extended to `width` bits
+fn popcount_widened(diff: &[FheBool], width: usize, cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    let mut count = popcount(diff, cancel)?;
+    count.resize(width, fhe_false_like(diff));
+    Ok(count)
+}
+
+/// Counter width needed to hold 0..=n
+pub fn counter_width(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()).max(1) as usize
+}
+
+/// Gate (or integer op) count of one implementation for a given input size
+#[derive(Debug, PartialEq, Eq)]
+pub struct GateCount {
+    pub and: usize,
+    pub xor: usize,
+    pub int_ops: usize,     // FheUint casts + additions
+}
+
+/// Ripple-carry: every input bit runs through the whole counter
+pub fn ripple_gates(bits: usize) -> GateCount {
+    let width = counter_width(bits);
+    GateCount { and: bits * width, xor: bits * width, int_ops: 0 }
+}
+
+/// Carry-save tree: replays the column reduction of `popcount_tree` on bit counts only
+pub fn tree_gates(bits: usize) -> GateCount {
+    let width = counter_width(bits);
+    let mut columns = vec![0usize; width + 1];
+    columns[0] = bits;
+    let mut gates = GateCount { and: 0, xor: 0, int_ops: 0 };
+    for k in 0..width {
+        while columns[k] >= 3 {
+            columns[k] -= 2;
+            columns[k + 1] += 1;
+            gates.and += 2;
+            gates.xor += 3;
+        }
+        if columns[k] == 2 {
+            columns[k] -= 1;
+            columns[k + 1] += 1;
+            gates.and += 1;
+            gates.xor += 1;
         }
     }
+    gates
+}
+
+/// Radix integers: one cast per bit and a pairwise addition tree
+pub fn uint16_gates(bits: usize) -> GateCount {
+    GateCount { and: 0, xor: 0, int_ops: bits + bits.saturating_sub(1) }
+}
+
+/// Whether a boolean verify job counts a full `bits`-bit diff with the
+/// checkpointed ripple counter (512/1024-bit templates) rather than the tree
+pub fn uses_ripple_counter(bits: usize) -> bool {
+    matches!(bits, 512 | 1024)
+}
+
+/// Gates of the popcount a boolean verify job runs over a whole `bits`-bit template
+pub fn template_popcount_gates(bits: usize) -> GateCount {
+    if uses_ripple_counter(bits) { ripple_gates(bits) } else { tree_gates(bits) }
+}
+
+/// Popcount with a carry-save (3:2 compressor) tree.
+///
+/// Bits are grouped in columns by weight; each column is reduced with full
+/// adders (2 AND + 3 XOR) until one bit is left, carries moving to the next
+/// column. FIFO order keeps the tree balanced, so the AND depth grows with
+/// log(n) instead of n. Output is LSB-first, `counter_width(diff.len())` bits.
+#[tracing::instrument(name = "popcount", skip_all, fields(bits = diff.len(), method = "tree"))]
+pub fn popcount_tree(diff: &[FheBool], cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+    csa_tree(diff, &fhe_false_like(diff), || cancel.check())
+}
+
+/// Column reduction of `popcount_tree`; `check` runs before every full adder
+fn csa_tree<B: Clone>(
+    diff: &[B],
+    fhe_false: &B,
+    check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<Vec<B>, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    let width = counter_width(diff.len());
+    let mut columns: Vec<VecDeque<B>> = vec![VecDeque::new(); width + 1];
+    columns[0].extend(diff.iter().cloned());
+    reduce_columns(columns, width, fhe_false, check)
+}
 
-    acc
+/// Weighted Hamming distance: each differing bit adds its plaintext weight.
+///
+/// Shift-and-add without any adder of its own: a bit of weight w joins the
+/// column of every set bit of w before the carry-save reduction of
+/// `popcount_tree`. Output is LSB-first, `counter_width(sum of weights)` bits.
+#[tracing::instrument(name = "popcount", skip_all, fields(bits = diff.len(), method = "weighted"))]
+pub fn weighted_popcount(
+    diff: &[FheBool],
+    weights: &[u8],
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, Cancelled> {
+    weighted_csa_tree(diff, weights, &fhe_false_like(diff), || cancel.check())
+}
+
+/// Column placement of `weighted_popcount` (generic so it can be checked on plain bools)
+fn weighted_csa_tree<B: Clone>(
+    diff: &[B],
+    weights: &[u8],
+    fhe_false: &B,
+    check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<Vec<B>, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    assert_eq!(diff.len(), weights.len());
+    let width = counter_width(weights.iter().map(|&w| w as usize).sum());
+    let mut columns: Vec<VecDeque<B>> = vec![VecDeque::new(); width + 1];
+    for (bit, &weight) in diff.iter().zip(weights) {
+        for (k, column) in columns.iter_mut().enumerate().take(8) {
+            if (weight >> k) & 1 == 1 {
+                column.push_back(bit.clone());
+            }
+        }
+    }
+    reduce_columns(columns, width, fhe_false, check)
 }
 
-/// Backward compatibility: popcount_256 redirects to popcount_512 with padding
-pub fn popcount_256(diff: &[FheBool], fhe_true: &FheBool) -> Vec<FheBool> {
-    if diff.len() == 256 {
-        let fhe_false = fhe_true ^ fhe_true;
-        let mut padded = diff.to_vec();
-        padded.extend(vec![fhe_false.clone(); 256]);
-        return popcount_512(&padded, fhe_true);
+/// Full/half-adder reduction of weight columns into one LSB-first `width`-bit sum
+fn reduce_columns<B: Clone>(
+    mut columns: Vec<VecDeque<B>>,
+    width: usize,
+    fhe_false: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<Vec<B>, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    let mut out = Vec::with_capacity(width);
+    for k in 0..width {
+        while columns[k].len() >= 3 {
+            check()?;
+            let a = columns[k].pop_front().unwrap();
+            let b = columns[k].pop_front().unwrap();
+            let c = columns[k].pop_front().unwrap();
+            let t = &a ^ &b;
+            let carry = &(&a & &b) ^ &(&c & &t);   // a&b and c&(a^b) never both hold
+            columns[k].push_back(&t ^ &c);
+            columns[k + 1].push_back(carry);
+        }
+        if columns[k].len() == 2 {
+            let a = columns[k].pop_front().unwrap();
+            let b = columns[k].pop_front().unwrap();
+            columns[k + 1].push_back(&a & &b);
+            columns[k].push_back(&a ^ &b);
+        }
+        out.push(columns[k].pop_front().unwrap_or_else(|| fhe_false.clone()));
     }
-    popcount_512(diff, fhe_true)
+
+    Ok(out)
 }
 
-/// Backward compatibility: popcount_128 redirects to popcount_512 with padding
-pub fn popcount_128(diff: &[FheBool], fhe_true: &FheBool) -> Vec<FheBool> {
-    if diff.len() == 128 {
-        let fhe_false = fhe_true ^ fhe_true;
-        let mut padded = diff.to_vec();
-        padded.extend(vec![fhe_false.clone(); 384]);
-        return popcount_512(&padded, fhe_true);
+/// Popcount using TFHE-rs radix integers: every bit is cast to an
+/// `FheUint16` and the values are summed pairwise.
+#[tracing::instrument(name = "popcount", skip_all, fields(bits = diff.len(), method = "radix"))]
+pub fn popcount_uint16(diff: &[FheBool], cancel: &CancellationToken) -> Result<FheUint16, Cancelled> {
+    assert!(!diff.is_empty() && diff.len() < u16::MAX as usize, "popcount_uint16 needs 1..65535 bits");
+
+    let mut level: Vec<FheUint16> = diff.iter().map(|b| FheUint16::cast_from(b.clone())).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| -> Result<FheUint16, Cancelled> {
+                cancel.check()?;
+                Ok(match pair {
+                    [a, b] => a + b,
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
     }
-    popcount_512(diff, fhe_true)
+    Ok(level.pop().unwrap())
+}
+
+/// Bitwise multiplexer: cond ? a : b
+///
+/// b XOR (cond AND (a XOR b)), one AND per bit.
+pub fn select_bits(cond: &FheBool, a: &[FheBool], b: &[FheBool]) -> Vec<FheBool> {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| y ^ &(cond & &(x ^ y)))
+        .collect()
 }
 
 /// Compute (distance <= threshold) where distance is encrypted bits (LSB-first),
-/// threshold is plaintext usize.
-pub fn leq_constant(distance_bits_lsb: &[FheBool], threshold: usize, fhe_true: &FheBool) -> FheBool {
-    // Convert threshold to bits (same width), MSB-first loop
+/// threshold is plaintext usize. A threshold past the widest distance always
+/// matches, through an encrypted `true` rather than a trivial one.
+#[tracing::instrument(name = "threshold", skip_all, fields(bits = distance_bits_lsb.len(), threshold = threshold))]
+pub fn leq_constant(
+    distance_bits_lsb: &[FheBool],
+    threshold: usize,
+    cancel: &CancellationToken,
+) -> Result<FheBool, Cancelled> {
+    constant_leq(distance_bits_lsb, threshold, &fhe_false_like(distance_bits_lsb), || cancel.check())
+}
+
+/// Comparator of `leq_constant` (generic so it can be checked on plain bools)
+fn constant_leq<B: Clone>(
+    distance_bits_lsb: &[B],
+    threshold: usize,
+    fhe_false: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<B, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B> + Not<Output = B>,
+{
     let k = distance_bits_lsb.len();
-    let mut thr_bits = vec![false; k];
-    for i in 0..k {
-        thr_bits[i] = ((threshold >> i) & 1) == 1;
+    // Every k-bit distance is below a threshold with bits at or above k
+    if threshold.checked_shr(k as u32).unwrap_or(0) != 0 {
+        return Ok(!fhe_false);
     }
 
-    let fhe_false = fhe_true ^ fhe_true;
-
     // gt = false; eq = true
     let mut gt = fhe_false.clone();
-    let mut eq = fhe_true.clone();
+    let mut eq = !fhe_false;
 
-    for i in (0..k).rev() {
-        let di = &distance_bits_lsb[i];
+    // MSB-first over the threshold's bits
+    for (i, di) in distance_bits_lsb.iter().enumerate().rev() {
+        check()?;
 
-        if thr_bits[i] == false {
+        if (threshold >> i) & 1 == 0 {
             // if thr=0, gt can happen when distance bit is 1 and all higher bits equal
-            gt = fhe_or(&gt, &(&eq & di));
+            gt = &gt ^ &(&eq & di);   // gt is still false while eq holds
             // eq remains only if di==0
-            eq = &eq & &fhe_not(di, fhe_true);
+            eq = &eq & &!di;
         } else {
             // thr=1: gt cannot be triggered at this bit
             // eq remains only if di==1
@@ -113,5 +380,393 @@ pub fn leq_constant(distance_bits_lsb: &[FheBool], threshold: usize, fhe_true: &
     }
 
     // distance <= threshold  <=>  NOT(gt)
-    fhe_not(&gt, fhe_true)
-}
\ No newline at end of file
+    Ok(!&gt)
+}
+
+/// Compute (distance <= threshold) where both are encrypted bits (LSB-first),
+/// so the server never learns the threshold. The shorter input is
+/// zero-extended.
+#[tracing::instrument(name = "threshold", skip_all, fields(bits = distance_bits_lsb.len(), encrypted = true))]
+pub fn leq_encrypted(
+    distance_bits_lsb: &[FheBool],
+    threshold_bits_lsb: &[FheBool],
+    cancel: &CancellationToken,
+) -> Result<FheBool, Cancelled> {
+    leq_bits(distance_bits_lsb, threshold_bits_lsb, &fhe_constant(true), || cancel.check())
+}
+
+/// Comparator of `leq_encrypted` (generic so it can be checked on plain bools)
+fn leq_bits<B: Clone>(
+    a: &[B],
+    b: &[B],
+    fhe_true: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<B, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    #[allow(clippy::eq_op)] // x ^ x: false at the gate level, like the inputs
+    let fhe_false = fhe_true ^ fhe_true;
+    let bit = |bits: &[B], i: usize| bits.get(i).unwrap_or(&fhe_false).clone();
+
+    // gt = false; eq = true, MSB-first
+    let mut gt = fhe_false.clone();
+    let mut eq = fhe_true.clone();
+
+    for i in (0..a.len().max(b.len())).rev() {
+        check()?;
+        let (ai, bi) = (bit(a, i), bit(b, i));
+        // First differing bit from the top: a > b iff a has the 1 there
+        let first_diff = &eq & &(&ai ^ &bi);
+        gt = &gt ^ &(&first_diff & &ai);   // gt is still false while eq holds
+        eq = &eq ^ &first_diff;
+    }
+
+    // a <= b  <=>  NOT(gt)
+    Ok(&gt ^ fhe_true)
+}
+
+/// Compute (distance / kept <= threshold / compared) for a masked distance:
+/// `distance * compared <= threshold * kept`, with `kept` the encrypted number
+/// of mask bits among the `compared` positions. The threshold is the one for
+/// all `compared` bits; it shrinks with the share of bits the mask keeps.
+/// A mask keeping fewer than `min_kept` bits never matches (with none kept
+/// every distance would be 0).
+#[tracing::instrument(name = "threshold", skip_all, fields(bits = distance_bits_lsb.len(), compared = compared, threshold = threshold))]
+pub fn leq_scaled(
+    distance_bits_lsb: &[FheBool],
+    kept_bits_lsb: &[FheBool],
+    compared: usize,
+    threshold: usize,
+    min_kept: usize,
+    cancel: &CancellationToken,
+) -> Result<FheBool, Cancelled> {
+    scaled_leq(distance_bits_lsb, kept_bits_lsb, compared, threshold, min_kept, &fhe_constant(true), || cancel.check())
+}
+
+/// Comparison of `leq_scaled` (generic so it can be checked on plain bools)
+fn scaled_leq<B: Clone>(
+    distance: &[B],
+    kept: &[B],
+    compared: usize,
+    threshold: usize,
+    min_kept: usize,
+    fhe_true: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<B, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    #[allow(clippy::eq_op)] // see leq_bits
+    let fhe_false = fhe_true ^ fhe_true;
+    let lhs = mul_constant(distance, compared, &fhe_false);
+    let rhs = mul_constant(kept, threshold, &fhe_false);
+    let within = leq_bits(&lhs, &rhs, fhe_true, &mut check)?;
+    if min_kept == 0 {
+        return Ok(within);
+    }
+    let fewest_rejected: Vec<B> = (0..counter_width(min_kept - 1))
+        .map(|i| if ((min_kept - 1) >> i) & 1 == 1 { fhe_true.clone() } else { fhe_false.clone() })
+        .collect();
+    let too_few = leq_bits(kept, &fewest_rejected, fhe_true, &mut check)?;
+    Ok(&within & &(&too_few ^ fhe_true))
+}
+
+/// LSB-first sum, one bit wider than the longer input
+fn add_bits<B: Clone>(a: &[B], b: &[B], fhe_false: &B) -> Vec<B>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    let bit = |bits: &[B], i: usize| bits.get(i).unwrap_or(fhe_false).clone();
+    let mut carry = fhe_false.clone();
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    for i in 0..a.len().max(b.len()) {
+        let (ai, bi) = (bit(a, i), bit(b, i));
+        let t = &ai ^ &bi;
+        sum.push(&t ^ &carry);
+        carry = &(&ai & &bi) ^ &(&carry & &t);  // both terms never hold together
+    }
+    sum.push(carry);
+    sum
+}
+
+/// `value * k` by shift-and-add over the set bits of the plaintext `k`
+fn mul_constant<B: Clone>(value: &[B], k: usize, fhe_false: &B) -> Vec<B>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    let mut product = vec![fhe_false.clone()];
+    for shift in (0..usize::BITS as usize).filter(|&i| (k >> i) & 1 == 1) {
+        let shifted: Vec<B> = std::iter::repeat_n(fhe_false.clone(), shift).chain(value.iter().cloned()).collect();
+        product = add_bits(&product, &shifted, fhe_false);
+    }
+    product
+}
+
+/// Smallest of several encrypted distances (LSB-first, equal widths), e.g. of
+/// a probe against rotated variants of one template. Pairs are compared and
+/// the smaller kept, level by level, so `n` distances take `n - 1` comparisons
+/// at a depth of log2(n).
+#[tracing::instrument(skip_all, fields(distances = distances.len()))]
+pub fn min_distance(
+    distances: Vec<Vec<FheBool>>,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, Cancelled> {
+    min_tree(distances, &fhe_constant(true), || cancel.check())
+}
+
+/// Comparison tree of `min_distance` (generic so it can be checked on plain bools)
+fn min_tree<B: Clone>(
+    mut level: Vec<Vec<B>>,
+    fhe_true: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<Vec<B>, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    while level.len() > 1 {
+        let mut next = Vec::with_capacity(level.len().div_ceil(2));
+        let mut pairs = level.into_iter();
+        while let Some(a) = pairs.next() {
+            let Some(b) = pairs.next() else {
+                next.push(a);
+                break;
+            };
+            // a <= b ? a : b, one AND per bit
+            let a_smaller = leq_bits(&a, &b, fhe_true, &mut check)?;
+            next.push(a.iter().zip(&b).map(|(x, y)| y ^ &(&a_smaller & &(x ^ y))).collect());
+        }
+        level = next;
+    }
+    Ok(level.pop().unwrap_or_default())
+}
+
+/// Distance a fused decision stands for: the smallest under `AnyFinger`, the
+/// second smallest under `TwoOfN`, since two fingers match exactly when it is
+/// within the threshold (see fusion.rs)
+#[tracing::instrument(skip_all, fields(distances = distances.len(), rule = %rule))]
+pub fn fused_distance(
+    distances: Vec<Vec<FheBool>>,
+    rule: FusionRule,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, Cancelled> {
+    match rule {
+        FusionRule::AnyFinger => min_distance(distances, cancel),
+        FusionRule::TwoOfN => second_min(distances, &fhe_constant(true), || cancel.check()),
+    }
+}
+
+/// Second smallest distance (the smallest of fewer than two): each distance is
+/// folded into the running smallest and second smallest with two comparisons
+fn second_min<B: Clone>(
+    distances: Vec<Vec<B>>,
+    fhe_true: &B,
+    mut check: impl FnMut() -> Result<(), Cancelled>,
+) -> Result<Vec<B>, Cancelled>
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    // cond ? a : b, one AND per bit
+    let select = |cond: &B, a: &[B], b: &[B]| -> Vec<B> { a.iter().zip(b).map(|(x, y)| y ^ &(cond & &(x ^ y))).collect() };
+    let mut distances = distances.into_iter();
+    let (a, b) = match (distances.next(), distances.next()) {
+        (Some(a), Some(b)) => (a, b),
+        (only, _) => return Ok(only.unwrap_or_default()),
+    };
+    let a_smaller = leq_bits(&a, &b, fhe_true, &mut check)?;
+    let (mut first, mut second) = (select(&a_smaller, &a, &b), select(&a_smaller, &b, &a));
+    for distance in distances {
+        let below_first = leq_bits(&distance, &first, fhe_true, &mut check)?;
+        let below_second = leq_bits(&distance, &second, fhe_true, &mut check)?;
+        second = select(&below_first, &first, &select(&below_second, &distance, &second));
+        first = select(&below_first, &distance, &first);
+    }
+    Ok(second)
+}
+
+// ==================== MATCHING ====================
+
+/// How a diff is counted and compared with the match threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchingBackend {
+    #[default]
+    Boolean,    // FheBool carry-save tree and bitwise comparator
+    Radix,      // FheUint16 sums and the TFHE-rs `le` comparison
+}
+
+impl std::fmt::Display for MatchingBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MatchingBackend::Boolean => "boolean",
+            MatchingBackend::Radix => "radix",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Hamming weight of `diff` compared with `threshold`.
+///
+/// Returns (distance <= threshold, LSB-first distance zero-extended to
+/// `width` bits), the same for both backends.
+#[tracing::instrument(skip_all, fields(bits = diff.len(), threshold = threshold, backend = %backend))]
+pub fn match_distance(
+    diff: &[FheBool],
+    threshold: usize,
+    width: usize,
+    backend: MatchingBackend,
+    cancel: &CancellationToken,
+) -> Result<(FheBool, Vec<FheBool>), Cancelled> {
+    match backend {
+        MatchingBackend::Boolean => {
+            let mut distance = popcount_tree(diff, cancel)?;
+            distance.resize(width.max(distance.len()), fhe_false_like(diff));
+            let matched = leq_constant(&distance, threshold, cancel)?;
+            Ok((matched, distance))
+        }
+        MatchingBackend::Radix => {
+            let count = popcount_uint16(diff, cancel)?;
+            let matched = count.le(u16::try_from(threshold).unwrap_or(u16::MAX));
+            let distance = (0..width)
+                .map(|i| {
+                    cancel.check()?;
+                    Ok(((&count >> i as u16) & 1u16).eq(1u16))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((matched, distance))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_needs_far_fewer_gates_than_ripple() {
+        let tree = tree_gates(1024);
+        assert!(tree.and < ripple_gates(1024).and / 4);
+        assert_eq!(tree_gates(3), GateCount { and: 2, xor: 3, int_ops: 0 });
+        assert_eq!(tree_gates(1), GateCount { and: 0, xor: 0, int_ops: 0 });
+    }
+
+    fn value(bits: &[bool]) -> usize {
+        bits.iter().enumerate().filter(|(_, &b)| b).map(|(i, _)| 1 << i).sum()
+    }
+
+    #[test]
+    fn tree_counts_like_the_ripple_counter() {
+        let mut seed = 0x9e37_79b9u32;
+        for len in (1..=70).chain([128, 256, 512, 1024]) {
+            let diff: Vec<bool> = (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    seed & 0x1_0000 != 0
+                })
+                .collect();
+            let expected = diff.iter().filter(|&&b| b).count();
+
+            let mut ripple = vec![false; counter_width(len)];
+            diff.iter().for_each(|bit| ripple_add(&mut ripple, bit));
+            let tree = csa_tree(&diff, &false, || Ok(())).unwrap();
+
+            assert_eq!(tree.len(), counter_width(len));
+            assert_eq!(value(&ripple), expected, "ripple, {} bits", len);
+            assert_eq!(value(&tree), expected, "tree, {} bits", len);
+        }
+        let all_set = csa_tree(&[true; 1024], &false, || Ok(())).unwrap();
+        assert_eq!(value(&all_set), 1024);
+    }
+
+    #[test]
+    fn weighted_count_adds_the_weights_of_set_bits() {
+        let diff: Vec<bool> = (0..100).map(|i| i % 3 != 1).collect();
+        let weights: Vec<u8> = (0..100).map(|i| (i % 4 + 1) as u8).collect();
+        let expected: usize = diff.iter().zip(&weights).filter(|(&d, _)| d).map(|(_, &w)| w as usize).sum();
+        let sum = weighted_csa_tree(&diff, &weights, &false, || Ok(())).unwrap();
+        assert_eq!(sum.len(), counter_width(250));
+        assert_eq!(value(&sum), expected);
+
+        // Unit weights count like the tree
+        let ones = vec![1u8; 100];
+        assert_eq!(value(&weighted_csa_tree(&diff, &ones, &false, || Ok(())).unwrap()), diff.iter().filter(|&&d| d).count());
+        assert_eq!(value(&weighted_csa_tree(&[true; 4], &[255; 4], &false, || Ok(())).unwrap()), 1020);
+    }
+
+    #[test]
+    fn comparison_tree_keeps_the_smallest_distance() {
+        let bits = |v: usize| (0..11).map(|i| (v >> i) & 1 == 1).collect::<Vec<_>>();
+        for distances in [vec![204], vec![310, 95], vec![120, 87, 87, 400, 1024], vec![5, 3, 7, 2, 9, 2, 6, 4, 8]] {
+            let expected = *distances.iter().min().unwrap();
+            let min = min_tree(distances.into_iter().map(bits).collect(), &true, || Ok(())).unwrap();
+            assert_eq!(value(&min), expected);
+        }
+    }
+
+    #[test]
+    fn two_of_n_keeps_the_second_smallest_distance() {
+        let bits = |v: usize| (0..11).map(|i| (v >> i) & 1 == 1).collect::<Vec<_>>();
+        for distances in [vec![310, 95], vec![120, 87, 87, 400], vec![400, 300, 200, 100], vec![5, 9, 1, 7]] {
+            let mut sorted = distances.clone();
+            sorted.sort();
+            let second = second_min(distances.into_iter().map(bits).collect(), &true, || Ok(())).unwrap();
+            assert_eq!(value(&second), sorted[1]);
+        }
+        assert_eq!(value(&second_min(vec![bits(204)], &true, || Ok(())).unwrap()), 204);
+    }
+
+    #[test]
+    fn constant_comparison_matches_every_threshold() {
+        let bits = |v: usize| (0..4).map(|i| (v >> i) & 1 == 1).collect::<Vec<_>>();
+        for distance in 0..16 {
+            for threshold in [0, 1, 7, 15, 16, 17, 300, usize::MAX] {
+                let leq = constant_leq(&bits(distance), threshold, &false, || Ok(())).unwrap();
+                assert_eq!(leq, distance <= threshold, "{} <= {}", distance, threshold);
+            }
+        }
+        // No distance bits: only the zero distance, below every threshold
+        assert!(constant_leq(&[], 0, &false, || Ok(())).unwrap());
+        assert!(constant_leq(&[], 5, &false, || Ok(())).unwrap());
+    }
+
+    #[test]
+    fn scaled_comparison_matches_the_ratio() {
+        let bits = |v: usize, width: usize| (0..width).map(|i| (v >> i) & 1 == 1).collect::<Vec<_>>();
+        for a in 0..40 {
+            for b in 0..16 {
+                assert_eq!(value(&add_bits(&bits(a, 6), &bits(b, 4), &false)), a + b);
+            }
+            for k in [0, 1, 7, 204, 1024] {
+                assert_eq!(value(&mul_constant(&bits(a, 6), k, &false)), a * k);
+            }
+        }
+        // Differing of kept bits, 1024 compared with threshold 204, at least 256 kept
+        for (distance, kept, leq) in [(40, 600, true), (119, 600, true), (120, 600, false), (0, 255, false), (0, 0, false)] {
+            let scaled = scaled_leq(&bits(distance, 11), &bits(kept, 11), 1024, 204, 256, &true, || Ok(())).unwrap();
+            assert_eq!(scaled, leq, "{}/{}", distance, kept);
+        }
+        assert!(scaled_leq(&bits(0, 11), &bits(0, 11), 1024, 204, 0, &true, || Ok(())).unwrap());
+    }
+
+    #[test]
+    fn backend_names_match_the_config_values() {
+        assert_eq!(MatchingBackend::default(), MatchingBackend::Boolean);
+        let radix: MatchingBackend = serde_json::from_str("\"radix\"").unwrap();
+        assert_eq!(radix, MatchingBackend::Radix);
+        let boolean = serde_json::to_string(&MatchingBackend::Boolean).unwrap();
+        assert_eq!(boolean, format!("\"{}\"", MatchingBackend::Boolean));
+    }
+
+    #[test]
+    fn encrypted_comparison_matches_plain_leq() {
+        let bits = |v: usize, width: usize| (0..width).map(|i| (v >> i) & 1 == 1).collect::<Vec<_>>();
+        for (wa, wb) in [(5, 5), (5, 3), (3, 5)] {
+            for a in 0..1 << wa {
+                for b in 0..1 << wb {
+                    let leq = leq_bits(&bits(a, wa), &bits(b, wb), &true, || Ok(())).unwrap();
+                    assert_eq!(leq, a <= b, "{} <= {}", a, b);
+                }
+            }
+        }
+    }
+}
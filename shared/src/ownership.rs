@@ -0,0 +1,104 @@
+// shared/src/ownership.rs
+
+//! Proof of ownership for deleting an enrollment.
+//!
+//! A verify request may ask for an ownership challenge. The server draws a
+//! random nonce, encrypts each bit under the client's compact public key and
+//! ANDs it with the encrypted match bit, so only a client whose probe matched
+//! decrypts the nonce; a failed match decrypts to zeros. Each output bit
+//! comes from its own fresh encryption: copies of one ciphertext would let
+//! the client read the nonce by comparing them. Presenting the nonce in a
+//! delete request proves the match without the server ever learning the
+//! decision.
+
+use std::ops::BitAnd;
+use tfhe::prelude::*;
+use tfhe::{CompactCiphertextList, CompactPublicKey, FheBool};
+
+use crate::error::FingerprintError;
+
+/// Nonce length
+pub const CHALLENGE_BITS: usize = 128;
+
+/// `nonce` masked with `matched`: bit i is `matched AND encrypt(nonce_i)`, with
+/// one fresh encryption per bit. Generic so the plain logic can be tested on bools.
+pub fn mask_with_match<B, E>(nonce: &[bool], matched: &B, mut encrypt: impl FnMut(bool) -> Result<B, E>) -> Result<Vec<B>, E>
+where
+    for<'a> &'a B: BitAnd<&'a B, Output = B>,
+{
+    nonce.iter().map(|&bit| Ok(matched & &encrypt(bit)?)).collect()
+}
+
+/// One nonce bit encrypted under the client's compact public key, in a list of
+/// its own so that every bit gets fresh randomness
+pub fn encrypt_bit(bit: bool, public_key: &CompactPublicKey) -> Result<FheBool, FingerprintError> {
+    let fhe = |e: tfhe::Error| FingerprintError::Fhe(format!("Nonce bit not encrypted: {}", e));
+    CompactCiphertextList::builder(public_key)
+        .push(bit)
+        .build()
+        .expand()
+        .map_err(fhe)?
+        .get::<FheBool>(0)
+        .map_err(fhe)?
+        .ok_or_else(|| FingerprintError::Fhe("Nonce bit not encrypted: empty list".to_string()))
+}
+
+/// Hex form of a (decrypted) nonce, as sent in a delete request
+pub fn proof_hex(bits: &[bool]) -> String {
+    bits.chunks(8)
+        .map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, &b)| acc | ((b as u8) << i)))
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_match_reveals_the_nonce() {
+        let nonce: Vec<bool> = (0..CHALLENGE_BITS).map(|i| i % 3 == 0).collect();
+        let plain = |bit: bool| Ok::<_, ()>(bit);
+        assert_eq!(mask_with_match(&nonce, &true, plain).unwrap(), nonce);
+        assert!(mask_with_match(&nonce, &false, plain).unwrap().iter().all(|&b| !b));
+
+        let hex = proof_hex(&nonce);
+        assert_eq!(hex.len(), CHALLENGE_BITS / 4);
+        assert_eq!(&hex[..2], "49");    // bits 0, 3, 6 of the first byte
+        assert_ne!(hex, proof_hex(&[false; CHALLENGE_BITS]));
+    }
+
+    #[test]
+    fn every_bit_gets_its_own_encryption() {
+        let nonce = [true, false, true, true];
+        let mut encryptions = 0;
+        let masked = mask_with_match(&nonce, &true, |bit| {
+            encryptions += 1;
+            Ok::<_, ()>(bit)
+        });
+        assert_eq!(masked.unwrap().len(), nonce.len());
+        assert_eq!(encryptions, nonce.len());
+
+        let failed = mask_with_match(&nonce, &true, |_| Err("no key"));
+        assert_eq!(failed, Err("no key"));
+    }
+
+    #[test]
+    fn a_matching_probe_decrypts_the_nonce_under_its_public_key() {
+        use tfhe::{generate_keys, set_server_key, ConfigBuilder};
+
+        let (client_key, server_key) = generate_keys(ConfigBuilder::default().build());
+        set_server_key(server_key);
+        let public_key = CompactPublicKey::try_new(&client_key).unwrap();
+        let nonce = [true, false, true, true];
+        let decrypt = |bits: Vec<FheBool>| bits.iter().map(|b| b.decrypt(&client_key)).collect::<Vec<bool>>();
+
+        let matched = FheBool::encrypt(true, &client_key);
+        let masked = mask_with_match(&nonce, &matched, |bit| encrypt_bit(bit, &public_key)).unwrap();
+        assert_eq!(decrypt(masked), nonce);
+
+        let failed = FheBool::encrypt(false, &client_key);
+        let masked = mask_with_match(&nonce, &failed, |bit| encrypt_bit(bit, &public_key)).unwrap();
+        assert_eq!(decrypt(masked), [false; 4]);
+    }
+}
@@ -0,0 +1,88 @@
+// shared/src/params.rs
+//! TFHE parameter sets the client can generate keys with.
+
+use serde::{Serialize, Deserialize};
+use tfhe::shortint::parameters::{
+    ClassicPBSParameters, PARAM_MESSAGE_1_CARRY_1_KS_PBS, PARAM_MESSAGE_2_CARRY_2_KS_PBS,
+    PARAM_MESSAGE_2_CARRY_2_KS_PBS_TUNIFORM_2M64,
+};
+use tfhe::{Config, ConfigBuilder};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterSet {
+    #[default]
+    Default,                    // ConfigBuilder::default()
+    Message2Carry2,
+    Message1Carry1,
+    Message2Carry2Tuniform2m64,
+}
+
+impl ParameterSet {
+    /// Explicit sets the advisor benchmarks
+    pub const CANDIDATES: [ParameterSet; 3] = [
+        ParameterSet::Message2Carry2,
+        ParameterSet::Message1Carry1,
+        ParameterSet::Message2Carry2Tuniform2m64,
+    ];
+
+    pub fn config(&self) -> Config {
+        let builder = ConfigBuilder::default();
+        match self {
+            ParameterSet::Default => builder,
+            _ => builder.use_custom_parameters(self.parameters()),
+        }
+        .build()
+    }
+
+    /// tfhe's shortint parameters of this set; `ConfigBuilder::default()` uses
+    /// `PARAM_MESSAGE_2_CARRY_2_KS_PBS` on the CPU
+    pub fn parameters(&self) -> ClassicPBSParameters {
+        match self {
+            ParameterSet::Default | ParameterSet::Message2Carry2 => PARAM_MESSAGE_2_CARRY_2_KS_PBS,
+            ParameterSet::Message1Carry1 => PARAM_MESSAGE_1_CARRY_1_KS_PBS,
+            ParameterSet::Message2Carry2Tuniform2m64 => PARAM_MESSAGE_2_CARRY_2_KS_PBS_TUNIFORM_2M64,
+        }
+    }
+
+    /// Security level in bits. tfhe's parameters don't carry one: all sets it
+    /// ships are generated for at least 128 bits, its TUniform ones for 132.
+    pub fn security_bits(&self) -> u32 {
+        match self {
+            ParameterSet::Message2Carry2Tuniform2m64 => 132,
+            ParameterSet::Default | ParameterSet::Message2Carry2 | ParameterSet::Message1Carry1 => 128,
+        }
+    }
+
+    /// Bootstrapping failure probability is at most 2^-pfail_log2, from the parameters' own estimate
+    pub fn pfail_log2(&self) -> u32 {
+        (-self.parameters().log2_p_fail).floor() as u32
+    }
+}
+
+impl std::fmt::Display for ParameterSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ParameterSet::Default => "default",
+            ParameterSet::Message2Carry2 => "message_2_carry_2",
+            ParameterSet::Message1Carry1 => "message_1_carry_1",
+            ParameterSet::Message2Carry2Tuniform2m64 => "message_2_carry_2_tuniform_2m64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_probabilities_come_from_the_parameters() {
+        // The unsuffixed sets are tfhe 0.9's Gaussian 2^-64 aliases
+        assert_eq!(ParameterSet::Message2Carry2.pfail_log2(), 64);
+        assert_eq!(ParameterSet::Message1Carry1.pfail_log2(), 64);
+        assert_eq!(ParameterSet::Message2Carry2Tuniform2m64.pfail_log2(), 64);
+        assert_eq!(ParameterSet::Default.pfail_log2(), ParameterSet::Message2Carry2.pfail_log2());
+        assert!(ParameterSet::CANDIDATES.iter().all(|p| p.security_bits() >= 128));
+    }
+}
@@ -1,5 +1,40 @@
 use serde::{Serialize, Deserialize};
 
+use crate::compute::ComputeBackend;
+use crate::error::ErrorCode;
+use crate::identity::ResultAttestation;
+use crate::delta::TemplateDelta;
+use crate::fusion::FusionRule;
+use crate::fuzzy::FuzzySketch;
+use crate::session::SessionBinding;
+use crate::quality::QualityReport;
+use crate::soft::SoftProfile;
+use crate::template::TemplateParams;
+use crate::trivium::Cipher;
+
+// ==================== FACTORS ====================
+
+/// Authentication factor a template belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Factor {
+    #[default]
+    Fingerprint,   // Primary enrolled finger
+    SecondFinger,  // Alternate finger used as fallback
+    Pin,           // PIN expanded to a template-sized bit string
+}
+
+impl std::fmt::Display for Factor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Factor::Fingerprint => "fingerprint",
+            Factor::SecondFinger => "second_finger",
+            Factor::Pin => "pin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 // ==================== REGISTER ENDPOINT ====================
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,6 +44,87 @@ pub struct RegisterRequest {
     pub encrypted_key_bytes: Vec<u8>,       // FHE encrypted Trivium key (80 bits)
     pub encrypted_iv_bytes: Vec<u8>,        // FHE encrypted Trivium IV (80 bits)
     pub server_key_bytes: Option<Vec<u8>>,  // İlk kayıtta gönderilir
+    #[serde(default)]
+    pub duress: bool,                       // Enroll as the user's duress finger
+    #[serde(default)]
+    pub factor: Factor,                     // Which factor this template enrolls
+    #[serde(default)]
+    pub consent: Option<ConsentInfo>,       // Consent record for this enrollment
+    #[serde(default)]
+    pub api_key: Option<String>,            // Tenant API key (None = default tenant)
+    #[serde(default = "crate::template::default_template_bits")]
+    pub template_bits: usize,               // Negotiated template length (see template.rs)
+    #[serde(default)]
+    pub credential_key: Option<String>,     // Enrollment credential public key (base64 Ed25519)
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake (see session.rs)
+    #[serde(default)]
+    pub soft: Option<SoftProfile>,          // Soft attributes of this finger (see soft.rs)
+    #[serde(default)]
+    pub threshold_bits: Option<EnrolledThreshold>, // Per-user threshold of the primary finger (None = policy)
+    #[serde(default)]
+    pub cipher: Cipher,                     // Transciphering cipher; sets the key/IV length (Kreyvium: 128 bits)
+    #[serde(default)]
+    pub replace_existing: bool,             // Confirms replacing an enrolled template; rejected as a duplicate otherwise
+    #[serde(default)]
+    pub samples: Vec<EncryptedSample>,      // Captures a multi-sample `ciphertext` was voted from (see consensus.rs)
+    #[serde(default)]
+    pub reliability_mask: Option<Vec<bool>>, // Template bits the samples agree on; verification compares only these
+    #[serde(default)]
+    pub quality_mask: Option<QualityMask>,  // Template bits of good quality, primary finger only (None = all)
+    #[serde(default)]
+    pub bit_weights: Option<Vec<u8>>,       // Extractor weight of each template bit, primary finger only (None = unweighted)
+    #[serde(default)]
+    pub rotations: Vec<EncryptedSample>,    // The primary capture rotated a few degrees; the closest one counts (see template.rs)
+    #[serde(default)]
+    pub capture_quality: Option<u8>,        // Quality score (0-100) of the primary capture, recorded with the template
+    #[serde(default)]
+    pub params: Option<TemplateParams>,     // Length, grid and quantization of the template (None = LBP of `template_bits`)
+    #[serde(default)]
+    pub fuzzy: Option<FuzzySketch>,         // Match by error correction instead of a threshold (see fuzzy.rs)
+    #[serde(default)]
+    pub transform_id: Option<String>,       // Cancellable transform of the fingerprint bits (see transform.rs)
+    #[serde(default)]
+    pub fingers: Vec<EncryptedSample>,      // Further fingers decided on with the primary one (see fusion.rs)
+    #[serde(default)]
+    pub fusion: Option<FusionRule>,         // How their decisions combine; set with `fingers`
+    #[serde(default)]
+    pub gpu_server_key_bytes: Option<Vec<u8>>, // CompressedServerKey the server decompresses onto a GPU (see compute.rs)
+}
+
+/// One capture of a multi-sample enrollment (or a rotated variant of the primary
+/// one, or another finger of a fused one), Trivium-encrypted under its own key/IV (cipher and length as in the
+/// request carrying it)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptedSample {
+    pub ciphertext: Vec<bool>,
+    pub encrypted_key_bytes: Vec<u8>,
+    pub encrypted_iv_bytes: Vec<u8>,
+}
+
+/// Match threshold (most differing bits) chosen for a user at enrollment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrolledThreshold {
+    Plain(usize),           // Visible to the server
+    Encrypted(Vec<u8>),     // Vec<FheBool> LSB-first, `template::distance_width` bits
+}
+
+/// Per-bit quality mask of a template, produced by feature extraction (see quality.rs).
+/// Positions it clears are left out of the Hamming distance.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityMask {
+    Plain(Vec<bool>),       // Visible to the server, narrows the compared bits
+    Encrypted(Vec<u8>),     // Vec<FheBool>, one per template bit; the threshold scales homomorphically
+}
+
+/// Consent and retention metadata attached to an enrollment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConsentInfo {
+    pub reference: String,                  // Consent record id in the upstream system
+    pub purpose: String,                    // Declared processing purpose
+    pub retention_until: Option<String>,    // RFC 3339; the server purges the enrollment after this
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,6 +133,14 @@ pub struct RegisterResponse {
     pub message: String,
     pub user_id: String,
     pub timestamp: String,
+    #[serde(default)]
+    pub quality: Option<QualityReport>,     // Filled in by the client library (see quality.rs)
+    #[serde(default)]
+    pub enrollment_count: Option<u32>,      // Enrollments of the primary finger so far, this one included
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,      // Kind of failure (None on success)
+    #[serde(default)]
+    pub server_key_fingerprint: Option<String>, // Fingerprint of the tenant's stored server key (see `server_key_fingerprint`)
 }
 
 impl RegisterRequest {
@@ -29,12 +153,151 @@ impl RegisterRequest {
     ) -> Self {
         Self {
             user_id,
+            template_bits: ciphertext.len(),
             ciphertext,
             encrypted_key_bytes,
             encrypted_iv_bytes,
             server_key_bytes,
+            duress: false,
+            factor: Factor::Fingerprint,
+            consent: None,
+            api_key: None,
+            credential_key: None,
+            session: None,
+            soft: None,
+            threshold_bits: None,
+            cipher: Cipher::Trivium,
+            replace_existing: false,
+            samples: Vec::new(),
+            reliability_mask: None,
+            quality_mask: None,
+            bit_weights: None,
+            rotations: Vec::new(),
+            capture_quality: None,
+            params: None,
+            fuzzy: None,
+            transform_id: None,
+            fingers: Vec::new(),
+            fusion: None,
+            gpu_server_key_bytes: None,
         }
     }
+
+    /// Enroll this template as an alternate (fallback) factor
+    pub fn with_factor(mut self, factor: Factor) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Mark this registration as the user's duress finger
+    pub fn with_duress(mut self) -> Self {
+        self.duress = true;
+        self
+    }
+
+    /// Attach consent and retention metadata
+    pub fn with_consent(mut self, consent: ConsentInfo) -> Self {
+        self.consent = Some(consent);
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// Register the enrollment credential sessions are later proven with
+    pub fn with_credential_key(mut self, credential_key: String) -> Self {
+        self.credential_key = Some(credential_key);
+        self
+    }
+
+    pub fn with_session(mut self, session: Option<SessionBinding>) -> Self {
+        self.session = session;
+        self
+    }
+
+    pub fn with_soft(mut self, soft: Option<SoftProfile>) -> Self {
+        self.soft = soft;
+        self
+    }
+
+    /// Match this user against their own threshold instead of the server's policy
+    pub fn with_threshold(mut self, threshold: Option<EnrolledThreshold>) -> Self {
+        self.threshold_bits = threshold;
+        self
+    }
+
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Confirm re-enrollment: the enrolled template is replaced and kept in its history
+    pub fn with_replace_existing(mut self, replace: bool) -> Self {
+        self.replace_existing = replace;
+        self
+    }
+
+    /// Enroll a majority-voted template together with the captures it came from
+    pub fn with_samples(mut self, samples: Vec<EncryptedSample>, reliability_mask: Vec<bool>) -> Self {
+        self.samples = samples;
+        self.reliability_mask = Some(reliability_mask);
+        self
+    }
+
+    /// Leave the bits feature extraction flagged as unreliable out of every verification
+    pub fn with_quality_mask(mut self, mask: Option<QualityMask>) -> Self {
+        self.quality_mask = mask;
+        self
+    }
+
+    /// Match this user with a weighted distance (see template.rs)
+    pub fn with_bit_weights(mut self, weights: Option<Vec<u8>>) -> Self {
+        self.bit_weights = weights;
+        self
+    }
+
+    /// Enroll rotated variants of the primary finger, for rotation-tolerant matching
+    pub fn with_rotations(mut self, rotations: Vec<EncryptedSample>) -> Self {
+        self.rotations = rotations;
+        self
+    }
+
+    /// Record the quality score of the primary capture with the enrollment
+    pub fn with_capture_quality(mut self, score: u8) -> Self {
+        self.capture_quality = Some(score);
+        self
+    }
+
+    pub fn with_params(mut self, params: TemplateParams) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    /// Enroll the primary finger as a fuzzy extractor (no threshold, weights or quality masks)
+    pub fn with_fuzzy_sketch(mut self, sketch: FuzzySketch) -> Self {
+        self.fuzzy = Some(sketch);
+        self
+    }
+
+    pub fn with_transform_id(mut self, transform_id: Option<String>) -> Self {
+        self.transform_id = transform_id;
+        self
+    }
+
+    /// Enroll further fingers, matched together with the primary one and fused by `rule`
+    pub fn with_fingers(mut self, fingers: Vec<EncryptedSample>, rule: FusionRule) -> Self {
+        self.fingers = fingers;
+        self.fusion = Some(rule);
+        self
+    }
+
+    /// Also send a compressed server key, so the server can verify on a GPU
+    pub fn with_gpu_server_key(mut self, gpu_server_key_bytes: Option<Vec<u8>>) -> Self {
+        self.gpu_server_key_bytes = gpu_server_key_bytes;
+        self
+    }
 }
 
 impl RegisterResponse {
@@ -44,6 +307,10 @@ impl RegisterResponse {
             message: "Fingerprint registered successfully".to_string(),
             user_id,
             timestamp: chrono::Utc::now().to_rfc3339(),
+            quality: None,
+            enrollment_count: None,
+            error_code: None,
+            server_key_fingerprint: None,
         }
     }
 
@@ -53,26 +320,165 @@ impl RegisterResponse {
             message,
             user_id,
             timestamp: chrono::Utc::now().to_rfc3339(),
+            quality: None,
+            enrollment_count: None,
+            error_code: None,
+            server_key_fingerprint: None,
         }
     }
+
+    pub fn with_quality(mut self, quality: Option<QualityReport>) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn with_enrollment_count(mut self, count: u32) -> Self {
+        self.enrollment_count = Some(count);
+        self
+    }
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+
+    pub fn with_server_key_fingerprint(mut self, fingerprint: Option<String>) -> Self {
+        self.server_key_fingerprint = fingerprint;
+        self
+    }
+}
+
+/// Fingerprint of a serialized server key: its SHA-256, in hex. A verify
+/// request carrying one is refused if the tenant's stored key differs, since
+/// evaluating under a key of another client key only decrypts to garbage.
+pub fn server_key_fingerprint(server_key_bytes: &[u8]) -> String {
+    crate::attestation::sha256_hex(server_key_bytes)
+}
+
+// ==================== DELTA ENDPOINT ====================
+
+/// Refresh of the enrolled fingerprint with only its changed regions (see delta.rs).
+/// Answered with a `RegisterResponse`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeltaRequest {
+    pub user_id: String,
+    pub delta: TemplateDelta,
+    #[serde(default)]
+    pub api_key: Option<String>,            // Tenant API key (None = default tenant)
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake (see session.rs)
+}
+
+impl DeltaRequest {
+    pub fn new(user_id: String, delta: TemplateDelta) -> Self {
+        Self { user_id, delta, api_key: None, session: None }
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn with_session(mut self, session: Option<SessionBinding>) -> Self {
+        self.session = session;
+        self
+    }
 }
 
 // ==================== VERIFY ENDPOINT ====================
 
+/// What a verify response returns. The exact distance tells a client how
+/// close each probe came, enough to hill-climb towards the enrolled template;
+/// `MatchOnly` leaves `encrypted_distance_bytes` empty.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultMode {
+    MatchOnly,
+    #[default]
+    MatchAndDistance,
+}
+
+impl ResultMode {
+    /// The mode a request gets under this policy: a client can ask for less
+    /// than the policy returns, never for more
+    pub fn narrowed_by(self, requested: ResultMode) -> ResultMode {
+        match (self, requested) {
+            (ResultMode::MatchAndDistance, ResultMode::MatchAndDistance) => ResultMode::MatchAndDistance,
+            _ => ResultMode::MatchOnly,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VerifyRequest {
     pub user_id: String,
     pub ciphertext: Vec<bool>,              // 128 bits - Trivium encrypted (probe)
     pub encrypted_key_bytes: Vec<u8>,       // FHE encrypted Trivium key (probe)
     pub encrypted_iv_bytes: Vec<u8>,        // FHE encrypted Trivium IV (probe)
-    pub encrypted_true_bytes: Vec<u8>,      // FHE encrypted true constant
+    pub encrypted_true_bytes: Vec<u8>,      // FHE encrypted true, the match bit of fail-open answers
+    #[serde(default)]
+    pub factor: Factor,                     // Which enrolled factor to match against
+    #[serde(default)]
+    pub api_key: Option<String>,            // Tenant API key (None = default tenant)
+    #[serde(default)]
+    pub request_id: Option<String>,         // Echoed in the signed result
+    #[serde(default = "crate::template::default_template_bits")]
+    pub template_bits: usize,               // Must equal the enrolled template's length
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake (see session.rs)
+    #[serde(default)]
+    pub soft: Option<SoftProfile>,          // Declared soft attributes of the probe (see soft.rs)
+    #[serde(default)]
+    pub mask: Option<Vec<bool>>,            // Covered extractor regions of a partial probe (see template.rs)
+    #[serde(default)]
+    pub encrypted_threshold_bytes: Option<Vec<u8>>, // Vec<FheBool> LSB-first: client's max distance (None = server policy)
+    #[serde(default)]
+    pub cipher: Cipher,                     // Transciphering cipher of the probe (Kreyvium: 128-bit key/IV)
+    #[serde(default)]
+    pub ownership_challenge: bool,          // Return a masked nonce for a delete request (see ownership.rs)
+    #[serde(default)]
+    pub ownership_public_key: Option<Vec<u8>>, // tfhe::CompactPublicKey of the client key the nonce bits are encrypted under
+    #[serde(default)]
+    pub quality_mask: Option<QualityMask>,  // Probe bits of good quality, ANDed with the enrolled mask (None = all)
+    #[serde(default)]
+    pub params: Option<TemplateParams>,     // Length, grid and quantization of the probe (None = not checked)
+    #[serde(default)]
+    pub transform_id: Option<String>,       // Cancellable transform of the probe bits; must be the enrollment's
+    #[serde(default)]
+    pub fingers: Vec<EncryptedSample>,      // Probes of a fused enrollment's further fingers, in enrollment order
+    #[serde(default)]
+    pub server_key_fingerprint: Option<String>, // Server key the client expects its tenant to hold (None = not checked)
+    #[serde(default)]
+    pub result_mode: ResultMode,            // Whether the encrypted distance comes back with the match bit
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VerifyResponse {
     pub success: bool,
     pub encrypted_match_bytes: Vec<u8>,     // FheBool serialized
-    pub encrypted_distance_bytes: Vec<u8>,  // Vec<FheBool>[8] serialized
+    pub encrypted_distance_bytes: Vec<u8>,  // Vec<FheBool>[8] serialized (empty for `ResultMode::MatchOnly`)
+    #[serde(default)]
+    pub encrypted_duress_bytes: Option<Vec<u8>>, // FheBool: probe matched the duress finger
+    #[serde(default)]
+    pub failure: Option<FailureNotice>,     // Set when the error policy decided the result
+    #[serde(default)]
+    pub attestation: Option<ResultAttestation>, // Server identity signature over the result
+    #[serde(default)]
+    pub template_bits: Option<usize>,       // Template length the distance was computed over
+    #[serde(default)]
+    pub compared_bits: Option<usize>,       // Bits actually compared (partial probes)
+    #[serde(default)]
+    pub weighted_bits: Option<usize>,       // Total weight of the compared bits (weighted enrollments)
+    #[serde(default)]
+    pub encrypted_ownership_bytes: Option<Vec<u8>>, // Vec<FheBool>: ownership nonce AND match bit
+    #[serde(default)]
+    pub encrypted_fuzzy_key_bytes: Option<Vec<u8>>, // Vec<FheBool>: key bits of the corrected probe (fuzzy enrollments)
+    #[serde(default)]
+    pub fuzzy_key_hash: Option<String>,     // Enrolled key hash the decrypted key must match
+    #[serde(default)]
+    pub compute_backend: Option<ComputeBackend>, // Backend the job was evaluated on
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,      // Kind of failure (None on success)
     pub timestamp: String,
     
     // 🚫 DEBUG ONLY - production'da None olacak
@@ -90,12 +496,111 @@ impl VerifyRequest {
     ) -> Self {
         Self {
             user_id,
+            template_bits: ciphertext.len(),
             ciphertext,
             encrypted_key_bytes,
             encrypted_iv_bytes,
             encrypted_true_bytes,
+            factor: Factor::Fingerprint,
+            api_key: None,
+            request_id: None,
+            session: None,
+            soft: None,
+            mask: None,
+            encrypted_threshold_bytes: None,
+            cipher: Cipher::Trivium,
+            ownership_challenge: false,
+            ownership_public_key: None,
+            quality_mask: None,
+            params: None,
+            transform_id: None,
+            fingers: Vec::new(),
+            server_key_fingerprint: None,
+            result_mode: ResultMode::default(),
         }
     }
+
+    pub fn with_factor(mut self, factor: Factor) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: String) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    pub fn with_session(mut self, session: Option<SessionBinding>) -> Self {
+        self.session = session;
+        self
+    }
+
+    pub fn with_soft(mut self, soft: Option<SoftProfile>) -> Self {
+        self.soft = soft;
+        self
+    }
+
+    /// Match only the covered regions of a partial probe
+    pub fn with_mask(mut self, mask: Option<Vec<bool>>) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Match against an FHE-encrypted threshold instead of the server's policy
+    pub fn with_encrypted_threshold(mut self, threshold_bytes: Option<Vec<u8>>) -> Self {
+        self.encrypted_threshold_bytes = threshold_bytes;
+        self
+    }
+
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Ask for an ownership proof along with the result; the nonce bits are
+    /// encrypted under `public_key`, a serialized `tfhe::CompactPublicKey`
+    pub fn with_ownership_challenge(mut self, public_key: Option<Vec<u8>>) -> Self {
+        self.ownership_challenge = public_key.is_some();
+        self.ownership_public_key = public_key;
+        self
+    }
+
+    pub fn with_quality_mask(mut self, mask: Option<QualityMask>) -> Self {
+        self.quality_mask = mask;
+        self
+    }
+
+    pub fn with_params(mut self, params: TemplateParams) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    pub fn with_transform_id(mut self, transform_id: Option<String>) -> Self {
+        self.transform_id = transform_id;
+        self
+    }
+
+    /// Probe the further fingers of a fused enrollment along with the primary one
+    pub fn with_fingers(mut self, fingers: Vec<EncryptedSample>) -> Self {
+        self.fingers = fingers;
+        self
+    }
+
+    /// Refuse the probe unless the tenant's server key has this fingerprint
+    pub fn with_server_key_fingerprint(mut self, fingerprint: Option<String>) -> Self {
+        self.server_key_fingerprint = fingerprint;
+        self
+    }
+
+    pub fn with_result_mode(mut self, result_mode: ResultMode) -> Self {
+        self.result_mode = result_mode;
+        self
+    }
 }
 
 impl VerifyResponse {
@@ -107,12 +612,77 @@ impl VerifyResponse {
             success: true,
             encrypted_match_bytes,
             encrypted_distance_bytes,
+            encrypted_duress_bytes: None,
+            failure: None,
+            attestation: None,
+            template_bits: None,
+            compared_bits: None,
+            weighted_bits: None,
+            encrypted_ownership_bytes: None,
+            encrypted_fuzzy_key_bytes: None,
+            fuzzy_key_hash: None,
+            compute_backend: None,
+            error_code: None,
             timestamp: chrono::Utc::now().to_rfc3339(),
             debug_server_match: None,
             debug_server_distance: None,
         }
     }
 
+    /// Attach the encrypted duress flag (for the relying party, not shown to the user)
+    pub fn with_duress(mut self, encrypted_duress_bytes: Vec<u8>) -> Self {
+        self.encrypted_duress_bytes = Some(encrypted_duress_bytes);
+        self
+    }
+
+    pub fn with_attestation(mut self, attestation: ResultAttestation) -> Self {
+        self.attestation = Some(attestation);
+        self
+    }
+
+    pub fn with_failure(mut self, failure: FailureNotice) -> Self {
+        self.failure = Some(failure);
+        self
+    }
+
+    pub fn with_template_bits(mut self, template_bits: usize) -> Self {
+        self.template_bits = Some(template_bits);
+        self
+    }
+
+    pub fn with_compared_bits(mut self, compared_bits: usize) -> Self {
+        self.compared_bits = Some(compared_bits);
+        self
+    }
+
+    /// The distance is weighted; its counter is `template::result_width` bits wide
+    pub fn with_weighted_bits(mut self, weighted_bits: usize) -> Self {
+        self.weighted_bits = Some(weighted_bits);
+        self
+    }
+
+    pub fn with_ownership(mut self, encrypted_ownership_bytes: Vec<u8>) -> Self {
+        self.encrypted_ownership_bytes = Some(encrypted_ownership_bytes);
+        self
+    }
+
+    /// Key recovered by a fuzzy enrollment; the match only counts if it hashes to `key_hash`
+    pub fn with_fuzzy_key(mut self, encrypted_key_bytes: Vec<u8>, key_hash: String) -> Self {
+        self.encrypted_fuzzy_key_bytes = Some(encrypted_key_bytes);
+        self.fuzzy_key_hash = Some(key_hash);
+        self
+    }
+
+    pub fn with_compute_backend(mut self, backend: ComputeBackend) -> Self {
+        self.compute_backend = Some(backend);
+        self
+    }
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+
     pub fn with_debug(mut self, match_result: bool, distance: usize) -> Self {
         self.debug_server_match = Some(match_result);
         self.debug_server_distance = Some(distance);
@@ -124,6 +694,17 @@ impl VerifyResponse {
             success: false,
             encrypted_match_bytes: vec![],
             encrypted_distance_bytes: vec![],
+            encrypted_duress_bytes: None,
+            failure: None,
+            attestation: None,
+            template_bits: None,
+            compared_bits: None,
+            weighted_bits: None,
+            encrypted_ownership_bytes: None,
+            encrypted_fuzzy_key_bytes: None,
+            fuzzy_key_hash: None,
+            compute_backend: None,
+            error_code: None,
             timestamp: chrono::Utc::now().to_rfc3339(),
             debug_server_match: None,
             debug_server_distance: None,
@@ -131,6 +712,567 @@ impl VerifyResponse {
     }
 }
 
+// ==================== POLICY ENDPOINT ====================
+
+/// Server-side fallback policy (`../database/policy.json`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FallbackPolicy {
+    pub enabled: bool,
+    pub order: Vec<Factor>,     // Fallback factors, tried in this order
+    pub on_timeout: bool,       // Also fall back when the fingerprint verify timed out
+    pub max_fallbacks: usize,   // Upper bound on fallback attempts per authentication
+    #[serde(default)]
+    pub on_error: ErrorPolicy,  // What the server does when a verify job cannot complete
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            order: vec![Factor::SecondFinger, Factor::Pin],
+            on_timeout: true,
+            max_fallbacks: 2,
+            on_error: ErrorPolicy::default(),
+        }
+    }
+}
+
+/// Conditions under which a verify job cannot produce a real decision
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCondition {
+    ServerKeyMissing,
+    JobTimeout,
+    CorruptTemplate,
+}
+
+impl std::fmt::Display for ErrorCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ErrorCondition::ServerKeyMissing => "server key missing",
+            ErrorCondition::JobTimeout => "job timeout",
+            ErrorCondition::CorruptTemplate => "corrupted template",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reaction to an `ErrorCondition`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureAction {
+    /// Fail closed: the authentication is denied, no fallback factors
+    #[default]
+    Reject,
+    /// Fail open: report a match (the client's encrypted `true`), flagged in the audit log
+    Allow,
+    /// Deny this factor but let the client continue with its fallback factors
+    Fallback,
+}
+
+/// Per-condition actions (`on_error` in `../database/policy.json`); everything rejects by default
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ErrorPolicy {
+    #[serde(default)]
+    pub server_key_missing: FailureAction,
+    #[serde(default)]
+    pub job_timeout: FailureAction,
+    #[serde(default)]
+    pub corrupt_template: FailureAction,
+    #[serde(default)]
+    pub job_timeout_secs: Option<u64>,  // Verify jobs running longer count as timed out (None = no limit)
+}
+
+impl ErrorPolicy {
+    pub fn action(&self, condition: ErrorCondition) -> FailureAction {
+        match condition {
+            ErrorCondition::ServerKeyMissing => self.server_key_missing,
+            ErrorCondition::JobTimeout => self.job_timeout,
+            ErrorCondition::CorruptTemplate => self.corrupt_template,
+        }
+    }
+}
+
+/// Attached to a verify response decided by the error policy instead of by matching
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FailureNotice {
+    pub condition: ErrorCondition,
+    pub action: FailureAction,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolicyRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PolicyResponse {
+    pub user_id: String,
+    pub policy: FallbackPolicy,         // `order` only lists factors the user enrolled
+    pub enrolled_factors: Vec<Factor>,
+    pub timestamp: String,
+}
+
+impl PolicyResponse {
+    pub fn new(user_id: String, policy: FallbackPolicy, enrolled_factors: Vec<Factor>) -> Self {
+        Self {
+            user_id,
+            policy,
+            enrolled_factors,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// ==================== ACCOUNT ENDPOINT ====================
+
+/// User account management, for when identifiers change upstream
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AccountOperation {
+    /// Move an enrollment to a new user id
+    Rename { from: String, to: String },
+    /// Fold `source` into `target`; target's own templates win on conflict
+    Merge { source: String, target: String },
+}
+
+impl std::fmt::Display for AccountOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountOperation::Rename { from, to } => write!(f, "rename {} -> {}", from, to),
+            AccountOperation::Merge { source, target } => write!(f, "merge {} -> {}", source, target),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountRequest {
+    pub operation: AccountOperation,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccountResponse {
+    pub success: bool,
+    pub user_id: String,            // Resulting user id
+    pub message: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
+}
+
+impl AccountResponse {
+    pub fn success(user_id: String, message: String) -> Self {
+        Self {
+            success: true,
+            user_id,
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn error(user_id: String, message: String) -> Self {
+        Self {
+            success: false,
+            user_id,
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+}
+
+// ==================== DELETE ENDPOINT ====================
+
+/// Remove an enrollment with all its factors
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub ownership_proof: Option<String>,    // Nonce from a challenged verify (hex, see ownership.rs)
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake, without an ownership proof
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteResponse {
+    pub success: bool,
+    pub user_id: String,
+    pub message: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
+}
+
+impl DeleteResponse {
+    pub fn success(user_id: String, message: String) -> Self {
+        Self {
+            success: true,
+            user_id,
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn error(user_id: String, message: String) -> Self {
+        Self {
+            success: false,
+            user_id,
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+}
+
+// ==================== RECEIPT ENDPOINT ====================
+
+/// Ask for a verification receipt (see attestation.rs) after a matching
+/// verify: the proof of its ownership challenge shows the client decrypted
+/// the match, so a receipt is never signed for a result nobody opened
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReceiptRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub ownership_proof: String,            // Nonce from a challenged verify (hex, see ownership.rs)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReceiptResponse {
+    pub success: bool,
+    pub user_id: String,
+    #[serde(default)]
+    pub receipt: Option<String>,            // Server attestation, exchangeable for OIDC tokens
+    pub message: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
+}
+
+impl ReceiptResponse {
+    pub fn success(user_id: String, receipt: String) -> Self {
+        Self {
+            success: true,
+            user_id,
+            receipt: Some(receipt),
+            message: "Receipt issued".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn error(user_id: String, message: String) -> Self {
+        Self {
+            success: false,
+            user_id,
+            receipt: None,
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            error_code: None,
+        }
+    }
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+}
+
+// ==================== REVOKE / ROTATE ENDPOINTS ====================
+
+/// Revoke the cancellable transform of an enrollment: nothing matches until the user registers again
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevokeRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake (see session.rs)
+}
+
+/// Replace the tenant's server key with the one of a new client key. Every
+/// enrollment of the tenant was encrypted under the old key, so none matches
+/// until it is registered again; an enrolled user of the tenant proves the
+/// request with a session, or an operator with the server's admin key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyRotationRequest {
+    pub user_id: String,
+    pub server_key_bytes: Vec<u8>,          // Server key of the new client key
+    #[serde(default)]
+    pub gpu_server_key_bytes: Option<Vec<u8>>, // Compressed one for GPU evaluation (see compute.rs)
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub session: Option<SessionBinding>,    // Proof from the session handshake (see session.rs)
+    #[serde(default)]
+    pub admin_key: Option<String>,          // The server's admin key, instead of a session
+}
+
+// ==================== ADMIN ENDPOINT ====================
+
+/// User id whose exchange slot carries admin requests; no user can have it
+pub const ADMIN_USER_ID: &str = "admin";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminCommand {
+    List,    // Enrolled users
+    Stats,   // Template count and on-disk sizes
+}
+
+impl std::fmt::Display for AdminCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::List => write!(f, "list"),
+            Self::Stats => write!(f, "stats"),
+        }
+    }
+}
+
+/// Operator query; authenticated with the admin key (`server admin key`), not a tenant key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminRequest {
+    pub command: AdminCommand,
+    pub admin_key: String,
+}
+
+/// Enrollment metadata of one user (never template data)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserSummary {
+    pub user_id: String,
+    pub tenant: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub factors: Vec<Factor>,
+    pub template_bits: usize,
+    #[serde(default)]
+    pub enrollment_count: u32,
+    pub stored_bytes: u64,       // Ciphertext, key and IV of all the user's templates
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DatabaseStats {
+    pub version: String,
+    pub backend: String,
+    pub total_users: usize,
+    pub total_templates: usize,  // Including duress and fallback templates
+    pub tenants: usize,
+    pub database_bytes: Option<u64>, // Template database file (None = not on this host)
+    pub blob_bytes: u64,             // Blob files in ../database/blobs
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default)]
+    pub users: Vec<UserSummary>,
+    #[serde(default)]
+    pub stats: Option<DatabaseStats>,
+    pub timestamp: String,
+}
+
+impl AdminResponse {
+    pub fn users(users: Vec<UserSummary>, stats: DatabaseStats) -> Self {
+        Self {
+            success: true,
+            message: format!("{} users", users.len()),
+            users,
+            stats: Some(stats),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn stats(stats: DatabaseStats) -> Self {
+        Self {
+            success: true,
+            message: format!("{} templates", stats.total_templates),
+            users: Vec::new(),
+            stats: Some(stats),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            message,
+            users: Vec::new(),
+            stats: None,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// ==================== JOBS ====================
+
+/// Written to the client's slot as soon as the server queues a verify request
+pub const JOB_TICKET_FILE: &str = "verify_ticket.json";
+
+/// Acknowledgement of a queued verify request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobTicket {
+    pub job_id: String,
+    pub queued_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,      // Waiting for a verify slot
+    Running,
+    Completed,   // Result available (the match itself is encrypted)
+    Failed,      // Result carries the error
+    Cancelled,   // Stopped on the client's cancel request
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Completed | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+impl std::fmt::Display for JobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Completed => "completed",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Progress of a verify job, rewritten to [`job_status_file`] on every change
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub state: JobState,
+    #[serde(default)]
+    pub phase: Option<String>,              // Step a running job is in (deserialize, decrypt_probe, ...)
+    #[serde(default)]
+    pub error: Option<String>,              // Why a failed job failed
+    #[serde(default)]
+    pub result: Option<VerifyResponse>,     // Set once the job has finished
+    pub updated_at: String,
+}
+
+impl JobStatus {
+    pub fn new(job_id: &str, state: JobState) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            state,
+            phase: None,
+            error: None,
+            result: None,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Asks the server to stop a queued or running verify job of the same slot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelRequest {
+    pub job_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CancelResponse {
+    pub job_id: String,
+    pub cancelled: bool,                // False if the job was unknown or had already finished
+    pub timestamp: String,
+}
+
+impl CancelResponse {
+    pub fn new(job_id: String, cancelled: bool) -> Self {
+        Self { job_id, cancelled, timestamp: chrono::Utc::now().to_rfc3339() }
+    }
+}
+
+/// Exchange file holding the status of a job (`verify_status_<job_id>.json`)
+pub fn job_status_file(job_id: &str) -> String {
+    format!("verify_status_{}.json", job_id)
+}
+
+/// Job ids are 32 lowercase hex digits, safe to embed in file names
+pub fn valid_job_id(job_id: &str) -> bool {
+    job_id.len() == 32 && job_id.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+// ==================== SERVER STATUS ====================
+
+/// Exchange file the server publishes its `ServerStatus` to
+pub const SERVER_STATUS_FILE: &str = "server_status.json";
+
+/// Published by the server to `server_status.json`: job queues and calibration
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ServerStatus {
+    pub verify: JobCounts,
+    pub register: JobCounts,
+    pub max_concurrent_verify: usize,
+    pub max_concurrent_register: usize,
+    #[serde(default)]
+    pub supported_template_bits: Vec<usize>, // Template lengths accepted at registration
+    #[serde(default)]
+    pub supported_ciphers: Vec<Cipher>,     // Transciphering ciphers accepted (empty = Trivium, Kreyvium)
+    #[serde(default)]
+    pub calibration: Calibration,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct JobCounts {
+    pub running: usize,
+    pub queued: usize,
+}
+
+/// Observed job durations on this server (moving average)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Calibration {
+    #[serde(default)]
+    pub template_bits: Option<usize>,
+    #[serde(default)]
+    pub verify_job_secs: Option<f64>,   // Per template matched: rotated variants, the duress finger and fused fingers add to a job
+    #[serde(default)]
+    pub register_job_secs: Option<f64>,
+    #[serde(default)]
+    pub samples: u64,               // Completed verify jobs the average is based on
+}
+
+impl ServerStatus {
+    /// Expected wait before a new verify job starts, in units of one job duration
+    pub fn verify_queue_jobs(&self) -> f64 {
+        let max = self.max_concurrent_verify.max(1);
+        let ahead = self.verify.running + self.verify.queued;
+        if ahead < max {
+            return 0.0;
+        }
+        // Slots free up in waves of `max` jobs
+        ((ahead - max) / max + 1) as f64
+    }
+}
+
 // ==================== LEGACY (BACKWARD COMPATIBILITY) ====================
 
 #[derive(Serialize, Deserialize, Debug)]
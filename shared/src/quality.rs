@@ -0,0 +1,148 @@
+// shared/src/quality.rs
+
+//! Quality report of an enrolled template.
+//!
+//! Computed by the client from the plaintext template and the coverage of
+//! its extractor regions, right after extraction, and returned with the
+//! `RegisterResponse`. A weak enrollment (blank regions, saturated or empty
+//! histograms) can then be re-captured immediately instead of surfacing
+//! later as failed verifications.
+//!
+//! Extraction also flags each template bit whose histogram bin sat at the
+//! cut between selected and unselected patterns; such bits flip between
+//! captures. With the blank regions they make up the per-bit quality mask
+//! (`QualityMask` in protocol.rs) that verifications leave out of the
+//! distance, in the clear or FHE-encrypted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::template::BITS_PER_REGION;
+
+/// Below these a re-capture is recommended
+const MIN_VALID_REGION_PERCENT: usize = 75;
+const MIN_DISTINCTIVENESS: f32 = 0.5;
+const BALANCE_RANGE: (f32, f32) = (0.2, 0.8);
+
+/// Quality masks must keep at least this share of the template bits (percent)
+pub const MIN_QUALITY_MASK_PERCENT: usize = 25;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub template_bits: usize,
+    pub bit_balance: f32,               // Share of set bits (0.5 = balanced)
+    pub distinctiveness: f32,           // Mean binary entropy of the valid regions, 0..1
+    pub valid_regions: usize,           // Regions holding ridge texture
+    pub total_regions: usize,
+    pub recapture_recommended: bool,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+impl QualityReport {
+    /// Assess template bits with one coverage flag per region
+    pub fn assess(bits: &[bool], coverage: &[bool]) -> Self {
+        let ones = bits.iter().filter(|&&b| b).count();
+        let bit_balance = ones as f32 / bits.len().max(1) as f32;
+
+        let valid: Vec<&[bool]> = bits
+            .chunks(BITS_PER_REGION)
+            .zip(coverage)
+            .filter(|(_, &covered)| covered)
+            .map(|(region, _)| region)
+            .collect();
+        let distinctiveness = if valid.is_empty() {
+            0.0
+        } else {
+            valid.iter().map(|region| entropy(region)).sum::<f32>() / valid.len() as f32
+        };
+
+        let mut warnings = Vec::new();
+        if valid.len() * 100 < coverage.len() * MIN_VALID_REGION_PERCENT {
+            warnings.push(format!("Only {} of {} regions hold ridge texture", valid.len(), coverage.len()));
+        }
+        if bit_balance < BALANCE_RANGE.0 || bit_balance > BALANCE_RANGE.1 {
+            warnings.push(format!("Unbalanced template: {:.0}% of bits set", bit_balance * 100.0));
+        }
+        if distinctiveness < MIN_DISTINCTIVENESS {
+            warnings.push(format!("Low distinctiveness ({:.2})", distinctiveness));
+        }
+
+        Self {
+            template_bits: bits.len(),
+            bit_balance,
+            distinctiveness,
+            valid_regions: valid.len(),
+            total_regions: coverage.len(),
+            recapture_recommended: !warnings.is_empty(),
+            warnings,
+        }
+    }
+}
+
+/// Per-bit quality mask: the stable bits of the covered regions
+pub fn quality_mask(stable: &[bool], coverage: &[bool]) -> Vec<bool> {
+    stable
+        .chunks(BITS_PER_REGION)
+        .zip(coverage)
+        .flat_map(|(region, &covered)| region.iter().map(move |&s| s && covered))
+        .collect()
+}
+
+/// Check a plaintext quality mask against the template length; returns the bits it keeps
+pub fn check_quality_mask(mask: &[bool], bits: usize) -> Result<usize, String> {
+    if mask.len() != bits {
+        return Err(format!("Quality mask has {} bits, the template {}", mask.len(), bits));
+    }
+    let kept = mask.iter().filter(|&&k| k).count();
+    if kept * 100 < bits * MIN_QUALITY_MASK_PERCENT {
+        return Err(format!(
+            "Quality mask keeps {} of {} bits, at least {}% are needed; recapture the finger",
+            kept, bits, MIN_QUALITY_MASK_PERCENT
+        ));
+    }
+    Ok(kept)
+}
+
+/// Binary entropy of the share of set bits
+fn entropy(bits: &[bool]) -> f32 {
+    let p = bits.iter().filter(|&&b| b).count() as f32 / bits.len().max(1) as f32;
+    if p == 0.0 || p == 1.0 {
+        return 0.0;
+    }
+    -(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_or_saturated_captures_ask_for_recapture() {
+        let good: Vec<bool> = (0..1024).map(|i| (i * 7 / 3) % 2 == 0).collect();
+        let report = QualityReport::assess(&good, &[true; 64]);
+        assert!(!report.recapture_recommended, "{:?}", report.warnings);
+        assert_eq!(report.valid_regions, 64);
+
+        let mut coverage = [true; 64];
+        coverage[..32].iter_mut().for_each(|c| *c = false);
+        assert!(QualityReport::assess(&good, &coverage).recapture_recommended);
+
+        let saturated = QualityReport::assess(&[true; 1024], &[true; 64]);
+        assert!(saturated.recapture_recommended);
+        assert_eq!(saturated.distinctiveness, 0.0);
+    }
+
+    #[test]
+    fn quality_mask_drops_blank_regions_and_unstable_bits() {
+        let mut stable = vec![true; 4 * BITS_PER_REGION];
+        stable[3] = false;
+        let mask = quality_mask(&stable, &[true, false, true, true]);
+        assert_eq!(mask.len(), stable.len());
+        assert_eq!(mask.iter().filter(|&&k| k).count(), 3 * BITS_PER_REGION - 1);
+        assert!(!mask[3] && !mask[BITS_PER_REGION]);
+
+        assert_eq!(check_quality_mask(&mask, mask.len()), Ok(3 * BITS_PER_REGION - 1));
+        assert!(check_quality_mask(&mask, 1024).is_err());
+        assert!(check_quality_mask(&quality_mask(&stable, &[true, false, false, false]), stable.len()).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+// shared/src/sealed.rs
+
+//! Encrypted exchange files.
+//!
+//! The server publishes an X25519 public key in `exchange_key.json` at the
+//! exchange root (the handshake file). Each request is sealed to it with a
+//! fresh ephemeral key: X25519 → HKDF-SHA256 → AES-256-GCM. The request
+//! header carries the client's own public key, and the server seals its
+//! response back to that key the same way, so other users of a shared
+//! (NFS) directory only ever see ciphertext.
+//!
+//! Envelope: `"FPX1" | recipient key id (8) | ephemeral public (32) |
+//! reply-to public (32, zero if none) | nonce (12) | AES-GCM ciphertext`.
+//! The header is authenticated as associated data.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::transport::Transport;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+/// Handshake file published by the server at the exchange root
+pub const HANDSHAKE_FILE: &str = "exchange_key.json";
+
+const MAGIC: &[u8; 4] = b"FPX1";
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 12;
+/// Bytes before the ciphertext
+pub const HEADER_LEN: usize = 4 + KEY_ID_LEN + 32 + 32 + NONCE_LEN;
+const HKDF_INFO: &[u8] = b"fingerprint-fhe exchange v1";
+
+/// Contents of the handshake file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    pub public_key: String,   // base64 X25519 public key
+    pub key_id: String,       // hex, first 8 bytes of SHA-256(public key)
+    pub created_at: String,
+    #[serde(default)]
+    pub identity_key: Option<String>, // base64 Ed25519 key results are signed with (see identity.rs)
+    #[serde(default)]
+    pub identity_signature: Option<String>, // Identity key's signature over the exchange key
+}
+
+impl Handshake {
+    pub fn with_identity(mut self, identity_public: &[u8; 32]) -> Self {
+        self.identity_key = Some(STANDARD.encode(identity_public));
+        self
+    }
+
+    pub fn identity_key(&self) -> Result<Option<[u8; 32]>, String> {
+        let Some(encoded) = &self.identity_key else { return Ok(None) };
+        let bytes = STANDARD.decode(encoded).map_err(|e| format!("Invalid identity key: {}", e))?;
+        let key: [u8; 32] = bytes.try_into().map_err(|_| "Identity key must be 32 bytes".to_string())?;
+        Ok(Some(key))
+    }
+
+    pub fn public_key(&self) -> Result<[u8; 32], String> {
+        let bytes = STANDARD.decode(&self.public_key).map_err(|e| format!("Invalid handshake key: {}", e))?;
+        bytes.try_into().map_err(|_| "Handshake key must be 32 bytes".to_string())
+    }
+}
+
+/// Long-lived X25519 key of one side of the exchange
+pub struct ExchangeKey {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl ExchangeKey {
+    pub fn generate() -> Self {
+        let secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let public = PublicKey::from(&secret);
+        Self { secret, public }
+    }
+
+    /// Load the key at `path`, creating it (mode 0600) on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Ok(bytes) = fs::read(path) {
+            let bytes: [u8; 32] = bytes.try_into().map_err(|_| format!("{} must hold 32 bytes", path.display()))?;
+            let secret = StaticSecret::from(bytes);
+            let public = PublicKey::from(&secret);
+            return Ok(Self { secret, public });
+        }
+        let key = Self::generate();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_secret(path, &key.secret.to_bytes())?;
+        Ok(key)
+    }
+
+    pub fn public_bytes(&self) -> [u8; 32] {
+        self.public.to_bytes()
+    }
+
+    pub fn handshake(&self) -> Handshake {
+        Handshake {
+            public_key: STANDARD.encode(self.public_bytes()),
+            key_id: hex(&key_id(&self.public_bytes())),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            identity_key: None,
+            identity_signature: None,
+        }
+    }
+}
+
+/// An opened envelope
+pub struct Opened {
+    pub plaintext: Vec<u8>,
+    pub reply_to: Option<[u8; 32]>,
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Reply-to key from an envelope header (the first `HEADER_LEN` bytes suffice)
+pub fn reply_to(header: &[u8]) -> Option<[u8; 32]> {
+    if !is_sealed(header) || header.len() < HEADER_LEN {
+        return None;
+    }
+    let start = 4 + KEY_ID_LEN + 32;
+    let key: [u8; 32] = header[start..start + 32].try_into().ok()?;
+    (key != [0u8; 32]).then_some(key)
+}
+
+/// Encrypt `plaintext` so only the holder of `recipient`'s secret can read it
+pub fn seal(recipient: &[u8; 32], plaintext: &[u8], reply_to: Option<&[u8; 32]>) -> Result<Vec<u8>, String> {
+    let recipient = PublicKey::from(*recipient);
+    let ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&recipient);
+    let cipher = cipher_for(shared.as_bytes(), &ephemeral_public, &recipient)?;
+    
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    
+    let mut envelope = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    envelope.extend_from_slice(MAGIC);
+    envelope.extend_from_slice(&key_id(recipient.as_bytes()));
+    envelope.extend_from_slice(ephemeral_public.as_bytes());
+    envelope.extend_from_slice(reply_to.unwrap_or(&[0u8; 32]));
+    envelope.extend_from_slice(&nonce);
+    
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &envelope })
+        .map_err(|_| "Exchange encryption failed".to_string())?;
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Decrypt an envelope sealed to `key`
+pub fn open(key: &ExchangeKey, envelope: &[u8]) -> Result<Opened, String> {
+    if !is_sealed(envelope) || envelope.len() < HEADER_LEN {
+        return Err("Not an encrypted exchange file".to_string());
+    }
+    let (header, ciphertext) = envelope.split_at(HEADER_LEN);
+    if header[4..4 + KEY_ID_LEN] != key_id(&key.public_bytes()) {
+        return Err("Exchange file was encrypted for a different key (stale handshake?)".to_string());
+    }
+    let ephemeral_public: [u8; 32] = header[4 + KEY_ID_LEN..4 + KEY_ID_LEN + 32].try_into().expect("header length checked");
+    let ephemeral_public = PublicKey::from(ephemeral_public);
+    let shared = key.secret.diffie_hellman(&ephemeral_public);
+    let cipher = cipher_for(shared.as_bytes(), &ephemeral_public, &key.public)?;
+    
+    let nonce = &header[HEADER_LEN - NONCE_LEN..];
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| "Exchange file failed authentication".to_string())?;
+    Ok(Opened { plaintext, reply_to: reply_to(header) })
+}
+
+/// Client side of the exchange: files put are sealed to the server, sealed files read are opened
+pub struct SealedTransport {
+    inner: Box<dyn Transport>,
+    server: [u8; 32],
+    own: Arc<ExchangeKey>,
+}
+
+impl SealedTransport {
+    pub fn new(inner: Box<dyn Transport>, server: [u8; 32], own: Arc<ExchangeKey>) -> Self {
+        Self { inner, server, own }
+    }
+}
+
+impl Transport for SealedTransport {
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let envelope = seal(&self.server, data, Some(&self.own.public_bytes()))?;
+        self.inner.put(name, &envelope)
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match self.inner.get(name)? {
+            Some(data) if is_sealed(&data) => Ok(Some(open(&self.own, &data)?.plaintext)),
+            other => Ok(other),
+        }
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.delete(name)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.inner.list()
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.inner.exists(name)
+    }
+
+    fn modified(&self, name: &str) -> Result<Option<std::time::SystemTime>, Box<dyn std::error::Error>> {
+        self.inner.modified(name)
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.inner.poll_interval()
+    }
+
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    fn scoped(&self, slot: &str) -> Box<dyn Transport> {
+        Box::new(SealedTransport::new(self.inner.scoped(slot), self.server, self.own.clone()))
+    }
+
+    fn user_slots(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.inner.user_slots()
+    }
+
+    fn user_area(&self, api_key: Option<&str>, user_id: &str) -> Box<dyn Transport> {
+        Box::new(SealedTransport::new(self.inner.user_area(api_key, user_id), self.server, self.own.clone()))
+    }
+
+    fn is_private(&self) -> bool {
+        self.inner.is_private()
+    }
+}
+
+fn cipher_for(shared: &[u8; 32], ephemeral: &PublicKey, recipient: &PublicKey) -> Result<Aes256Gcm, String> {
+    if shared == &[0u8; 32] {
+        return Err("Invalid exchange public key".to_string());
+    }
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral.as_bytes());
+    salt[32..].copy_from_slice(recipient.as_bytes());
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(HKDF_INFO, &mut key)
+        .map_err(|_| "HKDF expand failed".to_string())?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+pub(crate) fn key_id(public: &[u8; 32]) -> [u8; KEY_ID_LEN] {
+    let digest = Sha256::digest(public);
+    digest[..KEY_ID_LEN].try_into().expect("digest is longer than a key id")
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Create a file only the owner can read (fails if it already exists)
+#[cfg(unix)]
+pub fn write_secret(path: &Path, secret: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    file.write_all(secret)
+}
+
+#[cfg(not(unix))]
+pub fn write_secret(path: &Path, secret: &[u8]) -> std::io::Result<()> {
+    fs::write(path, secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_and_reply_round_trip() {
+        let server = ExchangeKey::generate();
+        let client = ExchangeKey::generate();
+        
+        let request = seal(&server.public_bytes(), b"{\"user_id\":\"alice\"}", Some(&client.public_bytes())).unwrap();
+        assert!(!request.windows(5).any(|w| w == b"alice"));
+        let opened = open(&server, &request).unwrap();
+        assert_eq!(opened.plaintext, b"{\"user_id\":\"alice\"}");
+        assert_eq!(opened.reply_to, Some(client.public_bytes()));
+        
+        let response = seal(&opened.reply_to.unwrap(), b"ok", None).unwrap();
+        assert_eq!(open(&client, &response).unwrap().plaintext, b"ok");
+        assert!(open(&server, &response).is_err());
+    }
+
+    #[test]
+    fn tampered_envelope_is_rejected() {
+        let server = ExchangeKey::generate();
+        let mut envelope = seal(&server.public_bytes(), b"payload", None).unwrap();
+        let last = envelope.len() - 1;
+        envelope[last] ^= 1;
+        assert!(open(&server, &envelope).is_err());
+    }
+}
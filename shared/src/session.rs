@@ -0,0 +1,260 @@
+// shared/src/session.rs
+
+//! Mutual authentication before register/verify.
+//!
+//! 1. The client writes `session_request.json` with a fresh nonce.
+//! 2. The server answers with its own nonce, a session id, a challenge and
+//!    the session's expiry, and signs the transcript digest with its
+//!    identity key (see identity.rs).
+//! 3. The client checks that signature against the pinned identity, signs
+//!    the same digest with its enrollment credential and attaches both, with
+//!    the challenge, as a `SessionBinding`, to the register/verify request
+//!    that follows.
+//!
+//! The digest doubles as the session binding token both sides derive. The
+//! server registers the credential's public key at enrollment and only
+//! accepts a binding for a session it issued, for the same user, once.
+//!
+//! A verify result is signed together with its session's `SessionClaim`
+//! (id, challenge, expiry): it can't be passed off as the answer to another
+//! session, and clients refuse it once the session has expired.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::sealed::{hex, write_secret};
+
+const SESSION_CONTEXT: &[u8] = b"fingerprint-fhe session v2";
+
+/// How long an issued session can be used
+pub const SESSION_LIFETIME_SECS: u64 = 300;
+
+/// Default time from issue until a session, and any result signed for it,
+/// expires. Covers the FHE matching (the client waits up to two hours).
+pub const SESSION_EXPIRY_SECS: u64 = 7200;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionRequest {
+    pub user_id: String,
+    pub client_nonce: String,           // base64, 32 random bytes
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionResponse {
+    pub success: bool,
+    pub session_id: String,
+    pub server_nonce: String,           // base64, 32 random bytes
+    #[serde(default)]
+    pub challenge: String,              // base64, 32 random bytes; echoed in the request and the signed result
+    #[serde(default)]
+    pub expires_at: String,             // RFC 3339
+    pub signature: String,              // Server identity over the transcript digest
+    pub message: String,
+    pub timestamp: String,
+}
+
+/// Proof attached to the request that follows a handshake
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionBinding {
+    pub session_id: String,
+    pub token: String,                  // hex transcript digest, derived by both sides
+    pub proof: String,                  // Client credential's signature over the digest
+    #[serde(default)]
+    pub challenge: String,              // The session's challenge
+    #[serde(default)]
+    pub expires_at: String,             // The session's expiry, RFC 3339
+}
+
+/// Session a verify result was produced in, covered by the result signature
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionClaim {
+    pub session_id: String,
+    pub challenge: String,
+    pub expires_at: String,             // RFC 3339
+}
+
+impl SessionClaim {
+    /// Whether the session has expired (an unreadable expiry counts as expired)
+    pub fn is_expired(&self) -> bool {
+        chrono::DateTime::parse_from_rfc3339(&self.expires_at)
+            .map(|t| t <= chrono::Utc::now())
+            .unwrap_or(true)
+    }
+}
+
+impl SessionBinding {
+    /// Claim a result for this session must carry
+    pub fn claim(&self) -> SessionClaim {
+        SessionClaim {
+            session_id: self.session_id.clone(),
+            challenge: self.challenge.clone(),
+            expires_at: self.expires_at.clone(),
+        }
+    }
+}
+
+impl SessionRequest {
+    pub fn new(user_id: &str, api_key: Option<String>) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            client_nonce: new_nonce(),
+            api_key,
+        }
+    }
+}
+
+impl SessionResponse {
+    pub fn success(claim: SessionClaim, server_nonce: String, signature: String) -> Self {
+        Self {
+            success: true,
+            session_id: claim.session_id,
+            server_nonce,
+            challenge: claim.challenge,
+            expires_at: claim.expires_at,
+            signature,
+            message: "Session established".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            session_id: String::new(),
+            server_nonce: String::new(),
+            challenge: String::new(),
+            expires_at: String::new(),
+            signature: String::new(),
+            message,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+pub fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Fresh base64 nonce for one side of the handshake
+pub fn new_nonce() -> String {
+    STANDARD.encode(random_bytes())
+}
+
+pub fn new_session_id() -> String {
+    hex(&random_bytes()[..16])
+}
+
+/// Digest both sides sign: user, both nonces and the session (id, challenge, expiry)
+pub fn transcript_digest(user_id: &str, client_nonce: &str, server_nonce: &str, session: &SessionClaim) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(SESSION_CONTEXT);
+    for field in [user_id, client_nonce, server_nonce, &session.session_id, &session.challenge, &session.expires_at] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+pub fn token(digest: &[u8; 32]) -> String {
+    hex(digest)
+}
+
+/// Check an Ed25519 signature (base64) over a digest
+pub fn verify_signature(public_key: &[u8; 32], digest: &[u8; 32], signature: &str) -> Result<(), String> {
+    let signature = STANDARD.decode(signature).map_err(|e| format!("Invalid signature encoding: {}", e))?;
+    let signature = Signature::from_slice(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+    VerifyingKey::from_bytes(public_key)
+        .map_err(|e| format!("Invalid public key: {}", e))?
+        .verify(digest, &signature)
+        .map_err(|_| "Signature mismatch".to_string())
+}
+
+pub fn decode_public_key(encoded: &str) -> Result<[u8; 32], String> {
+    let bytes = STANDARD.decode(encoded).map_err(|e| format!("Invalid key encoding: {}", e))?;
+    bytes.try_into().map_err(|_| "Public key must be 32 bytes".to_string())
+}
+
+/// Enrollment credential of a client (Ed25519), registered with the server at enrollment
+pub struct ClientCredential {
+    signing: SigningKey,
+}
+
+impl ClientCredential {
+    /// Load the credential at `path`, creating it (mode 0600) on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Ok(bytes) = fs::read(path) {
+            let seed: [u8; 32] = bytes.try_into().map_err(|_| format!("{} must hold 32 bytes", path.display()))?;
+            return Ok(Self { signing: SigningKey::from_bytes(&seed) });
+        }
+        let credential = Self { signing: SigningKey::from_bytes(&random_bytes()) };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_secret(path, &credential.signing.to_bytes())?;
+        Ok(credential)
+    }
+
+    /// base64 public key, as sent at enrollment
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.signing.verifying_key().to_bytes())
+    }
+
+    /// Secret derived from the credential for another purpose (e.g. soft attribute blinding)
+    pub fn derive_key(&self, context: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(context);
+        hasher.update(self.signing.to_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Bind a request to a verified session
+    pub fn bind(&self, session: &SessionClaim, digest: &[u8; 32]) -> SessionBinding {
+        SessionBinding {
+            session_id: session.session_id.clone(),
+            token: token(digest),
+            proof: STANDARD.encode(self.signing.sign(digest).to_bytes()),
+            challenge: session.challenge.clone(),
+            expires_at: session.expires_at.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_proves_credential_possession() {
+        let credential = ClientCredential { signing: SigningKey::from_bytes(&random_bytes()) };
+        let session = SessionClaim {
+            session_id: "sid".to_string(),
+            challenge: "ch".to_string(),
+            expires_at: (chrono::Utc::now() + chrono::Duration::seconds(300)).to_rfc3339(),
+        };
+        let digest = transcript_digest("alice", "cn", "sn", &session);
+        let binding = credential.bind(&session, &digest);
+
+        let public = decode_public_key(&credential.public_key()).unwrap();
+        assert!(verify_signature(&public, &digest, &binding.proof).is_ok());
+        assert_eq!(binding.token, token(&digest));
+
+        // Same proof for another user's transcript
+        let other = transcript_digest("bob", "cn", "sn", &session);
+        assert!(verify_signature(&public, &other, &binding.proof).is_err());
+
+        assert_eq!(binding.claim(), session);
+        assert!(!session.is_expired());
+        let expired = (chrono::Utc::now() - chrono::Duration::seconds(1)).to_rfc3339();
+        assert!(SessionClaim { expires_at: expired, ..session }.is_expired());
+    }
+}
@@ -0,0 +1,179 @@
+// shared/src/soft.rs
+
+//! Soft-biometric consistency checks.
+//!
+//! A template can be enrolled with optional soft attributes (hand, finger
+//! position, pattern class). A probe that declares attributes contradicting
+//! the enrolled ones (left thumb against right index) is rejected before the
+//! FHE matcher runs, which takes seconds instead of most of an hour.
+//!
+//! Attributes travel either in clear or blinded: as keyed SHA-256 tags the
+//! server can compare for equality without learning the values. Only
+//! attributes present on both sides, in the same mode, are compared.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::sealed::hex;
+
+const BLINDING_CONTEXT: &[u8] = b"fingerprint-fhe soft attributes v1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerPosition {
+    Thumb,
+    Index,
+    Middle,
+    Ring,
+    Little,
+}
+
+/// Henry class of the ridge pattern
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternClass {
+    Arch,
+    Loop,
+    Whorl,
+}
+
+/// Attributes the client declares for a capture
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SoftAttributes {
+    #[serde(default)]
+    pub hand: Option<Hand>,
+    #[serde(default)]
+    pub finger: Option<FingerPosition>,
+    #[serde(default)]
+    pub pattern: Option<PatternClass>,
+    #[serde(default)]
+    pub blind: bool,                    // Send keyed tags instead of the values
+}
+
+/// Attributes as sent to and stored by the server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SoftProfile {
+    #[serde(default)]
+    pub blinded: bool,                  // Values are keyed tags (hex), not attribute names
+    #[serde(default)]
+    pub hand: Option<String>,
+    #[serde(default)]
+    pub finger: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+impl SoftAttributes {
+    pub fn is_empty(&self) -> bool {
+        self.hand.is_none() && self.finger.is_none() && self.pattern.is_none()
+    }
+
+    /// Profile for the server; `blinding_key` is only used when `blind` is set
+    pub fn profile(&self, blinding_key: &[u8; 32]) -> SoftProfile {
+        let encode = |name: &str, value: Option<String>| {
+            value.map(|v| if self.blind { blind_tag(blinding_key, name, &v) } else { v })
+        };
+        SoftProfile {
+            blinded: self.blind,
+            hand: encode("hand", self.hand.map(|h| name_of(&h))),
+            finger: encode("finger", self.finger.map(|f| name_of(&f))),
+            pattern: encode("pattern", self.pattern.map(|p| name_of(&p))),
+        }
+    }
+
+    /// Parse `--hand`, `--finger`, `--pattern` and `--blind-soft`; `None` if none was given
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let attributes = Self {
+            hand: flag_value(args, "--hand")?,
+            finger: flag_value(args, "--finger")?,
+            pattern: flag_value(args, "--pattern")?,
+            blind: args.iter().any(|a| a == "--blind-soft"),
+        };
+        Ok((!attributes.is_empty()).then_some(attributes))
+    }
+}
+
+fn flag_value<T: DeserializeOwned>(args: &[String], flag: &str) -> Result<Option<T>, String> {
+    let Some(value) = args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)) else {
+        return Ok(None);
+    };
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map(Some)
+        .map_err(|_| format!("Invalid {} value '{}'", flag, value))
+}
+
+fn name_of<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Keyed tag of one attribute value
+pub fn blind_tag(key: &[u8; 32], attribute: &str, value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(BLINDING_CONTEXT);
+    hasher.update(key);
+    for field in [attribute, value] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hex(&hasher.finalize())
+}
+
+/// First attribute on which `probe` contradicts `enrolled`
+pub fn conflict(enrolled: &SoftProfile, probe: &SoftProfile) -> Option<&'static str> {
+    if enrolled.blinded != probe.blinded {
+        return None;
+    }
+    [
+        ("hand", &enrolled.hand, &probe.hand),
+        ("finger", &enrolled.finger, &probe.finger),
+        ("pattern", &enrolled.pattern, &probe.pattern),
+    ]
+    .into_iter()
+    .find(|(_, a, b)| matches!((a, b), (Some(a), Some(b)) if a != b))
+    .map(|(name, _, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn contradicting_attributes_conflict_in_both_modes() {
+        let key = [7u8; 32];
+        let left_thumb = SoftAttributes::from_args(&args(&["--hand", "left", "--finger", "thumb"])).unwrap().unwrap();
+        let right_index = SoftAttributes::from_args(&args(&["--hand", "Right", "--finger", "index"])).unwrap().unwrap();
+        let left_only = SoftAttributes { hand: Some(Hand::Left), ..SoftAttributes::default() };
+
+        assert_eq!(conflict(&left_thumb.profile(&key), &right_index.profile(&key)), Some("hand"));
+        assert_eq!(conflict(&left_thumb.profile(&key), &left_only.profile(&key)), None);
+
+        let blind = |a: &SoftAttributes| SoftAttributes { blind: true, ..a.clone() }.profile(&key);
+        assert_eq!(conflict(&blind(&left_thumb), &blind(&right_index)), Some("hand"));
+        assert_eq!(conflict(&blind(&left_thumb), &blind(&left_only)), None);
+        assert_ne!(blind(&left_thumb).hand, Some("left".to_string()));
+
+        // Clear and blinded profiles cannot be compared
+        assert_eq!(conflict(&left_thumb.profile(&key), &blind(&right_index)), None);
+    }
+
+    #[test]
+    fn rejects_unknown_attribute_values() {
+        assert!(SoftAttributes::from_args(&args(&["--finger", "pinky"])).is_err());
+        assert_eq!(SoftAttributes::from_args(&args(&["--blind-soft"])), Ok(None));
+    }
+}
@@ -0,0 +1,241 @@
+// shared/src/telemetry.rs
+//! Opt-in, anonymous performance telemetry.
+//!
+//! When enabled in `telemetry.json` (client: `~/.fingerprint_client/`,
+//! server: `../database/`), each register/verify run appends one record of
+//! phase timings to a local JSONL file and/or posts it to an endpoint. The
+//! records are meant for a cross-machine performance corpus, so they carry
+//! only what explains a timing: parameter set, template length, per-phase
+//! durations and a coarse hardware summary. No user ids, tenants, hostnames,
+//! ciphertexts or time of day are ever included.
+
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Bumped when fields change meaning
+pub const SCHEMA_VERSION: u32 = 1;
+
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,                  // Off unless explicitly turned on
+    #[serde(default)]
+    pub path: Option<PathBuf>,          // Local JSONL file (default: next to the config)
+    #[serde(default)]
+    pub endpoint: Option<String>,       // HTTP(S) URL records are POSTed to as JSON
+}
+
+impl TelemetryConfig {
+    /// Missing file = disabled; an unreadable file also disables telemetry
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+        match fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("⚠️  Invalid telemetry config ({}), telemetry disabled", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Phase {
+    pub name: String,
+    pub ms: u64,
+}
+
+/// Coarse description of the machine a run was measured on
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hardware {
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    #[serde(default)]
+    pub memory_gb: Option<u64>,         // Rounded to whole GiB
+}
+
+impl Hardware {
+    pub fn detect() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            memory_gb: fs::read_to_string("/proc/meminfo").ok().and_then(|m| memory_gb(&m)),
+        }
+    }
+}
+
+/// Total memory from `/proc/meminfo`, rounded to GiB
+fn memory_gb(meminfo: &str) -> Option<u64> {
+    let kb: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some((kb + 512 * 1024) / (1024 * 1024))
+}
+
+/// Lap timer: each `lap` closes the phase that started at the previous lap
+pub struct PhaseTimer {
+    last: Instant,
+    phases: Vec<Phase>,
+}
+
+impl PhaseTimer {
+    pub fn start() -> Self {
+        Self { last: Instant::now(), phases: Vec::new() }
+    }
+
+    pub fn lap(&mut self, name: &str) {
+        let now = Instant::now();
+        self.phases.push(Phase {
+            name: name.to_string(),
+            ms: now.duration_since(self.last).as_millis() as u64,
+        });
+        self.last = now;
+    }
+
+    /// Restart the clock without recording a phase, for work whose duration
+    /// would reveal something about the user
+    pub fn skip(&mut self) {
+        self.last = Instant::now();
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TelemetryRecord {
+    pub schema: u32,
+    pub component: String,              // "client" / "server"
+    pub operation: String,              // "register" / "verify"
+    pub recorded_on: String,            // Date only (UTC)
+    #[serde(default)]
+    pub parameter_set: Option<String>,
+    pub template_bits: usize,
+    #[serde(default)]
+    pub fhe_bool_bytes: Option<usize>,  // Serialized size of one ciphertext bit
+    pub success: bool,
+    pub phases: Vec<Phase>,
+    pub hardware: Hardware,
+}
+
+impl TelemetryRecord {
+    pub fn new(component: &str, operation: &str, template_bits: usize, timer: &PhaseTimer) -> Self {
+        Self {
+            schema: SCHEMA_VERSION,
+            component: component.to_string(),
+            operation: operation.to_string(),
+            recorded_on: chrono::Utc::now().format("%Y-%m-%d").to_string(),
+            parameter_set: None,
+            template_bits,
+            fhe_bool_bytes: None,
+            success: true,
+            phases: timer.phases().to_vec(),
+            hardware: Hardware::detect(),
+        }
+    }
+
+    pub fn with_parameter_set(mut self, params: impl std::fmt::Display) -> Self {
+        self.parameter_set = Some(params.to_string());
+        self
+    }
+
+    pub fn with_fhe_bool_bytes(mut self, bytes: usize) -> Self {
+        self.fhe_bool_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_success(mut self, success: bool) -> Self {
+        self.success = success;
+        self
+    }
+}
+
+/// Store a record where the config says; `default_path` is used when no
+/// endpoint and no path are configured. Failures only print a warning.
+pub fn submit(config: &TelemetryConfig, default_path: &Path, record: &TelemetryRecord) {
+    if !config.enabled {
+        return;
+    }
+    let line = match serde_json::to_string(record) {
+        Ok(line) => line,
+        Err(e) => {
+            eprintln!("⚠️  Telemetry record not written: {}", e);
+            return;
+        }
+    };
+
+    if let Some(endpoint) = &config.endpoint {
+        let sent = ureq::post(endpoint)
+            .timeout(ENDPOINT_TIMEOUT)
+            .set("Content-Type", "application/json")
+            .send_string(&line);
+        if let Err(e) = sent {
+            eprintln!("⚠️  Telemetry endpoint unreachable: {}", e);
+        }
+    }
+
+    if config.path.is_some() || config.endpoint.is_none() {
+        let path = config.path.as_deref().unwrap_or(default_path);
+        if let Err(e) = append_line(path, &line) {
+            eprintln!("⚠️  Telemetry record not written to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn append_line(path: &Path, line: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_is_rounded_to_gib() {
+        let meminfo = "MemTotal:       16318480 kB\nMemFree:         1234 kB\n";
+        assert_eq!(memory_gb(meminfo), Some(16));
+        assert_eq!(memory_gb("MemFree: 1 kB"), None);
+    }
+
+    #[test]
+    fn laps_are_recorded_in_order() {
+        let mut timer = PhaseTimer::start();
+        timer.lap("features");
+        timer.lap("encrypt");
+        let names: Vec<&str> = timer.phases().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["features", "encrypt"]);
+    }
+
+    #[test]
+    fn skipped_work_is_not_recorded() {
+        let mut timer = PhaseTimer::start();
+        timer.lap("match");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        timer.skip();
+        timer.lap("serialize");
+        assert_eq!(timer.phases().len(), 2);
+        assert!(timer.phases()[1].ms < 20);
+    }
+}
@@ -0,0 +1,455 @@
+// shared/src/template.rs
+
+//! Template length and everything derived from it.
+//!
+//! The length is chosen by the client at registration (negotiated against
+//! the sizes the server advertises in `server_status.json`), carried in every
+//! register/verify request and stored with the enrollment. The extractor
+//! grid, popcount counter width, match threshold and the similarity shown to
+//! the user are all computed from it, and both sides reject requests and
+//! results whose sizes disagree.
+//!
+//! A partial probe (small-area sensor, partial touch) carries a coverage mask
+//! with one flag per extractor region. The server then compares only the
+//! covered regions and scales the threshold to the bits compared.
+//!
+//! An enrollment may also carry one small weight per bit, exported by the
+//! extractor (edge patterns tell fingers apart better than flat ones). The
+//! distance then sums the weights of the differing bits, and the threshold
+//! and counter width follow the total weight of the bits compared.
+//!
+//! For rotation tolerance the client can enroll templates of the primary
+//! capture rotated by a few degrees either way, each under its own key/IV.
+//! The server matches the probe against all of them and keeps the smallest
+//! encrypted distance.
+//!
+//! `TemplateParams` bundles the length with the extractor, its region grid,
+//! the quantization that turns each region into bits and whether captures
+//! were centred on their core point first. Requests carry them and the
+//! enrollment stores them, so a probe from another extractor or alignment is
+//! refused instead of just failing to match; entries from before the
+//! parameters existed are read as unaligned LBP templates of their stored
+//! length.
+//!
+//! The match threshold defaults to `MATCH_SIMILARITY_PERCENT` of the length.
+//! A `TunedThreshold` measured on a dataset (client `tune-threshold`)
+//! replaces it on the server for templates of its parameters.
+
+use serde::{Deserialize, Serialize};
+
+use crate::matching_fhe::counter_width;
+
+/// Length used when nothing else was negotiated (and by all pre-negotiation data)
+pub const DEFAULT_TEMPLATE_BITS: usize = 1024;
+
+/// Lengths the extractor can produce: 16 bits per LBP region, power-of-two grids
+pub const SUPPORTED_TEMPLATE_BITS: [usize; 4] = [128, 256, 512, 1024];
+
+/// Fingerprints match at this similarity (percent) or more
+pub const MATCH_SIMILARITY_PERCENT: usize = 80;
+
+/// Template bits per extractor region (one LBP histogram)
+pub const BITS_PER_REGION: usize = 16;
+
+/// Partial probes must cover at least this share of the regions (percent)
+pub const MIN_COVERAGE_PERCENT: usize = 40;
+
+/// Largest weight a template bit may carry
+pub const MAX_BIT_WEIGHT: u8 = 7;
+
+/// Rotated variants an enrollment may carry (each one more match per verification)
+pub const MAX_ROTATIONS: usize = 8;
+
+pub fn default_template_bits() -> usize {
+    DEFAULT_TEMPLATE_BITS
+}
+
+pub fn validate(bits: usize) -> Result<(), String> {
+    if SUPPORTED_TEMPLATE_BITS.contains(&bits) {
+        Ok(())
+    } else {
+        Err(format!("Unsupported template length {} bits (supported: {:?})", bits, SUPPORTED_TEMPLATE_BITS))
+    }
+}
+
+/// Largest Hamming distance still accepted as a fingerprint match (1024 bits: 204)
+pub fn match_threshold(bits: usize) -> usize {
+    bits * (100 - MATCH_SIMILARITY_PERCENT) / 100
+}
+
+/// Bits in the encrypted distance counter
+pub fn distance_width(bits: usize) -> usize {
+    counter_width(bits)
+}
+
+pub fn similarity(distance: usize, bits: usize) -> f32 {
+    1.0 - distance as f32 / bits as f32
+}
+
+/// Bits in the encrypted distance of a result; a weighted one is as wide as its total weight needs
+pub fn result_width(bits: usize, weight: Option<usize>) -> usize {
+    distance_width(bits).max(weight.map_or(0, counter_width))
+}
+
+/// Check bit weights against the template length; returns their total
+pub fn check_weights(weights: &[u8], bits: usize) -> Result<usize, String> {
+    if weights.len() != bits {
+        return Err(format!("{} bit weights for a {}-bit template", weights.len(), bits));
+    }
+    if let Some(weight) = weights.iter().find(|&&w| w == 0 || w > MAX_BIT_WEIGHT) {
+        return Err(format!("Bit weight {} outside 1..={}", weight, MAX_BIT_WEIGHT));
+    }
+    Ok(weights.iter().map(|&w| w as usize).sum())
+}
+
+/// Total weight of the compared bits (None = all)
+pub fn compared_weight(weights: &[u8], positions: Option<&[usize]>) -> usize {
+    match positions {
+        Some(positions) => positions.iter().map(|&i| weights[i] as usize).sum(),
+        None => weights.iter().map(|&w| w as usize).sum(),
+    }
+}
+
+/// Threshold for a weighted distance: the share of `compared_bits` the
+/// plain threshold allows, applied to their total `weight`
+pub fn weighted_threshold(threshold: usize, weight: usize, compared_bits: usize) -> usize {
+    threshold * weight / compared_bits.max(1)
+}
+
+/// Check the number of rotated variants sent with an enrollment
+pub fn check_rotation_count(count: usize) -> Result<(), String> {
+    if count > MAX_ROTATIONS {
+        return Err(format!("At most {} rotated templates can be enrolled, got {}", MAX_ROTATIONS, count));
+    }
+    Ok(())
+}
+
+/// LBP grid (columns, rows) producing `bits` (16 bits per region)
+pub fn extractor_grid(bits: usize) -> (usize, usize) {
+    let regions = (bits / 16).max(1);
+    let rows = 1 << (regions.trailing_zeros() / 2);
+    (regions / rows, rows)
+}
+
+pub fn regions(bits: usize) -> usize {
+    bits / BITS_PER_REGION
+}
+
+/// Check a coverage mask against the template length; returns the bits it covers
+pub fn check_mask(mask: &[bool], bits: usize) -> Result<usize, String> {
+    if mask.len() != regions(bits) {
+        return Err(format!("Coverage mask has {} regions, {}-bit templates have {}", mask.len(), bits, regions(bits)));
+    }
+    let covered = mask.iter().filter(|&&c| c).count();
+    if covered * 100 < mask.len() * MIN_COVERAGE_PERCENT {
+        return Err(format!(
+            "Probe covers {} of {} regions, at least {}% are needed",
+            covered,
+            mask.len(),
+            MIN_COVERAGE_PERCENT
+        ));
+    }
+    Ok(covered * BITS_PER_REGION)
+}
+
+/// Template bit positions inside the covered regions
+pub fn masked_positions(mask: &[bool]) -> Vec<usize> {
+    mask.iter()
+        .enumerate()
+        .filter(|(_, &covered)| covered)
+        .flat_map(|(region, _)| region * BITS_PER_REGION..(region + 1) * BITS_PER_REGION)
+        .collect()
+}
+
+/// Threshold scaled to the bits a partial probe is compared on
+pub fn partial_threshold(threshold: usize, compared_bits: usize, bits: usize) -> usize {
+    threshold * compared_bits / bits.max(1)
+}
+
+/// Check a request's declared length against the ciphertext it carries
+pub fn check_ciphertext(bits: usize, ciphertext_len: usize) -> Result<(), String> {
+    validate(bits)?;
+    if ciphertext_len != bits {
+        return Err(format!("Request declares {}-bit templates but carries {} bits", bits, ciphertext_len));
+    }
+    Ok(())
+}
+
+/// Check the client's length against the ones the server advertises.
+/// An empty `server` list (older server) only accepts the default length.
+pub fn negotiate(wanted: usize, server: &[usize]) -> Result<usize, String> {
+    validate(wanted)?;
+    let server: &[usize] = if server.is_empty() { &[DEFAULT_TEMPLATE_BITS] } else { server };
+    if server.contains(&wanted) {
+        return Ok(wanted);
+    }
+    Err(format!("Server does not accept {}-bit templates (supported: {:?})", wanted, server))
+}
+
+/// Texture features the template bits are computed from (client
+/// feature_extraction.rs). Both produce 16 bits per region; a probe only
+/// matches templates of the same extractor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Extractor {
+    #[default]
+    Lbp,      // Regional histograms of uniform Local Binary Patterns
+    Gabor,    // Regional Gabor filterbank energies (client gabor.rs)
+}
+
+impl Extractor {
+    pub fn description(self) -> &'static str {
+        match self {
+            Extractor::Lbp => "LBP texture features",
+            Extractor::Gabor => "Gabor filterbank features",
+        }
+    }
+
+    /// How this extractor's region features become bits
+    pub fn quantization(self) -> Quantization {
+        match self {
+            Extractor::Lbp => Quantization::LbpHistogram,
+            Extractor::Gabor => Quantization::GaborEnergy,
+        }
+    }
+}
+
+impl std::fmt::Display for Extractor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Extractor::Lbp => "lbp",
+            Extractor::Gabor => "gabor",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How the extractor turns the features of one region into its bits
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    #[default]
+    LbpHistogram,   // Uniform LBP histogram bins above the region's median
+    GaborEnergy,    // Gabor kernel energies above the region's median
+}
+
+impl std::fmt::Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Quantization::LbpHistogram => "lbp_histogram",
+            Quantization::GaborEnergy => "gabor_energy",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Quantization {
+    /// Extractor producing this quantization, for parameters recorded without one
+    fn extractor(self) -> Extractor {
+        match self {
+            Quantization::LbpHistogram => Extractor::Lbp,
+            Quantization::GaborEnergy => Extractor::Gabor,
+        }
+    }
+}
+
+/// Shape of a template; enrollment and verification must agree on all of it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateParams {
+    pub bits: usize,
+    pub grid: (usize, usize),           // Extractor regions (columns, rows)
+    #[serde(default)]
+    pub quantization: Quantization,
+    #[serde(default)]
+    pub extractor: Option<Extractor>,   // None = recorded before the extractor was (see `extractor()`)
+    #[serde(default)]
+    pub align_core: bool,               // Captures were centred on their core point first
+}
+
+impl TemplateParams {
+    pub fn new(bits: usize, extractor: Extractor) -> Result<Self, String> {
+        validate(bits)?;
+        Ok(Self {
+            bits,
+            grid: extractor_grid(bits),
+            quantization: extractor.quantization(),
+            extractor: Some(extractor),
+            align_core: false,
+        })
+    }
+
+    /// Templates of captures centred on their core point (client alignment.rs)
+    pub fn with_core_alignment(mut self, align_core: bool) -> Self {
+        self.align_core = align_core;
+        self
+    }
+
+    /// Parameters of an enrollment stored before they were recorded: LBP templates
+    pub fn legacy(bits: usize) -> Self {
+        Self {
+            bits,
+            grid: extractor_grid(bits),
+            quantization: Quantization::LbpHistogram,
+            extractor: Some(Extractor::Lbp),
+            align_core: false,
+        }
+    }
+
+    /// Extractor of the templates; parameters recorded with only the
+    /// quantization name the extractor producing it
+    pub fn extractor(&self) -> Extractor {
+        self.extractor.unwrap_or_else(|| self.quantization.extractor())
+    }
+
+    /// Check parameters received in a request: a supported length on its grid
+    pub fn validate(&self) -> Result<(), String> {
+        validate(self.bits)?;
+        if self.grid != extractor_grid(self.bits) {
+            return Err(format!(
+                "A {}-bit template has a {}×{} region grid, not {}×{}",
+                self.bits, extractor_grid(self.bits).0, extractor_grid(self.bits).1, self.grid.0, self.grid.1
+            ));
+        }
+        if self.quantization != self.extractor().quantization() {
+            return Err(format!("The {} extractor does not quantize as {}", self.extractor(), self.quantization));
+        }
+        Ok(())
+    }
+
+    /// A probe with these parameters can be matched against an `enrolled` template
+    pub fn check_compatible(&self, enrolled: &TemplateParams) -> Result<(), String> {
+        if self.bits != enrolled.bits {
+            return Err(format!("Probe has {} bits, enrolled template has {}", self.bits, enrolled.bits));
+        }
+        if self.extractor() != enrolled.extractor() {
+            return Err(format!(
+                "Probe was extracted with {}, the enrolled template with {}; use the enrollment's extractor",
+                self.extractor().description(),
+                enrolled.extractor().description()
+            ));
+        }
+        if self.align_core != enrolled.align_core {
+            let aligned = |align_core: bool| if align_core { "centred on the core point" } else { "not aligned" };
+            return Err(format!(
+                "Probe was {}, the enrolled template {}; use the enrollment's alignment",
+                aligned(self.align_core),
+                aligned(enrolled.align_core)
+            ));
+        }
+        if self.quantization != enrolled.quantization {
+            return Err(format!(
+                "Probe was quantized as {}, the enrolled template as {}; use the enrollment's extractor",
+                self.quantization, enrolled.quantization
+            ));
+        }
+        Ok(())
+    }
+
+    /// Bits of the encrypted distance counter (and the popcount output)
+    pub fn distance_width(&self) -> usize {
+        distance_width(self.bits)
+    }
+
+    pub fn match_threshold(&self) -> usize {
+        match_threshold(self.bits)
+    }
+
+    pub fn similarity(&self, distance: usize) -> f32 {
+        similarity(distance, self.bits)
+    }
+}
+
+/// Match threshold chosen on a dataset for one kind of template: the widest one
+/// whose false accept rate stayed within the target (server `thresholds.json`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TunedThreshold {
+    pub params: TemplateParams,         // Templates the threshold applies to
+    pub threshold_bits: usize,          // Most differing bits still accepted
+    pub target_far: f64,                // False accept rate aimed for (fraction)
+    pub far: f64,                       // Rates measured at the threshold
+    pub frr: f64,
+    pub genuine_pairs: usize,
+    pub impostor_pairs: usize,
+    pub tuned_at: String,               // RFC 3339
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_sizes_match_the_1024_bit_pipeline() {
+        assert_eq!(match_threshold(1024), 204);
+        assert_eq!(match_threshold(512), 102);
+        assert_eq!(distance_width(1024), 11);
+        assert_eq!(distance_width(512), 10);
+        assert_eq!(extractor_grid(1024), (8, 8));
+        for bits in SUPPORTED_TEMPLATE_BITS {
+            let (cols, rows) = extractor_grid(bits);
+            assert_eq!(cols * rows * 16, bits);
+        }
+    }
+
+    #[test]
+    fn params_follow_the_length_and_reject_other_extractors() {
+        let params = TemplateParams::new(512, Extractor::Lbp).unwrap();
+        assert_eq!((params.grid, params.distance_width(), params.match_threshold()), ((8, 4), 10, 102));
+        assert_eq!(params, TemplateParams::legacy(512));
+        assert!(TemplateParams::new(300, Extractor::Lbp).is_err());
+        assert!(TemplateParams { grid: (4, 8), ..params }.validate().is_err());
+        assert!(TemplateParams { quantization: Quantization::GaborEnergy, ..params }.validate().is_err());
+
+        let gabor = TemplateParams::new(512, Extractor::Gabor).unwrap();
+        assert_eq!(gabor.quantization, Quantization::GaborEnergy);
+        assert!(gabor.check_compatible(&params).is_err());
+        assert!(params.check_compatible(&gabor).is_err());
+
+        // Recorded with only the quantization: the extractor producing it
+        let recorded: TemplateParams = serde_json::from_str(r#"{"bits":512,"grid":[8,4],"quantization":"gabor_energy"}"#).unwrap();
+        assert_eq!((recorded.extractor, recorded.extractor()), (None, Extractor::Gabor));
+        assert!(recorded.check_compatible(&gabor).is_ok());
+
+        let aligned = params.with_core_alignment(true);
+        assert!(aligned.check_compatible(&params).is_err());
+        assert!(params.check_compatible(&aligned).is_err());
+        assert!(aligned.check_compatible(&TemplateParams::new(512, Extractor::Lbp).unwrap().with_core_alignment(true)).is_ok());
+        assert!(TemplateParams::legacy(1024).check_compatible(&params).is_err());
+        assert!(params.check_compatible(&TemplateParams::legacy(512)).is_ok());
+    }
+
+    #[test]
+    fn partial_probes_compare_covered_regions_only() {
+        let mut mask = vec![false; regions(1024)];
+        mask[..32].iter_mut().for_each(|c| *c = true);
+        assert_eq!(check_mask(&mask, 1024), Ok(512));
+        assert_eq!(masked_positions(&mask), (0..512).collect::<Vec<_>>());
+        assert_eq!(partial_threshold(match_threshold(1024), 512, 1024), 102);
+
+        assert!(check_mask(&mask[..32], 1024).is_err());
+        assert!(check_mask(&vec![true; 8].into_iter().chain(vec![false; 56]).collect::<Vec<_>>(), 1024).is_err());
+    }
+
+    #[test]
+    fn weights_scale_the_threshold_and_counter() {
+        let weights: Vec<u8> = (0..1024).map(|i| (i % 3 + 1) as u8).collect();
+        let total = check_weights(&weights, 1024).unwrap();
+        assert_eq!(total, compared_weight(&weights, None));
+        assert_eq!(compared_weight(&weights, Some(&[0, 1, 2, 3])), 1 + 2 + 3 + 1);
+        assert_eq!(weighted_threshold(match_threshold(1024), 2048, 1024), 408);
+        assert_eq!(result_width(1024, None), 11);
+        assert_eq!(result_width(1024, Some(total)), 11);
+        assert_eq!(result_width(1024, Some(3 * 1024)), 12);
+
+        assert!(check_weights(&weights[..512], 1024).is_err());
+        assert!(check_weights(&[0; 128], 128).is_err());
+        assert!(check_weights(&[MAX_BIT_WEIGHT + 1; 128], 128).is_err());
+    }
+
+    #[test]
+    fn negotiation_needs_a_size_the_server_accepts() {
+        assert_eq!(negotiate(512, &[512, 1024]), Ok(512));
+        assert_eq!(negotiate(1024, &[]), Ok(1024));
+        assert!(negotiate(512, &[]).is_err());
+        assert!(negotiate(300, &[300]).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Cancellable biometrics: a revocable, user-specific transform of the
+//! template bits.
+//!
+//! Before transciphering, the client permutes the bits within each extractor
+//! region and XORs them with a salt, both drawn from a secret key it keeps
+//! per user. The same transform on the enrolled and the probe bits leaves
+//! their Hamming distance unchanged, and region masks and deltas still fit,
+//! but a leaked template is only good under that key. Revoking issues a new
+//! key: the server refuses to match until the user registers again, and
+//! templates made under the old key no longer match transformed probes.
+//! The server only ever sees the transform's id.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::template::BITS_PER_REGION;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CancelableTransform {
+    pub id: String,     // Public name of the transform, stored with the enrollment
+    key: [u8; 32],      // Secret the permutation and salt are drawn from
+}
+
+impl CancelableTransform {
+    /// A fresh transform under a random key
+    pub fn generate() -> Self {
+        Self::from_key(rand::random())
+    }
+
+    fn from_key(key: [u8; 32]) -> Self {
+        let digest = Sha256::new().chain_update(b"fingerprint-fhe transform id").chain_update(key).finalize();
+        Self { id: crate::sealed::hex(&digest[..8]), key }
+    }
+
+    /// Transformed template bits: permuted within their regions, then salted
+    pub fn apply(&self, bits: &[bool]) -> Vec<bool> {
+        let salt = self.stream(b"salt", bits.len().div_ceil(8));
+        self.permute(bits)
+            .into_iter()
+            .enumerate()
+            .map(|(i, bit)| bit ^ ((salt[i / 8] >> (i % 8)) & 1 == 1))
+            .collect()
+    }
+
+    /// Per-bit values (quality masks, bit weights) moved along with their bits
+    pub fn permute<T: Clone>(&self, values: &[T]) -> Vec<T> {
+        self.permutation(values.len()).iter().map(|&i| values[i].clone()).collect()
+    }
+
+    /// Source position of each output bit; a shuffle of every region
+    fn permutation(&self, len: usize) -> Vec<usize> {
+        let random = self.stream(b"permutation", 4 * len);
+        let mut draws = random.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as usize);
+        let mut order: Vec<usize> = (0..len).collect();
+        for region in order.chunks_mut(BITS_PER_REGION) {
+            for j in (1..region.len()).rev() {
+                let k = draws.next().expect("one draw per bit") % (j + 1);
+                region.swap(j, k);
+            }
+        }
+        order
+    }
+
+    /// `len` bytes of SHA-256(key, label, counter)
+    fn stream(&self, label: &[u8], len: usize) -> Vec<u8> {
+        (0u32..)
+            .flat_map(|counter| {
+                Sha256::new()
+                    .chain_update(self.key)
+                    .chain_update(label)
+                    .chain_update(counter.to_le_bytes())
+                    .finalize()
+            })
+            .take(len)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_keeps_distances_and_regions_but_not_bits() {
+        let transform = CancelableTransform::from_key([7; 32]);
+        let enrolled: Vec<bool> = (0..1024).map(|i| (i * 31 + i / 5) % 3 == 0).collect();
+        let mut probe = enrolled.clone();
+        for i in (0..1024).step_by(9) {
+            probe[i] ^= true;
+        }
+        let distance = |a: &[bool], b: &[bool]| a.iter().zip(b).filter(|(x, y)| x != y).count();
+        let (enrolled_t, probe_t) = (transform.apply(&enrolled), transform.apply(&probe));
+        assert_eq!(distance(&enrolled_t, &probe_t), distance(&enrolled, &probe));
+        assert!(distance(&enrolled_t, &enrolled) > 300);
+
+        let positions: Vec<usize> = (0..1024).collect();
+        let moved = transform.permute(&positions);
+        assert!(moved.iter().enumerate().all(|(i, &from)| i / BITS_PER_REGION == from / BITS_PER_REGION));
+        assert_ne!(moved, positions);
+
+        // A revoked template doesn't match the same finger under the new transform
+        let renewed = CancelableTransform::from_key([8; 32]);
+        assert_ne!(renewed.id, transform.id);
+        assert!(distance(&renewed.apply(&probe), &enrolled_t) > 300);
+    }
+}
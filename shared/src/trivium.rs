@@ -1,5 +1,9 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
 pub struct Trivium {
     state: Vec<bool>,
+    position: usize,    // Keystream bits produced or skipped since the warmup
 }
 
 impl Trivium {
@@ -26,12 +30,13 @@ impl Trivium {
         state[286] = true;
         state[287] = true;
         
-        let mut trivium = Trivium { state };
+        let mut trivium = Trivium { state, position: 0 };
         
         // Warmup: 1152 cycles (4 × 288)
         for _ in 0..1152 {
             trivium.clock();
         }
+        trivium.position = 0;
         
         trivium
     }
@@ -55,6 +60,7 @@ impl Trivium {
     self.state[0] = s3;
     self.state[93] = s1;
     self.state[177] = s2;
+    self.position += 1;
     
     output
     }
@@ -64,17 +70,301 @@ impl Trivium {
             .map(|&bit| bit ^ self.clock())
             .collect()
     }
-}
 
-// Helper function
-pub fn u64_to_bits_80(value: u64) -> Vec<bool> {
-    let mut bits = vec![false; 80];
-    for i in 0..64 {
-        bits[i] = ((value >> i) & 1) == 1;
+    /// Advance `n` keystream bits without producing them
+    pub fn skip(&mut self, n: usize) {
+        for _ in 0..n {
+            self.clock();
+        }
+    }
+
+    /// Keystream bits produced or skipped so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// `len` keystream bits starting at bit `offset`, which must not be behind [`position`](Self::position)
+    pub fn keystream_at(&mut self, offset: usize, len: usize) -> Vec<bool> {
+        assert!(offset >= self.position, "Keystream offset {} is behind position {}", offset, self.position);
+        self.skip(offset - self.position);
+        (0..len).map(|_| self.clock()).collect()
     }
+
+    /// Encrypt (or decrypt) bytes. Keystream bits fill each byte from the
+    /// least significant bit, as in the eSTREAM test vectors.
+    pub fn process_bytes(&mut self, data: &[u8]) -> Vec<u8> {
+        let keystream = self.keystream_bytes(data.len());
+        data.iter().zip(keystream).map(|(d, k)| d ^ k).collect()
+    }
+
+    /// Next `n` keystream bytes (bit order as in [`process_bytes`](Self::process_bytes))
+    pub fn keystream_bytes(&mut self, n: usize) -> Vec<u8> {
+        (0..n).map(|_| pack_byte((0..8).map(|_| self.clock()))).collect()
+    }
+
+    /// Check this implementation against the eSTREAM test vectors
+    pub fn self_test() -> Result<(), String> {
+        for (key, iv, offset, expected) in KNOWN_ANSWERS {
+            let mut trivium = Trivium::new(&estream_key_bits(key), &estream_key_bits(iv));
+            trivium.skip(offset * 8);
+            let got = trivium.keystream_bytes(expected.len() / 2);
+            if got != hex_to_bytes(expected) {
+                return Err(format!(
+                    "Trivium key {} IV {}: stream[{}..] is {}, expected {}",
+                    key, iv, offset, bytes_to_hex(&got), expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// eSTREAM known answers: key, IV, first keystream byte, keystream (64 bytes)
+const KNOWN_ANSWERS: [(&str, &str, usize, &str); 3] = [
+    // Set 1, vector 0
+    (
+        "80000000000000000000",
+        "00000000000000000000",
+        0,
+        "38EB86FF730D7A9CAF8DF13A4420540DBB7B651464C87501552041C249F29A64\
+         D2FBF515610921EBE06C8F92CECF7F8098FF20CCCC6A62B97BE8EF7454FC80F9",
+    ),
+    // Set 6, vector 0
+    (
+        "0053A6F94C9FF24598EB",
+        "0D74DB42A91077DE45AC",
+        0,
+        "F4CD954A717F26A7D6930830C4E7CF0819F80E03F25F342C64ADC66ABA7F8A8E\
+         6EAA49F23632AE3CD41A7BD290A0132F81C6D4043B6E397D7388F3A03B5FE358",
+    ),
+    (
+        "0053A6F94C9FF24598EB",
+        "0D74DB42A91077DE45AC",
+        192,
+        "0E552C0DDEECBA7EB1729D87440612E758347FF72B4449776E3F82C10EE463AE\
+         D066F0FCBF895F85354646E59692BC1B92ACB30984F25B366FF27AED8333053F",
+    ),
+];
+
+/// Bits of a byte, least significant first (the order eSTREAM uses for keystream bytes)
+fn byte_bits(byte: u8) -> impl Iterator<Item = bool> {
+    (0..8).map(move |bit| (byte >> bit) & 1 == 1)
+}
+
+/// Inverse of [`byte_bits`]
+fn pack_byte(bits: impl IntoIterator<Item = bool>) -> u8 {
+    bits.into_iter().enumerate().fold(0, |byte, (i, bit)| byte | ((bit as u8) << i))
+}
+
+fn hex_to_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).expect("hex digit"))
+        .collect()
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Key or IV from eSTREAM notation. eSTREAM numbers the register from the
+/// other end, so its last bit goes into `state[0]` here.
+fn estream_key_bits(hex: &str) -> Vec<bool> {
+    let mut bits: Vec<bool> = hex_to_bytes(hex).into_iter().flat_map(byte_bits).collect();
+    bits.reverse();
     bits
 }
 
+/// Kreyvium: Trivium with 128-bit key and IV (Canteaut et al., FSE 2016).
+///
+/// Same 288-bit state and taps; the key and IV also sit in two rotating
+/// 128-bit registers K* and IV*, whose current bit is added to t3 and to
+/// the feedback into register 2 on every clock.
+pub struct Kreyvium {
+    state: Vec<bool>,
+    key: Vec<bool>,     // K*, read at `register_index(cycles)` as it only rotates
+    iv: Vec<bool>,      // IV*, likewise
+    cycles: usize,
+}
+
+impl Kreyvium {
+    pub fn new(key: &[bool], iv: &[bool]) -> Self {
+        assert_eq!(key.len(), 128);
+        assert_eq!(iv.len(), 128);
+
+        let mut state = vec![false; 288];
+        // Register 1: key bits 0-92
+        state[0..93].copy_from_slice(&key[0..93]);
+        // Register 2: IV bits 0-83
+        state[93..177].copy_from_slice(&iv[0..84]);
+        // Register 3: IV bits 84-127, 66 ones, a final zero
+        state[177..221].copy_from_slice(&iv[84..128]);
+        state[221..287].fill(true);
+
+        let mut kreyvium = Kreyvium { state, key: key.to_vec(), iv: iv.to_vec(), cycles: 0 };
+        for _ in 0..1152 {
+            kreyvium.clock();
+        }
+        kreyvium
+    }
+
+    fn clock(&mut self) -> bool {
+        let k = self.key[register_index(self.cycles)];
+        let v = self.iv[register_index(self.cycles)];
+        self.cycles += 1;
+
+        let t1 = self.state[65] ^ self.state[92];
+        let t2 = self.state[161] ^ self.state[176];
+        let t3 = self.state[242] ^ self.state[287] ^ k;
+
+        let output = t1 ^ t2 ^ t3;
+
+        let s1 = t1 ^ (self.state[90] & self.state[91]) ^ self.state[170] ^ v;
+        let s2 = t2 ^ (self.state[174] & self.state[175]) ^ self.state[263];
+        let s3 = t3 ^ (self.state[285] & self.state[286]) ^ self.state[68];
+
+        self.state.rotate_right(1);
+        self.state[0] = s3;
+        self.state[93] = s1;
+        self.state[177] = s2;
+
+        output
+    }
+
+    pub fn process(&mut self, data: &[bool]) -> Vec<bool> {
+        data.iter()
+            .map(|&bit| bit ^ self.clock())
+            .collect()
+    }
+
+    /// Check this implementation against the reference implementation's vector
+    pub fn self_test() -> Result<(), String> {
+        for (key, iv, expected) in KREYVIUM_KNOWN_ANSWERS {
+            let keystream = Kreyvium::new(&estream_key_bits(key), &estream_key_bits(iv)).process(&vec![false; expected.len() * 4]);
+            let got: Vec<u8> = keystream.chunks(8).map(|c| pack_byte(c.iter().copied())).collect();
+            if bytes_to_hex(&got) != expected {
+                return Err(format!("Kreyvium key {} IV {}: stream is {}, expected {}", key, iv, bytes_to_hex(&got), expected));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kreyvium known answer of the reference implementation: key, IV, first 8 keystream bytes.
+/// Only the all-zero key/IV so far, which cannot catch a reversed key or IV
+/// bit order; a vector with nonzero key and IV from the reference
+/// implementation still needs to be added here.
+const KREYVIUM_KNOWN_ANSWERS: [(&str, &str, &str); 1] = [(
+    "00000000000000000000000000000000",
+    "00000000000000000000000000000000",
+    "26DCF1F4BC0F1922",
+)];
+
+/// Key/IV bit in the first slot of Kreyvium's K* / IV* register at clock
+/// `cycles`: the registers start reversed (K*_0 = K_127) and rotate by one
+pub fn register_index(cycles: usize) -> usize {
+    127 - cycles % 128
+}
+
+/// Stream cipher a template is transciphered with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Cipher {
+    #[default]
+    Trivium,    // 80-bit key and IV
+    Kreyvium,   // 128-bit key and IV
+}
+
+impl Cipher {
+    pub const ALL: [Cipher; 2] = [Cipher::Trivium, Cipher::Kreyvium];
+
+    pub fn key_bits(self) -> usize {
+        match self {
+            Cipher::Trivium => 80,
+            Cipher::Kreyvium => 128,
+        }
+    }
+
+    pub fn iv_bits(self) -> usize {
+        self.key_bits()
+    }
+
+    /// Encrypted bits an FHE instance keeps: the state, plus K*/IV* for Kreyvium
+    pub fn state_bits(self) -> usize {
+        match self {
+            Cipher::Trivium => 288,
+            Cipher::Kreyvium => 288 + 2 * 128,
+        }
+    }
+
+    /// Cipher taking keys of `bits` bits
+    pub fn for_key_bits(bits: usize) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.key_bits() == bits)
+    }
+
+    /// Cipher of a key and IV of these lengths (the key picks it, the IV must match)
+    pub fn for_key_iv(key_bits: usize, iv_bits: usize) -> Result<Self, String> {
+        match Self::for_key_bits(key_bits) {
+            Some(cipher) if cipher.iv_bits() == iv_bits => Ok(cipher),
+            _ => Err(format!("Key/IV of {}/{} bits fit no cipher (trivium: 80/80, kreyvium: 128/128)", key_bits, iv_bits)),
+        }
+    }
+
+    /// Check the client's cipher against the ones the server advertises.
+    /// An empty `server` list (older server) only knows Trivium and Kreyvium.
+    pub fn negotiate(self, server: &[Cipher]) -> Result<Self, String> {
+        let server: &[Cipher] = if server.is_empty() { &[Cipher::Trivium, Cipher::Kreyvium] } else { server };
+        if server.contains(&self) {
+            return Ok(self);
+        }
+        let names: Vec<String> = server.iter().map(|c| c.to_string()).collect();
+        Err(format!("Server does not accept {} (supported: {})", self, names.join(", ")))
+    }
+
+    /// Encrypt (or decrypt) `data` with a fresh keystream, starting `skip` bits into it
+    pub fn process(self, key: &[bool], iv: &[bool], skip: usize, data: &[bool]) -> Vec<bool> {
+        match self {
+            Cipher::Trivium => {
+                let mut trivium = Trivium::new(key, iv);
+                trivium.skip(skip);
+                trivium.process(data)
+            }
+            Cipher::Kreyvium => {
+                let mut kreyvium = Kreyvium::new(key, iv);
+                kreyvium.process(&vec![false; skip]);
+                kreyvium.process(data)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Cipher::Trivium => "trivium",
+            Cipher::Kreyvium => "kreyvium",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 80 key/IV bits from 10 bytes, least significant bit of each byte first
+pub fn bytes_to_bits_80(bytes: &[u8; 10]) -> Vec<bool> {
+    bytes.iter().copied().flat_map(byte_bits).collect()
+}
+
+/// Fresh 80-bit Trivium key or IV, every bit drawn from `rng`
+pub fn random_bits_80<R: Rng + ?Sized>(rng: &mut R) -> Vec<bool> {
+    bytes_to_bits_80(&rng.gen())
+}
+
+/// Bits 64-79 are always zero, so a random `value` leaves 16 key bits fixed
+#[deprecated(note = "use `random_bits_80` or `bytes_to_bits_80`, which fill all 80 bits")]
+pub fn u64_to_bits_80(value: u64) -> Vec<bool> {
+    let mut bytes = [0u8; 10];
+    bytes[..8].copy_from_slice(&value.to_le_bytes());
+    bytes_to_bits_80(&bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +386,102 @@ mod tests {
         
         assert_eq!(plaintext, decrypted, "Trivium encryption/decryption mismatch!");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn kreyvium_round_trips_and_depends_on_the_whole_key() {
+        let key: Vec<bool> = (0..128).map(|i| i % 3 == 0).collect();
+        let iv: Vec<bool> = (0..128).map(|i| i % 5 == 1).collect();
+        let plaintext: Vec<bool> = (0..256).map(|i| i % 7 < 3).collect();
+
+        let ciphertext = Cipher::Kreyvium.process(&key, &iv, 0, &plaintext);
+        assert_ne!(ciphertext, plaintext);
+        assert_eq!(Cipher::Kreyvium.process(&key, &iv, 0, &ciphertext), plaintext);
+        // Skipping keystream continues where a longer run left off
+        assert_eq!(Cipher::Kreyvium.process(&key, &iv, 100, &ciphertext[100..]), plaintext[100..]);
+
+        // Key bits past Trivium's 80 only enter through K*
+        let mut other_key = key.clone();
+        other_key[127] = !other_key[127];
+        assert_ne!(Cipher::Kreyvium.process(&other_key, &iv, 0, &plaintext), ciphertext);
+    }
+
+    #[test]
+    fn kreyvium_matches_the_reference_vector() {
+        assert_eq!(Kreyvium::self_test(), Ok(()));
+        // One flipped key bit, and the known answer no longer holds
+        let mut key = vec![false; 128];
+        key[5] = true;
+        let keystream = Kreyvium::new(&key, &[false; 128]).process(&[false; 64]);
+        let got: Vec<u8> = keystream.chunks(8).map(|c| pack_byte(c.iter().copied())).collect();
+        assert_ne!(bytes_to_hex(&got), KREYVIUM_KNOWN_ANSWERS[0].2);
+    }
+
+    #[test]
+    fn key_and_iv_lengths_pick_the_cipher() {
+        assert_eq!(Cipher::for_key_iv(80, 80), Ok(Cipher::Trivium));
+        assert_eq!(Cipher::for_key_iv(128, 128), Ok(Cipher::Kreyvium));
+        assert!(Cipher::for_key_iv(128, 80).is_err());
+        assert!(Cipher::for_key_iv(80, 128).is_err());
+        assert!(Cipher::for_key_iv(64, 64).is_err());
+    }
+
+    #[test]
+    fn matches_estream_test_vectors() {
+        for (key, iv, offset, expected) in KNOWN_ANSWERS {
+            let mut trivium = Trivium::new(&estream_key_bits(key), &estream_key_bits(iv));
+            trivium.process(&vec![false; offset * 8]);
+            let keystream: Vec<u8> = trivium.process(&vec![false; expected.len() * 4]).chunks(8).map(|c| pack_byte(c.iter().copied())).collect();
+            assert_eq!(bytes_to_hex(&keystream), expected, "key {} IV {} stream[{}..]", key, iv, offset);
+        }
+        assert_eq!(Trivium::self_test(), Ok(()));
+    }
+
+    #[test]
+    fn skipping_matches_producing_the_keystream() {
+        let key: Vec<bool> = (0..80).map(|i| i % 3 == 1).collect();
+        let iv: Vec<bool> = (0..80).map(|i| i % 4 == 0).collect();
+        let full = Trivium::new(&key, &iv).process(&[false; 300]);
+
+        let mut trivium = Trivium::new(&key, &iv);
+        trivium.skip(100);
+        assert_eq!(trivium.position(), 100);
+        assert_eq!(trivium.process(&[false; 50]), full[100..150]);
+        assert_eq!(trivium.keystream_at(200, 100), full[200..]);
+        assert_eq!(trivium.position(), 300);
+    }
+
+    #[test]
+    fn bytes_round_trip_with_the_bit_keystream() {
+        let key = bytes_to_bits_80(&[0x53, 0x00, 0xA6, 0xF9, 0x4C, 0x9F, 0xF2, 0x45, 0x98, 0xEB]);
+        let iv = bytes_to_bits_80(&[0x0D, 0x74, 0xDB, 0x42, 0xA9, 0x10, 0x77, 0xDE, 0x45, 0xAC]);
+        let data = b"fingerprint template".to_vec();
+
+        let ciphertext = Trivium::new(&key, &iv).process_bytes(&data);
+        assert_ne!(ciphertext, data);
+        assert_eq!(Trivium::new(&key, &iv).process_bytes(&ciphertext), data);
+
+        let bits: Vec<bool> = data.iter().copied().flat_map(byte_bits).collect();
+        let bit_ciphertext = Trivium::new(&key, &iv).process(&bits);
+        assert_eq!(bit_ciphertext.chunks(8).map(|c| pack_byte(c.iter().copied())).collect::<Vec<u8>>(), ciphertext);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn bytes_fill_all_80_bits() {
+        let bits = bytes_to_bits_80(&[0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert!(bits[0] && bits[79]);
+        assert_eq!(bits.iter().filter(|&&b| b).count(), 2);
+        assert_eq!(u64_to_bits_80(0x8000_0000_0000_0001), bytes_to_bits_80(&[0x01, 0, 0, 0, 0, 0, 0, 0x80, 0, 0]));
+
+        let mut rng = rand::thread_rng();
+        let high_set = (0..64).any(|_| random_bits_80(&mut rng)[64..].contains(&true));
+        assert!(high_set, "bits 64-79 must come from the RNG too");
+    }
+
+    #[test]
+    fn ciphers_are_negotiated_against_the_advertised_list() {
+        assert_eq!(Cipher::Kreyvium.negotiate(&Cipher::ALL), Ok(Cipher::Kreyvium));
+        assert_eq!(Cipher::Kreyvium.negotiate(&[]), Ok(Cipher::Kreyvium));
+        assert!(Cipher::Kreyvium.negotiate(&[Cipher::Trivium]).is_err());
+    }
+}
@@ -1,7 +1,135 @@
 // shared/src/trivium_fhe.rs
 
+use serde::{Deserialize, Serialize};
+use std::ops::{BitAnd, BitXor};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 use tfhe::prelude::*;
-use tfhe::{set_server_key, FheBool, ServerKey};
+use tfhe::FheBool;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::sync::Arc;
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::compute::EvaluationKey;
+use crate::error::FingerprintError;
+use crate::matching_fhe::fhe_constant;
+use crate::trivium::{register_index, Cipher};
+
+/// Clocks discarded before the first keystream bit
+pub const WARMUP_CYCLES: usize = 1152;
+
+/// Clocks evaluated as one batch. A bit entering a register first reaches a
+/// tap 66 clocks later, so the next 66 clocks read only bits of the current
+/// state and are independent of each other.
+pub const CLOCK_BATCH: usize = 64;
+
+/// Threads per Trivium evaluation (1 = serial, 0 = all of the pool's)
+static CLOCK_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+/// Evaluate the clocks of a batch on up to `threads` threads (0 = all) of the
+/// rayon pool the evaluation is started in, so they come out of that pool's
+/// budget; an evaluation started outside a pool (global pool included) clocks
+/// serially. Applies to evaluations started afterwards; without the
+/// `parallel` feature clocks always run serially.
+pub fn set_clock_threads(threads: usize) {
+    CLOCK_THREADS.store(threads, Ordering::Relaxed);
+}
+
+/// Threads clocking an evaluation started on a pool of `pool` threads (None =
+/// outside one): an evaluation never builds a pool of its own, it borrows
+/// the caller's, so concurrent evaluations don't multiply the threads
+#[cfg(feature = "parallel")]
+fn clock_thread_count(setting: usize, pool: Option<usize>) -> Option<usize> {
+    let available = pool?;
+    let threads = match setting {
+        0 => available,
+        threads => threads.min(available),
+    };
+    (threads > 1).then_some(threads)
+}
+
+/// Console progress line every this many warmup cycles
+const WARMUP_REPORT_CYCLES: usize = 192;
+
+// ==================== PROGRESS ====================
+
+/// Where an FHE-Trivium evaluation is, as reported to a [`ProgressSink`].
+///
+/// Counting events (`Warmup`, `Keystream`, `Xor`) are reported with
+/// `done: 0` when the stage starts and after every clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Decryption,                                     // A homomorphic decryption starts
+    Init,                                           // State loaded with key/IV
+    Resumed { cycles: usize },                      // Continuing from a checkpoint
+    Warmup { done: usize, total: usize },
+    WarmupDone,
+    Keystream { done: usize, total: usize },
+    Xor { done: usize, total: Option<usize> },      // Total unknown for unsized streams
+    Done,
+}
+
+/// Receives progress of Trivium warmup, keystream and decryption, e.g. to
+/// drive a UI, write a status file or estimate the remaining time.
+/// Any `Fn(Progress)` closure is a sink.
+pub trait ProgressSink {
+    fn report(&self, progress: Progress);
+}
+
+impl<F: Fn(Progress)> ProgressSink for F {
+    fn report(&self, progress: Progress) {
+        self(progress)
+    }
+}
+
+/// Reports progress as `tracing` events: phase boundaries at debug level,
+/// every clock at trace level. For embedding, where nothing should be
+/// printed; the binaries' subscriber shows them with `FINGERPRINT_LOG`.
+pub struct TracingProgress;
+
+impl ProgressSink for TracingProgress {
+    fn report(&self, progress: Progress) {
+        match progress {
+            Progress::Warmup { done, total } | Progress::Keystream { done, total } if done > 0 && done < total => {
+                tracing::trace!(?progress)
+            }
+            Progress::Xor { done, .. } if done > 0 => tracing::trace!(?progress),
+            _ => tracing::debug!(?progress),
+        }
+    }
+}
+
+/// Prints the usual console lines (through the message catalog)
+pub struct ConsoleProgress;
+
+impl ProgressSink for ConsoleProgress {
+    fn report(&self, progress: Progress) {
+        if let Some(line) = console_line(progress) {
+            crate::i18n::emit(false, &line);
+        }
+    }
+}
+
+fn console_line(progress: Progress) -> Option<String> {
+    Some(match progress {
+        Progress::Decryption => crate::tr!("fhe.decryption_title"),
+        Progress::Init => crate::tr!("fhe.init_state"),
+        Progress::Resumed { cycles } => crate::tr!("fhe.resuming", cycles),
+        Progress::Warmup { done: 0, .. } => crate::tr!("fhe.warmup"),
+        Progress::Warmup { done, total } if done % WARMUP_REPORT_CYCLES == 0 && done < total => {
+            crate::tr!("fhe.warmup_progress", done)
+        }
+        Progress::WarmupDone => crate::tr!("fhe.warmup_done"),
+        Progress::Keystream { done: 0, total } => crate::tr!("fhe.keystream", total),
+        Progress::Xor { done: 0, .. } => crate::tr!("fhe.xoring"),
+        Progress::Done => crate::tr!("fhe.decryption_done"),
+        _ => return None,
+    })
+}
+
+// ==================== CIPHER ====================
 
 /// Trivium stream cipher state under FHE (288 bits).
 ///
@@ -9,170 +137,670 @@ use tfhe::{set_server_key, FheBool, ServerKey};
 /// - Register 1: state[0..=92]   (93 bits)
 /// - Register 2: state[93..=176] (84 bits)
 /// - Register 3: state[177..=287](111 bits)
+///
+/// Also runs Kreyvium (see [`KreyviumFhe`]), which clocks the same state
+/// with two more registers.
+///
+/// Serializable so a long decryption can be checkpointed and resumed.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TriviumFhe {
     state: Vec<FheBool>, // length 288
+    cycles: usize,       // Clocks done so far, warmup included
+    kreyvium: Option<Registers>, // K*/IV* when running Kreyvium
+    #[cfg(feature = "parallel")]
+    #[serde(skip)]
+    clock_threads: Option<usize>, // Batches run on this many threads of the current pool (None = serially)
+}
+
+/// Kreyvium's key and IV registers (128 bits each). They only rotate, so
+/// they are kept as loaded and read at `trivium::register_index(cycles)`.
+#[derive(Clone, Serialize, Deserialize)]
+struct Registers {
+    key: Vec<FheBool>,
+    iv: Vec<FheBool>,
 }
 
 impl TriviumFhe {
-    /// Create a new FHE-Trivium instance with encrypted key/iv.
+    /// Create a new FHE-Trivium instance with encrypted key/iv. The constant
+    /// state bits are trivial encryptions (see [`fhe_constant`]).
     ///
-    /// `encrypted_true` is required to build homomorphic constants:
-    /// - false := true XOR true
-    /// - true  := encrypted_true.clone()
+    /// Runs under the calling thread's server key, which the caller sets once
+    /// ([`EvaluationKey::install`] or `set_server_key`); `server_key` is only
+    /// installed on the threads of the caller's pool that clock in parallel
+    /// (see [`set_clock_threads`]).
+    /// Key and IV of other lengths than 80 bits are a [`FingerprintError::KeyMismatch`].
     pub fn new(
         encrypted_key: &[FheBool],  // 80 bits
         encrypted_iv: &[FheBool],   // 80 bits
-        encrypted_true: &FheBool,
-        server_key: &ServerKey,
-    ) -> Self {
-        assert_eq!(encrypted_key.len(), 80, "Key must be 80 bits");
-        assert_eq!(encrypted_iv.len(), 80, "IV must be 80 bits");
-
-        // Ensure server key is set for all homomorphic operations.
-        set_server_key(server_key.clone());
-
-        println!("   🔧 Initializing Trivium state (288 bits)...");
+        server_key: &Arc<EvaluationKey>,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Self, FingerprintError> {
+        Self::start(Cipher::Trivium, encrypted_key, encrypted_iv, server_key, progress, cancel)
+    }
 
-        // Homomorphic constants derived from encrypted_true
-        let fhe_true = encrypted_true.clone();
-        let fhe_false = encrypted_true ^ encrypted_true; // 1 XOR 1 = 0
+    /// Load key/IV, which must be `cipher`'s, and run the warmup
+    fn start(
+        cipher: Cipher,
+        encrypted_key: &[FheBool],
+        encrypted_iv: &[FheBool],
+        server_key: &Arc<EvaluationKey>,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Self, FingerprintError> {
+        if encrypted_key.len() != cipher.key_bits() || encrypted_iv.len() != cipher.iv_bits() {
+            return Err(FingerprintError::KeyMismatch(format!(
+                "{} key/IV have {}/{} bits, expected {}/{}",
+                cipher, encrypted_key.len(), encrypted_iv.len(), cipher.key_bits(), cipher.iv_bits()
+            )));
+        }
+        progress.report(Progress::Init);
+        let mut trivium = Self::load(encrypted_key, encrypted_iv)?;
+        trivium.start_threads(server_key);
+        trivium.warm_up(0, &mut |_| {}, progress, cancel)?;
+        Ok(trivium)
+    }
 
-        let mut state: Vec<FheBool> = Vec::with_capacity(288);
+    /// State loaded with key/IV, warmup not yet run. 80-bit keys run
+    /// Trivium, 128-bit keys Kreyvium; the IV must be as long as the key.
+    fn load(encrypted_key: &[FheBool], encrypted_iv: &[FheBool]) -> Result<Self, FingerprintError> {
+        let cipher = Cipher::for_key_iv(encrypted_key.len(), encrypted_iv.len()).map_err(FingerprintError::KeyMismatch)?;
 
-        // state[0..79] = key (80)
-        for i in 0..80 {
-            state.push(encrypted_key[i].clone());
+        let state = load_state(cipher, encrypted_key, encrypted_iv, &fhe_constant(true), &fhe_constant(false));
+        let mut trivium = Self::from_state(state);
+        if cipher == Cipher::Kreyvium {
+            trivium.kreyvium = Some(Registers { key: encrypted_key.to_vec(), iv: encrypted_iv.to_vec() });
         }
+        Ok(trivium)
+    }
 
-        // state[80..92] = 13 zeros
-        for _ in 0..13 {
-            state.push(fhe_false.clone());
+    fn from_state(state: Vec<FheBool>) -> Self {
+        TriviumFhe {
+            state,
+            cycles: 0,
+            kreyvium: None,
+            #[cfg(feature = "parallel")]
+            clock_threads: None,
         }
+    }
 
-        // state[93..172] = iv (80)
-        for i in 0..80 {
-            state.push(encrypted_iv[i].clone());
+    /// Clock batches on the threads of the pool the caller is one of, with
+    /// `server_key` installed on each. Only a pool of the caller's own (a
+    /// server verify job's) is used: threads shared with other evaluations
+    /// could be handed another key in between.
+    #[cfg(feature = "parallel")]
+    fn start_threads(&mut self, server_key: &Arc<EvaluationKey>) {
+        let pool = rayon::current_thread_index().map(|_| rayon::current_num_threads());
+        self.clock_threads = clock_thread_count(CLOCK_THREADS.load(Ordering::Relaxed), pool);
+        if self.clock_threads.is_some() {
+            rayon::broadcast(|_| server_key.install());
         }
+    }
 
-        // state[173..176] = 4 zeros
-        for _ in 0..4 {
-            state.push(fhe_false.clone());
-        }
+    #[cfg(not(feature = "parallel"))]
+    fn start_threads(&mut self, _server_key: &Arc<EvaluationKey>) {}
 
-        // state[177..284] = 108 zeros
-        for _ in 0..108 {
-            state.push(fhe_false.clone());
+    /// Run the rest of the warmup (discarding output). With `every > 0`,
+    /// `checkpoint` sees the state every `every` cycles.
+    fn warm_up(
+        &mut self,
+        every: usize,
+        checkpoint: &mut dyn FnMut(&Self),
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        if self.is_warm() {
+            return Ok(());
         }
+        let _span = tracing::info_span!("warmup", from = self.cycles, cycles = WARMUP_CYCLES).entered();
+        let started = Instant::now();
+        let from = self.cycles;
+        if self.cycles == 0 {
+            progress.report(Progress::Warmup { done: 0, total: WARMUP_CYCLES });
+        }
+        while self.cycles < WARMUP_CYCLES {
+            cancel.check()?;
+            let start = self.cycles;
+            let _ = self.clock_batch(batch_len(start, WARMUP_CYCLES - start, every));
+            for done in start + 1..=self.cycles {
+                progress.report(Progress::Warmup { done, total: WARMUP_CYCLES });
+            }
+            if every > 0 && self.cycles.is_multiple_of(every) {
+                checkpoint(self);
+            }
+        }
+        tracing::debug!(cycles = WARMUP_CYCLES - from, elapsed_ms = elapsed_ms(started), "warmup done");
+        progress.report(Progress::WarmupDone);
+        Ok(())
+    }
+
+    /// Warmup finished: the next clock yields keystream
+    pub fn is_warm(&self) -> bool {
+        self.cycles >= WARMUP_CYCLES
+    }
+
+    /// State loaded with key/IV but without the 1152-cycle warmup; the key
+    /// length picks Trivium or Kreyvium.
+    ///
+    /// Only for timing the clock circuit (parameter advisor); the keystream
+    /// of an unwarmed state is not secure and must never be used to decrypt.
+    pub fn for_benchmark(encrypted_key: &[FheBool], encrypted_iv: &[FheBool]) -> Result<Self, FingerprintError> {
+        Self::load(encrypted_key, encrypted_iv)
+    }
 
-        // state[285..287] = 1,1,1
-        state.push(fhe_true.clone());
-        state.push(fhe_true.clone());
-        state.push(fhe_true.clone());
+    /// `k <= CLOCK_BATCH` clocks at once; returns their keystream bits in order.
+    ///
+    /// The clocks of a batch only read the state it starts from, so inside a
+    /// thread pool they are evaluated in parallel.
+    fn clock_batch(&mut self, k: usize) -> Vec<FheBool> {
+        debug_assert!(k <= CLOCK_BATCH);
+        let state = &self.state;
+        let registers = self.kreyvium.as_ref().map(|r| (r.key.as_slice(), r.iv.as_slice()));
+        let cycles = self.cycles;
+        let clock = |j: usize| step(state, j, register_bits(registers, cycles + j));
+        #[cfg(feature = "parallel")]
+        let steps: Vec<[FheBool; 4]> = match self.clock_threads {
+            // At most `threads` chunks, so the rest of the pool stays free
+            Some(threads) => (0..k).into_par_iter().with_min_len(k.div_ceil(threads)).map(clock).collect(),
+            None => (0..k).map(clock).collect(),
+        };
+        #[cfg(not(feature = "parallel"))]
+        let steps: Vec<[FheBool; 4]> = (0..k).map(clock).collect();
 
-        assert_eq!(state.len(), 288, "State must be 288 bits!");
+        self.cycles += k;
+        advance(&mut self.state, steps)
+    }
 
-        let mut trivium = TriviumFhe { state };
+    /// Generate `n` keystream bits under FHE.
+    pub fn keystream(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+        let _span = tracing::info_span!("keystream", bits = n, position = self.position()).entered();
+        let started = Instant::now();
+        progress.report(Progress::Keystream { done: 0, total: n });
+        let mut keystream = Vec::with_capacity(n);
+        while keystream.len() < n {
+            cancel.check()?;
+            for bit in self.clock_batch(batch_len(self.cycles, n - keystream.len(), 0)) {
+                keystream.push(bit);
+                progress.report(Progress::Keystream { done: keystream.len(), total: n });
+            }
+        }
+        tracing::debug!(bits = n, elapsed_ms = elapsed_ms(started), "keystream done");
+        Ok(keystream)
+    }
 
-        // Warmup: 1152 cycles (discard output)
-        println!("   ⏳ Warmup phase (1152 cycles)...");
-        for i in 0..1152 {
-            if i % 192 == 0 && i != 0 {
-                println!("      Progress: {}/1152", i);
+    /// Advance `n` keystream bits without keeping them. Under FHE the clocks
+    /// still have to be evaluated; only the output is dropped.
+    pub fn skip(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<(), Cancelled> {
+        let _span = tracing::info_span!("keystream", bits = n, position = self.position(), skipped = true).entered();
+        let started = Instant::now();
+        progress.report(Progress::Keystream { done: 0, total: n });
+        let mut done = 0;
+        while done < n {
+            cancel.check()?;
+            let k = batch_len(self.cycles, n - done, 0);
+            let _ = self.clock_batch(k);
+            for _ in 0..k {
+                done += 1;
+                progress.report(Progress::Keystream { done, total: n });
             }
-            let _ = trivium.clock();
         }
-        println!("   ✅ Warmup complete!");
+        tracing::debug!(bits = n, elapsed_ms = elapsed_ms(started), "keystream skipped");
+        Ok(())
+    }
 
-        trivium
+    /// Keystream bits produced or skipped since the warmup
+    pub fn position(&self) -> usize {
+        self.cycles.saturating_sub(WARMUP_CYCLES)
     }
 
-    /// Shift a register range [start..=end] right by 1 within that range, inserting `new_bit` at `start`.
-    ///
-    /// Example: for i=end..start+1: state[i] = state[i-1]; state[start] = new_bit.
-    fn shift_register(&mut self, start: usize, end: usize, new_bit: FheBool) {
-        for i in (start + 1..=end).rev() {
-            self.state[i] = self.state[i - 1].clone();
-        }
-        self.state[start] = new_bit;
+    /// `len` keystream bits starting at bit `offset`, which must not be behind [`position`](Self::position)
+    pub fn keystream_at(
+        &mut self,
+        offset: usize,
+        len: usize,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<FheBool>, Cancelled> {
+        assert!(self.is_warm(), "Keystream starts after the warmup");
+        assert!(offset >= self.position(), "Keystream offset {} is behind position {}", offset, self.position());
+        self.skip(offset - self.position(), progress, cancel)?;
+        self.keystream(len, progress, cancel)
     }
 
-    /// One Trivium clock: produces 1 keystream bit (under FHE) and updates internal state.
-    ///
-    /// Taps per Trivium spec:
-    /// t1 = s66  XOR s93
-    /// t2 = s162 XOR s177
-    /// t3 = s243 XOR s288
-    /// z  = t1 XOR t2 XOR t3
-    ///
-    /// s1 = t1 XOR (s91 AND s92) XOR s171
-    /// s2 = t2 XOR (s175 AND s176) XOR s264
-    /// s3 = t3 XOR (s286 AND s287) XOR s69
-    ///
-    /// Then shift:
-    /// reg1 in <= s3, reg2 in <= s1, reg3 in <= s2
-    fn clock(&mut self) -> FheBool {
-        // Compute t1/t2/t3
-        let t1 = &self.state[65] ^ &self.state[92];
-        let t2 = &self.state[161] ^ &self.state[176];
-        let t3 = &self.state[242] ^ &self.state[287];
+    /// Generate `n` keystream bytes under FHE, each least significant bit
+    /// first as in [`Trivium::keystream_bytes`](crate::Trivium::keystream_bytes)
+    pub fn keystream_bytes(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<Vec<[FheBool; 8]>, Cancelled> {
+        Ok(into_bytes(self.keystream(n * 8, progress, cancel)?))
+    }
+
+    /// Encrypt plain bytes under the encrypted key: every byte comes out as
+    /// eight encrypted bits, least significant first
+    pub fn process_bytes(
+        &mut self,
+        data: &[u8],
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<[FheBool; 8]>, Cancelled> {
+        let keystream = self.keystream(data.len() * 8, progress, cancel)?;
+        let data_bits = data.iter().flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1 == 1));
+        let ciphertext = keystream
+            .into_iter()
+            .zip(data_bits)
+            // k XOR 1 = NOT k
+            .map(|(k_bit, d_bit)| if d_bit { !&k_bit } else { k_bit })
+            .collect();
+        Ok(into_bytes(ciphertext))
+    }
+}
 
-        // Output bit
-        let output = &t1 ^ &t2 ^ &t3;
+/// Keystream bits grouped into bytes, least significant bit first
+fn into_bytes<B>(bits: Vec<B>) -> Vec<[B; 8]> {
+    assert_eq!(bits.len() % 8, 0, "Keystream must be whole bytes");
+    let bytes = bits.len() / 8;
+    let mut bits = bits.into_iter();
+    (0..bytes).map(|_| std::array::from_fn(|_| bits.next().unwrap())).collect()
+}
 
-        // Feedback values
-        let s1 = &t1 ^ &(&self.state[90] & &self.state[91]) ^ &self.state[170];
-        let s2 = &t2 ^ &(&self.state[174] & &self.state[175]) ^ &self.state[263];
-        let s3 = &t3 ^ &(&self.state[285] & &self.state[286]) ^ &self.state[68];
+/// Kreyvium under FHE (128-bit key and IV): the Trivium circuit with the
+/// K*/IV* register bits added to t3 and to register 2's feedback.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KreyviumFhe(TriviumFhe);
 
-        // IMPORTANT: do NOT rotate the whole 288-bit state.
-        // Shift each register separately.
-        self.shift_register(0, 92, s3);
-        self.shift_register(93, 176, s1);
-        self.shift_register(177, 287, s2);
+impl KreyviumFhe {
+    /// Create a new FHE-Kreyvium instance with encrypted key/iv (see [`TriviumFhe::new`])
+    pub fn new(
+        encrypted_key: &[FheBool],  // 128 bits
+        encrypted_iv: &[FheBool],   // 128 bits
+        server_key: &Arc<EvaluationKey>,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Self, FingerprintError> {
+        TriviumFhe::start(Cipher::Kreyvium, encrypted_key, encrypted_iv, server_key, progress, cancel).map(Self)
+    }
 
-        output
+    pub fn is_warm(&self) -> bool {
+        self.0.is_warm()
     }
 
     /// Generate `n` keystream bits under FHE.
-    pub fn keystream(&mut self, n: usize) -> Vec<FheBool> {
-        println!("   🔑 Generating {} keystream bits...", n);
-        (0..n).map(|_| self.clock()).collect()
+    pub fn keystream(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<Vec<FheBool>, Cancelled> {
+        self.0.keystream(n, progress, cancel)
+    }
+
+    /// See [`TriviumFhe::skip`]
+    pub fn skip(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<(), Cancelled> {
+        self.0.skip(n, progress, cancel)
+    }
+
+    /// See [`TriviumFhe::keystream_at`]
+    pub fn keystream_at(
+        &mut self,
+        offset: usize,
+        len: usize,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<FheBool>, Cancelled> {
+        self.0.keystream_at(offset, len, progress, cancel)
+    }
+
+    /// See [`TriviumFhe::keystream_bytes`]
+    pub fn keystream_bytes(&mut self, n: usize, progress: &dyn ProgressSink, cancel: &CancellationToken) -> Result<Vec<[FheBool; 8]>, Cancelled> {
+        self.0.keystream_bytes(n, progress, cancel)
+    }
+}
+
+/// Initial 288-bit state of `cipher` (Kreyvium: key and IV fill all three
+/// registers; Trivium: see [`TriviumFhe`]). Generic so it can be checked on plain bools.
+fn load_state<B: Clone>(cipher: Cipher, key: &[B], iv: &[B], fhe_true: &B, fhe_false: &B) -> Vec<B> {
+    let mut state: Vec<B> = Vec::with_capacity(288);
+    match cipher {
+        Cipher::Trivium => {
+            // state[0..79] = key (80), state[80..92] = 13 zeros
+            state.extend(key.iter().cloned());
+            state.extend(std::iter::repeat_n(fhe_false.clone(), 13));
+            // state[93..172] = iv (80), state[173..284] = 4 + 108 zeros
+            state.extend(iv.iter().cloned());
+            state.extend(std::iter::repeat_n(fhe_false.clone(), 112));
+            // state[285..287] = 1,1,1
+            state.extend(std::iter::repeat_n(fhe_true.clone(), 3));
+        }
+        Cipher::Kreyvium => {
+            // state[0..92] = key[0..92], state[93..220] = iv (128)
+            state.extend(key[..93].iter().cloned());
+            state.extend(iv.iter().cloned());
+            // state[221..286] = 66 ones, state[287] = 0
+            state.extend(std::iter::repeat_n(fhe_true.clone(), 66));
+            state.push(fhe_false.clone());
+        }
+    }
+    assert_eq!(state.len(), 288, "State must be 288 bits!");
+    state
+}
+
+/// K*/IV* bits added at clock `cycle`, if running Kreyvium
+fn register_bits<'a, B>(registers: Option<(&'a [B], &'a [B])>, cycle: usize) -> Option<[&'a B; 2]> {
+    registers.map(|(key, iv)| [&key[register_index(cycle)], &iv[register_index(cycle)]])
+}
+
+/// Clock `j` of a batch starting at `state` (j < CLOCK_BATCH): the keystream
+/// bit and the feedback bits (s1, s2, s3).
+///
+/// Taps per Trivium spec:
+/// t1 = s66  XOR s93
+/// t2 = s162 XOR s177
+/// t3 = s243 XOR s288
+/// z  = t1 XOR t2 XOR t3
+///
+/// s1 = t1 XOR (s91 AND s92) XOR s171
+/// s2 = t2 XOR (s175 AND s176) XOR s264
+/// s3 = t3 XOR (s286 AND s287) XOR s69
+///
+/// Kreyvium adds its K* bit to t3 and its IV* bit to s1 (`registers`).
+/// Generic over the bit type so the batching can be checked on plain bools.
+fn step<B>(state: &[B], j: usize, registers: Option<[&B; 2]>) -> [B; 4]
+where
+    for<'a> &'a B: BitXor<&'a B, Output = B> + BitAnd<&'a B, Output = B>,
+{
+    // After j clocks, bit i of a register is bit i - j of the batch's state
+    // (every tap is at least 65 bits from its register's start)
+    let s = |i: usize| &state[i - j];
+    let t1 = s(65) ^ s(92);
+    let t2 = s(161) ^ s(176);
+    let mut t3 = s(242) ^ s(287);
+    if let Some([key, _]) = registers {
+        t3 = &t3 ^ key;
+    }
+
+    let output = &(&t1 ^ &t2) ^ &t3;
+
+    let mut s1 = &(&t1 ^ &(s(90) & s(91))) ^ s(170);
+    if let Some([_, iv]) = registers {
+        s1 = &s1 ^ iv;
+    }
+    let s2 = &(&t2 ^ &(s(174) & s(175))) ^ s(263);
+    let s3 = &(&t3 ^ &(s(285) & s(286))) ^ s(68);
+    [output, s1, s2, s3]
+}
+
+/// Apply the steps of one batch to the state: reg1 in <= s3, reg2 in <= s1,
+/// reg3 in <= s2. Each register shifts separately (never rotate the whole
+/// 288-bit state). Returns the keystream bits.
+fn advance<B>(state: &mut [B], steps: Vec<[B; 4]>) -> Vec<B> {
+    let k = steps.len();
+    let mut output = Vec::with_capacity(k);
+    let (mut in1, mut in2, mut in3) = (Vec::with_capacity(k), Vec::with_capacity(k), Vec::with_capacity(k));
+    for [z, s1, s2, s3] in steps {
+        output.push(z);
+        in1.push(s3);
+        in2.push(s1);
+        in3.push(s2);
     }
+    shift_in(&mut state[0..=92], in1);
+    shift_in(&mut state[93..=176], in2);
+    shift_in(&mut state[177..=287], in3);
+    output
+}
+
+/// Shift `register` right by `new_bits.len()` with the bits entering at its
+/// start in clock order, like that many single shifts (the last bit ends up first)
+fn shift_in<B>(register: &mut [B], new_bits: Vec<B>) {
+    let k = new_bits.len();
+    register.rotate_right(k);
+    for (j, bit) in new_bits.into_iter().enumerate() {
+        register[k - 1 - j] = bit;
+    }
+}
+
+/// Length of the next batch: at most `wanted` clocks, none past the next checkpoint
+fn batch_len(cycles: usize, wanted: usize, every: usize) -> usize {
+    let to_checkpoint = if every > 0 { every - cycles % every } else { usize::MAX };
+    wanted.min(CLOCK_BATCH).min(to_checkpoint)
+}
+
+/// Milliseconds since `started`, as a tracing field
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
 }
 
 /// Homomorphic Trivium decryption:
 /// plaintext = ciphertext XOR keystream
 ///
-/// `ciphertext` is in clear (Vec<bool>), but `keystream` is FHE.
-/// So we implement XOR using `encrypted_true` as the only constant input:
+/// A 128-bit key and IV decrypt with Kreyvium instead.
+///
+/// `ciphertext` is in clear (Vec<bool>), but `keystream` is FHE, so the
+/// XOR needs no constant:
 /// - if c=0 => p = k
 /// - if c=1 => p = k XOR 1 = NOT k
 pub fn decrypt_homomorphic(
     ciphertext: &[bool],
     encrypted_key: &[FheBool],
     encrypted_iv: &[FheBool],
-    encrypted_true: &FheBool,
-    server_key: &ServerKey,
-) -> Vec<FheBool> {
-    println!("\n🔓 Homomorphic Trivium Decryption:");
-
-    let mut trivium = TriviumFhe::new(encrypted_key, encrypted_iv, encrypted_true, server_key);
-    let keystream = trivium.keystream(ciphertext.len());
-
-    println!("   ⚙️  XORing ciphertext with keystream...");
-    let plaintext: Vec<FheBool> = ciphertext
-        .iter()
-        .zip(keystream.iter())
-        .map(|(&c_bit, k_bit)| {
+    server_key: &Arc<EvaluationKey>,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, FingerprintError> {
+    let bits = ciphertext.iter().map(|&b| Ok(b));
+    decrypt_homomorphic_stream(bits, encrypted_key, encrypted_iv, server_key, progress, cancel)
+}
+
+/// Partial [`decrypt_homomorphic`]: the cipher state and the plaintext bits
+/// produced so far. Resuming from it skips the ciphertext bits already used.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecryptState {
+    pub trivium: TriviumFhe,
+    pub plaintext: Vec<FheBool>,
+}
+
+/// Streaming variant of [`decrypt_homomorphic`].
+///
+/// Ciphertext bits are pulled one at a time as keystream bits are produced,
+/// so the caller can read them lazily from storage and no full keystream
+/// vector is materialized. The first read error (or cancellation) aborts
+/// the decryption.
+pub fn decrypt_homomorphic_stream<I, E>(
+    ciphertext: I,
+    encrypted_key: &[FheBool],
+    encrypted_iv: &[FheBool],
+    server_key: &Arc<EvaluationKey>,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, E>
+where
+    I: IntoIterator<Item = Result<bool, E>>,
+    E: From<Cancelled> + From<FingerprintError>,
+{
+    decrypt_homomorphic_resumable(ciphertext, encrypted_key, encrypted_iv, server_key, None, 0, |_| {}, progress, cancel)
+}
+
+/// Checkpointed variant of [`decrypt_homomorphic_stream`].
+///
+/// Starts from `resume` if given (key and IV are then ignored), otherwise
+/// from a fresh state. With `every > 0`, `checkpoint` is handed the state
+/// every `every` clocks (warmup included) and once more when decryption is
+/// done, so a caller can persist it and resume after a crash. Like
+/// [`TriviumFhe::new`], runs under the server key the caller installed, and
+/// a key/IV that fit no cipher are a [`FingerprintError::KeyMismatch`].
+#[allow(clippy::too_many_arguments)]
+pub fn decrypt_homomorphic_resumable<I, E>(
+    ciphertext: I,
+    encrypted_key: &[FheBool],
+    encrypted_iv: &[FheBool],
+    server_key: &Arc<EvaluationKey>,
+    resume: Option<DecryptState>,
+    every: usize,
+    mut checkpoint: impl FnMut(&DecryptState),
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Vec<FheBool>, E>
+where
+    I: IntoIterator<Item = Result<bool, E>>,
+    E: From<Cancelled> + From<FingerprintError>,
+{
+    progress.report(Progress::Decryption);
+
+    let mut state = match resume {
+        Some(state) => {
+            progress.report(Progress::Resumed { cycles: state.trivium.cycles });
+            state
+        }
+        None => {
+            progress.report(Progress::Init);
+            DecryptState {
+                trivium: TriviumFhe::load(encrypted_key, encrypted_iv)?,
+                plaintext: Vec::new(),
+            }
+        }
+    };
+    state.trivium.start_threads(server_key);
+
+    let start = state.trivium.cycles;
+    state.trivium.warm_up(
+        every,
+        &mut |trivium| checkpoint(&DecryptState { trivium: trivium.clone(), plaintext: Vec::new() }),
+        progress,
+        cancel,
+    )?;
+
+    let ciphertext = ciphertext.into_iter();
+    let total = match ciphertext.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(upper),
+        _ => None,
+    };
+    progress.report(Progress::Xor { done: 0, total });
+    let mut ciphertext = ciphertext.skip(state.plaintext.len());
+    loop {
+        cancel.check()?;
+        let k = batch_len(state.trivium.cycles, CLOCK_BATCH, every);
+        let c_bits = ciphertext.by_ref().take(k).collect::<Result<Vec<bool>, E>>()?;
+        if c_bits.is_empty() {
+            break;
+        }
+        let keystream = state.trivium.clock_batch(c_bits.len());
+        for (c_bit, k_bit) in c_bits.into_iter().zip(keystream) {
             if c_bit {
                 // k XOR 1 = NOT k
-                k_bit ^ encrypted_true
+                state.plaintext.push(!&k_bit);
             } else {
-                k_bit.clone()
+                state.plaintext.push(k_bit);
             }
-        })
-        .collect();
+            progress.report(Progress::Xor { done: state.plaintext.len(), total });
+        }
+        if every > 0 && state.trivium.cycles % every == 0 {
+            checkpoint(&state);
+        }
+    }
+    if every > 0 && state.trivium.cycles != start && state.trivium.cycles % every != 0 {
+        checkpoint(&state);
+    }
+
+    progress.report(Progress::Done);
+    Ok(state.plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_prints_stage_starts_and_warmup_steps() {
+        assert!(console_line(Progress::Warmup { done: 0, total: WARMUP_CYCLES }).is_some());
+        assert!(console_line(Progress::Warmup { done: 192, total: WARMUP_CYCLES }).is_some());
+        assert!(console_line(Progress::Warmup { done: 193, total: WARMUP_CYCLES }).is_none());
+        assert!(console_line(Progress::Warmup { done: WARMUP_CYCLES, total: WARMUP_CYCLES }).is_none());
+        assert!(console_line(Progress::Xor { done: 0, total: Some(1024) }).is_some());
+        assert!(console_line(Progress::Xor { done: 5, total: Some(1024) }).is_none());
+    }
+
+    #[test]
+    fn batched_clocks_match_single_clocks() {
+        // Plain bools from a small LCG stand in for a loaded state
+        let mut seed = 0x2545_f491u32;
+        let start: Vec<bool> = (0..288)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                seed & 0x1_0000 != 0
+            })
+            .collect();
+
+        let mut single = start.clone();
+        let mut single_bits = Vec::new();
+        for _ in 0..3 * CLOCK_BATCH {
+            let steps = vec![step(&single, 0, None)];
+            single_bits.extend(advance(&mut single, steps));
+        }
+
+        let mut batched = start;
+        let mut batched_bits = Vec::new();
+        for _ in 0..3 {
+            let steps = (0..CLOCK_BATCH).map(|j| step(&batched, j, None)).collect();
+            batched_bits.extend(advance(&mut batched, steps));
+        }
+        assert_eq!(batched_bits, single_bits);
+        assert_eq!(batched, single);
+    }
+
+    #[test]
+    fn batched_kreyvium_matches_the_plain_cipher() {
+        let key: Vec<bool> = (0..128).map(|i| i % 3 == 0).collect();
+        let iv: Vec<bool> = (0..128).map(|i| i % 5 == 1).collect();
+        let mut state = load_state(Cipher::Kreyvium, &key, &iv, &true, &false);
+
+        let mut keystream = Vec::new();
+        let mut cycles = 0;
+        while cycles < WARMUP_CYCLES + 200 {
+            let k = batch_len(cycles, WARMUP_CYCLES + 200 - cycles, 0);
+            let steps = (0..k).map(|j| step(&state, j, register_bits(Some((&key, &iv)), cycles + j))).collect();
+            keystream.extend(advance(&mut state, steps));
+            cycles += k;
+        }
+        assert_eq!(keystream[WARMUP_CYCLES..], Cipher::Kreyvium.process(&key, &iv, 0, &[false; 200]));
+    }
+
+    #[test]
+    fn keystream_bytes_group_bits_like_the_plain_cipher() {
+        let key: Vec<bool> = (0..80).map(|i| i % 3 == 0).collect();
+        let iv: Vec<bool> = (0..80).map(|i| i % 7 == 2).collect();
+        let bits = crate::Trivium::new(&key, &iv).process(&[false; 80]);
+        let bytes: Vec<u8> = into_bytes(bits)
+            .into_iter()
+            .map(|byte| byte.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u8) << i)))
+            .collect();
+        assert_eq!(bytes, crate::Trivium::new(&key, &iv).keystream_bytes(10));
+    }
+
+    #[test]
+    fn key_and_iv_lengths_are_checked_before_loading() {
+        // Rejected before any constant is encrypted, so no server key is needed
+        assert!(matches!(TriviumFhe::load(&[], &[]), Err(FingerprintError::KeyMismatch(_))));
+        assert!(matches!(TriviumFhe::for_benchmark(&[], &[]), Err(FingerprintError::KeyMismatch(_))));
+    }
 
-    println!("   ✅ Decryption complete!");
-    plaintext
+    #[test]
+    fn batches_stop_at_checkpoints() {
+        assert_eq!(batch_len(0, WARMUP_CYCLES, 0), CLOCK_BATCH);
+        assert_eq!(batch_len(250, WARMUP_CYCLES, 256), 6);
+        assert_eq!(batch_len(256, 10, 256), 10);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn clocks_only_borrow_the_callers_pool() {
+        assert_eq!(clock_thread_count(0, None), None);
+        assert_eq!(clock_thread_count(4, None), None);
+        assert_eq!(clock_thread_count(1, Some(8)), None);
+        assert_eq!(clock_thread_count(0, Some(8)), Some(8));
+        assert_eq!(clock_thread_count(4, Some(8)), Some(4));
+        assert_eq!(clock_thread_count(16, Some(8)), Some(8));
+        assert_eq!(clock_thread_count(0, Some(1)), None);
+    }
+
+    #[test]
+    fn closures_are_progress_sinks() {
+        let seen = std::cell::RefCell::new(Vec::new());
+        let sink = |progress: Progress| seen.borrow_mut().push(progress);
+        let sink: &dyn ProgressSink = &sink;
+        sink.report(Progress::Init);
+        sink.report(Progress::Keystream { done: 1, total: 2 });
+        assert_eq!(seen.into_inner(), vec![Progress::Init, Progress::Keystream { done: 1, total: 2 }]);
+    }
 }